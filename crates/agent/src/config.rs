@@ -1,15 +1,93 @@
 /// 配置管理
 
+use common::utils::{BridgeNaming, ConfigValidator};
+use common::ws_rpc::{RpcCodec, DEFAULT_COMPRESS_THRESHOLD};
 use serde::Deserialize;
+use std::path::Path;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub node_id: String,
     pub node_name: String,
     pub server_ws_url: String,
+    pub node_ip: String,
     pub heartbeat_interval: u64,
     pub log_level: String,
     pub network_provider_interface: String,
+    pub bridge_name_prefix: String,
+    pub dead_letter_path: String,
+    pub ip_conflict_check: IpConflictCheck,
+    /// 启动时等待 libvirt 就绪的最长时间（秒）
+    pub libvirt_connect_timeout: u64,
+    /// 同时进行的 URL 下载建卷数量上限，超出的排队等待
+    pub max_concurrent_downloads: usize,
+    /// 以 file:// 地址建卷时允许引用的本地目录
+    pub local_source_dirs: Vec<String>,
+    /// 加密存储卷按密钥 ID 引用的密钥文件所在目录
+    pub encryption_key_dir: String,
+    /// qemu-img、lvm 等存储命令单次执行的超时（秒）
+    pub storage_command_timeout: u64,
+    /// qemu-img convert 单次执行的超时（秒），耗时与镜像大小成正比
+    pub storage_convert_timeout: u64,
+    /// 存储命令瞬时失败（文件锁冲突、NFS 句柄失效等）后的重试次数
+    pub storage_command_retries: u32,
+    /// Server 未下发 VLAN ID 时是否从 Bridge 名称推断并自动创建网络
+    pub vlan_inference: bool,
+    /// 推送虚拟机运行时指标的间隔（秒），0 表示不推送
+    pub vm_metrics_interval: u64,
+    /// 上报主机级指标的间隔（秒），0 表示不上报
+    pub node_metrics_interval: u64,
+    /// 注册时向 Server 声明的消息编码
+    pub rpc_codec: RpcCodec,
+    /// 发往 Server 的消息超过该字节数时 gzip 压缩，0 表示不压缩
+    pub rpc_compress_threshold: usize,
+    /// 断线重连指数退避的基础间隔（秒）
+    pub reconnect_base_secs: u64,
+    /// 断线重连指数退避的最大间隔（秒）
+    pub reconnect_max_secs: u64,
+    /// 连接 wss:// 地址时的 TLS 配置
+    pub tls: TlsConfig,
+    /// 注册时携带的共享密钥，需与 Server 配置一致
+    pub auth_token: Option<String>,
+}
+
+/// 连接 Server 的 TLS 配置，仅在 SERVER_WS_URL 为 wss:// 时生效
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TlsConfig {
+    /// 校验 Server 证书的 CA 证书（PEM）路径
+    pub ca_cert: Option<String>,
+    /// 双向 TLS 的客户端证书（PEM）路径
+    pub client_cert: Option<String>,
+    /// 双向 TLS 的客户端私钥（PEM）路径
+    pub client_key: Option<String>,
+    /// 跳过 Server 证书校验，仅用于开发环境
+    pub insecure_skip_verify: bool,
+}
+
+/// 虚拟机启动前的 IP 冲突检测策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpConflictCheck {
+    /// 不检测
+    #[default]
+    Off,
+    /// 检测到冲突时仅告警，继续启动
+    Warn,
+    /// 检测到冲突时拒绝启动
+    Fail,
+}
+
+impl std::str::FromStr for IpConflictCheck {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(IpConflictCheck::Off),
+            "warn" => Ok(IpConflictCheck::Warn),
+            "fail" => Ok(IpConflictCheck::Fail),
+            other => Err(anyhow::anyhow!("IP_CONFLICT_CHECK 取值无效: {}（可选 off/warn/fail）", other)),
+        }
+    }
 }
 
 impl Config {
@@ -27,9 +105,14 @@ impl Config {
         let server_ws_url = std::env::var("SERVER_WS_URL")
             .unwrap_or_else(|_| "ws://localhost:3000/ws/agent".to_string());
 
+        // 获取本机 IP 地址（简化处理，实际应该更智能）
+        let node_ip = std::env::var("NODE_IP")
+            .unwrap_or_else(|_| "127.0.0.1".to_string());
+
         let heartbeat_interval = std::env::var("HEARTBEAT_INTERVAL")
             .unwrap_or_else(|_| "30".to_string())
-            .parse()?;
+            .parse()
+            .map_err(|e| anyhow::anyhow!("HEARTBEAT_INTERVAL 应为秒数: {}", e))?;
 
         let log_level = std::env::var("LOG_LEVEL")
             .unwrap_or_else(|_| "debug".to_string());
@@ -37,14 +120,198 @@ impl Config {
         let network_provider_interface = std::env::var("NETWORK_PROVIDER_INTERFACE")
             .unwrap_or_else(|_| "eth0".to_string());
 
+        let bridge_name_prefix = std::env::var("BRIDGE_NAME_PREFIX")
+            .unwrap_or_else(|_| BridgeNaming::DEFAULT_PREFIX.to_string());
+
+        let dead_letter_path = std::env::var("DEAD_LETTER_PATH")
+            .unwrap_or_else(|_| "/var/lib/easy-vm-cloud/agent/dead_letters.jsonl".to_string());
+
+        let ip_conflict_check = std::env::var("IP_CONFLICT_CHECK")
+            .unwrap_or_else(|_| "off".to_string())
+            .parse()?;
+
+        let libvirt_connect_timeout = std::env::var("LIBVIRT_CONNECT_TIMEOUT")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .map_err(|e| anyhow::anyhow!("LIBVIRT_CONNECT_TIMEOUT 应为秒数: {}", e))?;
+
+        let max_concurrent_downloads = std::env::var("MAX_CONCURRENT_DOWNLOADS")
+            .unwrap_or_else(|_| "2".to_string())
+            .parse()
+            .map_err(|e| anyhow::anyhow!("MAX_CONCURRENT_DOWNLOADS 应为正整数: {}", e))?;
+
+        let local_source_dirs = std::env::var("LOCAL_SOURCE_DIRS")
+            .unwrap_or_else(|_| "/var/lib/easy-vm-cloud/images".to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|dir| !dir.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        let encryption_key_dir = std::env::var("ENCRYPTION_KEY_DIR")
+            .unwrap_or_else(|_| "/etc/easy-vm-cloud/keys".to_string());
+
+        let storage_command_timeout = std::env::var("STORAGE_COMMAND_TIMEOUT")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse()
+            .map_err(|e| anyhow::anyhow!("STORAGE_COMMAND_TIMEOUT 应为秒数: {}", e))?;
+
+        let storage_convert_timeout = std::env::var("STORAGE_CONVERT_TIMEOUT")
+            .unwrap_or_else(|_| "21600".to_string())
+            .parse()
+            .map_err(|e| anyhow::anyhow!("STORAGE_CONVERT_TIMEOUT 应为秒数: {}", e))?;
+
+        let storage_command_retries = std::env::var("STORAGE_COMMAND_RETRIES")
+            .unwrap_or_else(|_| "2".to_string())
+            .parse()
+            .map_err(|e| anyhow::anyhow!("STORAGE_COMMAND_RETRIES 应为非负整数: {}", e))?;
+
+        let vlan_inference = std::env::var("VLAN_INFERENCE")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse()
+            .map_err(|e| anyhow::anyhow!("VLAN_INFERENCE 应为 true 或 false: {}", e))?;
+
+        let vm_metrics_interval = std::env::var("VM_METRICS_INTERVAL")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .map_err(|e| anyhow::anyhow!("VM_METRICS_INTERVAL 应为秒数: {}", e))?;
+
+        let node_metrics_interval = std::env::var("NODE_METRICS_INTERVAL")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .map_err(|e| anyhow::anyhow!("NODE_METRICS_INTERVAL 应为秒数: {}", e))?;
+
+        let rpc_codec = std::env::var("RPC_CODEC")
+            .unwrap_or_else(|_| "msgpack".to_string())
+            .parse()
+            .map_err(|e| anyhow::anyhow!("RPC_CODEC 取值无效: {}（可选 json/msgpack）", e))?;
+
+        let rpc_compress_threshold = match std::env::var("RPC_COMPRESS_THRESHOLD") {
+            Ok(value) => value
+                .parse()
+                .map_err(|e| anyhow::anyhow!("RPC_COMPRESS_THRESHOLD 应为字节数: {}", e))?,
+            Err(_) => DEFAULT_COMPRESS_THRESHOLD,
+        };
+
+        let reconnect_base_secs = std::env::var("RECONNECT_BASE_SECS")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse()
+            .map_err(|e| anyhow::anyhow!("RECONNECT_BASE_SECS 应为秒数: {}", e))?;
+
+        let reconnect_max_secs = std::env::var("RECONNECT_MAX_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .map_err(|e| anyhow::anyhow!("RECONNECT_MAX_SECS 应为秒数: {}", e))?;
+
+        let tls = TlsConfig {
+            ca_cert: std::env::var("TLS_CA_CERT").ok(),
+            client_cert: std::env::var("TLS_CLIENT_CERT").ok(),
+            client_key: std::env::var("TLS_CLIENT_KEY").ok(),
+            insecure_skip_verify: std::env::var("TLS_INSECURE_SKIP_VERIFY")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|e| anyhow::anyhow!("TLS_INSECURE_SKIP_VERIFY 应为 true 或 false: {}", e))?,
+        };
+
+        let auth_token = std::env::var("AGENT_AUTH_TOKEN").ok();
+
         Ok(Self {
             node_id,
             node_name,
             server_ws_url,
+            node_ip,
             heartbeat_interval,
             log_level,
             network_provider_interface,
+            bridge_name_prefix,
+            dead_letter_path,
+            ip_conflict_check,
+            libvirt_connect_timeout,
+            max_concurrent_downloads,
+            local_source_dirs,
+            encryption_key_dir,
+            storage_command_timeout,
+            storage_convert_timeout,
+            storage_command_retries,
+            vlan_inference,
+            vm_metrics_interval,
+            node_metrics_interval,
+            rpc_codec,
+            rpc_compress_threshold,
+            reconnect_base_secs,
+            reconnect_max_secs,
+            tls,
+            auth_token,
         })
     }
-}
 
+    /// 校验配置，汇总列出所有问题
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut v = ConfigValidator::new();
+
+        v.check(!self.node_id.trim().is_empty(), "NODE_ID", "不能为空");
+        v.check_url("SERVER_WS_URL", &self.server_ws_url, &["ws", "wss"]);
+        v.check_ip("NODE_IP", &self.node_ip);
+        v.check(self.heartbeat_interval > 0, "HEARTBEAT_INTERVAL", "必须大于 0");
+        v.check(self.reconnect_base_secs > 0, "RECONNECT_BASE_SECS", "必须大于 0");
+        v.check(
+            self.reconnect_max_secs >= self.reconnect_base_secs,
+            "RECONNECT_MAX_SECS",
+            "不能小于 RECONNECT_BASE_SECS",
+        );
+        v.check(self.max_concurrent_downloads > 0, "MAX_CONCURRENT_DOWNLOADS", "必须大于 0");
+        v.check(self.storage_command_timeout > 0, "STORAGE_COMMAND_TIMEOUT", "必须大于 0");
+        v.check(self.storage_convert_timeout > 0, "STORAGE_CONVERT_TIMEOUT", "必须大于 0");
+        for dir in &self.local_source_dirs {
+            v.check(Path::new(dir).is_absolute(), "LOCAL_SOURCE_DIRS", format!("必须为绝对路径: {}", dir));
+        }
+        v.check(
+            Path::new(&self.encryption_key_dir).is_absolute(),
+            "ENCRYPTION_KEY_DIR",
+            "必须为绝对路径",
+        );
+        v.check(
+            !self.network_provider_interface.is_empty(),
+            "NETWORK_PROVIDER_INTERFACE",
+            "不能为空",
+        );
+        if let Err(e) = BridgeNaming::new(self.bridge_name_prefix.clone()) {
+            v.error("BRIDGE_NAME_PREFIX", e);
+        }
+        v.check_parent_dir("DEAD_LETTER_PATH", &self.dead_letter_path);
+        v.check(
+            self.auth_token.as_ref().map_or(true, |token| !token.is_empty()),
+            "AGENT_AUTH_TOKEN",
+            "不能为空",
+        );
+
+        if self.uses_tls() {
+            v.check(
+                self.tls.ca_cert.is_some() || self.tls.insecure_skip_verify,
+                "TLS_CA_CERT",
+                "SERVER_WS_URL 为 wss:// 时必须配置 CA 证书，或设置 TLS_INSECURE_SKIP_VERIFY=true",
+            );
+        }
+        v.check(
+            self.tls.client_cert.is_some() == self.tls.client_key.is_some(),
+            "TLS_CLIENT_CERT",
+            "TLS_CLIENT_CERT 与 TLS_CLIENT_KEY 必须同时配置",
+        );
+        for (key, path) in [
+            ("TLS_CA_CERT", &self.tls.ca_cert),
+            ("TLS_CLIENT_CERT", &self.tls.client_cert),
+            ("TLS_CLIENT_KEY", &self.tls.client_key),
+        ] {
+            if let Some(path) = path {
+                v.check(Path::new(path).is_file(), key, format!("文件不存在: {}", path));
+            }
+        }
+
+        v.finish()
+    }
+
+    /// Server 地址是否为 wss://
+    pub fn uses_tls(&self) -> bool {
+        self.server_ws_url.starts_with("wss://")
+    }
+}
@@ -0,0 +1,131 @@
+/// cloud-init 配置光盘
+///
+/// 按 NoCloud 数据源约定生成卷标为 `cidata` 的 ISO，包含 user-data、meta-data
+/// 和可选的 network-config，作为光驱挂载到虚拟机
+use common::ws_rpc::types::CloudInitConfig;
+use common::{Error, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+/// 配置光盘所在目录
+const CONFIG_DRIVE_DIR: &str = "/var/lib/libvirt/cloud-init";
+
+/// NoCloud 数据源要求的卷标
+const VOLUME_LABEL: &str = "cidata";
+
+/// 虚拟机配置光盘的路径
+pub fn config_drive_path(vm_id: &str) -> PathBuf {
+    Path::new(CONFIG_DRIVE_DIR).join(format!("{}-cidata.iso", vm_id))
+}
+
+/// 未提供 meta-data 时按虚拟机 ID 和名称生成
+///
+/// instance-id 变化时 cloud-init 会重新执行首次启动流程，因此固定使用虚拟机 ID
+fn default_meta_data(vm_id: &str, name: &str) -> String {
+    format!("instance-id: {}\nlocal-hostname: {}\n", vm_id, name)
+}
+
+/// 生成（覆盖）虚拟机的配置光盘，每次定义虚拟机前调用，使 user-data 的修改生效
+pub async fn build_config_drive(vm_id: &str, name: &str, config: &CloudInitConfig) -> Result<PathBuf> {
+    let iso_path = config_drive_path(vm_id);
+    let work_dir = Path::new(CONFIG_DRIVE_DIR).join(format!(".{}-{}", vm_id, uuid::Uuid::new_v4()));
+
+    fs::create_dir_all(&work_dir)
+        .await
+        .map_err(|e| Error::Hypervisor(format!("创建 cloud-init 临时目录失败: {}", e)))?;
+
+    let result = write_and_pack(vm_id, name, config, &work_dir, &iso_path).await;
+    if let Err(e) = fs::remove_dir_all(&work_dir).await {
+        warn!("清理 cloud-init 临时目录 {:?} 失败: {}", work_dir, e);
+    }
+    result?;
+
+    info!("已生成虚拟机 {} 的 cloud-init 配置光盘: {:?}", vm_id, iso_path);
+    Ok(iso_path)
+}
+
+async fn write_and_pack(
+    vm_id: &str,
+    name: &str,
+    config: &CloudInitConfig,
+    work_dir: &Path,
+    iso_path: &Path,
+) -> Result<()> {
+    let meta_data = if config.meta_data.trim().is_empty() {
+        default_meta_data(vm_id, name)
+    } else {
+        config.meta_data.clone()
+    };
+
+    let mut files = vec![
+        ("user-data", config.user_data.as_str()),
+        ("meta-data", meta_data.as_str()),
+    ];
+    if let Some(network_config) = config.network_config.as_deref() {
+        files.push(("network-config", network_config));
+    }
+
+    for (file_name, content) in &files {
+        fs::write(work_dir.join(file_name), content)
+            .await
+            .map_err(|e| Error::Hypervisor(format!("写入 cloud-init {} 失败: {}", file_name, e)))?;
+    }
+
+    // 先写入临时文件再改名，避免运行中的虚拟机读到写了一半的光盘
+    let part_path = iso_path.with_extension("iso.part");
+    let output = Command::new("genisoimage")
+        .arg("-output")
+        .arg(&part_path)
+        .arg("-volid")
+        .arg(VOLUME_LABEL)
+        .arg("-joliet")
+        .arg("-rock")
+        .args(files.iter().map(|(file_name, _)| work_dir.join(file_name)))
+        .output()
+        .await
+        .map_err(|e| Error::Hypervisor(format!("执行 genisoimage 失败: {}", e)))?;
+
+    if !output.status.success() {
+        let _ = fs::remove_file(&part_path).await;
+        return Err(Error::Hypervisor(format!(
+            "生成 cloud-init 配置光盘失败: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    fs::rename(&part_path, iso_path)
+        .await
+        .map_err(|e| Error::Hypervisor(format!("保存 cloud-init 配置光盘失败: {}", e)))?;
+    Ok(())
+}
+
+/// 删除虚拟机的配置光盘，不存在时忽略
+pub async fn remove_config_drive(vm_id: &str) {
+    let iso_path = config_drive_path(vm_id);
+    match fs::remove_file(&iso_path).await {
+        Ok(()) => info!("已删除虚拟机 {} 的 cloud-init 配置光盘", vm_id),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            debug!("虚拟机 {} 没有 cloud-init 配置光盘", vm_id)
+        }
+        Err(e) => warn!("删除虚拟机 {} 的 cloud-init 配置光盘失败: {}", vm_id, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_meta_data() {
+        assert_eq!(
+            default_meta_data("vm-1", "web-1"),
+            "instance-id: vm-1\nlocal-hostname: web-1\n"
+        );
+        assert_eq!(
+            config_drive_path("vm-1"),
+            PathBuf::from("/var/lib/libvirt/cloud-init/vm-1-cidata.iso")
+        );
+    }
+}
@@ -0,0 +1,755 @@
+/// 虚拟化后端抽象
+///
+/// RPC 处理器通过该 trait 操作虚拟机，生产环境由基于 libvirt 的 HypervisorManager 实现，
+/// 测试中可替换为内存中的 MockHypervisor
+use async_trait::async_trait;
+use common::ws_rpc::types::{
+    BackingStore, DiskBusType, DiskDeviceType, DiskIoLimits, GuestNetworkInterface, MigrationMode, NicBandwidth,
+    VmStats, VncInfo,
+};
+use chrono::{DateTime, Utc};
+use common::Result;
+
+use crate::storage::encryption::Passphrase;
+
+use super::manager::{
+    GuestExecStatus, HypervisorManager, MigrationOptions, VMConfig, VMInfo, VmLiveState,
+};
+
+#[async_trait]
+pub trait Hypervisor: Send + Sync {
+    /// 后端是否已连接
+    fn is_connected(&self) -> bool;
+
+    /// 未连接时尝试重连，返回当前是否已连接
+    async fn ensure_connected(&self) -> bool;
+
+    /// 检查虚拟机是否存在
+    async fn vm_exists(&self, vm_id: &str) -> Result<bool>;
+
+    /// 读取虚拟机实时状态，虚拟机未定义时返回 None
+    async fn get_vm_state(&self, vm_id: &str) -> Result<Option<VmLiveState>>;
+
+    /// 读取运行中虚拟机实际分配的 VNC 端口，未配置 VNC 时返回 None
+    async fn get_vnc_info(&self, vm_id: &str) -> Result<Option<VncInfo>>;
+
+    /// 读取虚拟机的 CPU、内存、磁盘和网络累计统计
+    async fn get_vm_stats(&self, vm_id: &str) -> Result<VmStats>;
+
+    /// 启动已定义的虚拟机
+    async fn start_vm(&self, vm_id: &str) -> Result<()>;
+
+    /// 按配置定义（或更新）并启动虚拟机
+    async fn start_vm_with_config(&self, vm_id: &str, config: &VMConfig) -> Result<()>;
+
+    /// 停止虚拟机，`force` 为 true 时强制断电
+    async fn stop_vm(&self, vm_id: &str, force: bool) -> Result<()>;
+
+    /// 暂停运行中的虚拟机
+    async fn pause_vm(&self, vm_id: &str) -> Result<()>;
+
+    /// 恢复已暂停的虚拟机
+    async fn resume_vm(&self, vm_id: &str) -> Result<()>;
+
+    /// 调整 vCPU 数量，`live` 为 true 时同时作用于运行中的虚拟机
+    async fn set_vcpus(&self, vm_id: &str, count: u32, live: bool) -> Result<()>;
+
+    /// 调整内存（MiB），`live` 为 true 时同时作用于运行中的虚拟机
+    async fn set_memory(&self, vm_id: &str, memory_mb: u64, live: bool) -> Result<()>;
+
+    /// 取消定义虚拟机
+    async fn undefine_vm(&self, vm_id: &str) -> Result<()>;
+
+    /// 热挂载存储卷，返回分配的设备名
+    async fn attach_volume(
+        &self,
+        vm_id: &str,
+        volume_id: &str,
+        volume_path: &str,
+        bus_type: DiskBusType,
+        device_type: DiskDeviceType,
+        format: &str,
+        limits: DiskIoLimits,
+        backing: Option<&BackingStore>,
+        encrypted: bool,
+    ) -> Result<String>;
+
+    /// 为加密存储卷定义 libvirt secret（UUID 与存储卷 ID 相同），启动或挂载前调用
+    async fn define_volume_secret(&self, volume_id: &str, volume_path: &str, passphrase: &Passphrase) -> Result<()>;
+
+    /// 热分离存储卷
+    async fn detach_volume(&self, vm_id: &str, volume_id: &str) -> Result<()>;
+
+    /// 调整运行中虚拟机上存储卷的 I/O 限速
+    async fn set_volume_iotune(&self, vm_id: &str, volume_id: &str, limits: &DiskIoLimits) -> Result<()>;
+
+    /// 调整运行中虚拟机上网卡（按 MAC 地址定位）的带宽限速
+    async fn set_interface_bandwidth(&self, vm_id: &str, mac_address: &str, limits: &NicBandwidth) -> Result<()>;
+
+    /// 查找运行中虚拟机网卡（按 MAC 地址定位）在宿主机上的 tap 设备名
+    async fn interface_target(&self, vm_id: &str, mac_address: &str) -> Result<Option<String>>;
+
+    /// 热迁移虚拟机到目标节点，返回实际采用的迁移方式
+    async fn live_migrate(
+        &self,
+        vm_id: &str,
+        target_uri: &str,
+        options: &MigrationOptions,
+    ) -> Result<MigrationMode>;
+
+    /// 中止正在进行的迁移作业
+    async fn abort_migration(&self, vm_id: &str) -> Result<()>;
+
+    /// 查询迁移作业进度，返回已传输的百分比和预计剩余时间（秒）
+    async fn get_migration_progress(&self, vm_id: &str) -> Result<(f64, u64)>;
+
+    /// 设置迁移带宽上限（MiB/s），返回按下限修正后实际生效的值
+    async fn set_migration_speed(&self, vm_id: &str, mbps: u64) -> Result<u64>;
+
+    /// 查询当前的迁移带宽上限（MiB/s），0 表示不限速
+    async fn get_migration_speed(&self, vm_id: &str) -> Result<u64>;
+
+    /// 通过 guest agent 执行命令，返回客户机内进程 PID
+    async fn guest_exec(&self, vm_id: &str, command: &str, args: &[String]) -> Result<i64>;
+
+    /// 查询 guest agent 命令的执行状态
+    async fn guest_exec_status(&self, vm_id: &str, pid: i64) -> Result<GuestExecStatus>;
+
+    /// 通过 guest agent 查询客户机内的网卡与 IP 地址
+    async fn qga_get_interfaces(&self, vm_id: &str) -> Result<Vec<GuestNetworkInterface>>;
+
+    /// 读取虚拟机当前的域 XML
+    async fn get_domain_xml(&self, vm_id: &str) -> Result<String>;
+
+    /// 修改运行中虚拟机的控制台密码，`valid_to` 之后不再接受新连接
+    async fn set_console_password(
+        &self,
+        vm_id: &str,
+        password: &str,
+        valid_to: Option<DateTime<Utc>>,
+    ) -> Result<()>;
+
+    /// 通过 guest agent 冻结客户机文件系统
+    async fn fsfreeze(&self, vm_id: &str) -> Result<()>;
+
+    /// 通过 guest agent 解冻客户机文件系统
+    async fn fsthaw(&self, vm_id: &str) -> Result<()>;
+
+    /// 列出运行中的虚拟机 (vm_id, name)
+    async fn list_active_vms(&self) -> Result<Vec<(String, String)>>;
+
+    /// 列出所有已定义的虚拟机及其实时状态
+    async fn list_vms(&self) -> Result<Vec<VMInfo>>;
+
+    /// 保存虚拟机内存状态并停止（managed save）
+    async fn managed_save_vm(&self, vm_id: &str) -> Result<()>;
+
+    /// 列出所有虚拟机的恢复状态 (vm_id, name, has_managed_save)
+    async fn list_restore_states(&self) -> Result<Vec<(String, String, bool)>>;
+}
+
+#[async_trait]
+impl Hypervisor for HypervisorManager {
+    fn is_connected(&self) -> bool {
+        HypervisorManager::is_connected(self)
+    }
+
+    async fn ensure_connected(&self) -> bool {
+        HypervisorManager::ensure_connected(self).await
+    }
+
+    async fn vm_exists(&self, vm_id: &str) -> Result<bool> {
+        HypervisorManager::vm_exists(self, vm_id).await
+    }
+
+    async fn get_vm_state(&self, vm_id: &str) -> Result<Option<VmLiveState>> {
+        HypervisorManager::get_vm_state(self, vm_id).await
+    }
+
+    async fn get_vnc_info(&self, vm_id: &str) -> Result<Option<VncInfo>> {
+        HypervisorManager::get_vnc_info(self, vm_id).await
+    }
+
+    async fn get_vm_stats(&self, vm_id: &str) -> Result<VmStats> {
+        HypervisorManager::get_vm_stats(self, vm_id).await
+    }
+
+    async fn start_vm(&self, vm_id: &str) -> Result<()> {
+        HypervisorManager::start_vm(self, vm_id).await
+    }
+
+    async fn start_vm_with_config(&self, vm_id: &str, config: &VMConfig) -> Result<()> {
+        HypervisorManager::start_vm_with_config(self, vm_id, config).await
+    }
+
+    async fn stop_vm(&self, vm_id: &str, force: bool) -> Result<()> {
+        HypervisorManager::stop_vm(self, vm_id, force).await
+    }
+
+    async fn pause_vm(&self, vm_id: &str) -> Result<()> {
+        HypervisorManager::pause_vm(self, vm_id).await
+    }
+
+    async fn resume_vm(&self, vm_id: &str) -> Result<()> {
+        HypervisorManager::resume_vm(self, vm_id).await
+    }
+
+    async fn set_vcpus(&self, vm_id: &str, count: u32, live: bool) -> Result<()> {
+        HypervisorManager::set_vcpus(self, vm_id, count, live).await
+    }
+
+    async fn set_memory(&self, vm_id: &str, memory_mb: u64, live: bool) -> Result<()> {
+        HypervisorManager::set_memory(self, vm_id, memory_mb, live).await
+    }
+
+    async fn undefine_vm(&self, vm_id: &str) -> Result<()> {
+        HypervisorManager::undefine_vm(self, vm_id).await
+    }
+
+    async fn attach_volume(
+        &self,
+        vm_id: &str,
+        volume_id: &str,
+        volume_path: &str,
+        bus_type: DiskBusType,
+        device_type: DiskDeviceType,
+        format: &str,
+        limits: DiskIoLimits,
+        backing: Option<&BackingStore>,
+        encrypted: bool,
+    ) -> Result<String> {
+        HypervisorManager::attach_volume(
+            self,
+            vm_id,
+            volume_id,
+            volume_path,
+            bus_type,
+            device_type,
+            format,
+            limits,
+            backing,
+            encrypted,
+        )
+        .await
+    }
+
+    async fn define_volume_secret(&self, volume_id: &str, volume_path: &str, passphrase: &Passphrase) -> Result<()> {
+        HypervisorManager::define_volume_secret(self, volume_id, volume_path, passphrase).await
+    }
+
+    async fn detach_volume(&self, vm_id: &str, volume_id: &str) -> Result<()> {
+        HypervisorManager::detach_volume(self, vm_id, volume_id).await
+    }
+
+    async fn set_volume_iotune(&self, vm_id: &str, volume_id: &str, limits: &DiskIoLimits) -> Result<()> {
+        HypervisorManager::set_volume_iotune(self, vm_id, volume_id, limits).await
+    }
+
+    async fn set_interface_bandwidth(&self, vm_id: &str, mac_address: &str, limits: &NicBandwidth) -> Result<()> {
+        HypervisorManager::set_interface_bandwidth(self, vm_id, mac_address, limits).await
+    }
+
+    async fn interface_target(&self, vm_id: &str, mac_address: &str) -> Result<Option<String>> {
+        HypervisorManager::interface_target(self, vm_id, mac_address).await
+    }
+
+    async fn live_migrate(
+        &self,
+        vm_id: &str,
+        target_uri: &str,
+        options: &MigrationOptions,
+    ) -> Result<MigrationMode> {
+        HypervisorManager::live_migrate(self, vm_id, target_uri, options).await
+    }
+
+    async fn abort_migration(&self, vm_id: &str) -> Result<()> {
+        HypervisorManager::abort_migration(self, vm_id).await
+    }
+
+    async fn get_migration_progress(&self, vm_id: &str) -> Result<(f64, u64)> {
+        HypervisorManager::get_migration_progress(self, vm_id).await
+    }
+
+    async fn set_migration_speed(&self, vm_id: &str, mbps: u64) -> Result<u64> {
+        HypervisorManager::set_migration_speed(self, vm_id, mbps).await
+    }
+
+    async fn get_migration_speed(&self, vm_id: &str) -> Result<u64> {
+        HypervisorManager::get_migration_speed(self, vm_id).await
+    }
+
+    async fn guest_exec(&self, vm_id: &str, command: &str, args: &[String]) -> Result<i64> {
+        HypervisorManager::guest_exec(self, vm_id, command, args).await
+    }
+
+    async fn guest_exec_status(&self, vm_id: &str, pid: i64) -> Result<GuestExecStatus> {
+        HypervisorManager::guest_exec_status(self, vm_id, pid).await
+    }
+
+    async fn qga_get_interfaces(&self, vm_id: &str) -> Result<Vec<GuestNetworkInterface>> {
+        HypervisorManager::qga_get_interfaces(self, vm_id).await
+    }
+
+    async fn get_domain_xml(&self, vm_id: &str) -> Result<String> {
+        HypervisorManager::get_domain_xml(self, vm_id).await
+    }
+
+    async fn set_console_password(
+        &self,
+        vm_id: &str,
+        password: &str,
+        valid_to: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        HypervisorManager::set_console_password(self, vm_id, password, valid_to).await
+    }
+
+    async fn fsfreeze(&self, vm_id: &str) -> Result<()> {
+        HypervisorManager::fsfreeze(self, vm_id).await
+    }
+
+    async fn fsthaw(&self, vm_id: &str) -> Result<()> {
+        HypervisorManager::fsthaw(self, vm_id).await
+    }
+
+    async fn list_active_vms(&self) -> Result<Vec<(String, String)>> {
+        HypervisorManager::list_active_vms(self).await
+    }
+
+    async fn list_vms(&self) -> Result<Vec<VMInfo>> {
+        HypervisorManager::list_vms(self).await
+    }
+
+    async fn managed_save_vm(&self, vm_id: &str) -> Result<()> {
+        HypervisorManager::managed_save_vm(self, vm_id).await
+    }
+
+    async fn list_restore_states(&self) -> Result<Vec<(String, String, bool)>> {
+        HypervisorManager::list_restore_states(self).await
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use crate::hypervisor::manager::MIN_MIGRATION_SPEED_MBPS;
+    use std::collections::{BTreeMap, HashSet};
+    use std::sync::Mutex;
+
+    /// 内存中的虚拟机记录
+    #[derive(Debug, Clone)]
+    pub struct MockVm {
+        pub name: String,
+        pub state: VmLiveState,
+        pub has_managed_save: bool,
+        pub disks: Vec<String>,
+        pub iotune: BTreeMap<String, DiskIoLimits>,
+        /// 按 MAC 地址记录的网卡带宽限速
+        pub bandwidth: BTreeMap<String, NicBandwidth>,
+        /// guest agent 上报的网卡，None 表示客户机内未安装 qemu-guest-agent
+        pub guest_interfaces: Option<Vec<GuestNetworkInterface>>,
+        /// 迁移带宽上限（MiB/s），0 表示未设置
+        pub migration_speed_mbps: u64,
+    }
+
+    /// 不依赖 libvirt 的虚拟化后端，虚拟机保存在内存中并记录每次调用
+    #[derive(Default)]
+    pub struct MockHypervisor {
+        vms: Mutex<BTreeMap<String, MockVm>>,
+        failing: Mutex<HashSet<String>>,
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl MockHypervisor {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// 预置一台虚拟机
+        pub fn with_vm(self, vm_id: &str, name: &str, state: &str) -> Self {
+            self.vms.lock().unwrap().insert(
+                vm_id.to_string(),
+                MockVm {
+                    name: name.to_string(),
+                    state: live_state(state, 2, 1024),
+                    has_managed_save: false,
+                    disks: Vec::new(),
+                    iotune: BTreeMap::new(),
+                    bandwidth: BTreeMap::new(),
+                    guest_interfaces: None,
+                    migration_speed_mbps: 0,
+                },
+            );
+            self
+        }
+
+        /// 设置客户机通过 guest agent 上报的网卡
+        pub fn with_guest_interfaces(self, vm_id: &str, interfaces: Vec<GuestNetworkInterface>) -> Self {
+            if let Some(vm) = self.vms.lock().unwrap().get_mut(vm_id) {
+                vm.guest_interfaces = Some(interfaces);
+            }
+            self
+        }
+
+        /// 让指定方法返回错误
+        pub fn fail_on(self, method: &str) -> Self {
+            self.failing.lock().unwrap().insert(method.to_string());
+            self
+        }
+
+        /// 已发生的调用（方法名）
+        pub fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        /// 读取虚拟机当前记录
+        pub fn vm(&self, vm_id: &str) -> Option<MockVm> {
+            self.vms.lock().unwrap().get(vm_id).cloned()
+        }
+
+        fn record(&self, method: &str) -> Result<()> {
+            self.calls.lock().unwrap().push(method.to_string());
+            if self.failing.lock().unwrap().contains(method) {
+                return Err(common::Error::Internal(format!("mock {} 失败", method)));
+            }
+            Ok(())
+        }
+
+        fn update<T>(&self, vm_id: &str, f: impl FnOnce(&mut MockVm) -> T) -> Result<T> {
+            self.vms
+                .lock()
+                .unwrap()
+                .get_mut(vm_id)
+                .map(f)
+                .ok_or_else(|| common::Error::NotFound(format!("虚拟机不存在: {}", vm_id)))
+        }
+    }
+
+    fn live_state(state: &str, vcpu: u32, memory_mb: u64) -> VmLiveState {
+        VmLiveState {
+            state: state.to_string(),
+            vcpu,
+            memory_mb,
+            max_memory_mb: memory_mb,
+            uptime_secs: (state == "running").then_some(0),
+        }
+    }
+
+    #[async_trait]
+    impl Hypervisor for MockHypervisor {
+        fn is_connected(&self) -> bool {
+            true
+        }
+
+        async fn ensure_connected(&self) -> bool {
+            true
+        }
+
+        async fn vm_exists(&self, vm_id: &str) -> Result<bool> {
+            self.record("vm_exists")?;
+            Ok(self.vms.lock().unwrap().contains_key(vm_id))
+        }
+
+        async fn get_vm_state(&self, vm_id: &str) -> Result<Option<VmLiveState>> {
+            self.record("get_vm_state")?;
+            Ok(self.vm(vm_id).map(|vm| vm.state))
+        }
+
+        async fn get_vnc_info(&self, vm_id: &str) -> Result<Option<VncInfo>> {
+            self.record("get_vnc_info")?;
+            self.update(vm_id, |vm| {
+                (vm.state.state == "running").then(|| VncInfo {
+                    port: 5900,
+                    listen: "0.0.0.0".to_string(),
+                    protocol: Default::default(),
+                })
+            })
+        }
+
+        async fn get_vm_stats(&self, vm_id: &str) -> Result<VmStats> {
+            self.record("get_vm_stats")?;
+            self.update(vm_id, |vm| {
+                let running = vm.state.state == "running";
+                VmStats {
+                    vm_id: vm_id.to_string(),
+                    cpu_time_ns: if running { 1_000_000_000 } else { 0 },
+                    rss_kb: if running { vm.state.memory_mb * 1024 } else { 0 },
+                    ..Default::default()
+                }
+            })
+        }
+
+        async fn start_vm(&self, vm_id: &str) -> Result<()> {
+            self.record("start_vm")?;
+            self.update(vm_id, |vm| {
+                vm.state = live_state("running", vm.state.vcpu, vm.state.max_memory_mb);
+                vm.has_managed_save = false;
+            })
+        }
+
+        async fn start_vm_with_config(&self, vm_id: &str, config: &VMConfig) -> Result<()> {
+            self.record("start_vm_with_config")?;
+            self.vms.lock().unwrap().insert(
+                vm_id.to_string(),
+                MockVm {
+                    name: config.name.clone(),
+                    state: live_state("running", config.vcpu, config.memory_mb),
+                    has_managed_save: false,
+                    disks: config.volumes.iter().map(|v| v.volume_id.clone()).collect(),
+                    iotune: config
+                        .volumes
+                        .iter()
+                        .filter(|v| !v.limits.is_unlimited())
+                        .map(|v| (v.volume_id.clone(), v.limits))
+                        .collect(),
+                    bandwidth: config
+                        .networks
+                        .iter()
+                        .filter(|n| !n.bandwidth.is_unlimited())
+                        .filter_map(|n| Some((n.mac_address.clone()?, n.bandwidth)))
+                        .collect(),
+                    guest_interfaces: None,
+                    migration_speed_mbps: 0,
+                },
+            );
+            Ok(())
+        }
+
+        async fn stop_vm(&self, vm_id: &str, _force: bool) -> Result<()> {
+            self.record("stop_vm")?;
+            self.update(vm_id, |vm| {
+                vm.state = live_state("shutoff", vm.state.vcpu, vm.state.max_memory_mb);
+            })
+        }
+
+        async fn pause_vm(&self, vm_id: &str) -> Result<()> {
+            self.record("pause_vm")?;
+            self.update(vm_id, |vm| {
+                vm.state = live_state("paused", vm.state.vcpu, vm.state.max_memory_mb);
+            })
+        }
+
+        async fn resume_vm(&self, vm_id: &str) -> Result<()> {
+            self.record("resume_vm")?;
+            self.update(vm_id, |vm| {
+                vm.state = live_state("running", vm.state.vcpu, vm.state.max_memory_mb);
+            })
+        }
+
+        async fn set_vcpus(&self, vm_id: &str, count: u32, _live: bool) -> Result<()> {
+            self.record("set_vcpus")?;
+            self.update(vm_id, |vm| vm.state.vcpu = count)
+        }
+
+        async fn set_memory(&self, vm_id: &str, memory_mb: u64, _live: bool) -> Result<()> {
+            self.record("set_memory")?;
+            self.update(vm_id, |vm| vm.state.memory_mb = memory_mb)
+        }
+
+        async fn undefine_vm(&self, vm_id: &str) -> Result<()> {
+            self.record("undefine_vm")?;
+            self.vms.lock().unwrap().remove(vm_id);
+            Ok(())
+        }
+
+        async fn attach_volume(
+            &self,
+            vm_id: &str,
+            volume_id: &str,
+            _volume_path: &str,
+            bus_type: DiskBusType,
+            device_type: DiskDeviceType,
+            _format: &str,
+            _limits: DiskIoLimits,
+            _backing: Option<&BackingStore>,
+            _encrypted: bool,
+        ) -> Result<String> {
+            self.record("attach_volume")?;
+            self.update(vm_id, |vm| {
+                vm.disks.push(volume_id.to_string());
+                common::ws_rpc::types::disk_device_name(&bus_type, &device_type, vm.disks.len() - 1)
+            })
+        }
+
+        async fn define_volume_secret(
+            &self,
+            _volume_id: &str,
+            _volume_path: &str,
+            _passphrase: &Passphrase,
+        ) -> Result<()> {
+            self.record("define_volume_secret")
+        }
+
+        async fn detach_volume(&self, vm_id: &str, volume_id: &str) -> Result<()> {
+            self.record("detach_volume")?;
+            self.update(vm_id, |vm| vm.disks.retain(|d| d != volume_id))
+        }
+
+        async fn set_volume_iotune(&self, vm_id: &str, volume_id: &str, limits: &DiskIoLimits) -> Result<()> {
+            self.record("set_volume_iotune")?;
+            self.update(vm_id, |vm| {
+                if !vm.disks.iter().any(|d| d == volume_id) {
+                    return Err(common::Error::NotFound(format!("未找到存储卷: {}", volume_id)));
+                }
+                vm.iotune.insert(volume_id.to_string(), *limits);
+                Ok(())
+            })?
+        }
+
+        async fn set_interface_bandwidth(&self, vm_id: &str, mac_address: &str, limits: &NicBandwidth) -> Result<()> {
+            self.record("set_interface_bandwidth")?;
+            self.update(vm_id, |vm| {
+                vm.bandwidth.insert(mac_address.to_lowercase(), *limits);
+            })
+        }
+
+        /// 模拟的虚拟机没有 tap 设备
+        async fn interface_target(&self, vm_id: &str, _mac_address: &str) -> Result<Option<String>> {
+            self.record("interface_target")?;
+            self.update(vm_id, |_| None)
+        }
+
+        async fn live_migrate(
+            &self,
+            vm_id: &str,
+            _target_uri: &str,
+            _options: &MigrationOptions,
+        ) -> Result<MigrationMode> {
+            self.record("live_migrate")?;
+            self.vms
+                .lock()
+                .unwrap()
+                .remove(vm_id)
+                .map(|_| MigrationMode::Live)
+                .ok_or_else(|| common::Error::NotFound(format!("虚拟机不存在: {}", vm_id)))
+        }
+
+        async fn abort_migration(&self, vm_id: &str) -> Result<()> {
+            self.record("abort_migration")?;
+            self.update(vm_id, |_| ())
+        }
+
+        async fn get_migration_progress(&self, vm_id: &str) -> Result<(f64, u64)> {
+            self.record("get_migration_progress")?;
+            self.update(vm_id, |_| (50.0, 3))
+        }
+
+        async fn set_migration_speed(&self, vm_id: &str, mbps: u64) -> Result<u64> {
+            self.record("set_migration_speed")?;
+            self.update(vm_id, |vm| {
+                vm.migration_speed_mbps = mbps.max(MIN_MIGRATION_SPEED_MBPS);
+                vm.migration_speed_mbps
+            })
+        }
+
+        async fn get_migration_speed(&self, vm_id: &str) -> Result<u64> {
+            self.record("get_migration_speed")?;
+            self.update(vm_id, |vm| vm.migration_speed_mbps)
+        }
+
+        async fn guest_exec(&self, vm_id: &str, _command: &str, _args: &[String]) -> Result<i64> {
+            self.record("guest_exec")?;
+            self.update(vm_id, |_| 1)
+        }
+
+        async fn guest_exec_status(&self, vm_id: &str, _pid: i64) -> Result<GuestExecStatus> {
+            self.record("guest_exec_status")?;
+            self.update(vm_id, |_| GuestExecStatus {
+                exited: true,
+                exit_code: Some(0),
+                signal: None,
+                stdout: String::new(),
+                stderr: String::new(),
+                truncated: false,
+            })
+        }
+
+        async fn qga_get_interfaces(&self, vm_id: &str) -> Result<Vec<GuestNetworkInterface>> {
+            self.record("qga_get_interfaces")?;
+            self.update(vm_id, |vm| vm.guest_interfaces.clone())?
+                .ok_or_else(|| common::Error::GuestAgentUnavailable("mock 未安装 qemu-guest-agent".to_string()))
+        }
+
+        async fn get_domain_xml(&self, vm_id: &str) -> Result<String> {
+            self.record("get_domain_xml")?;
+            self.update(vm_id, |vm| {
+                format!("<domain type='kvm'><name>{}</name><uuid>{}</uuid></domain>", vm.name, vm_id)
+            })
+        }
+
+        async fn set_console_password(
+            &self,
+            vm_id: &str,
+            _password: &str,
+            _valid_to: Option<DateTime<Utc>>,
+        ) -> Result<()> {
+            self.record("set_console_password")?;
+            if self.update(vm_id, |vm| vm.state.state != "running")? {
+                return Err(common::Error::InvalidArgument("虚拟机未运行".to_string()));
+            }
+            Ok(())
+        }
+
+        async fn fsfreeze(&self, vm_id: &str) -> Result<()> {
+            self.record("fsfreeze")?;
+            if self.update(vm_id, |vm| vm.guest_interfaces.is_none())? {
+                return Err(common::Error::GuestAgentUnavailable(
+                    "mock 未安装 qemu-guest-agent".to_string(),
+                ));
+            }
+            Ok(())
+        }
+
+        async fn fsthaw(&self, vm_id: &str) -> Result<()> {
+            self.record("fsthaw")?;
+            self.update(vm_id, |_| ())
+        }
+
+        async fn list_active_vms(&self) -> Result<Vec<(String, String)>> {
+            self.record("list_active_vms")?;
+            Ok(self
+                .vms
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(_, vm)| vm.state.state == "running")
+                .map(|(id, vm)| (id.clone(), vm.name.clone()))
+                .collect())
+        }
+
+        async fn list_vms(&self) -> Result<Vec<VMInfo>> {
+            self.record("list_vms")?;
+            Ok(self
+                .vms
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(id, vm)| VMInfo {
+                    id: id.clone(),
+                    name: vm.name.clone(),
+                    state: vm.state.state.clone(),
+                    vcpu: vm.state.vcpu,
+                    memory_mb: vm.state.memory_mb,
+                })
+                .collect())
+        }
+
+        async fn managed_save_vm(&self, vm_id: &str) -> Result<()> {
+            self.record("managed_save_vm")?;
+            self.update(vm_id, |vm| {
+                vm.state = live_state("shutoff", vm.state.vcpu, vm.state.max_memory_mb);
+                vm.has_managed_save = true;
+            })
+        }
+
+        async fn list_restore_states(&self) -> Result<Vec<(String, String, bool)>> {
+            self.record("list_restore_states")?;
+            Ok(self
+                .vms
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(id, vm)| (id.clone(), vm.name.clone(), vm.has_managed_save))
+                .collect())
+        }
+    }
+}
@@ -0,0 +1,177 @@
+/// virt crate 未封装的 libvirt 接口
+///
+/// 直接调用 virt::sys 中的 C 函数：参数中含 NUL 字符时返回 InvalidArgument，
+/// libvirt 调用失败时返回携带 libvirt 错误信息的 Hypervisor
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use virt::domain::Domain;
+use virt::error::Error;
+use virt::sys;
+
+extern "C" {
+    /// libvirt 返回的字符串由调用方通过 free 释放
+    fn free(ptr: *mut c_void);
+}
+
+/// 转换为 C 字符串，内部含 NUL 字符的参数无法传给 libvirt
+fn c_string(value: &str) -> common::Result<CString> {
+    CString::new(value).map_err(|_| common::Error::InvalidArgument(format!("参数包含 NUL 字符: {:?}", value)))
+}
+
+/// 取出当前线程上最近一次 libvirt 错误
+fn last_error() -> common::Error {
+    common::Error::Hypervisor(Error::last_error().to_string())
+}
+
+/// 磁盘设备的累计读写统计
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockStats {
+    pub rd_bytes: i64,
+    pub wr_bytes: i64,
+}
+
+/// 读取磁盘设备（如 vda）的累计读写字节数
+pub fn block_stats(domain: &Domain, disk: &str) -> common::Result<BlockStats> {
+    let disk = c_string(disk)?;
+    let mut stats = sys::virDomainBlockStatsStruct {
+        rd_req: 0,
+        rd_bytes: 0,
+        wr_req: 0,
+        wr_bytes: 0,
+        errs: 0,
+    };
+    let ret = unsafe {
+        sys::virDomainBlockStats(
+            domain.as_ptr(),
+            disk.as_ptr(),
+            &mut stats,
+            std::mem::size_of::<sys::virDomainBlockStatsStruct>(),
+        )
+    };
+    if ret == -1 {
+        return Err(last_error());
+    }
+    Ok(BlockStats {
+        rd_bytes: stats.rd_bytes,
+        wr_bytes: stats.wr_bytes,
+    })
+}
+
+/// 设置磁盘设备的总 IOPS 与总带宽限速，0 表示取消限速
+pub fn set_block_io_tune(
+    domain: &Domain,
+    disk: &str,
+    total_iops_sec: u64,
+    total_bytes_sec: u64,
+    flags: u32,
+) -> common::Result<()> {
+    let disk = c_string(disk)?;
+    let mut params: sys::virTypedParameterPtr = std::ptr::null_mut();
+    let mut nparams: c_int = 0;
+    let mut maxparams: c_int = 0;
+
+    let ret = unsafe {
+        let mut ret = sys::virTypedParamsAddULLong(
+            &mut params,
+            &mut nparams,
+            &mut maxparams,
+            sys::VIR_DOMAIN_BLOCK_IOTUNE_TOTAL_IOPS_SEC.as_ptr(),
+            total_iops_sec,
+        );
+        if ret == 0 {
+            ret = sys::virTypedParamsAddULLong(
+                &mut params,
+                &mut nparams,
+                &mut maxparams,
+                sys::VIR_DOMAIN_BLOCK_IOTUNE_TOTAL_BYTES_SEC.as_ptr(),
+                total_bytes_sec,
+            );
+        }
+        if ret == 0 {
+            ret = sys::virDomainSetBlockIoTune(domain.as_ptr(), disk.as_ptr(), params, nparams, flags);
+        }
+        ret
+    };
+    // 失败时先取出错误，再释放参数
+    let result = if ret == -1 { Err(last_error()) } else { Ok(()) };
+    unsafe { sys::virTypedParamsFree(params, nparams) };
+    result
+}
+
+/// 设置网卡的平均带宽（KiB/s），0 表示取消该方向的限速
+///
+/// `device` 可以是网卡的目标设备名（如 vnet0）或 MAC 地址
+pub fn set_interface_bandwidth(
+    domain: &Domain,
+    device: &str,
+    inbound_average: u32,
+    outbound_average: u32,
+    flags: u32,
+) -> common::Result<()> {
+    let device = c_string(device)?;
+    let mut params: sys::virTypedParameterPtr = std::ptr::null_mut();
+    let mut nparams: c_int = 0;
+    let mut maxparams: c_int = 0;
+
+    let ret = unsafe {
+        let mut ret = sys::virTypedParamsAddUInt(
+            &mut params,
+            &mut nparams,
+            &mut maxparams,
+            sys::VIR_DOMAIN_BANDWIDTH_IN_AVERAGE.as_ptr(),
+            inbound_average,
+        );
+        if ret == 0 {
+            ret = sys::virTypedParamsAddUInt(
+                &mut params,
+                &mut nparams,
+                &mut maxparams,
+                sys::VIR_DOMAIN_BANDWIDTH_OUT_AVERAGE.as_ptr(),
+                outbound_average,
+            );
+        }
+        if ret == 0 {
+            ret = sys::virDomainSetInterfaceParameters(domain.as_ptr(), device.as_ptr(), params, nparams, flags);
+        }
+        ret
+    };
+    // 失败时先取出错误，再释放参数
+    let result = if ret == -1 { Err(last_error()) } else { Ok(()) };
+    unsafe { sys::virTypedParamsFree(params, nparams) };
+    result
+}
+
+/// 通过 qemu-guest-agent 执行 JSON 命令，返回原始响应文本
+///
+/// `timeout` 为等待秒数，负值含义见 VIR_DOMAIN_QEMU_AGENT_COMMAND_*
+pub fn qemu_agent_command(domain: &Domain, cmd: &str, timeout: i32, flags: u32) -> common::Result<String> {
+    let cmd = c_string(cmd)?;
+    let result: *mut c_char =
+        unsafe { sys::virDomainQemuAgentCommand(domain.as_ptr(), cmd.as_ptr(), timeout, flags) };
+    if result.is_null() {
+        return Err(last_error());
+    }
+    let output = unsafe { CStr::from_ptr(result) }.to_string_lossy().into_owned();
+    unsafe { free(result as *mut c_void) };
+    Ok(output)
+}
+
+/// 中止域上正在进行的后台作业（如迁移）
+pub fn abort_job(domain: &Domain) -> common::Result<()> {
+    let ret = unsafe { sys::virDomainAbortJob(domain.as_ptr()) };
+    if ret == -1 {
+        return Err(last_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_c_string_rejects_interior_nul() {
+        assert_eq!(c_string("vda").unwrap().as_bytes(), b"vda");
+        assert!(matches!(c_string("vda\0"), Err(common::Error::InvalidArgument(_))));
+    }
+}
@@ -1,45 +1,324 @@
-use common::ws_rpc::types::{DiskBusType, DiskDeviceType};
+use common::ws_rpc::types::{
+    disk_device_name, BackingStore, CloudInitConfig, CpuPinning, DhcpConfig, DiskBusType, DiskDeviceType, DiskIoLimits, FirmwareType,
+    GraphicsType, GuestNetworkInterface, MigrationMode, MigrationStorageMode, NicBandwidth, NumaConfig, SecurityGroupRule,
+    VmStats, VncInfo, VolumeEncryption,
+};
 /// 虚拟化管理器
 ///
 /// 负责与 libvirt 交互，管理虚拟机生命周期
 use common::Result;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
 use virt::connect::Connect;
 
+use crate::storage::encryption::Passphrase;
+
 pub struct HypervisorManager {
-    conn: Arc<Mutex<Connect>>,
+    /// libvirt 连接，启动时未能连接则为 None（降级运行，后续按需重连）
+    conn: Arc<Mutex<Option<Connect>>>,
+    /// 连接状态，供心跳读取（不经过连接锁，避免被长时间操作阻塞）
+    connected: Arc<AtomicBool>,
+    /// 连接后从 libvirt capabilities 检测到的各架构模拟器与机器类型
+    guests: Arc<std::sync::RwLock<Vec<GuestArch>>>,
 }
 
+/// libvirt 连接地址
+const LIBVIRT_URI: &str = "qemu:///system";
+
+/// 初次连接重试的最大退避间隔
+const CONNECT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// 定义虚拟机时预留的最大 vCPU 数，运行中可在此范围内热插拔
+const HOTPLUG_MAX_VCPUS: u32 = 16;
+
+/// 内存上限为启动内存的倍数，运行中通过 balloon 在上限内调整
+const HOTPLUG_MEMORY_FACTOR: u64 = 2;
+
+/// libvirt 修改作用范围：运行中的虚拟机 / 持久化定义
+const VIR_DOMAIN_AFFECT_LIVE: u32 = 1;
+const VIR_DOMAIN_AFFECT_CONFIG: u32 = 2;
+
+/// 迁移带宽下限（MiB/s），误填 0 或过小的值会让迁移几乎停滞
+pub const MIN_MIGRATION_SPEED_MBPS: u64 = 10;
+
+/// libvirt 在未限速时报告的带宽（INT64_MAX 字节/秒换算为 MiB/s）
+const UNLIMITED_MIGRATION_SPEED_MBPS: u64 = (i64::MAX as u64) >> 20;
+
 impl HypervisorManager {
-    pub fn new() -> Result<Self> {
-        // 连接到本地 QEMU/KVM hypervisor
-        let conn = Connect::open(Some("qemu:///system"))
-            .map_err(|e| common::Error::Internal(format!("无法连接到 libvirt: {}", e)))?;
+    /// 连接到本地 QEMU/KVM hypervisor
+    ///
+    /// libvirtd 尚未就绪时（开机启动顺序）按指数退避重试，最多等待 `max_wait`；
+    /// 仍无法连接时不返回错误，而是以降级状态运行，由心跳上报并在后续调用时重连
+    pub async fn connect(max_wait: Duration) -> Self {
+        let deadline = Instant::now() + max_wait;
+        let mut backoff = Duration::from_secs(1);
+        let mut attempt = 1;
+
+        let conn = loop {
+            match Connect::open(Some(LIBVIRT_URI)) {
+                Ok(conn) => {
+                    tracing::info!("✅ 成功连接到 libvirt");
+                    break Some(conn);
+                }
+                Err(e) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        tracing::error!(
+                            "❌ 无法连接到 libvirt（已重试 {} 次）: {}，Agent 以降级状态运行",
+                            attempt, e
+                        );
+                        break None;
+                    }
 
-        tracing::info!("✅ 成功连接到 libvirt");
+                    let wait = backoff.min(deadline - now);
+                    tracing::warn!("连接 libvirt 失败（第 {} 次）: {}，{:?} 后重试", attempt, e, wait);
+                    tokio::time::sleep(wait).await;
+                    backoff = (backoff * 2).min(CONNECT_MAX_BACKOFF);
+                    attempt += 1;
+                }
+            }
+        };
 
-        Ok(Self {
+        let guests = conn.as_ref().map(detect_guests).unwrap_or_default();
+        Self {
+            connected: Arc::new(AtomicBool::new(conn.is_some())),
             conn: Arc::new(Mutex::new(conn)),
-        })
+            guests: Arc::new(std::sync::RwLock::new(guests)),
+        }
+    }
+
+    /// libvirt 是否已连接
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    /// 降级状态下尝试重新连接 libvirt，返回当前是否已连接
+    pub async fn ensure_connected(&self) -> bool {
+        self.connection().await.is_ok()
+    }
+
+    /// 获取 libvirt 连接，未连接时先尝试重连
+    async fn connection(&self) -> Result<MappedMutexGuard<'_, Connect>> {
+        let mut guard = self.conn.lock().await;
+
+        if guard.is_none() {
+            let conn = Connect::open(Some(LIBVIRT_URI))
+                .map_err(|e| common::Error::Internal(format!("无法连接到 libvirt: {}", e)))?;
+            tracing::info!("✅ 已重新连接到 libvirt");
+            if self.guests.read().unwrap().is_empty() {
+                *self.guests.write().unwrap() = detect_guests(&conn);
+            }
+            *guard = Some(conn);
+            self.connected.store(true, Ordering::SeqCst);
+        }
+
+        Ok(MutexGuard::map(guard, |conn| conn.as_mut().expect("libvirt 连接已建立")))
     }
 
     /// 检查虚拟机是否存在
     pub async fn vm_exists(&self, vm_id: &str) -> Result<bool> {
-        let conn = self.conn.lock().await;
+        let conn = self.connection().await?;
+
+        Ok(lookup_domain(&conn, vm_id).is_ok())
+    }
+
+    /// 读取虚拟机在 libvirt 中的实时状态，虚拟机未定义时返回 None
+    pub async fn get_vm_state(&self, vm_id: &str) -> Result<Option<VmLiveState>> {
+        let conn = self.connection().await?;
+
+        let domain = match lookup_domain(&conn, vm_id) {
+            Ok(domain) => domain,
+            Err(_) => return Ok(None),
+        };
+
+        let info = domain
+            .get_info()
+            .map_err(|e| common::Error::Internal(format!("无法获取虚拟机信息: {}", e)))?;
+
+        let state = domain_state_name(info.state);
+        let uptime_secs = if state == "running" || state == "paused" || state == "blocked" {
+            domain.get_name().ok().and_then(|name| qemu_uptime_secs(&name))
+        } else {
+            None
+        };
+
+        Ok(Some(VmLiveState {
+            state: state.to_string(),
+            vcpu: info.nr_virt_cpu,
+            // libvirt 返回的内存单位为 KiB
+            memory_mb: info.memory / 1024,
+            max_memory_mb: info.max_mem / 1024,
+            uptime_secs,
+        }))
+    }
+
+    /// 读取运行中虚拟机实际分配的控制台（VNC 或 SPICE）端口和监听地址
+    ///
+    /// 域 XML 使用 autoport，端口在启动后才由 libvirt 分配；未配置图形控制台（如安全模式）时返回 None
+    pub async fn get_vnc_info(&self, vm_id: &str) -> Result<Option<VncInfo>> {
+        let conn = self.connection().await?;
+
+        let domain = lookup_domain(&conn, vm_id)?;
+        let xml = domain
+            .get_xml_desc(0)
+            .map_err(|e| common::Error::Internal(format!("获取虚拟机XML失败: {}", e)))?;
+
+        parse_vnc_info(&xml)
+    }
+
+    /// 读取虚拟机运行时统计
+    ///
+    /// 磁盘和网卡按域 XML 中的设备逐个读取后累加，单个设备读取失败时跳过该设备
+    pub async fn get_vm_stats(&self, vm_id: &str) -> Result<VmStats> {
+        // libvirt 内存统计中的 RSS 标签（VIR_DOMAIN_MEMORY_STAT_RSS）
+        const VIR_DOMAIN_MEMORY_STAT_RSS: u32 = 7;
+
+        let conn = self.connection().await?;
+
+        let domain = lookup_domain(&conn, vm_id)?;
+        let info = domain
+            .get_info()
+            .map_err(|e| common::Error::Internal(format!("无法获取虚拟机信息: {}", e)))?;
+
+        let mut stats = VmStats {
+            vm_id: vm_id.to_string(),
+            cpu_time_ns: info.cpu_time,
+            ..Default::default()
+        };
+
+        // 未运行的虚拟机没有 QEMU 进程，内存和设备统计不可读
+        let state = domain_state_name(info.state);
+        if state != "running" && state != "paused" && state != "blocked" {
+            return Ok(stats);
+        }
+
+        let memory_stats = domain
+            .memory_stats(0)
+            .map_err(|e| common::Error::Internal(format!("获取虚拟机内存统计失败: {}", e)))?;
+        stats.rss_kb = memory_stats
+            .iter()
+            .find(|stat| stat.tag == VIR_DOMAIN_MEMORY_STAT_RSS)
+            .map(|stat| stat.val)
+            .unwrap_or(0);
+
+        let xml = domain
+            .get_xml_desc(0)
+            .map_err(|e| common::Error::Internal(format!("获取虚拟机XML失败: {}", e)))?;
+        let devices = parse_stat_devices(&xml)?;
+
+        for disk in &devices.disks {
+            match super::ffi::block_stats(&domain, disk) {
+                Ok(block) => {
+                    stats.disk_read_bytes += block.rd_bytes.max(0) as u64;
+                    stats.disk_write_bytes += block.wr_bytes.max(0) as u64;
+                }
+                Err(e) => tracing::debug!("读取虚拟机 {} 磁盘 {} 统计失败: {}", vm_id, disk, e),
+            }
+        }
+
+        for interface in &devices.interfaces {
+            match domain.interface_stats(interface) {
+                Ok(net) => {
+                    stats.net_rx_bytes += net.rx_bytes.max(0) as u64;
+                    stats.net_tx_bytes += net.tx_bytes.max(0) as u64;
+                }
+                Err(e) => tracing::debug!("读取虚拟机 {} 网卡 {} 统计失败: {}", vm_id, interface, e),
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// 读取虚拟机当前的域 XML，运行中为实时配置
+    pub async fn get_domain_xml(&self, vm_id: &str) -> Result<String> {
+        let conn = self.connection().await?;
+
+        let domain = lookup_domain(&conn, vm_id)?;
+        domain
+            .get_xml_desc(0)
+            .map_err(|e| common::Error::Internal(format!("获取虚拟机XML失败: {}", e)))
+    }
 
-        // 先尝试通过 UUID 查找
-        if let Ok(_) = virt::domain::Domain::lookup_by_uuid_string(&conn, vm_id) {
-            return Ok(true);
+    /// 修改运行中虚拟机的控制台密码
+    ///
+    /// `valid_to` 之后不再接受新的控制台连接（已建立的连接不受影响），只修改运行中的配置，
+    /// 下次启动时使用 Server 下发的密码
+    pub async fn set_console_password(
+        &self,
+        vm_id: &str,
+        password: &str,
+        valid_to: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        // libvirt 域状态常量
+        const VIR_DOMAIN_RUNNING: u32 = 1;
+        const VIR_DOMAIN_PAUSED: u32 = 3;
+
+        let conn = self.connection().await?;
+        let domain = lookup_domain(&conn, vm_id)?;
+
+        let (state, _reason) = domain.get_state()
+            .map_err(|e| common::Error::Internal(format!("无法获取虚拟机状态: {}", e)))?;
+        if state != VIR_DOMAIN_RUNNING && state != VIR_DOMAIN_PAUSED {
+            return Err(common::Error::InvalidArgument(format!(
+                "虚拟机未运行，无法设置控制台密码 (状态: {})",
+                domain_state_name(state)
+            )));
         }
 
-        // 再尝试通过名称查找
-        if let Ok(_) = virt::domain::Domain::lookup_by_name(&conn, vm_id) {
-            return Ok(true);
+        let xml = domain
+            .get_xml_desc(0)
+            .map_err(|e| common::Error::Internal(format!("获取虚拟机XML失败: {}", e)))?;
+        let graphics = graphics_password_xml(&xml, password, valid_to)?;
+
+        domain
+            .update_device_flags(&graphics, VIR_DOMAIN_AFFECT_LIVE)
+            .map_err(|e| common::Error::Internal(format!("设置控制台密码失败: {}", e)))?;
+
+        tracing::info!("✅ 虚拟机 {} 控制台密码已更新，有效期至 {:?}", vm_id, valid_to);
+        Ok(())
+    }
+
+    /// 按检测到的 capabilities 补全未指定的机器类型与模拟器，并校验指定的机器类型
+    ///
+    /// 未能检测（如启动时 libvirt 不可用）时保持原样，由生成器按架构使用默认值
+    fn with_guest_defaults(&self, config: &VMConfig) -> Result<VMConfig> {
+        let mut config = config.clone();
+        let guests = self.guests.read().unwrap();
+        let Some(guest) = guests.iter().find(|g| g.arch == config.arch) else {
+            return Ok(config);
+        };
+
+        match &config.machine_type {
+            Some(machine) if !guest.machines.is_empty() && !guest.machines.contains(machine) => {
+                return Err(common::Error::InvalidArgument(format!(
+                    "机器类型 {} 不受支持（{} 可用: {}）",
+                    machine,
+                    guest.arch,
+                    guest.machines.join(", ")
+                )));
+            }
+            Some(_) => {}
+            None => config.machine_type = guest.machine.clone(),
+        }
+        if config.emulator.is_none() {
+            config.emulator = Some(guest.emulator.clone());
         }
+        Ok(config)
+    }
 
-        Ok(false)
+    /// 定义虚拟机使用的 XML：配置了自定义 XML 时校验后原样使用，否则按配置生成
+    fn domain_xml(config: &VMConfig) -> Result<String> {
+        match &config.raw_xml_override {
+            Some(xml) => {
+                validate_xml_override(xml, config)?;
+                Ok(xml.clone())
+            }
+            None => Self::generate_vm_xml(config),
+        }
     }
 
     /// 生成虚拟机 XML 配置
@@ -54,32 +333,96 @@ impl HypervisorManager {
         writeln!(xml, "<domain type='kvm'>").unwrap();
         writeln!(xml, "  <name>{}</name>", config.name).unwrap();
         writeln!(xml, "  <uuid>{}</uuid>", vm_uuid).unwrap();
-        writeln!(xml, "  <memory unit='MiB'>{}</memory>", config.memory_mb).unwrap();
+        // <memory> 与 <vcpu> 为上限，实际分配由 currentMemory 与 current 指定，留出热调整余量
+        writeln!(xml, "  <memory unit='MiB'>{}</memory>", config.max_memory_mb()).unwrap();
         writeln!(xml, "  <currentMemory unit='MiB'>{}</currentMemory>", config.memory_mb).unwrap();
-        writeln!(xml, "  <vcpu placement='static'>{}</vcpu>", config.vcpu).unwrap();
+        writeln!(
+            xml,
+            "  <vcpu placement='static' current='{}'>{}</vcpu>",
+            config.vcpu,
+            config.max_vcpu()
+        )
+        .unwrap();
+
+        if let Some(pinning) = config.cpu_pinning.as_ref().filter(|p| !p.is_empty()) {
+            writeln!(xml, "  <cputune>").unwrap();
+            for (vcpu, cpu) in pinning {
+                writeln!(xml, "    <vcpupin vcpu='{}' cpuset='{}'/>", vcpu, cpu).unwrap();
+            }
+            writeln!(xml, "  </cputune>").unwrap();
+        }
+        if let Some(numa) = config.numa.as_ref().filter(|n| !n.host_nodes.is_empty()) {
+            let nodeset: Vec<String> = numa.host_nodes.iter().map(|id| id.to_string()).collect();
+            writeln!(xml, "  <numatune>").unwrap();
+            writeln!(
+                xml,
+                "    <memory mode='{}' nodeset='{}'/>",
+                numa.mode.as_str(),
+                nodeset.join(",")
+            )
+            .unwrap();
+            writeln!(xml, "  </numatune>").unwrap();
+        }
 
         // CPU 配置 - 根据操作系统类型优化
+        let numa_cells = config.guest_numa_cells();
         if config.os_type == "windows" {
             // Windows 优化：使用 host-model 模式，启用更多特性
             writeln!(xml, "  <cpu mode='host-model' check='partial'>").unwrap();
-            writeln!(xml, "    <topology sockets='1' dies='1' cores='{}' threads='1'/>", config.vcpu).unwrap();
+            // 拓扑需覆盖全部可热插拔的 vCPU
+            writeln!(xml, "    <topology sockets='1' dies='1' cores='{}' threads='1'/>", config.max_vcpu()).unwrap();
             writeln!(xml, "    <feature policy='require' name='vmx'/>").unwrap();
             writeln!(xml, "    <feature policy='require' name='svm'/>").unwrap();
+            xml.push_str(&numa_cells);
             writeln!(xml, "  </cpu>").unwrap();
-        } else {
+        } else if numa_cells.is_empty() {
             // Linux 默认配置
             writeln!(xml, "  <cpu mode='host-passthrough' check='none'/>").unwrap();
+        } else {
+            writeln!(xml, "  <cpu mode='host-passthrough' check='none'>").unwrap();
+            xml.push_str(&numa_cells);
+            writeln!(xml, "  </cpu>").unwrap();
         }
 
         // 操作系统配置
-        writeln!(xml, "  <os>").unwrap();
-        writeln!(xml, "    <type arch='x86_64' machine='pc-q35-7.2'>hvm</type>").unwrap();
-        writeln!(xml, "  </os>").unwrap();
+        let os_type = format!(
+            "    <type arch='{}' machine='{}'>hvm</type>",
+            config.arch,
+            config.machine_type.as_deref().unwrap_or(default_machine(&config.arch))
+        );
+        match config.firmware {
+            FirmwareType::Bios => {
+                writeln!(xml, "  <os>").unwrap();
+                writeln!(xml, "{}", os_type).unwrap();
+                if config.boot_menu {
+                    writeln!(xml, "    <bootmenu enable='yes'/>").unwrap();
+                }
+                writeln!(xml, "  </os>").unwrap();
+            }
+            FirmwareType::Uefi => {
+                // 由 libvirt 自动选择支持安全启动的 OVMF 固件；NVRAM 文件按虚拟机 ID 命名，
+                // 首次启动时由 libvirt 从模板创建，之后重新定义时保留，安全启动变量因此得以持久化
+                writeln!(xml, "  <os firmware='efi'>").unwrap();
+                writeln!(xml, "{}", os_type).unwrap();
+                writeln!(xml, "    <loader secure='yes'/>").unwrap();
+                writeln!(xml, "    <nvram>{}</nvram>", nvram_path(vm_uuid)).unwrap();
+                if config.boot_menu {
+                    writeln!(xml, "    <bootmenu enable='yes'/>").unwrap();
+                }
+                writeln!(xml, "  </os>").unwrap();
+            }
+        }
 
         // 特性 - 根据操作系统类型优化
         writeln!(xml, "  <features>").unwrap();
         writeln!(xml, "    <acpi/>").unwrap();
-        writeln!(xml, "    <apic/>").unwrap();
+        if is_x86(&config.arch) {
+            writeln!(xml, "    <apic/>").unwrap();
+        }
+        if config.firmware == FirmwareType::Uefi {
+            // 安全启动固件要求启用 SMM
+            writeln!(xml, "    <smm state='on'/>").unwrap();
+        }
         if config.os_type == "windows" {
             // Windows 优化特性
             writeln!(xml, "    <hyperv mode='custom'>").unwrap();
@@ -118,10 +461,15 @@ impl HypervisorManager {
         writeln!(xml, "  <devices>").unwrap();
 
         // 模拟器
-        writeln!(xml, "    <emulator>/usr/bin/qemu-system-x86_64</emulator>").unwrap();
+        let emulator = config
+            .emulator
+            .clone()
+            .unwrap_or_else(|| format!("/usr/bin/qemu-system-{}", config.arch));
+        writeln!(xml, "    <emulator>{}</emulator>", emulator).unwrap();
 
         // 磁盘 - 根据操作系统类型和配置优化
-        for (idx, volume) in config.volumes.iter().enumerate() {
+        let volumes = config.volumes_with_config_drive();
+        for (idx, volume) in volumes.iter().enumerate() {
             let device_type = match volume.device_type {
                 DiskDeviceType::Disk => "disk",
                 DiskDeviceType::Cdrom => "cdrom",
@@ -144,17 +492,22 @@ impl HypervisorManager {
             }
 
             writeln!(xml, "      <source file='{}'/>", volume.volume_path).unwrap();
+            if let Some(backing_store) = backing_store_xml(volume.backing.as_ref()) {
+                writeln!(xml, "      {}", backing_store).unwrap();
+            }
+            if volume.encryption.is_some() {
+                writeln!(xml, "      {}", encryption_xml(&volume.volume_id)).unwrap();
+            }
 
             // 添加序列号 - 使用 volume_id 作为序列号
             writeln!(xml, "      <serial>{}</serial>", volume.volume_id).unwrap();
 
+            if let Some(iotune) = iotune_xml(&volume.limits) {
+                writeln!(xml, "      {}", iotune).unwrap();
+            }
+
             // 自动生成设备名 - 根据总线类型和设备类型
-            let device_name = match (volume.bus_type.clone(), volume.device_type.clone()) {
-                (DiskBusType::Virtio, DiskDeviceType::Disk) => format!("vd{}", (b'a' + idx as u8) as char),
-                (DiskBusType::Scsi, DiskDeviceType::Disk) => format!("sd{}", (b'a' + idx as u8) as char),
-                (DiskBusType::Ide, DiskDeviceType::Disk) => format!("hd{}", (b'a' + idx as u8) as char),
-                (_, DiskDeviceType::Cdrom) => format!("hd{}", (b'a' + idx as u8) as char),
-            };
+            let device_name = disk_device_name(&volume.bus_type, &volume.device_type, idx);
 
             // 根据总线类型设置总线和控制器
             match volume.bus_type {
@@ -165,29 +518,49 @@ impl HypervisorManager {
                     writeln!(xml, "      <target dev='{}' bus='scsi'/>", device_name).unwrap();
                     writeln!(xml, "      <address type='drive' controller='0' bus='0' target='0' unit='{}'/>", idx).unwrap();
                 }
+                DiskBusType::Sata => {
+                    // 端口由 libvirt 在 AHCI 控制器上自动分配
+                    writeln!(xml, "      <target dev='{}' bus='sata'/>", device_name).unwrap();
+                }
                 DiskBusType::Ide => {
                     writeln!(xml, "      <target dev='{}' bus='ide'/>", device_name).unwrap();
                 }
             }
+            // 逐设备的引导顺序，不能与 <os><boot dev=.../> 同时使用
+            if let Some(order) = volume.boot_order {
+                writeln!(xml, "      <boot order='{}'/>", order).unwrap();
+            }
             writeln!(xml, "    </disk>").unwrap();
         }
 
         // 网络接口 - 根据操作系统类型优化
         for network in &config.networks {
-            // 使用 Bridge 类型直接连接到 Linux Bridge
-            writeln!(xml, "    <interface type='bridge'>").unwrap();
-
-            if let Some(mac) = &network.mac_address {
-                writeln!(xml, "      <mac address='{}'/>", mac).unwrap();
-            }
-
             // 使用 bridge_name 而不是 network_name
             let bridge = if network.bridge_name.is_empty() {
                 "virbr0"  // 默认 Bridge
             } else {
                 &network.bridge_name
             };
-            writeln!(xml, "      <source bridge='{}'/>", bridge).unwrap();
+
+            if network.is_macvlan() {
+                // Macvlan 网络以 macvtap 直连，source 为网络的 macvlan 设备
+                writeln!(xml, "    <interface type='direct'>").unwrap();
+                if let Some(mac) = &network.mac_address {
+                    writeln!(xml, "      <mac address='{}'/>", mac).unwrap();
+                }
+                writeln!(xml, "      <source dev='{}' mode='bridge'/>", bridge).unwrap();
+            } else {
+                // 使用 Bridge 类型直接连接到 Linux Bridge
+                writeln!(xml, "    <interface type='bridge'>").unwrap();
+                if let Some(mac) = &network.mac_address {
+                    writeln!(xml, "      <mac address='{}'/>", mac).unwrap();
+                }
+                writeln!(xml, "      <source bridge='{}'/>", bridge).unwrap();
+                if network.is_ovs() {
+                    // 由 libvirt 通过 ovs-vsctl 将 tap 设备加入 OVS Bridge
+                    writeln!(xml, "      <virtualport type='openvswitch'/>").unwrap();
+                }
+            }
 
             let model = if network.model.is_empty() {
                 if config.os_type == "windows" {
@@ -200,6 +573,10 @@ impl HypervisorManager {
             };
 
             writeln!(xml, "      <model type='{}'/>", model).unwrap();
+            writeln!(xml, "      <mtu size='{}'/>", network.mtu()).unwrap();
+            if let Some(bandwidth) = bandwidth_xml(&network.bandwidth) {
+                writeln!(xml, "      {}", bandwidth).unwrap();
+            }
 
             // Windows 网络优化
             if config.os_type == "windows" {
@@ -221,18 +598,40 @@ impl HypervisorManager {
         writeln!(xml, "    </console>").unwrap();
 
         // VirtIO 串口控制器 - QGA 必需
-        writeln!(xml, "    <controller type='virtio-serial' index='0'>").unwrap();
-        writeln!(xml, "      <address type='pci' domain='0x0000' bus='0x00' slot='0x06' function='0x0'/>").unwrap();
-        writeln!(xml, "    </controller>").unwrap();
+        if !config.safe_mode {
+            writeln!(xml, "    <controller type='virtio-serial' index='0'>").unwrap();
+            writeln!(xml, "      <address type='pci' domain='0x0000' bus='0x00' slot='0x06' function='0x0'/>").unwrap();
+            writeln!(xml, "    </controller>").unwrap();
+        }
 
         // 检查是否需要 virtio-scsi 控制器
-        let needs_virtio_scsi = config.volumes.iter().any(|volume| volume.bus_type == DiskBusType::Scsi);
+        let needs_virtio_scsi = volumes.iter().any(|volume| volume.bus_type == DiskBusType::Scsi);
         if needs_virtio_scsi {
             writeln!(xml, "    <controller type='scsi' index='0' model='virtio-scsi'>").unwrap();
             writeln!(xml, "      <address type='pci' domain='0x0000' bus='0x00' slot='0x07' function='0x0'/>").unwrap();
             writeln!(xml, "    </controller>").unwrap();
         }
 
+        // SATA 磁盘/光驱需要 AHCI 控制器
+        let needs_sata = volumes.iter().any(|volume| volume.bus_type == DiskBusType::Sata);
+        if needs_sata {
+            writeln!(xml, "    <controller type='sata' index='0'/>").unwrap();
+        }
+
+        // 模拟 TPM 2.0，由宿主机 swtpm 提供；安全模式同样保留，避免 BitLocker 等要求恢复密钥
+        if config.tpm {
+            writeln!(xml, "    <tpm model='tpm-crb'>").unwrap();
+            writeln!(xml, "      <backend type='emulator' version='2.0'/>").unwrap();
+            writeln!(xml, "    </tpm>").unwrap();
+        }
+
+        // 安全模式只保留串口控制台，不生成 QGA 通道和图形设备
+        if config.safe_mode {
+            writeln!(xml, "  </devices>").unwrap();
+            writeln!(xml, "</domain>").unwrap();
+            return Ok(xml);
+        }
+
         // QEMU Guest Agent 串口设备
         writeln!(xml, "    <channel type='unix'>").unwrap();
         writeln!(xml, "      <source mode='bind'/>").unwrap();
@@ -240,13 +639,29 @@ impl HypervisorManager {
         writeln!(xml, "      <address type='virtio-serial' controller='0' bus='0' port='1'/>").unwrap();
         writeln!(xml, "    </channel>").unwrap();
 
-        // VGA 图形 - 根据操作系统类型优化
-        writeln!(xml, "    <graphics type='vnc' port='-1' autoport='yes' listen='0.0.0.0'>").unwrap();
+        // 图形控制台：SPICE 额外需要 spicevmc 通道（剪贴板共享）和 USB 重定向设备
+        let graphics = config.graphics.as_str();
+        let passwd = config
+            .console_password
+            .as_deref()
+            .map(|password| format!(" passwd='{}'", xml_attr(password)))
+            .unwrap_or_default();
+        writeln!(xml, "    <graphics type='{}' port='-1' autoport='yes' listen='0.0.0.0'{}>", graphics, passwd).unwrap();
         writeln!(xml, "      <listen type='address' address='0.0.0.0'/>").unwrap();
         writeln!(xml, "    </graphics>").unwrap();
+        if config.graphics == GraphicsType::Spice {
+            writeln!(xml, "    <channel type='spicevmc'>").unwrap();
+            writeln!(xml, "      <target type='virtio' name='com.redhat.spice.0'/>").unwrap();
+            writeln!(xml, "      <address type='virtio-serial' controller='0' bus='0' port='2'/>").unwrap();
+            writeln!(xml, "    </channel>").unwrap();
+            for _ in 0..2 {
+                writeln!(xml, "    <redirdev bus='usb' type='spicevmc'/>").unwrap();
+            }
+        }
 
+        // VGA 图形 - 根据操作系统类型优化，SPICE 一律使用 qxl
         writeln!(xml, "    <video>").unwrap();
-        if config.os_type == "windows" {
+        if config.os_type == "windows" && config.graphics == GraphicsType::Vnc {
             // Windows 优化：使用 cirrus 显卡，更好的兼容性
             writeln!(xml, "      <model type='cirrus' vram='16384' heads='1' primary='yes'/>").unwrap();
         } else {
@@ -284,17 +699,9 @@ impl HypervisorManager {
 
         tracing::info!("🚀 启动虚拟机: {}", vm_id);
 
-        let conn = self.conn.lock().await;
+        let conn = self.connection().await?;
 
-        // 通过 UUID 或名称查找虚拟机
-        let domain = match virt::domain::Domain::lookup_by_uuid_string(&conn, vm_id) {
-            Ok(dom) => dom,
-            Err(_) => {
-                // 如果通过 UUID 查找失败，尝试通过名称查找
-                virt::domain::Domain::lookup_by_name(&conn, vm_id)
-                    .map_err(|e| common::Error::NotFound(format!("虚拟机不存在: {} ({})", vm_id, e)))?
-            }
-        };
+        let domain = lookup_domain(&conn, vm_id)?;
 
         // 检查虚拟机当前状态
         let (state, _reason) = domain.get_state()
@@ -329,10 +736,20 @@ impl HypervisorManager {
     pub async fn start_vm_with_config(&self, vm_id: &str, config: &VMConfig) -> Result<()> {
         tracing::info!("🚀 根据配置重新定义并启动虚拟机: {}", vm_id);
 
-        let conn = self.conn.lock().await;
+        let conn = self.connection().await?;
 
         // 检查虚拟机是否已存在
-        if let Ok(domain) = virt::domain::Domain::lookup_by_uuid_string(&conn, vm_id) {
+        if let Ok(domain) = lookup_domain(&conn, vm_id) {
+            // 存在 managed save 镜像时直接启动以恢复挂起前的状态，
+            // 此时重新定义会被 libvirt 拒绝，且内存状态依赖原有配置
+            if domain.has_managed_save(0).unwrap_or(false) {
+                tracing::info!("虚拟机 {} 存在挂起镜像，从挂起状态恢复", vm_id);
+                domain.create()
+                    .map_err(|e| common::Error::Internal(format!("无法恢复挂起的虚拟机: {}", e)))?;
+                tracing::info!("✅ 虚拟机 {} 已从挂起状态恢复", vm_id);
+                return Ok(());
+            }
+
             // 如果虚拟机已存在，先删除旧定义
             tracing::info!("虚拟机 {} 已存在，先删除旧定义", vm_id);
             let (state, _reason) = domain.get_state()
@@ -346,21 +763,30 @@ impl HypervisorManager {
             }
 
             // 删除虚拟机定义
-            domain.undefine()
+            undefine_keep_nvram(&domain)
                 .map_err(|e| common::Error::Internal(format!("无法删除虚拟机定义: {}", e)))?;
         }
 
-        // 生成新的虚拟机 XML 配置
-        let xml = Self::generate_vm_xml(config)?;
+        // 每次定义前重新生成 cloud-init 配置光盘，使 user-data 的修改生效；未配置时清理旧光盘
+        match &config.cloud_init {
+            Some(cloud_init) => {
+                super::cloud_init::build_config_drive(vm_id, &config.name, cloud_init).await?;
+            }
+            None => super::cloud_init::remove_config_drive(vm_id).await,
+        }
+
+        // 生成新的虚拟机 XML 配置，define 之前先落盘，失败时可直接查看
+        let config = &self.with_guest_defaults(config)?;
+        let xml = Self::domain_xml(config)?;
         tracing::info!("虚拟机 XML 配置:\n{}", xml);
+        persist_domain_xml(vm_id, &xml).await;
 
         // 重新定义虚拟机
         let _domain = virt::domain::Domain::define_xml(&conn, &xml)
             .map_err(|e| common::Error::Internal(format!("无法定义虚拟机: {}", e)))?;
 
         // 启动虚拟机
-        let domain = virt::domain::Domain::lookup_by_uuid_string(&conn, vm_id)
-            .map_err(|e| common::Error::Internal(format!("无法查找虚拟机: {}", e)))?;
+        let domain = lookup_domain(&conn, vm_id)?;
 
         domain.create()
             .map_err(|e| common::Error::Internal(format!("无法启动虚拟机: {}", e)))?;
@@ -369,6 +795,23 @@ impl HypervisorManager {
         Ok(())
     }
 
+    /// 为加密存储卷定义 libvirt secret，UUID 与存储卷 ID 相同，重复定义时更新口令
+    pub async fn define_volume_secret(
+        &self,
+        volume_id: &str,
+        volume_path: &str,
+        passphrase: &Passphrase,
+    ) -> Result<()> {
+        let conn = self.connection().await?;
+        let secret = virt::secret::Secret::define_xml(&conn, &volume_secret_xml(volume_id, volume_path), 0)
+            .map_err(|e| common::Error::Internal(format!("定义存储卷 {} 的密钥失败: {}", volume_id, e)))?;
+        secret
+            .set_value(passphrase.as_bytes(), 0)
+            .map_err(|e| common::Error::Internal(format!("设置存储卷 {} 的密钥失败: {}", volume_id, e)))?;
+        tracing::info!("已定义存储卷 {} 的加密密钥", volume_id);
+        Ok(())
+    }
+
     /// 停止虚拟机
     pub async fn stop_vm(&self, vm_id: &str, force: bool) -> Result<()> {
         // libvirt 域状态常量
@@ -378,17 +821,9 @@ impl HypervisorManager {
 
         tracing::info!("🛑 停止虚拟机: {} (强制: {})", vm_id, force);
 
-        let conn = self.conn.lock().await;
+        let conn = self.connection().await?;
 
-        // 通过 UUID 或名称查找虚拟机
-        let domain = match virt::domain::Domain::lookup_by_uuid_string(&conn, vm_id) {
-            Ok(dom) => dom,
-            Err(_) => {
-                // 如果通过 UUID 查找失败，尝试通过名称查找
-                virt::domain::Domain::lookup_by_name(&conn, vm_id)
-                    .map_err(|e| common::Error::NotFound(format!("虚拟机不存在: {} ({})", vm_id, e)))?
-            }
-        };
+        let domain = lookup_domain(&conn, vm_id)?;
 
         // 检查虚拟机当前状态
         let (state, _reason) = domain.get_state()
@@ -405,6 +840,12 @@ impl HypervisorManager {
             return Err(common::Error::Internal(format!("虚拟机 {} 不在运行状态，无法停止", vm_id)));
         }
 
+        // 停止后删除加密磁盘的 libvirt secret，口令不在节点上保留
+        let secret_uuids = domain
+            .get_xml_desc(0)
+            .map(|xml| disk_secret_uuids(&xml))
+            .unwrap_or_default();
+
         if force {
             // 强制停止虚拟机
             tracing::info!("⚡ 强制停止虚拟机: {}", vm_id);
@@ -425,6 +866,7 @@ impl HypervisorManager {
 
                 if state == VIR_DOMAIN_SHUTOFF {
                     tracing::info!("✅ 虚拟机 {} 已优雅停止", vm_id);
+                    undefine_volume_secrets(&conn, &secret_uuids);
                     return Ok(());
                 }
             }
@@ -435,10 +877,195 @@ impl HypervisorManager {
                 .map_err(|e| common::Error::Internal(format!("无法强制停止虚拟机: {}", e)))?;
         }
 
+        undefine_volume_secrets(&conn, &secret_uuids);
         tracing::info!("✅ 虚拟机 {} 停止成功", vm_id);
         Ok(())
     }
 
+    /// 暂停虚拟机（vCPU 停止调度，内存保留在节点上），已暂停时直接返回成功
+    pub async fn pause_vm(&self, vm_id: &str) -> Result<()> {
+        const VIR_DOMAIN_RUNNING: u32 = 1;
+        const VIR_DOMAIN_PAUSED: u32 = 3;
+
+        tracing::info!("⏸️ 暂停虚拟机: {}", vm_id);
+
+        let conn = self.connection().await?;
+
+        let domain = lookup_domain(&conn, vm_id)?;
+
+        let (state, _reason) = domain.get_state()
+            .map_err(|e| common::Error::Internal(format!("无法获取虚拟机状态: {}", e)))?;
+
+        if state == VIR_DOMAIN_PAUSED {
+            tracing::info!("✅ 虚拟机 {} 已经处于暂停状态", vm_id);
+            return Ok(());
+        }
+        if state != VIR_DOMAIN_RUNNING {
+            return Err(common::Error::InvalidArgument(format!(
+                "虚拟机未运行，无法暂停 (状态: {})",
+                domain_state_name(state)
+            )));
+        }
+
+        domain.suspend()
+            .map_err(|e| common::Error::Internal(format!("无法暂停虚拟机: {}", e)))?;
+
+        tracing::info!("✅ 虚拟机 {} 已暂停", vm_id);
+        Ok(())
+    }
+
+    /// 恢复已暂停的虚拟机，已在运行时直接返回成功
+    pub async fn resume_vm(&self, vm_id: &str) -> Result<()> {
+        const VIR_DOMAIN_RUNNING: u32 = 1;
+        const VIR_DOMAIN_PAUSED: u32 = 3;
+
+        tracing::info!("▶️ 恢复虚拟机: {}", vm_id);
+
+        let conn = self.connection().await?;
+
+        let domain = lookup_domain(&conn, vm_id)?;
+
+        let (state, _reason) = domain.get_state()
+            .map_err(|e| common::Error::Internal(format!("无法获取虚拟机状态: {}", e)))?;
+
+        if state == VIR_DOMAIN_RUNNING {
+            tracing::info!("✅ 虚拟机 {} 已经在运行", vm_id);
+            return Ok(());
+        }
+        if state != VIR_DOMAIN_PAUSED {
+            return Err(common::Error::InvalidArgument(format!(
+                "虚拟机未暂停，无法恢复 (状态: {})",
+                domain_state_name(state)
+            )));
+        }
+
+        domain.resume()
+            .map_err(|e| common::Error::Internal(format!("无法恢复虚拟机: {}", e)))?;
+
+        tracing::info!("✅ 虚拟机 {} 已恢复运行", vm_id);
+        Ok(())
+    }
+
+    /// 调整虚拟机 vCPU 数量
+    ///
+    /// `live` 为 true 时同时作用于运行中的虚拟机，数量不能超过定义时预留的上限
+    pub async fn set_vcpus(&self, vm_id: &str, count: u32, live: bool) -> Result<()> {
+        tracing::info!("🔧 调整虚拟机 vCPU: vm_id={}, vcpu={}, live={}", vm_id, count, live);
+
+        let conn = self.connection().await?;
+        let domain = lookup_domain(&conn, vm_id)?;
+
+        let flags = if live {
+            VIR_DOMAIN_AFFECT_LIVE | VIR_DOMAIN_AFFECT_CONFIG
+        } else {
+            VIR_DOMAIN_AFFECT_CONFIG
+        };
+        domain
+            .set_vcpus_flags(count, flags)
+            .map_err(|e| common::Error::Internal(format!("调整 vCPU 失败: {}", e)))?;
+
+        tracing::info!("✅ 虚拟机 {} vCPU 已调整为 {}", vm_id, count);
+        Ok(())
+    }
+
+    /// 调整虚拟机内存
+    ///
+    /// 运行中的虚拟机通过 balloon 调整，不能超过定义时的内存上限，客户机需加载 virtio-balloon 驱动
+    pub async fn set_memory(&self, vm_id: &str, memory_mb: u64, live: bool) -> Result<()> {
+        tracing::info!("🔧 调整虚拟机内存: vm_id={}, memory={}MiB, live={}", vm_id, memory_mb, live);
+
+        let conn = self.connection().await?;
+        let domain = lookup_domain(&conn, vm_id)?;
+
+        let flags = if live {
+            VIR_DOMAIN_AFFECT_LIVE | VIR_DOMAIN_AFFECT_CONFIG
+        } else {
+            VIR_DOMAIN_AFFECT_CONFIG
+        };
+        // libvirt 内存单位为 KiB
+        domain
+            .set_memory_flags(memory_mb * 1024, flags)
+            .map_err(|e| common::Error::Internal(format!("调整内存失败: {}", e)))?;
+
+        tracing::info!("✅ 虚拟机 {} 内存已调整为 {}MiB", vm_id, memory_mb);
+        Ok(())
+    }
+
+    /// 调整磁盘设备的 I/O 限速，同时写入持久化定义
+    ///
+    /// `device` 为域内的目标设备名（如 vdb），未设置的限速项按 0 下发，即取消限速
+    pub async fn set_block_iotune(&self, vm_id: &str, device: &str, limits: &DiskIoLimits) -> Result<()> {
+        tracing::info!("🔧 调整磁盘 I/O 限速: vm_id={}, device={}, limits={:?}", vm_id, device, limits);
+
+        let conn = self.connection().await?;
+        let domain = lookup_domain(&conn, vm_id)?;
+
+        super::ffi::set_block_io_tune(
+            &domain,
+            device,
+            limits.iops_limit.unwrap_or(0),
+            limits.bps_limit.unwrap_or(0),
+            VIR_DOMAIN_AFFECT_LIVE | VIR_DOMAIN_AFFECT_CONFIG,
+        )
+        .map_err(|e| match e {
+            common::Error::Hypervisor(e) => common::Error::Internal(format!("调整磁盘 I/O 限速失败: {}", e)),
+            e => e,
+        })?;
+
+        tracing::info!("✅ 虚拟机 {} 磁盘 {} I/O 限速已更新", vm_id, device);
+        Ok(())
+    }
+
+    /// 按存储卷 ID 调整运行中虚拟机的磁盘 I/O 限速
+    pub async fn set_volume_iotune(&self, vm_id: &str, volume_id: &str, limits: &DiskIoLimits) -> Result<()> {
+        let device = {
+            let conn = self.connection().await?;
+            let domain = lookup_domain(&conn, vm_id)?;
+            let xml = domain
+                .get_xml_desc(0)
+                .map_err(|e| common::Error::Internal(format!("获取虚拟机XML失败: {}", e)))?;
+            find_disk_target_by_volume_id(&xml, volume_id)?
+                .ok_or_else(|| common::Error::NotFound(format!("未找到存储卷: {}", volume_id)))?
+        };
+
+        self.set_block_iotune(vm_id, &device, limits).await
+    }
+
+    /// 查找运行中虚拟机网卡（按 MAC 地址定位）的 tap 设备名，虚拟机未运行或网卡不存在时返回 None
+    pub async fn interface_target(&self, vm_id: &str, mac_address: &str) -> Result<Option<String>> {
+        let conn = self.connection().await?;
+        let domain = lookup_domain(&conn, vm_id)?;
+        let xml = domain
+            .get_xml_desc(0)
+            .map_err(|e| common::Error::Internal(format!("获取虚拟机XML失败: {}", e)))?;
+        find_interface_target_by_mac(&xml, mac_address)
+    }
+
+    /// 调整网卡的带宽限速，同时写入持久化定义
+    ///
+    /// 网卡按 MAC 地址定位，未设置的方向按 0 下发，即取消限速
+    pub async fn set_interface_bandwidth(&self, vm_id: &str, mac_address: &str, limits: &NicBandwidth) -> Result<()> {
+        tracing::info!("🔧 调整网卡带宽限速: vm_id={}, mac={}, limits={:?}", vm_id, mac_address, limits);
+
+        let conn = self.connection().await?;
+        let domain = lookup_domain(&conn, vm_id)?;
+
+        super::ffi::set_interface_bandwidth(
+            &domain,
+            mac_address,
+            limits.inbound_kbps.unwrap_or(0),
+            limits.outbound_kbps.unwrap_or(0),
+            VIR_DOMAIN_AFFECT_LIVE | VIR_DOMAIN_AFFECT_CONFIG,
+        )
+        .map_err(|e| match e {
+            common::Error::Hypervisor(e) => common::Error::Internal(format!("调整网卡带宽限速失败: {}", e)),
+            e => e,
+        })?;
+
+        tracing::info!("✅ 虚拟机 {} 网卡 {} 带宽限速已更新", vm_id, mac_address);
+        Ok(())
+    }
+
     /// 取消定义虚拟机（用于冷迁移）
     ///
     /// 从节点上移除虚拟机定义，但不删除磁盘文件
@@ -446,21 +1073,15 @@ impl HypervisorManager {
     pub async fn undefine_vm(&self, vm_id: &str) -> Result<()> {
         tracing::info!("🗑️ 取消定义虚拟机: {}", vm_id);
 
-        let conn = self.conn.lock().await;
+        let conn = self.connection().await?;
 
         // 查找虚拟机
-        let domain = match virt::domain::Domain::lookup_by_uuid_string(&conn, vm_id) {
+        let domain = match lookup_domain(&conn, vm_id) {
             Ok(dom) => dom,
             Err(_) => {
-                // 如果通过 UUID 查找失败，尝试通过名称查找
-                match virt::domain::Domain::lookup_by_name(&conn, vm_id) {
-                    Ok(dom) => dom,
-                    Err(_) => {
-                        // 虚拟机不存在，按最终一致性处理，视为已达成目标
-                        tracing::info!("ℹ️ 虚拟机 {} 不存在，已按成功处理", vm_id);
-                        return Ok(());
-                    }
-                }
+                // 虚拟机不存在，按最终一致性处理，视为已达成目标
+                tracing::info!("ℹ️ 虚拟机 {} 不存在，已按成功处理", vm_id);
+                return Ok(());
             }
         };
 
@@ -480,8 +1101,7 @@ impl HypervisorManager {
         }
 
         // 取消定义虚拟机（不删除存储）
-        domain
-            .undefine()
+        undefine_keep_nvram(&domain)
             .map_err(|e| common::Error::Internal(format!("取消定义虚拟机失败: {}", e)))?;
 
         tracing::info!("✅ 虚拟机 {} 已取消定义", vm_id);
@@ -497,19 +1117,16 @@ impl HypervisorManager {
         bus_type: DiskBusType,
         device_type: DiskDeviceType,
         format: &str,
+        limits: DiskIoLimits,
+        backing: Option<&BackingStore>,
+        encrypted: bool,
     ) -> Result<String> {
         tracing::info!("🔗 挂载存储卷: vm_id={}, volume_id={}, path={}", vm_id, volume_id, volume_path);
 
-        let conn = self.conn.lock().await;
+        let conn = self.connection().await?;
 
         // 查找虚拟机
-        let domain = if let Ok(domain) = virt::domain::Domain::lookup_by_uuid_string(&conn, vm_id) {
-            domain
-        } else if let Ok(domain) = virt::domain::Domain::lookup_by_name(&conn, vm_id) {
-            domain
-        } else {
-            return Err(common::Error::NotFound(format!("虚拟机不存在: {}", vm_id)));
-        };
+        let domain = lookup_domain(&conn, vm_id)?;
 
         // 检查虚拟机状态
         let (state, _reason) = domain.get_state()
@@ -526,8 +1143,15 @@ impl HypervisorManager {
         }
         tracing::info!("虚拟机状态: {} (运行中: true)", state);
 
+        // QEMU 的 AHCI 控制器不支持热插拔
+        if bus_type == DiskBusType::Sata {
+            return Err(common::Error::InvalidArgument(
+                "SATA 总线不支持热插拔，请关机后通过更新虚拟机配置添加磁盘".to_string(),
+            ));
+        }
+
         // 获取当前磁盘设备列表，确定下一个设备名
-        let device_name = self.get_next_disk_device(&domain).await?;
+        let device_name = self.get_next_disk_device(&domain, &bus_type, &device_type).await?;
 
         // 构建磁盘XML配置
         let disk_xml = self.build_disk_xml(
@@ -537,6 +1161,9 @@ impl HypervisorManager {
             device_type,
             format,
             volume_id,
+            &limits,
+            backing,
+            encrypted,
         )?;
 
         tracing::debug!("磁盘XML配置: {}", disk_xml);
@@ -558,16 +1185,10 @@ impl HypervisorManager {
     ) -> Result<()> {
         tracing::info!("🔌 分离存储卷: vm_id={}, volume_id={}", vm_id, volume_id);
 
-        let conn = self.conn.lock().await;
+        let conn = self.connection().await?;
 
         // 查找虚拟机
-        let domain = if let Ok(domain) = virt::domain::Domain::lookup_by_uuid_string(&conn, vm_id) {
-            domain
-        } else if let Ok(domain) = virt::domain::Domain::lookup_by_name(&conn, vm_id) {
-            domain
-        } else {
-            return Err(common::Error::NotFound(format!("虚拟机不存在: {}", vm_id)));
-        };
+        let domain = lookup_domain(&conn, vm_id)?;
 
         // 检查虚拟机状态
         let (state, _reason) = domain.get_state()
@@ -599,6 +1220,9 @@ impl HypervisorManager {
                     .map_err(|e| common::Error::Internal(format!("分离存储卷失败: {}", e)))?;
 
                 tracing::info!("✅ 存储卷分离成功: vm_id={}, volume_id={}", vm_id, volume_id);
+                if disk_secret_uuids(&xml).iter().any(|uuid| uuid == volume_id) {
+                    undefine_volume_secrets(&conn, &[volume_id.to_string()]);
+                }
             }
             Err(common::Error::NotFound(_)) => {
                 // 存储卷不存在，直接返回成功（最终一致性）
@@ -614,7 +1238,12 @@ impl HypervisorManager {
     }
 
     /// 获取下一个可用的磁盘设备名
-    async fn get_next_disk_device(&self, domain: &virt::domain::Domain) -> Result<String> {
+    async fn get_next_disk_device(
+        &self,
+        domain: &virt::domain::Domain,
+        bus_type: &DiskBusType,
+        device_type: &DiskDeviceType,
+    ) -> Result<String> {
         // 获取虚拟机XML配置
         let xml = domain.get_xml_desc(0)
             .map_err(|e| common::Error::Internal(format!("获取虚拟机XML失败: {}", e)))?;
@@ -622,9 +1251,9 @@ impl HypervisorManager {
         // 解析XML，查找已使用的磁盘设备
         let used_devices = self.parse_disk_devices(&xml)?;
 
-        // 生成下一个设备名 (vda, vdb, vdc, ...)
+        // 按总线生成下一个设备名 (vda/sda/hda, ...)
         for i in 0..26 {
-            let device = format!("vd{}", (b'a' + i as u8) as char);
+            let device = disk_device_name(bus_type, device_type, i);
             if !used_devices.contains(&device) {
                 return Ok(device);
             }
@@ -665,12 +1294,11 @@ impl HypervisorManager {
         device_type: DiskDeviceType,
         format: &str,
         volume_id: &str,
+        limits: &DiskIoLimits,
+        backing: Option<&BackingStore>,
+        encrypted: bool,
     ) -> Result<String> {
-        let bus_str = match bus_type {
-            DiskBusType::Virtio => "virtio",
-            DiskBusType::Scsi => "scsi",
-            DiskBusType::Ide => "ide",
-        };
+        let bus_str = bus_type.as_str();
 
         let device_str = match device_type {
             DiskDeviceType::Disk => "disk",
@@ -681,10 +1309,21 @@ impl HypervisorManager {
             r#"<disk type="file" device="{}">
                 <driver name="qemu" type="{}"/>
                 <source file="{}"/>
+                {}
+                {}
                 <target dev="{}" bus="{}"/>
                 <serial>{}</serial>
+                {}
             </disk>"#,
-            device_str, format, volume_path, device_name, bus_str, volume_id
+            device_str,
+            format,
+            volume_path,
+            backing_store_xml(backing).unwrap_or_default(),
+            if encrypted { encryption_xml(volume_id) } else { String::new() },
+            device_name,
+            bus_str,
+            volume_id,
+            iotune_xml(limits).unwrap_or_default()
         );
 
         Ok(xml)
@@ -747,20 +1386,20 @@ impl HypervisorManager {
     /// 执行虚拟机热迁移
     ///
     /// # 参数
-    /// - vm_id: 虚拟机 ID 或名称
+    /// - vm_id: 虚拟机 ID（即 libvirt 域 UUID）
     /// - target_uri: 目标节点的 libvirt URI，例如: "qemu+ssh://user@target-host/system"
-    /// - flags: 迁移选项标志
+    /// - options: 迁移标志、带宽/停机时间限制及超时降级设置
     ///
     /// # 返回
-    /// - Ok(()) 表示迁移成功启动
+    /// - Ok(mode) 迁移完成，mode 表示是否在超时后挂起虚拟机完成
     /// - Err 表示迁移失败
     pub async fn live_migrate(
         &self,
         vm_id: &str,
         target_uri: &str,
-        flags: Option<u32>,
-    ) -> Result<()> {
-        let conn = self.conn.lock().await;
+        options: &MigrationOptions,
+    ) -> Result<MigrationMode> {
+        let conn = self.connection().await?;
 
         tracing::info!(
             "🔄 开始热迁移虚拟机: vm_id={}, target={}",
@@ -769,9 +1408,7 @@ impl HypervisorManager {
         );
 
         // 查找虚拟机
-        let domain = virt::domain::Domain::lookup_by_name(&conn, vm_id)
-            .or_else(|_| virt::domain::Domain::lookup_by_uuid_string(&conn, vm_id))
-            .map_err(|e| common::Error::NotFound(format!("虚拟机不存在: {}", e)))?;
+        let domain = lookup_domain(&conn, vm_id)?;
 
         // 检查虚拟机状态
         let state = domain
@@ -786,62 +1423,101 @@ impl HypervisorManager {
             )));
         }
 
-        // 设置迁移标志
-        // VIR_MIGRATE_LIVE = 1 (热迁移)
-        // VIR_MIGRATE_PEER2PEER = 2 (点对点迁移，源和目标直接通信)
-        // VIR_MIGRATE_TUNNELLED = 4 (通过 libvirtd 隧道传输)
-        // VIR_MIGRATE_PERSIST_DEST = 8 (在目标节点持久化定义)
-        // VIR_MIGRATE_UNDEFINE_SOURCE = 16 (迁移后在源节点取消定义)
-        // VIR_MIGRATE_COMPRESSED = 2048 (压缩传输数据)
-        // VIR_MIGRATE_AUTO_CONVERGE = 8192 (自动收敛，在迁移困难时降低 CPU)
-        // VIR_MIGRATE_NON_SHARED_DISK = 64 (非共享磁盘，需要迁移存储，不设置此标志表示共享存储，不迁移存储)
-        // VIR_MIGRATE_UNSAFE = 512 (允许不安全迁移，用于共享存储但 libvirt 无法自动检测的场景)
-        // 注意：后台使用共享存储，因此：
-        // 1. 不设置 VIR_MIGRATE_NON_SHARED_DISK (64)，表示共享存储，不迁移存储
-        // 2. 设置 VIR_MIGRATE_UNSAFE (512)，绕过 libvirt 的安全检查（在共享存储场景下是安全的）
-        let migrate_flags = flags.unwrap_or(
-            1 |    // VIR_MIGRATE_LIVE
-            2 |    // VIR_MIGRATE_PEER2PEER
-            8 |    // VIR_MIGRATE_PERSIST_DEST
-            16 |   // VIR_MIGRATE_UNDEFINE_SOURCE
-            512 |  // VIR_MIGRATE_UNSAFE (允许共享存储迁移，绕过安全检查)
-            2048 | // VIR_MIGRATE_COMPRESSED
-            8192, // VIR_MIGRATE_AUTO_CONVERGE
-            // 不设置 VIR_MIGRATE_NON_SHARED_DISK (64)，表示共享存储，不迁移存储
-        );
+        let migrate_flags = options
+            .flags
+            .unwrap_or_else(|| default_migration_flags(options.storage_mode));
 
         tracing::info!("🔧 迁移标志: 0x{:x}", migrate_flags);
 
-        // 执行迁移
+        // 停机时间目标需在迁移开始前设置，QEMU 在最终切换阶段据此决定何时暂停虚拟机
+        if let Some(downtime_ms) = options.max_downtime_ms {
+            domain
+                .migrate_set_max_downtime(downtime_ms, 0)
+                .map_err(|e| common::Error::Internal(format!("设置最大停机时间失败: {}", e)))?;
+            tracing::info!("🔧 最大停机时间: {} ms", downtime_ms);
+        }
+
+        // 迁移调用会阻塞到完成为止，超时降级由独立线程负责
+        let finished = Arc::new(AtomicBool::new(false));
+        let suspended = Arc::new(AtomicBool::new(false));
+        let watcher = options.suspend_after.map(|timeout| {
+            tracing::info!("🔧 热迁移超过 {:?} 未完成时将挂起虚拟机", timeout);
+            spawn_suspend_watcher(vm_id.to_string(), timeout, finished.clone(), suspended.clone())
+        });
+
+        // 执行迁移
         // 注意：这是阻塞调用，可能需要较长时间
-        // 使用简化版本的 migrate 方法
-        match domain.migrate(&conn, migrate_flags, None, Some(target_uri), 0) {
+        // 带宽上限由调用方事先通过 set_migration_speed 设置，这里传 0 表示沿用已设置的上限
+        let result = domain.migrate(&conn, migrate_flags, None, Some(target_uri), 0);
+
+        finished.store(true, Ordering::SeqCst);
+        if let Some(watcher) = watcher {
+            let _ = watcher.join();
+        }
+
+        let mode = if suspended.load(Ordering::SeqCst) {
+            MigrationMode::Suspended
+        } else {
+            MigrationMode::Live
+        };
+
+        match result {
             Ok(_) => {
-                tracing::info!("✅ 虚拟机热迁移成功: vm_id={}", vm_id);
-                Ok(())
+                tracing::info!("✅ 虚拟机热迁移成功: vm_id={}, mode={}", vm_id, mode.as_str());
+                Ok(mode)
             }
             Err(e) => {
                 tracing::error!("❌ 虚拟机热迁移失败: vm_id={}, error={}", vm_id, e);
+                // 迁移失败时虚拟机仍在源节点，被降级挂起的需要恢复运行
+                if mode == MigrationMode::Suspended {
+                    match domain.resume() {
+                        Ok(_) => tracing::info!("▶️ 已恢复源节点上被挂起的虚拟机: {}", vm_id),
+                        Err(e) => tracing::error!("恢复源节点虚拟机失败: vm_id={}, error={}", vm_id, e),
+                    }
+                }
                 Err(common::Error::Internal(format!("热迁移失败: {}", e)))
             }
         }
     }
 
+    /// 设置虚拟机的迁移带宽上限（MiB/s），迁移进行中调用会立即生效
+    ///
+    /// 低于 MIN_MIGRATION_SPEED_MBPS 的值按下限处理，返回实际生效的上限
+    pub async fn set_migration_speed(&self, vm_id: &str, mbps: u64) -> Result<u64> {
+        let conn = self.connection().await?;
+        let domain = lookup_domain(&conn, vm_id)?;
+
+        let mbps = mbps.max(MIN_MIGRATION_SPEED_MBPS);
+        domain
+            .migrate_set_max_speed(mbps, 0)
+            .map_err(|e| common::Error::Internal(format!("设置迁移带宽上限失败: {}", e)))?;
+        Ok(mbps)
+    }
+
+    /// 查询虚拟机当前的迁移带宽上限（MiB/s），0 表示不限速
+    pub async fn get_migration_speed(&self, vm_id: &str) -> Result<u64> {
+        let conn = self.connection().await?;
+        let domain = lookup_domain(&conn, vm_id)?;
+
+        let mbps = domain
+            .migrate_get_max_speed(0)
+            .map_err(|e| common::Error::Internal(format!("查询迁移带宽上限失败: {}", e)))?;
+        Ok(if mbps >= UNLIMITED_MIGRATION_SPEED_MBPS { 0 } else { mbps })
+    }
+
     /// 获取虚拟机迁移进度信息
     ///
     /// # 参数
-    /// - vm_id: 虚拟机 ID 或名称
+    /// - vm_id: 虚拟机 ID（即 libvirt 域 UUID）
     ///
     /// # 返回
     /// - Ok((progress, remaining_time)) 进度百分比和剩余时间(秒)
     /// - Err 表示获取失败
     pub async fn get_migration_progress(&self, vm_id: &str) -> Result<(f64, u64)> {
-        let conn = self.conn.lock().await;
+        let conn = self.connection().await?;
 
         // 查找虚拟机
-        let domain = virt::domain::Domain::lookup_by_name(&conn, vm_id)
-            .or_else(|_| virt::domain::Domain::lookup_by_uuid_string(&conn, vm_id))
-            .map_err(|e| common::Error::NotFound(format!("虚拟机不存在: {}", e)))?;
+        let domain = lookup_domain(&conn, vm_id)?;
 
         // 获取作业信息
         match domain.get_job_info() {
@@ -868,56 +1544,1061 @@ impl HypervisorManager {
         }
     }
 
+    /// 通过 qemu-guest-agent 启动客户机内命令（guest-exec），返回客户机内进程 PID
+    pub async fn guest_exec(&self, vm_id: &str, command: &str, args: &[String]) -> Result<i64> {
+        tracing::info!("💻 执行客户机命令: vm_id={}, command={}", vm_id, command);
+
+        let cmd = serde_json::json!({
+            "execute": "guest-exec",
+            "arguments": {
+                "path": command,
+                "arg": args,
+                "capture-output": true
+            }
+        });
+
+        let ret = self.guest_agent_command(vm_id, &cmd).await?;
+        ret["pid"].as_i64().ok_or_else(|| {
+            common::Error::Internal(format!("guest-exec 返回结果缺少 pid: {}", ret))
+        })
+    }
+
+    /// 查询 guest-exec 启动的进程状态（guest-exec-status）
+    pub async fn guest_exec_status(&self, vm_id: &str, pid: i64) -> Result<GuestExecStatus> {
+        use base64::Engine;
+
+        let cmd = serde_json::json!({
+            "execute": "guest-exec-status",
+            "arguments": { "pid": pid }
+        });
+
+        let ret = self.guest_agent_command(vm_id, &cmd).await?;
+
+        let decode = |field: &str| -> String {
+            ret[field]
+                .as_str()
+                .and_then(|data| base64::engine::general_purpose::STANDARD.decode(data).ok())
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                .unwrap_or_default()
+        };
+
+        Ok(GuestExecStatus {
+            exited: ret["exited"].as_bool().unwrap_or(false),
+            exit_code: ret["exitcode"].as_i64().map(|c| c as i32),
+            signal: ret["signal"].as_i64().map(|s| s as i32),
+            stdout: decode("out-data"),
+            stderr: decode("err-data"),
+            truncated: ret["out-truncated"].as_bool().unwrap_or(false)
+                || ret["err-truncated"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// 通过 qemu-guest-agent 查询客户机内的网卡及 IP 地址（guest-network-get-interfaces）
+    ///
+    /// 可以拿到客户机实际配置的地址，而不只是 DHCP 预留的地址
+    pub async fn qga_get_interfaces(&self, vm_id: &str) -> Result<Vec<GuestNetworkInterface>> {
+        let cmd = serde_json::json!({ "execute": "guest-network-get-interfaces" });
+        let ret = self.guest_agent_command(vm_id, &cmd).await?;
+        Ok(parse_guest_interfaces(&ret))
+    }
+
+    /// 通过 qemu-guest-agent 冻结客户机内的文件系统（guest-fsfreeze-freeze）
+    ///
+    /// 冻结期间客户机内的写入会被阻塞，调用方必须保证之后调用 `fsthaw`
+    pub async fn fsfreeze(&self, vm_id: &str) -> Result<()> {
+        let cmd = serde_json::json!({ "execute": "guest-fsfreeze-freeze" });
+        let ret = self.guest_agent_command(vm_id, &cmd).await?;
+        tracing::info!("🧊 已冻结虚拟机 {} 的 {} 个文件系统", vm_id, ret.as_i64().unwrap_or(0));
+        Ok(())
+    }
+
+    /// 解冻客户机内的文件系统（guest-fsfreeze-thaw）
+    pub async fn fsthaw(&self, vm_id: &str) -> Result<()> {
+        let cmd = serde_json::json!({ "execute": "guest-fsfreeze-thaw" });
+        let ret = self.guest_agent_command(vm_id, &cmd).await?;
+        tracing::info!("已解冻虚拟机 {} 的 {} 个文件系统", vm_id, ret.as_i64().unwrap_or(0));
+        Ok(())
+    }
+
+    /// 向 qemu-guest-agent 发送命令，返回结果中的 `return` 字段
+    ///
+    /// 命令本身发送失败（未安装、未启动或无响应）时返回 `GuestAgentUnavailable`
+    async fn guest_agent_command(
+        &self,
+        vm_id: &str,
+        cmd: &serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        // guest agent 无响应时的等待时间（秒）
+        const GUEST_AGENT_TIMEOUT_SECS: i32 = 10;
+
+        let conn = self.connection().await?;
+
+        let domain = lookup_domain(&conn, vm_id)?;
+
+        let output = super::ffi::qemu_agent_command(&domain, &cmd.to_string(), GUEST_AGENT_TIMEOUT_SECS, 0)
+            .map_err(|e| match e {
+                common::Error::Hypervisor(e) => common::Error::GuestAgentUnavailable(format!(
+                    "guest agent 命令执行失败（请确认客户机内 qemu-guest-agent 已运行）: {}",
+                    e
+                )),
+                e => e,
+            })?;
+
+        let value: serde_json::Value = serde_json::from_str(&output).map_err(|e| {
+            common::Error::Internal(format!("解析 guest agent 响应失败: {}", e))
+        })?;
+
+        Ok(value["return"].clone())
+    }
+
+    /// 列出当前运行中（含暂停）的虚拟机，返回 (UUID, 名称)
+    pub async fn list_active_vms(&self) -> Result<Vec<(String, String)>> {
+        let conn = self.connection().await?;
+
+        let domains = conn
+            .list_all_domains(virt::sys::VIR_CONNECT_LIST_DOMAINS_ACTIVE)
+            .map_err(|e| common::Error::Internal(format!("无法列出虚拟机: {}", e)))?;
+
+        let mut vms = Vec::with_capacity(domains.len());
+        for domain in domains {
+            let uuid = domain
+                .get_uuid_string()
+                .map_err(|e| common::Error::Internal(format!("无法获取虚拟机 UUID: {}", e)))?;
+            let name = domain.get_name().unwrap_or_else(|_| uuid.clone());
+            vms.push((uuid, name));
+        }
+
+        Ok(vms)
+    }
+
+    /// 列出节点上所有已定义的虚拟机及其实时状态
+    pub async fn list_vms(&self) -> Result<Vec<VMInfo>> {
+        let conn = self.connection().await?;
+
+        let domains = conn
+            .list_all_domains(0)
+            .map_err(|e| common::Error::Internal(format!("无法列出虚拟机: {}", e)))?;
+
+        let mut vms = Vec::with_capacity(domains.len());
+        for domain in domains {
+            let uuid = match domain.get_uuid_string() {
+                Ok(uuid) => uuid,
+                Err(_) => continue,
+            };
+            // 域可能在枚举后被并发取消定义，跳过即可
+            let info = match domain.get_info() {
+                Ok(info) => info,
+                Err(e) => {
+                    tracing::warn!("获取虚拟机 {} 信息失败，已跳过: {}", uuid, e);
+                    continue;
+                }
+            };
+            let name = domain.get_name().unwrap_or_else(|_| uuid.clone());
+            vms.push(VMInfo {
+                id: uuid,
+                name,
+                state: domain_state_name(info.state).to_string(),
+                vcpu: info.nr_virt_cpu,
+                memory_mb: info.memory / 1024,
+            });
+        }
+
+        Ok(vms)
+    }
+
+    /// 挂起虚拟机到磁盘（managed save）
+    ///
+    /// 内存状态保存到 libvirt 管理的镜像中，下次启动时自动恢复
+    pub async fn managed_save_vm(&self, vm_id: &str) -> Result<()> {
+        tracing::info!("💾 挂起虚拟机到磁盘: {}", vm_id);
+
+        let conn = self.connection().await?;
+
+        let domain = lookup_domain(&conn, vm_id)?;
+
+        domain
+            .managed_save(0)
+            .map_err(|e| common::Error::Internal(format!("挂起虚拟机失败: {}", e)))?;
+
+        tracing::info!("✅ 虚拟机 {} 已挂起到磁盘", vm_id);
+        Ok(())
+    }
+
+    /// 列出所有已定义虚拟机的恢复状态
+    pub async fn list_restore_states(&self) -> Result<Vec<(String, String, bool)>> {
+        let conn = self.connection().await?;
+
+        let domains = conn
+            .list_all_domains(0)
+            .map_err(|e| common::Error::Internal(format!("无法列出虚拟机: {}", e)))?;
+
+        let mut states = Vec::with_capacity(domains.len());
+        for domain in domains {
+            let uuid = match domain.get_uuid_string() {
+                Ok(uuid) => uuid,
+                Err(_) => continue,
+            };
+            let name = domain.get_name().unwrap_or_else(|_| uuid.clone());
+            let has_managed_save = domain.has_managed_save(0).unwrap_or(false);
+            states.push((uuid, name, has_managed_save));
+        }
+
+        Ok(states)
+    }
+
     /// 取消正在进行的虚拟机迁移
     ///
+    /// 迁移期间主连接被阻塞中的 migrate 调用占用，这里使用独立连接中止域上的当前作业；
+    /// 作业中止后源节点的 migrate 调用以失败返回，虚拟机继续在源节点运行
+    ///
     /// # 参数
-    /// - vm_id: 虚拟机 ID 或名称
+    /// - vm_id: 虚拟机 ID（即 libvirt 域 UUID）
     ///
     /// # 返回
     /// - Ok(()) 表示取消成功
     /// - Err 表示取消失败
     pub async fn abort_migration(&self, vm_id: &str) -> Result<()> {
-        let _conn = self.conn.lock().await;
-
         tracing::info!("⏹️ 取消虚拟机迁移: vm_id={}", vm_id);
 
-        // 尝试中止当前作业
-        // 注意：libvirt Rust 绑定可能不支持此方法，返回未实现错误
-        tracing::warn!("⚠️ 取消迁移功能暂不支持，请等待迁移完成");
-        Err(common::Error::Internal("取消迁移功能暂不支持".to_string()))
+        let mut conn = Connect::open(Some(LIBVIRT_URI))
+            .map_err(|e| common::Error::Internal(format!("无法连接 libvirt: {}", e)))?;
+
+        let result = lookup_domain(&conn, vm_id).and_then(|domain| {
+            super::ffi::abort_job(&domain).map_err(|e| match e {
+                common::Error::Hypervisor(e) => common::Error::Internal(format!("取消迁移失败: {}", e)),
+                e => e,
+            })
+        });
+        let _ = conn.close();
+        result?;
+
+        tracing::info!("✅ 已中止虚拟机 {} 的迁移作业", vm_id);
+        Ok(())
+    }
+}
+
+/// 按虚拟机 ID 查找 libvirt 域
+///
+/// 虚拟机 ID 即定义时写入 `<uuid>` 的域 UUID，是唯一的查找依据；
+/// 域名称来自用户填写的虚拟机名称，可能重复，不用于查找
+fn lookup_domain(conn: &Connect, vm_id: &str) -> Result<virt::domain::Domain> {
+    virt::domain::Domain::lookup_by_uuid_string(conn, vm_id)
+        .map_err(|e| common::Error::NotFound(format!("虚拟机不存在: {} ({})", vm_id, e)))
+}
+
+/// 热迁移的默认 libvirt 迁移标志
+///
+/// - VIR_MIGRATE_LIVE = 1 (热迁移)
+/// - VIR_MIGRATE_PEER2PEER = 2 (点对点迁移，源和目标直接通信)
+/// - VIR_MIGRATE_PERSIST_DEST = 8 (在目标节点持久化定义)
+/// - VIR_MIGRATE_UNDEFINE_SOURCE = 16 (迁移后在源节点取消定义)
+/// - VIR_MIGRATE_NON_SHARED_DISK = 64 (非共享磁盘，随内存一起复制磁盘数据)
+/// - VIR_MIGRATE_UNSAFE = 512 (允许不安全迁移，用于共享存储但 libvirt 无法自动检测的场景)
+/// - VIR_MIGRATE_COMPRESSED = 2048 (压缩传输数据)
+/// - VIR_MIGRATE_AUTO_CONVERGE = 8192 (自动收敛，在迁移困难时降低 CPU)
+///
+/// 共享存储不复制磁盘，并设置 UNSAFE 绕过 libvirt 对磁盘缓存模式的检查；
+/// 复制存储设置 NON_SHARED_DISK，目标节点需已存在同路径、同尺寸的空白卷，
+/// 此时磁盘不共享，不需要也不应设置 UNSAFE
+fn default_migration_flags(storage_mode: MigrationStorageMode) -> u32 {
+    let base = 1 | 2 | 8 | 16 | 2048 | 8192;
+    match storage_mode {
+        MigrationStorageMode::SharedStorage => base | 512,
+        MigrationStorageMode::CopyStorage => base | 64,
+    }
+}
+
+/// 热迁移参数
+#[derive(Debug, Clone, Default)]
+pub struct MigrationOptions {
+    /// 覆盖默认迁移标志
+    pub flags: Option<u32>,
+    /// 磁盘位于共享存储还是需要随迁移复制，决定默认迁移标志
+    pub storage_mode: MigrationStorageMode,
+    /// 最大停机时间（毫秒）
+    pub max_downtime_ms: Option<u64>,
+    /// 超过该时长仍未完成时挂起虚拟机，以暂停状态完成剩余拷贝
+    pub suspend_after: Option<Duration>,
+}
+
+/// 启动超时降级线程：到期时迁移仍未结束则挂起虚拟机
+///
+/// 迁移期间主连接被阻塞的 migrate 调用占用，因此使用独立的 libvirt 连接
+fn spawn_suspend_watcher(
+    vm_id: String,
+    timeout: Duration,
+    finished: Arc<AtomicBool>,
+    suspended: Arc<AtomicBool>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if finished.load(Ordering::SeqCst) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        if finished.load(Ordering::SeqCst) {
+            return;
+        }
+
+        tracing::warn!("⏱️ 热迁移超过 {:?} 仍未完成，挂起虚拟机 {} 以完成迁移", timeout, vm_id);
+
+        let mut conn = match Connect::open(Some(LIBVIRT_URI)) {
+            Ok(conn) => conn,
+            Err(e) => {
+                tracing::error!("挂起虚拟机失败，无法连接 libvirt: {}", e);
+                return;
+            }
+        };
+
+        let result = lookup_domain(&conn, &vm_id).and_then(|domain| {
+            domain
+                .suspend()
+                .map_err(|e| common::Error::Internal(e.to_string()))
+        });
+        match result {
+            Ok(_) => suspended.store(true, Ordering::SeqCst),
+            Err(e) => tracing::error!("挂起虚拟机 {} 失败，继续热迁移: {}", vm_id, e),
+        }
+
+        let _ = conn.close();
+    })
+}
+
+/// 虚拟机实时状态
+#[derive(Debug, Clone)]
+pub struct VmLiveState {
+    pub state: String,
+    pub vcpu: u32,
+    pub memory_mb: u64,
+    pub max_memory_mb: u64,
+    pub uptime_secs: Option<u64>,
+}
+
+/// libvirt 域状态码对应的名称
+fn domain_state_name(state: u32) -> &'static str {
+    match state {
+        1 => "running",
+        2 => "blocked",
+        3 => "paused",
+        4 => "shutdown",
+        5 => "shutoff",
+        6 => "crashed",
+        7 => "pmsuspended",
+        _ => "nostate",
+    }
+}
+
+/// 生成磁盘的 `<iotune>` 元素，未设置任何限速时返回 None
+fn iotune_xml(limits: &DiskIoLimits) -> Option<String> {
+    if limits.is_unlimited() {
+        return None;
+    }
+
+    let mut xml = String::from("<iotune>");
+    if let Some(iops) = limits.iops_limit {
+        xml.push_str(&format!("<total_iops_sec>{}</total_iops_sec>", iops));
+    }
+    if let Some(bps) = limits.bps_limit {
+        xml.push_str(&format!("<total_bytes_sec>{}</total_bytes_sec>", bps));
+    }
+    xml.push_str("</iotune>");
+    Some(xml)
+}
+
+/// 生成链接克隆磁盘的 `<backingStore>` 元素，非链接克隆时返回 None
+///
+/// 基础镜像本身没有 backing file，以空的 `<backingStore/>` 结束链，避免 libvirt 再探测镜像头
+fn backing_store_xml(backing: Option<&BackingStore>) -> Option<String> {
+    backing.map(|backing| {
+        format!(
+            "<backingStore type='file'><format type='{}'/><source file='{}'/><backingStore/></backingStore>",
+            backing.format, backing.path
+        )
+    })
+}
+
+/// 生成加密磁盘的 `<encryption>` 元素，口令保存在 UUID 与存储卷 ID 相同的 libvirt secret 中
+fn encryption_xml(volume_id: &str) -> String {
+    format!(
+        "<encryption format='luks'><secret type='passphrase' uuid='{}'/></encryption>",
+        volume_id
+    )
+}
+
+/// 加密存储卷的 libvirt secret：ephemeral 不写入磁盘，private 不能通过 API 读回口令
+fn volume_secret_xml(volume_id: &str, volume_path: &str) -> String {
+    format!(
+        "<secret ephemeral='yes' private='yes'><uuid>{}</uuid><usage type='volume'><volume>{}</volume></usage></secret>",
+        volume_id, volume_path
+    )
+}
+
+/// 域 XML 中加密磁盘引用的 secret UUID
+fn disk_secret_uuids(xml: &str) -> Vec<String> {
+    let Ok(doc) = roxmltree::Document::parse(xml) else {
+        return Vec::new();
+    };
+    doc.descendants()
+        .filter(|node| node.has_tag_name("secret") && node.attribute("type") == Some("passphrase"))
+        .filter(|node| node.ancestors().any(|a| a.has_tag_name("encryption")))
+        .filter_map(|node| node.attribute("uuid").map(str::to_string))
+        .collect()
+}
+
+/// 删除加密磁盘的 libvirt secret，secret 不存在或删除失败只记录日志
+fn undefine_volume_secrets(conn: &Connect, uuids: &[String]) {
+    for uuid in uuids {
+        match virt::secret::Secret::lookup_by_uuid_string(conn, uuid) {
+            Ok(secret) => match secret.undefine() {
+                Ok(()) => tracing::info!("已删除存储卷 {} 的加密密钥", uuid),
+                Err(e) => tracing::warn!("删除存储卷 {} 的加密密钥失败: {}", uuid, e),
+            },
+            Err(e) => tracing::debug!("存储卷 {} 的加密密钥不存在: {}", uuid, e),
+        }
+    }
+}
+
+/// 生成网卡的 `<bandwidth>` 元素，未设置任何限速时返回 None
+fn bandwidth_xml(limits: &NicBandwidth) -> Option<String> {
+    if limits.is_unlimited() {
+        return None;
+    }
+
+    let mut xml = String::from("<bandwidth>");
+    if let Some(inbound) = limits.inbound_kbps {
+        xml.push_str(&format!("<inbound average='{}'/>", inbound));
+    }
+    if let Some(outbound) = limits.outbound_kbps {
+        xml.push_str(&format!("<outbound average='{}'/>", outbound));
+    }
+    xml.push_str("</bandwidth>");
+    Some(xml)
+}
+
+/// 按序列号（存储卷 ID）查找磁盘的目标设备名
+fn find_disk_target_by_volume_id(xml: &str, volume_id: &str) -> Result<Option<String>> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|e| common::Error::Internal(format!("解析XML失败: {}", e)))?;
+
+    let device = doc
+        .descendants()
+        .filter(|n| n.tag_name().name() == "disk")
+        .find(|disk| {
+            disk.children()
+                .find(|n| n.tag_name().name() == "serial")
+                .and_then(|n| n.text())
+                .map(|text| text.trim() == volume_id)
+                .unwrap_or(false)
+        })
+        .and_then(|disk| disk.children().find(|n| n.tag_name().name() == "target"))
+        .and_then(|target| target.attribute("dev"))
+        .map(|dev| dev.to_string());
+
+    Ok(device)
+}
+
+/// 按 MAC 地址查找网卡在宿主机上的 target 设备名（例如 vnet0），未运行时网卡没有 target
+fn find_interface_target_by_mac(xml: &str, mac_address: &str) -> Result<Option<String>> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|e| common::Error::Internal(format!("解析XML失败: {}", e)))?;
+
+    let device = doc
+        .descendants()
+        .filter(|n| n.tag_name().name() == "interface")
+        .find(|interface| {
+            interface
+                .children()
+                .find(|n| n.tag_name().name() == "mac")
+                .and_then(|n| n.attribute("address"))
+                .map(|mac| mac.eq_ignore_ascii_case(mac_address))
+                .unwrap_or(false)
+        })
+        .and_then(|interface| interface.children().find(|n| n.tag_name().name() == "target"))
+        .and_then(|target| target.attribute("dev"))
+        .map(|dev| dev.to_string());
+
+    Ok(device)
+}
+
+/// 解析 guest-network-get-interfaces 的返回结果，去掉回环地址及没有地址的网卡
+fn parse_guest_interfaces(ret: &serde_json::Value) -> Vec<GuestNetworkInterface> {
+    let mut interfaces = Vec::new();
+    for iface in ret.as_array().into_iter().flatten() {
+        let mut ipv4_addresses = Vec::new();
+        let mut ipv6_addresses = Vec::new();
+        for addr in iface["ip-addresses"].as_array().into_iter().flatten() {
+            let ip = match addr["ip-address"].as_str().and_then(|ip| ip.parse::<std::net::IpAddr>().ok()) {
+                Some(ip) if !ip.is_loopback() => ip,
+                _ => continue,
+            };
+            let cidr = match addr["prefix"].as_u64() {
+                Some(prefix) => format!("{}/{}", ip, prefix),
+                None => ip.to_string(),
+            };
+            if ip.is_ipv4() {
+                ipv4_addresses.push(cidr);
+            } else {
+                ipv6_addresses.push(cidr);
+            }
+        }
+
+        if ipv4_addresses.is_empty() && ipv6_addresses.is_empty() {
+            continue;
+        }
+        interfaces.push(GuestNetworkInterface {
+            name: iface["name"].as_str().unwrap_or_default().to_string(),
+            mac_address: iface["hardware-address"].as_str().map(|mac| mac.to_string()),
+            ipv4_addresses,
+            ipv6_addresses,
+        });
+    }
+    interfaces
+}
+
+/// 从运行中的域 XML 解析控制台端口和监听地址，端口尚未分配（-1）时返回 None
+fn parse_vnc_info(xml: &str) -> Result<Option<VncInfo>> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|e| common::Error::Internal(format!("解析XML失败: {}", e)))?;
+
+    let (graphics, protocol) = match doc.descendants().find_map(|n| {
+        if n.tag_name().name() != "graphics" {
+            return None;
+        }
+        n.attribute("type")?.parse::<GraphicsType>().ok().map(|protocol| (n, protocol))
+    }) {
+        Some(found) => found,
+        None => return Ok(None),
+    };
+
+    let port = match graphics.attribute("port").and_then(|p| p.parse::<u16>().ok()) {
+        Some(port) => port,
+        None => return Ok(None),
+    };
+
+    // 新版 libvirt 把监听地址放在 <listen> 子元素中；都没有时 QEMU 默认只监听本机
+    let listen = graphics
+        .attribute("listen")
+        .or_else(|| {
+            graphics
+                .children()
+                .find(|n| n.tag_name().name() == "listen")
+                .and_then(|n| n.attribute("address"))
+        })
+        .unwrap_or("127.0.0.1");
+
+    Ok(Some(VncInfo {
+        port,
+        listen: listen.to_string(),
+        protocol,
+    }))
+}
+
+/// 转义 XML 属性值（单引号包围）
+fn xml_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('\'', "&apos;")
+        .replace('"', "&quot;")
+}
+
+/// 生成修改控制台密码用的 graphics 设备 XML
+///
+/// libvirt 按类型匹配要更新的 graphics 设备，端口与监听地址须与运行中的配置一致，
+/// 因此沿用运行中域 XML 中的值
+fn graphics_password_xml(live_xml: &str, password: &str, valid_to: Option<DateTime<Utc>>) -> Result<String> {
+    use std::fmt::Write;
+
+    let doc = roxmltree::Document::parse(live_xml)
+        .map_err(|e| common::Error::Internal(format!("解析XML失败: {}", e)))?;
+    let graphics = doc
+        .descendants()
+        .find(|n| n.tag_name().name() == "graphics")
+        .ok_or_else(|| common::Error::InvalidArgument("虚拟机没有图形控制台".to_string()))?;
+
+    let mut xml = format!("<graphics type='{}'", graphics.attribute("type").unwrap_or("vnc"));
+    for name in ["port", "autoport", "listen"] {
+        if let Some(value) = graphics.attribute(name) {
+            write!(xml, " {}='{}'", name, xml_attr(value)).unwrap();
+        }
+    }
+    write!(xml, " passwd='{}'", xml_attr(password)).unwrap();
+    if let Some(valid_to) = valid_to {
+        // libvirt 按 UTC 解析，不带时区后缀
+        write!(xml, " passwdValidTo='{}'", valid_to.format("%Y-%m-%dT%H:%M:%S")).unwrap();
+    }
+    xml.push('>');
+    for listen in graphics.children().filter(|n| n.has_tag_name("listen")) {
+        xml.push_str("<listen");
+        for attr in listen.attributes() {
+            write!(xml, " {}='{}'", attr.name(), xml_attr(attr.value())).unwrap();
+        }
+        xml.push_str("/>");
+    }
+    xml.push_str("</graphics>");
+    Ok(xml)
+}
+
+/// 需要读取统计的设备（域 XML 中的 target dev）
+#[derive(Debug, Default, PartialEq)]
+struct StatDevices {
+    disks: Vec<String>,
+    interfaces: Vec<String>,
+}
+
+/// 从域 XML 解析磁盘和网卡的 target 设备名，光驱不计入磁盘
+fn parse_stat_devices(xml: &str) -> Result<StatDevices> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|e| common::Error::Internal(format!("解析XML失败: {}", e)))?;
+
+    let target_dev = |node: roxmltree::Node| {
+        node.children()
+            .find(|n| n.tag_name().name() == "target")
+            .and_then(|n| n.attribute("dev"))
+            .map(str::to_string)
+    };
+
+    let mut devices = StatDevices::default();
+    for node in doc.descendants() {
+        match node.tag_name().name() {
+            "disk" if node.attribute("device") == Some("disk") => {
+                devices.disks.extend(target_dev(node));
+            }
+            "interface" => devices.interfaces.extend(target_dev(node)),
+            _ => {}
+        }
     }
+    Ok(devices)
+}
+
+/// 根据 QEMU 进程的启动时间计算虚拟机运行时长
+///
+/// libvirt 不直接提供运行时长，这里读取 pid 文件后比较 /proc/<pid>/stat 的
+/// starttime 与系统 uptime（按 Linux 默认 USER_HZ=100 换算）
+fn qemu_uptime_secs(domain_name: &str) -> Option<u64> {
+    const USER_HZ: u64 = 100;
+
+    let pid = std::fs::read_to_string(format!("/run/libvirt/qemu/{}.pid", domain_name)).ok()?;
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim())).ok()?;
+    // 进程名可能包含空格，从最后一个 ')' 之后开始切分；starttime 是第 22 个字段
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let start_ticks: u64 = fields.get(19)?.parse().ok()?;
+
+    let system_uptime: f64 = std::fs::read_to_string("/proc/uptime")
+        .ok()?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+
+    Some((system_uptime as u64).saturating_sub(start_ticks / USER_HZ))
 }
 
 /// 虚拟机配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VMConfig {
     pub name: String,
-    pub uuid: String,  // 使用传入的 UUID
+    pub uuid: String,  // 虚拟机 ID，写入域 <uuid>，后续操作均按此查找
     pub vcpu: u32,
     pub memory_mb: u64,
     pub os_type: String,  // 操作系统类型: linux, windows
     pub volumes: Vec<VolumeConfig>,
     pub networks: Vec<NetworkConfig>,
+    /// 固件类型，旧版本 Server 未下发时为 BIOS
+    #[serde(default)]
+    pub firmware: FirmwareType,
+    /// cloud-init 配置，存在时以 cidata 光驱挂载配置光盘
+    #[serde(default)]
+    pub cloud_init: Option<CloudInitConfig>,
+    /// 挂载模拟 TPM 2.0 设备（Windows 11 要求），宿主机需安装 swtpm
+    #[serde(default)]
+    pub tpm: bool,
+    /// 安全模式：只生成系统盘、默认网卡和串口控制台，用于修复无法启动的配置
+    #[serde(default)]
+    pub safe_mode: bool,
+    /// 启动时显示固件的引导菜单，可临时选择引导设备
+    #[serde(default)]
+    pub boot_menu: bool,
+    /// vCPU 绑定到宿主机物理 CPU
+    #[serde(default)]
+    pub cpu_pinning: Option<CpuPinning>,
+    /// NUMA 内存绑定与客户机 NUMA 拓扑
+    #[serde(default)]
+    pub numa: Option<NumaConfig>,
+    /// 自定义域 XML，存在时不再生成而是原样定义（name 与 uuid 须与虚拟机一致）
+    #[serde(default)]
+    pub raw_xml_override: Option<String>,
+    /// 客户机架构，旧版本 Server 未下发时为 x86_64
+    #[serde(default = "default_arch")]
+    pub arch: String,
+    /// 机器类型，未指定时使用从 libvirt capabilities 检测到的默认值
+    #[serde(default)]
+    pub machine_type: Option<String>,
+    /// QEMU 模拟器路径，未指定时按架构从 capabilities 检测
+    #[serde(default)]
+    pub emulator: Option<String>,
+    /// 图形控制台协议
+    #[serde(default)]
+    pub graphics: GraphicsType,
+    /// 控制台密码，未设置时无需密码即可连接
+    #[serde(default)]
+    pub console_password: Option<String>,
+}
+
+fn default_arch() -> String {
+    DEFAULT_ARCH.to_string()
+}
+
+impl VMConfig {
+    /// 可热插拔的 vCPU 上限
+    pub fn max_vcpu(&self) -> u32 {
+        self.vcpu.max(HOTPLUG_MAX_VCPUS)
+    }
+
+    /// 内存上限（MiB）
+    ///
+    /// Windows 默认没有 virtio-balloon 驱动，启动后会直接占满上限，因此不预留余量
+    pub fn max_memory_mb(&self) -> u64 {
+        if self.os_type == "windows" {
+            self.memory_mb
+        } else {
+            self.memory_mb.saturating_mul(HOTPLUG_MEMORY_FACTOR)
+        }
+    }
+
+    /// 客户机 NUMA 节点的 `<numa>` 片段，未配置时为空
+    ///
+    /// 各节点须覆盖全部可热插拔的 vCPU，内存合计等于内存上限，因此按上限平均分配，
+    /// 余数归入最后一个节点
+    fn guest_numa_cells(&self) -> String {
+        use std::fmt::Write;
+
+        let cells = self.numa.as_ref().map_or(0, |numa| numa.guest_nodes);
+        if cells == 0 {
+            return String::new();
+        }
+        let max_vcpu = self.max_vcpu();
+        let max_memory_mb = self.max_memory_mb();
+        let memory_per_cell = max_memory_mb / cells as u64;
+
+        let mut xml = String::new();
+        writeln!(xml, "    <numa>").unwrap();
+        for cell in 0..cells {
+            let first = cell * max_vcpu / cells;
+            let last = (cell + 1) * max_vcpu / cells - 1;
+            let memory = if cell + 1 == cells {
+                max_memory_mb - memory_per_cell * (cells as u64 - 1)
+            } else {
+                memory_per_cell
+            };
+            writeln!(
+                xml,
+                "      <cell id='{}' cpus='{}-{}' memory='{}' unit='MiB'/>",
+                cell, first, last, memory
+            )
+            .unwrap();
+        }
+        writeln!(xml, "    </numa>").unwrap();
+        xml
+    }
+
+    /// 转换为安全模式配置
+    ///
+    /// 只保留第一块磁盘设备（系统盘）和第一块网卡，网卡型号交由生成器按操作系统选择默认值，
+    /// 不挂载 cloud-init 配置光盘
+    pub fn into_safe_mode(mut self) -> Self {
+        self.volumes = self
+            .volumes
+            .into_iter()
+            .find(|volume| volume.device_type == DiskDeviceType::Disk)
+            .into_iter()
+            .collect();
+        // 只剩系统盘，按固件默认顺序从其引导
+        for volume in &mut self.volumes {
+            volume.boot_order = None;
+        }
+        self.networks.truncate(1);
+        for network in &mut self.networks {
+            network.model.clear();
+        }
+        self.cloud_init = None;
+        // 错误的 CPU 绑定也可能导致无法启动，安全模式交由调度器放置
+        self.cpu_pinning = None;
+        self.numa = None;
+        // 自定义 XML 可能正是无法启动的原因
+        self.raw_xml_override = None;
+        self.safe_mode = true;
+        self
+    }
+
+    /// 虚拟机的全部磁盘设备，配置了 cloud-init 时在末尾追加配置光盘
+    fn volumes_with_config_drive(&self) -> Vec<VolumeConfig> {
+        let mut volumes = self.volumes.clone();
+        if self.cloud_init.is_some() {
+            volumes.push(VolumeConfig {
+                volume_id: "cidata".to_string(),
+                volume_path: super::cloud_init::config_drive_path(&self.uuid)
+                    .to_string_lossy()
+                    .to_string(),
+                bus_type: DiskBusType::Sata,
+                device_type: DiskDeviceType::Cdrom,
+                format: "raw".to_string(),
+                limits: DiskIoLimits::default(),
+                backing: None,
+                encryption: None,
+                boot_order: None,
+            });
+        }
+        volumes
+    }
+}
+
+
+/// UEFI 虚拟机 NVRAM 文件所在目录
+const NVRAM_DIR: &str = "/var/lib/libvirt/qemu/nvram";
+
+/// 未指定架构时的客户机架构
+pub const DEFAULT_ARCH: &str = "x86_64";
+
+/// libvirt capabilities 中一种架构的客户机支持
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuestArch {
+    pub arch: String,
+    pub emulator: String,
+    /// 默认机器类型：q35 / virt 别名对应的具体版本，没有时为 None
+    pub machine: Option<String>,
+    /// 支持的全部机器类型（含别名）
+    pub machines: Vec<String>,
+}
+
+fn is_x86(arch: &str) -> bool {
+    matches!(arch, "x86_64" | "i686")
+}
+
+/// 各架构首选的机器类型别名，由 libvirt 展开为当前 QEMU 的具体版本
+fn default_machine(arch: &str) -> &'static str {
+    if is_x86(arch) {
+        "q35"
+    } else {
+        "virt"
+    }
+}
+
+/// 读取 libvirt capabilities，失败时返回空列表（生成器回退到默认值）
+fn detect_guests(conn: &Connect) -> Vec<GuestArch> {
+    let guests = conn
+        .get_capabilities()
+        .map_err(|e| common::Error::Internal(format!("读取 libvirt capabilities 失败: {}", e)))
+        .and_then(|xml| parse_guest_capabilities(&xml));
+    match guests {
+        Ok(guests) => {
+            for guest in &guests {
+                tracing::info!(
+                    "检测到客户机架构 {}: 模拟器 {}，默认机器类型 {}",
+                    guest.arch,
+                    guest.emulator,
+                    guest.machine.as_deref().unwrap_or("-")
+                );
+            }
+            guests
+        }
+        Err(e) => {
+            tracing::warn!("{}，使用默认机器类型与模拟器", e);
+            Vec::new()
+        }
+    }
+}
+
+/// 解析 capabilities XML 中 hvm 客户机的架构、模拟器与机器类型
+fn parse_guest_capabilities(xml: &str) -> Result<Vec<GuestArch>> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|e| common::Error::Internal(format!("解析XML失败: {}", e)))?;
+
+    let mut guests = Vec::new();
+    for guest in doc.root_element().children().filter(|n| n.has_tag_name("guest")) {
+        let hvm = guest
+            .children()
+            .any(|n| n.has_tag_name("os_type") && n.text() == Some("hvm"));
+        let Some(arch) = guest.children().find(|n| n.has_tag_name("arch")) else {
+            continue;
+        };
+        let (Some(name), Some(emulator)) = (
+            arch.attribute("name"),
+            arch.children().find(|n| n.has_tag_name("emulator")).and_then(|n| n.text()),
+        ) else {
+            continue;
+        };
+        if !hvm {
+            continue;
+        }
+
+        let machines: Vec<roxmltree::Node> = arch.children().filter(|n| n.has_tag_name("machine")).collect();
+        let alias = default_machine(name);
+        let machine = machines
+            .iter()
+            .find(|n| n.text() == Some(alias))
+            .map(|n| n.attribute("canonical").unwrap_or(alias).to_string());
+        let mut names: Vec<String> = machines.iter().filter_map(|n| n.text()).map(str::to_string).collect();
+        names.dedup();
+
+        guests.push(GuestArch {
+            arch: name.to_string(),
+            emulator: emulator.to_string(),
+            machine,
+            machines: names,
+        });
+    }
+    Ok(guests)
+}
+
+/// 每次定义前保存域 XML 的目录，文件按虚拟机 ID 命名
+const DOMAIN_XML_DIR: &str = "/var/lib/easy-vm-cloud/domains";
+
+/// 保存即将定义的域 XML，供 define 失败时排查；写入失败不影响启动
+async fn persist_domain_xml(vm_id: &str, xml: &str) {
+    let dir = std::path::Path::new(DOMAIN_XML_DIR);
+    let path = dir.join(format!("{}.xml", vm_id));
+    // XML 中可能包含控制台密码，只允许 root 读取
+    let result = async {
+        use tokio::io::AsyncWriteExt;
+        tokio::fs::create_dir_all(dir).await?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&path)
+            .await?;
+        file.write_all(xml.as_bytes()).await
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("保存虚拟机 {} 的域 XML 到 {} 失败: {}", vm_id, path.display(), e);
+    }
 }
 
+/// 自定义域 XML 的最小校验：根元素为 domain，name 与 uuid 与虚拟机一致
+///
+/// 其余内容由 libvirt 在定义时校验
+fn validate_xml_override(xml: &str, config: &VMConfig) -> Result<()> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|e| common::Error::InvalidArgument(format!("自定义域 XML 解析失败: {}", e)))?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "domain" {
+        return Err(common::Error::InvalidArgument(
+            "自定义域 XML 的根元素必须是 domain".to_string(),
+        ));
+    }
+
+    let text = |tag: &str| {
+        root.children()
+            .find(|n| n.tag_name().name() == tag)
+            .and_then(|n| n.text())
+            .map(str::trim)
+    };
+    if text("name") != Some(config.name.as_str()) {
+        return Err(common::Error::InvalidArgument(format!(
+            "自定义域 XML 的 name 必须为 {}",
+            config.name
+        )));
+    }
+    if !text("uuid").is_some_and(|uuid| uuid.eq_ignore_ascii_case(&config.uuid)) {
+        return Err(common::Error::InvalidArgument(format!(
+            "自定义域 XML 的 uuid 必须为 {}",
+            config.uuid
+        )));
+    }
+    Ok(())
+}
+
+/// UEFI 虚拟机的 NVRAM 文件路径
+fn nvram_path(vm_id: &str) -> String {
+    format!("{}/{}_VARS.fd", NVRAM_DIR, vm_id)
+}
+
+/// 取消定义域并保留 NVRAM 文件
+///
+/// 带 NVRAM 的域不允许直接 undefine；每次启动都会重新定义，需保留 UEFI 变量
+fn undefine_keep_nvram(domain: &virt::domain::Domain) -> std::result::Result<(), virt::error::Error> {
+    domain.undefine_flags(virt::sys::VIR_DOMAIN_UNDEFINE_KEEP_NVRAM)
+}
 
 /// 存储卷配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VolumeConfig {
     pub volume_id: String,           // 存储卷ID，用作序列号
     pub volume_path: String,
-    pub bus_type: DiskBusType,      // 总线类型: virtio, scsi, ide
+    pub bus_type: DiskBusType,      // 总线类型: virtio, scsi, sata, ide
     pub device_type: DiskDeviceType, // 设备类型: disk, cdrom
     pub format: String,              // 磁盘格式: qcow2, raw, vmdk 等
+    /// I/O 限速，旧版本 Server 未下发时不限速
+    #[serde(flatten)]
+    pub limits: DiskIoLimits,    /// 链接克隆的基础镜像，普通卷为空
+    #[serde(default)]
+    pub backing: Option<BackingStore>,
+    /// LUKS 加密的密钥来源，启动前由 RPC 处理器定义对应的 libvirt secret
+    #[serde(default)]
+    pub encryption: Option<VolumeEncryption>,
+    /// 引导顺序（从 1 开始）；任一磁盘设置后只有设置了顺序的设备可以引导
+    #[serde(default)]
+    pub boot_order: Option<u32>,
 }
 
 /// 网络配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkConfig {
+    /// Server 下发的字段名为 network_id
+    #[serde(alias = "network_id")]
     pub network_name: String,
+    #[serde(default)]
     pub bridge_name: String,  // Bridge 名称，例如：br-vlan100
     pub mac_address: Option<String>,
     pub model: String,  // virtio, e1000, etc.
+    /// 网络的 VLAN ID：外层 None 表示 Server 未下发（旧版本），Some(None) 表示无 VLAN 网络
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub vlan_id: Option<Option<u32>>,
+    /// 网络类型（bridge / ovs / macvlan），旧版本 Server 不下发，按 bridge 处理
+    #[serde(default)]
+    pub network_type: Option<String>,
+    /// 网络的 MTU，旧版本 Server 不下发或网络未设置时为 1500
+    #[serde(default)]
+    pub mtu: Option<u32>,
+    /// 带宽限速（inbound_kbps / outbound_kbps），旧版本 Server 未下发时不限速
+    #[serde(flatten)]
+    pub bandwidth: NicBandwidth,
+    /// 安全组规则，None 表示网卡未关联安全组（不过滤入站流量）
+    #[serde(default)]
+    pub security_group_rules: Option<Vec<SecurityGroupRule>>,
+    /// 网络开启了托管 DHCP 时附带网段、网关和静态租约，启动前在 Bridge 上启动 dnsmasq
+    #[serde(default)]
+    pub dhcp: Option<DhcpConfig>,
+}
+
+impl NetworkConfig {
+    /// 是否为 Open vSwitch 网络
+    pub fn is_ovs(&self) -> bool {
+        self.network_type.as_deref() == Some("ovs")
+    }
+
+    /// 是否为 Macvlan 网络
+    pub fn is_macvlan(&self) -> bool {
+        self.network_type.as_deref() == Some("macvlan")
+    }
+
+    pub fn mtu(&self) -> u32 {
+        self.mtu.unwrap_or(crate::network::DEFAULT_MTU)
+    }
+}
+
+/// 字段存在（包括 null）时反序列化为 Some，用于区分“未下发”和“显式为空”
+fn deserialize_present<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
 
 /// 虚拟机信息
@@ -926,5 +2607,900 @@ pub struct VMInfo {
     pub id: String,
     pub name: String,
     pub state: String,
+    pub vcpu: u32,
+    pub memory_mb: u64,
 }
 
+/// guest-exec 进程状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuestExecStatus {
+    pub exited: bool,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub truncated: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume(volume_id: &str, bus_type: DiskBusType, device_type: DiskDeviceType) -> VolumeConfig {
+        VolumeConfig {
+            volume_id: volume_id.to_string(),
+            volume_path: format!("/mnt/nfs/{}.qcow2", volume_id),
+            bus_type,
+            device_type,
+            format: "qcow2".to_string(),
+            limits: DiskIoLimits::default(),
+            backing: None,
+            encryption: None,
+            boot_order: None,
+        }
+    }
+
+    fn network(network_name: &str, model: &str) -> NetworkConfig {
+        NetworkConfig {
+            network_name: network_name.to_string(),
+            bridge_name: format!("br-{}", network_name),
+            mac_address: None,
+            model: model.to_string(),
+            vlan_id: None,
+            network_type: None,
+            mtu: None,
+            bandwidth: NicBandwidth::default(),
+            security_group_rules: None,
+            dhcp: None,
+        }
+    }
+
+    #[test]
+    fn test_safe_mode_xml_keeps_root_disk_and_console() {
+        let config = VMConfig {
+            name: "web-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 2,
+            memory_mb: 2048,
+            os_type: "linux".to_string(),
+            volumes: vec![
+                volume("iso", DiskBusType::Sata, DiskDeviceType::Cdrom),
+                volume("root", DiskBusType::Scsi, DiskDeviceType::Disk),
+                volume("data", DiskBusType::Virtio, DiskDeviceType::Disk),
+            ],
+            networks: vec![network("prod", "e1000"), network("backup", "virtio")],
+            firmware: FirmwareType::Bios,
+            cloud_init: Some(CloudInitConfig::default()),
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        }
+        .into_safe_mode();
+
+        assert_eq!(config.volumes.len(), 1);
+        assert_eq!(config.volumes[0].volume_id, "root");
+        assert_eq!(config.networks.len(), 1);
+        assert!(config.networks[0].model.is_empty());
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert_eq!(xml.matches("<disk ").count(), 1);
+        assert!(xml.contains("<serial>root</serial>"));
+        assert!(xml.contains("model='virtio-scsi'"));
+        assert!(!xml.contains("type='sata'"));
+        assert_eq!(xml.matches("<interface ").count(), 1);
+        assert!(xml.contains("<model type='virtio'/>"));
+        assert!(xml.contains("<console type='pty'>"));
+        assert!(!xml.contains("<graphics"));
+        assert!(!xml.contains("org.qemu.guest_agent.0"));
+        assert!(!xml.contains("cidata"));
+        assert!(xml.trim_end().ends_with("</domain>"));
+    }
+
+    #[test]
+    fn test_uefi_xml_uses_per_vm_nvram() {
+        let config = VMConfig {
+            name: "win-11".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 4,
+            memory_mb: 8192,
+            os_type: "windows".to_string(),
+            volumes: vec![volume("root", DiskBusType::Sata, DiskDeviceType::Disk)],
+            networks: Vec::new(),
+            firmware: FirmwareType::Uefi,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert!(xml.contains("<os firmware='efi'>"));
+        assert!(xml.contains("<loader secure='yes'/>"));
+        assert!(xml.contains("<nvram>/var/lib/libvirt/qemu/nvram/vm-1_VARS.fd</nvram>"));
+        assert!(xml.contains("<smm state='on'/>"));
+
+        let bios = HypervisorManager::generate_vm_xml(&VMConfig {
+            firmware: FirmwareType::Bios,
+            ..config.clone()
+        })
+        .unwrap();
+        assert!(!bios.contains("firmware='efi'"));
+        assert!(!bios.contains("<nvram>"));
+        assert!(!bios.contains("<tpm"));
+
+        let with_tpm = HypervisorManager::generate_vm_xml(&VMConfig { tpm: true, ..config }).unwrap();
+        assert!(with_tpm.contains("<tpm model='tpm-crb'>"));
+        assert!(with_tpm.contains("<backend type='emulator' version='2.0'/>"));
+    }
+
+    #[test]
+    fn test_xml_reserves_hotplug_headroom() {
+        let config = VMConfig {
+            name: "web-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 2,
+            memory_mb: 2048,
+            os_type: "linux".to_string(),
+            volumes: vec![volume("root", DiskBusType::Virtio, DiskDeviceType::Disk)],
+            networks: Vec::new(),
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert!(xml.contains("<vcpu placement='static' current='2'>16</vcpu>"));
+        assert!(xml.contains("<memory unit='MiB'>4096</memory>"));
+        assert!(xml.contains("<currentMemory unit='MiB'>2048</currentMemory>"));
+
+        // Windows 不预留内存余量，CPU 拓扑覆盖全部可热插拔的 vCPU
+        let windows = HypervisorManager::generate_vm_xml(&VMConfig {
+            os_type: "windows".to_string(),
+            ..config
+        })
+        .unwrap();
+        assert!(windows.contains("<memory unit='MiB'>2048</memory>"));
+        assert!(windows.contains("cores='16'"));
+    }
+
+    #[test]
+    fn test_cloud_init_config_drive_attached_as_cdrom() {
+        let config = VMConfig {
+            name: "web-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 1,
+            memory_mb: 1024,
+            os_type: "linux".to_string(),
+            volumes: vec![volume("root", DiskBusType::Virtio, DiskDeviceType::Disk)],
+            networks: Vec::new(),
+            firmware: FirmwareType::Bios,
+            cloud_init: Some(CloudInitConfig {
+                user_data: "#cloud-config\n".to_string(),
+                ..Default::default()
+            }),
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert_eq!(xml.matches("<disk ").count(), 2);
+        assert!(xml.contains("<disk type='file' device='cdrom'>"));
+        assert!(xml.contains("<source file='/var/lib/libvirt/cloud-init/vm-1-cidata.iso'/>"));
+        assert!(xml.contains("<target dev='sdb' bus='sata'/>"));
+        assert!(xml.contains("<controller type='sata' index='0'/>"));
+
+        let without = HypervisorManager::generate_vm_xml(&VMConfig {
+            cloud_init: None,
+            ..config
+        })
+        .unwrap();
+        assert!(!without.contains("cidata"));
+    }
+
+    #[test]
+    fn test_parse_vnc_info_from_live_xml() {
+        let xml = r#"<domain type='kvm' id='3'>
+  <devices>
+    <graphics type='vnc' port='5901' autoport='yes' listen='0.0.0.0'>
+      <listen type='address' address='0.0.0.0'/>
+    </graphics>
+  </devices>
+</domain>"#;
+        let vnc = parse_vnc_info(xml).unwrap().unwrap();
+        assert_eq!(vnc.port, 5901);
+        assert!(vnc.listens_on_all());
+        assert_eq!(vnc.protocol, GraphicsType::Vnc);
+
+        let spice = parse_vnc_info(&xml.replace("type='vnc' port='5901'", "type='spice' port='5902'"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(spice.port, 5902);
+        assert_eq!(spice.protocol, GraphicsType::Spice);
+    }
+
+    #[test]
+    fn test_graphics_password_xml() {
+        let live = r#"<domain type='kvm' id='3'>
+  <devices>
+    <graphics type='vnc' port='5901' autoport='yes' listen='0.0.0.0'>
+      <listen type='address' address='0.0.0.0'/>
+    </graphics>
+  </devices>
+</domain>"#;
+        let valid_to = chrono::TimeZone::with_ymd_and_hms(&Utc, 2026, 10, 17, 8, 30, 0).unwrap();
+
+        let xml = graphics_password_xml(live, "a'b", Some(valid_to)).unwrap();
+        assert_eq!(
+            xml,
+            "<graphics type='vnc' port='5901' autoport='yes' listen='0.0.0.0' passwd='a&apos;b' \
+             passwdValidTo='2026-10-17T08:30:00'><listen type='address' address='0.0.0.0'/></graphics>"
+        );
+        assert!(graphics_password_xml("<domain><devices/></domain>", "pw", None).is_err());
+
+        let mut config = VMConfig {
+            name: "web-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 1,
+            memory_mb: 1024,
+            os_type: "linux".to_string(),
+            volumes: Vec::new(),
+            networks: Vec::new(),
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+        assert!(!HypervisorManager::generate_vm_xml(&config).unwrap().contains("passwd="));
+        config.console_password = Some("Xk3pQ9aZ".to_string());
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert!(xml.contains("<graphics type='vnc' port='-1' autoport='yes' listen='0.0.0.0' passwd='Xk3pQ9aZ'>"));
+
+        // 未启动时端口为 -1，安全模式没有图形设备
+        let inactive = xml.replace("port='5901'", "port='-1'");
+        assert_eq!(parse_vnc_info(&inactive).unwrap(), None);
+        assert_eq!(parse_vnc_info("<domain><devices/></domain>").unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_stat_devices_skips_cdrom() {
+        let xml = r#"<domain type='kvm'>
+  <devices>
+    <disk type='file' device='disk'><target dev='vda' bus='virtio'/></disk>
+    <disk type='file' device='cdrom'><target dev='sda' bus='sata'/></disk>
+    <disk type='block' device='disk'><target dev='vdb' bus='virtio'/></disk>
+    <interface type='bridge'><target dev='vnet0'/></interface>
+    <interface type='bridge'><mac address='52:54:00:00:00:02'/></interface>
+  </devices>
+</domain>"#;
+        let devices = parse_stat_devices(xml).unwrap();
+        assert_eq!(devices.disks, vec!["vda", "vdb"]);
+        // 未运行时网卡没有 target，不参与统计
+        assert_eq!(devices.interfaces, vec!["vnet0"]);
+    }
+
+    #[test]
+    fn test_find_interface_target_by_mac() {
+        let xml = r#"<domain type='kvm'>
+  <devices>
+    <interface type='bridge'><mac address='52:54:00:00:00:01'/><target dev='vnet3'/></interface>
+    <interface type='bridge'><mac address='52:54:00:00:00:02'/></interface>
+  </devices>
+</domain>"#;
+        assert_eq!(
+            find_interface_target_by_mac(xml, "52:54:00:00:00:01").unwrap().as_deref(),
+            Some("vnet3")
+        );
+        assert_eq!(find_interface_target_by_mac(xml, "52:54:00:00:00:02").unwrap(), None);
+        assert_eq!(find_interface_target_by_mac(xml, "52:54:00:00:00:03").unwrap(), None);
+    }
+
+    #[test]
+    fn test_xml_disk_iotune() {
+        let mut data = volume("data", DiskBusType::Virtio, DiskDeviceType::Disk);
+        data.limits = DiskIoLimits {
+            iops_limit: Some(500),
+            bps_limit: None,
+        };
+        let config = VMConfig {
+            name: "db-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 2,
+            memory_mb: 2048,
+            os_type: "linux".to_string(),
+            volumes: vec![volume("root", DiskBusType::Virtio, DiskDeviceType::Disk), data],
+            networks: Vec::new(),
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        // 只有设置了限速的磁盘带 iotune，未设置的项不输出
+        assert_eq!(xml.matches("<iotune>").count(), 1);
+        assert!(xml.contains("<iotune><total_iops_sec>500</total_iops_sec></iotune>"));
+        assert_eq!(
+            find_disk_target_by_volume_id(&xml, "data").unwrap().as_deref(),
+            Some("vdb")
+        );
+        assert_eq!(find_disk_target_by_volume_id(&xml, "missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_xml_encrypted_disk_references_volume_secret() {
+        let mut data = volume("0b6f9c1e-3c1a-4c3e-9f57-8d1c2a4b5e6f", DiskBusType::Virtio, DiskDeviceType::Disk);
+        data.encryption = Some(VolumeEncryption::Passphrase { passphrase: "pw".to_string() });
+        let config = VMConfig {
+            name: "db-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 1,
+            memory_mb: 1024,
+            os_type: "linux".to_string(),
+            volumes: vec![volume("root", DiskBusType::Virtio, DiskDeviceType::Disk), data],
+            networks: Vec::new(),
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert_eq!(xml.matches("<encryption format='luks'>").count(), 1);
+        assert!(!xml.contains("pw"));
+        assert_eq!(disk_secret_uuids(&xml), vec!["0b6f9c1e-3c1a-4c3e-9f57-8d1c2a4b5e6f".to_string()]);
+
+        let secret = volume_secret_xml("0b6f9c1e-3c1a-4c3e-9f57-8d1c2a4b5e6f", "/mnt/nfs/data.qcow2");
+        assert!(secret.starts_with("<secret ephemeral='yes' private='yes'>"));
+        assert!(secret.contains("<volume>/mnt/nfs/data.qcow2</volume>"));
+    }
+
+    #[test]
+    fn test_xml_boot_order_and_menu() {
+        let mut iso = volume("iso", DiskBusType::Sata, DiskDeviceType::Cdrom);
+        iso.boot_order = Some(1);
+        let mut root = volume("root", DiskBusType::Virtio, DiskDeviceType::Disk);
+        root.boot_order = Some(2);
+        let config = VMConfig {
+            name: "web-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 1,
+            memory_mb: 1024,
+            os_type: "linux".to_string(),
+            volumes: vec![root, volume("data", DiskBusType::Virtio, DiskDeviceType::Disk), iso],
+            networks: Vec::new(),
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: true,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert!(xml.contains("<bootmenu enable='yes'/>\n  </os>"));
+        assert!(!xml.contains("<boot dev="));
+        assert_eq!(xml.matches("<boot order=").count(), 2);
+        assert!(xml.contains("<target dev='vda' bus='virtio'/>\n      <boot order='2'/>"));
+        assert!(xml.contains("<target dev='sdc' bus='sata'/>\n      <boot order='1'/>"));
+
+        // 安全模式只保留系统盘，恢复固件默认的引导顺序
+        let safe = HypervisorManager::generate_vm_xml(&config.into_safe_mode()).unwrap();
+        assert!(!safe.contains("<boot order="));
+    }
+
+    #[test]
+    fn test_raw_xml_override() {
+        let mut config = VMConfig {
+            name: "web-1".to_string(),
+            uuid: "6f1b2c3d-0000-4000-8000-000000000001".to_string(),
+            vcpu: 1,
+            memory_mb: 1024,
+            os_type: "linux".to_string(),
+            volumes: vec![volume("root", DiskBusType::Virtio, DiskDeviceType::Disk)],
+            networks: Vec::new(),
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+        let custom = "<domain type='kvm'>\n  <name>web-1</name>\n  <uuid>6F1B2C3D-0000-4000-8000-000000000001</uuid>\n  <memory>1</memory>\n</domain>";
+
+        config.raw_xml_override = Some(custom.to_string());
+        assert_eq!(HypervisorManager::domain_xml(&config).unwrap(), custom);
+
+        // name、uuid 不一致或不是域定义时拒绝
+        for invalid in [
+            custom.replace("web-1", "web-2"),
+            custom.replace("0001</uuid>", "0002</uuid>"),
+            "<network><name>web-1</name></network>".to_string(),
+            "<domain>".to_string(),
+        ] {
+            config.raw_xml_override = Some(invalid);
+            assert!(matches!(
+                HypervisorManager::domain_xml(&config),
+                Err(common::Error::InvalidArgument(_))
+            ));
+        }
+
+        // 安全模式忽略自定义 XML
+        let safe = config.into_safe_mode();
+        assert!(HypervisorManager::domain_xml(&safe).unwrap().contains("<name>web-1</name>"));
+    }
+
+    #[test]
+    fn test_xml_cpu_pinning_and_numa() {
+        let mut config = VMConfig {
+            name: "db-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 2,
+            memory_mb: 4096,
+            os_type: "linux".to_string(),
+            volumes: vec![volume("root", DiskBusType::Virtio, DiskDeviceType::Disk)],
+            networks: Vec::new(),
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: Some([(0, 4), (1, 5)].into_iter().collect()),
+            numa: Some(NumaConfig {
+                host_nodes: vec![1],
+                mode: common::ws_rpc::types::NumaMode::Strict,
+                guest_nodes: 3,
+            }),
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert!(xml.contains("  <cputune>\n    <vcpupin vcpu='0' cpuset='4'/>\n    <vcpupin vcpu='1' cpuset='5'/>\n  </cputune>"));
+        assert!(xml.contains("<numatune>\n    <memory mode='strict' nodeset='1'/>\n  </numatune>"));
+        // 16 个可热插拔 vCPU 与 8192 MiB 内存上限分到 3 个客户机节点
+        assert!(xml.contains("<cpu mode='host-passthrough' check='none'>\n    <numa>"));
+        assert!(xml.contains("<cell id='0' cpus='0-4' memory='2730' unit='MiB'/>"));
+        assert!(xml.contains("<cell id='1' cpus='5-9' memory='2730' unit='MiB'/>"));
+        assert!(xml.contains("<cell id='2' cpus='10-15' memory='2732' unit='MiB'/>"));
+
+        config.os_type = "windows".to_string();
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert!(xml.contains("<feature policy='require' name='svm'/>\n    <numa>"));
+
+        let safe = HypervisorManager::generate_vm_xml(&config.into_safe_mode()).unwrap();
+        assert!(!safe.contains("<cputune>"));
+        assert!(!safe.contains("<numa"));
+    }
+
+    #[test]
+    fn test_parse_guest_capabilities() {
+        let caps = r#"<capabilities>
+  <host><cpu><arch>x86_64</arch></cpu></host>
+  <guest>
+    <os_type>hvm</os_type>
+    <arch name='x86_64'>
+      <wordsize>64</wordsize>
+      <emulator>/usr/libexec/qemu-kvm</emulator>
+      <machine maxCpus='240'>pc-i440fx-8.2</machine>
+      <machine canonical='pc-i440fx-8.2' maxCpus='240'>pc</machine>
+      <machine maxCpus='4096'>pc-q35-8.2</machine>
+      <machine canonical='pc-q35-8.2' maxCpus='4096'>q35</machine>
+    </arch>
+  </guest>
+  <guest>
+    <os_type>hvm</os_type>
+    <arch name='aarch64'>
+      <emulator>/usr/bin/qemu-system-aarch64</emulator>
+      <machine maxCpus='512'>virt-8.2</machine>
+    </arch>
+  </guest>
+  <guest>
+    <os_type>xen</os_type>
+    <arch name='x86_64'><emulator>/usr/bin/xen</emulator></arch>
+  </guest>
+</capabilities>"#;
+
+        let guests = parse_guest_capabilities(caps).unwrap();
+        assert_eq!(guests.len(), 2);
+        assert_eq!(guests[0].arch, "x86_64");
+        assert_eq!(guests[0].emulator, "/usr/libexec/qemu-kvm");
+        assert_eq!(guests[0].machine.as_deref(), Some("pc-q35-8.2"));
+        assert!(guests[0].machines.contains(&"q35".to_string()));
+        // 没有 virt 别名时不指定默认值，由生成器使用别名
+        assert_eq!(guests[1].arch, "aarch64");
+        assert_eq!(guests[1].machine, None);
+    }
+
+    #[test]
+    fn test_xml_machine_type_and_emulator() {
+        let mut config = VMConfig {
+            name: "arm-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 2,
+            memory_mb: 2048,
+            os_type: "linux".to_string(),
+            volumes: Vec::new(),
+            networks: Vec::new(),
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert!(xml.contains("<type arch='x86_64' machine='q35'>hvm</type>"));
+        assert!(xml.contains("<emulator>/usr/bin/qemu-system-x86_64</emulator>"));
+        assert!(xml.contains("<apic/>"));
+
+        config.arch = "aarch64".to_string();
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert!(xml.contains("<type arch='aarch64' machine='virt'>hvm</type>"));
+        assert!(xml.contains("<emulator>/usr/bin/qemu-system-aarch64</emulator>"));
+        assert!(!xml.contains("<apic/>"));
+
+        config.machine_type = Some("virt-8.2".to_string());
+        config.emulator = Some("/usr/libexec/qemu-kvm".to_string());
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert!(xml.contains("<type arch='aarch64' machine='virt-8.2'>hvm</type>"));
+        assert!(xml.contains("<emulator>/usr/libexec/qemu-kvm</emulator>"));
+    }
+
+    #[test]
+    fn test_xml_spice_graphics() {
+        let mut config = VMConfig {
+            name: "win-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 2,
+            memory_mb: 4096,
+            os_type: "windows".to_string(),
+            volumes: Vec::new(),
+            networks: Vec::new(),
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert!(xml.contains("<graphics type='vnc' port='-1' autoport='yes' listen='0.0.0.0'>"));
+        assert!(xml.contains("<model type='cirrus'"));
+        assert!(!xml.contains("spicevmc"));
+
+        config.graphics = GraphicsType::Spice;
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert!(xml.contains("<graphics type='spice' port='-1' autoport='yes' listen='0.0.0.0'>"));
+        assert!(xml.contains("<channel type='spicevmc'>\n      <target type='virtio' name='com.redhat.spice.0'/>"));
+        assert_eq!(xml.matches("<redirdev bus='usb' type='spicevmc'/>").count(), 2);
+        assert!(xml.contains("<model type='qxl'"));
+        assert!(!xml.contains("type='vnc'"));
+    }
+
+    #[test]
+    fn test_xml_linked_clone_disk_has_backing_store() {
+        let mut root = volume("root", DiskBusType::Virtio, DiskDeviceType::Disk);
+        root.backing = Some(BackingStore {
+            path: "/mnt/nfs/base.qcow2".to_string(),
+            format: "qcow2".to_string(),
+        });
+        let config = VMConfig {
+            name: "web-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 1,
+            memory_mb: 1024,
+            os_type: "linux".to_string(),
+            volumes: vec![root, volume("data", DiskBusType::Virtio, DiskDeviceType::Disk)],
+            networks: Vec::new(),
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert_eq!(xml.matches("<backingStore type='file'>").count(), 1);
+        assert!(xml.contains(
+            "<source file='/mnt/nfs/root.qcow2'/>\n      <backingStore type='file'><format type='qcow2'/><source file='/mnt/nfs/base.qcow2'/><backingStore/></backingStore>"
+        ));
+    }
+
+    #[test]
+    fn test_xml_ovs_interface_uses_virtualport() {
+        let mut ovs = network("prod", "virtio");
+        ovs.network_type = Some("ovs".to_string());
+        let config = VMConfig {
+            name: "web-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 1,
+            memory_mb: 1024,
+            os_type: "linux".to_string(),
+            volumes: vec![volume("root", DiskBusType::Virtio, DiskDeviceType::Disk)],
+            networks: vec![ovs, network("backup", "virtio")],
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert_eq!(xml.matches("<interface type='bridge'>").count(), 2);
+        assert_eq!(xml.matches("<virtualport type='openvswitch'/>").count(), 1);
+        assert!(xml.contains("<source bridge='br-prod'/>\n      <virtualport type='openvswitch'/>"));
+    }
+
+    #[test]
+    fn test_xml_macvlan_interface_is_direct() {
+        let mut macvlan = network("fast", "virtio");
+        macvlan.network_type = Some("macvlan".to_string());
+        macvlan.mac_address = Some("52:54:00:12:34:56".to_string());
+        let config = VMConfig {
+            name: "web-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 1,
+            memory_mb: 1024,
+            os_type: "linux".to_string(),
+            volumes: vec![volume("root", DiskBusType::Virtio, DiskDeviceType::Disk)],
+            networks: vec![macvlan, network("backup", "virtio")],
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert_eq!(xml.matches("<interface type='bridge'>").count(), 1);
+        assert!(xml.contains(
+            "<interface type='direct'>\n      <mac address='52:54:00:12:34:56'/>\n      <source dev='br-fast' mode='bridge'/>"
+        ));
+    }
+
+    #[test]
+    fn test_xml_interface_mtu_defaults_to_1500() {
+        let mut jumbo = network("storage", "virtio");
+        jumbo.mtu = Some(9000);
+        let config = VMConfig {
+            name: "web-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 1,
+            memory_mb: 1024,
+            os_type: "linux".to_string(),
+            volumes: vec![volume("root", DiskBusType::Virtio, DiskDeviceType::Disk)],
+            networks: vec![jumbo, network("backup", "virtio")],
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        assert!(xml.contains("<source bridge='br-storage'/>\n      <model type='virtio'/>\n      <mtu size='9000'/>"));
+        assert!(xml.contains("<source bridge='br-backup'/>\n      <model type='virtio'/>\n      <mtu size='1500'/>"));
+    }
+
+    #[test]
+    fn test_xml_interface_bandwidth() {
+        let mut limited = network("prod", "virtio");
+        limited.bandwidth = NicBandwidth {
+            inbound_kbps: Some(1000),
+            outbound_kbps: None,
+        };
+        let config = VMConfig {
+            name: "web-1".to_string(),
+            uuid: "vm-1".to_string(),
+            vcpu: 1,
+            memory_mb: 1024,
+            os_type: "linux".to_string(),
+            volumes: vec![volume("root", DiskBusType::Virtio, DiskDeviceType::Disk)],
+            networks: vec![limited, network("backup", "virtio")],
+            firmware: FirmwareType::Bios,
+            cloud_init: None,
+            tpm: false,
+            safe_mode: false,
+            boot_menu: false,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: DEFAULT_ARCH.to_string(),
+            machine_type: None,
+            emulator: None,
+            graphics: GraphicsType::Vnc,
+            console_password: None,
+        };
+
+        let xml = HypervisorManager::generate_vm_xml(&config).unwrap();
+        // 只有设置了限速的网卡带 bandwidth，未设置的方向不输出
+        assert_eq!(xml.matches("<bandwidth>").count(), 1);
+        assert!(xml.contains("<bandwidth><inbound average='1000'/></bandwidth>"));
+
+        let network: NetworkConfig = serde_json::from_value(serde_json::json!({
+            "network_id": "net-1",
+            "model": "virtio",
+            "vlan_id": null,
+            "outbound_kbps": 500
+        }))
+        .unwrap();
+        assert_eq!(network.vlan_id, Some(None));
+        assert_eq!(network.bandwidth.outbound_kbps, Some(500));
+        assert_eq!(network.bandwidth.inbound_kbps, None);
+    }
+
+    #[test]
+    fn test_parse_guest_interfaces() {
+        let ret = serde_json::json!([
+            {
+                "name": "lo",
+                "hardware-address": "00:00:00:00:00:00",
+                "ip-addresses": [
+                    { "ip-address-type": "ipv4", "ip-address": "127.0.0.1", "prefix": 8 },
+                    { "ip-address-type": "ipv6", "ip-address": "::1", "prefix": 128 }
+                ]
+            },
+            {
+                "name": "eth0",
+                "hardware-address": "52:54:00:12:34:56",
+                "ip-addresses": [
+                    { "ip-address-type": "ipv4", "ip-address": "10.0.0.5", "prefix": 24 },
+                    { "ip-address-type": "ipv4", "ip-address": "192.168.1.9", "prefix": 24 },
+                    { "ip-address-type": "ipv6", "ip-address": "fe80::5054:ff:fe12:3456", "prefix": 64 }
+                ]
+            },
+            { "name": "eth1", "hardware-address": "52:54:00:12:34:57" }
+        ]);
+
+        let interfaces = parse_guest_interfaces(&ret);
+        assert_eq!(
+            interfaces,
+            vec![GuestNetworkInterface {
+                name: "eth0".to_string(),
+                mac_address: Some("52:54:00:12:34:56".to_string()),
+                ipv4_addresses: vec!["10.0.0.5/24".to_string(), "192.168.1.9/24".to_string()],
+                ipv6_addresses: vec!["fe80::5054:ff:fe12:3456/64".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_default_migration_flags() {
+        let shared = default_migration_flags(MigrationStorageMode::SharedStorage);
+        assert_eq!(shared & 512, 512);
+        assert_eq!(shared & 64, 0);
+
+        let copy = default_migration_flags(MigrationStorageMode::CopyStorage);
+        assert_eq!(copy & 64, 64);
+        assert_eq!(copy & 512, 0);
+        assert_eq!(copy & !64, shared & !512);
+    }
+}
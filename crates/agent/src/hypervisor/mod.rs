@@ -2,13 +2,19 @@
 /// 
 /// 与 libvirt/QEMU/KVM 交互
 
+pub mod cloud_init;
+pub mod driver;
+mod ffi;
 pub mod manager;
 
+pub use driver::Hypervisor;
 pub use manager::{
     HypervisorManager,
+    MigrationOptions,
     VMConfig,
     VolumeConfig,
     NetworkConfig,
+    DEFAULT_ARCH,
 };
 
 pub use common::ws_rpc::types::{DiskBusType, DiskDeviceType};
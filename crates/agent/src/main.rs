@@ -2,9 +2,11 @@
 /// 
 /// 节点代理程序，运行在宿主机上，负责执行虚拟化操作
 
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{info, warn};
 
 mod config;
 mod hypervisor;
@@ -14,7 +16,7 @@ mod node;
 mod storage;
 mod ws;
 
-use ws::{WsClient, RpcHandlerRegistry};
+use ws::{DeadLetterQueue, WsClient, RpcHandlerRegistry};
 use node::NodeManager;
 
 #[tokio::main]
@@ -38,6 +40,7 @@ async fn main() -> anyhow::Result<()> {
     // 加载配置
     dotenvy::dotenv().ok();
     let cfg = config::Config::from_env()?;
+    cfg.validate()?;
     info!("✅ 配置加载成功");
 
     // 初始化组件
@@ -46,24 +49,37 @@ async fn main() -> anyhow::Result<()> {
 
     // 初始化管理器
     info!("🔧 初始化 hypervisor 管理器...");
-    let hypervisor = Arc::new(hypervisor::HypervisorManager::new()?);
+    let hypervisor = Arc::new(
+        hypervisor::HypervisorManager::connect(Duration::from_secs(cfg.libvirt_connect_timeout)).await,
+    );
     
-    info!("💾 初始化存储管理器...");
-    let storage = Arc::new(storage::StorageManager::new());
+    info!("💾 初始化存储管理器 (最大并发下载: {})...", cfg.max_concurrent_downloads);
+    let storage = Arc::new(storage::StorageManager::new(
+        cfg.max_concurrent_downloads,
+        cfg.local_source_dirs.iter().map(PathBuf::from).collect(),
+        storage::CommandPolicy {
+            timeout: Duration::from_secs(cfg.storage_command_timeout),
+            convert_timeout: Duration::from_secs(cfg.storage_convert_timeout),
+            retries: cfg.storage_command_retries,
+            ..Default::default()
+        },
+    ));
     
-    // 从环境变量获取网络 provider 接口
-    let provider_interface = std::env::var("NETWORK_PROVIDER_INTERFACE")
-        .unwrap_or_else(|_| "eth0".to_string());
-    info!("🌐 初始化网络管理器 (provider: {})...", provider_interface);
-    let network = Arc::new(network::NetworkManager::new(provider_interface));
+    let provider_interface = cfg.network_provider_interface.clone();
+    let bridge_naming = common::utils::BridgeNaming::new(cfg.bridge_name_prefix.clone())
+        .map_err(|e| anyhow::anyhow!("Bridge 命名配置无效: {}", e))?;
+    info!("🌐 初始化网络管理器 (provider: {}, bridge 前缀: {})...", provider_interface, bridge_naming.prefix());
+    let network = Arc::new(network::NetworkManager::new(provider_interface, bridge_naming));
 
     // 创建 RPC 处理器注册表
-    let handler_registry = Arc::new(RwLock::new(RpcHandlerRegistry::new(
+    let mut registry = RpcHandlerRegistry::new(
         hypervisor.clone(),
         storage.clone(),
         network.clone(),
-    )));
-    info!("✅ RPC 处理器已初始化");
+    );
+    registry.set_ip_conflict_check(cfg.ip_conflict_check);
+    registry.set_vlan_inference(cfg.vlan_inference);
+    registry.set_encryption_key_dir(PathBuf::from(&cfg.encryption_key_dir));
 
     // 创建节点管理器
     let hostname = hostname::get()
@@ -71,22 +87,38 @@ async fn main() -> anyhow::Result<()> {
         .and_then(|h| h.into_string().ok())
         .unwrap_or_else(|| "unknown".to_string());
     
-    // 获取本机 IP 地址（简化处理，实际应该更智能）
-    let ip_address = std::env::var("NODE_IP")
-        .unwrap_or_else(|_| "127.0.0.1".to_string());
-
     let node_manager = NodeManager::new(
         cfg.node_id.clone(),
         hostname,
-        ip_address,
+        cfg.node_ip.clone(),
     );
+    registry.set_node_manager(node_manager.clone());
+    let handler_registry = Arc::new(RwLock::new(registry));
+    info!("✅ RPC 处理器已初始化");
+
+    // 通知死信队列（断线期间发送失败的通知，重连后重放）
+    let dead_letters = Arc::new(DeadLetterQueue::new(cfg.dead_letter_path.clone()));
+    info!("📮 通知死信队列: {}", cfg.dead_letter_path);
 
     // 创建 WebSocket 客户端
-    let ws_client = WsClient::new(
+    let mut ws_client = WsClient::new(
         cfg.server_ws_url.clone(),
         node_manager,
         handler_registry,
+        dead_letters,
     );
+    ws_client.set_vm_metrics_interval(cfg.vm_metrics_interval);
+    ws_client.set_node_metrics_interval(cfg.node_metrics_interval);
+    ws_client.set_rpc_codec(cfg.rpc_codec);
+    ws_client.set_compress_threshold(cfg.rpc_compress_threshold);
+    ws_client.set_reconnect_backoff(cfg.reconnect_base_secs, cfg.reconnect_max_secs);
+    ws_client.set_auth_token(cfg.auth_token.clone());
+    if cfg.uses_tls() {
+        if cfg.tls.insecure_skip_verify {
+            warn!("⚠️ 已跳过 Server 证书校验，仅可用于开发环境");
+        }
+        ws_client.set_tls_connector(ws::tls::build_connector(&cfg.tls)?);
+    }
 
     info!("🎯 连接到 Server: {}", cfg.server_ws_url);
     info!("📌 节点 ID: {}", cfg.node_id);
@@ -3,6 +3,8 @@
 /// 收集节点资源使用情况并暴露 Prometheus 指标
 
 pub mod collector;
+pub mod vm;
 
 pub use collector::MetricsCollector;
+pub use vm::collect_vm_metrics;
 
@@ -0,0 +1,48 @@
+/// 虚拟机运行时指标
+///
+/// 定期读取节点上运行中虚拟机的累计统计，作为 vm_metrics 通知推送给 Server
+use common::ws_rpc::types::VmMetricsReport;
+use common::Result;
+use tracing::debug;
+
+use crate::hypervisor::Hypervisor;
+
+/// 采集节点上所有运行中虚拟机的统计，单台读取失败时跳过
+pub async fn collect_vm_metrics(hypervisor: &dyn Hypervisor, node_id: &str) -> Result<VmMetricsReport> {
+    let mut vms = Vec::new();
+    for vm in hypervisor.list_vms().await? {
+        if vm.state != "running" {
+            continue;
+        }
+        match hypervisor.get_vm_stats(&vm.id).await {
+            Ok(stats) => vms.push(stats),
+            // 采集期间虚拟机可能恰好关机或被删除
+            Err(e) => debug!("读取虚拟机 {} 统计失败: {}", vm.id, e),
+        }
+    }
+
+    Ok(VmMetricsReport {
+        node_id: node_id.to_string(),
+        timestamp: chrono::Utc::now().timestamp(),
+        vms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hypervisor::driver::mock::MockHypervisor;
+
+    #[tokio::test]
+    async fn test_collect_vm_metrics_only_running() {
+        let hypervisor = MockHypervisor::new()
+            .with_vm("vm-1", "web", "running")
+            .with_vm("vm-2", "db", "shutoff");
+
+        let report = collect_vm_metrics(&hypervisor, "node-1").await.unwrap();
+        assert_eq!(report.node_id, "node-1");
+        assert_eq!(report.vms.len(), 1);
+        assert_eq!(report.vms[0].vm_id, "vm-1");
+        assert!(report.vms[0].rss_kb > 0);
+    }
+}
@@ -0,0 +1,81 @@
+/// ARP 探测
+///
+/// 虚拟机启动前在目标 Bridge 上对分配的 IP 发送 ARP 请求，
+/// 发现被客户机静态配置或外部设备占用的地址（数据库无法感知此类冲突）
+
+use common::Result;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::debug;
+
+/// 单次探测发送的 ARP 请求数
+const ARP_PROBE_COUNT: &str = "2";
+
+/// 单次探测的等待时间（秒）
+const ARP_PROBE_WAIT_SECS: u64 = 2;
+
+/// 在指定 Bridge 上探测 IP，返回应答方的 MAC 地址列表（小写，已去重）
+///
+/// 无应答时返回空列表
+pub async fn probe_ip(bridge_name: &str, ip_address: &str) -> Result<Vec<String>> {
+    let wait = ARP_PROBE_WAIT_SECS.to_string();
+    let mut cmd = Command::new("arping");
+    cmd.args(["-c", ARP_PROBE_COUNT, "-w", &wait, "-I", bridge_name, ip_address])
+        .kill_on_drop(true);
+
+    // 额外留出进程启动与退出的时间
+    let output = tokio::time::timeout(Duration::from_secs(ARP_PROBE_WAIT_SECS + 3), cmd.output())
+        .await
+        .map_err(|_| common::Error::Internal(format!("arping 执行超时: {}", ip_address)))?
+        .map_err(|e| common::Error::Internal(format!("执行 arping 失败: {}", e)))?;
+
+    // arping 无应答时以非零状态退出，不视为错误
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    debug!("arping {} on {}: {}", ip_address, bridge_name, stdout.trim());
+
+    Ok(parse_arping_replies(&stdout))
+}
+
+/// 解析 arping 输出中的应答 MAC
+///
+/// 例如：`Unicast reply from 10.0.0.5 [52:54:00:AA:BB:CC]  0.612ms`
+fn parse_arping_replies(output: &str) -> Vec<String> {
+    let mut macs: Vec<String> = Vec::new();
+
+    for line in output.lines().filter(|line| line.contains("reply from")) {
+        let mac = line
+            .split_once('[')
+            .and_then(|(_, rest)| rest.split_once(']'))
+            .map(|(mac, _)| mac.trim().to_lowercase());
+
+        if let Some(mac) = mac {
+            if !mac.is_empty() && !macs.contains(&mac) {
+                macs.push(mac);
+            }
+        }
+    }
+
+    macs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_arping_replies() {
+        let output = "\
+ARPING 10.0.0.5 from 10.0.0.1 br-vlan100
+Unicast reply from 10.0.0.5 [52:54:00:AA:BB:CC]  0.612ms
+Unicast reply from 10.0.0.5 [52:54:00:aa:bb:cc]  0.580ms
+Unicast reply from 10.0.0.5 [00:11:22:33:44:55]  0.701ms
+Sent 2 probes (1 broadcast(s))
+Received 3 response(s)
+";
+        assert_eq!(
+            parse_arping_replies(output),
+            vec!["52:54:00:aa:bb:cc".to_string(), "00:11:22:33:44:55".to_string()]
+        );
+        assert!(parse_arping_replies("Sent 2 probes\nReceived 0 response(s)\n").is_empty());
+    }
+}
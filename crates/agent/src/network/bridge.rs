@@ -7,19 +7,63 @@
 /// 2. Provider 接口的 VLAN 子接口（例如：eth0.100）连接到对应的 Bridge
 /// 3. VM 的虚拟网口（tap 设备）连接到对应的 Bridge
 
+use common::utils::BridgeNaming;
 use common::Result;
 use std::process::Command;
 use tracing::{info, warn};
 
+/// 单次创建网络过程中新建或变更的资源，失败时据此回滚
+#[derive(Debug, Default)]
+struct CreatedResources {
+    /// 本次新建的 Bridge
+    bridge: Option<String>,
+    /// 本次新建的 VLAN 子接口
+    vlan_interface: Option<String>,
+    /// 本次加入 Bridge 的接口
+    enslaved_interface: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum RollbackStep {
+    DetachInterface(String),
+    DeleteInterface(String),
+    DeleteBridge(String),
+}
+
+impl CreatedResources {
+    /// 按创建的逆序生成回滚步骤
+    ///
+    /// 即将被删除的子接口无需先移出 Bridge；
+    /// 已存在的 Provider 接口只移出本次新加入的 Bridge 关系，不会被删除
+    fn rollback_steps(&self) -> Vec<RollbackStep> {
+        let mut steps = Vec::new();
+        if let Some(interface) = &self.enslaved_interface {
+            if self.vlan_interface.as_ref() != Some(interface) {
+                steps.push(RollbackStep::DetachInterface(interface.clone()));
+            }
+        }
+        if let Some(interface) = &self.vlan_interface {
+            steps.push(RollbackStep::DeleteInterface(interface.clone()));
+        }
+        if let Some(bridge) = &self.bridge {
+            steps.push(RollbackStep::DeleteBridge(bridge.clone()));
+        }
+        steps
+    }
+}
+
 pub struct LinuxBridge {
     /// Provider 网络接口（例如：eth0）
     provider_interface: String,
+    /// Bridge 命名规则
+    naming: BridgeNaming,
 }
 
 impl LinuxBridge {
-    pub fn new(provider_interface: String) -> Self {
+    pub fn new(provider_interface: String, naming: BridgeNaming) -> Self {
         Self {
             provider_interface,
+            naming,
         }
     }
 
@@ -29,13 +73,34 @@ impl LinuxBridge {
     /// 1. 检查并创建 VLAN Bridge（例如：br-vlan100）
     /// 2. 检查并创建 Provider 接口的 VLAN 子接口（例如：eth0.100）
     /// 3. 将 VLAN 子接口添加到 Bridge
-    pub async fn create_vlan_network(&self, vlan_id: u32, bridge_name: &str) -> Result<()> {
-        info!("创建 VLAN {} 网络，Bridge: {}", vlan_id, bridge_name);
+    /// 4. 为 VLAN 子接口和 Bridge 设置 MTU
+    ///
+    /// 任一步骤失败时回滚本次调用新建的接口和 Bridge，已存在的保持不变
+    pub async fn create_vlan_network(&self, vlan_id: u32, bridge_name: &str, mtu: u32) -> Result<()> {
+        info!("创建 VLAN {} 网络，Bridge: {}, MTU: {}", vlan_id, bridge_name, mtu);
+
+        let mut created = CreatedResources::default();
+        if let Err(e) = self.build_vlan_network(vlan_id, bridge_name, mtu, &mut created).await {
+            self.rollback(&created);
+            return Err(e);
+        }
+
+        info!("VLAN {} 网络创建成功", vlan_id);
+        Ok(())
+    }
 
+    async fn build_vlan_network(
+        &self,
+        vlan_id: u32,
+        bridge_name: &str,
+        mtu: u32,
+        created: &mut CreatedResources,
+    ) -> Result<()> {
         // 1. 检查 Bridge 是否存在
         if !self.bridge_exists(bridge_name).await {
             info!("创建 Bridge: {}", bridge_name);
             self.create_bridge(bridge_name)?;
+            created.bridge = Some(bridge_name.to_string());
         } else {
             info!("Bridge {} 已存在", bridge_name);
         }
@@ -45,6 +110,7 @@ impl LinuxBridge {
         if !self.interface_exists(&vlan_interface)? {
             info!("创建 VLAN 子接口: {}", vlan_interface);
             self.create_vlan_interface(&vlan_interface, vlan_id)?;
+            created.vlan_interface = Some(vlan_interface.clone());
         } else {
             info!("VLAN 子接口 {} 已存在", vlan_interface);
         }
@@ -53,15 +119,19 @@ impl LinuxBridge {
         if !self.interface_in_bridge(bridge_name, &vlan_interface)? {
             info!("将 {} 添加到 Bridge {}", vlan_interface, bridge_name);
             self.add_interface_to_bridge(bridge_name, &vlan_interface)?;
+            created.enslaved_interface = Some(vlan_interface.clone());
         } else {
             info!("接口 {} 已在 Bridge {} 中", vlan_interface, bridge_name);
         }
 
-        // 4. 确保 Bridge 和 VLAN 子接口处于 UP 状态
+        // 4. 设置 MTU：子接口先于 Bridge，Bridge 的 MTU 不能超过其端口
+        self.set_mtu(&vlan_interface, mtu)?;
+        self.set_mtu(bridge_name, mtu)?;
+
+        // 5. 确保 Bridge 和 VLAN 子接口处于 UP 状态
         self.set_interface_up(&vlan_interface)?;
         self.set_interface_up(bridge_name)?;
 
-        info!("VLAN {} 网络创建成功", vlan_id);
         Ok(())
     }
 
@@ -70,14 +140,34 @@ impl LinuxBridge {
     /// 步骤：
     /// 1. 检查并创建 Bridge（例如：br-default）
     /// 2. 将 Provider 接口直接添加到 Bridge
-    /// 3. 确保 Bridge 和 Provider 接口处于 UP 状态
-    pub async fn create_no_vlan_network(&self, bridge_name: &str) -> Result<()> {
-        info!("创建无 VLAN 网络，Bridge: {}", bridge_name);
+    /// 3. 为 Bridge 设置 MTU（Provider 接口的 MTU 由宿主机配置，不做修改）
+    /// 4. 确保 Bridge 和 Provider 接口处于 UP 状态
+    ///
+    /// 失败时同样回滚本次调用新建的 Bridge
+    pub async fn create_no_vlan_network(&self, bridge_name: &str, mtu: u32) -> Result<()> {
+        info!("创建无 VLAN 网络，Bridge: {}, MTU: {}", bridge_name, mtu);
+
+        let mut created = CreatedResources::default();
+        if let Err(e) = self.build_no_vlan_network(bridge_name, mtu, &mut created).await {
+            self.rollback(&created);
+            return Err(e);
+        }
+
+        info!("无 VLAN 网络创建成功");
+        Ok(())
+    }
 
+    async fn build_no_vlan_network(
+        &self,
+        bridge_name: &str,
+        mtu: u32,
+        created: &mut CreatedResources,
+    ) -> Result<()> {
         // 1. 检查 Bridge 是否存在
         if !self.bridge_exists(bridge_name).await {
             info!("创建 Bridge: {}", bridge_name);
             self.create_bridge(bridge_name)?;
+            created.bridge = Some(bridge_name.to_string());
         } else {
             info!("Bridge {} 已存在", bridge_name);
         }
@@ -86,18 +176,38 @@ impl LinuxBridge {
         if !self.interface_in_bridge(bridge_name, &self.provider_interface)? {
             info!("将 {} 添加到 Bridge {}", self.provider_interface, bridge_name);
             self.add_interface_to_bridge(bridge_name, &self.provider_interface)?;
+            created.enslaved_interface = Some(self.provider_interface.clone());
         } else {
             info!("接口 {} 已在 Bridge {} 中", self.provider_interface, bridge_name);
         }
 
-        // 3. 确保 Bridge 和 Provider 接口处于 UP 状态
+        // 3. 设置 Bridge 的 MTU
+        self.set_mtu(bridge_name, mtu)?;
+
+        // 4. 确保 Bridge 和 Provider 接口处于 UP 状态
         self.set_interface_up(&self.provider_interface)?;
         self.set_interface_up(bridge_name)?;
 
-        info!("无 VLAN 网络创建成功");
         Ok(())
     }
 
+    /// 回滚创建失败时已完成的步骤，回滚本身失败只记录日志
+    fn rollback(&self, created: &CreatedResources) {
+        for step in created.rollback_steps() {
+            warn!("网络创建失败，回滚: {:?}", step);
+            let result = match &step {
+                RollbackStep::DetachInterface(interface) => {
+                    self.remove_interface_from_bridge("", interface)
+                }
+                RollbackStep::DeleteInterface(interface) => self.delete_interface(interface),
+                RollbackStep::DeleteBridge(bridge) => self.delete_bridge(bridge),
+            };
+            if let Err(e) = result {
+                warn!("回滚步骤 {:?} 失败: {}", step, e);
+            }
+        }
+    }
+
     /// 删除无 VLAN 网络
     pub async fn delete_no_vlan_network(&self, bridge_name: &str) -> Result<()> {
         info!("删除无 VLAN 网络，Bridge: {}", bridge_name);
@@ -330,6 +440,24 @@ impl LinuxBridge {
     }
 
     /// 设置接口为 UP 状态
+    /// 设置接口 MTU，超过下层设备 MTU 时内核会拒绝
+    fn set_mtu(&self, interface: &str, mtu: u32) -> Result<()> {
+        let output = Command::new("ip")
+            .args(["link", "set", interface, "mtu", &mtu.to_string()])
+            .output()
+            .map_err(|e| common::Error::Internal(format!("执行命令失败: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(common::Error::Internal(format!(
+                "设置接口 {} 的 MTU 为 {} 失败: {}",
+                interface, mtu, stderr
+            )));
+        }
+
+        Ok(())
+    }
+
     fn set_interface_up(&self, interface: &str) -> Result<()> {
         let output = Command::new("ip")
             .args(["link", "set", interface, "up"])
@@ -346,11 +474,13 @@ impl LinuxBridge {
     }
 
     /// 生成 Bridge 名称（根据 VLAN ID）
-    pub fn generate_bridge_name(vlan_id: Option<u32>) -> String {
-        match vlan_id {
-            Some(id) => format!("br-vlan{}", id),
-            None => "br-default".to_string(),
-        }
+    pub fn generate_bridge_name(&self, vlan_id: Option<u32>) -> String {
+        self.naming.bridge_name(vlan_id)
+    }
+
+    /// 从 Bridge 名称推断 VLAN ID
+    pub fn parse_vlan_id(&self, bridge_name: &str) -> Option<u32> {
+        self.naming.parse_vlan_id(bridge_name)
     }
 }
 
@@ -360,9 +490,66 @@ mod tests {
 
     #[test]
     fn test_generate_bridge_name() {
-        assert_eq!(LinuxBridge::generate_bridge_name(Some(100)), "br-vlan100");
-        assert_eq!(LinuxBridge::generate_bridge_name(Some(200)), "br-vlan200");
-        assert_eq!(LinuxBridge::generate_bridge_name(None), "br-default");
+        let bridge = LinuxBridge::new("eth0".to_string(), BridgeNaming::default());
+        assert_eq!(bridge.generate_bridge_name(Some(100)), "br-vlan100");
+        assert_eq!(bridge.generate_bridge_name(Some(200)), "br-vlan200");
+        assert_eq!(bridge.generate_bridge_name(None), "br-default");
+    }
+
+    #[test]
+    fn test_rollback_nothing_created() {
+        assert!(CreatedResources::default().rollback_steps().is_empty());
+    }
+
+    #[test]
+    fn test_rollback_new_bridge_and_vlan_interface() {
+        // 子接口加入 Bridge 后失败：删除子接口即可，无需单独移出
+        let created = CreatedResources {
+            bridge: Some("br-vlan100".to_string()),
+            vlan_interface: Some("eth0.100".to_string()),
+            enslaved_interface: Some("eth0.100".to_string()),
+        };
+        assert_eq!(
+            created.rollback_steps(),
+            vec![
+                RollbackStep::DeleteInterface("eth0.100".to_string()),
+                RollbackStep::DeleteBridge("br-vlan100".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rollback_keeps_existing_resources() {
+        // Bridge 已存在、子接口创建后加入 Bridge 失败：只删除新建的子接口
+        let created = CreatedResources {
+            vlan_interface: Some("eth0.100".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            created.rollback_steps(),
+            vec![RollbackStep::DeleteInterface("eth0.100".to_string())]
+        );
+
+        // 无 VLAN 网络：Provider 接口只移出 Bridge，不删除
+        let created = CreatedResources {
+            bridge: Some("br-default".to_string()),
+            enslaved_interface: Some("eth0".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            created.rollback_steps(),
+            vec![
+                RollbackStep::DetachInterface("eth0".to_string()),
+                RollbackStep::DeleteBridge("br-default".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_vlan_id_with_custom_prefix() {
+        let bridge = LinuxBridge::new("eth0".to_string(), BridgeNaming::new("vmbr").unwrap());
+        assert_eq!(bridge.parse_vlan_id(&bridge.generate_bridge_name(Some(100))), Some(100));
+        assert_eq!(bridge.parse_vlan_id(&bridge.generate_bridge_name(None)), None);
     }
 }
 
@@ -0,0 +1,382 @@
+/// 托管 DHCP/DNS 实现
+///
+/// 为开启 DHCP 的网络在 Bridge 上启动一个 dnsmasq 实例，只下发 Server 分配的静态租约，
+/// 适合没有外部 DHCP 服务器的隔离网络
+///
+/// 工作原理：
+/// 1. 网关地址配置到 Bridge 上，dnsmasq 以 bind-interfaces 只监听该地址
+/// 2. dhcp-range 使用 static 模式，只应答 hosts 文件中登记过 MAC 的虚拟机，
+///    多个节点同时运行时应答内容一致
+/// 3. 租约变化时原子替换 hosts 文件后发送 SIGHUP，dnsmasq 重新读取而不中断服务
+/// 4. 虚拟机名称作为主机名登记，同网络内可通过名称解析
+///
+/// 每个 Bridge 的文件位于 `/var/lib/easy-vm-cloud/dnsmasq/<bridge>.{conf,hosts,pid,leases}`
+
+use common::ws_rpc::DhcpConfig;
+use common::Result;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+const STATE_DIR: &str = "/var/lib/easy-vm-cloud/dnsmasq";
+
+/// 配置文件中记录 Bridge 地址的注释前缀，停止服务时据此删除地址
+const ADDRESS_MARKER: &str = "# address=";
+
+pub struct DhcpServer {
+    state_dir: PathBuf,
+}
+
+impl DhcpServer {
+    pub fn new() -> Self {
+        Self {
+            state_dir: PathBuf::from(STATE_DIR),
+        }
+    }
+
+    /// 启动网络的 DHCP 服务，已在运行时刷新租约（配置变化时重启）
+    pub async fn start(&self, config: &DhcpConfig) -> Result<()> {
+        let subnet = Subnet::parse(&config.cidr, &config.gateway)?;
+        validate_bridge(&config.bridge_name)?;
+        let bridge = config.bridge_name.as_str();
+        info!("启动 Bridge {} 的 DHCP 服务（{} 条静态租约）", bridge, config.leases.len());
+
+        std::fs::create_dir_all(&self.state_dir)
+            .map_err(|e| common::Error::Internal(format!("创建 dnsmasq 目录失败: {}", e)))?;
+
+        ip(&["addr", "replace", &subnet.gateway_cidr(), "dev", bridge], "配置 Bridge 地址失败")?;
+        write_atomic(&self.path(bridge, "hosts"), &render_hosts(config))?;
+
+        let conf = render_conf(bridge, &subnet, &self.state_dir);
+        let conf_path = self.path(bridge, "conf");
+        let conf_changed = std::fs::read_to_string(&conf_path).ok().as_deref() != Some(conf.as_str());
+        if conf_changed {
+            write_atomic(&conf_path, &conf)?;
+        }
+
+        match self.running_pid(bridge) {
+            Some(pid) if !conf_changed => {
+                debug!("dnsmasq（pid {}）重新读取 {} 的租约", pid, bridge);
+                signal(pid, "HUP")
+            }
+            Some(pid) => {
+                info!("Bridge {} 的 DHCP 配置已变化，重启 dnsmasq", bridge);
+                terminate(pid).await?;
+                self.spawn(&conf_path)
+            }
+            None => self.spawn(&conf_path),
+        }
+    }
+
+    /// 刷新租约，本节点未运行该网络的 DHCP 服务时不做任何事
+    ///
+    /// 服务在节点上首次启动该网络的虚拟机时才会启动
+    pub async fn reload(&self, config: &DhcpConfig) -> Result<()> {
+        validate_bridge(&config.bridge_name)?;
+        if self.running_pid(&config.bridge_name).is_none() {
+            debug!("Bridge {} 未运行 DHCP 服务，跳过刷新", config.bridge_name);
+            return Ok(());
+        }
+        self.start(config).await
+    }
+
+    /// 停止 DHCP 服务，删除 Bridge 上的网关地址和相关文件
+    pub async fn stop(&self, bridge: &str) -> Result<()> {
+        validate_bridge(bridge)?;
+        let conf_path = self.path(bridge, "conf");
+        let address = std::fs::read_to_string(&conf_path)
+            .ok()
+            .and_then(|conf| conf.lines().find_map(|l| l.strip_prefix(ADDRESS_MARKER).map(str::to_string)));
+
+        if let Some(pid) = self.running_pid(bridge) {
+            info!("停止 Bridge {} 的 dnsmasq（pid {}）", bridge, pid);
+            terminate(pid).await?;
+        }
+        if let Some(address) = address {
+            // Bridge 可能已被删除
+            if let Err(e) = ip(&["addr", "del", &address, "dev", bridge], "删除 Bridge 地址失败") {
+                debug!("{}", e);
+            }
+        }
+        for ext in ["conf", "hosts", "pid", "leases"] {
+            let path = self.path(bridge, ext);
+            if path.exists() {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    warn!("删除 {} 失败: {}", path.display(), e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, bridge: &str, ext: &str) -> PathBuf {
+        self.state_dir.join(format!("{}.{}", bridge, ext))
+    }
+
+    /// pid 文件对应的进程仍是 dnsmasq 时返回 pid
+    fn running_pid(&self, bridge: &str) -> Option<u32> {
+        let pid: u32 = std::fs::read_to_string(self.path(bridge, "pid")).ok()?.trim().parse().ok()?;
+        let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+        (comm.trim() == "dnsmasq").then_some(pid)
+    }
+
+    /// dnsmasq 默认以守护进程运行，父进程在监听就绪后退出
+    fn spawn(&self, conf_path: &Path) -> Result<()> {
+        let output = Command::new("dnsmasq")
+            .arg(format!("--conf-file={}", conf_path.display()))
+            .output()
+            .map_err(|e| common::Error::Internal(format!("执行 dnsmasq 失败: {}", e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(common::Error::Internal(format!("启动 dnsmasq 失败: {}", stderr.trim())));
+        }
+        Ok(())
+    }
+}
+
+/// 网络的 IPv4 网段与网关
+#[derive(Debug, PartialEq, Eq)]
+struct Subnet {
+    network: Ipv4Addr,
+    prefix: u8,
+    gateway: Ipv4Addr,
+}
+
+impl Subnet {
+    fn parse(cidr: &str, gateway: &str) -> Result<Self> {
+        let invalid = || common::Error::InvalidArgument(format!("DHCP 只支持 IPv4 网段: {}", cidr));
+        let (address, prefix) = cidr.split_once('/').ok_or_else(invalid)?;
+        let address: Ipv4Addr = address.parse().map_err(|_| invalid())?;
+        let prefix: u8 = prefix.parse().ok().filter(|p| *p <= 30).ok_or_else(invalid)?;
+        let gateway: Ipv4Addr = gateway
+            .parse()
+            .map_err(|_| common::Error::InvalidArgument(format!("无效的网关地址: {}", gateway)))?;
+
+        let subnet = Self {
+            network: Ipv4Addr::from(u32::from(address) & mask(prefix)),
+            prefix,
+            gateway,
+        };
+        if u32::from(gateway) & mask(prefix) != u32::from(subnet.network) {
+            return Err(common::Error::InvalidArgument(format!("网关 {} 不在网段 {} 内", gateway, cidr)));
+        }
+        Ok(subnet)
+    }
+
+    fn netmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(mask(self.prefix))
+    }
+
+    fn gateway_cidr(&self) -> String {
+        format!("{}/{}", self.gateway, self.prefix)
+    }
+}
+
+fn mask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)
+}
+
+fn render_conf(bridge: &str, subnet: &Subnet, state_dir: &Path) -> String {
+    let file = |ext: &str| state_dir.join(format!("{}.{}", bridge, ext)).display().to_string();
+    [
+        "# 由 easy-vm-cloud agent 生成，请勿手动修改".to_string(),
+        format!("{}{}", ADDRESS_MARKER, subnet.gateway_cidr()),
+        format!("interface={}", bridge),
+        "bind-interfaces".to_string(),
+        "except-interface=lo".to_string(),
+        format!("listen-address={}", subnet.gateway),
+        format!("dhcp-range={},static,{},12h", subnet.network, subnet.netmask()),
+        format!("dhcp-option=option:router,{}", subnet.gateway),
+        format!("dhcp-option=option:dns-server,{}", subnet.gateway),
+        format!("dhcp-hostsfile={}", file("hosts")),
+        format!("dhcp-leasefile={}", file("leases")),
+        format!("pid-file={}", file("pid")),
+        "dhcp-authoritative".to_string(),
+        "no-hosts".to_string(),
+        "domain-needed".to_string(),
+        "bogus-priv".to_string(),
+    ]
+    .join("\n")
+        + "\n"
+}
+
+/// dhcp-hostsfile 内容，每行 `mac,ip[,hostname]`，格式无效的租约跳过
+fn render_hosts(config: &DhcpConfig) -> String {
+    let mut hosts = String::new();
+    for lease in &config.leases {
+        if !valid_mac(&lease.mac_address) || lease.ip_address.parse::<Ipv4Addr>().is_err() {
+            warn!("跳过无效的 DHCP 租约: {} -> {}", lease.mac_address, lease.ip_address);
+            continue;
+        }
+        hosts.push_str(&format!("{},{}", lease.mac_address.to_ascii_lowercase(), lease.ip_address));
+        if let Some(hostname) = lease.hostname.as_deref().and_then(sanitize_hostname) {
+            hosts.push(',');
+            hosts.push_str(&hostname);
+        }
+        hosts.push('\n');
+    }
+    hosts
+}
+
+/// 虚拟机名称转换为合法的 DNS 标签：非字母数字替换为 `-`，最长 63 个字符
+fn sanitize_hostname(name: &str) -> Option<String> {
+    let label: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    let label = label.trim_matches('-');
+    let label = label[..label.len().min(63)].trim_end_matches('-');
+    (!label.is_empty()).then(|| label.to_string())
+}
+
+fn valid_mac(mac: &str) -> bool {
+    let parts: Vec<&str> = mac.split(':').collect();
+    parts.len() == 6 && parts.iter().all(|p| p.len() == 2 && p.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Bridge 名称会拼接进配置文件和文件路径，只接受合法的网络接口名
+fn validate_bridge(bridge: &str) -> Result<()> {
+    let valid = !bridge.is_empty()
+        && bridge.len() < 16
+        && bridge.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(common::Error::InvalidArgument(format!("无效的网络接口名: {}", bridge)))
+    }
+}
+
+/// 先写临时文件再 rename，dnsmasq 不会读到写了一半的文件
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, content)
+        .and_then(|_| std::fs::rename(&tmp_path, path))
+        .map_err(|e| common::Error::Internal(format!("写入 {} 失败: {}", path.display(), e)))
+}
+
+fn signal(pid: u32, signal: &str) -> Result<()> {
+    let output = Command::new("kill")
+        .args([format!("-{}", signal), pid.to_string()])
+        .output()
+        .map_err(|e| common::Error::Internal(format!("执行 kill 失败: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(common::Error::Internal(format!("向 dnsmasq 发送 SIG{} 失败: {}", signal, stderr.trim())));
+    }
+    Ok(())
+}
+
+/// 发送 SIGTERM 并等待进程退出（最多 2 秒），重启时新进程才能绑定同一地址
+async fn terminate(pid: u32) -> Result<()> {
+    signal(pid, "TERM")?;
+    for _ in 0..20 {
+        if !Path::new(&format!("/proc/{}", pid)).exists() {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    warn!("dnsmasq（pid {}）未在 2 秒内退出", pid);
+    Ok(())
+}
+
+/// 执行 ip 命令，失败时以 `context: stderr` 作为错误信息
+fn ip(args: &[&str], context: &str) -> Result<()> {
+    let output = Command::new("ip")
+        .args(args)
+        .output()
+        .map_err(|e| common::Error::Internal(format!("执行命令失败: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(common::Error::Internal(format!("{}: {}", context, stderr.trim())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ws_rpc::DhcpLease;
+
+    fn lease(mac: &str, ip: &str, hostname: Option<&str>) -> DhcpLease {
+        DhcpLease {
+            mac_address: mac.to_string(),
+            ip_address: ip.to_string(),
+            hostname: hostname.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_subnet_parse() {
+        let subnet = Subnet::parse("192.168.100.0/24", "192.168.100.1").unwrap();
+        assert_eq!(subnet.network, Ipv4Addr::new(192, 168, 100, 0));
+        assert_eq!(subnet.netmask(), Ipv4Addr::new(255, 255, 255, 0));
+        assert_eq!(subnet.gateway_cidr(), "192.168.100.1/24");
+
+        // 主机位非零的网段按网络地址处理
+        let subnet = Subnet::parse("10.1.2.3/16", "10.1.0.1").unwrap();
+        assert_eq!(subnet.network, Ipv4Addr::new(10, 1, 0, 0));
+
+        assert!(Subnet::parse("2001:db8::/64", "2001:db8::1").is_err());
+        assert!(Subnet::parse("192.168.100.0/24", "192.168.101.1").is_err());
+        assert!(Subnet::parse("192.168.100.0/33", "192.168.100.1").is_err());
+        assert!(Subnet::parse("192.168.100.0", "192.168.100.1").is_err());
+    }
+
+    #[test]
+    fn test_render_conf() {
+        let subnet = Subnet::parse("192.168.100.0/24", "192.168.100.1").unwrap();
+        let conf = render_conf("br-vlan100", &subnet, Path::new("/run/dnsmasq"));
+        let lines: Vec<&str> = conf.lines().collect();
+
+        assert!(lines.contains(&"# address=192.168.100.1/24"));
+        assert!(lines.contains(&"interface=br-vlan100"));
+        assert!(lines.contains(&"bind-interfaces"));
+        assert!(lines.contains(&"listen-address=192.168.100.1"));
+        assert!(lines.contains(&"dhcp-range=192.168.100.0,static,255.255.255.0,12h"));
+        assert!(lines.contains(&"dhcp-option=option:router,192.168.100.1"));
+        assert!(lines.contains(&"dhcp-hostsfile=/run/dnsmasq/br-vlan100.hosts"));
+        assert!(lines.contains(&"pid-file=/run/dnsmasq/br-vlan100.pid"));
+    }
+
+    #[test]
+    fn test_render_hosts() {
+        let config = DhcpConfig {
+            network_id: "net-1".to_string(),
+            bridge_name: "br-vlan100".to_string(),
+            cidr: "192.168.100.0/24".to_string(),
+            gateway: "192.168.100.1".to_string(),
+            leases: vec![
+                lease("52:54:00:AB:CD:EF", "192.168.100.10", Some("Web Server_1")),
+                lease("52:54:00:12:34:56", "192.168.100.11", None),
+                lease("not-a-mac", "192.168.100.12", Some("bad")),
+                lease("52:54:00:12:34:57", "2001:db8::5", Some("v6")),
+                lease("52:54:00:12:34:58", "192.168.100.13", Some("中文")),
+            ],
+        };
+
+        assert_eq!(
+            render_hosts(&config),
+            "52:54:00:ab:cd:ef,192.168.100.10,web-server-1\n\
+             52:54:00:12:34:56,192.168.100.11\n\
+             52:54:00:12:34:58,192.168.100.13\n"
+        );
+    }
+
+    #[test]
+    fn test_sanitize_hostname() {
+        assert_eq!(sanitize_hostname("web-1").as_deref(), Some("web-1"));
+        assert_eq!(sanitize_hostname("-db.prod-").as_deref(), Some("db-prod"));
+        assert_eq!(sanitize_hostname("___"), None);
+        assert_eq!(sanitize_hostname(&"a".repeat(80)).map(|h| h.len()), Some(63));
+    }
+
+    #[test]
+    fn test_validate_bridge() {
+        assert!(validate_bridge("br-vlan100").is_ok());
+        assert!(validate_bridge("br0\ndhcp-script=/tmp/x").is_err());
+        assert!(validate_bridge("../etc").is_err());
+    }
+}
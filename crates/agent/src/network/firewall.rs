@@ -0,0 +1,272 @@
+/// 安全组防火墙实现
+///
+/// 以 nftables 的 bridge 表过滤转发到 VM tap 设备的入站流量，只对 Linux Bridge 网络生效
+/// （OVS 与 macvlan 的流量不经过 bridge netfilter）
+///
+/// 工作原理：
+/// 1. 所有规则位于 `bridge easy_vm_cloud` 表，forward / output 链按出接口名查 `vm_inbound` 映射，
+///    未关联安全组的 tap 设备不受影响
+/// 2. 每个关联了安全组的 tap 设备对应一条 `sg-<tap>` 链：放行已建立连接的回包、ARP、
+///    IPv6 邻居发现和 DHCP 应答，再逐条放行安全组规则，其余流量丢弃
+/// 3. 每次下发都在一个 nft 事务中重建该 tap 的链，规则替换是原子的
+///
+/// 连接跟踪依赖 nf_conntrack_bridge（内核 5.3 及以上）
+
+use common::ws_rpc::{SecurityGroupProtocol, SecurityGroupRule};
+use common::Result;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::info;
+
+const TABLE: &str = "bridge easy_vm_cloud";
+const INBOUND_MAP: &str = "vm_inbound";
+
+pub struct Firewall;
+
+impl Firewall {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// 为 tap 设备下发安全组规则（默认拒绝入站，只放行匹配规则的流量）
+    pub async fn apply(&self, tap: &str, rules: &[SecurityGroupRule]) -> Result<()> {
+        validate_tap(tap)?;
+        // 规则中的 CIDR 会原样拼接进 nft 脚本，不能只依赖服务端的校验
+        for rule in rules {
+            rule.validate()
+                .map_err(|e| common::Error::InvalidArgument(format!("无效的安全组规则: {}", e)))?;
+        }
+        info!("为 {} 下发安全组规则（{} 条）", tap, rules.len());
+        nft(&apply_script(tap, rules)).await
+    }
+
+    /// 删除 tap 设备上的安全组规则，tap 未关联安全组时不做任何事
+    pub async fn remove(&self, tap: &str) -> Result<()> {
+        validate_tap(tap)?;
+        info!("删除 {} 的安全组规则", tap);
+        nft(&remove_script(tap)).await
+    }
+}
+
+/// 表、映射与入口链，每次下发时重复声明，保证首次使用或被手动清空后也能恢复
+fn base_script() -> String {
+    let mut script = String::new();
+    script.push_str(&format!("add table {}\n", TABLE));
+    script.push_str(&format!("add map {} {} {{ type ifname : verdict; }}\n", TABLE, INBOUND_MAP));
+    // 宿主机自身发往 VM 的流量走 output 钩子，与转发流量使用相同的规则
+    for hook in ["forward", "output"] {
+        script.push_str(&format!(
+            "add chain {} {} {{ type filter hook {} priority 0; policy accept; }}\n",
+            TABLE, hook, hook
+        ));
+        script.push_str(&format!("flush chain {} {}\n", TABLE, hook));
+        script.push_str(&format!("add rule {} {} oifname vmap @{}\n", TABLE, hook, INBOUND_MAP));
+    }
+    script
+}
+
+fn chain_name(tap: &str) -> String {
+    format!("sg-{}", tap)
+}
+
+fn apply_script(tap: &str, rules: &[SecurityGroupRule]) -> String {
+    let chain = chain_name(tap);
+    let mut script = base_script();
+    script.push_str(&format!("add chain {} {}\n", TABLE, chain));
+    script.push_str(&format!("flush chain {} {}\n", TABLE, chain));
+
+    let mut rule_lines = vec![
+        "ct state established,related accept".to_string(),
+        "ether type arp accept".to_string(),
+        "icmpv6 type { nd-neighbor-solicit, nd-neighbor-advert, nd-router-advert } accept".to_string(),
+        "udp sport 67 udp dport 68 accept".to_string(),
+        "udp sport 547 udp dport 546 accept".to_string(),
+    ];
+    rule_lines.extend(rules.iter().map(rule_statement));
+    rule_lines.push("drop".to_string());
+    for line in rule_lines {
+        script.push_str(&format!("add rule {} {} {}\n", TABLE, chain, line));
+    }
+
+    script.push_str(&format!(
+        "add element {} {} {{ \"{}\" : jump {} }}\n",
+        TABLE, INBOUND_MAP, tap, chain
+    ));
+    script
+}
+
+/// 先声明再删除，tap 之前是否关联过安全组都能在同一事务中成功执行
+fn remove_script(tap: &str) -> String {
+    let chain = chain_name(tap);
+    let mut script = base_script();
+    script.push_str(&format!("add chain {} {}\n", TABLE, chain));
+    script.push_str(&format!(
+        "add element {} {} {{ \"{}\" : jump {} }}\n",
+        TABLE, INBOUND_MAP, tap, chain
+    ));
+    script.push_str(&format!("delete element {} {} {{ \"{}\" }}\n", TABLE, INBOUND_MAP, tap));
+    script.push_str(&format!("flush chain {} {}\n", TABLE, chain));
+    script.push_str(&format!("delete chain {} {}\n", TABLE, chain));
+    script
+}
+
+/// 单条安全组规则对应的 nft 放行语句
+fn rule_statement(rule: &SecurityGroupRule) -> String {
+    let ipv6 = rule.is_ipv6_source();
+    let mut parts = Vec::new();
+    if let Some(cidr) = &rule.cidr {
+        parts.push(format!("{} saddr {}", if ipv6 { "ip6" } else { "ip" }, cidr));
+    }
+    match rule.protocol {
+        SecurityGroupProtocol::Tcp | SecurityGroupProtocol::Udp => {
+            let proto = if rule.protocol == SecurityGroupProtocol::Tcp { "tcp" } else { "udp" };
+            match (rule.port_from, rule.port_to) {
+                (Some(from), Some(to)) if from != to => parts.push(format!("{} dport {}-{}", proto, from, to)),
+                (Some(port), _) => parts.push(format!("{} dport {}", proto, port)),
+                _ => parts.push(format!("meta l4proto {}", proto)),
+            }
+        }
+        SecurityGroupProtocol::Icmp => parts.push(
+            match (&rule.cidr, ipv6) {
+                (Some(_), true) => "meta l4proto ipv6-icmp",
+                (Some(_), false) => "meta l4proto icmp",
+                (None, _) => "meta l4proto { icmp, ipv6-icmp }",
+            }
+            .to_string(),
+        ),
+        SecurityGroupProtocol::Any => {}
+    }
+    parts.push("accept".to_string());
+    parts.join(" ")
+}
+
+/// tap 名称会拼接进 nft 脚本，只接受合法的网络接口名
+fn validate_tap(tap: &str) -> Result<()> {
+    let valid = !tap.is_empty()
+        && tap.len() < 16
+        && tap.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(common::Error::InvalidArgument(format!("无效的网络接口名: {}", tap)))
+    }
+}
+
+/// 以 `nft -f -` 在一个事务中执行脚本
+async fn nft(script: &str) -> Result<()> {
+    let mut child = Command::new("nft")
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| common::Error::Internal(format!("执行 nft 失败: {}", e)))?;
+
+    // 写完后关闭 stdin，nft 读到 EOF 才会开始执行
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(script.as_bytes())
+            .await
+            .map_err(|e| common::Error::Internal(format!("写入 nft 脚本失败: {}", e)))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| common::Error::Internal(format!("执行 nft 失败: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(common::Error::Internal(format!("nft 执行失败: {}", stderr.trim())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(protocol: SecurityGroupProtocol, ports: (Option<u16>, Option<u16>), cidr: Option<&str>) -> SecurityGroupRule {
+        SecurityGroupRule {
+            protocol,
+            port_from: ports.0,
+            port_to: ports.1,
+            cidr: cidr.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_rule_statement() {
+        use SecurityGroupProtocol::*;
+        assert_eq!(
+            rule_statement(&rule(Tcp, (Some(22), None), Some("10.0.0.0/8"))),
+            "ip saddr 10.0.0.0/8 tcp dport 22 accept"
+        );
+        assert_eq!(rule_statement(&rule(Udp, (Some(1000), Some(2000)), None)), "udp dport 1000-2000 accept");
+        assert_eq!(rule_statement(&rule(Tcp, (Some(80), Some(80)), None)), "tcp dport 80 accept");
+        assert_eq!(rule_statement(&rule(Tcp, (None, None), None)), "meta l4proto tcp accept");
+        assert_eq!(
+            rule_statement(&rule(Icmp, (None, None), Some("2001:db8::/32"))),
+            "ip6 saddr 2001:db8::/32 meta l4proto ipv6-icmp accept"
+        );
+        assert_eq!(rule_statement(&rule(Icmp, (None, None), None)), "meta l4proto { icmp, ipv6-icmp } accept");
+        assert_eq!(rule_statement(&rule(Any, (None, None), Some("192.168.1.5"))), "ip saddr 192.168.1.5 accept");
+    }
+
+    #[test]
+    fn test_apply_script_default_deny_after_established() {
+        let script = apply_script("vnet0", &[rule(SecurityGroupProtocol::Tcp, (Some(22), None), None)]);
+        let lines: Vec<&str> = script.lines().collect();
+
+        assert!(lines.contains(&"add rule bridge easy_vm_cloud forward oifname vmap @vm_inbound"));
+        assert!(lines.contains(&"add rule bridge easy_vm_cloud output oifname vmap @vm_inbound"));
+
+        let chain_rules: Vec<&str> = lines
+            .iter()
+            .filter_map(|line| line.strip_prefix("add rule bridge easy_vm_cloud sg-vnet0 "))
+            .collect();
+        assert_eq!(chain_rules.first(), Some(&"ct state established,related accept"));
+        assert!(chain_rules.contains(&"tcp dport 22 accept"));
+        assert_eq!(chain_rules.last(), Some(&"drop"));
+
+        // 先重建链再挂到映射上
+        let flush = lines.iter().position(|l| *l == "flush chain bridge easy_vm_cloud sg-vnet0").unwrap();
+        let element = lines
+            .iter()
+            .position(|l| *l == "add element bridge easy_vm_cloud vm_inbound { \"vnet0\" : jump sg-vnet0 }")
+            .unwrap();
+        assert!(flush < element);
+    }
+
+    #[test]
+    fn test_remove_script_deletes_element_before_chain() {
+        let script = remove_script("vnet3");
+        let lines: Vec<&str> = script.lines().collect();
+        let element = lines
+            .iter()
+            .position(|l| *l == "delete element bridge easy_vm_cloud vm_inbound { \"vnet3\" }")
+            .unwrap();
+        let chain = lines
+            .iter()
+            .position(|l| *l == "delete chain bridge easy_vm_cloud sg-vnet3")
+            .unwrap();
+        assert!(element < chain);
+    }
+
+    #[tokio::test]
+    async fn test_apply_rejects_invalid_rule_before_running_nft() {
+        let rules = [rule(SecurityGroupProtocol::Tcp, (Some(22), None), Some("0.0.0.0/0 accept; flush ruleset"))];
+        let err = Firewall::new().apply("vnet0", &rules).await.unwrap_err();
+        assert!(matches!(err, common::Error::InvalidArgument(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_tap() {
+        assert!(validate_tap("vnet0").is_ok());
+        assert!(validate_tap("tap-1a2b.3").is_ok());
+        assert!(validate_tap("").is_err());
+        assert!(validate_tap("vnet0; flush ruleset").is_err());
+        assert!(validate_tap("averyveryverylongname").is_err());
+    }
+}
@@ -0,0 +1,229 @@
+/// Macvlan 网络实现
+///
+/// VM 网卡以 macvtap（libvirt direct 接口）直接挂在 Provider 接口上，不经过 Bridge，
+/// 转发开销小，适合高吞吐且不需要同节点 VM 之间隔离的场景
+///
+/// 工作原理：
+/// 1. 无 VLAN 网络以 Provider 接口为下层设备，VLAN 网络以其 VLAN 子接口（例如：eth0.100）为下层设备
+/// 2. 每个网络在下层设备上创建一个 bridge 模式的 macvlan 设备，名称与 Linux Bridge 相同（例如：br-vlan100），
+///    作为宿主机在该网络上的接口（ARP 探测等）
+/// 3. VM 接口为 `<interface type='direct'><source dev='br-vlan100' mode='bridge'/>`，
+///    内核会将 macvtap 挂到 macvlan 设备的下层设备上，同一下层设备上的 VM 之间可直接互通
+
+use common::Result;
+use std::process::Command;
+use tracing::{info, warn};
+
+pub struct MacvlanNetwork {
+    /// Provider 网络接口（例如：eth0）
+    provider_interface: String,
+}
+
+impl MacvlanNetwork {
+    pub fn new(provider_interface: String) -> Self {
+        Self { provider_interface }
+    }
+
+    /// 创建 Macvlan 网络
+    ///
+    /// 失败时删除本次新建的 VLAN 子接口，已存在的设备保持不变
+    pub async fn create_network(&self, device_name: &str, vlan_id: Option<u32>, mtu: u32) -> Result<()> {
+        info!("创建 Macvlan 网络，设备: {}, VLAN: {:?}, MTU: {}", device_name, vlan_id, mtu);
+
+        let lower = self.lower_device(vlan_id);
+        let mut created_vlan_interface = false;
+        if let Some(vlan) = vlan_id {
+            if !interface_exists(&lower) {
+                info!("创建 VLAN 子接口: {}", lower);
+                ip(&vlan_interface_args(&self.provider_interface, &lower, vlan), "创建 VLAN 子接口失败")?;
+                created_vlan_interface = true;
+            }
+        }
+
+        if let Err(e) = self.build_macvlan_device(device_name, &lower, vlan_id.is_some(), mtu) {
+            if created_vlan_interface {
+                warn!("Macvlan 网络创建失败，回滚: 删除 VLAN 子接口 {}", lower);
+                if let Err(e) = ip(&["link", "delete", &lower], "删除接口失败") {
+                    warn!("回滚删除 {} 失败: {}", lower, e);
+                }
+            }
+            return Err(e);
+        }
+
+        info!("Macvlan 网络 {} 创建成功", device_name);
+        Ok(())
+    }
+
+    /// Provider 接口的 MTU 由宿主机配置，只为 VLAN 子接口和 macvlan 设备设置 MTU
+    fn build_macvlan_device(&self, device_name: &str, lower: &str, is_vlan: bool, mtu: u32) -> Result<()> {
+        let mtu = mtu.to_string();
+        if is_vlan {
+            ip(&["link", "set", lower, "mtu", &mtu], "设置 VLAN 子接口 MTU 失败")?;
+        }
+        ip(&["link", "set", lower, "up"], "启动下层设备失败")?;
+
+        if interface_exists(device_name) {
+            info!("Macvlan 设备 {} 已存在", device_name);
+        } else {
+            info!("在 {} 上创建 Macvlan 设备 {}", lower, device_name);
+            ip(&macvlan_device_args(lower, device_name), "创建 Macvlan 设备失败")?;
+        }
+
+        ip(&["link", "set", device_name, "mtu", &mtu], "设置 Macvlan 设备 MTU 失败")?;
+        ip(&["link", "set", device_name, "up"], "启动 Macvlan 设备失败")
+    }
+
+    /// 删除 Macvlan 网络
+    ///
+    /// 删除宿主机侧的 macvlan 设备；VLAN 子接口上没有其他上层设备（例如仍在运行的 VM 的 macvtap）时一并删除
+    pub async fn delete_network(&self, device_name: &str, vlan_id: Option<u32>) -> Result<()> {
+        info!("删除 Macvlan 网络，设备: {}, VLAN: {:?}", device_name, vlan_id);
+
+        if interface_exists(device_name) {
+            ip(&["link", "delete", device_name], "删除 Macvlan 设备失败")?;
+        }
+
+        if vlan_id.is_some() {
+            let lower = self.lower_device(vlan_id);
+            if interface_exists(&lower) {
+                if has_upper_devices(&lower) {
+                    info!("VLAN 子接口 {} 仍有上层设备，保留", lower);
+                } else {
+                    info!("删除 VLAN 子接口: {}", lower);
+                    ip(&["link", "delete", &lower], "删除 VLAN 子接口失败")?;
+                }
+            }
+        }
+
+        info!("Macvlan 网络 {} 删除成功", device_name);
+        Ok(())
+    }
+
+    /// 删除 MAC 地址对应的 macvtap 设备
+    ///
+    /// libvirt 分离 direct 接口时通常会自行删除 macvtap，这里清理 VM 异常退出等情况下的残留设备
+    pub async fn cleanup_macvtap(&self, mac_address: &str) -> Result<()> {
+        let output = Command::new("ip")
+            .args(["-o", "link", "show", "type", "macvtap"])
+            .output()
+            .map_err(|e| common::Error::Internal(format!("执行命令失败: {}", e)))?;
+        if !output.status.success() {
+            // 未加载 macvtap 模块时没有设备需要清理
+            return Ok(());
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for device in macvtap_devices_with_mac(&stdout, mac_address) {
+            info!("删除残留的 macvtap 设备 {}（MAC: {}）", device, mac_address);
+            ip(&["link", "delete", &device], "删除 macvtap 设备失败")?;
+        }
+        Ok(())
+    }
+
+    /// macvlan 设备的下层设备：VLAN 网络为 Provider 接口的 VLAN 子接口
+    fn lower_device(&self, vlan_id: Option<u32>) -> String {
+        match vlan_id {
+            Some(vlan) => format!("{}.{}", self.provider_interface, vlan),
+            None => self.provider_interface.clone(),
+        }
+    }
+}
+
+fn vlan_interface_args(provider: &str, name: &str, vlan_id: u32) -> Vec<String> {
+    ["link", "add", "link", provider, "name", name, "type", "vlan", "id", &vlan_id.to_string()]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+fn macvlan_device_args(lower: &str, device_name: &str) -> Vec<String> {
+    ["link", "add", "link", lower, "name", device_name, "type", "macvlan", "mode", "bridge"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// 从 `ip -o link show type macvtap` 的输出中找出 MAC 地址匹配的设备名
+///
+/// 每行形如 `12: macvtap0@eth0.100: <...> mtu 1500 ... link/ether 52:54:00:12:34:56 brd ff:ff:ff:ff:ff:ff`
+fn macvtap_devices_with_mac(output: &str, mac_address: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.nth(1)?.trim_end_matches(':');
+            let name = name.split('@').next()?;
+            let mut rest = fields.skip_while(|field| *field != "link/ether");
+            rest.next()?;
+            let mac = rest.next()?;
+            mac.eq_ignore_ascii_case(mac_address).then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// 接口是否有上层设备（Bridge master、macvlan/macvtap 等）
+fn has_upper_devices(interface: &str) -> bool {
+    std::fs::read_dir(format!("/sys/class/net/{}", interface))
+        .map(|entries| {
+            entries
+                .flatten()
+                .any(|entry| entry.file_name().to_string_lossy().starts_with("upper_"))
+        })
+        .unwrap_or(false)
+}
+
+fn interface_exists(interface: &str) -> bool {
+    Command::new("ip")
+        .args(["link", "show", interface])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// 执行 ip 命令，失败时以 `context: stderr` 作为错误信息
+fn ip<S: AsRef<str>>(args: &[S], context: &str) -> Result<()> {
+    let output = Command::new("ip")
+        .args(args.iter().map(|s| s.as_ref()))
+        .output()
+        .map_err(|e| common::Error::Internal(format!("执行命令失败: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(common::Error::Internal(format!("{}: {}", context, stderr.trim())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lower_device() {
+        let macvlan = MacvlanNetwork::new("eth0".to_string());
+        assert_eq!(macvlan.lower_device(Some(100)), "eth0.100");
+        assert_eq!(macvlan.lower_device(None), "eth0");
+    }
+
+    #[test]
+    fn test_macvlan_device_uses_bridge_mode() {
+        assert_eq!(
+            macvlan_device_args("eth0.100", "br-vlan100").join(" "),
+            "link add link eth0.100 name br-vlan100 type macvlan mode bridge"
+        );
+    }
+
+    #[test]
+    fn test_macvtap_devices_with_mac() {
+        let output = "\
+12: macvtap0@eth0.100: <BROADCAST,MULTICAST,UP,LOWER_UP> mtu 1500 qdisc fq_codel state UP mode DEFAULT group default qlen 500\\    link/ether 52:54:00:12:34:56 brd ff:ff:ff:ff:ff:ff
+13: macvtap1@eth0: <BROADCAST,MULTICAST,UP,LOWER_UP> mtu 1500 qdisc fq_codel state UP mode DEFAULT group default qlen 500\\    link/ether 52:54:00:ab:cd:ef brd ff:ff:ff:ff:ff:ff
+";
+        assert_eq!(
+            macvtap_devices_with_mac(output, "52:54:00:AB:CD:EF"),
+            vec!["macvtap1".to_string()]
+        );
+        assert!(macvtap_devices_with_mac(output, "52:54:00:00:00:01").is_empty());
+        assert!(macvtap_devices_with_mac("", "52:54:00:12:34:56").is_empty());
+    }
+}
@@ -2,18 +2,39 @@
 /// 
 /// 负责创建、配置网络和网桥
 
+use common::utils::BridgeNaming;
+use common::ws_rpc::{DhcpConfig, SecurityGroupRule};
 use common::Result;
-use tracing::info;
+use tracing::{info, warn};
 use crate::network::bridge::LinuxBridge;
+use crate::network::dhcp::DhcpServer;
+use crate::network::firewall::Firewall;
+use crate::network::macvlan::MacvlanNetwork;
+use crate::network::ovs::OvsBridge;
 
 pub struct NetworkManager {
     bridge: LinuxBridge,
+    ovs: OvsBridge,
+    macvlan: MacvlanNetwork,
+    firewall: Firewall,
+    dhcp: DhcpServer,
+    /// 启动时检测到 ovs-vsctl 可用
+    ovs_available: bool,
 }
 
 impl NetworkManager {
-    pub fn new(provider_interface: String) -> Self {
+    pub fn new(provider_interface: String, naming: BridgeNaming) -> Self {
+        let ovs_available = OvsBridge::is_available();
+        if !ovs_available {
+            warn!("未检测到 Open vSwitch（ovs-vsctl），OVS 网络不可用");
+        }
         Self {
-            bridge: LinuxBridge::new(provider_interface),
+            ovs: OvsBridge::new(provider_interface.clone(), &naming),
+            macvlan: MacvlanNetwork::new(provider_interface.clone()),
+            bridge: LinuxBridge::new(provider_interface, naming),
+            firewall: Firewall::new(),
+            dhcp: DhcpServer::new(),
+            ovs_available,
         }
     }
 
@@ -25,22 +46,27 @@ impl NetworkManager {
         network_type: &str,
         bridge_name: &str,
         vlan_id: Option<u32>,
+        mtu: u32,
     ) -> Result<()> {
-        info!("创建网络: id={}, name={}, type={}, bridge={}, vlan={:?}", 
-              network_id, name, network_type, bridge_name, vlan_id);
+        info!("创建网络: id={}, name={}, type={}, bridge={}, vlan={:?}, mtu={}", 
+              network_id, name, network_type, bridge_name, vlan_id, mtu);
 
         match network_type {
             "bridge" => {
                 if let Some(vlan) = vlan_id {
                     // 创建 VLAN 网络
-                    self.bridge.create_vlan_network(vlan, bridge_name).await?;
+                    self.bridge.create_vlan_network(vlan, bridge_name, mtu).await?;
                 } else {
                     // 创建无 VLAN 网络
-                    self.bridge.create_no_vlan_network(bridge_name).await?;
+                    self.bridge.create_no_vlan_network(bridge_name, mtu).await?;
                 }
             }
             "ovs" => {
-                return Err(common::Error::Internal("暂不支持 OVS 网络".to_string()));
+                self.ensure_ovs_available()?;
+                self.ovs.create_network(bridge_name, vlan_id, mtu).await?;
+            }
+            "macvlan" => {
+                self.macvlan.create_network(bridge_name, vlan_id, mtu).await?;
             }
             _ => {
                 return Err(common::Error::Internal(format!("不支持的网络类型: {}", network_type)));
@@ -54,20 +80,45 @@ impl NetworkManager {
     pub async fn delete_network(
         &self,
         network_id: &str,
+        network_type: &str,
         bridge_name: &str,
         vlan_id: Option<u32>,
     ) -> Result<()> {
-        info!("删除网络: id={}, bridge={}, vlan={:?}", network_id, bridge_name, vlan_id);
+        info!("删除网络: id={}, type={}, bridge={}, vlan={:?}", network_id, network_type, bridge_name, vlan_id);
 
-        if let Some(vlan) = vlan_id {
-            self.bridge.delete_vlan_network(vlan, bridge_name).await?;
-        } else {
-            self.bridge.delete_no_vlan_network(bridge_name).await?;
+        // 先停止 DHCP 服务，dnsmasq 仍绑定在 Bridge 上
+        if let Err(e) = self.dhcp.stop(bridge_name).await {
+            warn!("停止 Bridge {} 的 DHCP 服务失败: {}", bridge_name, e);
+        }
+
+        match network_type {
+            "ovs" => {
+                self.ensure_ovs_available()?;
+                self.ovs.delete_network(bridge_name).await?;
+            }
+            "macvlan" => {
+                self.macvlan.delete_network(bridge_name, vlan_id).await?;
+            }
+            _ => {
+                if let Some(vlan) = vlan_id {
+                    self.bridge.delete_vlan_network(vlan, bridge_name).await?;
+                } else {
+                    self.bridge.delete_no_vlan_network(bridge_name).await?;
+                }
+            }
         }
 
         Ok(())
     }
 
+    fn ensure_ovs_available(&self) -> Result<()> {
+        if self.ovs_available {
+            Ok(())
+        } else {
+            Err(common::Error::Internal("节点未安装 Open vSwitch（ovs-vsctl 不可用）".to_string()))
+        }
+    }
+
     /// 附加网络接口到虚拟机
     pub async fn attach_interface(&self, _vm_id: &str, _network_id: &str) -> Result<()> {
         // 注意：在使用 libvirt 时，网络接口的附加通常在 VM 创建时通过 XML 配置完成
@@ -77,15 +128,46 @@ impl NetworkManager {
     }
 
     /// 从虚拟机分离网络接口
-    pub async fn detach_interface(&self, _vm_id: &str, _mac_address: &str) -> Result<()> {
+    pub async fn detach_interface(&self, _vm_id: &str, mac_address: &str) -> Result<()> {
         // 注意：在使用 libvirt 时，网络接口的分离通常由 libvirt 处理
         info!("分离网络接口（由 libvirt 处理）");
-        Ok(())
+        // Macvlan 网络的 macvtap 设备可能在 VM 异常退出后残留
+        self.macvlan.cleanup_macvtap(mac_address).await
+    }
+
+    /// 为 VM 的 tap 设备下发安全组规则
+    pub async fn apply_security_group(&self, tap: &str, rules: &[SecurityGroupRule]) -> Result<()> {
+        self.firewall.apply(tap, rules).await
+    }
+
+    /// 删除 VM 的 tap 设备上的安全组规则
+    pub async fn remove_security_group(&self, tap: &str) -> Result<()> {
+        self.firewall.remove(tap).await
+    }
+
+    /// 在 Bridge 上启动网络的 DHCP 服务，已运行时刷新静态租约
+    pub async fn start_dhcp(&self, network: &DhcpConfig) -> Result<()> {
+        self.dhcp.start(network).await
+    }
+
+    /// IP 分配变化后刷新静态租约，本节点未运行该网络的 DHCP 服务时忽略
+    pub async fn reload_dhcp(&self, network: &DhcpConfig) -> Result<()> {
+        self.dhcp.reload(network).await
+    }
+
+    /// 停止 Bridge 上的 DHCP 服务
+    pub async fn stop_dhcp(&self, bridge_name: &str) -> Result<()> {
+        self.dhcp.stop(bridge_name).await
     }
 
     /// 获取 Bridge 名称（根据 VLAN ID）
-    pub fn get_bridge_name(vlan_id: Option<u32>) -> String {
-        LinuxBridge::generate_bridge_name(vlan_id)
+    pub fn get_bridge_name(&self, vlan_id: Option<u32>) -> String {
+        self.bridge.generate_bridge_name(vlan_id)
+    }
+
+    /// 从 Bridge 名称推断 VLAN ID（与 get_bridge_name 使用同一命名规则）
+    pub fn parse_vlan_id(&self, bridge_name: &str) -> Option<u32> {
+        self.bridge.parse_vlan_id(bridge_name)
     }
     
     /// 检查 Bridge 是否存在
@@ -1,9 +1,17 @@
 /// 网络管理
 /// 
-/// 支持 Linux Bridge 和 Open vSwitch
+/// 支持 Linux Bridge、Open vSwitch 和 Macvlan
 
 pub mod manager;
 pub mod bridge;
+pub mod ovs;
+pub mod macvlan;
+pub mod firewall;
+pub mod dhcp;
+pub mod arp;
 
 pub use manager::NetworkManager;
 
+/// 网络未设置 MTU 时使用的默认值
+pub const DEFAULT_MTU: u32 = 1500;
+
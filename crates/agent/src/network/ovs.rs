@@ -0,0 +1,223 @@
+/// Open vSwitch 网络实现
+///
+/// 通过 ovs-vsctl 管理 OVS Bridge，VLAN 隔离由端口 tag 实现
+///
+/// 工作原理：
+/// 1. Provider 接口作为 trunk 端口加入上行 Bridge（例如：br-uplink），所有 OVS 网络共用
+/// 2. 每个网络对应一个 OVS Bridge（例如：br-vlan100），命名规则与 Linux Bridge 相同
+/// 3. 网络 Bridge 与上行 Bridge 之间通过一对 patch 端口相连，
+///    上行侧 patch 端口设置 tag 为网络的 VLAN ID（无 VLAN 网络为 0，即只转发不带 tag 的帧）
+/// 4. VM 接口由 libvirt 以 `<virtualport type='openvswitch'/>` 加入网络 Bridge
+
+use common::utils::BridgeNaming;
+use common::Result;
+use std::process::Command;
+use tracing::{info, warn};
+
+pub struct OvsBridge {
+    /// Provider 网络接口（例如：eth0）
+    provider_interface: String,
+    /// 所有 OVS 网络共用的上行 Bridge
+    uplink_bridge: String,
+}
+
+impl OvsBridge {
+    pub fn new(provider_interface: String, naming: &BridgeNaming) -> Self {
+        Self {
+            provider_interface,
+            uplink_bridge: format!("{}uplink", naming.prefix()),
+        }
+    }
+
+    /// 检测节点是否安装 Open vSwitch（ovs-vsctl 可执行且能连接 ovsdb）
+    pub fn is_available() -> bool {
+        Command::new("ovs-vsctl")
+            .arg("show")
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
+    /// 创建 OVS 网络
+    ///
+    /// 失败时删除本次新建的网络 Bridge 及其上行 patch 端口，上行 Bridge 保留供其他网络使用
+    pub async fn create_network(&self, bridge_name: &str, vlan_id: Option<u32>, mtu: u32) -> Result<()> {
+        info!("创建 OVS 网络，Bridge: {}, VLAN: {:?}, MTU: {}", bridge_name, vlan_id, mtu);
+
+        let existed = self.bridge_exists(bridge_name).await;
+        for args in self.create_commands(bridge_name, vlan_id, mtu) {
+            if let Err(e) = vsctl(&args) {
+                if !existed {
+                    self.rollback(bridge_name);
+                }
+                return Err(e);
+            }
+        }
+
+        // 网络 Bridge 的内部接口默认为 DOWN
+        let output = Command::new("ip")
+            .args(["link", "set", bridge_name, "up"])
+            .output()
+            .map_err(|e| common::Error::Internal(format!("执行命令失败: {}", e)))?;
+        if !output.status.success() {
+            warn!(
+                "设置 OVS Bridge {} 为 UP 失败: {}",
+                bridge_name,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        info!("OVS 网络 {} 创建成功", bridge_name);
+        Ok(())
+    }
+
+    /// 删除 OVS 网络，Bridge 上仍有虚拟机端口时保留
+    pub async fn delete_network(&self, bridge_name: &str) -> Result<()> {
+        info!("删除 OVS 网络，Bridge: {}", bridge_name);
+
+        if !self.bridge_exists(bridge_name).await {
+            return Ok(());
+        }
+
+        let (local_patch, _) = self.patch_ports(bridge_name);
+        let ports = vsctl(&["list-ports".to_string(), bridge_name.to_string()])?;
+        if ports.lines().any(|port| !port.is_empty() && port != local_patch) {
+            info!("OVS Bridge {} 仍有其他端口，保留", bridge_name);
+            return Ok(());
+        }
+
+        for args in self.delete_commands(bridge_name) {
+            vsctl(&args)?;
+        }
+
+        info!("OVS 网络 {} 删除成功", bridge_name);
+        Ok(())
+    }
+
+    /// 检查 OVS Bridge 是否存在
+    pub async fn bridge_exists(&self, bridge_name: &str) -> bool {
+        Command::new("ovs-vsctl")
+            .args(["br-exists", bridge_name])
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
+    /// 网络 Bridge 与上行 Bridge 之间的 patch 端口名称（网络侧, 上行侧）
+    ///
+    /// patch 端口不创建内核网络设备，名称不受接口名长度限制
+    fn patch_ports(&self, bridge_name: &str) -> (String, String) {
+        (
+            format!("{}-to-uplink", bridge_name),
+            format!("uplink-to-{}", bridge_name),
+        )
+    }
+
+    /// 创建网络的 ovs-vsctl 命令序列，全部使用 --may-exist 保证可重复执行
+    fn create_commands(&self, bridge_name: &str, vlan_id: Option<u32>, mtu: u32) -> Vec<Vec<String>> {
+        let (local_patch, uplink_patch) = self.patch_ports(bridge_name);
+        let args = |args: &[&str]| args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        vec![
+            args(&["--may-exist", "add-br", &self.uplink_bridge]),
+            args(&["--may-exist", "add-port", &self.uplink_bridge, &self.provider_interface]),
+            args(&["--may-exist", "add-br", bridge_name]),
+            args(&[
+                "--may-exist", "add-port", bridge_name, &local_patch,
+                "--", "set", "interface", &local_patch, "type=patch",
+                &format!("options:peer={}", uplink_patch),
+            ]),
+            args(&[
+                "--may-exist", "add-port", &self.uplink_bridge, &uplink_patch,
+                "--", "set", "interface", &uplink_patch, "type=patch",
+                &format!("options:peer={}", local_patch),
+            ]),
+            // 上行侧 patch 端口为 access 端口，只放行该网络 VLAN 的流量
+            args(&[
+                "set", "port", &uplink_patch, "vlan_mode=access",
+                &format!("tag={}", vlan_id.unwrap_or(0)),
+            ]),
+            // 网络 Bridge 的内部接口 MTU，VM 的 tap 设备由 libvirt 按域 XML 设置
+            args(&["set", "interface", bridge_name, &format!("mtu_request={}", mtu)]),
+        ]
+    }
+
+    /// 删除网络的 ovs-vsctl 命令序列，删除 Bridge 会一并删除其上的端口
+    fn delete_commands(&self, bridge_name: &str) -> Vec<Vec<String>> {
+        let (_, uplink_patch) = self.patch_ports(bridge_name);
+        vec![
+            vec!["--if-exists".to_string(), "del-port".to_string(), self.uplink_bridge.clone(), uplink_patch],
+            vec!["--if-exists".to_string(), "del-br".to_string(), bridge_name.to_string()],
+        ]
+    }
+
+    /// 回滚创建失败时新建的网络 Bridge，回滚本身失败只记录日志
+    fn rollback(&self, bridge_name: &str) {
+        warn!("OVS 网络创建失败，回滚: 删除 Bridge {}", bridge_name);
+        for args in self.delete_commands(bridge_name) {
+            if let Err(e) = vsctl(&args) {
+                warn!("回滚步骤 {:?} 失败: {}", args, e);
+            }
+        }
+    }
+}
+
+/// 执行 ovs-vsctl 命令，返回标准输出
+fn vsctl(args: &[String]) -> Result<String> {
+    let output = Command::new("ovs-vsctl")
+        .args(args)
+        .output()
+        .map_err(|e| common::Error::Internal(format!("执行 ovs-vsctl 失败: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(common::Error::Internal(format!(
+            "ovs-vsctl {} 失败: {}",
+            args.join(" "),
+            stderr.trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ovs() -> OvsBridge {
+        OvsBridge::new("eth0".to_string(), &BridgeNaming::default())
+    }
+
+    #[test]
+    fn test_create_commands_tag_uplink_patch() {
+        let commands = ovs().create_commands("br-vlan100", Some(100), 9000);
+        let joined: Vec<String> = commands.iter().map(|args| args.join(" ")).collect();
+
+        assert_eq!(joined[0], "--may-exist add-br br-uplink");
+        assert_eq!(joined[1], "--may-exist add-port br-uplink eth0");
+        assert_eq!(joined[2], "--may-exist add-br br-vlan100");
+        assert!(joined[3].contains("add-port br-vlan100 br-vlan100-to-uplink"));
+        assert!(joined[3].ends_with("options:peer=uplink-to-br-vlan100"));
+        assert!(joined[4].contains("add-port br-uplink uplink-to-br-vlan100"));
+        assert!(joined[4].ends_with("options:peer=br-vlan100-to-uplink"));
+        assert_eq!(joined[5], "set port uplink-to-br-vlan100 vlan_mode=access tag=100");
+        assert_eq!(joined[6], "set interface br-vlan100 mtu_request=9000");
+    }
+
+    #[test]
+    fn test_no_vlan_network_only_passes_untagged_frames() {
+        let commands = ovs().create_commands("br-default", None, 1500);
+        assert_eq!(
+            commands[5].join(" "),
+            "set port uplink-to-br-default vlan_mode=access tag=0"
+        );
+    }
+
+    #[test]
+    fn test_delete_commands_remove_uplink_patch() {
+        let commands = ovs().delete_commands("br-vlan100");
+        assert_eq!(commands[0].join(" "), "--if-exists del-port br-uplink uplink-to-br-vlan100");
+        assert_eq!(commands[1].join(" "), "--if-exists del-br br-vlan100");
+    }
+}
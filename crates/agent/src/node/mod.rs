@@ -5,8 +5,9 @@
 /// - 虚拟化能力检测
 /// - 节点配置信息
 
-use common::ws_rpc::NodeResourceInfo;
+use common::ws_rpc::{DiskUsageSample, HostNumaNode, NodeMetricsSample, NodeResourceInfo};
 use std::error::Error;
+use std::path::Path;
 use tracing::debug;
 
 /// 节点信息管理器
@@ -56,22 +57,32 @@ impl NodeManager {
         let mut sys = System::new_all();
         sys.refresh_all();
         
+        // CPU 利用率需要间隔两次采样才能计算
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        sys.refresh_cpu_usage();
+        
         // 获取 CPU 信息
         let cpu_cores = sys.cpus().len() as u32;
         let cpu_threads = sys.cpus().len() as u32;
+        let cpu_usage = sys.global_cpu_usage();
         
-        // 获取内存信息
-        let memory_total = sys.total_memory() * 1024; // 转换为字节
+        // 获取内存信息（sysinfo 0.30 起单位已是字节）
+        let memory_total = sys.total_memory();
+        let memory_used = sys.used_memory();
         
         // 获取磁盘信息
         let disks = Disks::new_with_refreshed_list();
         let disk_total = disks.list().iter()
             .map(|disk| disk.total_space())
             .sum();
+        let disk_used = disks.list().iter()
+            .map(|disk| disk.total_space().saturating_sub(disk.available_space()))
+            .sum();
         
         // 获取虚拟化信息
         let hypervisor_type = self.detect_hypervisor_type();
         let hypervisor_version = self.detect_hypervisor_version();
+        let capability = self.check_virtualization_capability();
         
         Ok(NodeResourceInfo {
             node_id: self.node_id.clone(),
@@ -81,10 +92,50 @@ impl NodeManager {
             disk_total,
             hypervisor_type: Some(hypervisor_type),
             hypervisor_version: Some(hypervisor_version),
+            cpu_usage: Some(cpu_usage),
+            memory_used: Some(memory_used),
+            disk_used: Some(disk_used),
+            has_swtpm: Some(capability.has_swtpm),
+            has_ovs: Some(capability.has_ovs),
+            numa_nodes: Some(host_numa_nodes()),
+            supported_architectures: Some(capability.supported_architectures),
             timestamp: chrono::Utc::now().timestamp(),
         })
     }
 
+    /// 采样主机级指标（CPU 利用率与负载、可用内存、各挂载点磁盘使用）
+    ///
+    /// CPU 利用率需要间隔采样，调用会阻塞约 200ms，应放在阻塞线程中执行
+    pub fn sample_metrics(&self) -> NodeMetricsSample {
+        use sysinfo::{Disks, System};
+
+        let mut sys = System::new();
+        sys.refresh_cpu_usage();
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        sys.refresh_cpu_usage();
+        sys.refresh_memory();
+
+        let disks = Disks::new_with_refreshed_list()
+            .list()
+            .iter()
+            .map(|disk| DiskUsageSample {
+                mount_point: disk.mount_point().to_string_lossy().into_owned(),
+                total: disk.total_space(),
+                available: disk.available_space(),
+            })
+            .collect();
+
+        NodeMetricsSample {
+            node_id: self.node_id.clone(),
+            timestamp: chrono::Utc::now().timestamp(),
+            cpu_usage: sys.global_cpu_usage(),
+            load_avg_1: System::load_average().one,
+            memory_total: sys.total_memory(),
+            memory_free: sys.available_memory(),
+            disks,
+        }
+    }
+
     /// 检测虚拟化类型
     fn detect_hypervisor_type(&self) -> String {
         // 检测 KVM 支持
@@ -166,40 +217,115 @@ impl NodeManager {
         let hypervisor_type = self.detect_hypervisor_type();
         let has_kvm = std::path::Path::new("/dev/kvm").exists();
         let has_libvirt = std::path::Path::new("/usr/bin/virsh").exists();
+        let has_swtpm = std::path::Path::new("/usr/bin/swtpm").exists();
+        let has_ovs = crate::network::ovs::OvsBridge::is_available();
         
         VirtualizationCapability {
             hypervisor_type,
             has_kvm,
             has_libvirt,
-            supported_architectures: self.get_supported_architectures(),
+            has_swtpm,
+            has_ovs,
+            supported_architectures: supported_architectures(),
         }
     }
 
-    /// 获取支持的架构列表
-    fn get_supported_architectures(&self) -> Vec<String> {
-        let mut architectures = Vec::new();
-        
-        // 检查 x86_64 支持
-        if std::path::Path::new("/usr/bin/qemu-system-x86_64").exists() {
-            architectures.push("x86_64".to_string());
-        }
-        
-        // 检查 ARM 支持
-        if std::path::Path::new("/usr/bin/qemu-system-aarch64").exists() {
-            architectures.push("aarch64".to_string());
-        }
-        
-        // 检查其他架构
-        if std::path::Path::new("/usr/bin/qemu-system-arm").exists() {
-            architectures.push("arm".to_string());
-        }
-        
-        if std::path::Path::new("/usr/bin/qemu-system-ppc64").exists() {
-            architectures.push("ppc64".to_string());
-        }
-        
-        architectures
+}
+
+/// 获取支持的架构列表（按已安装的 QEMU 模拟器判断）
+pub fn supported_architectures() -> Vec<String> {
+    let mut architectures = Vec::new();
+    
+    // 检查 x86_64 支持
+    if std::path::Path::new("/usr/bin/qemu-system-x86_64").exists() {
+        architectures.push("x86_64".to_string());
+    }
+    
+    // 检查 ARM 支持
+    if std::path::Path::new("/usr/bin/qemu-system-aarch64").exists() {
+        architectures.push("aarch64".to_string());
+    }
+    
+    // 检查其他架构
+    if std::path::Path::new("/usr/bin/qemu-system-arm").exists() {
+        architectures.push("arm".to_string());
     }
+    
+    if std::path::Path::new("/usr/bin/qemu-system-ppc64").exists() {
+        architectures.push("ppc64".to_string());
+    }
+    
+    architectures
+}
+
+/// sysfs 中 NUMA 节点目录
+const SYSFS_NODE_DIR: &str = "/sys/devices/system/node";
+
+/// 宿主机 NUMA 拓扑
+///
+/// 内核未启用 NUMA（没有 node 目录）时视为单个节点 0，包含全部在线 CPU 和内存
+pub fn host_numa_nodes() -> Vec<HostNumaNode> {
+    let nodes = read_numa_nodes(Path::new(SYSFS_NODE_DIR));
+    if !nodes.is_empty() {
+        return nodes;
+    }
+
+    let mut sys = sysinfo::System::new();
+    sys.refresh_cpu_all();
+    sys.refresh_memory();
+    vec![HostNumaNode {
+        id: 0,
+        cpus: (0..sys.cpus().len() as u32).collect(),
+        memory_mb: sys.total_memory() / 1024 / 1024,
+    }]
+}
+
+/// 读取 `root` 下的 nodeN/cpulist 与 nodeN/meminfo，按节点编号排序
+fn read_numa_nodes(root: &Path) -> Vec<HostNumaNode> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+
+    let mut nodes: Vec<HostNumaNode> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let id = entry.file_name().to_str()?.strip_prefix("node")?.parse().ok()?;
+            let cpus = std::fs::read_to_string(entry.path().join("cpulist")).ok()?;
+            // 格式: "Node 0 MemTotal:       32768000 kB"
+            let memory_kb = std::fs::read_to_string(entry.path().join("meminfo"))
+                .ok()
+                .and_then(|meminfo| {
+                    meminfo
+                        .lines()
+                        .find(|line| line.contains("MemTotal:"))
+                        .and_then(|line| line.split_whitespace().rev().nth(1)?.parse::<u64>().ok())
+                })
+                .unwrap_or(0);
+            Some(HostNumaNode {
+                id,
+                cpus: parse_cpu_list(&cpus),
+                memory_mb: memory_kb / 1024,
+            })
+        })
+        .collect();
+    nodes.sort_by_key(|node| node.id);
+    nodes
+}
+
+/// 解析内核的 CPU 列表格式，如 "0-3,8-11"
+fn parse_cpu_list(list: &str) -> Vec<u32> {
+    list.trim()
+        .split(',')
+        .filter(|part| !part.is_empty())
+        .filter_map(|part| {
+            let (start, end) = part.split_once('-').unwrap_or((part, part));
+            match (start.parse::<u32>(), end.parse::<u32>()) {
+                (Ok(start), Ok(end)) => Some(start..=end),
+                _ => None,
+            }
+        })
+        .flatten()
+        .collect()
 }
 
 /// 节点基本信息
@@ -216,6 +342,10 @@ pub struct VirtualizationCapability {
     pub hypervisor_type: String,
     pub has_kvm: bool,
     pub has_libvirt: bool,
+    /// 是否安装 swtpm，决定能否为虚拟机提供模拟 TPM
+    pub has_swtpm: bool,
+    /// 是否安装 Open vSwitch，决定能否创建 OVS 网络
+    pub has_ovs: bool,
     pub supported_architectures: Vec<String>,
 }
 
@@ -236,3 +366,46 @@ impl VirtualizationCapability {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(parse_cpu_list("0-3,8-9\n"), [0, 1, 2, 3, 8, 9]);
+        assert_eq!(parse_cpu_list("5"), [5]);
+        assert!(parse_cpu_list("").is_empty());
+        // 无法解析的片段被跳过
+        assert_eq!(parse_cpu_list("0-3,x,5"), [0, 1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn test_read_numa_nodes() {
+        let root = std::env::temp_dir().join(format!("numa-{}", uuid::Uuid::new_v4()));
+        for (id, cpus) in [(1, "4-7"), (0, "0-3")] {
+            let dir = root.join(format!("node{}", id));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("cpulist"), cpus).unwrap();
+            std::fs::write(
+                dir.join("meminfo"),
+                format!("Node {} MemTotal:       16777216 kB\nNode {} MemFree: 1 kB\n", id, id),
+            )
+            .unwrap();
+        }
+        // 非节点目录忽略
+        std::fs::create_dir_all(root.join("power")).unwrap();
+
+        let nodes = read_numa_nodes(&root);
+        assert_eq!(
+            nodes,
+            [
+                HostNumaNode { id: 0, cpus: vec![0, 1, 2, 3], memory_mb: 16384 },
+                HostNumaNode { id: 1, cpus: vec![4, 5, 6, 7], memory_mb: 16384 },
+            ]
+        );
+        assert!(read_numa_nodes(&root.join("missing")).is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
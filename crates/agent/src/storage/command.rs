@@ -0,0 +1,199 @@
+/// 外部存储命令的执行
+///
+/// qemu-img、lvm 等命令在 NFS 挂载挂起时可能永远不返回，所有调用都带超时，
+/// 超时后终止子进程；文件锁冲突、ESTALE 等瞬时失败按策略有限次重试
+use common::{Error, Result};
+use std::process::Output;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::warn;
+
+/// 逐行接收命令标准输出的回调
+pub type LineFn<'a> = &'a (dyn Fn(&str) + Send + Sync);
+
+/// stderr 中出现这些内容时视为瞬时失败，稍后重试可能成功
+const TRANSIENT_ERRORS: &[&str] = &[
+    // qemu-img 打开镜像时与其他进程的文件锁冲突
+    "\"write\" lock",
+    "Stale file handle",
+    "Resource temporarily unavailable",
+];
+
+/// 外部命令的超时与重试策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandPolicy {
+    /// 普通命令（info、create、resize、快照、lvm 等）单次执行的超时
+    pub timeout: Duration,
+    /// 复制整个镜像的 convert 单次执行的超时
+    pub convert_timeout: Duration,
+    /// 瞬时失败后的最多重试次数
+    pub retries: u32,
+    /// 两次尝试之间的等待时间
+    pub retry_delay: Duration,
+}
+
+impl Default for CommandPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(300),
+            convert_timeout: Duration::from_secs(6 * 3600),
+            retries: 2,
+            retry_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl CommandPolicy {
+    /// 执行普通命令
+    ///
+    /// 非零退出码原样返回，由调用方根据 stderr 生成错误信息
+    pub async fn output(&self, cmd: &mut Command) -> Result<Output> {
+        self.execute(cmd, self.timeout, None).await
+    }
+
+    /// 执行 qemu-img convert，给出 `on_line` 时逐行（`\r` 或 `\n` 结尾）回调标准输出
+    pub async fn convert(&self, cmd: &mut Command, on_line: Option<LineFn<'_>>) -> Result<Output> {
+        self.execute(cmd, self.convert_timeout, on_line).await
+    }
+
+    async fn execute(
+        &self,
+        cmd: &mut Command,
+        timeout: Duration,
+        on_line: Option<LineFn<'_>>,
+    ) -> Result<Output> {
+        let program = cmd.as_std().get_program().to_string_lossy().to_string();
+        // 超时或请求被取消时随 future 一起终止子进程
+        cmd.kill_on_drop(true);
+
+        let mut attempt = 0;
+        loop {
+            // 超时不重试：挂起的 NFS 挂载通常不会很快恢复，重试只会让调用方成倍等待
+            let output = tokio::time::timeout(timeout, Self::spawn(cmd, on_line))
+                .await
+                .map_err(|_| {
+                    Error::CommandTimeout(format!(
+                        "{} did not finish within {}s",
+                        program,
+                        timeout.as_secs()
+                    ))
+                })?
+                .map_err(|e| match e.kind() {
+                    std::io::ErrorKind::NotFound => Error::ToolNotFound(program.clone()),
+                    _ => Error::Storage(format!("Failed to run {}: {}", program, e)),
+                })?;
+
+            if output.status.success() || attempt >= self.retries || !is_transient(&output) {
+                return Ok(output);
+            }
+            attempt += 1;
+            warn!(
+                "{} failed transiently, retrying ({}/{}): {}",
+                program,
+                attempt,
+                self.retries,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            tokio::time::sleep(self.retry_delay).await;
+        }
+    }
+
+    async fn spawn(cmd: &mut Command, on_line: Option<LineFn<'_>>) -> std::io::Result<Output> {
+        use tokio::io::AsyncReadExt;
+
+        let Some(on_line) = on_line else {
+            return cmd.output().await;
+        };
+
+        let mut child = cmd
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()?;
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+
+        let mut buf = [0u8; 256];
+        let mut pending = String::new();
+        loop {
+            let n = stdout.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            pending.push_str(&String::from_utf8_lossy(&buf[..n]));
+            while let Some(pos) = pending.find(['\r', '\n']) {
+                let line: String = pending.drain(..=pos).collect();
+                on_line(&line);
+            }
+        }
+
+        child.wait_with_output().await
+    }
+}
+
+fn is_transient(output: &Output) -> bool {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    TRANSIENT_ERRORS.iter().any(|pattern| stderr.contains(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn policy(timeout: Duration) -> CommandPolicy {
+        CommandPolicy {
+            timeout,
+            convert_timeout: timeout,
+            retries: 2,
+            retry_delay: Duration::from_millis(10),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_distinguishes_missing_tool_failure_and_timeout() {
+        let policy = policy(Duration::from_millis(200));
+
+        let err = policy.output(&mut Command::new("easy-vm-cloud-no-such-tool")).await.unwrap_err();
+        assert!(matches!(err, Error::ToolNotFound(ref program) if program == "easy-vm-cloud-no-such-tool"));
+
+        let output = policy.output(&mut Command::new("false")).await.unwrap();
+        assert!(!output.status.success());
+
+        let err = policy.output(Command::new("sleep").arg("5")).await.unwrap_err();
+        assert!(matches!(err, Error::CommandTimeout(_)));
+    }
+
+    #[tokio::test]
+    async fn test_output_retries_transient_failures() {
+        let counter = std::env::temp_dir().join(format!("attempts-{}", uuid::Uuid::new_v4()));
+        let script = format!(
+            "echo x >> {}; echo 'Stale file handle' >&2; exit 1",
+            counter.display()
+        );
+
+        let output = policy(Duration::from_secs(5))
+            .output(Command::new("sh").arg("-c").arg(&script))
+            .await
+            .unwrap();
+
+        assert!(!output.status.success());
+        assert_eq!(std::fs::read_to_string(&counter).unwrap().lines().count(), 3);
+        std::fs::remove_file(&counter).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_convert_streams_lines() {
+        let lines = Mutex::new(Vec::new());
+        let on_line = |line: &str| lines.lock().unwrap().push(line.trim().to_string());
+
+        let output = policy(Duration::from_secs(5))
+            .convert(
+                Command::new("printf").args(["%s\\r%s\\n", "(10.00/100%)", "(100.00/100%)"]),
+                Some(&on_line),
+            )
+            .await
+            .unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(*lines.lock().unwrap(), ["(10.00/100%)", "(100.00/100%)"]);
+    }
+}
@@ -0,0 +1,540 @@
+/// URL 下载
+///
+/// 并发控制：同一节点上所有存储池共享一个限额，超出的下载排队等待
+/// 完整性校验：下载数据边写入文件边计算摘要，无需下载完成后重新读取整个文件
+/// 凭据保护：请求头与认证信息不写入日志，日志和错误信息中的 URL 隐藏 userinfo 与查询参数
+use common::utils::redact_url;
+use common::ws_rpc::{ChecksumAlgorithm, SourceAuth, VolumeChecksum};
+use common::{Error, Result};
+use reqwest::header::{HeaderName, HeaderValue};
+use sha2::digest::DynDigest;
+use sha2::{Sha256, Sha512};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{error, info};
+
+use super::driver::{ProgressFn, StorageProgress, VolumeSource};
+
+/// 检测 HTML 错误页时读取的文件头长度
+const SNIFF_LEN: usize = 512;
+
+/// 复制本地源文件的读缓冲区大小
+const COPY_BUFFER_LEN: usize = 1024 * 1024;
+
+/// 建立连接的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 下载进度的上报间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 节点级下载并发限制器
+pub struct DownloadLimiter {
+    semaphore: Arc<Semaphore>,
+    max_concurrent: usize,
+    /// 正在排队等待的下载数
+    waiting: AtomicUsize,
+}
+
+impl DownloadLimiter {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_concurrent)),
+            max_concurrent,
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    /// 获取下载许可，许可释放前占用一个并发名额
+    pub async fn acquire(&self, volume_id: &str) -> OwnedSemaphorePermit {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return permit;
+        }
+
+        let queued = self.waiting.fetch_add(1, Ordering::SeqCst) + 1;
+        info!(
+            "下载并发已满 ({}), 存储卷 {} 排队等待，当前排队数: {}",
+            self.max_concurrent, volume_id, queued
+        );
+
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("download semaphore closed");
+
+        let remaining = self.waiting.fetch_sub(1, Ordering::SeqCst) - 1;
+        info!("存储卷 {} 开始下载，剩余排队数: {}", volume_id, remaining);
+        permit
+    }
+}
+
+/// 获取存储卷源文件到本地，指定了校验和时在传输过程中计算并比对摘要
+///
+/// 支持的源地址：
+/// - http(s)://：可附加自定义请求头与 Basic/Bearer 认证
+/// - file://：复制节点本地文件，允许的目录由 StorageManager 在调用前校验
+///
+/// s3:// 地址由 Server 签发为预签名 URL 后下发，Agent 不直接访问对象存储。
+///
+/// 传输期间约每秒回调一次进度（已传输字节数 / 总大小）。失败时删除已写入的文件，
+/// 除连接失败外以下情况也视为失败：
+/// - HTTP 错误状态码
+/// - 服务端返回 200 但内容是 HTML 页面（登录页、错误页等）
+/// - 摘要与校验和不一致，返回 `Error::ChecksumMismatch`
+pub async fn download(source: &VolumeSource, dest: &Path, progress: Option<&ProgressFn>) -> Result<()> {
+    let result = match local_path(&source.url) {
+        Some(path) => copy_local(path, dest, source.checksum.as_ref(), progress).await,
+        None => download_to_file(source, dest, progress).await,
+    };
+    if result.is_err() {
+        let _ = fs::remove_file(dest).await;
+    }
+    result
+}
+
+/// file:// 源地址对应的本地路径，只接受绝对路径
+pub fn local_path(url: &str) -> Option<&Path> {
+    url.strip_prefix("file://")
+        .filter(|path| path.starts_with('/'))
+        .map(Path::new)
+}
+
+async fn download_to_file(
+    source: &VolumeSource,
+    dest: &Path,
+    progress: Option<&ProgressFn>,
+) -> Result<()> {
+    let url = source.url.as_str();
+    let scheme = url.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase());
+    if !matches!(scheme.as_deref(), Some("http" | "https")) {
+        return Err(Error::InvalidArgument(format!(
+            "Unsupported source URL: {}",
+            redact_url(url)
+        )));
+    }
+
+    // 错误信息中的 URL 可能包含预签名参数，不附带 URL
+    let download_error =
+        |e: reqwest::Error| Error::Storage(format!("Failed to download from URL: {}", e.without_url()));
+
+    // 镜像文件可能很大，只限制建立连接的时间，不限制整体下载时长
+    let client = reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()
+        .map_err(download_error)?;
+    let mut response = build_request(&client, source)?
+        .send()
+        .await
+        .map_err(download_error)?;
+
+    let status = response.status();
+    if !status.is_success() {
+        error!("下载失败: {} 返回 HTTP {}", redact_url(url), status);
+        return Err(Error::Storage(format!(
+            "Failed to download from URL: HTTP {}",
+            status
+        )));
+    }
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim_start().to_ascii_lowercase().starts_with("text/html"));
+    if is_html {
+        return Err(html_error());
+    }
+    let total_bytes = response.content_length();
+    info!("开始下载 {}，大小: {:?} 字节", redact_url(url), total_bytes);
+
+    let mut receiver = Receiver::create(dest, source.checksum.as_ref(), total_bytes, true, progress).await?;
+    while let Some(chunk) = response.chunk().await.map_err(download_error)? {
+        receiver.write(&chunk).await?;
+    }
+    receiver.finish(source.checksum.as_ref()).await
+}
+
+/// 构造下载请求，附加源地址的请求头与认证信息（标记为敏感，不会出现在调试输出中）
+fn build_request(client: &reqwest::Client, source: &VolumeSource) -> Result<reqwest::RequestBuilder> {
+    let mut request = client.get(&source.url);
+    for (name, value) in &source.headers {
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| Error::InvalidArgument(format!("Invalid source header name: {}", name)))?;
+        let mut header_value = HeaderValue::from_str(value)
+            .map_err(|_| Error::InvalidArgument(format!("Invalid value for source header {}", name)))?;
+        header_value.set_sensitive(true);
+        request = request.header(header_name, header_value);
+    }
+    Ok(match &source.auth {
+        Some(SourceAuth::Basic { username, password }) => request.basic_auth(username, Some(password)),
+        Some(SourceAuth::Bearer { token }) => request.bearer_auth(token),
+        None => request,
+    })
+}
+
+/// 复制节点本地文件，与下载共用校验和计算与进度上报
+async fn copy_local(
+    path: &Path,
+    dest: &Path,
+    checksum: Option<&VolumeChecksum>,
+    progress: Option<&ProgressFn>,
+) -> Result<()> {
+    let mut file = fs::File::open(path).await.map_err(|e| {
+        Error::Storage(format!("Failed to open local source {}: {}", path.display(), e))
+    })?;
+    let total_bytes = file.metadata().await.ok().map(|m| m.len());
+    info!("复制本地源文件 {}，大小: {:?} 字节", path.display(), total_bytes);
+
+    let mut receiver = Receiver::create(dest, checksum, total_bytes, false, progress).await?;
+    let mut buf = vec![0u8; COPY_BUFFER_LEN];
+    loop {
+        let n = file.read(&mut buf).await.map_err(|e| {
+            Error::Storage(format!("Failed to read local source {}: {}", path.display(), e))
+        })?;
+        if n == 0 {
+            break;
+        }
+        receiver.write(&buf[..n]).await?;
+    }
+    receiver.finish(checksum).await
+}
+
+/// 将源文件数据写入本地文件，同时计算摘要、检测 HTML 页面并上报进度
+struct Receiver<'a> {
+    file: fs::File,
+    hasher: Option<Box<dyn DynDigest + Send>>,
+    /// 文件头，用于识别 HTML 页面；本地文件不检测
+    head: Option<Vec<u8>>,
+    received_bytes: u64,
+    total_bytes: Option<u64>,
+    last_report: Instant,
+    progress: Option<&'a ProgressFn>,
+}
+
+impl<'a> Receiver<'a> {
+    async fn create(
+        dest: &Path,
+        checksum: Option<&VolumeChecksum>,
+        total_bytes: Option<u64>,
+        sniff_html: bool,
+        progress: Option<&'a ProgressFn>,
+    ) -> Result<Self> {
+        let file = fs::File::create(dest)
+            .await
+            .map_err(|e| Error::Storage(format!("Failed to create download file: {}", e)))?;
+        let receiver = Self {
+            file,
+            hasher: checksum.map(|c| new_hasher(c.algorithm)),
+            head: sniff_html.then(|| Vec::with_capacity(SNIFF_LEN)),
+            received_bytes: 0,
+            total_bytes,
+            last_report: Instant::now(),
+            progress,
+        };
+        receiver.report();
+        Ok(receiver)
+    }
+
+    fn report(&self) {
+        if let Some(progress) = self.progress {
+            progress(StorageProgress::Download {
+                downloaded_bytes: self.received_bytes,
+                total_bytes: self.total_bytes,
+            });
+        }
+    }
+
+    async fn write(&mut self, chunk: &[u8]) -> Result<()> {
+        if let Some(head) = self.head.as_mut() {
+            if head.len() < SNIFF_LEN {
+                head.extend_from_slice(&chunk[..chunk.len().min(SNIFF_LEN - head.len())]);
+                if head.len() >= SNIFF_LEN && looks_like_html(head) {
+                    return Err(html_error());
+                }
+            }
+        }
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(chunk);
+        }
+        self.file
+            .write_all(chunk)
+            .await
+            .map_err(|e| Error::Storage(format!("Failed to write download file: {}", e)))?;
+
+        self.received_bytes += chunk.len() as u64;
+        if self.last_report.elapsed() >= PROGRESS_INTERVAL {
+            self.last_report = Instant::now();
+            self.report();
+        }
+        Ok(())
+    }
+
+    async fn finish(mut self, checksum: Option<&VolumeChecksum>) -> Result<()> {
+        self.file
+            .flush()
+            .await
+            .map_err(|e| Error::Storage(format!("Failed to write download file: {}", e)))?;
+        self.report();
+
+        if let Some(total) = self.total_bytes {
+            if self.received_bytes != total {
+                return Err(Error::Storage(format!(
+                    "Download truncated: received {} of {} bytes",
+                    self.received_bytes, total
+                )));
+            }
+        }
+        if self.head.as_deref().is_some_and(looks_like_html) {
+            return Err(html_error());
+        }
+
+        if let (Some(expected), Some(hasher)) = (checksum, self.hasher) {
+            let actual = hex(&hasher.finalize());
+            if actual != expected.digest {
+                error!("下载文件校验和不匹配: 期望 {}, 实际 {}", expected.digest, actual);
+                return Err(Error::ChecksumMismatch(format!(
+                    "expected {}, got {}:{}",
+                    expected,
+                    expected.algorithm.as_str(),
+                    actual
+                )));
+            }
+            info!("下载文件校验和验证通过: {}", expected);
+        }
+        Ok(())
+    }
+}
+
+fn html_error() -> Error {
+    Error::Storage("URL returned an HTML page instead of a disk image".to_string())
+}
+
+fn new_hasher(algorithm: ChecksumAlgorithm) -> Box<dyn DynDigest + Send> {
+    match algorithm {
+        ChecksumAlgorithm::Sha256 => Box::new(Sha256::default()),
+        ChecksumAlgorithm::Sha512 => Box::new(Sha512::default()),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 文件头是否为 HTML 文档（磁盘镜像不会以 `<` 开头）
+fn looks_like_html(head: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(head);
+    let text = text.trim_start_matches('\u{feff}').trim_start().to_ascii_lowercase();
+    text.starts_with("<!doctype html") || text.starts_with("<html") || text.starts_with("<head")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    /// 启动只应答一次的 HTTP 服务，返回 URL 与收到的请求头
+    async fn serve_once(
+        content_type: &str,
+        body: &'static [u8],
+        content_length: bool,
+    ) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut header = format!("HTTP/1.1 200 OK\r\nContent-Type: {}\r\nConnection: close\r\n", content_type);
+        if content_length {
+            header.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        header.push_str("\r\n");
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            // 客户端识别出 HTML 后可能提前断开，忽略写入错误
+            let _ = socket.write_all(header.as_bytes()).await;
+            let _ = socket.write_all(body).await;
+            String::from_utf8_lossy(&request).into_owned()
+        });
+        (format!("http://{}/image.qcow2", addr), handle)
+    }
+
+    fn source(url: String, checksum: Option<&str>) -> VolumeSource {
+        VolumeSource {
+            url,
+            checksum: checksum.map(|c| c.parse().unwrap()),
+            headers: HashMap::new(),
+            auth: None,
+        }
+    }
+
+    fn temp_dest() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("download-test-{}.tmp", uuid::Uuid::new_v4()))
+    }
+
+    fn recorder() -> (ProgressFn, Arc<Mutex<Vec<StorageProgress>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        (Arc::new(move |p| sink.lock().unwrap().push(p)), events)
+    }
+
+    #[tokio::test]
+    async fn test_download_reports_progress_and_verifies_checksum() {
+        let (url, _) = serve_once("application/octet-stream", b"abc", true).await;
+        let dest = temp_dest();
+        let checksum = format!("sha256:{}", ABC_SHA256);
+        let (progress, events) = recorder();
+
+        download(&source(url, Some(&checksum)), &dest, Some(&progress)).await.unwrap();
+
+        assert_eq!(std::fs::read(&dest).unwrap(), b"abc");
+        let events = events.lock().unwrap();
+        assert_eq!(
+            events.last(),
+            Some(&StorageProgress::Download { downloaded_bytes: 3, total_bytes: Some(3) })
+        );
+        let _ = std::fs::remove_file(&dest);
+    }
+
+    #[tokio::test]
+    async fn test_download_without_content_length_reports_bytes_only() {
+        let (url, _) = serve_once("application/octet-stream", b"abcdef", false).await;
+        let dest = temp_dest();
+        let (progress, events) = recorder();
+
+        download(&source(url, None), &dest, Some(&progress)).await.unwrap();
+
+        assert_eq!(
+            events.lock().unwrap().last(),
+            Some(&StorageProgress::Download { downloaded_bytes: 6, total_bytes: None })
+        );
+        let _ = std::fs::remove_file(&dest);
+    }
+
+    #[tokio::test]
+    async fn test_download_checksum_mismatch_removes_file() {
+        let (url, _) = serve_once("application/octet-stream", b"abd", true).await;
+        let dest = temp_dest();
+        let checksum = format!("sha256:{}", ABC_SHA256);
+
+        let err = download(&source(url, Some(&checksum)), &dest, None).await.unwrap_err();
+
+        assert!(matches!(err, Error::ChecksumMismatch(_)));
+        assert!(!dest.exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_rejects_html_page() {
+        let (url, _) = serve_once("text/html; charset=utf-8", b"<html>login</html>", true).await;
+        let dest = temp_dest();
+        assert!(download(&source(url, None), &dest, None).await.is_err());
+        assert!(!dest.exists());
+
+        // Content-Type 不可信时按内容识别
+        let (url, _) = serve_once("application/octet-stream", b"<!DOCTYPE html><p>404</p>", true).await;
+        assert!(download(&source(url, None), &dest, None).await.is_err());
+        assert!(!dest.exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_sends_headers_and_auth() {
+        let (url, request) = serve_once("application/octet-stream", b"abc", true).await;
+        let dest = temp_dest();
+        let mut source = source(url, None);
+        source.headers.insert("X-Registry-Project".to_string(), "images".to_string());
+        source.auth = Some(SourceAuth::Bearer { token: "tok-123".to_string() });
+
+        download(&source, &dest, None).await.unwrap();
+
+        let request = request.await.unwrap().to_ascii_lowercase();
+        assert!(request.contains("authorization: bearer tok-123\r\n"));
+        assert!(request.contains("x-registry-project: images\r\n"));
+        let _ = std::fs::remove_file(&dest);
+    }
+
+    #[tokio::test]
+    async fn test_download_rejects_invalid_source() {
+        let dest = temp_dest();
+        let err = download(&source("s3://bucket/image.qcow2".to_string(), None), &dest, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument(_)));
+
+        let mut bad_header = source("http://127.0.0.1:1/image.qcow2".to_string(), None);
+        bad_header.headers.insert("Bad Header".to_string(), "x".to_string());
+        let err = download(&bad_header, &dest, None).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument(_)));
+        assert!(!dest.exists());
+    }
+
+    #[tokio::test]
+    async fn test_copy_local_file_verifies_checksum() {
+        let src = temp_dest();
+        std::fs::write(&src, b"abc").unwrap();
+        let dest = temp_dest();
+        let checksum = format!("sha256:{}", ABC_SHA256);
+        let (progress, events) = recorder();
+
+        let url = format!("file://{}", src.display());
+        download(&source(url.clone(), Some(&checksum)), &dest, Some(&progress)).await.unwrap();
+
+        assert_eq!(std::fs::read(&dest).unwrap(), b"abc");
+        assert_eq!(
+            events.lock().unwrap().last(),
+            Some(&StorageProgress::Download { downloaded_bytes: 3, total_bytes: Some(3) })
+        );
+
+        std::fs::write(&src, b"abd").unwrap();
+        let err = download(&source(url, Some(&checksum)), &dest, None).await.unwrap_err();
+        assert!(matches!(err, Error::ChecksumMismatch(_)));
+        assert!(!dest.exists());
+        let _ = std::fs::remove_file(&src);
+    }
+
+    #[test]
+    fn test_local_path() {
+        assert_eq!(local_path("file:///var/lib/images/a.img"), Some(Path::new("/var/lib/images/a.img")));
+        assert_eq!(local_path("file://relative/a.img"), None);
+        assert_eq!(local_path("https://example.com/a.img"), None);
+    }
+
+    #[test]
+    fn test_streamed_digest_matches_whole_input() {
+        let mut hasher = new_hasher(ChecksumAlgorithm::Sha256);
+        for chunk in [&b"a"[..], b"b", b"c"] {
+            hasher.update(chunk);
+        }
+        assert_eq!(
+            hex(&hasher.finalize()),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let mut hasher = new_hasher(ChecksumAlgorithm::Sha512);
+        hasher.update(b"abc");
+        assert!(hex(&hasher.finalize()).starts_with("ddaf35a193617aba"));
+    }
+
+    #[test]
+    fn test_looks_like_html() {
+        assert!(looks_like_html(b"<!DOCTYPE html><html><body>404</body></html>"));
+        assert!(looks_like_html(b"\n  <html lang=\"en\">"));
+        assert!(looks_like_html("\u{feff}<!doctype html>".as_bytes()));
+        assert!(!looks_like_html(b"QFI\xfb\x00\x00\x00\x03"));
+        assert!(!looks_like_html(b""));
+    }
+}
@@ -2,9 +2,29 @@
 ///
 /// 定义统一的存储驱动接口，支持多种存储后端
 use async_trait::async_trait;
+use common::utils::redact_url;
+use common::ws_rpc::{SourceAuth, VolumeChecksum, VolumeImportMode};
 use common::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use super::encryption::Passphrase;
+
+/// 长时间存储操作的进度
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageProgress {
+    /// 下载源文件，服务端未返回 Content-Length 时 total_bytes 为空
+    Download {
+        downloaded_bytes: u64,
+        total_bytes: Option<u64>,
+    },
+    /// qemu-img 格式转换，完成百分比（0-100）
+    Convert { percent: f64 },
+}
+
+/// 长时间存储操作的进度回调
+pub type ProgressFn = Arc<dyn Fn(StorageProgress) + Send + Sync>;
 
 /// 存储卷信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +38,82 @@ pub struct VolumeInfo {
     pub status: String,
 }
 
+/// 导入已有镜像的结果
+#[derive(Debug, Clone)]
+pub struct ImportedVolume {
+    pub volume: VolumeInfo,
+    /// 解析符号链接与 `..` 后的源镜像路径，服务端据此判断镜像是否已被登记
+    pub source_path: String,
+}
+
+/// 快照信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub name: String,
+    pub vm_state_size: u64,
+    pub created_at: Option<i64>,
+}
+
+/// 孤立卷文件（存储池中存在但没有对应卷记录）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphanedFile {
+    pub file_name: String,
+    pub path: String,
+    pub size_bytes: u64,
+    pub modified_at: Option<i64>,
+}
+
+/// 从外部 URL 创建存储卷时的数据源
+///
+/// Debug 输出隐藏 URL 中的凭据，只列出请求头名称
+#[derive(Clone)]
+pub struct VolumeSource {
+    /// http(s):// 或 file:// 地址
+    pub url: String,
+    /// 下载内容的校验和，为空时不校验
+    pub checksum: Option<VolumeChecksum>,
+    /// 下载时附加的请求头
+    pub headers: HashMap<String, String>,
+    /// 下载时使用的认证信息
+    pub auth: Option<SourceAuth>,
+}
+
+impl std::fmt::Debug for VolumeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VolumeSource")
+            .field("url", &redact_url(&self.url))
+            .field("checksum", &self.checksum.as_ref().map(|c| c.to_string()))
+            .field("headers", &self.headers.keys().collect::<Vec<_>>())
+            .field("auth", &self.auth)
+            .finish()
+    }
+}
+
+/// 跨存储池克隆时的源数据位置
+#[derive(Debug, Clone)]
+pub struct CloneSource {
+    pub path: String,
+    pub format: String,
+    /// qcow2 内部快照名，读取时通过 `-l snapshot.name=` 指定
+    pub snapshot_name: Option<String>,
+}
+
+/// 供下载的卷导出副本
+#[derive(Debug, Clone)]
+pub struct ExportInfo {
+    pub path: String,
+    pub format: String,
+    pub size_bytes: u64,
+}
+
+/// 存储池底层存储的实际容量（字节）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolUsage {
+    pub total_bytes: u64,
+    pub used_bytes: u64,
+    pub available_bytes: u64,
+}
+
 /// 存储池配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoragePoolConfig {
@@ -37,7 +133,11 @@ pub trait StorageDriver: Send + Sync + 'static {
         name: &str,
         size_gb: u64,
         format: &str,
-        source: Option<&str>, // 外部URL，可选
+        source: Option<&VolumeSource>, // 外部URL，可选
+        compress: bool,                // 是否压缩（仅 qcow2 支持）
+        backing_volume_id: Option<&str>, // 基础镜像卷 ID，指定时创建 qcow2 链接克隆
+        encryption: Option<&Passphrase>, // LUKS 口令，指定时创建加密的 qcow2 卷
+        progress: Option<ProgressFn>, // 从 URL 创建时的下载与格式转换进度
     ) -> Result<VolumeInfo>;
 
     /// 删除存储卷
@@ -61,14 +161,51 @@ pub trait StorageDriver: Send + Sync + 'static {
     /// 恢复快照
     async fn restore_snapshot(&self, volume_id: &str, snapshot_id: &str) -> Result<()>;
 
-    /// 克隆存储卷
+    /// 列出卷文件中实际存在的快照
+    async fn list_snapshots(&self, volume_id: &str) -> Result<Vec<SnapshotInfo>>;
+
+    /// 列出不属于任何已知卷的文件（被引用为 backing file 的文件除外）
+    async fn list_orphaned_files(&self, known_volume_ids: &HashSet<String>) -> Result<Vec<OrphanedFile>>;
+
+    /// 删除孤立文件，删除前重新核对，返回实际删除的文件名
+    async fn delete_orphaned_files(
+        &self,
+        known_volume_ids: &HashSet<String>,
+        file_names: &[String],
+    ) -> Result<Vec<String>>;
+
+    /// 克隆存储卷，指定 snapshot_id 时从该快照时间点克隆
     async fn clone_volume(
         &self,
         source_volume_id: &str,
         target_volume_id: &str,
         target_name: &str,
+        snapshot_id: Option<&str>,
     ) -> Result<VolumeInfo>;
 
+    /// 定位卷（或其快照）的源数据，供其他存储池导入
+    async fn clone_source(&self, volume_id: &str, snapshot_id: Option<&str>) -> Result<CloneSource>;
+
+    /// 从其他存储池的源数据完整拷贝出本存储池的新卷
+    async fn clone_from_source(
+        &self,
+        source: &CloneSource,
+        target_volume_id: &str,
+        target_name: &str,
+    ) -> Result<VolumeInfo>;
+
+    /// 把存储池中已存在的镜像文件登记为卷，返回 qemu-img 检测到的格式、虚拟大小与源镜像的规范路径
+    async fn import_volume(&self, path: &str, volume_id: &str, mode: VolumeImportMode) -> Result<ImportedVolume>;
+
+    /// 生成卷的一致性导出副本（已存在且不旧于源卷时直接复用）
+    async fn prepare_export(&self, volume_id: &str) -> Result<ExportInfo>;
+
+    /// 从导出副本的 offset 处读取至多 length 字节
+    async fn read_export(&self, volume_id: &str, offset: u64, length: u64) -> Result<Vec<u8>>;
+
+    /// 查询存储池底层存储的实际容量
+    async fn pool_usage(&self) -> Result<PoolUsage>;
+
     /// 获取存储驱动类型
     fn driver_type(&self) -> &str;
 }
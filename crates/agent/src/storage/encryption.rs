@@ -0,0 +1,135 @@
+/// 存储卷 LUKS 加密
+///
+/// 口令只在内存中传递：qemu-img 通过权限为 0600 的临时文件读取，用完即删；
+/// 虚拟机启动时定义为 libvirt 的临时私有 secret，不落盘也不能通过 libvirt 读回
+
+use common::ws_rpc::VolumeEncryption;
+use common::{Error, Result};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+/// qemu-img 命令中引用口令的 secret 对象 ID
+pub const QEMU_SECRET_ID: &str = "sec0";
+
+/// LUKS 口令，Debug 输出不包含内容
+#[derive(Clone)]
+pub struct Passphrase(String);
+
+impl Passphrase {
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl std::fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Passphrase(***)")
+    }
+}
+
+/// 取得加密配置对应的口令，密钥文件从 `key_dir` 下读取
+pub fn resolve_passphrase(encryption: &VolumeEncryption, key_dir: &Path) -> Result<Passphrase> {
+    let passphrase = match encryption {
+        VolumeEncryption::Passphrase { passphrase } => passphrase.clone(),
+        VolumeEncryption::KeyFile { key_id } => {
+            validate_key_id(key_id)?;
+            let content = std::fs::read_to_string(key_dir.join(key_id)).map_err(|e| {
+                Error::InvalidArgument(format!("Failed to read encryption key {}: {}", key_id, e))
+            })?;
+            content.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if passphrase.is_empty() {
+        return Err(Error::InvalidArgument("Encryption passphrase must not be empty".to_string()));
+    }
+    Ok(Passphrase(passphrase))
+}
+
+/// 密钥 ID 会拼接为文件路径，只接受不含路径分隔符的文件名
+fn validate_key_id(key_id: &str) -> Result<()> {
+    let valid = !key_id.is_empty()
+        && !key_id.starts_with('.')
+        && key_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument(format!("Invalid encryption key id: {}", key_id)))
+    }
+}
+
+/// 供 qemu-img `--object secret,file=` 读取的口令文件，离开作用域时删除
+pub struct SecretFile {
+    path: PathBuf,
+}
+
+impl SecretFile {
+    pub fn create(passphrase: &Passphrase) -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("easy-vm-cloud-secret-{}", uuid::Uuid::new_v4()));
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .map_err(|e| Error::Storage(format!("Failed to create secret file: {}", e)))?;
+        let secret = Self { path };
+        file.write_all(passphrase.as_bytes())
+            .map_err(|e| Error::Storage(format!("Failed to write secret file: {}", e)))?;
+        Ok(secret)
+    }
+
+    /// qemu-img 的 `--object` 参数
+    pub fn object_arg(&self) -> String {
+        format!("secret,id={},file={}", QEMU_SECRET_ID, self.path.display())
+    }
+}
+
+impl Drop for SecretFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_passphrase_from_key_file() {
+        let dir = std::env::temp_dir().join(format!("keys-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("tenant-a"), "s3cret\n").unwrap();
+
+        let key_file = |key_id: &str| VolumeEncryption::KeyFile { key_id: key_id.to_string() };
+        assert_eq!(resolve_passphrase(&key_file("tenant-a"), &dir).unwrap().as_bytes(), b"s3cret");
+        assert!(resolve_passphrase(&key_file("missing"), &dir).is_err());
+        assert!(resolve_passphrase(&key_file("../tenant-a"), &dir).is_err());
+        assert!(resolve_passphrase(&key_file(".hidden"), &dir).is_err());
+
+        let passphrase = VolumeEncryption::Passphrase { passphrase: String::new() };
+        assert!(resolve_passphrase(&passphrase, &dir).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_secret_file_is_private_and_removed_on_drop() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let passphrase =
+            resolve_passphrase(&VolumeEncryption::Passphrase { passphrase: "pw".to_string() }, Path::new("/"))
+                .unwrap();
+        assert_eq!(format!("{:?}", passphrase), "Passphrase(***)");
+
+        let secret = SecretFile::create(&passphrase).unwrap();
+        let path = secret.path.clone();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "pw");
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(secret.object_arg().starts_with("secret,id=sec0,file="));
+
+        drop(secret);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,821 @@
+/// LVM 存储驱动
+///
+/// 在卷组中为每个存储卷创建一个逻辑卷（raw 块设备），LV 名称即卷 ID；
+/// 快照使用 LVM 快照卷，命名为 `{volume_id}-{snapshot_id}`
+use async_trait::async_trait;
+use common::ws_rpc::VolumeImportMode;
+use common::{Error, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tokio::fs;
+use tokio::process::Command;
+use tracing::{debug, error, info, warn};
+
+use super::command::CommandPolicy;
+use super::driver::{
+    CloneSource, ExportInfo, ImportedVolume, OrphanedFile, PoolUsage, ProgressFn, SnapshotInfo, StorageDriver,
+    StoragePoolConfig, VolumeInfo, VolumeSource,
+};
+use super::encryption::Passphrase;
+
+/// 驱动创建的 LV 都带有此标签，孤立卷扫描只考虑带标签的 LV，
+/// 避免把卷组中由其他程序创建的 LV 当作孤立卷删除
+const LV_TAG: &str = "easy-vm-cloud";
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+/// `lvs` 需要输出的字段
+const LVS_FIELDS: &str = "lv_name,lv_path,lv_size,lv_attr,origin,lv_tags,lv_time";
+
+/// `lvs --reportformat json` 的输出
+#[derive(Debug, Deserialize)]
+struct LvsOutput {
+    report: Vec<LvsReport>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LvsReport {
+    #[serde(default)]
+    lv: Vec<LogicalVolume>,
+}
+
+/// 单个逻辑卷的信息（lvs 以字符串输出所有字段）
+#[derive(Debug, Clone, Deserialize)]
+struct LogicalVolume {
+    lv_name: String,
+    #[serde(default)]
+    lv_path: String,
+    /// 以 `--units b --nosuffix` 输出的字节数
+    lv_size: String,
+    lv_attr: String,
+    #[serde(default)]
+    origin: String,
+    #[serde(default)]
+    lv_tags: String,
+    #[serde(default)]
+    lv_time: String,
+}
+
+impl LogicalVolume {
+    fn size_bytes(&self) -> u64 {
+        self.lv_size.parse().unwrap_or(0)
+    }
+
+    /// lv_attr 第 1 位为 s/S 表示快照卷
+    fn is_snapshot(&self) -> bool {
+        !self.origin.is_empty() || matches!(self.lv_attr.chars().next(), Some('s' | 'S'))
+    }
+
+    /// lv_attr 第 6 位为 o 表示设备被打开（虚拟机正在使用等）
+    fn is_open(&self) -> bool {
+        self.lv_attr.chars().nth(5) == Some('o')
+    }
+
+    fn is_managed(&self) -> bool {
+        self.lv_tags.split(',').any(|tag| tag == LV_TAG)
+    }
+
+    /// lv_time 形如 `2024-05-01 10:00:00 +0800`
+    fn created_at(&self) -> Option<i64> {
+        chrono::DateTime::parse_from_str(&self.lv_time, "%Y-%m-%d %H:%M:%S %z")
+            .ok()
+            .map(|t| t.timestamp())
+    }
+}
+
+/// LVM 存储驱动
+pub struct LvmDriver {
+    /// 卷组名称
+    vg_name: String,
+    /// lvm / qemu-img 调用的超时与重试策略
+    commands: CommandPolicy,
+}
+
+impl LvmDriver {
+    /// 创建新的 LVM 驱动实例
+    pub fn new(pool_config: StoragePoolConfig, commands: CommandPolicy) -> Result<Self> {
+        let vg_name = pool_config
+            .config
+            .get("vg_name")
+            .ok_or_else(|| Error::Config("LVM vg_name not configured".to_string()))?;
+        validate_lv_name(vg_name)
+            .map_err(|_| Error::Config(format!("Invalid LVM vg_name: {}", vg_name)))?;
+
+        info!(
+            "LVM pool {} uses volume group {}",
+            pool_config.pool_name, vg_name
+        );
+        Ok(Self {
+            vg_name: vg_name.clone(),
+            commands,
+        })
+    }
+
+    /// `vg/lv` 形式的 LV 标识
+    fn lv_spec(&self, lv_name: &str) -> String {
+        format!("{}/{}", self.vg_name, lv_name)
+    }
+
+    /// LV 的设备路径
+    fn lv_path(&self, lv_name: &str) -> PathBuf {
+        PathBuf::from("/dev").join(&self.vg_name).join(lv_name)
+    }
+
+    fn snapshot_lv_name(volume_id: &str, snapshot_id: &str) -> String {
+        format!("{}-{}", volume_id, snapshot_id)
+    }
+
+    /// 执行 LVM / qemu-img 命令，失败时返回 stderr
+    async fn run(&self, program: &str, args: &[&str]) -> Result<Vec<u8>> {
+        debug!("Running {} {}", program, args.join(" "));
+        let output = self.commands.output(Command::new(program).args(args)).await?;
+        Self::stdout_of(program, output)
+    }
+
+    /// 执行 qemu-img convert，耗时与镜像大小成正比，使用 convert 的超时
+    async fn run_convert(&self, args: &[&str]) -> Result<Vec<u8>> {
+        debug!("Running qemu-img {}", args.join(" "));
+        let output = self
+            .commands
+            .convert(Command::new("qemu-img").args(args), None)
+            .await?;
+        Self::stdout_of("qemu-img", output)
+    }
+
+    fn stdout_of(program: &str, output: std::process::Output) -> Result<Vec<u8>> {
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!("{} failed: {}", program, stderr);
+            return Err(Error::Storage(format!(
+                "{} failed: {}",
+                program,
+                stderr.trim()
+            )));
+        }
+        Ok(output.stdout)
+    }
+
+    /// 列出卷组中的所有 LV
+    async fn list_lvs(&self) -> Result<Vec<LogicalVolume>> {
+        let stdout = self.run(
+            "lvs",
+            &[
+                "--reportformat",
+                "json",
+                "--units",
+                "b",
+                "--nosuffix",
+                "-o",
+                LVS_FIELDS,
+                &self.vg_name,
+            ],
+        )
+        .await?;
+        Self::parse_lvs_output(&stdout)
+    }
+
+    fn parse_lvs_output(stdout: &[u8]) -> Result<Vec<LogicalVolume>> {
+        let output: LvsOutput = serde_json::from_slice(stdout)
+            .map_err(|e| Error::Storage(format!("Failed to parse lvs output: {}", e)))?;
+        Ok(output.report.into_iter().flat_map(|r| r.lv).collect())
+    }
+
+    /// 解析 `vgs --noheadings --units b --nosuffix -o vg_size,vg_free` 的输出
+    fn parse_vgs_output(stdout: &[u8]) -> Result<PoolUsage> {
+        let output = String::from_utf8_lossy(stdout);
+        let fields: Vec<u64> = output
+            .split_whitespace()
+            .map(|field| field.parse())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| Error::Storage(format!("Failed to parse vgs output: {}", e)))?;
+        match fields[..] {
+            [total_bytes, free_bytes] => Ok(PoolUsage {
+                total_bytes,
+                used_bytes: total_bytes.saturating_sub(free_bytes),
+                available_bytes: free_bytes,
+            }),
+            _ => Err(Error::Storage(format!("Unexpected vgs output: {}", output.trim()))),
+        }
+    }
+
+    /// 查找存储卷对应的 LV（不含快照卷）
+    async fn find_volume(&self, volume_id: &str) -> Result<LogicalVolume> {
+        self.list_lvs()
+            .await?
+            .into_iter()
+            .find(|lv| lv.lv_name == volume_id && !lv.is_snapshot())
+            .ok_or_else(|| Error::NotFound(format!("Volume {} not found", volume_id)))
+    }
+
+    /// 查找存储卷的快照卷
+    async fn find_snapshot(&self, volume_id: &str, snapshot_id: &str) -> Result<LogicalVolume> {
+        let lv_name = Self::snapshot_lv_name(volume_id, snapshot_id);
+        self.list_lvs()
+            .await?
+            .into_iter()
+            .find(|lv| lv.lv_name == lv_name && lv.origin == volume_id)
+            .ok_or_else(|| Error::NotFound(format!("Snapshot {} not found", snapshot_id)))
+    }
+
+    async fn ensure_absent(&self, lv_name: &str) -> Result<()> {
+        if self.list_lvs().await?.iter().any(|lv| lv.lv_name == lv_name) {
+            return Err(Error::AlreadyExists(format!(
+                "Volume {} already exists",
+                lv_name
+            )));
+        }
+        Ok(())
+    }
+
+    /// 创建带管理标签的 LV，size 为 lvcreate -L 接受的大小（如 `10G`、`1073741824b`）
+    async fn create_lv(&self, lv_name: &str, size: &str) -> Result<()> {
+        validate_lv_name(lv_name)?;
+        self.run(
+            "lvcreate",
+            &[
+                "-y",
+                "--wipesignatures",
+                "y",
+                "-n",
+                lv_name,
+                "-L",
+                size,
+                "--addtag",
+                LV_TAG,
+                &self.vg_name,
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_lv(&self, lv_name: &str) -> Result<()> {
+        self.run("lvremove", &["-y", &self.lv_spec(lv_name)]).await?;
+        Ok(())
+    }
+
+    /// 将源数据完整写入已创建的目标 LV，失败时删除目标 LV
+    async fn copy_into_lv(
+        &self,
+        source_path: &str,
+        source_format: &str,
+        snapshot_name: Option<&str>,
+        target_lv: &str,
+    ) -> Result<()> {
+        let target_path = self.lv_path(target_lv).to_string_lossy().to_string();
+        let snapshot_arg = snapshot_name.map(|name| format!("snapshot.name={}", name));
+
+        // -n：目标是已存在的块设备，不由 qemu-img 创建
+        let mut args = vec!["convert", "-n", "-f", source_format];
+        if let Some(snapshot_arg) = &snapshot_arg {
+            args.extend(["-l", snapshot_arg.as_str()]);
+        }
+        args.extend(["-O", "raw", source_path, target_path.as_str()]);
+
+        if let Err(e) = self.run_convert(&args).await {
+            if let Err(cleanup) = self.remove_lv(target_lv).await {
+                warn!("Failed to remove incomplete volume {}: {}", target_lv, cleanup);
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// 读取镜像的虚拟大小（字节）
+    async fn image_virtual_size(&self, path: &str, format: &str) -> Result<u64> {
+        let stdout =
+            self.run("qemu-img", &["info", "--output=json", "-f", format, path]).await?;
+        let info: serde_json::Value = serde_json::from_slice(&stdout)
+            .map_err(|e| Error::Storage(format!("Failed to parse qemu-img output: {}", e)))?;
+        info["virtual-size"].as_u64().ok_or_else(|| {
+            Error::Storage("virtual-size not found in qemu-img output".to_string())
+        })
+    }
+
+    fn volume_info(lv: &LogicalVolume, name: &str) -> VolumeInfo {
+        let size_gb = lv.size_bytes() / GIB;
+        VolumeInfo {
+            volume_id: lv.lv_name.clone(),
+            name: name.to_string(),
+            path: lv.lv_path.clone(),
+            size_gb,
+            // LV 为厚置备，实际占用即为 LV 大小
+            actual_size_gb: size_gb,
+            format: "raw".to_string(),
+            status: "available".to_string(),
+        }
+    }
+
+    /// 找出不属于任何已知卷、且未被使用的受管 LV
+    fn orphaned_lvs(
+        lvs: Vec<LogicalVolume>,
+        known_volume_ids: &HashSet<String>,
+    ) -> Vec<LogicalVolume> {
+        lvs.into_iter()
+            .filter(|lv| lv.is_managed() && !lv.is_open())
+            .filter(|lv| {
+                if lv.is_snapshot() {
+                    !known_volume_ids.contains(&lv.origin)
+                } else {
+                    !known_volume_ids.contains(&lv.lv_name)
+                }
+            })
+            .collect()
+    }
+}
+
+/// 校验 LVM 名称：只允许字母、数字和 `+_.-`，不能以 `-` 开头
+fn validate_lv_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 127
+        && !name.starts_with('-')
+        && name != "."
+        && name != ".."
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '_' | '.' | '-'));
+    if !valid {
+        return Err(Error::InvalidArgument(format!(
+            "Invalid LVM volume name: {}",
+            name
+        )));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl StorageDriver for LvmDriver {
+    async fn create_volume(
+        &self,
+        volume_id: &str,
+        name: &str,
+        size_gb: u64,
+        format: &str,
+        source: Option<&VolumeSource>,
+        compress: bool,
+        backing_volume_id: Option<&str>,
+        encryption: Option<&Passphrase>,
+        _progress: Option<ProgressFn>,
+    ) -> Result<VolumeInfo> {
+        info!(
+            "Creating LVM volume: id={}, name={}, size={}GB, format={}, vg={}",
+            volume_id, name, size_gb, format, self.vg_name
+        );
+
+        if format != "raw" {
+            return Err(Error::InvalidArgument(format!(
+                "LVM volumes only support raw format, got {}",
+                format
+            )));
+        }
+        if compress {
+            return Err(Error::InvalidArgument(
+                "Compression is not supported for LVM volumes".to_string(),
+            ));
+        }
+        if source.is_some() {
+            return Err(Error::InvalidArgument(
+                "LVM volumes cannot be created from a URL; create it in an NFS pool and clone it instead"
+                    .to_string(),
+            ));
+        }
+        if encryption.is_some() {
+            return Err(Error::InvalidArgument(
+                "LVM volumes do not support encryption; use a qcow2 volume in an NFS pool".to_string(),
+            ));
+        }
+        if backing_volume_id.is_some() {
+            return Err(Error::InvalidArgument(
+                "LVM volumes do not support backing files; use an NFS pool for linked clones"
+                    .to_string(),
+            ));
+        }
+        if size_gb == 0 {
+            return Err(Error::InvalidArgument(
+                "Volume size must be greater than 0".to_string(),
+            ));
+        }
+
+        validate_lv_name(volume_id)?;
+        self.ensure_absent(volume_id).await?;
+        self.create_lv(volume_id, &format!("{}G", size_gb)).await?;
+
+        info!("Successfully created LVM volume {}", volume_id);
+        let lv = self.find_volume(volume_id).await?;
+        Ok(Self::volume_info(&lv, name))
+    }
+
+    async fn delete_volume(&self, volume_id: &str) -> Result<()> {
+        info!("Deleting LVM volume: {}", volume_id);
+
+        let lvs = self.list_lvs().await?;
+        let volume = lvs
+            .iter()
+            .find(|lv| lv.lv_name == volume_id && !lv.is_snapshot())
+            .ok_or_else(|| Error::NotFound(format!("Volume {} not found", volume_id)))?;
+
+        // 删除原卷会连同其快照一起删除，任何一个仍被打开都拒绝删除
+        if let Some(open) = lvs
+            .iter()
+            .filter(|lv| lv.lv_name == volume.lv_name || lv.origin == volume_id)
+            .find(|lv| lv.is_open())
+        {
+            return Err(Error::Storage(format!(
+                "Logical volume {} is still open and cannot be deleted",
+                open.lv_name
+            )));
+        }
+
+        self.remove_lv(volume_id).await?;
+        info!("Successfully deleted LVM volume {}", volume_id);
+        Ok(())
+    }
+
+    async fn resize_volume(&self, volume_id: &str, new_size_gb: u64) -> Result<VolumeInfo> {
+        info!("Resizing LVM volume: {} to {}GB", volume_id, new_size_gb);
+
+        let lv = self.find_volume(volume_id).await?;
+        let new_size_bytes = new_size_gb * GIB;
+        if new_size_bytes < lv.size_bytes() {
+            return Err(Error::InvalidArgument(format!(
+                "Shrinking LVM volume {} is not supported",
+                volume_id
+            )));
+        }
+
+        if new_size_bytes > lv.size_bytes() {
+            self.run(
+                "lvresize",
+                &["-L", &format!("{}G", new_size_gb), &self.lv_spec(volume_id)],
+            )
+            .await?;
+            info!("Successfully resized LVM volume {}", volume_id);
+        }
+
+        let lv = self.find_volume(volume_id).await?;
+        Ok(Self::volume_info(&lv, volume_id))
+    }
+
+    async fn get_volume_info(&self, volume_id: &str) -> Result<VolumeInfo> {
+        debug!("Getting LVM volume info: {}", volume_id);
+        let lv = self.find_volume(volume_id).await?;
+        Ok(Self::volume_info(&lv, volume_id))
+    }
+
+    async fn list_volumes(&self) -> Result<Vec<VolumeInfo>> {
+        debug!("Listing LVM volumes in volume group {}", self.vg_name);
+        Ok(self
+            .list_lvs()
+            .await?
+            .iter()
+            .filter(|lv| !lv.is_snapshot())
+            .map(|lv| Self::volume_info(lv, &lv.lv_name))
+            .collect())
+    }
+
+    async fn create_snapshot(&self, volume_id: &str, snapshot_id: &str) -> Result<String> {
+        info!("Creating snapshot {} for LVM volume {}", snapshot_id, volume_id);
+
+        self.find_volume(volume_id).await?;
+        let lv_name = Self::snapshot_lv_name(volume_id, snapshot_id);
+        validate_lv_name(&lv_name)?;
+        self.ensure_absent(&lv_name).await?;
+
+        // 快照空间与原卷等大，原卷被完全改写也不会使快照失效
+        self.run(
+            "lvcreate",
+            &[
+                "-y",
+                "-s",
+                "-l",
+                "100%ORIGIN",
+                "-n",
+                &lv_name,
+                "--addtag",
+                LV_TAG,
+                &self.lv_spec(volume_id),
+            ],
+        )
+        .await?;
+
+        info!(
+            "Successfully created snapshot {} for LVM volume {}",
+            snapshot_id, volume_id
+        );
+        Ok(snapshot_id.to_string())
+    }
+
+    async fn delete_snapshot(&self, volume_id: &str, snapshot_id: &str) -> Result<()> {
+        info!("Deleting snapshot {} of LVM volume {}", snapshot_id, volume_id);
+
+        let snapshot = self.find_snapshot(volume_id, snapshot_id).await?;
+        self.remove_lv(&snapshot.lv_name).await?;
+
+        info!(
+            "Successfully deleted snapshot {} of LVM volume {}",
+            snapshot_id, volume_id
+        );
+        Ok(())
+    }
+
+    async fn restore_snapshot(&self, volume_id: &str, snapshot_id: &str) -> Result<()> {
+        info!("Restoring LVM volume {} from snapshot {}", volume_id, snapshot_id);
+
+        let volume = self.find_volume(volume_id).await?;
+        if volume.is_open() {
+            return Err(Error::Storage(format!(
+                "Logical volume {} is still open and cannot be restored",
+                volume_id
+            )));
+        }
+        let snapshot = self.find_snapshot(volume_id, snapshot_id).await?;
+
+        // 合并后快照卷被消耗，原卷回到快照时的内容
+        self.run("lvconvert", &["-y", "--merge", &self.lv_spec(&snapshot.lv_name)]).await?;
+
+        info!(
+            "Successfully restored LVM volume {} from snapshot {}",
+            volume_id, snapshot_id
+        );
+        Ok(())
+    }
+
+    async fn list_snapshots(&self, volume_id: &str) -> Result<Vec<SnapshotInfo>> {
+        let lvs = self.list_lvs().await?;
+        if !lvs
+            .iter()
+            .any(|lv| lv.lv_name == volume_id && !lv.is_snapshot())
+        {
+            return Err(Error::NotFound(format!("Volume {} not found", volume_id)));
+        }
+
+        let prefix = format!("{}-", volume_id);
+        Ok(lvs
+            .iter()
+            .filter(|lv| lv.origin == volume_id)
+            .filter_map(|lv| {
+                lv.lv_name.strip_prefix(&prefix).map(|snapshot_id| SnapshotInfo {
+                    name: snapshot_id.to_string(),
+                    vm_state_size: 0,
+                    created_at: lv.created_at(),
+                })
+            })
+            .collect())
+    }
+
+    async fn list_orphaned_files(&self, known_volume_ids: &HashSet<String>) -> Result<Vec<OrphanedFile>> {
+        info!("Scanning orphaned logical volumes in {}", self.vg_name);
+        Ok(Self::orphaned_lvs(self.list_lvs().await?, known_volume_ids)
+            .into_iter()
+            .map(|lv| OrphanedFile {
+                size_bytes: lv.size_bytes(),
+                modified_at: lv.created_at(),
+                file_name: lv.lv_name,
+                path: lv.lv_path,
+            })
+            .collect())
+    }
+
+    async fn delete_orphaned_files(
+        &self,
+        known_volume_ids: &HashSet<String>,
+        file_names: &[String],
+    ) -> Result<Vec<String>> {
+        // 重新扫描，只删除此刻仍为孤立状态的 LV
+        let orphans = Self::orphaned_lvs(self.list_lvs().await?, known_volume_ids);
+        let mut deleted = Vec::new();
+
+        for orphan in orphans.iter().filter(|lv| file_names.contains(&lv.lv_name)) {
+            match self.remove_lv(&orphan.lv_name).await {
+                Ok(()) => {
+                    info!("Deleted orphaned logical volume {}", orphan.lv_name);
+                    deleted.push(orphan.lv_name.clone());
+                }
+                Err(e) => {
+                    warn!("Failed to delete orphaned logical volume {}: {}", orphan.lv_name, e);
+                }
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    async fn clone_volume(
+        &self,
+        source_volume_id: &str,
+        target_volume_id: &str,
+        target_name: &str,
+        snapshot_id: Option<&str>,
+    ) -> Result<VolumeInfo> {
+        info!(
+            "Cloning LVM volume {} to {} with name {} (snapshot: {:?})",
+            source_volume_id, target_volume_id, target_name, snapshot_id
+        );
+
+        let source = self.find_volume(source_volume_id).await.map_err(|_| {
+            Error::NotFound(format!("Source volume {} not found", source_volume_id))
+        })?;
+        let copy_from = match snapshot_id {
+            Some(snapshot_id) => self.find_snapshot(source_volume_id, snapshot_id).await?,
+            None => source.clone(),
+        };
+
+        validate_lv_name(target_volume_id)?;
+        self.ensure_absent(target_volume_id).await?;
+        self.create_lv(target_volume_id, &format!("{}b", source.size_bytes()))
+            .await?;
+        self.copy_into_lv(&copy_from.lv_path, "raw", None, target_volume_id)
+            .await?;
+
+        info!(
+            "Successfully cloned LVM volume {} to {}",
+            source_volume_id, target_volume_id
+        );
+        let lv = self.find_volume(target_volume_id).await?;
+        Ok(Self::volume_info(&lv, target_name))
+    }
+
+    async fn clone_source(&self, volume_id: &str, snapshot_id: Option<&str>) -> Result<CloneSource> {
+        let lv = match snapshot_id {
+            Some(snapshot_id) => self.find_snapshot(volume_id, snapshot_id).await?,
+            None => self.find_volume(volume_id).await.map_err(|_| {
+                Error::NotFound(format!("Source volume {} not found", volume_id))
+            })?,
+        };
+
+        Ok(CloneSource {
+            path: lv.lv_path,
+            format: "raw".to_string(),
+            snapshot_name: None,
+        })
+    }
+
+    async fn clone_from_source(
+        &self,
+        source: &CloneSource,
+        target_volume_id: &str,
+        target_name: &str,
+    ) -> Result<VolumeInfo> {
+        info!(
+            "Importing LVM volume {} from {} (format: {}, snapshot: {:?})",
+            target_volume_id, source.path, source.format, source.snapshot_name
+        );
+
+        validate_lv_name(target_volume_id)?;
+        self.ensure_absent(target_volume_id).await?;
+
+        let size_bytes = self.image_virtual_size(&source.path, &source.format).await?;
+        self.create_lv(target_volume_id, &format!("{}b", size_bytes))
+            .await?;
+        self.copy_into_lv(
+            &source.path,
+            &source.format,
+            source.snapshot_name.as_deref(),
+            target_volume_id,
+        )
+        .await?;
+
+        let lv = self.find_volume(target_volume_id).await?;
+        Ok(Self::volume_info(&lv, target_name))
+    }
+
+    async fn import_volume(&self, path: &str, volume_id: &str, _mode: VolumeImportMode) -> Result<ImportedVolume> {
+        Err(Error::Storage(format!(
+            "LVM pools do not support importing existing images ({} -> {}); use an NFS pool",
+            path, volume_id
+        )))
+    }
+
+    async fn prepare_export(&self, volume_id: &str) -> Result<ExportInfo> {
+        // 未被打开的 LV 内容不会变化，直接从设备读取，无需额外的导出副本
+        let lv = self.find_volume(volume_id).await?;
+        if lv.is_open() {
+            return Err(Error::Storage(format!(
+                "Logical volume {} is still open and cannot be exported",
+                volume_id
+            )));
+        }
+
+        Ok(ExportInfo {
+            size_bytes: lv.size_bytes(),
+            path: lv.lv_path,
+            format: "raw".to_string(),
+        })
+    }
+
+    async fn read_export(&self, volume_id: &str, offset: u64, length: u64) -> Result<Vec<u8>> {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let mut device = fs::File::open(self.lv_path(volume_id)).await.map_err(|e| {
+            Error::NotFound(format!("Volume {} not found: {}", volume_id, e))
+        })?;
+        device
+            .seek(std::io::SeekFrom::Start(offset))
+            .await
+            .map_err(|e| Error::Storage(format!("Failed to seek logical volume: {}", e)))?;
+
+        let mut buf = Vec::with_capacity(length as usize);
+        device
+            .take(length)
+            .read_to_end(&mut buf)
+            .await
+            .map_err(|e| Error::Storage(format!("Failed to read logical volume: {}", e)))?;
+        Ok(buf)
+    }
+
+    async fn pool_usage(&self) -> Result<PoolUsage> {
+        let stdout = self.run(
+            "vgs",
+            &["--noheadings", "--units", "b", "--nosuffix", "-o", "vg_size,vg_free", &self.vg_name],
+        )
+        .await?;
+        Self::parse_vgs_output(&stdout)
+    }
+
+    fn driver_type(&self) -> &str {
+        "lvm"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LVS_OUTPUT: &str = r#"{
+        "report": [
+            {
+                "lv": [
+                    {"lv_name":"vol-1", "lv_path":"/dev/vg0/vol-1", "lv_size":"10737418240", "lv_attr":"owi-aos---", "origin":"", "lv_tags":"easy-vm-cloud", "lv_time":"2024-05-01 10:00:00 +0800"},
+                    {"lv_name":"vol-1-snap-a", "lv_path":"/dev/vg0/vol-1-snap-a", "lv_size":"10737418240", "lv_attr":"swi-a-s---", "origin":"vol-1", "lv_tags":"easy-vm-cloud", "lv_time":"2024-05-02 10:00:00 +0800"},
+                    {"lv_name":"vol-2", "lv_path":"/dev/vg0/vol-2", "lv_size":"5368709120", "lv_attr":"-wi-a-----", "origin":"", "lv_tags":"easy-vm-cloud", "lv_time":"2024-05-03 10:00:00 +0800"},
+                    {"lv_name":"root", "lv_path":"/dev/vg0/root", "lv_size":"53687091200", "lv_attr":"-wi-ao----", "origin":"", "lv_tags":"", "lv_time":"2023-01-01 00:00:00 +0000"}
+                ]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_lvs_output() {
+        let lvs = LvmDriver::parse_lvs_output(LVS_OUTPUT.as_bytes()).unwrap();
+        assert_eq!(lvs.len(), 4);
+
+        let vol = &lvs[0];
+        assert_eq!(vol.size_bytes(), 10 * GIB);
+        assert!(!vol.is_snapshot());
+        assert!(vol.is_open());
+        assert!(vol.is_managed());
+        assert_eq!(vol.created_at(), Some(1714528800));
+
+        let snapshot = &lvs[1];
+        assert!(snapshot.is_snapshot());
+        assert!(!snapshot.is_open());
+
+        let info = LvmDriver::volume_info(&lvs[2], "data");
+        assert_eq!(info.size_gb, 5);
+        assert_eq!(info.format, "raw");
+        assert_eq!(info.path, "/dev/vg0/vol-2");
+        assert!(!lvs[3].is_managed());
+    }
+
+    #[test]
+    fn test_parse_vgs_output() {
+        assert_eq!(
+            LvmDriver::parse_vgs_output(b"  107374182400 32212254720\n").unwrap(),
+            PoolUsage {
+                total_bytes: 107374182400,
+                used_bytes: 75161927680,
+                available_bytes: 32212254720,
+            }
+        );
+        assert!(LvmDriver::parse_vgs_output(b"").is_err());
+        assert!(LvmDriver::parse_vgs_output(b"  100G 20G\n").is_err());
+    }
+
+    #[test]
+    fn test_orphaned_lvs() {
+        let lvs = LvmDriver::parse_lvs_output(LVS_OUTPUT.as_bytes()).unwrap();
+
+        // 已知卷及其快照、未受管的 LV 都不算孤立
+        let known: HashSet<String> = ["vol-1".to_string(), "vol-2".to_string()]
+            .into_iter()
+            .collect();
+        assert!(LvmDriver::orphaned_lvs(lvs.clone(), &known).is_empty());
+
+        // vol-1 仍被打开，只有其快照和 vol-2 可被清理
+        let orphans: Vec<String> = LvmDriver::orphaned_lvs(lvs, &HashSet::new())
+            .into_iter()
+            .map(|lv| lv.lv_name)
+            .collect();
+        assert_eq!(orphans, vec!["vol-1-snap-a", "vol-2"]);
+    }
+
+    #[test]
+    fn test_validate_lv_name() {
+        assert!(validate_lv_name("0b6c1f5e-3a2d-4c1b-9f00-1234567890ab").is_ok());
+        assert!(validate_lv_name("vol_1.data+x").is_ok());
+        for name in ["", "-vol", ".", "..", "vol/1", "vol 1"] {
+            assert!(validate_lv_name(name).is_err(), "{}", name);
+        }
+    }
+}
@@ -1,25 +1,73 @@
 /// 存储管理器
 ///
 /// 负责管理多种存储驱动，根据存储类型分发请求
+use common::ws_rpc::VolumeImportMode;
 use common::{Error, Result};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, info};
 
-use super::driver::{StorageDriver, StoragePoolConfig, VolumeInfo};
+use super::command::CommandPolicy;
+use super::download::{self, DownloadLimiter};
+use super::driver::{
+    ExportInfo, ImportedVolume, OrphanedFile, PoolUsage, ProgressFn, SnapshotInfo, StorageDriver,
+    StoragePoolConfig, VolumeInfo, VolumeSource,
+};
+use super::encryption::Passphrase;
+use super::lvm::LvmDriver;
 use super::nfs::NfsDriver;
 
 /// 存储管理器
 pub struct StorageManager {
     /// 存储驱动映射: pool_id -> driver
     drivers: Arc<RwLock<HashMap<String, Arc<dyn StorageDriver>>>>,
+    /// 所有存储池共享的 URL 下载并发限制
+    download_limiter: Arc<DownloadLimiter>,
+    /// file:// 源地址允许引用的本地目录
+    local_source_dirs: Vec<PathBuf>,
+    /// 存储驱动调用外部命令的超时与重试策略
+    commands: CommandPolicy,
 }
 
 impl StorageManager {
-    pub fn new() -> Self {
+    pub fn new(
+        max_concurrent_downloads: usize,
+        local_source_dirs: Vec<PathBuf>,
+        commands: CommandPolicy,
+    ) -> Self {
         Self {
             drivers: Arc::new(RwLock::new(HashMap::new())),
+            download_limiter: Arc::new(DownloadLimiter::new(max_concurrent_downloads)),
+            local_source_dirs,
+            commands,
+        }
+    }
+
+    /// file:// 源地址只能引用允许目录下的文件，避免通过建卷读取节点上的任意文件
+    ///
+    /// 比较前解析符号链接和 `..`，指向目录外的链接同样会被拒绝
+    fn check_local_source(&self, source: &VolumeSource) -> Result<()> {
+        let Some(path) = download::local_path(&source.url) else {
+            return Ok(());
+        };
+        let path = std::fs::canonicalize(path).map_err(|e| {
+            Error::InvalidArgument(format!("Local source {} is not accessible: {}", path.display(), e))
+        })?;
+        let allowed = self
+            .local_source_dirs
+            .iter()
+            .filter_map(|dir| std::fs::canonicalize(dir).ok())
+            .any(|dir| path.starts_with(dir));
+        if allowed {
+            Ok(())
+        } else {
+            Err(Error::InvalidArgument(format!(
+                "Local source {} is outside the allowed directories {:?}",
+                path.display(),
+                self.local_source_dirs
+            )))
         }
     }
 
@@ -31,9 +79,13 @@ impl StorageManager {
         );
 
         let driver: Arc<dyn StorageDriver> = match pool_config.storage_type.as_str() {
-            "nfs" => Arc::new(NfsDriver::new(pool_config.clone())?),
+            "nfs" => Arc::new(NfsDriver::new(
+                pool_config.clone(),
+                self.download_limiter.clone(),
+                self.commands,
+            )?),
+            "lvm" => Arc::new(LvmDriver::new(pool_config.clone(), self.commands)?),
             // 未来可以添加更多驱动类型
-            // "lvm" => Arc::new(LvmDriver::new(pool_config.clone())?),
             // "ceph" => Arc::new(CephDriver::new(pool_config.clone())?),
             _ => {
                 return Err(Error::InvalidArgument(format!(
@@ -66,16 +118,33 @@ impl StorageManager {
         name: &str,
         size_gb: u64,
         format: &str,
-        source: Option<&str>, // 外部URL，可选
+        source: Option<&VolumeSource>, // 外部URL，可选
+        compress: bool,
+        backing_volume_id: Option<&str>,
+        encryption: Option<&Passphrase>,
+        progress: Option<ProgressFn>,
     ) -> Result<VolumeInfo> {
         debug!(
-            "Creating volume: pool={}, id={}, name={}, size={}GB, format={}, source={:?}",
-            pool_id, volume_id, name, size_gb, format, source
+            "Creating volume: pool={}, id={}, name={}, size={}GB, format={}, source={:?}, compress={}, backing={:?}, encrypted={}",
+            pool_id, volume_id, name, size_gb, format, source, compress, backing_volume_id, encryption.is_some()
         );
 
+        if let Some(source) = source {
+            self.check_local_source(source)?;
+        }
         let driver = self.get_driver(pool_id).await?;
         driver
-            .create_volume(volume_id, name, size_gb, format, source)
+            .create_volume(
+                volume_id,
+                name,
+                size_gb,
+                format,
+                source,
+                compress,
+                backing_volume_id,
+                encryption,
+                progress,
+            )
             .await
     }
 
@@ -167,6 +236,14 @@ impl StorageManager {
         driver.restore_snapshot(volume_id, snapshot_id).await
     }
 
+    /// 列出存储卷上的快照
+    pub async fn list_snapshots(&self, pool_id: &str, volume_id: &str) -> Result<Vec<SnapshotInfo>> {
+        debug!("Listing snapshots: pool={}, volume={}", pool_id, volume_id);
+
+        let driver = self.get_driver(pool_id).await?;
+        driver.list_snapshots(volume_id).await
+    }
+
     /// 克隆存储卷
     pub async fn clone_volume(
         &self,
@@ -174,18 +251,113 @@ impl StorageManager {
         source_volume_id: &str,
         target_volume_id: &str,
         target_name: &str,
+        snapshot_id: Option<&str>,
     ) -> Result<VolumeInfo> {
         debug!(
-            "Cloning volume: pool={}, source={}, target={}, name={}",
-            pool_id, source_volume_id, target_volume_id, target_name
+            "Cloning volume: pool={}, source={}, target={}, name={}, snapshot={:?}",
+            pool_id, source_volume_id, target_volume_id, target_name, snapshot_id
         );
 
         let driver = self.get_driver(pool_id).await?;
         driver
-            .clone_volume(source_volume_id, target_volume_id, target_name)
+            .clone_volume(source_volume_id, target_volume_id, target_name, snapshot_id)
             .await
     }
 
+    /// 跨存储池克隆存储卷：由源存储池定位数据，目标存储池完整拷贝
+    pub async fn clone_volume_across_pools(
+        &self,
+        source_pool_id: &str,
+        target_pool_id: &str,
+        source_volume_id: &str,
+        target_volume_id: &str,
+        target_name: &str,
+        snapshot_id: Option<&str>,
+    ) -> Result<VolumeInfo> {
+        debug!(
+            "Cloning volume across pools: {}/{} -> {}/{}, name={}, snapshot={:?}",
+            source_pool_id, source_volume_id, target_pool_id, target_volume_id, target_name, snapshot_id
+        );
+
+        let source_driver = self.get_driver(source_pool_id).await?;
+        let target_driver = self.get_driver(target_pool_id).await?;
+
+        let source = source_driver.clone_source(source_volume_id, snapshot_id).await?;
+        target_driver
+            .clone_from_source(&source, target_volume_id, target_name)
+            .await
+    }
+
+    /// 把存储池中已存在的镜像文件登记为卷
+    pub async fn import_volume(
+        &self,
+        pool_id: &str,
+        path: &str,
+        volume_id: &str,
+        mode: VolumeImportMode,
+    ) -> Result<ImportedVolume> {
+        info!(
+            "Importing volume: pool={}, path={}, volume={}, mode={:?}",
+            pool_id, path, volume_id, mode
+        );
+
+        let driver = self.get_driver(pool_id).await?;
+        driver.import_volume(path, volume_id, mode).await
+    }
+
+    /// 列出存储池中的孤立文件
+    pub async fn list_orphaned_files(
+        &self,
+        pool_id: &str,
+        known_volume_ids: &HashSet<String>,
+    ) -> Result<Vec<OrphanedFile>> {
+        debug!("Listing orphaned files: pool={}, known={}", pool_id, known_volume_ids.len());
+
+        let driver = self.get_driver(pool_id).await?;
+        driver.list_orphaned_files(known_volume_ids).await
+    }
+
+    /// 删除存储池中的孤立文件
+    pub async fn delete_orphaned_files(
+        &self,
+        pool_id: &str,
+        known_volume_ids: &HashSet<String>,
+        file_names: &[String],
+    ) -> Result<Vec<String>> {
+        info!("Deleting orphaned files: pool={}, files={:?}", pool_id, file_names);
+
+        let driver = self.get_driver(pool_id).await?;
+        driver.delete_orphaned_files(known_volume_ids, file_names).await
+    }
+
+    /// 准备卷的导出副本
+    pub async fn prepare_export(&self, pool_id: &str, volume_id: &str) -> Result<ExportInfo> {
+        info!("Preparing volume export: pool={}, volume={}", pool_id, volume_id);
+
+        let driver = self.get_driver(pool_id).await?;
+        driver.prepare_export(volume_id).await
+    }
+
+    /// 读取卷导出副本的一段数据
+    pub async fn read_export(
+        &self,
+        pool_id: &str,
+        volume_id: &str,
+        offset: u64,
+        length: u64,
+    ) -> Result<Vec<u8>> {
+        let driver = self.get_driver(pool_id).await?;
+        driver.read_export(volume_id, offset, length).await
+    }
+
+    /// 查询存储池底层存储的实际容量
+    pub async fn pool_usage(&self, pool_id: &str) -> Result<PoolUsage> {
+        debug!("Querying pool usage: pool={}", pool_id);
+
+        let driver = self.get_driver(pool_id).await?;
+        driver.pool_usage().await
+    }
+
     /// 检查存储池是否已注册
     pub async fn is_pool_registered(&self, pool_id: &str) -> bool {
         let drivers = self.drivers.read().await;
@@ -198,3 +370,37 @@ impl StorageManager {
         drivers.keys().cloned().collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_source(path: &std::path::Path) -> VolumeSource {
+        VolumeSource {
+            url: format!("file://{}", path.display()),
+            checksum: None,
+            headers: HashMap::new(),
+            auth: None,
+        }
+    }
+
+    #[test]
+    fn test_check_local_source_limits_directories() {
+        let dir = std::env::temp_dir().join(format!("local-source-{}", uuid::Uuid::new_v4()));
+        let images = dir.join("images");
+        std::fs::create_dir_all(&images).unwrap();
+        std::fs::write(images.join("a.img"), b"x").unwrap();
+        std::fs::write(dir.join("secret"), b"x").unwrap();
+
+        let manager = StorageManager::new(1, vec![images.clone()], CommandPolicy::default());
+        assert!(manager.check_local_source(&file_source(&images.join("a.img"))).is_ok());
+        assert!(manager.check_local_source(&file_source(&dir.join("secret"))).is_err());
+        assert!(manager.check_local_source(&file_source(&images.join("../secret"))).is_err());
+        assert!(manager.check_local_source(&file_source(&images.join("missing.img"))).is_err());
+
+        let http = VolumeSource { url: "https://example.com/a.img".to_string(), ..file_source(&dir) };
+        assert!(manager.check_local_source(&http).is_ok());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -2,9 +2,15 @@
 /// 
 /// 支持多种存储后端：LVM、QCOW2、Ceph、NFS
 
+pub mod command;
+pub mod download;
 pub mod driver;
+pub mod encryption;
+pub mod lvm;
 pub mod manager;
 pub mod nfs;
+pub mod path_template;
 
+pub use command::CommandPolicy;
 pub use manager::StorageManager;
 
@@ -2,13 +2,33 @@
 ///
 /// 在 NFS 共享目录中创建和管理 qcow2/raw 格式的磁盘镜像
 use async_trait::async_trait;
+use common::utils::redact_url;
+use common::ws_rpc::{BackingStore, VolumeImportMode};
 use common::{Error, Result};
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
 use tokio::process::Command;
 use tracing::{debug, error, info, warn};
 
-use super::driver::{StorageDriver, StoragePoolConfig, VolumeInfo};
+use super::command::CommandPolicy;
+use super::download::{self, DownloadLimiter};
+use super::encryption::{Passphrase, SecretFile, QEMU_SECRET_ID};
+use super::driver::{
+    CloneSource, ExportInfo, ImportedVolume, OrphanedFile, PoolUsage, ProgressFn, SnapshotInfo, StorageDriver,
+    StoragePoolConfig, StorageProgress, VolumeInfo, VolumeSource,
+};
+use super::path_template::{PathTemplate, DEFAULT_PATH_TEMPLATE};
+
+/// qcow2 压缩算法
+const QCOW2_COMPRESSION_TYPE: &str = "zstd";
+
+/// qcow2 compression_type 选项所需的最低 qemu-img 版本
+const MIN_ZSTD_QEMU_VERSION: (u32, u32) = (5, 1);
+
+/// 导出副本所在子目录，不参与孤立文件扫描
+const EXPORT_DIR: &str = ".exports";
 
 /// NFS 存储驱动
 pub struct NfsDriver {
@@ -16,11 +36,21 @@ pub struct NfsDriver {
     pool_config: StoragePoolConfig,
     /// NFS 挂载点路径
     mount_path: PathBuf,
+    /// 卷文件的目录布局
+    path_template: PathTemplate,
+    /// 节点级 URL 下载并发限制
+    download_limiter: Arc<DownloadLimiter>,
+    /// qemu-img 调用的超时与重试策略
+    commands: CommandPolicy,
 }
 
 impl NfsDriver {
     /// 创建新的 NFS 驱动实例
-    pub fn new(pool_config: StoragePoolConfig) -> Result<Self> {
+    pub fn new(
+        pool_config: StoragePoolConfig,
+        download_limiter: Arc<DownloadLimiter>,
+        commands: CommandPolicy,
+    ) -> Result<Self> {
         // 从配置中获取 NFS 挂载路径
         let mount_path = pool_config
             .config
@@ -29,15 +59,116 @@ impl NfsDriver {
 
         let mount_path = PathBuf::from(mount_path);
 
+        // 可选的路径模板，未配置时卷文件平铺在挂载点下
+        let path_template = PathTemplate::parse(
+            pool_config
+                .config
+                .get("path_template")
+                .map(String::as_str)
+                .unwrap_or(DEFAULT_PATH_TEMPLATE),
+        )?;
+
         Ok(Self {
             pool_config,
             mount_path,
+            path_template,
+            download_limiter,
+            commands,
         })
     }
 
+    /// 卷文件及其 raw 快照所在目录
+    fn volume_dir(&self, volume_id: &str) -> PathBuf {
+        self.path_template.volume_dir(&self.mount_path, volume_id)
+    }
+
     /// 获取卷的完整路径
     fn get_volume_path(&self, volume_id: &str, format: &str) -> PathBuf {
-        self.mount_path.join(format!("{}.{}", volume_id, format))
+        self.volume_dir(volume_id).join(format!("{}.{}", volume_id, format))
+    }
+
+    /// 查找作为 backing file 的基础镜像卷文件
+    fn find_backing_file(&self, volume_id: &str) -> Result<BackingStore> {
+        ["qcow2", "raw"]
+            .into_iter()
+            .map(|format| (format, self.get_volume_path(volume_id, format)))
+            .find(|(_, path)| path.exists())
+            .map(|(format, path)| BackingStore {
+                path: path.to_string_lossy().to_string(),
+                format: format.to_string(),
+            })
+            .ok_or_else(|| Error::NotFound(format!("Backing volume {} not found", volume_id)))
+    }
+
+    /// 确保卷文件所在目录存在
+    async fn ensure_volume_dir(&self, volume_path: &Path) -> Result<()> {
+        if let Some(parent) = volume_path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|e| Error::Storage(format!("Failed to create directory: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// 按路径模板的目录层数遍历存储池，返回卷目录中的所有文件
+    ///
+    /// 跳过以 `.` 开头的目录（如导出目录），不会进入模板之外的子目录
+    async fn walk_volume_files(root: &Path, depth: usize) -> Result<Vec<PathBuf>> {
+        let mut dirs = vec![root.to_path_buf()];
+        for level in 0..=depth {
+            let mut next = Vec::new();
+            for dir in dirs {
+                let mut entries = fs::read_dir(&dir)
+                    .await
+                    .map_err(|e| Error::Storage(format!("Failed to read directory: {}", e)))?;
+
+                while let Some(entry) = entries
+                    .next_entry()
+                    .await
+                    .map_err(|e| Error::Storage(format!("Failed to read directory entry: {}", e)))?
+                {
+                    let path = entry.path();
+                    if level == depth {
+                        if path.is_file() {
+                            next.push(path);
+                        }
+                    } else if path.is_dir()
+                        && !entry.file_name().to_string_lossy().starts_with('.')
+                    {
+                        next.push(path);
+                    }
+                }
+            }
+            dirs = next;
+        }
+        Ok(dirs)
+    }
+
+    /// 获取卷导出副本的路径
+    fn get_export_path(&self, volume_id: &str, format: &str) -> PathBuf {
+        self.mount_path
+            .join(EXPORT_DIR)
+            .join(format!("{}.{}", volume_id, format))
+    }
+
+    /// 查找卷文件及其格式
+    fn find_volume_file(&self, volume_id: &str) -> Option<(PathBuf, &'static str)> {
+        ["qcow2", "raw"]
+            .into_iter()
+            .map(|fmt| (self.get_volume_path(volume_id, fmt), fmt))
+            .find(|(path, _)| path.exists())
+    }
+
+    /// 导出副本是否存在且不旧于源卷文件
+    async fn is_export_fresh(source: &Path, export: &Path) -> bool {
+        let modified = |m: std::fs::Metadata| m.modified().ok();
+        match (
+            fs::metadata(source).await.ok().and_then(modified),
+            fs::metadata(export).await.ok().and_then(modified),
+        ) {
+            (Some(source_mtime), Some(export_mtime)) => export_mtime >= source_mtime,
+            _ => false,
+        }
     }
 
     /// 解析卷文件格式
@@ -51,12 +182,14 @@ impl NfsDriver {
     /// 检测文件的实际格式
     async fn detect_file_format(&self, file_path: &Path) -> Result<String> {
         // 使用 qemu-img info 命令检测文件格式
-        let output = Command::new("qemu-img")
-            .arg("info")
-            .arg(file_path)
-            .output()
-            .await
-            .map_err(|e| Error::Storage(format!("Failed to run qemu-img info: {}", e)))?;
+        let output = self
+            .commands
+            .output(
+                Command::new("qemu-img")
+                    .arg("info")
+                    .arg(file_path),
+            )
+            .await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -108,13 +241,15 @@ impl NfsDriver {
 
     /// 获取 qcow2 虚拟大小
     async fn get_qcow2_virtual_size(&self, path: &Path) -> Result<u64> {
-        let output = Command::new("qemu-img")
-            .arg("info")
-            .arg("--output=json")
-            .arg(path)
-            .output()
-            .await
-            .map_err(|e| Error::Storage(format!("Failed to run qemu-img info: {}", e)))?;
+        let output = self
+            .commands
+            .output(
+                Command::new("qemu-img")
+                    .arg("info")
+                    .arg("--output=json")
+                    .arg(path),
+            )
+            .await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -131,6 +266,288 @@ impl NfsDriver {
         Ok(virtual_size / (1024 * 1024 * 1024))
     }
 
+    /// 读取镜像的格式与虚拟大小（字节），文件可能正被其他进程打开，使用 `-U` 读取
+    async fn inspect_image(&self, path: &Path) -> Result<(String, u64)> {
+        let output = self
+            .commands
+            .output(
+                Command::new("qemu-img")
+                    .args(["info", "--output=json", "-U"])
+                    .arg(path),
+            )
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::Storage(format!("qemu-img info failed: {}", stderr.trim())));
+        }
+
+        let info: serde_json::Value = serde_json::from_slice(&output.stdout)
+            .map_err(|e| Error::Storage(format!("Failed to parse qemu-img output: {}", e)))?;
+        let format = info["format"]
+            .as_str()
+            .ok_or_else(|| Error::Storage("format not found in qemu-img output".to_string()))?;
+        let virtual_size = info["virtual-size"].as_u64().ok_or_else(|| {
+            Error::Storage("virtual-size not found in qemu-img output".to_string())
+        })?;
+        Ok((format.to_string(), virtual_size))
+    }
+
+    /// 待导入的镜像必须是存储池目录内（导出目录除外）的普通文件，返回解析符号链接后的路径
+    fn resolve_import_path(&self, path: &str) -> Result<PathBuf> {
+        let path = Path::new(path);
+        if !path.is_absolute() {
+            return Err(Error::InvalidArgument(format!("Import path must be absolute: {}", path.display())));
+        }
+        let resolved = path
+            .canonicalize()
+            .map_err(|e| Error::NotFound(format!("Image {} not found: {}", path.display(), e)))?;
+        let mount_path = self
+            .mount_path
+            .canonicalize()
+            .map_err(|e| Error::Storage(format!("Failed to resolve mount path: {}", e)))?;
+        if !resolved.starts_with(&mount_path) || resolved.starts_with(mount_path.join(EXPORT_DIR)) {
+            return Err(Error::InvalidArgument(format!(
+                "Image {} is outside storage pool {}",
+                path.display(),
+                self.mount_path.display()
+            )));
+        }
+        if !resolved.is_file() {
+            return Err(Error::InvalidArgument(format!("Image {} is not a regular file", path.display())));
+        }
+        // 已登记卷的文件（含链接克隆的 backing file）不能再次导入
+        let owner = resolved
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| self.find_volume_file(stem))
+            .filter(|(file, _)| file.canonicalize().is_ok_and(|file| file == resolved));
+        if let Some((file, _)) = owner {
+            return Err(Error::InvalidArgument(format!(
+                "Image {} is the volume file {}",
+                path.display(),
+                file.display()
+            )));
+        }
+        Ok(resolved)
+    }
+
+    /// 对挂载点执行 statvfs；可用空间取非特权用户可用的 f_bavail
+    fn statvfs(path: &Path) -> Result<PoolUsage> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| Error::InvalidArgument(format!("Invalid mount path: {}", path.display())))?;
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        // SAFETY: c_path 是以 NUL 结尾的合法字符串，stat 为可写的 statvfs 结构体
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+            return Err(Error::Storage(format!(
+                "statvfs {} failed: {}",
+                path.display(),
+                std::io::Error::last_os_error()
+            )));
+        }
+
+        let fragment_size = stat.f_frsize as u64;
+        let total_bytes = stat.f_blocks as u64 * fragment_size;
+        let free_bytes = stat.f_bfree as u64 * fragment_size;
+        Ok(PoolUsage {
+            total_bytes,
+            used_bytes: total_bytes.saturating_sub(free_bytes),
+            available_bytes: stat.f_bavail as u64 * fragment_size,
+        })
+    }
+
+    /// 从 `qemu-img info --output=json` 的结果中解析内部快照列表
+    fn parse_qcow2_snapshots(info: &serde_json::Value) -> Vec<SnapshotInfo> {
+        info["snapshots"]
+            .as_array()
+            .map(|snapshots| {
+                snapshots
+                    .iter()
+                    .filter_map(|snap| {
+                        Some(SnapshotInfo {
+                            name: snap["name"].as_str()?.to_string(),
+                            vm_state_size: snap["vm-state-size"].as_u64().unwrap_or(0),
+                            created_at: snap["date-sec"].as_i64(),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 文件是否属于已知卷
+    ///
+    /// 卷文件为 `{volume_id}.{format}`，下载中的临时文件为 `{volume_id}.tmp`，
+    /// raw 快照为 `{volume_id}-{snapshot_id}.raw`
+    fn is_known_file(stem: &str, known_volume_ids: &HashSet<String>) -> bool {
+        known_volume_ids.contains(stem)
+            || known_volume_ids
+                .iter()
+                .any(|id| stem.strip_prefix(id.as_str()).map_or(false, |rest| rest.starts_with('-')))
+    }
+
+    /// 读取 qcow2 文件的 backing file 文件名
+    async fn backing_file_name(&self, path: &Path) -> Option<String> {
+        let output = self
+            .commands
+            .output(Command::new("qemu-img").args(["info", "--output=json", "-U"]).arg(path))
+            .await
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        let info: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+        let backing = info["backing-filename"].as_str()?;
+        Path::new(backing)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.to_string())
+    }
+
+    /// 扫描存储池目录，找出不属于任何已知卷且未被引用为 backing file 的文件
+    async fn scan_orphaned_files(&self, known_volume_ids: &HashSet<String>) -> Result<Vec<OrphanedFile>> {
+        let mut candidates = Vec::new();
+        let mut referenced = HashSet::new();
+
+        for path in Self::walk_volume_files(&self.mount_path, self.path_template.depth()).await? {
+            let format = Self::parse_volume_format(&path);
+            if format != "qcow2" && format != "raw" && format != "tmp" {
+                continue;
+            }
+
+            // 所有 qcow2 文件（包括孤立文件）的 backing file 都不能删除
+            if format == "qcow2" {
+                if let Some(backing) = self.backing_file_name(&path).await {
+                    referenced.insert(backing);
+                }
+            }
+            // 引用方式导入的卷是指向原镜像的符号链接，原镜像同样不能删除
+            if let Ok(target) = fs::read_link(&path).await {
+                if let Some(name) = target.file_name().and_then(|n| n.to_str()) {
+                    referenced.insert(name.to_string());
+                }
+            }
+
+            let (stem, file_name) = match (
+                Self::extract_volume_id(&path),
+                path.file_name().and_then(|n| n.to_str()),
+            ) {
+                (Some(stem), Some(file_name)) => (stem, file_name.to_string()),
+                _ => continue,
+            };
+
+            if Self::is_known_file(&stem, known_volume_ids) {
+                continue;
+            }
+
+            let metadata = fs::metadata(&path).await.ok();
+            candidates.push(OrphanedFile {
+                file_name,
+                path: path.to_string_lossy().to_string(),
+                size_bytes: metadata.as_ref().map(|m| m.len()).unwrap_or(0),
+                modified_at: metadata
+                    .and_then(|m| m.modified().ok())
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64),
+            });
+        }
+
+        candidates.retain(|file| !referenced.contains(&file.file_name));
+        Ok(candidates)
+    }
+
+    /// 执行 qemu-img convert，带 `-p` 时从标准输出逐行解析进度并回调
+    async fn run_convert(
+        &self,
+        cmd: &mut Command,
+        progress: Option<&ProgressFn>,
+    ) -> Result<std::process::Output> {
+        let Some(progress) = progress else {
+            return self.commands.convert(cmd, None).await;
+        };
+
+        // 进度行以 \r 结尾，逐段刷新同一行
+        let on_line = |line: &str| {
+            if let Some(percent) = Self::parse_convert_progress(line) {
+                progress(StorageProgress::Convert { percent });
+            }
+        };
+        self.commands.convert(cmd, Some(&on_line)).await
+    }
+
+    /// 解析 `qemu-img convert -p` 输出的进度行，如 `    (42.00/100%)`
+    fn parse_convert_progress(line: &str) -> Option<f64> {
+        let percent = line
+            .trim()
+            .strip_prefix('(')?
+            .split_once('/')?
+            .0
+            .parse::<f64>()
+            .ok()?;
+        Some(percent.clamp(0.0, 100.0))
+    }
+
+    /// 从 `qemu-img --version` 输出中解析主/次版本号
+    fn parse_qemu_img_version(output: &str) -> Option<(u32, u32)> {
+        let version = output
+            .lines()
+            .next()?
+            .split_whitespace()
+            .skip_while(|w| *w != "version")
+            .nth(1)?;
+
+        let mut parts = version.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.trim_end_matches(|c: char| !c.is_ascii_digit()).parse().ok()?;
+        Some((major, minor))
+    }
+
+    /// 检查本机 qemu-img 是否支持 zstd 压缩
+    async fn ensure_compression_supported(&self) -> Result<()> {
+        let output = self
+            .commands
+            .output(Command::new("qemu-img").arg("--version"))
+            .await?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let version = Self::parse_qemu_img_version(&stdout).ok_or_else(|| {
+            Error::Storage(format!("Failed to parse qemu-img version: {}", stdout.trim()))
+        })?;
+
+        if version < MIN_ZSTD_QEMU_VERSION {
+            return Err(Error::Storage(format!(
+                "qemu-img {}.{} does not support qcow2 compression_type={} (requires >= {}.{})",
+                version.0,
+                version.1,
+                QCOW2_COMPRESSION_TYPE,
+                MIN_ZSTD_QEMU_VERSION.0,
+                MIN_ZSTD_QEMU_VERSION.1
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// 校验压缩参数：仅 qcow2 支持，且需要 qemu-img 支持 zstd
+    async fn validate_compress(&self, format: &str, compress: bool) -> Result<()> {
+        if !compress {
+            return Ok(());
+        }
+
+        if format != "qcow2" {
+            return Err(Error::InvalidArgument(format!(
+                "Compression is only supported for qcow2 volumes, got {}",
+                format
+            )));
+        }
+
+        self.ensure_compression_supported().await
+    }
+
     /// 创建空白存储卷（内部方法）
     async fn create_blank_volume(
         &self,
@@ -138,21 +555,38 @@ impl NfsDriver {
         name: &str,
         size_gb: u64,
         format: &str,
+        compress: bool,
+        backing: Option<&BackingStore>,
+        encryption: Option<&Passphrase>,
         volume_path: &std::path::Path,
     ) -> Result<VolumeInfo> {
         // 根据格式创建磁盘镜像
         match format {
             "qcow2" => {
                 // 使用 qemu-img 创建 qcow2 镜像
-                let output = Command::new("qemu-img")
-                    .arg("create")
-                    .arg("-f")
-                    .arg("qcow2")
-                    .arg(volume_path)
-                    .arg(format!("{}G", size_gb))
-                    .output()
-                    .await
-                    .map_err(|e| Error::Storage(format!("Failed to run qemu-img: {}", e)))?;
+                let mut cmd = Command::new("qemu-img");
+                cmd.arg("create").arg("-f").arg("qcow2");
+                if let Some(backing) = backing {
+                    // 只写入差异数据，未写入的簇从基础镜像读取
+                    cmd.arg("-b").arg(&backing.path).arg("-F").arg(&backing.format);
+                }
+                // 口令文件在 qemu-img 退出后随 secret_file 一起删除
+                let secret_file = encryption.map(SecretFile::create).transpose()?;
+                if let Some(secret_file) = &secret_file {
+                    cmd.arg("--object").arg(secret_file.object_arg()).arg("-o").arg(format!(
+                        "encrypt.format=luks,encrypt.key-secret={}",
+                        QEMU_SECRET_ID
+                    ));
+                }
+                if compress {
+                    // 仅设置压缩算法，之后以压缩方式写入的数据才会被压缩
+                    cmd.arg("-o")
+                        .arg(format!("compression_type={}", QCOW2_COMPRESSION_TYPE));
+                }
+                let output = self
+                    .commands
+                    .output(cmd.arg(volume_path).arg(format!("{}G", size_gb)))
+                    .await?;
 
                 if !output.status.success() {
                     let stderr = String::from_utf8_lossy(&output.stderr);
@@ -165,15 +599,17 @@ impl NfsDriver {
             }
             "raw" => {
                 // 使用 qemu-img 创建 raw 镜像
-                let output = Command::new("qemu-img")
-                    .arg("create")
-                    .arg("-f")
-                    .arg("raw")
-                    .arg(volume_path)
-                    .arg(format!("{}G", size_gb))
-                    .output()
-                    .await
-                    .map_err(|e| Error::Storage(format!("Failed to run qemu-img: {}", e)))?;
+                let output = self
+                    .commands
+                    .output(
+                        Command::new("qemu-img")
+                            .arg("create")
+                            .arg("-f")
+                            .arg("raw")
+                            .arg(volume_path)
+                            .arg(format!("{}G", size_gb)),
+                    )
+                    .await?;
 
                 if !output.status.success() {
                     let stderr = String::from_utf8_lossy(&output.stderr);
@@ -218,35 +654,24 @@ impl NfsDriver {
         name: &str,
         size_gb: u64,
         format: &str,
-        source_url: &str,
+        source: &VolumeSource,
+        compress: bool,
         volume_path: &std::path::Path,
+        progress: Option<ProgressFn>,
     ) -> Result<VolumeInfo> {
         info!(
-            "Creating NFS volume from URL: id={}, name={}, size={}GB, format={}, url={}",
-            volume_id, name, size_gb, format, source_url
+            "Creating NFS volume from URL: id={}, name={}, size={}GB, format={}, source={:?}",
+            volume_id, name, size_gb, format, source
         );
 
+        // 下载与格式转换都占用网络和磁盘带宽，整个过程持有并发许可
+        let _permit = self.download_limiter.acquire(volume_id).await;
+
         // 下载外部URL的内容到临时文件
         let temp_path = volume_path.with_extension("tmp");
 
-        // 使用curl下载文件
-        let output = Command::new("curl")
-            .arg("-L") // 跟随重定向
-            .arg("-o")
-            .arg(&temp_path)
-            .arg(source_url)
-            .output()
-            .await
-            .map_err(|e| Error::Storage(format!("Failed to download from URL: {}", e)))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            error!("curl download failed: {}", stderr);
-            return Err(Error::Storage(format!(
-                "Failed to download from URL: {}",
-                stderr
-            )));
-        }
+        // 下载过程中校验完整性，校验失败的文件不会进入格式转换
+        download::download(source, &temp_path, progress.as_ref()).await?;
 
         // 检测下载文件的格式
         let detected_format = self.detect_file_format(&temp_path).await?;
@@ -255,28 +680,33 @@ impl NfsDriver {
         // 根据目标格式转换下载的文件
         match format {
             "qcow2" => {
-                // 如果检测到的格式已经是qcow2，直接重命名
-                if detected_format == "qcow2" {
+                // 如果检测到的格式已经是qcow2且无需压缩，直接重命名
+                if detected_format == "qcow2" && !compress {
                     fs::rename(&temp_path, volume_path).await.map_err(|e| {
                         Error::Storage(format!("Failed to rename qcow2 file: {}", e))
                     })?;
                 } else {
                     // 使用 qemu-img 转换到 qcow2 格式
-                    let output = Command::new("qemu-img")
-                        .arg("convert")
-                        .arg("-f")
+                    let mut cmd = Command::new("qemu-img");
+                    cmd.arg("convert");
+                    if progress.is_some() {
+                        cmd.arg("-p");
+                    }
+                    cmd.arg("-f")
                         .arg(&detected_format)
                         .arg("-O")
-                        .arg("qcow2")
-                        .arg("-o")
-                        .arg("preallocation=metadata")
-                        .arg(&temp_path)
-                        .arg(volume_path)
-                        .output()
-                        .await
-                        .map_err(|e| {
-                            Error::Storage(format!("Failed to run qemu-img convert: {}", e))
-                        })?;
+                        .arg("qcow2");
+                    if compress {
+                        // 压缩与预分配不能同时使用
+                        cmd.arg("-c").arg("-o").arg(format!(
+                            "compression_type={}",
+                            QCOW2_COMPRESSION_TYPE
+                        ));
+                    } else {
+                        cmd.arg("-o").arg("preallocation=metadata");
+                    }
+                    cmd.arg(&temp_path).arg(volume_path);
+                    let output = self.run_convert(&mut cmd, progress.as_ref()).await?;
 
                     if !output.status.success() {
                         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -296,19 +726,18 @@ impl NfsDriver {
                     })?;
                 } else {
                     // 转换到 raw 格式
-                    let output = Command::new("qemu-img")
-                        .arg("convert")
-                        .arg("-f")
+                    let mut cmd = Command::new("qemu-img");
+                    cmd.arg("convert");
+                    if progress.is_some() {
+                        cmd.arg("-p");
+                    }
+                    cmd.arg("-f")
                         .arg(&detected_format)
                         .arg("-O")
                         .arg("raw")
                         .arg(&temp_path)
-                        .arg(volume_path)
-                        .output()
-                        .await
-                        .map_err(|e| {
-                            Error::Storage(format!("Failed to run qemu-img convert: {}", e))
-                        })?;
+                        .arg(volume_path);
+                    let output = self.run_convert(&mut cmd, progress.as_ref()).await?;
 
                     if !output.status.success() {
                         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -329,13 +758,15 @@ impl NfsDriver {
         }
 
         // 统一调整到指定大小
-        let output = Command::new("qemu-img")
-            .arg("resize")
-            .arg(volume_path)
-            .arg(format!("{}G", size_gb))
-            .output()
-            .await
-            .map_err(|e| Error::Storage(format!("Failed to run qemu-img resize: {}", e)))?;
+        let output = self
+            .commands
+            .output(
+                Command::new("qemu-img")
+                    .arg("resize")
+                    .arg(volume_path)
+                    .arg(format!("{}G", size_gb)),
+            )
+            .await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -379,13 +810,51 @@ impl StorageDriver for NfsDriver {
         name: &str,
         size_gb: u64,
         format: &str,
-        source: Option<&str>, // 外部URL，可选
+        source: Option<&VolumeSource>, // 外部URL，可选
+        compress: bool,
+        backing_volume_id: Option<&str>,
+        encryption: Option<&Passphrase>,
+        progress: Option<ProgressFn>,
     ) -> Result<VolumeInfo> {
         info!(
-            "Creating NFS volume: id={}, name={}, size={}GB, format={}, source={:?}",
-            volume_id, name, size_gb, format, source
+            "Creating NFS volume: id={}, name={}, size={}GB, format={}, source={:?}, compress={}, backing={:?}, encrypted={}",
+            volume_id,
+            name,
+            size_gb,
+            format,
+            source.map(|s| redact_url(&s.url)),
+            compress,
+            backing_volume_id,
+            encryption.is_some()
         );
 
+        self.validate_compress(format, compress).await?;
+        if encryption.is_some() {
+            // 压缩簇与加密不能同时使用，下载转换和 backing 链也需要额外传递口令，暂只支持空白卷
+            if format != "qcow2" || compress || source.is_some() || backing_volume_id.is_some() {
+                return Err(Error::InvalidArgument(
+                    "Encryption is only supported for blank, uncompressed qcow2 volumes".to_string(),
+                ));
+            }
+        }
+        let backing = match backing_volume_id {
+            Some(backing_volume_id) => {
+                if source.is_some() {
+                    return Err(Error::InvalidArgument(
+                        "A volume cannot have both a source URL and a backing volume".to_string(),
+                    ));
+                }
+                if format != "qcow2" {
+                    return Err(Error::InvalidArgument(format!(
+                        "Linked clones require qcow2 format, got {}",
+                        format
+                    )));
+                }
+                Some(self.find_backing_file(backing_volume_id)?)
+            }
+            None => None,
+        };
+
         let volume_path = self.get_volume_path(volume_id, format);
 
         // 检查文件是否已存在
@@ -397,28 +866,35 @@ impl StorageDriver for NfsDriver {
         }
 
         // 确保目录存在
-        if let Some(parent) = volume_path.parent() {
-            fs::create_dir_all(parent)
-                .await
-                .map_err(|e| Error::Storage(format!("Failed to create directory: {}", e)))?;
-        }
+        self.ensure_volume_dir(&volume_path).await?;
 
         // 根据是否有source URL选择不同的创建方式
-        if let Some(source_url) = source {
+        if let Some(source) = source {
             // 从外部URL创建存储卷
             self.create_volume_from_url_internal(
                 volume_id,
                 name,
                 size_gb,
                 format,
-                source_url,
+                source,
+                compress,
                 &volume_path,
+                progress,
             )
             .await
         } else {
-            // 创建空白存储卷
-            self.create_blank_volume(volume_id, name, size_gb, format, &volume_path)
-                .await
+            // 创建空白存储卷，指定基础镜像时为链接克隆
+            self.create_blank_volume(
+                volume_id,
+                name,
+                size_gb,
+                format,
+                compress,
+                backing.as_ref(),
+                encryption,
+                &volume_path,
+            )
+            .await
         }
     }
 
@@ -450,6 +926,22 @@ impl StorageDriver for NfsDriver {
             return Err(Error::NotFound(format!("Volume {} not found", volume_id)));
         }
 
+        // 顺带清理导出副本
+        for format in ["qcow2", "raw"] {
+            let _ = fs::remove_file(self.get_export_path(volume_id, format)).await;
+        }
+
+        // 清理变空的分片目录，非空时 remove_dir 会失败，直接忽略
+        let mut dir = self.volume_dir(volume_id);
+        while dir != self.mount_path {
+            if fs::remove_dir(&dir).await.is_err() {
+                break;
+            }
+            if !dir.pop() {
+                break;
+            }
+        }
+
         Ok(())
     }
 
@@ -474,13 +966,15 @@ impl StorageDriver for NfsDriver {
             .ok_or_else(|| Error::NotFound(format!("Volume {} not found", volume_id)))?;
 
         // 使用 qemu-img resize 调整大小
-        let output = Command::new("qemu-img")
-            .arg("resize")
-            .arg(&volume_path)
-            .arg(format!("{}G", new_size_gb))
-            .output()
-            .await
-            .map_err(|e| Error::Storage(format!("Failed to run qemu-img resize: {}", e)))?;
+        let output = self
+            .commands
+            .output(
+                Command::new("qemu-img")
+                    .arg("resize")
+                    .arg(&volume_path)
+                    .arg(format!("{}G", new_size_gb)),
+            )
+            .await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -555,23 +1049,8 @@ impl StorageDriver for NfsDriver {
 
         let mut volumes = Vec::new();
 
-        // 读取目录中的所有卷文件
-        let mut entries = fs::read_dir(&self.mount_path)
-            .await
-            .map_err(|e| Error::Storage(format!("Failed to read directory: {}", e)))?;
-
-        while let Some(entry) = entries
-            .next_entry()
-            .await
-            .map_err(|e| Error::Storage(format!("Failed to read directory entry: {}", e)))?
-        {
-            let path = entry.path();
-
-            // 只处理文件
-            if !path.is_file() {
-                continue;
-            }
-
+        // 按路径模板读取各级目录中的所有卷文件
+        for path in Self::walk_volume_files(&self.mount_path, self.path_template.depth()).await? {
             let format = Self::parse_volume_format(&path);
 
             // 只处理 qcow2 和 raw 格式
@@ -624,14 +1103,16 @@ impl StorageDriver for NfsDriver {
 
         // qcow2 支持内部快照
         if format == "qcow2" {
-            let output = Command::new("qemu-img")
-                .arg("snapshot")
-                .arg("-c")
-                .arg(snapshot_id)
-                .arg(&volume_path)
-                .output()
-                .await
-                .map_err(|e| Error::Storage(format!("Failed to run qemu-img snapshot: {}", e)))?;
+            let output = self
+                .commands
+                .output(
+                    Command::new("qemu-img")
+                        .arg("snapshot")
+                        .arg("-c")
+                        .arg(snapshot_id)
+                        .arg(&volume_path),
+                )
+                .await?;
 
             if !output.status.success() {
                 let stderr = String::from_utf8_lossy(&output.stderr);
@@ -650,7 +1131,7 @@ impl StorageDriver for NfsDriver {
         } else {
             // raw 格式使用拷贝创建快照
             let snapshot_path = self
-                .mount_path
+                .volume_dir(volume_id)
                 .join(format!("{}-{}.{}", volume_id, snapshot_id, format));
 
             fs::copy(&volume_path, &snapshot_path)
@@ -684,14 +1165,16 @@ impl StorageDriver for NfsDriver {
 
         // qcow2 支持内部快照
         if format == "qcow2" {
-            let output = Command::new("qemu-img")
-                .arg("snapshot")
-                .arg("-d")
-                .arg(snapshot_id)
-                .arg(&volume_path)
-                .output()
-                .await
-                .map_err(|e| Error::Storage(format!("Failed to run qemu-img snapshot: {}", e)))?;
+            let output = self
+                .commands
+                .output(
+                    Command::new("qemu-img")
+                        .arg("snapshot")
+                        .arg("-d")
+                        .arg(snapshot_id)
+                        .arg(&volume_path),
+                )
+                .await?;
 
             if !output.status.success() {
                 let stderr = String::from_utf8_lossy(&output.stderr);
@@ -710,7 +1193,7 @@ impl StorageDriver for NfsDriver {
         } else {
             // raw 格式删除快照文件
             let snapshot_path = self
-                .mount_path
+                .volume_dir(volume_id)
                 .join(format!("{}-{}.{}", volume_id, snapshot_id, format));
 
             if snapshot_path.exists() {
@@ -753,14 +1236,16 @@ impl StorageDriver for NfsDriver {
 
         // qcow2 支持内部快照恢复
         if format == "qcow2" {
-            let output = Command::new("qemu-img")
-                .arg("snapshot")
-                .arg("-a")
-                .arg(snapshot_id)
-                .arg(&volume_path)
-                .output()
-                .await
-                .map_err(|e| Error::Storage(format!("Failed to run qemu-img snapshot: {}", e)))?;
+            let output = self
+                .commands
+                .output(
+                    Command::new("qemu-img")
+                        .arg("snapshot")
+                        .arg("-a")
+                        .arg(snapshot_id)
+                        .arg(&volume_path),
+                )
+                .await?;
 
             if !output.status.success() {
                 let stderr = String::from_utf8_lossy(&output.stderr);
@@ -779,7 +1264,7 @@ impl StorageDriver for NfsDriver {
         } else {
             // raw 格式从快照文件恢复
             let snapshot_path = self
-                .mount_path
+                .volume_dir(volume_id)
                 .join(format!("{}-{}.{}", volume_id, snapshot_id, format));
 
             if !snapshot_path.exists() {
@@ -790,7 +1275,9 @@ impl StorageDriver for NfsDriver {
             }
 
             // 备份当前卷
-            let backup_path = self.mount_path.join(format!("{}.backup", volume_id));
+            let backup_path = self
+                .volume_dir(volume_id)
+                .join(format!("{}.backup", volume_id));
             fs::copy(&volume_path, &backup_path)
                 .await
                 .map_err(|e| Error::Storage(format!("Failed to backup current volume: {}", e)))?;
@@ -817,15 +1304,96 @@ impl StorageDriver for NfsDriver {
         }
     }
 
+    async fn list_snapshots(&self, volume_id: &str) -> Result<Vec<SnapshotInfo>> {
+        debug!("Listing snapshots for volume {}", volume_id);
+
+        let qcow2_path = self.get_volume_path(volume_id, "qcow2");
+        if qcow2_path.exists() {
+            // qemu-img snapshot -l 不支持 JSON 输出，改用 info 中的 snapshots 字段；
+            // -U 允许在虚拟机运行（镜像被锁定）时读取
+            let output = self
+                .commands
+                .output(
+                    Command::new("qemu-img")
+                        .arg("info")
+                        .arg("--output=json")
+                        .arg("-U")
+                        .arg(&qcow2_path),
+                )
+                .await?;
+
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                error!("qemu-img info failed: {}", stderr);
+                return Err(Error::Storage(format!(
+                    "Failed to list snapshots: {}",
+                    stderr
+                )));
+            }
+
+            let info: serde_json::Value = serde_json::from_slice(&output.stdout)
+                .map_err(|e| Error::Storage(format!("Failed to parse qemu-img output: {}", e)))?;
+
+            return Ok(Self::parse_qcow2_snapshots(&info));
+        }
+
+        let raw_path = self.get_volume_path(volume_id, "raw");
+        if !raw_path.exists() {
+            return Err(Error::NotFound(format!("Volume {} not found", volume_id)));
+        }
+
+        // raw 格式的快照是 {volume_id}-{snapshot_id}.raw 形式的完整拷贝
+        let prefix = format!("{}-", volume_id);
+        let mut snapshots = Vec::new();
+        let mut entries = fs::read_dir(self.volume_dir(volume_id))
+            .await
+            .map_err(|e| Error::Storage(format!("Failed to read directory: {}", e)))?;
+
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| Error::Storage(format!("Failed to read directory entry: {}", e)))?
+        {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("raw") {
+                continue;
+            }
+
+            let snapshot_id = match Self::extract_volume_id(&path)
+                .and_then(|stem| stem.strip_prefix(&prefix).map(|s| s.to_string()))
+            {
+                Some(id) => id,
+                None => continue,
+            };
+
+            let created_at = entry
+                .metadata()
+                .await
+                .ok()
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64);
+
+            snapshots.push(SnapshotInfo {
+                name: snapshot_id,
+                vm_state_size: 0,
+                created_at,
+            });
+        }
+
+        Ok(snapshots)
+    }
+
     async fn clone_volume(
         &self,
         source_volume_id: &str,
         target_volume_id: &str,
         target_name: &str,
+        snapshot_id: Option<&str>,
     ) -> Result<VolumeInfo> {
         info!(
-            "Cloning volume {} to {} with name {}",
-            source_volume_id, target_volume_id, target_name
+            "Cloning volume {} to {} with name {} (snapshot: {:?})",
+            source_volume_id, target_volume_id, target_name, snapshot_id
         );
 
         // 尝试找到源卷文件
@@ -855,26 +1423,25 @@ impl StorageDriver for NfsDriver {
                 target_volume_id
             )));
         }
+        self.ensure_volume_dir(&target_path).await?;
 
         // 根据格式选择克隆策略 - 使用完整数据拷贝确保独立性
         match format {
             "qcow2" => {
-                // 使用 qemu-img convert 进行完整数据拷贝，确保克隆卷完全独立
-                let output = Command::new("qemu-img")
-                    .arg("convert")
-                    .arg("-f")
-                    .arg("qcow2")
-                    .arg("-O")
+                // 使用 qemu-img convert 进行完整数据拷贝，确保克隆卷完全独立；
+                // 指定快照时通过 -l 读取内部快照的数据
+                let mut cmd = Command::new("qemu-img");
+                cmd.arg("convert").arg("-f").arg("qcow2");
+                if let Some(snapshot_id) = snapshot_id {
+                    cmd.arg("-l").arg(format!("snapshot.name={}", snapshot_id));
+                }
+                cmd.arg("-O")
                     .arg("qcow2")
                     .arg("-o")
                     .arg("preallocation=metadata")
                     .arg(&source_path)
-                    .arg(&target_path)
-                    .output()
-                    .await
-                    .map_err(|e| {
-                        Error::Storage(format!("Failed to run qemu-img convert: {}", e))
-                    })?;
+                    .arg(&target_path);
+                let output = self.commands.convert(&mut cmd, None).await?;
 
                 if !output.status.success() {
                     let stderr = String::from_utf8_lossy(&output.stderr);
@@ -886,8 +1453,24 @@ impl StorageDriver for NfsDriver {
                 }
             }
             "raw" => {
-                // raw 格式直接拷贝
-                fs::copy(&source_path, &target_path)
+                // raw 格式直接拷贝，快照为同目录下的完整副本
+                let copy_from = match snapshot_id {
+                    Some(snapshot_id) => {
+                        let snapshot_path = self
+                            .volume_dir(source_volume_id)
+                            .join(format!("{}-{}.{}", source_volume_id, snapshot_id, format));
+                        if !snapshot_path.exists() {
+                            return Err(Error::NotFound(format!(
+                                "Snapshot {} not found",
+                                snapshot_id
+                            )));
+                        }
+                        snapshot_path
+                    }
+                    None => source_path.clone(),
+                };
+
+                fs::copy(&copy_from, &target_path)
                     .await
                     .map_err(|e| Error::Storage(format!("Failed to copy raw volume: {}", e)))?;
             }
@@ -899,16 +1482,16 @@ impl StorageDriver for NfsDriver {
             }
         }
 
-        // 获取源卷信息
-        let source_info = self.get_volume_info(source_volume_id).await?;
+        // 读取克隆结果的大小（从快照克隆时可能与源卷当前大小不同）
+        let cloned_info = self.get_volume_info(target_volume_id).await?;
 
         // 创建目标卷信息
         let target_info = VolumeInfo {
             volume_id: target_volume_id.to_string(),
             name: target_name.to_string(),
             path: target_path.to_string_lossy().to_string(),
-            size_gb: source_info.size_gb,
-            actual_size_gb: source_info.actual_size_gb, // 完整拷贝后实际大小等于源卷
+            size_gb: cloned_info.size_gb,
+            actual_size_gb: cloned_info.actual_size_gb,
             format: format.to_string(),
             status: "available".to_string(),
         };
@@ -920,7 +1503,382 @@ impl StorageDriver for NfsDriver {
         Ok(target_info)
     }
 
+    async fn clone_source(&self, volume_id: &str, snapshot_id: Option<&str>) -> Result<CloneSource> {
+        let (path, format) = ["qcow2", "raw"]
+            .iter()
+            .map(|fmt| (self.get_volume_path(volume_id, fmt), *fmt))
+            .find(|(path, _)| path.exists())
+            .ok_or_else(|| Error::NotFound(format!("Source volume {} not found", volume_id)))?;
+
+        match (format, snapshot_id) {
+            // raw 快照是独立的完整副本，直接读取快照文件
+            ("raw", Some(snapshot_id)) => {
+                let snapshot_path = self
+                    .volume_dir(volume_id)
+                    .join(format!("{}-{}.raw", volume_id, snapshot_id));
+                if !snapshot_path.exists() {
+                    return Err(Error::NotFound(format!("Snapshot {} not found", snapshot_id)));
+                }
+                Ok(CloneSource {
+                    path: snapshot_path.to_string_lossy().to_string(),
+                    format: format.to_string(),
+                    snapshot_name: None,
+                })
+            }
+            _ => Ok(CloneSource {
+                path: path.to_string_lossy().to_string(),
+                format: format.to_string(),
+                snapshot_name: snapshot_id.map(|s| s.to_string()),
+            }),
+        }
+    }
+
+    async fn clone_from_source(
+        &self,
+        source: &CloneSource,
+        target_volume_id: &str,
+        target_name: &str,
+    ) -> Result<VolumeInfo> {
+        info!(
+            "Importing volume {} from {} (format: {}, snapshot: {:?})",
+            target_volume_id, source.path, source.format, source.snapshot_name
+        );
+
+        let target_path = self.get_volume_path(target_volume_id, &source.format);
+        if target_path.exists() {
+            return Err(Error::AlreadyExists(format!(
+                "Target volume {} already exists",
+                target_volume_id
+            )));
+        }
+        self.ensure_volume_dir(&target_path).await?;
+
+        let mut cmd = Command::new("qemu-img");
+        cmd.arg("convert").arg("-f").arg(&source.format);
+        if let Some(snapshot_name) = &source.snapshot_name {
+            cmd.arg("-l").arg(format!("snapshot.name={}", snapshot_name));
+        }
+        cmd.arg("-O").arg(&source.format);
+        if source.format == "qcow2" {
+            cmd.arg("-o").arg("preallocation=metadata");
+        }
+        cmd.arg(&source.path).arg(&target_path);
+        let output = self.commands.convert(&mut cmd, None).await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!("qemu-img convert failed: {}", stderr);
+            // 清理写了一半的目标文件
+            let _ = fs::remove_file(&target_path).await;
+            return Err(Error::Storage(format!("Failed to import volume: {}", stderr)));
+        }
+
+        let imported = self.get_volume_info(target_volume_id).await?;
+        Ok(VolumeInfo {
+            name: target_name.to_string(),
+            ..imported
+        })
+    }
+
+    /// 引用方式在卷路径上创建指向原镜像的符号链接，之后的操作都作用于原镜像，
+    /// 删除卷时只删除链接；复制方式与跨池导入一样完整拷贝一份新文件
+    async fn import_volume(&self, path: &str, volume_id: &str, mode: VolumeImportMode) -> Result<ImportedVolume> {
+        info!("Importing image {} as volume {} ({:?})", path, volume_id, mode);
+
+        let source_path = self.resolve_import_path(path)?;
+        let (format, virtual_size) = self.inspect_image(&source_path).await?;
+        if format != "qcow2" && format != "raw" {
+            return Err(Error::InvalidArgument(format!(
+                "Unsupported image format {} (only qcow2 and raw can be imported)",
+                format
+            )));
+        }
+        if let Some((existing, _)) = self.find_volume_file(volume_id) {
+            return Err(Error::AlreadyExists(format!(
+                "Volume {} already exists at {}",
+                volume_id,
+                existing.display()
+            )));
+        }
+
+        let target_path = self.get_volume_path(volume_id, &format);
+        match mode {
+            VolumeImportMode::Copy => {
+                let source = CloneSource {
+                    path: source_path.to_string_lossy().to_string(),
+                    format: format.clone(),
+                    snapshot_name: None,
+                };
+                self.clone_from_source(&source, volume_id, volume_id).await?;
+            }
+            VolumeImportMode::Reference => {
+                self.ensure_volume_dir(&target_path).await?;
+                fs::symlink(&source_path, &target_path)
+                    .await
+                    .map_err(|e| Error::Storage(format!("Failed to link image: {}", e)))?;
+            }
+        }
+
+        Ok(ImportedVolume {
+            volume: VolumeInfo {
+                volume_id: volume_id.to_string(),
+                name: volume_id.to_string(),
+                path: target_path.to_string_lossy().to_string(),
+                size_gb: virtual_size.div_ceil(1024 * 1024 * 1024),
+                actual_size_gb: self.get_file_actual_size(&target_path).await?,
+                format,
+                status: "available".to_string(),
+            },
+            source_path: source_path.to_string_lossy().to_string(),
+        })
+    }
+
+    async fn list_orphaned_files(&self, known_volume_ids: &HashSet<String>) -> Result<Vec<OrphanedFile>> {
+        info!("Scanning orphaned files in {:?}", self.mount_path);
+        self.scan_orphaned_files(known_volume_ids).await
+    }
+
+    async fn delete_orphaned_files(
+        &self,
+        known_volume_ids: &HashSet<String>,
+        file_names: &[String],
+    ) -> Result<Vec<String>> {
+        // 重新扫描，只删除此刻仍为孤立状态的文件
+        let orphans = self.scan_orphaned_files(known_volume_ids).await?;
+        let mut deleted = Vec::new();
+
+        for orphan in orphans.iter().filter(|o| file_names.contains(&o.file_name)) {
+            match fs::remove_file(&orphan.path).await {
+                Ok(_) => {
+                    info!("Deleted orphaned file {:?}", orphan.path);
+                    deleted.push(orphan.file_name.clone());
+                }
+                Err(e) => {
+                    warn!("Failed to delete orphaned file {:?}: {}", orphan.path, e);
+                }
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    async fn prepare_export(&self, volume_id: &str) -> Result<ExportInfo> {
+        let (source_path, format) = self
+            .find_volume_file(volume_id)
+            .ok_or_else(|| Error::NotFound(format!("Volume {} not found", volume_id)))?;
+        let export_path = self.get_export_path(volume_id, format);
+
+        if !Self::is_export_fresh(&source_path, &export_path).await {
+            info!("Preparing export of volume {} at {:?}", volume_id, export_path);
+
+            if let Some(dir) = export_path.parent() {
+                fs::create_dir_all(dir).await.map_err(|e| {
+                    Error::Storage(format!("Failed to create export directory: {}", e))
+                })?;
+            }
+
+            // 先转换到唯一的临时文件再改名，并发的导出请求不会读到写了一半的副本
+            let part_path =
+                export_path.with_extension(format!("{}.{}.part", format, uuid::Uuid::new_v4()));
+            let output = self
+                .commands
+                .convert(
+                    Command::new("qemu-img")
+                        .arg("convert")
+                        .arg("-f")
+                        .arg(format)
+                        .arg("-O")
+                        .arg(format)
+                        .arg(&source_path)
+                        .arg(&part_path),
+                    None,
+                )
+                .await?;
+
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                error!("qemu-img convert failed: {}", stderr);
+                let _ = fs::remove_file(&part_path).await;
+                return Err(Error::Storage(format!("Failed to export volume: {}", stderr)));
+            }
+
+            fs::rename(&part_path, &export_path).await.map_err(|e| {
+                Error::Storage(format!("Failed to finalize export file: {}", e))
+            })?;
+        } else {
+            debug!("Reusing export of volume {} at {:?}", volume_id, export_path);
+        }
+
+        let size_bytes = fs::metadata(&export_path)
+            .await
+            .map_err(|e| Error::Storage(format!("Failed to get file metadata: {}", e)))?
+            .len();
+
+        Ok(ExportInfo {
+            path: export_path.to_string_lossy().to_string(),
+            format: format.to_string(),
+            size_bytes,
+        })
+    }
+
+    async fn read_export(&self, volume_id: &str, offset: u64, length: u64) -> Result<Vec<u8>> {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let (_, format) = self
+            .find_volume_file(volume_id)
+            .ok_or_else(|| Error::NotFound(format!("Volume {} not found", volume_id)))?;
+        let export_path = self.get_export_path(volume_id, format);
+
+        let mut file = fs::File::open(&export_path).await.map_err(|e| {
+            Error::NotFound(format!("Export of volume {} not found: {}", volume_id, e))
+        })?;
+        file.seek(std::io::SeekFrom::Start(offset))
+            .await
+            .map_err(|e| Error::Storage(format!("Failed to seek export file: {}", e)))?;
+
+        let mut buf = Vec::with_capacity(length as usize);
+        file.take(length)
+            .read_to_end(&mut buf)
+            .await
+            .map_err(|e| Error::Storage(format!("Failed to read export file: {}", e)))?;
+        Ok(buf)
+    }
+
+    async fn pool_usage(&self) -> Result<PoolUsage> {
+        // NFS 服务端无响应时 statvfs 会阻塞，放到阻塞线程池执行
+        let mount_path = self.mount_path.clone();
+        tokio::task::spawn_blocking(move || Self::statvfs(&mount_path))
+            .await
+            .map_err(|e| Error::Internal(format!("statvfs task failed: {}", e)))?
+    }
+
     fn driver_type(&self) -> &str {
         "nfs"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_qcow2_snapshots() {
+        let info = serde_json::json!({
+            "virtual-size": 10737418240u64,
+            "format": "qcow2",
+            "snapshots": [
+                { "id": "1", "name": "snap-a", "vm-state-size": 0, "date-sec": 1700000000, "date-nsec": 0 },
+                { "id": "2", "name": "snap-b", "vm-state-size": 1024, "date-sec": 1700000100, "date-nsec": 0 }
+            ]
+        });
+
+        let snapshots = NfsDriver::parse_qcow2_snapshots(&info);
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].name, "snap-a");
+        assert_eq!(snapshots[1].vm_state_size, 1024);
+        assert_eq!(snapshots[1].created_at, Some(1700000100));
+
+        // 没有快照时 qemu-img 不输出 snapshots 字段
+        let empty = serde_json::json!({ "format": "qcow2" });
+        assert!(NfsDriver::parse_qcow2_snapshots(&empty).is_empty());
+    }
+
+    #[test]
+    fn test_is_known_file() {
+        let known: HashSet<String> = ["vol-1".to_string()].into_iter().collect();
+
+        assert!(NfsDriver::is_known_file("vol-1", &known));
+        assert!(NfsDriver::is_known_file("vol-1-snap-a", &known));
+        assert!(!NfsDriver::is_known_file("vol-10", &known));
+        assert!(!NfsDriver::is_known_file("vol-2", &known));
+    }
+
+    #[test]
+    fn test_parse_convert_progress() {
+        assert_eq!(NfsDriver::parse_convert_progress("    (42.17/100%)\r"), Some(42.17));
+        assert_eq!(NfsDriver::parse_convert_progress("    (100.00/100%)\n"), Some(100.0));
+        assert_eq!(NfsDriver::parse_convert_progress("qemu-img: error"), None);
+    }
+
+    #[test]
+    fn test_parse_qemu_img_version() {
+        assert_eq!(
+            NfsDriver::parse_qemu_img_version("qemu-img version 8.2.2 (Debian 1:8.2.2+ds-0ubuntu1)\nCopyright"),
+            Some((8, 2))
+        );
+        assert_eq!(
+            NfsDriver::parse_qemu_img_version("qemu-img version 4.2.1 (Debian 1:4.2-3ubuntu6)"),
+            Some((4, 2))
+        );
+        assert_eq!(NfsDriver::parse_qemu_img_version("unexpected"), None);
+    }
+
+    #[tokio::test]
+    async fn test_walk_volume_files_follows_template_depth() {
+        let root = std::env::temp_dir().join(format!("nfs_walk_{}", uuid::Uuid::new_v4()));
+        let template = PathTemplate::parse("{volume_id:2}/{volume_id}").unwrap();
+        for volume_id in ["abcdef", "ab1234", "cd5678"] {
+            let dir = template.volume_dir(&root, volume_id);
+            fs::create_dir_all(&dir).await.unwrap();
+            fs::write(dir.join(format!("{}.qcow2", volume_id)), b"").await.unwrap();
+        }
+        // 导出目录和模板层级之外的文件都不应被遍历到
+        fs::create_dir_all(root.join(EXPORT_DIR)).await.unwrap();
+        fs::write(root.join(EXPORT_DIR).join("abcdef.qcow2"), b"").await.unwrap();
+        fs::write(root.join("stray.qcow2"), b"").await.unwrap();
+
+        let mut names: Vec<String> = NfsDriver::walk_volume_files(&root, template.depth())
+            .await
+            .unwrap()
+            .iter()
+            .filter_map(|path| NfsDriver::extract_volume_id(path))
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["ab1234", "abcdef", "cd5678"]);
+
+        let _ = fs::remove_dir_all(&root).await;
+    }
+
+    #[test]
+    fn test_statvfs_reports_consistent_usage() {
+        let usage = NfsDriver::statvfs(&std::env::temp_dir()).unwrap();
+        assert!(usage.total_bytes > 0);
+        assert!(usage.used_bytes + usage.available_bytes <= usage.total_bytes);
+        assert!(NfsDriver::statvfs(Path::new("/nonexistent-mount-path")).is_err());
+    }
+
+    #[test]
+    fn test_resolve_import_path_stays_inside_pool() {
+        let root = std::env::temp_dir().join(format!("nfs_import_{}", uuid::Uuid::new_v4()));
+        let mount = root.join("pool");
+        std::fs::create_dir_all(mount.join("seed")).unwrap();
+        std::fs::create_dir_all(mount.join(EXPORT_DIR)).unwrap();
+        std::fs::write(mount.join("seed/ubuntu.qcow2"), b"").unwrap();
+        std::fs::write(mount.join(EXPORT_DIR).join("v1.qcow2"), b"").unwrap();
+        std::fs::write(root.join("outside.qcow2"), b"").unwrap();
+
+        let config = StoragePoolConfig {
+            pool_id: "p1".to_string(),
+            pool_name: "nfs".to_string(),
+            storage_type: "nfs".to_string(),
+            config: [("mount_path".to_string(), mount.to_string_lossy().to_string())].into(),
+        };
+        let driver = NfsDriver::new(config, Arc::new(DownloadLimiter::new(1)), CommandPolicy::default()).unwrap();
+        let path = |p: &Path| p.to_string_lossy().to_string();
+
+        assert_eq!(
+            driver.resolve_import_path(&path(&mount.join("seed/ubuntu.qcow2"))).unwrap(),
+            mount.join("seed/ubuntu.qcow2").canonicalize().unwrap()
+        );
+        assert!(driver.resolve_import_path(&path(&mount.join("seed/../../outside.qcow2"))).is_err());
+        assert!(driver.resolve_import_path(&path(&root.join("outside.qcow2"))).is_err());
+        assert!(driver.resolve_import_path(&path(&mount.join(EXPORT_DIR).join("v1.qcow2"))).is_err());
+        assert!(driver.resolve_import_path(&path(&mount.join("seed"))).is_err());
+        assert!(driver.resolve_import_path(&path(&mount.join("missing.qcow2"))).is_err());
+        assert!(driver.resolve_import_path("seed/ubuntu.qcow2").is_err());
+        std::fs::write(driver.get_volume_path("v2", "qcow2"), b"").unwrap();
+        assert!(driver.resolve_import_path(&path(&mount.join("seed/../v2.qcow2"))).is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,173 @@
+/// 存储卷路径模板
+///
+/// 存储池配置 `path_template` 决定卷文件相对挂载点的位置（不含扩展名），
+/// 用于把大量卷分散到子目录中，避免单目录文件过多：
+///
+/// - `{volume_id}`：默认，所有卷平铺在挂载点下
+/// - `{volume_id:2}/{volume_id}`：按卷 ID 前两个字符分目录，如 `ab/abcdef.qcow2`
+///
+/// 最后一级必须恰好是 `{volume_id}`，以便从文件名反推卷 ID，
+/// raw 快照等附属文件与卷文件放在同一目录
+use common::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// 默认模板：平铺
+pub const DEFAULT_PATH_TEMPLATE: &str = "{volume_id}";
+
+/// 目录层的组成片段
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    /// 卷 ID 的前 N 个字符（None 表示完整 ID）
+    VolumeId(Option<usize>),
+}
+
+/// 解析后的路径模板，默认平铺
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathTemplate {
+    /// 卷文件所在的各级目录，每级由若干片段拼接
+    dirs: Vec<Vec<Segment>>,
+}
+
+impl PathTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            Error::Config(format!("Invalid NFS path_template '{}': {}", template, reason))
+        };
+
+        let components: Vec<&str> = template.split('/').collect();
+        let (last, dirs) = components
+            .split_last()
+            .ok_or_else(|| invalid("empty template"))?;
+        if *last != "{volume_id}" {
+            return Err(invalid("last component must be {volume_id}"));
+        }
+
+        let dirs = dirs
+            .iter()
+            .map(|component| {
+                if component.is_empty() || *component == "." || *component == ".." {
+                    return Err(invalid("empty, '.' or '..' components are not allowed"));
+                }
+                if component.starts_with('.') {
+                    return Err(invalid("directories must not start with '.'"));
+                }
+                Self::parse_component(component).ok_or_else(|| invalid("unknown placeholder"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { dirs })
+    }
+
+    /// 解析一级目录，支持 `{volume_id}` 与 `{volume_id:N}`（N > 0）
+    fn parse_component(component: &str) -> Option<Vec<Segment>> {
+        let mut segments = Vec::new();
+        let mut rest = component;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..].find('}')? + start;
+            let placeholder = &rest[start + 1..end];
+            let segment = match placeholder.split_once(':') {
+                None if placeholder == "volume_id" => Segment::VolumeId(None),
+                Some(("volume_id", len)) => match len.parse::<usize>().ok()? {
+                    0 => return None,
+                    len => Segment::VolumeId(Some(len)),
+                },
+                _ => return None,
+            };
+            segments.push(segment);
+            rest = &rest[end + 1..];
+        }
+        if rest.contains('}') {
+            return None;
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+        Some(segments)
+    }
+
+    /// 卷文件所在目录相对挂载点的层数
+    pub fn depth(&self) -> usize {
+        self.dirs.len()
+    }
+
+    /// 卷文件所在目录
+    pub fn volume_dir(&self, root: &Path, volume_id: &str) -> PathBuf {
+        let mut dir = root.to_path_buf();
+        for segments in &self.dirs {
+            let name: String = segments
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Literal(text) => text.as_str(),
+                    Segment::VolumeId(None) => volume_id,
+                    Segment::VolumeId(Some(len)) => {
+                        let end = volume_id
+                            .char_indices()
+                            .nth(*len)
+                            .map_or(volume_id.len(), |(i, _)| i);
+                        &volume_id[..end]
+                    }
+                })
+                .collect();
+            dir.push(name);
+        }
+        dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_template_is_flat() {
+        let template = PathTemplate::parse(DEFAULT_PATH_TEMPLATE).unwrap();
+        assert_eq!(template, PathTemplate::default());
+        assert_eq!(template.depth(), 0);
+        assert_eq!(
+            template.volume_dir(Path::new("/mnt/nfs"), "abcdef"),
+            PathBuf::from("/mnt/nfs")
+        );
+    }
+
+    #[test]
+    fn test_sharded_template() {
+        let template = PathTemplate::parse("{volume_id:2}/{volume_id:4}/{volume_id}").unwrap();
+        assert_eq!(template.depth(), 2);
+        assert_eq!(
+            template.volume_dir(Path::new("/mnt/nfs"), "abcdef"),
+            PathBuf::from("/mnt/nfs/ab/abcd")
+        );
+        // 卷 ID 比前缀短时使用完整 ID
+        assert_eq!(
+            template.volume_dir(Path::new("/mnt/nfs"), "a"),
+            PathBuf::from("/mnt/nfs/a/a")
+        );
+
+        let template = PathTemplate::parse("vols-{volume_id:1}/{volume_id}").unwrap();
+        assert_eq!(
+            template.volume_dir(Path::new("/mnt/nfs"), "abcdef"),
+            PathBuf::from("/mnt/nfs/vols-a")
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        for template in [
+            "",
+            "{volume_id}/data",
+            "{volume_id:2}/{volume_id}.img",
+            "/{volume_id}",
+            "../{volume_id}",
+            ".exports/{volume_id}",
+            "{volume_id:0}/{volume_id}",
+            "{pool_id}/{volume_id}",
+            "{volume_id:2/{volume_id}",
+        ] {
+            assert!(PathTemplate::parse(template).is_err(), "{}", template);
+        }
+    }
+}
@@ -2,17 +2,121 @@
 /// 
 /// Agent 连接到 Server 的 WebSocket 客户端
 
-use common::ws_rpc::{RpcMessage, RegisterRequest};
+use common::ws_rpc::client::codec;
+use common::ws_rpc::{
+    CancelRequest, PingRequest, PongResponse, RegisterRequest, RegisterResponse, RpcCodec, RpcError, RpcMessage,
+    StreamSender, DEFAULT_COMPRESS_THRESHOLD,
+};
 use futures_util::{SinkExt, StreamExt};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, RwLock};
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio::task::AbortHandle;
+use tokio_tungstenite::{connect_async_tls_with_config, tungstenite::Message, Connector};
 use tracing::{debug, error, info, warn};
 
+use super::dead_letter::{DeadLetterQueue, NotificationSender};
 use super::handler::RpcHandlerRegistry;
+use crate::hypervisor::Hypervisor;
+use crate::metrics;
 use crate::node::NodeManager;
 
+/// 节点资源利用率上报间隔（秒）
+const RESOURCE_REPORT_INTERVAL_SECS: u64 = 60;
+
+/// 注册后连接保持超过该时长才视为稳定，下次断线从基础间隔重新退避
+const RECONNECT_RESET_AFTER: Duration = Duration::from_secs(30);
+
+/// 重连等待时间的随机抖动比例（±20%），避免 Server 重启后所有 Agent 同时重连
+const RECONNECT_JITTER: f64 = 0.2;
+
+/// 单次 ping 等待 pong 的最长时间，不超过心跳间隔
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 连续多少次 ping 收不到 pong 视为连接已失效（如半开的 TCP 连接），主动断开重连
+const MAX_MISSED_PONGS: u32 = 3;
+
+/// 重连指数退避：每次失败等待时间翻倍，不超过上限
+#[derive(Debug, Clone)]
+struct ReconnectBackoff {
+    base: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl ReconnectBackoff {
+    fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            current: base,
+        }
+    }
+
+    /// 返回本次等待时间（未加抖动），并将下次等待时间翻倍
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+
+    /// 连接稳定后回到基础间隔
+    fn reset(&mut self) {
+        self.current = self.base;
+    }
+}
+
+/// 为等待时间附加 ±RECONNECT_JITTER 的随机抖动
+fn with_jitter(delay: Duration) -> Duration {
+    // UUID v4 的低 32 位全部为随机位
+    let random = (uuid::Uuid::new_v4().as_u128() as u32) as f64 / u32::MAX as f64;
+    delay.mul_f64(1.0 + RECONNECT_JITTER * (2.0 * random - 1.0))
+}
+
+/// 正在处理的 Server 请求，收到 `cancel` 通知时中止对应的处理任务
+#[derive(Clone, Default)]
+struct InFlightRequests {
+    tasks: Arc<std::sync::Mutex<std::collections::HashMap<String, AbortHandle>>>,
+}
+
+impl InFlightRequests {
+    /// 在独立任务中处理请求，处理结束或被取消后移除记录；被取消时返回 None
+    async fn run<F>(&self, request_id: &str, future: F) -> Option<F::Output>
+    where
+        F: std::future::Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let task = tokio::spawn(future);
+        self.tasks
+            .lock()
+            .unwrap()
+            .insert(request_id.to_string(), task.abort_handle());
+
+        let result = task.await;
+        self.tasks.lock().unwrap().remove(request_id);
+        match result {
+            Ok(output) => Some(output),
+            Err(e) => {
+                if !e.is_cancelled() {
+                    error!("处理请求任务异常: id={}, error={}", request_id, e);
+                }
+                None
+            }
+        }
+    }
+
+    /// 中止请求对应的处理任务，请求已结束时返回 false
+    fn cancel(&self, request_id: &str) -> bool {
+        match self.tasks.lock().unwrap().remove(request_id) {
+            Some(task) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
 /// WebSocket 客户端状态
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ClientState {
@@ -37,8 +141,17 @@ pub struct WsClient {
     /// RPC 处理器注册表
     handler_registry: Arc<RwLock<RpcHandlerRegistry>>,
     
-    /// 重连间隔（秒）
-    reconnect_interval: u64,
+    /// 重连退避的基础间隔（秒）
+    reconnect_base_secs: u64,
+
+    /// 重连退避的最大间隔（秒）
+    reconnect_max_secs: u64,
+
+    /// wss:// 连接使用的 TLS 连接器，未设置时按地址 scheme 使用默认行为
+    tls_connector: Option<Connector>,
+
+    /// 注册时携带的共享密钥
+    auth_token: Option<String>,
     
     /// 心跳间隔（秒）
     heartbeat_interval: u64,
@@ -48,6 +161,24 @@ pub struct WsClient {
     
     /// 待响应的RPC请求（用于主动RPC调用）
     pending_requests: Arc<RwLock<std::collections::HashMap<String, mpsc::UnboundedSender<RpcMessage>>>>,
+
+    /// 正在处理的 Server 请求（用于响应 cancel 通知）
+    in_flight: InFlightRequests,
+
+    /// 通知死信队列（发送失败的通知在重连后重放）
+    dead_letters: Arc<DeadLetterQueue>,
+
+    /// 虚拟机运行时指标推送间隔（秒），0 表示不推送
+    vm_metrics_interval: u64,
+
+    /// 主机级指标上报间隔（秒），0 表示不上报
+    node_metrics_interval: u64,
+
+    /// 注册时声明的消息编码，实际编码以 Server 的注册响应为准
+    rpc_codec: RpcCodec,
+
+    /// 发往 Server 的消息超过该字节数时 gzip 压缩，0 表示不压缩
+    compress_threshold: usize,
 }
 
 impl WsClient {
@@ -56,25 +187,77 @@ impl WsClient {
         server_url: impl Into<String>,
         node_manager: NodeManager,
         handler_registry: Arc<RwLock<RpcHandlerRegistry>>,
+        dead_letters: Arc<DeadLetterQueue>,
     ) -> Self {
         Self {
             server_url: server_url.into(),
             node_manager,
             state: Arc::new(RwLock::new(ClientState::Disconnected)),
             handler_registry,
-            reconnect_interval: 5,
+            reconnect_base_secs: 1,
+            reconnect_max_secs: 60,
+            tls_connector: None,
+            auth_token: None,
             heartbeat_interval: 30,
             message_sender: Arc::new(RwLock::new(None)),
             pending_requests: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            in_flight: InFlightRequests::default(),
+            dead_letters,
+            vm_metrics_interval: 0,
+            node_metrics_interval: 0,
+            rpc_codec: RpcCodec::Json,
+            compress_threshold: DEFAULT_COMPRESS_THRESHOLD,
         }
     }
 
+    /// 设置虚拟机运行时指标推送间隔（秒），0 表示不推送
+    pub fn set_vm_metrics_interval(&mut self, secs: u64) {
+        self.vm_metrics_interval = secs;
+    }
+
+    /// 设置主机级指标上报间隔（秒），0 表示不上报
+    pub fn set_node_metrics_interval(&mut self, secs: u64) {
+        self.node_metrics_interval = secs;
+    }
+
+    /// 设置重连退避的基础间隔与最大间隔（秒）
+    pub fn set_reconnect_backoff(&mut self, base_secs: u64, max_secs: u64) {
+        self.reconnect_base_secs = base_secs;
+        self.reconnect_max_secs = max_secs;
+    }
+
+    /// 设置 wss:// 连接使用的 TLS 连接器
+    pub fn set_tls_connector(&mut self, connector: Connector) {
+        self.tls_connector = Some(connector);
+    }
+
+    /// 设置注册时携带的共享密钥
+    pub fn set_auth_token(&mut self, token: Option<String>) {
+        self.auth_token = token;
+    }
+
+    /// 设置注册时声明的消息编码
+    pub fn set_rpc_codec(&mut self, codec: RpcCodec) {
+        self.rpc_codec = codec;
+    }
+
+    /// 设置消息压缩阈值（字节），0 表示不压缩
+    pub fn set_compress_threshold(&mut self, threshold: usize) {
+        self.compress_threshold = threshold;
+    }
+
     /// 启动客户端（连接并保持）
     pub async fn run(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut backoff = ReconnectBackoff::new(
+            Duration::from_secs(self.reconnect_base_secs),
+            Duration::from_secs(self.reconnect_max_secs),
+        );
+
         loop {
             info!("尝试连接到 Server: {}", self.server_url);
-            
-            match self.connect_and_run().await {
+
+            let mut registered_at = None;
+            match self.connect_and_run(&mut registered_at).await {
                 Ok(_) => {
                     info!("连接正常关闭");
                 }
@@ -89,14 +272,23 @@ impl WsClient {
                 *state = ClientState::Disconnected;
             }
             
+            // 注册后稳定运行过一段时间，说明不是连续失败，从基础间隔重新退避
+            if registered_at.is_some_and(|at: Instant| at.elapsed() >= RECONNECT_RESET_AFTER) {
+                backoff.reset();
+            }
+
             // 等待后重连
-            warn!("{}秒后重新连接...", self.reconnect_interval);
-            tokio::time::sleep(Duration::from_secs(self.reconnect_interval)).await;
+            let delay = with_jitter(backoff.next_delay());
+            warn!("{:.1}秒后重新连接...", delay.as_secs_f64());
+            tokio::time::sleep(delay).await;
         }
     }
 
-    /// 连接并运行
-    async fn connect_and_run(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// 连接并运行，注册成功时记录注册时间
+    async fn connect_and_run(
+        &self,
+        registered_at: &mut Option<Instant>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // 更新状态
         {
             let mut state = self.state.write().await;
@@ -104,7 +296,9 @@ impl WsClient {
         }
 
         // 连接到 Server
-        let (ws_stream, _) = connect_async(&self.server_url).await?;
+        let (ws_stream, _) =
+            connect_async_tls_with_config(&self.server_url, None, false, self.tls_connector.clone())
+                .await?;
         info!("✅ WebSocket 连接成功");
 
         // 更新状态
@@ -124,6 +318,9 @@ impl WsClient {
             node_id: node_info.node_id.clone(),
             hostname: node_info.hostname.clone(),
             ip_address: node_info.ip_address.clone(),
+            codec: self.rpc_codec,
+            compression: true,
+            token: self.auth_token.clone(),
         };
         
         let register_msg = RpcMessage::request(
@@ -134,11 +331,27 @@ impl WsClient {
         self.send_message(&mut ws_sender, register_msg).await?;
         debug!("已发送注册请求");
 
-        // 等待注册响应
+        // 等待注册响应，旧版本 Server 不返回编码，按 JSON 通信且不压缩
+        let mut wire_codec = RpcCodec::Json;
+        let mut compress_threshold = None;
         if let Some(msg) = ws_receiver.next().await {
-            let rpc_msg = self.parse_message(msg?)?;
+            // Server 拒绝注册（如共享密钥不匹配）时直接关闭连接
+            let msg = msg?;
+            if let Message::Close(frame) = &msg {
+                let reason = frame.as_ref().map(|f| f.reason.to_string()).unwrap_or_default();
+                return Err(format!("Server 拒绝注册: {}", reason).into());
+            }
+            let rpc_msg = self.parse_message(msg)?;
             if rpc_msg.is_success() {
-                info!("✅ 注册成功");
+                if let Some(response) = rpc_msg
+                    .payload
+                    .and_then(|p| serde_json::from_value::<RegisterResponse>(p).ok())
+                {
+                    wire_codec = response.codec;
+                    compress_threshold = response.compression.then_some(self.compress_threshold);
+                }
+                info!("✅ 注册成功，消息编码: {}", wire_codec.as_str());
+                *registered_at = Some(Instant::now());
                 let mut state = self.state.write().await;
                 *state = ClientState::Registered;
                 
@@ -151,54 +364,172 @@ impl WsClient {
             }
         }
 
-        // 启动心跳任务
-        let tx_heartbeat = tx.clone();
-        let heartbeat_interval = self.heartbeat_interval;
-        let heartbeat_task = tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(heartbeat_interval));
+        // 启动心跳任务（同时上报 libvirt 连接状态，未连接时顺带尝试重连）
+        let heartbeat_interval = Duration::from_secs(self.heartbeat_interval);
+        let mut heartbeat_task = tokio::spawn(Self::run_heartbeat(
+            tx.clone(),
+            self.pending_requests.clone(),
+            self.handler_registry.read().await.hypervisor(),
+            heartbeat_interval,
+            PING_TIMEOUT.min(heartbeat_interval),
+        ));
+
+        // 定期上报资源利用率（注册时已上报一次），供 Server 计算节点健康度
+        let tx_resource = tx.clone();
+        let node_manager = self.node_manager.clone();
+        let resource_task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(RESOURCE_REPORT_INTERVAL_SECS));
+            interval.tick().await;
             loop {
                 interval.tick().await;
-                
-                let heartbeat_msg = RpcMessage::notification(
-                    "heartbeat",
-                    serde_json::json!({
-                        "timestamp": chrono::Utc::now().timestamp()
-                    }),
-                );
-                
-                if tx_heartbeat.send(heartbeat_msg).is_err() {
+
+                let manager = node_manager.clone();
+                let resource_info = match tokio::task::spawn_blocking(move || manager.get_system_resource_info()).await {
+                    Ok(Ok(info)) => info,
+                    Ok(Err(e)) => {
+                        warn!("采集节点资源信息失败: {}", e);
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("采集节点资源信息任务异常: {}", e);
+                        continue;
+                    }
+                };
+
+                let payload = match serde_json::to_value(&resource_info) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        warn!("序列化节点资源信息失败: {}", e);
+                        continue;
+                    }
+                };
+
+                if tx_resource.send(RpcMessage::notification("node_resource_info", payload)).is_err() {
                     break;
                 }
-                debug!("发送心跳");
+                debug!("上报节点资源利用率");
             }
         });
 
+        // 定期推送运行中虚拟机的统计
+        let vm_metrics_task = if self.vm_metrics_interval > 0 {
+            let tx_metrics = tx.clone();
+            let hypervisor = self.handler_registry.read().await.hypervisor();
+            let node_id = node_info.node_id.clone();
+            let interval_secs = self.vm_metrics_interval;
+            Some(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+                loop {
+                    interval.tick().await;
+
+                    if !hypervisor.is_connected() {
+                        continue;
+                    }
+
+                    let report = match metrics::collect_vm_metrics(hypervisor.as_ref(), &node_id).await {
+                        Ok(report) => report,
+                        Err(e) => {
+                            warn!("采集虚拟机指标失败: {}", e);
+                            continue;
+                        }
+                    };
+
+                    let payload = match serde_json::to_value(&report) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            warn!("序列化虚拟机指标失败: {}", e);
+                            continue;
+                        }
+                    };
+
+                    if tx_metrics.send(RpcMessage::notification("vm_metrics", payload)).is_err() {
+                        break;
+                    }
+                    debug!("推送 {} 台虚拟机的运行时指标", report.vms.len());
+                }
+            }))
+        } else {
+            None
+        };
+
+        // 定期上报主机级指标，由 Server 保存最近一段时间的历史
+        let node_metrics_task = if self.node_metrics_interval > 0 {
+            let tx_node_metrics = tx.clone();
+            let node_manager = self.node_manager.clone();
+            let interval_secs = self.node_metrics_interval;
+            Some(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+                loop {
+                    interval.tick().await;
+
+                    let manager = node_manager.clone();
+                    let sample = match tokio::task::spawn_blocking(move || manager.sample_metrics()).await {
+                        Ok(sample) => sample,
+                        Err(e) => {
+                            warn!("采样主机指标任务异常: {}", e);
+                            continue;
+                        }
+                    };
+
+                    let payload = match serde_json::to_value(&sample) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            warn!("序列化主机指标失败: {}", e);
+                            continue;
+                        }
+                    };
+
+                    if tx_node_metrics.send(RpcMessage::notification("node_metrics", payload)).is_err() {
+                        break;
+                    }
+                    debug!("上报主机指标");
+                }
+            }))
+        } else {
+            None
+        };
+
         // 启动发送任务
+        let dead_letters = self.dead_letters.clone();
         let send_task = tokio::spawn(async move {
             while let Some(msg) = rx.recv().await {
-                let json = match msg.to_json() {
-                    Ok(j) => j,
+                let ws_msg = match codec::encode_compressed(&msg, wire_codec, compress_threshold) {
+                    Ok(m) => m,
                     Err(e) => {
                         error!("序列化消息失败: {}", e);
                         continue;
                     }
                 };
                 
-                if let Err(e) = ws_sender.send(Message::Text(json)).await {
+                if let Err(e) = ws_sender.send(ws_msg).await {
                     error!("发送消息失败: {}", e);
+                    dead_letters.push(&msg);
                     break;
                 }
             }
+
+            // 连接已断开，通道中尚未发出的通知转入死信队列
+            rx.close();
+            while let Ok(msg) = rx.try_recv() {
+                dead_letters.push(&msg);
+            }
             debug!("发送任务结束");
         });
 
         // 设置通知发送器和WebSocket客户端引用到处理器注册表
+        let notification_sender = NotificationSender::new(tx.clone(), self.dead_letters.clone());
         {
             let mut registry = self.handler_registry.write().await;
-            registry.set_notification_sender(tx.clone());
+            registry.set_notification_sender(notification_sender.clone());
             registry.set_ws_client(Arc::new(self.clone()));
         }
 
+        // 重放上次断线期间未送达的通知
+        let replayed = notification_sender.replay_dead_letters();
+        if replayed > 0 {
+            info!("✅ 已重放 {} 条死信通知", replayed);
+        }
+
         // 设置消息发送通道（用于主动RPC调用）
         {
             let mut sender = self.message_sender.write().await;
@@ -209,6 +540,7 @@ impl WsClient {
         let handler_registry = self.handler_registry.clone();
         let tx_clone = tx.clone();
         let pending_requests = self.pending_requests.clone();
+        let in_flight = self.in_flight.clone();
         let recv_task = tokio::spawn(async move {
             while let Some(result) = ws_receiver.next().await {
                 match result {
@@ -217,12 +549,14 @@ impl WsClient {
                         let handler_registry = handler_registry.clone();
                         let tx_clone = tx_clone.clone();
                         let pending_requests = pending_requests.clone();
+                        let in_flight = in_flight.clone();
                         tokio::spawn(async move {
                             Self::handle_message_static(
                                 msg,
                                 &handler_registry,
                                 &tx_clone,
                                 &pending_requests,
+                                &in_flight,
                             ).await;
                         });
                     }
@@ -236,21 +570,116 @@ impl WsClient {
         });
 
         // 等待任一任务完成
+        let mut send_task = send_task;
+        let mut recv_task = recv_task;
+        let mut stale = false;
         tokio::select! {
-            _ = send_task => {
+            _ = &mut send_task => {
                 debug!("发送任务已结束");
             }
-            _ = recv_task => {
+            _ = &mut recv_task => {
                 debug!("接收任务已结束");
             }
+            result = &mut heartbeat_task => {
+                stale = result.unwrap_or(false);
+            }
         }
 
-        // 清理心跳任务
+        // 清理收发、心跳、资源与指标上报任务，连接随收发任务一起释放
+        send_task.abort();
+        recv_task.abort();
         heartbeat_task.abort();
+        resource_task.abort();
+        for task in [vm_metrics_task, node_metrics_task].into_iter().flatten() {
+            task.abort();
+        }
 
+        if stale {
+            return Err(format!("连续 {} 次 ping 未收到响应，连接已失效", MAX_MISSED_PONGS).into());
+        }
         Ok(())
     }
 
+    /// 心跳循环：定期发送 ping 并根据 pong 计算往返时延
+    ///
+    /// 连续 MAX_MISSED_PONGS 次超时未收到 pong 时返回 true；发送通道关闭时返回 false
+    async fn run_heartbeat(
+        tx: mpsc::UnboundedSender<RpcMessage>,
+        pending_requests: Arc<RwLock<std::collections::HashMap<String, mpsc::UnboundedSender<RpcMessage>>>>,
+        hypervisor: Arc<dyn Hypervisor>,
+        interval: Duration,
+        ping_timeout: Duration,
+    ) -> bool {
+        let mut ticker = tokio::time::interval(interval);
+        let mut missed_pongs = 0;
+        let mut last_rtt_ms = None;
+        loop {
+            ticker.tick().await;
+
+            let hypervisor_connected = hypervisor.is_connected() || hypervisor.ensure_connected().await;
+            let ping = PingRequest {
+                timestamp_ms: chrono::Utc::now().timestamp_millis(),
+                hypervisor_connected,
+                last_rtt_ms,
+            };
+            let request = match serde_json::to_value(&ping) {
+                Ok(payload) => RpcMessage::request("ping", payload),
+                Err(e) => {
+                    warn!("序列化 ping 请求失败: {}", e);
+                    continue;
+                }
+            };
+            let request_id = request.id.clone();
+
+            let (response_tx, mut response_rx) = mpsc::unbounded_channel();
+            pending_requests.write().await.insert(request_id.clone(), response_tx);
+            if tx.send(request).is_err() {
+                pending_requests.write().await.remove(&request_id);
+                return false;
+            }
+            let response = tokio::time::timeout(ping_timeout, response_rx.recv()).await;
+            pending_requests.write().await.remove(&request_id);
+
+            let response = match response {
+                Ok(Some(response)) => response,
+                _ => {
+                    missed_pongs += 1;
+                    warn!("ping 超时未收到响应 ({}/{})", missed_pongs, MAX_MISSED_PONGS);
+                    if missed_pongs >= MAX_MISSED_PONGS {
+                        return true;
+                    }
+                    continue;
+                }
+            };
+            missed_pongs = 0;
+
+            if !response.is_success() {
+                // 旧版本 Server 不支持 ping，改发心跳通知维持节点在线
+                let heartbeat = RpcMessage::notification(
+                    "heartbeat",
+                    serde_json::json!({
+                        "timestamp": chrono::Utc::now().timestamp(),
+                        "hypervisor_connected": hypervisor_connected
+                    }),
+                );
+                if tx.send(heartbeat).is_err() {
+                    return false;
+                }
+                debug!("发送心跳");
+                continue;
+            }
+
+            match response.payload.map(serde_json::from_value::<PongResponse>) {
+                Some(Ok(pong)) => {
+                    let rtt_ms = (chrono::Utc::now().timestamp_millis() - pong.timestamp_ms).max(0) as u64;
+                    last_rtt_ms = Some(rtt_ms);
+                    debug!("收到 pong，往返时延 {}ms", rtt_ms);
+                }
+                _ => warn!("解析 pong 响应失败"),
+            }
+        }
+    }
+
     /// 发送消息（辅助方法）
     async fn send_message(
         &self,
@@ -274,8 +703,7 @@ impl WsClient {
                 Ok(RpcMessage::from_json(&text)?)
             }
             Message::Binary(data) => {
-                let text = String::from_utf8(data)?;
-                Ok(RpcMessage::from_json(&text)?)
+                Ok(RpcMessage::from_binary(&data)?)
             }
             _ => Err("不支持的消息类型".into()),
         }
@@ -288,6 +716,7 @@ impl WsClient {
         handler_registry: &Arc<RwLock<RpcHandlerRegistry>>,
         tx: &mpsc::UnboundedSender<RpcMessage>,
         pending_requests: &Arc<RwLock<std::collections::HashMap<String, mpsc::UnboundedSender<RpcMessage>>>>,
+        in_flight: &InFlightRequests,
     ) {
         let rpc_msg = match msg {
             Message::Text(text) => {
@@ -300,14 +729,7 @@ impl WsClient {
                 }
             }
             Message::Binary(data) => {
-                let text = match String::from_utf8(data) {
-                    Ok(text) => text,
-                    Err(e) => {
-                        error!("二进制转字符串失败: {}", e);
-                        return;
-                    }
-                };
-                match RpcMessage::from_json(&text) {
+                match RpcMessage::from_binary(&data) {
                     Ok(msg) => msg,
                     Err(e) => {
                         error!("解析二进制消息失败: {}", e);
@@ -330,11 +752,21 @@ impl WsClient {
 
         match rpc_msg.message_type {
             common::MessageType::Request => {
-                // 处理请求并发送响应
-                let registry = handler_registry.read().await;
-                let response = registry.handle_request(rpc_msg).await;
-                if let Err(e) = tx.send(response) {
-                    error!("发送响应失败: {}", e);
+                // 处理请求并发送响应；Server 超时后发来 cancel 时中止处理，不再响应
+                let request_id = rpc_msg.id.clone();
+                let handler_registry = handler_registry.clone();
+                let response = in_flight
+                    .run(&request_id, async move {
+                        handler_registry.read().await.handle_request(rpc_msg).await
+                    })
+                    .await;
+                match response {
+                    Some(response) => {
+                        if let Err(e) = tx.send(response) {
+                            error!("发送响应失败: {}", e);
+                        }
+                    }
+                    None => info!("请求已取消: id={}", request_id),
                 }
             }
             common::MessageType::Response => {
@@ -364,6 +796,16 @@ impl WsClient {
                     }
                 };
                 let payload = rpc_msg.payload.clone().unwrap_or(serde_json::Value::Null);
+                if method == "cancel" {
+                    match serde_json::from_value::<CancelRequest>(payload) {
+                        Ok(cancel) if in_flight.cancel(&cancel.request_id) => {
+                            warn!("Server 已放弃等待，中止请求: id={}", cancel.request_id);
+                        }
+                        Ok(cancel) => debug!("待取消的请求已结束: id={}", cancel.request_id),
+                        Err(e) => error!("解析取消通知失败: {}", e),
+                    }
+                    return;
+                }
                 let registry = handler_registry.read().await;
                 if let Err(e) = registry.handle_notification(&method, payload).await {
                     error!("处理通知失败: method={}, error={}", method, e);
@@ -394,6 +836,16 @@ impl WsClient {
         None
     }
 
+    /// 为正在处理的 Server 请求打开流式响应
+    ///
+    /// 推送的 Stream 消息与请求 id 相同，请求处理结束后的 Response 作为流的结尾
+    pub async fn open_stream(&self, request_id: &str) -> Result<StreamSender, RpcError> {
+        let sender = self.message_sender.read().await.clone();
+        sender
+            .map(|sender| StreamSender::new(request_id, sender))
+            .ok_or_else(RpcError::connection_closed)
+    }
+
     /// 主动调用 Server 的 RPC 方法
     /// 
     /// 这个方法允许 Agent 主动向 Server 发起 RPC 调用
@@ -506,3 +958,111 @@ impl WsClient {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_backoff_doubles_up_to_cap_and_resets() {
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(1), Duration::from_secs(60));
+        let delays: Vec<u64> = (0..8).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_reports_stale_after_missed_pongs() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let pending = Arc::new(RwLock::new(std::collections::HashMap::new()));
+        let hypervisor = Arc::new(crate::hypervisor::driver::mock::MockHypervisor::new());
+
+        let stale = WsClient::run_heartbeat(
+            tx,
+            pending.clone(),
+            hypervisor,
+            Duration::from_millis(10),
+            Duration::from_millis(10),
+        )
+        .await;
+
+        assert!(stale);
+        let mut pings = 0;
+        while let Ok(msg) = rx.try_recv() {
+            assert_eq!(msg.method.as_deref(), Some("ping"));
+            pings += 1;
+        }
+        assert_eq!(pings, MAX_MISSED_PONGS);
+        assert!(pending.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_sends_measured_rtt_with_next_ping() {
+        let (tx, mut rx) = mpsc::unbounded_channel::<RpcMessage>();
+        let pending: Arc<RwLock<std::collections::HashMap<String, mpsc::UnboundedSender<RpcMessage>>>> =
+            Arc::new(RwLock::new(std::collections::HashMap::new()));
+        let hypervisor = Arc::new(crate::hypervisor::driver::mock::MockHypervisor::new());
+        let heartbeat = tokio::spawn(WsClient::run_heartbeat(
+            tx,
+            pending.clone(),
+            hypervisor,
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+        ));
+
+        // 模拟 Server 回显时间戳
+        let mut pings = Vec::new();
+        while pings.len() < 2 {
+            let msg = rx.recv().await.unwrap();
+            let ping: PingRequest = serde_json::from_value(msg.payload.clone().unwrap()).unwrap();
+            let pong = PongResponse {
+                timestamp_ms: ping.timestamp_ms,
+                server_time_ms: ping.timestamp_ms,
+            };
+            let response = RpcMessage::response(msg.id.clone(), serde_json::to_value(&pong).unwrap());
+            if let Some(sender) = pending.write().await.remove(&msg.id) {
+                sender.send(response).unwrap();
+            }
+            pings.push(ping);
+        }
+        heartbeat.abort();
+
+        assert!(pings[0].last_rtt_ms.is_none());
+        assert!(pings[1].last_rtt_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_aborts_in_flight_request() {
+        let in_flight = InFlightRequests::default();
+        let (dropped_tx, dropped_rx) = tokio::sync::oneshot::channel::<()>();
+
+        let running = in_flight.clone();
+        let request = tokio::spawn(async move {
+            running
+                .run("req-1", async move {
+                    // 任务被中止时 dropped_tx 随之释放
+                    let _guard = dropped_tx;
+                    std::future::pending::<()>().await
+                })
+                .await
+        });
+        while !in_flight.tasks.lock().unwrap().contains_key("req-1") {
+            tokio::task::yield_now().await;
+        }
+
+        assert!(in_flight.cancel("req-1"));
+        assert_eq!(request.await.unwrap(), None);
+        assert!(dropped_rx.await.is_err());
+        assert!(in_flight.tasks.lock().unwrap().is_empty());
+        assert!(!in_flight.cancel("req-1"));
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        for _ in 0..100 {
+            let delay = with_jitter(Duration::from_secs(10));
+            assert!(delay >= Duration::from_secs(8) && delay <= Duration::from_secs(12));
+        }
+    }
+}
@@ -0,0 +1,195 @@
+/// 通知死信队列
+///
+/// 连接断开期间发送失败的通知（例如 vm_operation_completed）会被持久化到本地文件，
+/// 重新连接并注册成功后按原顺序重放，保证 Server 最终能获知操作的真实结果。
+
+use common::ws_rpc::{MessageType, RpcMessage};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+/// 无需进入死信队列的通知（重连后会重新发送最新数据）
+const VOLATILE_METHODS: &[&str] = &["heartbeat", "node_resource_info"];
+
+/// 死信队列最多保留的通知数量，超出后丢弃最旧的记录
+const MAX_DEAD_LETTERS: usize = 10000;
+
+/// 持久化死信队列（JSON Lines 格式，每行一条 RpcMessage）
+pub struct DeadLetterQueue {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl DeadLetterQueue {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// 判断消息是否需要进入死信队列
+    pub fn should_persist(msg: &RpcMessage) -> bool {
+        msg.message_type == MessageType::Notification
+            && !msg
+                .method
+                .as_deref()
+                .map(|m| VOLATILE_METHODS.contains(&m))
+                .unwrap_or(false)
+    }
+
+    /// 写入一条发送失败的通知
+    pub fn push(&self, msg: &RpcMessage) {
+        if !Self::should_persist(msg) {
+            return;
+        }
+
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Err(e) = self.append(msg) {
+            error!(
+                "写入死信队列失败，通知将丢失: method={:?}, id={}, error={}",
+                msg.method, msg.id, e
+            );
+            return;
+        }
+
+        warn!("通知发送失败，已写入死信队列: method={:?}, id={}", msg.method, msg.id);
+    }
+
+    /// 取出所有待重放的通知并清空队列
+    pub fn drain(&self) -> Vec<RpcMessage> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        let file = match fs::File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+            Err(e) => {
+                error!("读取死信队列失败: path={}, error={}", self.path.display(), e);
+                return Vec::new();
+            }
+        };
+
+        let mut messages = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = match line {
+                Ok(l) => l,
+                Err(e) => {
+                    warn!("读取死信记录失败: {}", e);
+                    continue;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match RpcMessage::from_json(&line) {
+                Ok(msg) => messages.push(msg),
+                Err(e) => warn!("解析死信记录失败，已跳过: {}", e),
+            }
+        }
+
+        if let Err(e) = fs::remove_file(&self.path) {
+            error!("清空死信队列失败: path={}, error={}", self.path.display(), e);
+        }
+
+        if messages.len() > MAX_DEAD_LETTERS {
+            let dropped = messages.len() - MAX_DEAD_LETTERS;
+            warn!("死信队列超过上限，丢弃最旧的 {} 条通知", dropped);
+            messages.drain(..dropped);
+        }
+
+        messages
+    }
+
+    fn append(&self, msg: &RpcMessage) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let json = msg
+            .to_json()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", json)?;
+        file.sync_data()
+    }
+}
+
+/// 通知发送器
+///
+/// 包装连接的消息通道，发送失败时自动将通知写入死信队列
+#[derive(Clone)]
+pub struct NotificationSender {
+    sender: mpsc::UnboundedSender<RpcMessage>,
+    dead_letters: Arc<DeadLetterQueue>,
+}
+
+impl NotificationSender {
+    pub fn new(
+        sender: mpsc::UnboundedSender<RpcMessage>,
+        dead_letters: Arc<DeadLetterQueue>,
+    ) -> Self {
+        Self {
+            sender,
+            dead_letters,
+        }
+    }
+
+    /// 发送通知，失败时写入死信队列后返回原错误
+    pub fn send(&self, msg: RpcMessage) -> Result<(), mpsc::error::SendError<RpcMessage>> {
+        self.sender.send(msg).map_err(|e| {
+            self.dead_letters.push(&e.0);
+            e
+        })
+    }
+
+    /// 重放死信队列中的通知
+    pub fn replay_dead_letters(&self) -> usize {
+        let messages = self.dead_letters.drain();
+        if messages.is_empty() {
+            return 0;
+        }
+
+        info!("开始重放死信队列: {} 条通知", messages.len());
+        let mut replayed = 0;
+        for msg in messages {
+            // 发送失败会重新写回死信队列，等待下次重连
+            if self.send(msg).is_ok() {
+                replayed += 1;
+            }
+        }
+        replayed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_and_drain() {
+        let path = std::env::temp_dir().join(format!("dead_letters_{}.jsonl", uuid::Uuid::new_v4()));
+        let queue = DeadLetterQueue::new(&path);
+
+        queue.push(&RpcMessage::notification("heartbeat", serde_json::json!({})));
+        queue.push(&RpcMessage::notification(
+            "vm_operation_completed",
+            serde_json::json!({ "vm_id": "vm-1", "success": true }),
+        ));
+
+        // 心跳不进入死信队列
+        let messages = queue.drain();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].method.as_deref(), Some("vm_operation_completed"));
+
+        // drain 后队列已清空
+        assert!(queue.drain().is_empty());
+    }
+}
@@ -3,30 +3,49 @@ use common::ws_rpc::types::*;
 ///
 /// 注册和调度 Agent 端的 RPC 方法处理器
 use common::ws_rpc::{RpcError, RpcErrorCode, RpcMessage};
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::mpsc;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::hypervisor::{DiskBusType, DiskDeviceType, HypervisorManager};
+use crate::config::IpConflictCheck;
+use crate::hypervisor::{DiskBusType, DiskDeviceType, Hypervisor};
 use crate::network::NetworkManager;
+use crate::node::NodeManager;
+use crate::storage::driver::{ProgressFn, StorageProgress, VolumeSource};
+use crate::storage::encryption::{resolve_passphrase, Passphrase};
 use crate::storage::StorageManager;
 use crate::ws::client::WsClient;
+use crate::ws::dead_letter::NotificationSender;
+
+/// 单次读取导出副本的最大字节数，避免单条 RPC 消息过大
+const MAX_EXPORT_READ_BYTES: u64 = 4 * 1024 * 1024;
+
+/// 热迁移期间查询迁移作业进度的间隔
+const MIGRATION_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
 
 /// RPC 处理器注册表
 pub struct RpcHandlerRegistry {
-    hypervisor: Arc<HypervisorManager>,
+    hypervisor: Arc<dyn Hypervisor>,
     storage: Arc<StorageManager>,
     network: Arc<NetworkManager>,
-    /// 通知发送器，用于向 Server 发送通知
-    notification_sender: Option<mpsc::UnboundedSender<RpcMessage>>,
+    /// 通知发送器，用于向 Server 发送通知（发送失败时写入死信队列）
+    notification_sender: Option<NotificationSender>,
     /// WebSocket 客户端引用，用于主动调用 Server RPC
     ws_client: Option<Arc<WsClient>>,
+    /// 虚拟机启动前的 IP 冲突检测策略
+    ip_conflict_check: IpConflictCheck,
+    /// Server 未下发 VLAN ID 时是否从 Bridge 名称推断
+    vlan_inference: bool,
+    /// 加密存储卷的密钥文件目录
+    encryption_key_dir: PathBuf,
+    /// 节点信息管理器，用于按需重新检测节点能力
+    node_manager: Option<NodeManager>,
 }
 
 impl RpcHandlerRegistry {
     /// 创建新的处理器注册表
     pub fn new(
-        hypervisor: Arc<HypervisorManager>,
+        hypervisor: Arc<dyn Hypervisor>,
         storage: Arc<StorageManager>,
         network: Arc<NetworkManager>,
     ) -> Self {
@@ -36,11 +55,20 @@ impl RpcHandlerRegistry {
             network,
             notification_sender: None,
             ws_client: None,
+            ip_conflict_check: IpConflictCheck::default(),
+            vlan_inference: true,
+            encryption_key_dir: PathBuf::from("/etc/easy-vm-cloud/keys"),
+            node_manager: None,
         }
     }
 
+    /// 获取虚拟化后端
+    pub fn hypervisor(&self) -> Arc<dyn Hypervisor> {
+        self.hypervisor.clone()
+    }
+
     /// 设置通知发送器
-    pub fn set_notification_sender(&mut self, sender: mpsc::UnboundedSender<RpcMessage>) {
+    pub fn set_notification_sender(&mut self, sender: NotificationSender) {
         self.notification_sender = Some(sender);
     }
 
@@ -49,6 +77,32 @@ impl RpcHandlerRegistry {
         self.ws_client = Some(client);
     }
 
+    /// 设置 IP 冲突检测策略
+    pub fn set_ip_conflict_check(&mut self, check: IpConflictCheck) {
+        self.ip_conflict_check = check;
+    }
+
+    /// 设置是否允许从 Bridge 名称推断 VLAN ID
+    pub fn set_vlan_inference(&mut self, enabled: bool) {
+        self.vlan_inference = enabled;
+    }
+
+    /// 设置加密存储卷的密钥文件目录
+    pub fn set_encryption_key_dir(&mut self, dir: PathBuf) {
+        self.encryption_key_dir = dir;
+    }
+
+    /// 设置节点信息管理器
+    pub fn set_node_manager(&mut self, node_manager: NodeManager) {
+        self.node_manager = Some(node_manager);
+    }
+
+    /// 取得加密存储卷的口令
+    fn volume_passphrase(&self, encryption: &VolumeEncryption) -> Result<Passphrase, RpcError> {
+        resolve_passphrase(encryption, &self.encryption_key_dir)
+            .map_err(|e| RpcError::invalid_params(e.to_string()))
+    }
+
     /// 确保存储池已注册，如果未注册则从 Server 获取信息并注册
     async fn ensure_storage_pool_registered(&self, pool_id: &str) -> Result<(), RpcError> {
         // 检查存储池是否已注册
@@ -140,14 +194,33 @@ impl RpcHandlerRegistry {
         let result = match method.as_str() {
             // 节点信息
             "get_node_info" => self.handle_get_node_info(payload).await,
+            "drain_node" => self.handle_drain_node(payload).await,
+            "refresh_capabilities" => self.handle_refresh_capabilities(payload).await,
+            "get_restore_state" => self.handle_get_restore_state(payload).await,
+            "get_vm_state" => self.handle_get_vm_state(payload).await,
+            "list_vms" => self.handle_list_vms(payload).await,
+            "get_vm_stats" => self.handle_get_vm_stats(payload).await,
+            "pause_vm" => self.handle_pause_vm(payload).await,
+            "resume_vm" => self.handle_resume_vm(payload).await,
+            "resize_vm_cpu" => self.handle_resize_vm_cpu(payload).await,
+            "resize_vm_memory" => self.handle_resize_vm_memory(payload).await,
+            "abort_migration" => self.handle_abort_migration(payload).await,
+            "get_migration_speed" => self.handle_get_migration_speed(payload).await,
 
             // 存储管理
-            "create_volume" => self.handle_create_volume(payload).await,
+            "create_volume" => self.handle_create_volume(&msg.id, payload).await,
             "delete_volume" => self.handle_delete_volume(payload).await,
             "resize_volume" => self.handle_resize_volume(payload).await,
             "clone_volume" => self.handle_clone_volume(payload).await,
+            "import_volume" => self.handle_import_volume(payload).await,
+            "get_pool_usage" => self.handle_get_pool_usage(payload).await,
             "get_volume_info" => self.handle_get_volume_info(payload).await,
             "list_volumes" => self.handle_list_volumes(payload).await,
+            "list_volume_snapshots" => self.handle_list_volume_snapshots(payload).await,
+            "list_orphaned_volumes" => self.handle_list_orphaned_volumes(payload).await,
+            "delete_orphaned_volumes" => self.handle_delete_orphaned_volumes(payload).await,
+            "prepare_volume_export" => self.handle_prepare_volume_export(payload).await,
+            "read_volume_export" => self.handle_read_volume_export(payload).await,
 
             // 网络管理
             "create_network" => self.handle_create_network(payload).await,
@@ -158,10 +231,21 @@ impl RpcHandlerRegistry {
             // 虚拟机存储卷管理
             "attach_volume" => self.handle_attach_volume(payload).await,
             "detach_volume" => self.handle_detach_volume(payload).await,
+            "set_volume_iotune" => self.handle_set_volume_iotune(payload).await,
+
+            // 虚拟机网卡管理
+            "set_nic_bandwidth" => self.handle_set_nic_bandwidth(payload).await,
+            "apply_security_group" => self.handle_apply_security_group(payload).await,
 
             // 虚拟机迁移
             "migrate_vm" => self.handle_migrate_vm(payload).await,
 
+            // 客户机命令执行（输出通过 Stream 消息推送）
+            "guest_exec" => self.handle_guest_exec(&msg.id, payload).await,
+            "get_guest_network" => self.handle_get_guest_network(payload).await,
+            "get_domain_xml" => self.handle_get_domain_xml(payload).await,
+            "set_console_password" => self.handle_set_console_password(payload).await,
+
             // 异步卷操作通过通知
             _ => {
                 return RpcMessage::error_response(
@@ -200,6 +284,9 @@ impl RpcHandlerRegistry {
             "create_snapshot_async" => self.handle_create_snapshot_async_internal(payload).await,
             "delete_snapshot_async" => self.handle_delete_snapshot_async_internal(payload).await,
             "restore_snapshot_async" => self.handle_restore_snapshot_async_internal(payload).await,
+            "cleanup_vm_async" => self.handle_cleanup_vm_async_internal(payload).await,
+            "reload_dhcp" => self.handle_reload_dhcp_internal(payload).await,
+            "stop_dhcp" => self.handle_stop_dhcp_internal(payload).await,
             _ => {
                 debug!("未知的异步通知方法: {}", method);
                 Ok(())
@@ -234,6 +321,318 @@ impl RpcHandlerRegistry {
         serde_json::to_value(&node_info).map_err(|e| RpcError::serialization_error(e))
     }
 
+    /// 重新检测节点能力与资源信息（升级 QEMU、安装 swtpm 等之后由 Server 按需触发）
+    async fn handle_refresh_capabilities(
+        &self,
+        _payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let manager = self.node_manager.clone().ok_or_else(|| {
+            RpcError::new(RpcErrorCode::InternalError, "节点管理器未初始化")
+        })?;
+
+        // 资源采集需要间隔采样 CPU 利用率，放在阻塞线程中执行
+        let resource_info = tokio::task::spawn_blocking(move || {
+            manager.get_system_resource_info().map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| RpcError::new(RpcErrorCode::InternalError, format!("检测节点能力任务异常: {}", e)))?
+        .map_err(|e| RpcError::new(RpcErrorCode::InternalError, format!("检测节点能力失败: {}", e)))?;
+
+        info!(
+            "已重新检测节点能力: hypervisor={:?}, swtpm={:?}, ovs={:?}, architectures={:?}",
+            resource_info.hypervisor_version,
+            resource_info.has_swtpm,
+            resource_info.has_ovs,
+            resource_info.supported_architectures
+        );
+
+        serde_json::to_value(&resource_info).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 排空节点：按策略挂起或关闭所有运行中的虚拟机
+    ///
+    /// 单台虚拟机失败不会中断整体流程，结果逐台返回
+    async fn handle_drain_node(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: DrainNodeRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        info!("排空节点，策略: {}", req.policy.as_str());
+
+        let active_vms = self.hypervisor.list_active_vms().await.map_err(|e| {
+            RpcError::new(RpcErrorCode::InternalError, format!("列出虚拟机失败: {}", e))
+        })?;
+
+        let mut vms = Vec::with_capacity(active_vms.len());
+        for (vm_id, name) in active_vms {
+            let (action, result) = match req.policy {
+                HostShutdownPolicy::Suspend => {
+                    ("suspended", self.hypervisor.managed_save_vm(&vm_id).await)
+                }
+                HostShutdownPolicy::Shutdown => {
+                    ("shutdown", self.hypervisor.stop_vm(&vm_id, false).await)
+                }
+            };
+
+            let drained = match result {
+                Ok(()) => DrainedVm {
+                    vm_id,
+                    name,
+                    action: action.to_string(),
+                    error: None,
+                },
+                Err(e) => {
+                    warn!("排空虚拟机 {} 失败: {}", vm_id, e);
+                    DrainedVm {
+                        vm_id,
+                        name,
+                        action: "failed".to_string(),
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+            vms.push(drained);
+        }
+
+        info!("节点排空完成，共处理 {} 台虚拟机", vms.len());
+
+        let response = DrainNodeResponse {
+            policy: req.policy,
+            vms,
+        };
+
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 查询节点上虚拟机的恢复状态
+    async fn handle_get_restore_state(
+        &self,
+        _payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let states = self.hypervisor.list_restore_states().await.map_err(|e| {
+            RpcError::new(RpcErrorCode::InternalError, format!("查询恢复状态失败: {}", e))
+        })?;
+
+        let response = GetRestoreStateResponse {
+            vms: states
+                .into_iter()
+                .map(|(vm_id, name, has_managed_save)| VmRestoreState {
+                    vm_id,
+                    name,
+                    has_managed_save,
+                })
+                .collect(),
+        };
+
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    async fn handle_get_vm_state(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: GetVmStateRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        let state = self.hypervisor.get_vm_state(&req.vm_id).await.map_err(|e| {
+            RpcError::new(RpcErrorCode::InternalError, format!("查询虚拟机状态失败: {}", e))
+        })?;
+
+        let response = match state {
+            Some(state) => GetVmStateResponse {
+                vm_id: req.vm_id,
+                exists: true,
+                state: state.state,
+                vcpu: state.vcpu,
+                memory_mb: state.memory_mb,
+                max_memory_mb: state.max_memory_mb,
+                uptime_secs: state.uptime_secs,
+            },
+            None => GetVmStateResponse {
+                vm_id: req.vm_id,
+                exists: false,
+                state: "shutoff".to_string(),
+                vcpu: 0,
+                memory_mb: 0,
+                max_memory_mb: 0,
+                uptime_secs: None,
+            },
+        };
+
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 列出节点上实际定义的虚拟机，供 Server 对账数据库中的虚拟机状态
+    async fn handle_list_vms(
+        &self,
+        _payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let vms = self.hypervisor.list_vms().await.map_err(|e| {
+            RpcError::new(RpcErrorCode::InternalError, format!("列出虚拟机失败: {}", e))
+        })?;
+
+        let response = ListVmsResponse {
+            vms: vms
+                .into_iter()
+                .map(|vm| VmInfo {
+                    vm_id: vm.id.clone(),
+                    uuid: vm.id,
+                    name: vm.name,
+                    state: vm.state,
+                    vcpu: vm.vcpu,
+                    memory_mb: vm.memory_mb,
+                    disks: Vec::new(),
+                    networks: Vec::new(),
+                    usage: None,
+                })
+                .collect(),
+        };
+
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 读取虚拟机运行时统计
+    async fn handle_get_vm_stats(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: GetVmStatsRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        let stats = self.hypervisor.get_vm_stats(&req.vm_id).await.map_err(|e| match e {
+            common::Error::NotFound(msg) => RpcError::new(RpcErrorCode::VmNotFound, msg),
+            e => RpcError::new(RpcErrorCode::InternalError, format!("读取虚拟机统计失败: {}", e)),
+        })?;
+
+        serde_json::to_value(&stats).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 暂停虚拟机，操作在毫秒级完成，直接同步返回结果
+    async fn handle_pause_vm(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: VmOperationRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        self.hypervisor.pause_vm(&req.vm_id).await.map_err(|e| {
+            RpcError::new(RpcErrorCode::VmOperationFailed, format!("暂停虚拟机失败: {}", e))
+        })?;
+
+        let response = VmOperationResponse {
+            success: true,
+            message: "虚拟机已暂停".to_string(),
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 恢复已暂停的虚拟机
+    async fn handle_resume_vm(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: VmOperationRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        self.hypervisor.resume_vm(&req.vm_id).await.map_err(|e| {
+            RpcError::new(RpcErrorCode::VmOperationFailed, format!("恢复虚拟机失败: {}", e))
+        })?;
+
+        let response = VmOperationResponse {
+            success: true,
+            message: "虚拟机已恢复运行".to_string(),
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 调整虚拟机 vCPU 数量
+    async fn handle_resize_vm_cpu(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: ResizeVmCpuRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+        if req.vcpu == 0 {
+            return Err(RpcError::invalid_params("vCPU 数量必须大于 0"));
+        }
+
+        self.hypervisor
+            .set_vcpus(&req.vm_id, req.vcpu, req.live)
+            .await
+            .map_err(|e| RpcError::new(RpcErrorCode::VmOperationFailed, format!("调整 vCPU 失败: {}", e)))?;
+
+        let response = VmOperationResponse {
+            success: true,
+            message: format!("vCPU 已调整为 {}", req.vcpu),
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 调整虚拟机内存
+    async fn handle_resize_vm_memory(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: ResizeVmMemoryRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+        if req.memory_mb == 0 {
+            return Err(RpcError::invalid_params("内存必须大于 0"));
+        }
+
+        self.hypervisor
+            .set_memory(&req.vm_id, req.memory_mb, req.live)
+            .await
+            .map_err(|e| RpcError::new(RpcErrorCode::VmOperationFailed, format!("调整内存失败: {}", e)))?;
+
+        let response = VmOperationResponse {
+            success: true,
+            message: format!("内存已调整为 {}MiB", req.memory_mb),
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 取消虚拟机迁移
+    ///
+    /// 只负责中止 libvirt 作业，迁移结果仍由 migrate_vm 的完成通知上报
+    async fn handle_abort_migration(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: VmOperationRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        self.hypervisor.abort_migration(&req.vm_id).await.map_err(|e| {
+            RpcError::new(RpcErrorCode::VmOperationFailed, format!("取消迁移失败: {}", e))
+        })?;
+
+        let response = VmOperationResponse {
+            success: true,
+            message: "已取消迁移".to_string(),
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 查询虚拟机当前的迁移带宽上限
+    async fn handle_get_migration_speed(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: VmOperationRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        let max_mbps = self.hypervisor.get_migration_speed(&req.vm_id).await.map_err(|e| {
+            RpcError::new(RpcErrorCode::VmOperationFailed, format!("查询迁移带宽上限失败: {}", e))
+        })?;
+
+        let response = MigrationSpeedResponse {
+            vm_id: req.vm_id,
+            max_mbps,
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
     /// 处理异步启动虚拟机（内部方法，用于通知处理）
     async fn handle_start_vm_async_internal(
         &self,
@@ -246,6 +645,7 @@ impl RpcHandlerRegistry {
             .get("vm_id")
             .and_then(|v| v.as_str())
             .ok_or_else(|| RpcError::invalid_params("缺少 vm_id 参数".to_string()))?;
+        let task_id = req.get("task_id").and_then(|v| v.as_str()).map(|s| s.to_string());
 
         let name = req
             .get("name")
@@ -267,7 +667,74 @@ impl RpcHandlerRegistry {
             .and_then(|v| v.as_str())
             .unwrap_or("linux");
 
-        info!("异步启动虚拟机: vm_id={}, name={}", vm_id, name);
+        let firmware = req
+            .get("firmware")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        let cloud_init = req
+            .get("cloud_init")
+            .and_then(|v| serde_json::from_value(v.clone()).ok());
+
+        let tpm = req.get("tpm").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let boot_menu = req.get("boot_menu").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let cpu_pinning: Option<common::ws_rpc::CpuPinning> = req
+            .get("cpu_pinning")
+            .and_then(|v| serde_json::from_value(v.clone()).ok());
+
+        let numa: Option<common::ws_rpc::NumaConfig> = req
+            .get("numa")
+            .and_then(|v| serde_json::from_value(v.clone()).ok());
+
+        let raw_xml_override = req
+            .get("raw_xml_override")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let arch = req
+            .get("arch")
+            .and_then(|v| v.as_str())
+            .unwrap_or(crate::hypervisor::DEFAULT_ARCH)
+            .to_string();
+
+        let machine_type = req
+            .get("machine_type")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let emulator = req
+            .get("emulator")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let graphics = req
+            .get("graphics")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        let console_password = req
+            .get("console_password")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        // 没有检测到任何模拟器时（例如未安装 QEMU 的测试环境）不做限制，交由 libvirt 报错
+        let architectures = crate::node::supported_architectures();
+        if !architectures.is_empty() && !architectures.contains(&arch) {
+            return Err(RpcError::invalid_params(format!(
+                "节点不支持架构 {}（支持: {}）",
+                arch,
+                architectures.join(", ")
+            )));
+        }
+
+        let safe_mode = req
+            .get("safe_mode")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        info!("异步启动虚拟机: vm_id={}, name={}, safe_mode={}", vm_id, name, safe_mode);
 
         // 解析磁盘配置
         let mut volumes = Vec::new();
@@ -301,12 +768,59 @@ impl RpcHandlerRegistry {
             }
         }
 
+        // 构建虚拟机配置
+        let mut config = crate::hypervisor::VMConfig {
+            name: name.to_string(),
+            uuid: vm_id.to_string(),
+            vcpu: vcpu as u32,
+            memory_mb: memory_mb as u64,
+            os_type: os_type.to_string(),
+            volumes,
+            networks,
+            firmware,
+            cloud_init,
+            tpm,
+            safe_mode: false,
+            boot_menu,
+            cpu_pinning,
+            numa,
+            raw_xml_override,
+            arch,
+            machine_type,
+            emulator,
+            graphics,
+            console_password,
+        };
+        if safe_mode {
+            // 只影响本次生成的 XML，下次正常启动按 Server 下发的完整配置重新定义
+            config = config.into_safe_mode();
+            info!(
+                "虚拟机 {} 以安全模式启动: 磁盘 {} 块，网卡 {} 块",
+                vm_id,
+                config.volumes.len(),
+                config.networks.len()
+            );
+        }
+
+        // Server 按上报的拓扑校验过，这里再按本机实际拓扑拒绝绑定到不存在的 CPU
+        if config.cpu_pinning.is_some() || config.numa.is_some() {
+            common::ws_rpc::validate_cpu_placement(
+                config.max_vcpu(),
+                config.cpu_pinning.as_ref(),
+                config.numa.as_ref(),
+                &crate::node::host_numa_nodes(),
+            )
+            .map_err(RpcError::invalid_params)?;
+        }
+
         // 确保网络配置：检查每个网络对应的 Bridge 是否存在，如果不存在则自动创建
-        for network_config in &networks {
-            if let Err(e) = self
-                .ensure_network_bridge(&network_config.network_name, &network_config.bridge_name)
-                .await
-            {
+        // 同一网络可挂载多块网卡，Bridge 只需检查一次
+        let mut ensured_bridges = std::collections::HashSet::new();
+        for network_config in &config.networks {
+            if !ensured_bridges.insert(network_config.bridge_name.as_str()) {
+                continue;
+            }
+            if let Err(e) = self.ensure_network_bridge(network_config).await {
                 error!(
                     "网络配置失败: network_id={}, bridge={}, error={}",
                     network_config.network_name, network_config.bridge_name, e
@@ -316,21 +830,76 @@ impl RpcHandlerRegistry {
                     format!("网络配置失败: {}", e),
                 ));
             }
+            if let Some(dhcp) = &network_config.dhcp {
+                if let Err(e) = self.network.start_dhcp(dhcp).await {
+                    error!("启动 Bridge {} 的 DHCP 服务失败: {}", network_config.bridge_name, e);
+                    return Err(RpcError::new(
+                        RpcErrorCode::NetworkError,
+                        format!("启动 DHCP 服务失败: {}", e),
+                    ));
+                }
+            }
         }
 
-        // 构建虚拟机配置
-        let config = crate::hypervisor::VMConfig {
-            name: name.to_string(),
-            uuid: vm_id.to_string(),
-            vcpu: vcpu as u32,
-            memory_mb: memory_mb as u64,
-            os_type: os_type.to_string(),
-            volumes,
-            networks,
+        // 加密磁盘的口令定义为 libvirt secret，域 XML 按存储卷 ID 引用
+        for volume in &config.volumes {
+            let Some(encryption) = &volume.encryption else { continue };
+            let passphrase = self.volume_passphrase(encryption)?;
+            if let Err(e) = self
+                .hypervisor
+                .define_volume_secret(&volume.volume_id, &volume.volume_path, &passphrase)
+                .await
+            {
+                error!("定义存储卷 {} 的加密密钥失败: {}", volume.volume_id, e);
+                return Err(RpcError::new(
+                    RpcErrorCode::VmOperationFailed,
+                    format!("定义加密密钥失败: {}", e),
+                ));
+            }
+        }
+
+        // 启动前 ARP 探测分配的 IP 是否已被其他设备占用
+        let ip_conflicts = match req.get("networks") {
+            // 安全模式只挂载第一块网卡，其余网卡的 IP 不会被占用
+            Some(networks_json) if config.safe_mode => {
+                let kept: Vec<_> = networks_json
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .take(1)
+                    .cloned()
+                    .collect();
+                self.detect_ip_conflicts(&serde_json::Value::Array(kept)).await
+            }
+            Some(networks_json) => self.detect_ip_conflicts(networks_json).await,
+            None => Vec::new(),
         };
+        if !ip_conflicts.is_empty() && self.ip_conflict_check == IpConflictCheck::Fail {
+            let message = format!("虚拟机启动失败: 检测到 IP 冲突: {}", ip_conflicts.join("; "));
+            error!("虚拟机 {} {}", vm_id, message);
+
+            if let Some(sender) = &self.notification_sender {
+                let notification = RpcMessage::notification(
+                    "vm_operation_completed",
+                    serde_json::json!({
+                        "vm_id": vm_id,
+                        "operation": "start_vm",
+                        "task_id": task_id,
+                        "success": false,
+                        "message": message,
+                        "ip_conflicts": ip_conflicts
+                    }),
+                );
+                if let Err(e) = sender.send(notification) {
+                    error!("发送失败通知失败: {}", e);
+                }
+            }
+            return Ok(());
+        }
 
         // 异步执行启动操作，不等待结果
         let hypervisor = self.hypervisor.clone();
+        let network = self.network.clone();
         let vm_id = vm_id.to_string();
         let notification_sender = self.notification_sender.clone();
 
@@ -339,6 +908,26 @@ impl RpcHandlerRegistry {
                 Ok(_) => {
                     info!("虚拟机 {} 异步启动成功", vm_id);
 
+                    let started = if config.safe_mode {
+                        "虚拟机已以安全模式启动"
+                    } else {
+                        "虚拟机启动成功"
+                    };
+                    let mut message = if ip_conflicts.is_empty() {
+                        started.to_string()
+                    } else {
+                        format!("{}，但检测到 IP 冲突: {}", started, ip_conflicts.join("; "))
+                    };
+
+                    // tap 设备在启动后才存在，此时再下发安全组规则
+                    let firewall_errors =
+                        apply_security_groups(hypervisor.as_ref(), &network, &vm_id, &config.networks).await;
+                    if !firewall_errors.is_empty() {
+                        message = format!("{}，但安全组规则下发失败: {}", message, firewall_errors.join("; "));
+                    }
+
+                    let vnc = query_vnc_info(hypervisor.as_ref(), &vm_id).await;
+
                     // 发送成功通知到 Server
                     if let Some(sender) = notification_sender {
                         let notification = RpcMessage::notification(
@@ -346,8 +935,11 @@ impl RpcHandlerRegistry {
                             serde_json::json!({
                                 "vm_id": vm_id,
                                 "operation": "start_vm",
+                                "task_id": task_id,
                                 "success": true,
-                                "message": "虚拟机启动成功"
+                                "message": message,
+                                "ip_conflicts": ip_conflicts,
+                                "vnc": vnc
                             }),
                         );
                         if let Err(e) = sender.send(notification) {
@@ -365,6 +957,7 @@ impl RpcHandlerRegistry {
                             serde_json::json!({
                                 "vm_id": vm_id,
                                 "operation": "start_vm",
+                                "task_id": task_id,
                                 "success": false,
                                 "message": format!("虚拟机启动失败: {}", e)
                             }),
@@ -380,6 +973,106 @@ impl RpcHandlerRegistry {
         Ok(())
     }
 
+    /// 对虚拟机各网卡分配的 IP 做 ARP 探测，返回冲突描述列表
+    ///
+    /// 应答来自虚拟机自身 MAC 的不视为冲突；探测本身失败时仅记录日志
+    async fn detect_ip_conflicts(&self, networks_json: &serde_json::Value) -> Vec<String> {
+        let mut conflicts = Vec::new();
+
+        if self.ip_conflict_check == IpConflictCheck::Off {
+            return conflicts;
+        }
+
+        let interfaces = match networks_json.as_array() {
+            Some(interfaces) => interfaces,
+            None => return conflicts,
+        };
+
+        for interface in interfaces {
+            let ip_address = interface.get("ip_address").and_then(|v| v.as_str());
+            let bridge_name = interface.get("bridge_name").and_then(|v| v.as_str());
+            let (ip_address, bridge_name) = match (ip_address, bridge_name) {
+                (Some(ip), Some(bridge)) if !ip.is_empty() && !bridge.is_empty() => (ip, bridge),
+                _ => continue,
+            };
+            // IPv6 地址走 NDP，arping 无法探测
+            if ip_address.contains(':') {
+                continue;
+            }
+            let expected_mac = interface
+                .get("mac_address")
+                .and_then(|v| v.as_str())
+                .map(|mac| mac.to_lowercase());
+
+            match crate::network::arp::probe_ip(bridge_name, ip_address).await {
+                Ok(macs) => {
+                    let foreign: Vec<String> = macs
+                        .into_iter()
+                        .filter(|mac| Some(mac) != expected_mac.as_ref())
+                        .collect();
+                    if !foreign.is_empty() {
+                        warn!(
+                            "IP 冲突: {} 在 {} 上被 {} 应答",
+                            ip_address,
+                            bridge_name,
+                            foreign.join(", ")
+                        );
+                        conflicts.push(format!("{} 已被 {} 占用", ip_address, foreign.join(", ")));
+                    }
+                }
+                Err(e) => {
+                    warn!("ARP 探测 {} 失败，跳过冲突检测: {}", ip_address, e);
+                }
+            }
+        }
+
+        conflicts
+    }
+
+    /// 处理虚拟机删除后的清理（内部方法，用于通知处理）
+    ///
+    /// 删除 Agent 为该虚拟机生成的 cloud-init 配置光盘
+    async fn handle_cleanup_vm_async_internal(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<(), RpcError> {
+        let vm_id = payload
+            .get("vm_id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| RpcError::invalid_params("缺少 vm_id 参数".to_string()))?;
+
+        info!("清理已删除虚拟机 {} 的本地文件", vm_id);
+        crate::hypervisor::cloud_init::remove_config_drive(vm_id).await;
+        Ok(())
+    }
+
+    /// 网络的 IP 分配变化后刷新静态租约（内部方法，用于通知处理）
+    async fn handle_reload_dhcp_internal(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<(), RpcError> {
+        let config: DhcpConfig = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        self.network.reload_dhcp(&config).await.map_err(|e| {
+            RpcError::new(RpcErrorCode::NetworkError, format!("刷新 DHCP 租约失败: {}", e))
+        })
+    }
+
+    /// 网络关闭 DHCP 后停止本节点上的 dnsmasq（内部方法，用于通知处理）
+    async fn handle_stop_dhcp_internal(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<(), RpcError> {
+        let req: StopDhcpRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        info!("停止网络 {} 的 DHCP 服务", req.network_id);
+        self.network.stop_dhcp(&req.bridge_name).await.map_err(|e| {
+            RpcError::new(RpcErrorCode::NetworkError, format!("停止 DHCP 服务失败: {}", e))
+        })
+    }
+
     /// 处理异步停止虚拟机（内部方法，用于通知处理）
     pub async fn handle_stop_vm_async_internal(
         &self,
@@ -394,6 +1087,7 @@ impl RpcHandlerRegistry {
         let hypervisor = self.hypervisor.clone();
         let vm_id = req.vm_id.clone();
         let force = req.force;
+        let task_id = req.task_id.clone();
         let notification_sender = self.notification_sender.clone();
 
         tokio::spawn(async move {
@@ -408,6 +1102,7 @@ impl RpcHandlerRegistry {
                             serde_json::json!({
                                 "vm_id": vm_id,
                                 "operation": "stop_vm",
+                                "task_id": task_id,
                                 "success": true,
                                 "message": "虚拟机停止成功"
                             }),
@@ -427,6 +1122,7 @@ impl RpcHandlerRegistry {
                             serde_json::json!({
                                 "vm_id": vm_id,
                                 "operation": "stop_vm",
+                                "task_id": task_id,
                                 "success": false,
                                 "message": format!("虚拟机停止失败: {}", e)
                             }),
@@ -457,6 +1153,7 @@ impl RpcHandlerRegistry {
             .ok_or_else(|| RpcError::invalid_params("缺少 vm_id 参数".to_string()))?;
 
         let force = req.get("force").and_then(|v| v.as_bool()).unwrap_or(false);
+        let task_id = req.get("task_id").and_then(|v| v.as_str()).map(|s| s.to_string());
 
         info!("异步重启虚拟机: vm_id={}, force={}", vm_id, force);
 
@@ -479,14 +1176,18 @@ impl RpcHandlerRegistry {
                     match hypervisor.start_vm(&vm_id_string).await {
                         Ok(_) => {
                             info!("虚拟机 {} 异步重启成功", vm_id_string);
+                            // QEMU 进程重新启动后 VNC 端口可能变化
+                            let vnc = query_vnc_info(hypervisor.as_ref(), &vm_id_string).await;
                             if let Some(sender) = notification_sender {
                                 let notification = RpcMessage::notification(
                                     "vm_operation_completed",
                                     serde_json::json!({
                                         "vm_id": vm_id_string,
                                         "operation": "restart_vm",
+                                        "task_id": task_id,
                                         "success": true,
-                                        "message": "虚拟机重启成功"
+                                        "message": "虚拟机重启成功",
+                                        "vnc": vnc
                                     }),
                                 );
                                 if let Err(e) = sender.send(notification) {
@@ -502,6 +1203,7 @@ impl RpcHandlerRegistry {
                                     serde_json::json!({
                                         "vm_id": vm_id_string,
                                         "operation": "restart_vm",
+                                        "task_id": task_id,
                                         "success": false,
                                         "message": format!("虚拟机重启失败(启动阶段): {}", e)
                                     }),
@@ -521,6 +1223,7 @@ impl RpcHandlerRegistry {
                             serde_json::json!({
                                 "vm_id": vm_id_string,
                                 "operation": "restart_vm",
+                                "task_id": task_id,
                                 "success": false,
                                 "message": format!("虚拟机重启失败(停止阶段): {}", e)
                             }),
@@ -542,6 +1245,7 @@ impl RpcHandlerRegistry {
 
     async fn handle_create_volume(
         &self,
+        request_id: &str,
         payload: serde_json::Value,
     ) -> Result<serde_json::Value, RpcError> {
         let req: CreateVolumeRequest = serde_json::from_value(payload)
@@ -549,6 +1253,25 @@ impl RpcHandlerRegistry {
 
         info!("创建存储卷: {} (ID: {})", req.name, req.volume_id);
 
+        let checksum = req
+            .checksum
+            .as_deref()
+            .map(str::parse::<VolumeChecksum>)
+            .transpose()
+            .map_err(RpcError::invalid_params)?;
+        let source = req.source.clone().map(|url| VolumeSource {
+            url,
+            checksum,
+            headers: req.source_headers.clone().unwrap_or_default(),
+            auth: req.source_auth.clone(),
+        });
+
+        let passphrase = req
+            .encryption
+            .as_ref()
+            .map(|encryption| self.volume_passphrase(encryption))
+            .transpose()?;
+
         // 使用请求中的存储池ID
         let pool_id = &req.pool_id;
 
@@ -558,6 +1281,27 @@ impl RpcHandlerRegistry {
             return Err(e);
         }
 
+        // 从 URL 创建时下载和格式转换耗时较长，进度以流式消息推送给 Server
+        let progress = match (&req.source, &self.ws_client) {
+            (Some(_), Some(client)) => match client.open_stream(request_id).await {
+                Ok(stream) => {
+                    let volume_id = req.volume_id.clone();
+                    let progress: ProgressFn = Arc::new(move |progress: StorageProgress| {
+                        let frame = volume_create_progress(&volume_id, progress);
+                        if let Err(e) = stream.send(&frame) {
+                            debug!("推送存储卷创建进度失败: {}", e);
+                        }
+                    });
+                    Some(progress)
+                }
+                Err(e) => {
+                    warn!("无法推送存储卷创建进度: {}", e);
+                    None
+                }
+            },
+            _ => None,
+        };
+
         match self
             .storage
             .create_volume(
@@ -566,7 +1310,11 @@ impl RpcHandlerRegistry {
                 &req.name,
                 req.size_gb,
                 &req.format,
-                req.source.as_deref(), // 传递source参数到存储层
+                source.as_ref(), // 传递source参数到存储层
+                req.compress,
+                req.backing_volume_id.as_deref(),
+                passphrase.as_ref(),
+                progress,
             )
             .await
         {
@@ -580,10 +1328,8 @@ impl RpcHandlerRegistry {
             }
             Err(e) => {
                 error!("创建存储卷失败: {}", e);
-                Err(RpcError::new(
-                    RpcErrorCode::VolumeCreateFailed,
-                    format!("创建存储卷失败: {}", e),
-                ))
+                let code = storage_error_code(&e, RpcErrorCode::VolumeCreateFailed);
+                Err(RpcError::new(code, format!("创建存储卷失败: {}", e)))
             }
         }
     }
@@ -603,39 +1349,151 @@ impl RpcHandlerRegistry {
             return Err(e);
         }
 
-        match self
-            .storage
-            .delete_volume(&req.pool_id, &req.volume_id)
-            .await
-        {
-            Ok(_) => {
-                let response = DeleteVolumeResponse {
+        match self
+            .storage
+            .delete_volume(&req.pool_id, &req.volume_id)
+            .await
+        {
+            Ok(_) => {
+                let response = DeleteVolumeResponse {
+                    success: true,
+                    message: "存储卷已删除".to_string(),
+                };
+                serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+            }
+            Err(e) => {
+                error!("删除存储卷失败: {}", e);
+                Err(RpcError::new(
+                    storage_error_code(&e, RpcErrorCode::VolumeDeleteFailed),
+                    format!("删除存储卷失败: {}", e),
+                ))
+            }
+        }
+    }
+
+    async fn handle_resize_volume(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: ResizeVolumeRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        info!(
+            "调整存储卷大小: {} -> {} GB",
+            req.volume_id, req.new_size_gb
+        );
+
+        // 确保存储池已注册
+        if let Err(e) = self.ensure_storage_pool_registered(&req.pool_id).await {
+            error!("确保存储池注册失败: {}", e);
+            return Err(e);
+        }
+
+        match self
+            .storage
+            .resize_volume(&req.pool_id, &req.volume_id, req.new_size_gb)
+            .await
+        {
+            Ok(_) => {
+                let response = ResizeVolumeResponse {
+                    success: true,
+                    message: "存储卷大小已调整".to_string(),
+                };
+                serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+            }
+            Err(e) => {
+                error!("调整存储卷大小失败: {}", e);
+                Err(RpcError::new(
+                    storage_error_code(&e, RpcErrorCode::StorageError),
+                    format!("调整存储卷大小失败: {}", e),
+                ))
+            }
+        }
+    }
+
+    async fn handle_clone_volume(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: CloneVolumeRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        info!(
+            "克隆存储卷: {} -> {} (名称: {}, 快照: {:?}, 目标存储池: {:?})",
+            req.source_volume_id, req.target_volume_id, req.target_name, req.snapshot_id, req.target_pool_id
+        );
+
+        // 确保存储池已注册
+        if let Err(e) = self.ensure_storage_pool_registered(&req.pool_id).await {
+            error!("确保存储池注册失败: {}", e);
+            return Err(e);
+        }
+
+        // 目标存储池与源存储池不同时走跨池拷贝，否则保持同池克隆
+        let target_pool_id = req
+            .target_pool_id
+            .as_deref()
+            .filter(|pool_id| *pool_id != req.pool_id);
+        if let Some(target_pool_id) = target_pool_id {
+            if let Err(e) = self.ensure_storage_pool_registered(target_pool_id).await {
+                error!("确保目标存储池注册失败: {}", e);
+                return Err(e);
+            }
+        }
+
+        let result = match target_pool_id {
+            Some(target_pool_id) => {
+                self.storage
+                    .clone_volume_across_pools(
+                        &req.pool_id,
+                        target_pool_id,
+                        &req.source_volume_id,
+                        &req.target_volume_id,
+                        &req.target_name,
+                        req.snapshot_id.as_deref(),
+                    )
+                    .await
+            }
+            None => {
+                self.storage
+                    .clone_volume(
+                        &req.pool_id,
+                        &req.source_volume_id,
+                        &req.target_volume_id,
+                        &req.target_name,
+                        req.snapshot_id.as_deref(),
+                    )
+                    .await
+            }
+        };
+
+        match result {
+            Ok(volume_info) => {
+                let response = CloneVolumeResponse {
                     success: true,
-                    message: "存储卷已删除".to_string(),
+                    message: "存储卷克隆成功".to_string(),
+                    path: Some(volume_info.path),
                 };
-                serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+                Ok(serde_json::to_value(response).unwrap())
             }
             Err(e) => {
-                error!("删除存储卷失败: {}", e);
+                error!("克隆存储卷失败: {}", e);
                 Err(RpcError::new(
-                    RpcErrorCode::VolumeDeleteFailed,
-                    format!("删除存储卷失败: {}", e),
+                    storage_error_code(&e, RpcErrorCode::StorageError),
+                    format!("克隆存储卷失败: {}", e),
                 ))
             }
         }
     }
 
-    async fn handle_resize_volume(
+    async fn handle_import_volume(
         &self,
         payload: serde_json::Value,
     ) -> Result<serde_json::Value, RpcError> {
-        let req: ResizeVolumeRequest = serde_json::from_value(payload)
+        let req: ImportVolumeRequest = serde_json::from_value(payload)
             .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
 
-        info!(
-            "调整存储卷大小: {} -> {} GB",
-            req.volume_id, req.new_size_gb
-        );
+        info!("导入已有镜像: {} -> {} ({:?})", req.path, req.volume_id, req.mode);
 
         // 确保存储池已注册
         if let Err(e) = self.ensure_storage_pool_registered(&req.pool_id).await {
@@ -645,37 +1503,41 @@ impl RpcHandlerRegistry {
 
         match self
             .storage
-            .resize_volume(&req.pool_id, &req.volume_id, req.new_size_gb)
+            .import_volume(&req.pool_id, &req.path, &req.volume_id, req.mode)
             .await
         {
-            Ok(_) => {
-                let response = ResizeVolumeResponse {
+            Ok(imported) => {
+                let response = ImportVolumeResponse {
                     success: true,
-                    message: "存储卷大小已调整".to_string(),
+                    message: "镜像导入成功".to_string(),
+                    path: imported.volume.path,
+                    format: imported.volume.format,
+                    size_gb: imported.volume.size_gb,
+                    source_path: imported.source_path,
                 };
                 serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
             }
             Err(e) => {
-                error!("调整存储卷大小失败: {}", e);
-                Err(RpcError::new(
-                    RpcErrorCode::StorageError,
-                    format!("调整存储卷大小失败: {}", e),
-                ))
+                error!("导入镜像失败: {}", e);
+                let code = match e {
+                    common::Error::InvalidArgument(_)
+                    | common::Error::NotFound(_)
+                    | common::Error::AlreadyExists(_) => RpcErrorCode::InvalidParams,
+                    _ => storage_error_code(&e, RpcErrorCode::VolumeCreateFailed),
+                };
+                Err(RpcError::new(code, format!("导入镜像失败: {}", e)))
             }
         }
     }
 
-    async fn handle_clone_volume(
+    async fn handle_get_pool_usage(
         &self,
         payload: serde_json::Value,
     ) -> Result<serde_json::Value, RpcError> {
-        let req: CloneVolumeRequest = serde_json::from_value(payload)
+        let req: GetPoolUsageRequest = serde_json::from_value(payload)
             .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
 
-        info!(
-            "克隆存储卷: {} -> {} (名称: {})",
-            req.source_volume_id, req.target_volume_id, req.target_name
-        );
+        debug!("查询存储池容量: {}", req.pool_id);
 
         // 确保存储池已注册
         if let Err(e) = self.ensure_storage_pool_registered(&req.pool_id).await {
@@ -683,29 +1545,21 @@ impl RpcHandlerRegistry {
             return Err(e);
         }
 
-        match self
-            .storage
-            .clone_volume(
-                &req.pool_id,
-                &req.source_volume_id,
-                &req.target_volume_id,
-                &req.target_name,
-            )
-            .await
-        {
-            Ok(volume_info) => {
-                let response = CloneVolumeResponse {
-                    success: true,
-                    message: "存储卷克隆成功".to_string(),
-                    path: Some(volume_info.path),
+        match self.storage.pool_usage(&req.pool_id).await {
+            Ok(usage) => {
+                let response = GetPoolUsageResponse {
+                    pool_id: req.pool_id,
+                    total_bytes: usage.total_bytes,
+                    used_bytes: usage.used_bytes,
+                    available_bytes: usage.available_bytes,
                 };
-                Ok(serde_json::to_value(response).unwrap())
+                serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
             }
             Err(e) => {
-                error!("克隆存储卷失败: {}", e);
+                error!("查询存储池容量失败: {}", e);
                 Err(RpcError::new(
-                    RpcErrorCode::StorageError,
-                    format!("克隆存储卷失败: {}", e),
+                    storage_error_code(&e, RpcErrorCode::StorageError),
+                    format!("查询存储池容量失败: {}", e),
                 ))
             }
         }
@@ -791,6 +1645,192 @@ impl RpcHandlerRegistry {
         }
     }
 
+    /// 处理列出孤立卷文件请求
+    async fn handle_list_orphaned_volumes(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: ListOrphanedVolumesRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        info!("扫描存储池孤立文件: {}", req.pool_id);
+
+        self.ensure_storage_pool_registered(&req.pool_id).await?;
+
+        let known: std::collections::HashSet<String> = req.known_volume_ids.into_iter().collect();
+        let orphans = self
+            .storage
+            .list_orphaned_files(&req.pool_id, &known)
+            .await
+            .map_err(|e| {
+                RpcError::new(
+                    storage_error_code(&e, RpcErrorCode::StorageError),
+                    format!("扫描孤立文件失败: {}", e),
+                )
+            })?;
+
+        let response = ListOrphanedVolumesResponse {
+            pool_id: req.pool_id,
+            orphans: orphans
+                .into_iter()
+                .map(|o| OrphanedVolumeFile {
+                    file_name: o.file_name,
+                    path: o.path,
+                    size_bytes: o.size_bytes,
+                    modified_at: o.modified_at,
+                })
+                .collect(),
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 处理删除孤立卷文件请求
+    async fn handle_delete_orphaned_volumes(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: DeleteOrphanedVolumesRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        warn!("删除存储池 {} 的孤立文件: {:?}", req.pool_id, req.file_names);
+
+        self.ensure_storage_pool_registered(&req.pool_id).await?;
+
+        let known: std::collections::HashSet<String> = req.known_volume_ids.into_iter().collect();
+        let deleted = self
+            .storage
+            .delete_orphaned_files(&req.pool_id, &known, &req.file_names)
+            .await
+            .map_err(|e| {
+                RpcError::new(
+                    storage_error_code(&e, RpcErrorCode::StorageError),
+                    format!("删除孤立文件失败: {}", e),
+                )
+            })?;
+
+        let skipped = req
+            .file_names
+            .into_iter()
+            .filter(|name| !deleted.contains(name))
+            .collect();
+
+        let response = DeleteOrphanedVolumesResponse {
+            pool_id: req.pool_id,
+            deleted,
+            skipped,
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    async fn handle_prepare_volume_export(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: PrepareVolumeExportRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        info!("准备存储卷导出: {}", req.volume_id);
+
+        self.ensure_storage_pool_registered(&req.pool_id).await?;
+
+        let export = self
+            .storage
+            .prepare_export(&req.pool_id, &req.volume_id)
+            .await
+            .map_err(|e| {
+                error!("准备存储卷导出失败: {}", e);
+                RpcError::new(
+                    storage_error_code(&e, RpcErrorCode::StorageError),
+                    format!("准备存储卷导出失败: {}", e),
+                )
+            })?;
+
+        let response = PrepareVolumeExportResponse {
+            volume_id: req.volume_id,
+            format: export.format,
+            size_bytes: export.size_bytes,
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    async fn handle_read_volume_export(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        use base64::Engine;
+
+        let req: ReadVolumeExportRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        if req.length == 0 || req.length > MAX_EXPORT_READ_BYTES {
+            return Err(RpcError::invalid_params(format!(
+                "读取长度必须在 1 到 {} 字节之间",
+                MAX_EXPORT_READ_BYTES
+            )));
+        }
+
+        self.ensure_storage_pool_registered(&req.pool_id).await?;
+
+        let data = self
+            .storage
+            .read_export(&req.pool_id, &req.volume_id, req.offset, req.length)
+            .await
+            .map_err(|e| {
+                RpcError::new(RpcErrorCode::StorageError, format!("读取存储卷导出失败: {}", e))
+            })?;
+
+        let response = ReadVolumeExportResponse {
+            offset: req.offset,
+            eof: (data.len() as u64) < req.length,
+            data: base64::engine::general_purpose::STANDARD.encode(&data),
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    async fn handle_list_volume_snapshots(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: ListVolumeSnapshotsRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        info!("列出存储卷快照: {}", req.volume_id);
+
+        // 确保存储池已注册
+        if let Err(e) = self.ensure_storage_pool_registered(&req.pool_id).await {
+            error!("确保存储池注册失败: {}", e);
+            return Err(e);
+        }
+
+        match self
+            .storage
+            .list_snapshots(&req.pool_id, &req.volume_id)
+            .await
+        {
+            Ok(snapshots) => {
+                let response = ListVolumeSnapshotsResponse {
+                    volume_id: req.volume_id,
+                    snapshots: snapshots
+                        .into_iter()
+                        .map(|s| VolumeSnapshotInfo {
+                            name: s.name,
+                            vm_state_size: s.vm_state_size,
+                            created_at: s.created_at,
+                        })
+                        .collect(),
+                };
+                serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+            }
+            Err(e) => {
+                error!("列出存储卷快照失败: {}", e);
+                Err(RpcError::new(
+                    storage_error_code(&e, RpcErrorCode::StorageError),
+                    format!("列出存储卷快照失败: {}", e),
+                ))
+            }
+        }
+    }
+
     // ========================================================================
     // 网络管理处理
     // ========================================================================
@@ -813,6 +1853,7 @@ impl RpcHandlerRegistry {
                 &req.network_type,
                 &req.bridge_name,
                 vlan_id,
+                req.mtu.unwrap_or(crate::network::DEFAULT_MTU),
             )
             .await
         {
@@ -842,9 +1883,18 @@ impl RpcHandlerRegistry {
 
         info!("删除网络: {}", req.network_id);
 
+        let bridge_name = req
+            .bridge_name
+            .clone()
+            .unwrap_or_else(|| self.network.get_bridge_name(req.vlan_id));
         match self
             .network
-            .delete_network(&req.network_id, "bridge", None)
+            .delete_network(
+                &req.network_id,
+                req.network_type.as_deref().unwrap_or("bridge"),
+                &bridge_name,
+                req.vlan_id,
+            )
             .await
         {
             Ok(_) => {
@@ -904,6 +1954,13 @@ impl RpcHandlerRegistry {
 
         info!("从虚拟机分离网络接口: {}", req.vm_id);
 
+        // 网卡仍在虚拟机上时先删除其 tap 设备上的安全组规则
+        if let Ok(Some(tap)) = self.hypervisor.interface_target(&req.vm_id, &req.mac_address).await {
+            if let Err(e) = self.network.remove_security_group(&tap).await {
+                warn!("删除 {} 的安全组规则失败: {}", tap, e);
+            }
+        }
+
         match self
             .network
             .detach_interface(&req.vm_id, &req.mac_address)
@@ -930,73 +1987,61 @@ impl RpcHandlerRegistry {
     ///
     /// 功能：
     /// 1. 检查 Bridge 是否存在
-    /// 2. 如果不存在，从 Bridge 名称推断 VLAN ID 并自动创建网络
+    /// 2. 如果不存在，使用 Server 下发的 VLAN ID 自动创建网络；
+    ///    未下发时按配置从 Bridge 名称推断，或直接报错
     /// 3. 验证 Bridge 是否启动并可用
     async fn ensure_network_bridge(
         &self,
-        network_id: &str,
-        bridge_name: &str,
+        network: &crate::hypervisor::NetworkConfig,
     ) -> Result<(), RpcError> {
+        let network_id = network.network_name.as_str();
+        let bridge_name = network.bridge_name.as_str();
+
         // 检查 Bridge 是否存在
         if !self.network.bridge_exists(bridge_name).await {
             info!("网络 Bridge '{}' 不存在，开始自动创建", bridge_name);
 
-            // 从 bridge_name 推断 VLAN ID（格式：br-vlan100）
-            let vlan_id = if bridge_name.starts_with("br-vlan") {
-                bridge_name
-                    .strip_prefix("br-vlan")
-                    .and_then(|s| s.parse::<u32>().ok())
-            } else {
-                None
-            };
+            let vlan_id = resolve_vlan_id(network.vlan_id, self.vlan_inference, || {
+                self.network.parse_vlan_id(bridge_name)
+            })
+            .map_err(|message| {
+                error!("网络 {} 的 Bridge '{}' 不存在: {}", network_id, bridge_name, message);
+                RpcError::new(
+                    RpcErrorCode::NetworkError,
+                    format!("网络 Bridge '{}' 不存在: {}", bridge_name, message),
+                )
+            })?;
 
-            if let Some(vlan) = vlan_id {
-                // 自动创建 VLAN 网络（包括 Bridge 和 VLAN 子接口）
-                if let Err(e) = self
-                    .network
-                    .create_network(
-                        network_id,
-                        &format!("auto-created-{}", network_id),
-                        "bridge",
-                        bridge_name,
-                        Some(vlan),
-                    )
-                    .await
-                {
-                    error!("自动创建 VLAN 网络失败: {}", e);
-                    return Err(RpcError::new(
-                        RpcErrorCode::NetworkError,
-                        format!("自动创建 VLAN 网络失败: {}", e),
-                    ));
-                }
-                info!(
-                    "成功自动创建 VLAN 网络: network_id={}, bridge={}, vlan={}",
-                    network_id, bridge_name, vlan
-                );
-            } else {
-                // 自动创建无 VLAN 网络（直接使用 Provider 接口）
-                if let Err(e) = self
-                    .network
-                    .create_network(
-                        network_id,
-                        &format!("auto-created-{}", network_id),
-                        "bridge",
-                        bridge_name,
-                        None,
-                    )
-                    .await
-                {
-                    error!("自动创建无 VLAN 网络失败: {}", e);
-                    return Err(RpcError::new(
-                        RpcErrorCode::NetworkError,
-                        format!("自动创建无 VLAN 网络失败: {}", e),
-                    ));
-                }
-                info!(
-                    "成功自动创建无 VLAN 网络: network_id={}, bridge={}",
-                    network_id, bridge_name
-                );
+            let kind = match (network.network_type.as_deref(), vlan_id.is_some()) {
+                (Some("ovs"), true) => "OVS VLAN",
+                (Some("ovs"), false) => "OVS 无 VLAN",
+                (Some("macvlan"), true) => "Macvlan VLAN",
+                (Some("macvlan"), false) => "Macvlan 无 VLAN",
+                (_, true) => "VLAN",
+                (_, false) => "无 VLAN",
+            };
+            if let Err(e) = self
+                .network
+                .create_network(
+                    network_id,
+                    &format!("auto-created-{}", network_id),
+                    network.network_type.as_deref().unwrap_or("bridge"),
+                    bridge_name,
+                    vlan_id,
+                    network.mtu(),
+                )
+                .await
+            {
+                error!("自动创建{}网络失败: {}", kind, e);
+                return Err(RpcError::new(
+                    RpcErrorCode::NetworkError,
+                    format!("自动创建{}网络失败: {}", kind, e),
+                ));
             }
+            info!(
+                "成功自动创建{}网络: network_id={}, bridge={}, vlan={:?}",
+                kind, network_id, bridge_name, vlan_id
+            );
         }
 
         // 检查 Bridge 是否启动并可用
@@ -1052,6 +2097,16 @@ impl RpcHandlerRegistry {
             ));
         }
 
+        if let Some(encryption) = &request.encryption {
+            let passphrase = self.volume_passphrase(encryption)?;
+            self.hypervisor
+                .define_volume_secret(&request.volume_id, &request.volume_path, &passphrase)
+                .await
+                .map_err(|e| {
+                    RpcError::new(RpcErrorCode::VmOperationFailed, format!("定义加密密钥失败: {}", e))
+                })?;
+        }
+
         // 调用虚拟化管理器挂载存储卷
         match self
             .hypervisor
@@ -1062,6 +2117,9 @@ impl RpcHandlerRegistry {
                 request.bus_type,
                 request.device_type,
                 &request.format,
+                request.limits,
+                request.backing.as_ref(),
+                request.encryption.is_some(),
             )
             .await
         {
@@ -1167,6 +2225,88 @@ impl RpcHandlerRegistry {
         }
     }
 
+    /// 调整运行中虚拟机磁盘的 I/O 限速，无需重启即可生效
+    async fn handle_set_volume_iotune(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: SetVolumeIotuneRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        self.hypervisor
+            .set_volume_iotune(&req.vm_id, &req.volume_id, &req.limits)
+            .await
+            .map_err(|e| {
+                RpcError::new(RpcErrorCode::VmOperationFailed, format!("调整磁盘 I/O 限速失败: {}", e))
+            })?;
+
+        let response = VmOperationResponse {
+            success: true,
+            message: "磁盘 I/O 限速已更新".to_string(),
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 调整运行中虚拟机网卡的带宽限速，无需重启即可生效
+    async fn handle_set_nic_bandwidth(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: SetNicBandwidthRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        self.hypervisor
+            .set_interface_bandwidth(&req.vm_id, &req.mac_address, &req.limits)
+            .await
+            .map_err(|e| {
+                RpcError::new(RpcErrorCode::VmOperationFailed, format!("调整网卡带宽限速失败: {}", e))
+            })?;
+
+        let response = VmOperationResponse {
+            success: true,
+            message: "网卡带宽限速已更新".to_string(),
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 将安全组规则下发到运行中虚拟机的网卡，security_group_id 为空时删除规则
+    async fn handle_apply_security_group(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: ApplySecurityGroupRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+        for rule in &req.rules {
+            rule.validate().map_err(RpcError::invalid_params)?;
+        }
+
+        let tap = self
+            .hypervisor
+            .interface_target(&req.vm_id, &req.mac_address)
+            .await
+            .map_err(|e| RpcError::new(RpcErrorCode::VmOperationFailed, format!("查找网卡失败: {}", e)))?
+            .ok_or_else(|| {
+                RpcError::new(
+                    RpcErrorCode::NetworkError,
+                    format!("网卡 {} 未运行，找不到对应的 tap 设备", req.mac_address),
+                )
+            })?;
+
+        let result = match req.security_group_id {
+            Some(_) => self.network.apply_security_group(&tap, &req.rules).await,
+            None => self.network.remove_security_group(&tap).await,
+        };
+        result.map_err(|e| {
+            RpcError::new(RpcErrorCode::NetworkError, format!("下发安全组规则失败: {}", e))
+        })?;
+
+        let response = VmOperationResponse {
+            success: true,
+            message: "安全组规则已更新".to_string(),
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
     /// 处理异步挂载存储卷（内部方法，用于通知处理）
     async fn handle_attach_volume_async_internal(
         &self,
@@ -1190,21 +2330,51 @@ impl RpcHandlerRegistry {
             .and_then(|v| v.as_str())
             .ok_or_else(|| RpcError::invalid_params("缺少 volume_path 参数".to_string()))?;
 
-        let bus_type = req
-            .get("bus_type")
-            .and_then(|v| v.as_str())
-            .unwrap_or("virtio");
+        // 严格解析总线与设备类型，未知取值或不支持的组合直接报错，不再静默回退
+        let bus_type: DiskBusType = match req.get("bus_type").and_then(|v| v.as_str()) {
+            Some(value) => value.parse().map_err(RpcError::invalid_params)?,
+            None => DiskBusType::default(),
+        };
 
-        let device_type = req
-            .get("device_type")
-            .and_then(|v| v.as_str())
-            .unwrap_or("disk");
+        let device_type: DiskDeviceType = match req.get("device_type").and_then(|v| v.as_str()) {
+            Some(value) => value.parse().map_err(RpcError::invalid_params)?,
+            None => DiskDeviceType::default(),
+        };
+
+        validate_disk_combination(&bus_type, &device_type).map_err(RpcError::invalid_params)?;
 
         let format = req
             .get("format")
             .and_then(|v| v.as_str())
             .unwrap_or("qcow2");
 
+        let limits: DiskIoLimits = serde_json::from_value(req.clone())
+            .map_err(|e| RpcError::invalid_params(format!("限速参数错误: {}", e)))?;
+
+        let backing: Option<BackingStore> = match req.get("backing") {
+            Some(value) if !value.is_null() => Some(
+                serde_json::from_value(value.clone())
+                    .map_err(|e| RpcError::invalid_params(format!("基础镜像参数错误: {}", e)))?,
+            ),
+            _ => None,
+        };
+
+        let encrypted = match req.get("encryption") {
+            Some(value) if !value.is_null() => {
+                let encryption: VolumeEncryption = serde_json::from_value(value.clone())
+                    .map_err(|e| RpcError::invalid_params(format!("加密参数错误: {}", e)))?;
+                let passphrase = self.volume_passphrase(&encryption)?;
+                self.hypervisor
+                    .define_volume_secret(volume_id, volume_path, &passphrase)
+                    .await
+                    .map_err(|e| {
+                        RpcError::new(RpcErrorCode::VmOperationFailed, format!("定义加密密钥失败: {}", e))
+                    })?;
+                true
+            }
+            _ => false,
+        };
+
         info!("异步挂载存储卷: vm_id={}, volume_id={}", vm_id, volume_id);
 
         // 异步执行挂载操作，不等待结果
@@ -1212,34 +2382,21 @@ impl RpcHandlerRegistry {
         let vm_id = vm_id.to_string();
         let volume_id = volume_id.to_string();
         let volume_path = volume_path.to_string();
-        let bus_type = bus_type.to_string();
-        let device_type = device_type.to_string();
         let format = format.to_string();
         let notification_sender = self.notification_sender.clone();
 
         tokio::spawn(async move {
-            // 转换字符串为枚举类型
-            let bus_type_enum = match bus_type.as_str() {
-                "virtio" => DiskBusType::Virtio,
-                "scsi" => DiskBusType::Scsi,
-                "ide" => DiskBusType::Ide,
-                _ => DiskBusType::Virtio,
-            };
-
-            let device_type_enum = match device_type.as_str() {
-                "disk" => DiskDeviceType::Disk,
-                "cdrom" => DiskDeviceType::Cdrom,
-                _ => DiskDeviceType::Disk,
-            };
-
             match hypervisor
                 .attach_volume(
                     &vm_id,
                     &volume_id,
                     &volume_path,
-                    bus_type_enum,
-                    device_type_enum,
+                    bus_type,
+                    device_type,
                     &format,
+                    limits,
+                    backing.as_ref(),
+                    encrypted,
                 )
                 .await
             {
@@ -1388,6 +2545,12 @@ impl RpcHandlerRegistry {
             .ok_or_else(|| RpcError::invalid_params("缺少 pool_id 参数".to_string()))?
             .to_string();
 
+        // 卷挂载在运行中的虚拟机上时由 Server 传入，用于快照前冻结客户机文件系统
+        let vm_id = payload
+            .get("vm_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
         info!(
             "异步创建快照: snapshot_id={}, volume_id={}, snapshot_name={}",
             snapshot_id, volume_id, snapshot_name
@@ -1405,14 +2568,19 @@ impl RpcHandlerRegistry {
         let snapshot_id_clone = snapshot_id.clone();
         let pool_id_clone = pool_id.clone();
         let volume_id_clone = volume_id.clone();
+        let hypervisor = self.hypervisor.clone();
 
         // 异步执行快照创建
         tokio::spawn(async move {
             // 执行快照创建
-            match storage
-                .create_snapshot(&pool_id_clone, &volume_id_clone, &snapshot_id_clone)
-                .await
-            {
+            let (result, consistency) = snapshot_with_fsfreeze(
+                hypervisor.as_ref(),
+                vm_id.as_deref(),
+                storage.create_snapshot(&pool_id_clone, &volume_id_clone, &snapshot_id_clone),
+            )
+            .await;
+
+            match result {
                 Ok(snapshot_tag) => {
                     info!(
                         "快照 {} 创建成功, snapshot_tag={}",
@@ -1427,7 +2595,8 @@ impl RpcHandlerRegistry {
                                 "snapshot_id": snapshot_id_clone,
                                 "operation": "create_snapshot",
                                 "success": true,
-                                "message": format!("snapshot_tag:{}", snapshot_tag)
+                                "message": format!("snapshot_tag:{}", snapshot_tag),
+                                "consistency": consistency
                             }),
                         );
                         if let Err(e) = sender.send(notification) {
@@ -1661,8 +2830,16 @@ impl RpcHandlerRegistry {
             "冷迁移"
         };
         info!(
-            "开始{}虚拟机: vm_id={}, target_node={}, target_addr={}",
-            migration_type, req.vm_id, req.target_node_id, req.target_node_address
+            "开始{}虚拟机: vm_id={}, target_node={}, target_addr={}, max_bandwidth={:?} MiB/s, max_downtime={:?} ms, fallback={}, live_timeout={:?}s, storage={}",
+            migration_type,
+            req.vm_id,
+            req.target_node_id,
+            req.target_node_address,
+            req.max_bandwidth_mbps,
+            req.max_downtime_ms,
+            req.fallback_policy.as_str(),
+            req.live_timeout_secs,
+            req.storage_mode.as_str()
         );
 
         // 检查虚拟机状态
@@ -1685,12 +2862,38 @@ impl RpcHandlerRegistry {
             });
         }
 
+        // 冷迁移只在源节点取消定义，不传输任何数据
+        if !req.live_migration && req.storage_mode == MigrationStorageMode::CopyStorage {
+            return Err(RpcError {
+                code: RpcErrorCode::InvalidRequest,
+                message: "复制存储迁移仅支持热迁移".to_string(),
+                details: None,
+            });
+        }
+
         // 启动异步迁移任务
         if let Some(ref notification_sender) = self.notification_sender {
             let sender = notification_sender.clone();
             let vm_id_clone = req.vm_id.clone();
-            let target_addr = req.target_node_address.clone();
+            // 旧版本 Server 不下发 URI，沿用按节点地址拼接的 qemu+tcp
+            let target_uri = req
+                .target_uri
+                .clone()
+                .unwrap_or_else(|| format!("qemu+tcp://{}/system", req.target_node_address));
             let is_live = req.live_migration;
+            let storage_mode = req.storage_mode;
+            let options = crate::hypervisor::MigrationOptions {
+                flags: None,
+                storage_mode,
+                max_downtime_ms: req.max_downtime_ms,
+                suspend_after: match req.fallback_policy {
+                    MigrationFallbackPolicy::Suspend => {
+                        req.live_timeout_secs.map(std::time::Duration::from_secs)
+                    }
+                    MigrationFallbackPolicy::None => None,
+                },
+            };
+            let max_bandwidth_mbps = req.max_bandwidth_mbps;
             let hypervisor = self.hypervisor.clone();
 
             tokio::spawn(async move {
@@ -1711,14 +2914,10 @@ impl RpcHandlerRegistry {
 
                 if is_live {
                     // 执行热迁移
-                    info!("执行热迁移: vm_id={}, target={}", vm_id_clone, target_addr);
+                    info!("执行热迁移: vm_id={}, target={}", vm_id_clone, target_uri);
 
                     tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
 
-                    // 构建目标 libvirt URI
-                    // 格式: qemu+tcp://target_address/system
-                    let target_uri = format!("qemu+tcp://{}/system", target_addr);
-
                     // 发送迁移中通知
                     let notification = RpcMessage::notification(
                         "vm_migration_progress",
@@ -1726,7 +2925,10 @@ impl RpcHandlerRegistry {
                             "vm_id": vm_id_clone,
                             "stage": "migrating",
                             "progress_percent": 10.0,
-                            "message": "正在执行热迁移...",
+                            "message": match storage_mode {
+                                MigrationStorageMode::SharedStorage => "正在执行热迁移...",
+                                MigrationStorageMode::CopyStorage => "正在执行热迁移（复制存储：全部磁盘数据需经网络传输到目标节点，耗时和带宽占用远高于共享存储迁移，设置的带宽上限同时作用于磁盘复制）...",
+                            },
                             "completed": false
                         }),
                     );
@@ -1734,23 +2936,47 @@ impl RpcHandlerRegistry {
                         error!("发送迁移进度通知失败: {}", e);
                     }
 
-                    // 执行热迁移
-                    match hypervisor
-                        .live_migrate(&vm_id_clone, &target_uri, None)
-                        .await
-                    {
-                        Ok(_) => {
-                            info!("热迁移成功: vm_id={}", vm_id_clone);
-
-                            // 发送完成通知
+                    // libvirt 迁移调用会阻塞到完成，进度在独立任务中轮询
+                    let progress_reporter = tokio::spawn(report_migration_progress(
+                        hypervisor.clone(),
+                        sender.clone(),
+                        vm_id_clone.clone(),
+                        storage_mode,
+                        MIGRATION_PROGRESS_INTERVAL,
+                    ));
+                    // 带宽上限在发起迁移前设置，设置失败时不发起迁移
+                    let speed = match max_bandwidth_mbps {
+                        Some(mbps) => hypervisor
+                            .set_migration_speed(&vm_id_clone, mbps)
+                            .await
+                            .map(|applied| info!("迁移带宽上限: vm_id={}, {} MiB/s", vm_id_clone, applied)),
+                        None => Ok(()),
+                    };
+                    let result = match speed {
+                        Ok(()) => hypervisor.live_migrate(&vm_id_clone, &target_uri, &options).await,
+                        Err(e) => Err(e),
+                    };
+                    progress_reporter.abort();
+
+                    match result {
+                        Ok(mode) => {
+                            info!("热迁移成功: vm_id={}, mode={}", vm_id_clone, mode.as_str());
+
+                            let message = match mode {
+                                MigrationMode::Live => "虚拟机热迁移完成",
+                                MigrationMode::Suspended => "虚拟机迁移完成（热迁移超时，已挂起虚拟机完成迁移）",
+                            };
+
+                            // 发送完成通知，附带实际采用的迁移方式
                             let notification = RpcMessage::notification(
                                 "vm_migration_progress",
                                 serde_json::json!({
                                     "vm_id": vm_id_clone,
                                     "stage": "completed",
                                     "progress_percent": 100.0,
-                                    "message": "虚拟机热迁移完成",
-                                    "completed": true
+                                    "message": message,
+                                    "completed": true,
+                                    "migration_mode": mode
                                 }),
                             );
                             if let Err(e) = sender.send(notification) {
@@ -1846,4 +3072,1025 @@ impl RpcHandlerRegistry {
             "message": format!("虚拟机{}已开始", if req.live_migration { "热迁移" } else { "冷迁移" })
         }))
     }
+
+    /// 查询客户机内的网卡与 IP 地址
+    ///
+    /// 客户机内没有运行 qemu-guest-agent 时返回 GUEST_AGENT_UNAVAILABLE，便于 Server 区分处理
+    async fn handle_get_guest_network(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: GetGuestNetworkRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        let interfaces = self.hypervisor.qga_get_interfaces(&req.vm_id).await.map_err(|e| match e {
+            common::Error::NotFound(msg) => RpcError::new(RpcErrorCode::VmNotFound, msg),
+            common::Error::GuestAgentUnavailable(msg) => {
+                RpcError::new(RpcErrorCode::GuestAgentUnavailable, msg)
+            }
+            e => RpcError::new(RpcErrorCode::VmOperationFailed, format!("查询客户机网络失败: {}", e)),
+        })?;
+
+        let response = GetGuestNetworkResponse {
+            vm_id: req.vm_id,
+            interfaces,
+        };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 读取虚拟机当前的域 XML，用于排查配置
+    async fn handle_get_domain_xml(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: GetDomainXmlRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        let xml = self.hypervisor.get_domain_xml(&req.vm_id).await.map_err(|e| match e {
+            common::Error::NotFound(msg) => RpcError::new(RpcErrorCode::VmNotFound, msg),
+            e => RpcError::new(RpcErrorCode::VmOperationFailed, format!("读取域 XML 失败: {}", e)),
+        })?;
+
+        let response = GetDomainXmlResponse { vm_id: req.vm_id, xml };
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+
+    /// 修改运行中虚拟机的控制台密码（Server 在用户打开控制台时下发一次性密码）
+    async fn handle_set_console_password(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        let req: SetConsolePasswordRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+        if req.password.is_empty() {
+            return Err(RpcError::invalid_params("控制台密码不能为空".to_string()));
+        }
+
+        let valid_to = req
+            .valid_secs
+            .map(|secs| chrono::Utc::now() + chrono::Duration::seconds(secs as i64));
+        self.hypervisor
+            .set_console_password(&req.vm_id, &req.password, valid_to)
+            .await
+            .map_err(|e| match e {
+                common::Error::NotFound(msg) => RpcError::new(RpcErrorCode::VmNotFound, msg),
+                common::Error::InvalidArgument(msg) => RpcError::invalid_params(msg),
+                e => RpcError::new(RpcErrorCode::VmOperationFailed, format!("设置控制台密码失败: {}", e)),
+            })?;
+
+        Ok(serde_json::json!({ "vm_id": req.vm_id, "success": true }))
+    }
+
+    /// 通过 qemu-guest-agent 执行客户机命令
+    ///
+    /// 使用 guest-exec 启动进程后轮询 guest-exec-status，每次拿到输出都以 Stream 消息
+    /// （id 与请求相同）推送给 Server，进程结束或超时后返回最终结果
+    async fn handle_guest_exec(
+        &self,
+        request_id: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError> {
+        /// 状态轮询间隔
+        const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+        let req: GuestExecRequest = serde_json::from_value(payload)
+            .map_err(|e| RpcError::invalid_params(format!("参数错误: {}", e)))?;
+
+        info!("执行客户机命令: vm_id={}, command={}", req.vm_id, req.command);
+
+        let pid = self
+            .hypervisor
+            .guest_exec(&req.vm_id, &req.command, &req.args)
+            .await
+            .map_err(|e| {
+                error!("启动客户机命令失败: {}", e);
+                RpcError::new(
+                    RpcErrorCode::VmOperationFailed,
+                    format!("启动客户机命令失败: {}", e),
+                )
+            })?;
+
+        let timeout_secs = req
+            .timeout_secs
+            .unwrap_or(GuestExecRequest::DEFAULT_TIMEOUT_SECS)
+            .min(GuestExecRequest::MAX_TIMEOUT_SECS);
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(timeout_secs);
+
+        let mut response = GuestExecResponse {
+            vm_id: req.vm_id.clone(),
+            pid,
+            exited: false,
+            exit_code: None,
+            signal: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            truncated: false,
+            timed_out: false,
+        };
+
+        loop {
+            let status = self
+                .hypervisor
+                .guest_exec_status(&req.vm_id, pid)
+                .await
+                .map_err(|e| {
+                    error!("查询客户机命令状态失败: pid={}, error={}", pid, e);
+                    RpcError::new(
+                        RpcErrorCode::VmOperationFailed,
+                        format!("查询客户机命令状态失败: {}", e),
+                    )
+                })?;
+
+            if !status.stdout.is_empty() || !status.stderr.is_empty() || status.exited {
+                if let Some(sender) = &self.notification_sender {
+                    let chunk = GuestExecOutput {
+                        vm_id: req.vm_id.clone(),
+                        pid,
+                        stdout: status.stdout.clone(),
+                        stderr: status.stderr.clone(),
+                        exited: status.exited,
+                    };
+                    if let Ok(chunk) = serde_json::to_value(&chunk) {
+                        if let Err(e) = sender.send(RpcMessage::stream(request_id, chunk)) {
+                            error!("推送客户机命令输出失败: {}", e);
+                        }
+                    }
+                }
+            }
+
+            response.stdout.push_str(&status.stdout);
+            response.stderr.push_str(&status.stderr);
+            response.truncated |= status.truncated;
+
+            if status.exited {
+                response.exited = true;
+                response.exit_code = status.exit_code;
+                response.signal = status.signal;
+                break;
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                warn!("客户机命令执行超时: vm_id={}, pid={}", req.vm_id, pid);
+                response.timed_out = true;
+                break;
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+
+        info!(
+            "客户机命令执行结束: vm_id={}, pid={}, exit_code={:?}",
+            req.vm_id, pid, response.exit_code
+        );
+
+        serde_json::to_value(&response).map_err(|e| RpcError::serialization_error(e))
+    }
+}
+
+/// 存储操作失败时的错误码，缺少外部命令与命令超时单独区分，Server 据此给出明确提示
+fn storage_error_code(e: &common::Error, default: RpcErrorCode) -> RpcErrorCode {
+    match e {
+        common::Error::ToolNotFound(_) => RpcErrorCode::ToolNotFound,
+        common::Error::CommandTimeout(_) => RpcErrorCode::Timeout,
+        common::Error::ChecksumMismatch(_) => RpcErrorCode::ChecksumMismatch,
+        _ => default,
+    }
+}
+
+/// 存储层进度转换为推送给 Server 的存储卷创建进度
+fn volume_create_progress(volume_id: &str, progress: StorageProgress) -> VolumeCreateProgress {
+    let (stage, progress_percent, downloaded_bytes, total_bytes) = match progress {
+        StorageProgress::Download { downloaded_bytes, total_bytes } => {
+            let percent = match total_bytes {
+                Some(total) if total > 0 => downloaded_bytes as f64 * 100.0 / total as f64,
+                _ => 0.0,
+            };
+            ("download", percent, Some(downloaded_bytes), total_bytes)
+        }
+        StorageProgress::Convert { percent } => ("convert", percent, None, None),
+    };
+    VolumeCreateProgress {
+        volume_id: volume_id.to_string(),
+        stage: stage.to_string(),
+        progress_percent,
+        downloaded_bytes,
+        total_bytes,
+    }
+}
+
+/// 确定自动创建网络时使用的 VLAN ID
+///
+/// 优先使用 Server 下发的值；未下发时仅在允许推断的情况下按 Bridge 名称解析
+fn resolve_vlan_id(
+    explicit: Option<Option<u32>>,
+    inference: bool,
+    infer: impl FnOnce() -> Option<u32>,
+) -> Result<Option<u32>, String> {
+    match explicit {
+        Some(vlan_id) => Ok(vlan_id),
+        None if inference => Ok(infer()),
+        None => Err("Server 未提供 VLAN 信息，且已禁用 VLAN 推断（VLAN_INFERENCE=false）".to_string()),
+    }
+}
+
+/// 为刚启动的虚拟机的各网卡下发安全组规则，返回失败描述列表
+///
+/// 未关联安全组的网卡也清理一次：tap 设备名会被复用，可能残留其他虚拟机的规则
+async fn apply_security_groups(
+    hypervisor: &dyn Hypervisor,
+    network: &NetworkManager,
+    vm_id: &str,
+    networks: &[crate::hypervisor::NetworkConfig],
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    for config in networks {
+        let Some(mac_address) = config.mac_address.as_deref() else { continue };
+        let tap = match hypervisor.interface_target(vm_id, mac_address).await {
+            Ok(Some(tap)) => tap,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("查找虚拟机 {} 网卡 {} 的 tap 设备失败: {}", vm_id, mac_address, e);
+                continue;
+            }
+        };
+
+        let result = match &config.security_group_rules {
+            // OVS 与 macvlan 的流量不经过 bridge netfilter，Server 不会为其下发规则
+            Some(rules) if !config.is_ovs() && !config.is_macvlan() => {
+                network.apply_security_group(&tap, rules).await
+            }
+            _ => network.remove_security_group(&tap).await,
+        };
+        if let Err(e) = result {
+            warn!("虚拟机 {} 网卡 {} 的安全组规则下发失败: {}", vm_id, mac_address, e);
+            if config.security_group_rules.is_some() {
+                errors.push(format!("{}: {}", mac_address, e));
+            }
+        }
+    }
+    errors
+}
+
+/// 读取虚拟机启动后分配的 VNC 端口，失败时只记录日志，不影响操作结果
+async fn query_vnc_info(hypervisor: &dyn Hypervisor, vm_id: &str) -> Option<VncInfo> {
+    match hypervisor.get_vnc_info(vm_id).await {
+        Ok(vnc) => vnc,
+        Err(e) => {
+            warn!("读取虚拟机 {} 的 VNC 端口失败: {}", vm_id, e);
+            None
+        }
+    }
+}
+
+/// 在冻结客户机文件系统的状态下执行快照，返回快照结果与实际达到的一致性级别
+///
+/// 卷未挂载到运行中的虚拟机（`vm_id` 为 None）时直接执行快照，一致性为 None；
+/// 冻结失败（通常是客户机内没有 qemu-guest-agent）时退化为崩溃一致性快照。
+/// 冻结成功后无论快照是否成功都会解冻
+async fn snapshot_with_fsfreeze<T, F>(
+    hypervisor: &dyn Hypervisor,
+    vm_id: Option<&str>,
+    snapshot: F,
+) -> (common::Result<T>, Option<&'static str>)
+where
+    F: std::future::Future<Output = common::Result<T>>,
+{
+    let Some(vm_id) = vm_id else {
+        return (snapshot.await, None);
+    };
+
+    if let Err(e) = hypervisor.fsfreeze(vm_id).await {
+        warn!("冻结虚拟机 {} 的文件系统失败，创建崩溃一致性快照: {}", vm_id, e);
+        return (snapshot.await, Some("crash"));
+    }
+
+    let result = snapshot.await;
+    if let Err(e) = hypervisor.fsthaw(vm_id).await {
+        error!("解冻虚拟机 {} 的文件系统失败: {}", vm_id, e);
+    }
+    (result, Some("filesystem"))
+}
+
+/// 热迁移期间定期查询迁移作业进度并上报 Server，由调用方在迁移结束后中止
+///
+/// 开始迁移时已上报 10%，作业尚未开始传输时不上报；完成由迁移结果单独上报，
+/// 因此进度限制在 10% 到 99% 之间。复制存储时传输量包含全部磁盘数据
+async fn report_migration_progress(
+    hypervisor: Arc<dyn Hypervisor>,
+    sender: NotificationSender,
+    vm_id: String,
+    storage_mode: MigrationStorageMode,
+    interval: std::time::Duration,
+) {
+    let label = match storage_mode {
+        MigrationStorageMode::SharedStorage => "正在执行热迁移",
+        MigrationStorageMode::CopyStorage => "正在执行热迁移并复制磁盘",
+    };
+
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let (percent, remaining_secs) = match hypervisor.get_migration_progress(&vm_id).await {
+            Ok(progress) => progress,
+            Err(e) => {
+                debug!("查询虚拟机 {} 的迁移进度失败: {}", vm_id, e);
+                continue;
+            }
+        };
+        if percent <= 0.0 {
+            continue;
+        }
+
+        let notification = RpcMessage::notification(
+            "vm_migration_progress",
+            serde_json::json!({
+                "vm_id": vm_id,
+                "stage": "migrating",
+                "progress_percent": percent.clamp(10.0, 99.0),
+                "message": format!("{}，已传输 {:.0}%", label, percent),
+                "completed": false,
+                "remaining_secs": remaining_secs
+            }),
+        );
+        if let Err(e) = sender.send(notification) {
+            error!("发送迁移进度通知失败: {}", e);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hypervisor::driver::mock::MockHypervisor;
+    use crate::ws::dead_letter::DeadLetterQueue;
+    use common::utils::BridgeNaming;
+    use tokio::sync::mpsc;
+
+    fn registry(hypervisor: Arc<MockHypervisor>) -> RpcHandlerRegistry {
+        RpcHandlerRegistry::new(
+            hypervisor,
+            Arc::new(StorageManager::new(2, Vec::new(), crate::storage::CommandPolicy::default())),
+            Arc::new(NetworkManager::new(
+                "eth0".to_string(),
+                BridgeNaming::new(BridgeNaming::DEFAULT_PREFIX).unwrap(),
+            )),
+        )
+    }
+
+    /// 注入通知发送器，返回接收端以检查发出的通知
+    fn capture_notifications(
+        registry: &mut RpcHandlerRegistry,
+    ) -> mpsc::UnboundedReceiver<RpcMessage> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let path = std::env::temp_dir().join(format!("dead_letters_{}.jsonl", uuid::Uuid::new_v4()));
+        registry.set_notification_sender(NotificationSender::new(
+            tx,
+            Arc::new(DeadLetterQueue::new(path)),
+        ));
+        rx
+    }
+
+    async fn next_notification(rx: &mut mpsc::UnboundedReceiver<RpcMessage>) -> RpcMessage {
+        tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+            .await
+            .expect("等待通知超时")
+            .expect("通知通道已关闭")
+    }
+
+    fn error_code(msg: &RpcMessage) -> &str {
+        msg.error.as_ref().map(|e| e.code.as_str()).unwrap_or("")
+    }
+
+    #[tokio::test]
+    async fn test_unknown_method_returns_method_not_found() {
+        let registry = registry(Arc::new(MockHypervisor::new()));
+
+        let request = RpcMessage::request("no_such_method", serde_json::json!({}));
+        let request_id = request.id.clone();
+        let response = registry.handle_request(request).await;
+
+        assert_eq!(response.id, request_id);
+        assert_eq!(error_code(&response), RpcErrorCode::MethodNotFound.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_request_without_method_is_invalid() {
+        let registry = registry(Arc::new(MockHypervisor::new()));
+
+        let mut request = RpcMessage::request("get_vm_state", serde_json::json!({}));
+        request.method = None;
+        let response = registry.handle_request(request).await;
+
+        assert_eq!(error_code(&response), RpcErrorCode::InvalidRequest.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_params() {
+        let hypervisor = Arc::new(MockHypervisor::new());
+        let registry = registry(hypervisor.clone());
+
+        let response = registry
+            .handle_request(RpcMessage::request("get_vm_state", serde_json::json!({ "id": "vm-1" })))
+            .await;
+
+        assert_eq!(error_code(&response), RpcErrorCode::InvalidParams.as_str());
+        // 参数校验失败时不应触达虚拟化后端
+        assert!(hypervisor.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_vm_state() {
+        let registry = registry(Arc::new(MockHypervisor::new().with_vm("vm-1", "web", "running")));
+
+        let response = registry
+            .handle_request(RpcMessage::request("get_vm_state", serde_json::json!({ "vm_id": "vm-1" })))
+            .await;
+        let state: GetVmStateResponse = serde_json::from_value(response.payload.unwrap()).unwrap();
+        assert!(state.exists);
+        assert_eq!(state.state, "running");
+        assert_eq!(state.vcpu, 2);
+
+        let response = registry
+            .handle_request(RpcMessage::request("get_vm_state", serde_json::json!({ "vm_id": "vm-2" })))
+            .await;
+        let state: GetVmStateResponse = serde_json::from_value(response.payload.unwrap()).unwrap();
+        assert!(!state.exists);
+        assert_eq!(state.state, "shutoff");
+    }
+
+    #[tokio::test]
+    async fn test_get_vm_state_backend_error() {
+        let registry = registry(Arc::new(MockHypervisor::new().fail_on("get_vm_state")));
+
+        let response = registry
+            .handle_request(RpcMessage::request("get_vm_state", serde_json::json!({ "vm_id": "vm-1" })))
+            .await;
+
+        assert_eq!(error_code(&response), RpcErrorCode::InternalError.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_list_vms_reports_all_defined_domains() {
+        let registry = registry(Arc::new(
+            MockHypervisor::new()
+                .with_vm("vm-1", "web", "running")
+                .with_vm("vm-2", "db", "shutoff"),
+        ));
+
+        let response = registry
+            .handle_request(RpcMessage::request("list_vms", serde_json::json!({})))
+            .await;
+        let list: ListVmsResponse = serde_json::from_value(response.payload.unwrap()).unwrap();
+
+        let states: Vec<(&str, &str)> = list
+            .vms
+            .iter()
+            .map(|vm| (vm.vm_id.as_str(), vm.state.as_str()))
+            .collect();
+        assert_eq!(states, vec![("vm-1", "running"), ("vm-2", "shutoff")]);
+        assert_eq!(list.vms[0].vcpu, 2);
+        assert_eq!(list.vms[0].memory_mb, 1024);
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume_vm() {
+        let hypervisor = Arc::new(MockHypervisor::new().with_vm("vm-1", "web", "running"));
+        let registry = registry(hypervisor.clone());
+
+        let response = registry
+            .handle_request(RpcMessage::request("pause_vm", serde_json::json!({ "vm_id": "vm-1" })))
+            .await;
+        assert!(response.error.is_none());
+        assert_eq!(hypervisor.vm("vm-1").unwrap().state.state, "paused");
+
+        let response = registry
+            .handle_request(RpcMessage::request("resume_vm", serde_json::json!({ "vm_id": "vm-1" })))
+            .await;
+        assert!(response.error.is_none());
+        assert_eq!(hypervisor.vm("vm-1").unwrap().state.state, "running");
+
+        let response = registry
+            .handle_request(RpcMessage::request("pause_vm", serde_json::json!({ "vm_id": "vm-2" })))
+            .await;
+        assert_eq!(error_code(&response), RpcErrorCode::VmOperationFailed.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_resize_vm_cpu_and_memory() {
+        let hypervisor = Arc::new(MockHypervisor::new().with_vm("vm-1", "web", "running"));
+        let registry = registry(hypervisor.clone());
+
+        let response = registry
+            .handle_request(RpcMessage::request(
+                "resize_vm_cpu",
+                serde_json::json!({ "vm_id": "vm-1", "vcpu": 4, "live": true }),
+            ))
+            .await;
+        assert!(response.error.is_none());
+
+        let response = registry
+            .handle_request(RpcMessage::request(
+                "resize_vm_memory",
+                serde_json::json!({ "vm_id": "vm-1", "memory_mb": 2048, "live": true }),
+            ))
+            .await;
+        assert!(response.error.is_none());
+
+        let vm = hypervisor.vm("vm-1").unwrap();
+        assert_eq!((vm.state.vcpu, vm.state.memory_mb), (4, 2048));
+
+        let response = registry
+            .handle_request(RpcMessage::request(
+                "resize_vm_cpu",
+                serde_json::json!({ "vm_id": "vm-1", "vcpu": 0, "live": true }),
+            ))
+            .await;
+        assert!(response.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_attach_volume_and_set_iotune() {
+        let hypervisor = Arc::new(MockHypervisor::new().with_vm("vm-1", "db", "running"));
+        let registry = registry(hypervisor.clone());
+
+        let response = registry
+            .handle_request(RpcMessage::request(
+                "attach_volume",
+                serde_json::json!({
+                    "vm_id": "vm-1",
+                    "volume_id": "vol-1",
+                    "volume_path": "/mnt/nfs/vol-1.qcow2",
+                    "bus_type": "virtio",
+                    "device_type": "disk",
+                    "format": "qcow2",
+                    "iops_limit": 500
+                }),
+            ))
+            .await;
+        assert!(response.error.is_none());
+
+        let response = registry
+            .handle_request(RpcMessage::request(
+                "set_volume_iotune",
+                serde_json::json!({ "vm_id": "vm-1", "volume_id": "vol-1", "bps_limit": 10485760 }),
+            ))
+            .await;
+        assert!(response.error.is_none());
+        let vm = hypervisor.vm("vm-1").unwrap();
+        assert_eq!(
+            vm.iotune.get("vol-1"),
+            Some(&DiskIoLimits { iops_limit: None, bps_limit: Some(10485760) })
+        );
+
+        let response = registry
+            .handle_request(RpcMessage::request(
+                "set_volume_iotune",
+                serde_json::json!({ "vm_id": "vm-1", "volume_id": "vol-2", "iops_limit": 100 }),
+            ))
+            .await;
+        assert_eq!(error_code(&response), RpcErrorCode::VmOperationFailed.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_set_nic_bandwidth() {
+        let hypervisor = Arc::new(MockHypervisor::new().with_vm("vm-1", "web", "running"));
+        let registry = registry(hypervisor.clone());
+
+        let response = registry
+            .handle_request(RpcMessage::request(
+                "set_nic_bandwidth",
+                serde_json::json!({
+                    "vm_id": "vm-1",
+                    "mac_address": "52:54:00:12:34:56",
+                    "inbound_kbps": 1000
+                }),
+            ))
+            .await;
+        assert!(response.error.is_none());
+        let vm = hypervisor.vm("vm-1").unwrap();
+        assert_eq!(
+            vm.bandwidth.get("52:54:00:12:34:56"),
+            Some(&NicBandwidth { inbound_kbps: Some(1000), outbound_kbps: None })
+        );
+
+        let response = registry
+            .handle_request(RpcMessage::request(
+                "set_nic_bandwidth",
+                serde_json::json!({ "vm_id": "vm-2", "mac_address": "52:54:00:12:34:56" }),
+            ))
+            .await;
+        assert_eq!(error_code(&response), RpcErrorCode::VmOperationFailed.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_apply_security_group_requires_running_interface() {
+        let hypervisor = Arc::new(MockHypervisor::new().with_vm("vm-1", "web", "stopped"));
+        let registry = registry(hypervisor.clone());
+
+        let response = registry
+            .handle_request(RpcMessage::request(
+                "apply_security_group",
+                serde_json::json!({
+                    "vm_id": "vm-1",
+                    "mac_address": "52:54:00:12:34:56",
+                    "security_group_id": "sg-1",
+                    "rules": [{ "protocol": "icmp", "port_from": 22 }]
+                }),
+            ))
+            .await;
+        assert_eq!(error_code(&response), RpcErrorCode::InvalidParams.as_str());
+
+        // 模拟的虚拟机没有 tap 设备
+        let response = registry
+            .handle_request(RpcMessage::request(
+                "apply_security_group",
+                serde_json::json!({
+                    "vm_id": "vm-1",
+                    "mac_address": "52:54:00:12:34:56",
+                    "security_group_id": "sg-1",
+                    "rules": [{ "protocol": "tcp", "port_from": 22 }]
+                }),
+            ))
+            .await;
+        assert_eq!(error_code(&response), RpcErrorCode::NetworkError.as_str());
+        assert!(hypervisor.calls().contains(&"interface_target".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_get_guest_network() {
+        let eth0 = GuestNetworkInterface {
+            name: "eth0".to_string(),
+            mac_address: Some("52:54:00:12:34:56".to_string()),
+            ipv4_addresses: vec!["10.0.0.5/24".to_string()],
+            ipv6_addresses: Vec::new(),
+        };
+        let hypervisor = Arc::new(
+            MockHypervisor::new()
+                .with_vm("vm-1", "web", "running")
+                .with_vm("vm-2", "legacy", "running")
+                .with_guest_interfaces("vm-1", vec![eth0.clone()]),
+        );
+        let registry = registry(hypervisor);
+
+        let response = registry
+            .handle_request(RpcMessage::request("get_guest_network", serde_json::json!({ "vm_id": "vm-1" })))
+            .await;
+        let result: GetGuestNetworkResponse = serde_json::from_value(response.payload.unwrap()).unwrap();
+        assert_eq!(result.interfaces, vec![eth0]);
+
+        // 未安装 guest agent 与虚拟机不存在需要区分
+        let response = registry
+            .handle_request(RpcMessage::request("get_guest_network", serde_json::json!({ "vm_id": "vm-2" })))
+            .await;
+        assert_eq!(error_code(&response), RpcErrorCode::GuestAgentUnavailable.as_str());
+
+        let response = registry
+            .handle_request(RpcMessage::request("get_guest_network", serde_json::json!({ "vm_id": "vm-3" })))
+            .await;
+        assert_eq!(error_code(&response), RpcErrorCode::VmNotFound.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_get_domain_xml() {
+        let registry = registry(Arc::new(MockHypervisor::new().with_vm("vm-1", "web", "running")));
+
+        let response = registry
+            .handle_request(RpcMessage::request("get_domain_xml", serde_json::json!({ "vm_id": "vm-1" })))
+            .await;
+        let result: GetDomainXmlResponse = serde_json::from_value(response.payload.unwrap()).unwrap();
+        assert!(result.xml.contains("<name>web</name>"));
+
+        let response = registry
+            .handle_request(RpcMessage::request("get_domain_xml", serde_json::json!({ "vm_id": "vm-2" })))
+            .await;
+        assert_eq!(error_code(&response), RpcErrorCode::VmNotFound.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_set_console_password() {
+        let hypervisor = Arc::new(
+            MockHypervisor::new()
+                .with_vm("vm-1", "web", "running")
+                .with_vm("vm-2", "db", "shut off"),
+        );
+        let registry = registry(hypervisor.clone());
+        let request = |vm_id: &str, password: &str| {
+            RpcMessage::request(
+                "set_console_password",
+                serde_json::json!({ "vm_id": vm_id, "password": password, "valid_secs": 60 }),
+            )
+        };
+
+        let response = registry.handle_request(request("vm-1", "Xk3pQ9aZ")).await;
+        assert_eq!(response.payload.unwrap()["success"], true);
+        assert!(hypervisor.calls().contains(&"set_console_password".to_string()));
+
+        let response = registry.handle_request(request("vm-1", "")).await;
+        assert_eq!(error_code(&response), RpcErrorCode::InvalidParams.as_str());
+        let response = registry.handle_request(request("vm-2", "Xk3pQ9aZ")).await;
+        assert_eq!(error_code(&response), RpcErrorCode::InvalidParams.as_str());
+        let response = registry.handle_request(request("vm-3", "Xk3pQ9aZ")).await;
+        assert_eq!(error_code(&response), RpcErrorCode::VmNotFound.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_with_fsfreeze() {
+        let hypervisor = MockHypervisor::new()
+            .with_vm("vm-1", "web", "running")
+            .with_vm("vm-2", "legacy", "running")
+            .with_guest_interfaces("vm-1", Vec::new());
+
+        // 快照失败时也必须解冻
+        let (result, consistency) = snapshot_with_fsfreeze(&hypervisor, Some("vm-1"), async {
+            Err::<(), _>(common::Error::Internal("快照失败".to_string()))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(consistency, Some("filesystem"));
+        assert_eq!(hypervisor.calls(), vec!["fsfreeze", "fsthaw"]);
+
+        // 没有 guest agent 时退化为崩溃一致性快照
+        let (result, consistency) =
+            snapshot_with_fsfreeze(&hypervisor, Some("vm-2"), async { Ok("tag") }).await;
+        assert_eq!(result.unwrap(), "tag");
+        assert_eq!(consistency, Some("crash"));
+        assert_eq!(hypervisor.calls(), vec!["fsfreeze", "fsthaw", "fsfreeze"]);
+
+        let (_, consistency) = snapshot_with_fsfreeze(&hypervisor, None, async { Ok(()) }).await;
+        assert_eq!(consistency, None);
+    }
+
+    #[tokio::test]
+    async fn test_abort_migration() {
+        let hypervisor = Arc::new(MockHypervisor::new().with_vm("vm-1", "web", "running"));
+        let registry = registry(hypervisor.clone());
+
+        let response = registry
+            .handle_request(RpcMessage::request("abort_migration", serde_json::json!({ "vm_id": "vm-1" })))
+            .await;
+        assert!(response.error.is_none());
+        assert!(hypervisor.calls().contains(&"abort_migration".to_string()));
+
+        let response = registry
+            .handle_request(RpcMessage::request("abort_migration", serde_json::json!({ "vm_id": "vm-2" })))
+            .await;
+        assert_eq!(error_code(&response), RpcErrorCode::VmOperationFailed.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_get_migration_speed() {
+        let hypervisor = Arc::new(MockHypervisor::new().with_vm("vm-1", "web", "running"));
+        let registry = registry(hypervisor.clone());
+
+        // 误填 0 时按下限生效，避免迁移停滞
+        assert_eq!(hypervisor.set_migration_speed("vm-1", 0).await.unwrap(), 10);
+
+        let response = registry
+            .handle_request(RpcMessage::request("get_migration_speed", serde_json::json!({ "vm_id": "vm-1" })))
+            .await;
+        let speed: MigrationSpeedResponse = serde_json::from_value(response.payload.unwrap()).unwrap();
+        assert_eq!(speed.vm_id, "vm-1");
+        assert_eq!(speed.max_mbps, 10);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_vm_applies_bandwidth_cap_before_migrating() {
+        let hypervisor = Arc::new(MockHypervisor::new().with_vm("vm-1", "web", "running"));
+        let mut registry = registry(hypervisor.clone());
+        let mut rx = capture_notifications(&mut registry);
+
+        let response = registry
+            .handle_request(RpcMessage::request(
+                "migrate_vm",
+                serde_json::json!({
+                    "vm_id": "vm-1",
+                    "target_node_id": "node-2",
+                    "target_node_address": "10.0.0.12",
+                    "live_migration": true,
+                    "max_mbps": 0
+                }),
+            ))
+            .await;
+        assert!(response.error.is_none());
+
+        loop {
+            let payload = next_notification(&mut rx).await.payload.unwrap();
+            if payload["completed"] == true {
+                assert_eq!(payload["stage"], "completed");
+                break;
+            }
+        }
+        let calls = hypervisor.calls();
+        let speed = calls.iter().position(|c| c == "set_migration_speed").unwrap();
+        let migrate = calls.iter().position(|c| c == "live_migrate").unwrap();
+        assert!(speed < migrate);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_capabilities_reports_resource_info() {
+        let mut registry = registry(Arc::new(MockHypervisor::new()));
+
+        // 未设置节点管理器时返回错误
+        let response = registry
+            .handle_request(RpcMessage::request("refresh_capabilities", serde_json::json!({})))
+            .await;
+        assert!(response.error.is_some());
+
+        registry.set_node_manager(NodeManager::new("node-1", "host-1", "10.0.0.5"));
+        let response = registry
+            .handle_request(RpcMessage::request("refresh_capabilities", serde_json::json!({})))
+            .await;
+        assert!(response.error.is_none());
+        let info: NodeResourceInfo = serde_json::from_value(response.payload.unwrap()).unwrap();
+        assert_eq!(info.node_id, "node-1");
+        assert!(info.cpu_cores > 0);
+        assert!(info.supported_architectures.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_drain_node_suspend_then_restore_state() {
+        let hypervisor = Arc::new(
+            MockHypervisor::new()
+                .with_vm("vm-1", "web", "running")
+                .with_vm("vm-2", "db", "running")
+                .with_vm("vm-3", "idle", "shutoff"),
+        );
+        let registry = registry(hypervisor.clone());
+
+        let response = registry
+            .handle_request(RpcMessage::request("drain_node", serde_json::json!({ "policy": "suspend" })))
+            .await;
+        let drained: DrainNodeResponse = serde_json::from_value(response.payload.unwrap()).unwrap();
+
+        // 仅处理运行中的虚拟机
+        assert_eq!(drained.vms.len(), 2);
+        assert!(drained.vms.iter().all(|vm| vm.action == "suspended"));
+
+        let response = registry
+            .handle_request(RpcMessage::request("get_restore_state", serde_json::json!({})))
+            .await;
+        let restore: GetRestoreStateResponse = serde_json::from_value(response.payload.unwrap()).unwrap();
+        let saved: Vec<_> = restore
+            .vms
+            .iter()
+            .filter(|vm| vm.has_managed_save)
+            .map(|vm| vm.vm_id.as_str())
+            .collect();
+        assert_eq!(saved, vec!["vm-1", "vm-2"]);
+    }
+
+    #[tokio::test]
+    async fn test_drain_node_reports_per_vm_failure() {
+        let hypervisor = Arc::new(
+            MockHypervisor::new()
+                .with_vm("vm-1", "web", "running")
+                .fail_on("stop_vm"),
+        );
+        let registry = registry(hypervisor);
+
+        let response = registry
+            .handle_request(RpcMessage::request("drain_node", serde_json::json!({ "policy": "shutdown" })))
+            .await;
+        let drained: DrainNodeResponse = serde_json::from_value(response.payload.unwrap()).unwrap();
+
+        assert_eq!(drained.vms.len(), 1);
+        assert_eq!(drained.vms[0].action, "failed");
+        assert!(drained.vms[0].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_stop_vm_async_emits_completion() {
+        let hypervisor = Arc::new(MockHypervisor::new().with_vm("vm-1", "web", "running"));
+        let mut registry = registry(hypervisor.clone());
+        let mut rx = capture_notifications(&mut registry);
+
+        registry
+            .handle_notification(
+                "stop_vm_async",
+                serde_json::json!({ "vm_id": "vm-1", "task_id": "task-1" }),
+            )
+            .await
+            .unwrap();
+
+        let notification = next_notification(&mut rx).await;
+        assert_eq!(notification.method.as_deref(), Some("vm_operation_completed"));
+        let payload = notification.payload.unwrap();
+        assert_eq!(payload["operation"], "stop_vm");
+        assert_eq!(payload["success"], true);
+        // 任务 ID 原样带回，Server 据此更新任务状态
+        assert_eq!(payload["task_id"], "task-1");
+        assert_eq!(hypervisor.vm("vm-1").unwrap().state.state, "shutoff");
+    }
+
+    #[tokio::test]
+    async fn test_start_vm_async_reports_vnc_port() {
+        let hypervisor = Arc::new(MockHypervisor::new());
+        let mut registry = registry(hypervisor.clone());
+        let mut rx = capture_notifications(&mut registry);
+
+        registry
+            .handle_notification(
+                "start_vm_async",
+                serde_json::json!({ "vm_id": "vm-1", "name": "web", "vcpu": 1, "memory_mb": 512 }),
+            )
+            .await
+            .unwrap();
+
+        let payload = next_notification(&mut rx).await.payload.unwrap();
+        assert_eq!(payload["operation"], "start_vm");
+        assert_eq!(payload["success"], true);
+        assert_eq!(payload["vnc"]["port"], 5900);
+        assert_eq!(payload["vnc"]["listen"], "0.0.0.0");
+        assert!(hypervisor.calls().contains(&"get_vnc_info".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_stop_vm_async_emits_failure() {
+        let hypervisor = Arc::new(MockHypervisor::new().with_vm("vm-1", "web", "running").fail_on("stop_vm"));
+        let mut registry = registry(hypervisor);
+        let mut rx = capture_notifications(&mut registry);
+
+        registry
+            .handle_notification("stop_vm_async", serde_json::json!({ "vm_id": "vm-1", "force": true }))
+            .await
+            .unwrap();
+
+        let payload = next_notification(&mut rx).await.payload.unwrap();
+        assert_eq!(payload["vm_id"], "vm-1");
+        assert_eq!(payload["success"], false);
+    }
+
+    #[tokio::test]
+    async fn test_notification_param_errors_and_unknown_methods() {
+        let registry = registry(Arc::new(MockHypervisor::new()));
+
+        let err = registry
+            .handle_notification("stop_vm_async", serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, RpcErrorCode::InvalidParams);
+
+        // 未知通知仅记录日志
+        assert!(registry
+            .handle_notification("no_such_notification", serde_json::json!({}))
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_resolve_vlan_id() {
+        // Server 显式下发时忽略 Bridge 名称
+        assert_eq!(resolve_vlan_id(Some(Some(100)), true, || Some(200)), Ok(Some(100)));
+        assert_eq!(resolve_vlan_id(Some(None), false, || Some(200)), Ok(None));
+
+        // 未下发时按配置推断或报错
+        assert_eq!(resolve_vlan_id(None, true, || Some(200)), Ok(Some(200)));
+        assert!(resolve_vlan_id(None, false, || Some(200)).is_err());
+    }
+
+    #[test]
+    fn test_network_config_vlan_presence() {
+        let parse = |value: serde_json::Value| {
+            serde_json::from_value::<crate::hypervisor::NetworkConfig>(value).unwrap().vlan_id
+        };
+        let base = serde_json::json!({ "network_id": "net-1", "bridge_name": "br-vlan100", "model": "virtio" });
+
+        assert_eq!(parse(base.clone()), None);
+
+        let mut with_null = base.clone();
+        with_null["vlan_id"] = serde_json::Value::Null;
+        assert_eq!(parse(with_null), Some(None));
+
+        let mut with_vlan = base;
+        with_vlan["vlan_id"] = serde_json::json!(100);
+        assert_eq!(parse(with_vlan), Some(Some(100)));
+    }
+
+    #[tokio::test]
+    async fn test_report_migration_progress() {
+        let hypervisor = Arc::new(MockHypervisor::new().with_vm("vm-1", "web", "running"));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let path = std::env::temp_dir().join(format!("dead_letters_{}.jsonl", uuid::Uuid::new_v4()));
+        let sender = NotificationSender::new(tx, Arc::new(DeadLetterQueue::new(path)));
+
+        let reporter = tokio::spawn(report_migration_progress(
+            hypervisor.clone(),
+            sender,
+            "vm-1".to_string(),
+            MigrationStorageMode::CopyStorage,
+            std::time::Duration::from_millis(10),
+        ));
+        let notification = next_notification(&mut rx).await;
+        reporter.abort();
+
+        assert_eq!(notification.method.as_deref(), Some("vm_migration_progress"));
+        let payload = notification.payload.unwrap();
+        assert_eq!(payload["stage"], "migrating");
+        assert_eq!(payload["message"], "正在执行热迁移并复制磁盘，已传输 50%");
+        assert_eq!(payload["progress_percent"], 50.0);
+        assert_eq!(payload["remaining_secs"], 3);
+        assert_eq!(payload["completed"], false);
+        assert!(hypervisor.calls().contains(&"get_migration_progress".to_string()));
+    }
 }
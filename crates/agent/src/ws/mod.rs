@@ -3,8 +3,11 @@
 /// Agent 通过 WebSocket 连接到 Server
 
 pub mod client;
+pub mod dead_letter;
 pub mod handler;
+pub mod tls;
 
 pub use client::WsClient;
+pub use dead_letter::DeadLetterQueue;
 pub use handler::RpcHandlerRegistry;
 
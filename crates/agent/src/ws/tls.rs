@@ -0,0 +1,123 @@
+/// WSS 连接的 TLS 配置
+///
+/// 使用 rustls 构建连接器：按配置的 CA 证书校验 Server 证书，
+/// 可选携带客户端证书进行双向 TLS；跳过校验仅用于开发环境
+
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{self, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use tokio_tungstenite::Connector;
+
+use crate::config::TlsConfig;
+
+/// 根据配置构建 TLS 连接器
+pub fn build_connector(tls: &TlsConfig) -> anyhow::Result<Connector> {
+    let provider = Arc::new(crypto::ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| anyhow::anyhow!("TLS 协议版本配置失败: {}", e))?;
+
+    let builder = if tls.insecure_skip_verify {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(SkipServerVerification { provider }))
+    } else {
+        let ca_cert = tls
+            .ca_cert
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("未配置 TLS_CA_CERT，无法校验 Server 证书"))?;
+        builder.with_root_certificates(load_root_store(ca_cert)?)
+    };
+
+    let config = match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => builder
+            .with_client_auth_cert(load_certs(cert)?, load_private_key(key)?)
+            .map_err(|e| anyhow::anyhow!("客户端证书无效: {}", e))?,
+        _ => builder.with_no_client_auth(),
+    };
+
+    Ok(Connector::Rustls(Arc::new(config)))
+}
+
+/// 读取 PEM 格式的证书链
+fn load_certs(path: &str) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| anyhow::anyhow!("读取证书 {} 失败: {}", path, e))?;
+    if certs.is_empty() {
+        anyhow::bail!("证书文件 {} 中没有证书", path);
+    }
+    Ok(certs)
+}
+
+/// 读取 PEM 格式的私钥（PKCS#1、PKCS#8 或 SEC1）
+fn load_private_key(path: &str) -> anyhow::Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_file(path).map_err(|e| anyhow::anyhow!("读取私钥 {} 失败: {}", path, e))
+}
+
+/// 以 CA 证书构建信任根
+fn load_root_store(path: &str) -> anyhow::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    let (added, ignored) = roots.add_parsable_certificates(load_certs(path)?);
+    if added == 0 {
+        anyhow::bail!("CA 证书文件 {} 中没有可用的证书（忽略 {} 个）", path, ignored);
+    }
+    Ok(roots)
+}
+
+/// 不校验 Server 证书，仅校验握手签名
+#[derive(Debug)]
+struct SkipServerVerification {
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
@@ -36,6 +36,18 @@ pub enum Error {
     #[error("网络错误: {0}")]
     Network(String),
 
+    #[error("客户机代理不可用: {0}")]
+    GuestAgentUnavailable(String),
+
+    #[error("校验和不匹配: {0}")]
+    ChecksumMismatch(String),
+
+    #[error("命令不存在: {0}")]
+    ToolNotFound(String),
+
+    #[error("命令执行超时: {0}")]
+    CommandTimeout(String),
+
     #[error("内部错误: {0}")]
     Internal(String),
 
@@ -0,0 +1,117 @@
+/// Bridge 命名规则
+///
+/// Server 生成网络接口配置和 Agent 按需创建 Bridge 时都使用同一套规则，
+/// 避免两端各自拼接名称导致不一致。
+///
+/// 命名格式：
+/// - VLAN 网络：`{prefix}vlan{vlan_id}`（默认：br-vlan100）
+/// - 无 VLAN 网络：`{prefix}default`（默认：br-default）
+
+use crate::errors::{Error, Result};
+
+/// Linux 网络接口名称最大长度（IFNAMSIZ - 1）
+const MAX_IFNAME_LEN: usize = 15;
+
+/// VLAN ID 最大值
+const MAX_VLAN_ID: u32 = 4094;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeNaming {
+    prefix: String,
+}
+
+impl BridgeNaming {
+    /// 默认 Bridge 名称前缀
+    pub const DEFAULT_PREFIX: &'static str = "br-";
+
+    /// 使用指定前缀创建命名规则
+    ///
+    /// 前缀需保证最长的 VLAN Bridge 名称（`{prefix}vlan4094`）不超过接口名长度限制
+    pub fn new(prefix: impl Into<String>) -> Result<Self> {
+        let prefix = prefix.into();
+
+        if prefix.is_empty() {
+            return Err(Error::InvalidArgument("Bridge 名称前缀不能为空".to_string()));
+        }
+
+        if !prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(Error::InvalidArgument(format!(
+                "Bridge 名称前缀包含非法字符: {}",
+                prefix
+            )));
+        }
+
+        let longest = prefix.len() + format!("vlan{}", MAX_VLAN_ID).len();
+        if longest > MAX_IFNAME_LEN {
+            return Err(Error::InvalidArgument(format!(
+                "Bridge 名称前缀过长: {}（生成的名称不能超过 {} 个字符）",
+                prefix, MAX_IFNAME_LEN
+            )));
+        }
+
+        Ok(Self { prefix })
+    }
+
+    /// 获取 Bridge 名称前缀
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// 生成 Bridge 名称（根据 VLAN ID）
+    pub fn bridge_name(&self, vlan_id: Option<u32>) -> String {
+        match vlan_id {
+            Some(id) => format!("{}vlan{}", self.prefix, id),
+            None => format!("{}default", self.prefix),
+        }
+    }
+
+    /// 从 Bridge 名称推断 VLAN ID
+    ///
+    /// 名称不符合当前命名规则时返回 None
+    pub fn parse_vlan_id(&self, bridge_name: &str) -> Option<u32> {
+        bridge_name
+            .strip_prefix(self.prefix.as_str())
+            .and_then(|s| s.strip_prefix("vlan"))
+            .and_then(|s| s.parse::<u32>().ok())
+    }
+}
+
+impl Default for BridgeNaming {
+    fn default() -> Self {
+        Self {
+            prefix: Self::DEFAULT_PREFIX.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_bridge_name() {
+        let naming = BridgeNaming::default();
+        assert_eq!(naming.bridge_name(Some(100)), "br-vlan100");
+        assert_eq!(naming.bridge_name(None), "br-default");
+    }
+
+    #[test]
+    fn test_parse_vlan_id_roundtrip() {
+        let naming = BridgeNaming::new("vmbr").unwrap();
+        assert_eq!(naming.bridge_name(Some(200)), "vmbrvlan200");
+        assert_eq!(naming.parse_vlan_id(&naming.bridge_name(Some(200))), Some(200));
+        assert_eq!(naming.parse_vlan_id(&naming.bridge_name(None)), None);
+        // 其他前缀生成的名称不应被识别
+        assert_eq!(naming.parse_vlan_id("br-vlan200"), None);
+    }
+
+    #[test]
+    fn test_invalid_prefix() {
+        assert!(BridgeNaming::new("").is_err());
+        assert!(BridgeNaming::new("br/").is_err());
+        assert!(BridgeNaming::new("too-long-pfx").is_err());
+    }
+}
@@ -1,5 +1,13 @@
 /// 工具函数集合
 
+pub mod bridge;
+pub mod config;
+pub mod redact;
+
+pub use bridge::BridgeNaming;
+pub use config::ConfigValidator;
+pub use redact::{redact_payload, redact_url};
+
 use uuid::Uuid;
 
 /// 生成唯一 ID
@@ -33,16 +41,9 @@ pub fn validate_mac_address(mac: &str) -> bool {
     })
 }
 
-/// 验证 IP 地址格式（简单验证）
+/// 验证 IP 地址格式（IPv4 或 IPv6）
 pub fn validate_ip_address(ip: &str) -> bool {
-    let parts: Vec<&str> = ip.split('.').collect();
-    if parts.len() != 4 {
-        return false;
-    }
-
-    parts.iter().all(|part| {
-        part.parse::<u8>().is_ok()
-    })
+    ip.parse::<std::net::IpAddr>().is_ok()
 }
 
 #[cfg(test)]
@@ -81,6 +82,10 @@ mod tests {
         assert!(!validate_ip_address("256.1.1.1"));
         assert!(!validate_ip_address("192.168.1"));
         assert!(!validate_ip_address("invalid"));
+        assert!(validate_ip_address("2001:db8::1"));
+        assert!(validate_ip_address("::1"));
+        assert!(!validate_ip_address("2001:db8::g"));
+        assert!(!validate_ip_address("2001:db8::1/64"));
     }
 }
 
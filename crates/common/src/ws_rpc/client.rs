@@ -1,6 +1,6 @@
 /// WebSocket RPC 客户端辅助工具
 
-use super::{RpcMessage, RpcError, RpcErrorCode};
+use super::{EncodedFrame, RpcCodec, RpcMessage, RpcError, RpcErrorCode};
 use futures_util::{SinkExt, StreamExt};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -155,8 +155,24 @@ pub mod codec {
     
     /// 编码 RPC 消息为 WebSocket 消息
     pub fn encode(msg: &RpcMessage) -> Result<WsMessage, RpcError> {
-        let json = msg.to_json()?;
-        Ok(WsMessage::Text(json))
+        encode_with(msg, RpcCodec::Json)
+    }
+
+    /// 按协商的编码方式编码：JSON 使用文本帧，MessagePack 使用二进制帧
+    pub fn encode_with(msg: &RpcMessage, codec: RpcCodec) -> Result<WsMessage, RpcError> {
+        encode_compressed(msg, codec, None)
+    }
+
+    /// 按协商的编码方式编码，超过压缩阈值的消息以 gzip 压缩的二进制帧发送
+    pub fn encode_compressed(
+        msg: &RpcMessage,
+        codec: RpcCodec,
+        compress_threshold: Option<usize>,
+    ) -> Result<WsMessage, RpcError> {
+        Ok(match msg.encode_frame(codec, compress_threshold)? {
+            EncodedFrame::Text(text) => WsMessage::Text(text),
+            EncodedFrame::Binary(data) => WsMessage::Binary(data),
+        })
     }
     
     /// 解码 WebSocket 消息为 RPC 消息
@@ -167,14 +183,7 @@ pub mod codec {
                     RpcError::serialization_error(e)
                 })
             }
-            WsMessage::Binary(data) => {
-                let text = String::from_utf8(data).map_err(|e| {
-                    RpcError::serialization_error(e)
-                })?;
-                RpcMessage::from_json(&text).map_err(|e| {
-                    RpcError::serialization_error(e)
-                })
-            }
+            WsMessage::Binary(data) => RpcMessage::from_binary(&data),
             WsMessage::Close(_) => {
                 Err(RpcError::connection_closed())
             }
@@ -23,11 +23,17 @@ pub enum RpcErrorCode {
     VmStartFailed,
     VmStopFailed,
     VmDeleteFailed,
+    /// 客户机未安装或未运行 qemu-guest-agent
+    GuestAgentUnavailable,
     
     StorageError,
     VolumeNotFound,
     VolumeCreateFailed,
     VolumeDeleteFailed,
+    /// 下载的源文件与请求中的校验和不一致
+    ChecksumMismatch,
+    /// 节点上缺少执行操作所需的外部命令（如 qemu-img）
+    ToolNotFound,
     
     NetworkError,
     NetworkCreateFailed,
@@ -56,11 +62,14 @@ impl RpcErrorCode {
             Self::VmStartFailed => "VM_START_FAILED",
             Self::VmStopFailed => "VM_STOP_FAILED",
             Self::VmDeleteFailed => "VM_DELETE_FAILED",
+            Self::GuestAgentUnavailable => "GUEST_AGENT_UNAVAILABLE",
             
             Self::StorageError => "STORAGE_ERROR",
             Self::VolumeNotFound => "VOLUME_NOT_FOUND",
             Self::VolumeCreateFailed => "VOLUME_CREATE_FAILED",
             Self::VolumeDeleteFailed => "VOLUME_DELETE_FAILED",
+            Self::ChecksumMismatch => "CHECKSUM_MISMATCH",
+            Self::ToolNotFound => "TOOL_NOT_FOUND",
             
             Self::NetworkError => "NETWORK_ERROR",
             Self::NetworkCreateFailed => "NETWORK_CREATE_FAILED",
@@ -1,8 +1,24 @@
 /// WebSocket RPC 消息定义
 
+use std::io::{Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use super::RpcError;
+
+/// 默认压缩阈值（字节），编码后超过该大小的消息使用 gzip 压缩
+pub const DEFAULT_COMPRESS_THRESHOLD: usize = 4 * 1024;
+
+/// 压缩帧的首字节标记，JSON（`{`）与 MessagePack map 都不会以该字节开头
+const GZIP_FRAME_PREFIX: u8 = 0x01;
+
+/// 压缩帧解压后的最大字节数，防止异常数据占满内存
+const MAX_DECOMPRESSED_SIZE: u64 = 64 * 1024 * 1024;
+
 /// RPC 消息类型
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -17,6 +33,49 @@ pub enum MessageType {
     Stream,
 }
 
+/// 消息编码方式
+///
+/// Agent 在注册请求中声明希望使用的编码，Server 在注册响应中返回最终采用的编码；
+/// 注册消息本身始终使用 JSON，任一方未声明时回退到 JSON
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RpcCodec {
+    /// MessagePack 二进制帧，节点资源信息、指标等较大负载体积更小
+    Msgpack,
+    /// JSON 文本帧（未知取值也按 JSON 处理）
+    #[default]
+    #[serde(other)]
+    Json,
+}
+
+impl RpcCodec {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RpcCodec::Msgpack => "msgpack",
+            RpcCodec::Json => "json",
+        }
+    }
+}
+
+impl std::str::FromStr for RpcCodec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(RpcCodec::Json),
+            "msgpack" => Ok(RpcCodec::Msgpack),
+            other => Err(format!("未知的消息编码: {}", other)),
+        }
+    }
+}
+
+/// 编码后的 WebSocket 帧内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodedFrame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
 /// RPC 消息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RpcMessage {
@@ -129,6 +188,64 @@ impl RpcMessage {
         serde_json::from_str(json)
     }
 
+    /// 序列化为 MessagePack
+    ///
+    /// 使用带字段名的 map 编码，可选字段省略后仍能按名称解析
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+        rmp_serde::to_vec_named(self)
+    }
+
+    /// 从 MessagePack 反序列化
+    pub fn from_msgpack(data: &[u8]) -> Result<Self, rmp_serde::decode::Error> {
+        rmp_serde::from_slice(data)
+    }
+
+    /// 按编码方式生成 WebSocket 帧
+    ///
+    /// `compress_threshold` 为 None 表示对端不支持压缩；编码后超过阈值的消息
+    /// gzip 压缩后以二进制帧发送，首字节为压缩标记，小消息保持原样
+    pub fn encode_frame(
+        &self,
+        codec: RpcCodec,
+        compress_threshold: Option<usize>,
+    ) -> Result<EncodedFrame, RpcError> {
+        let frame = match codec {
+            RpcCodec::Json => EncodedFrame::Text(self.to_json()?),
+            RpcCodec::Msgpack => EncodedFrame::Binary(
+                self.to_msgpack().map_err(RpcError::serialization_error)?,
+            ),
+        };
+
+        let encoded = match &frame {
+            EncodedFrame::Text(text) => text.as_bytes(),
+            EncodedFrame::Binary(data) => data.as_slice(),
+        };
+        match compress_threshold {
+            Some(threshold) if threshold > 0 && encoded.len() > threshold => {
+                Ok(EncodedFrame::Binary(gzip_frame(encoded)?))
+            }
+            _ => Ok(frame),
+        }
+    }
+
+    /// 解析二进制帧
+    ///
+    /// 带压缩标记的帧先解压；旧版本对端会把 JSON 放在二进制帧中发送，
+    /// 以 `{` 开头的按 JSON 解析，其余按 MessagePack
+    pub fn from_binary(data: &[u8]) -> Result<Self, RpcError> {
+        if let Some((&GZIP_FRAME_PREFIX, compressed)) = data.split_first() {
+            let data = gunzip_frame(compressed)?;
+            return Self::from_binary(&data);
+        }
+
+        if data.trim_ascii_start().first() == Some(&b'{') {
+            let text = std::str::from_utf8(data).map_err(RpcError::serialization_error)?;
+            Ok(Self::from_json(text)?)
+        } else {
+            Self::from_msgpack(data).map_err(RpcError::serialization_error)
+        }
+    }
+
     /// 判断是否是成功响应
     pub fn is_success(&self) -> bool {
         self.message_type == MessageType::Response && self.error.is_none()
@@ -140,6 +257,32 @@ impl RpcMessage {
     }
 }
 
+/// gzip 压缩编码后的消息并加上压缩标记
+fn gzip_frame(data: &[u8]) -> Result<Vec<u8>, RpcError> {
+    let mut encoder = GzEncoder::new(vec![GZIP_FRAME_PREFIX], Compression::fast());
+    encoder
+        .write_all(data)
+        .map_err(RpcError::serialization_error)?;
+    encoder.finish().map_err(RpcError::serialization_error)
+}
+
+/// 解压压缩帧（不含标记字节）
+fn gunzip_frame(compressed: &[u8]) -> Result<Vec<u8>, RpcError> {
+    let mut data = Vec::new();
+    GzDecoder::new(compressed)
+        .take(MAX_DECOMPRESSED_SIZE + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| RpcError::serialization_error(format!("解压消息失败: {}", e)))?;
+    if data.len() as u64 > MAX_DECOMPRESSED_SIZE {
+        return Err(RpcError::serialization_error("解压后的消息超过大小上限"));
+    }
+    // 压缩帧内不允许再嵌套压缩帧
+    if data.first() == Some(&GZIP_FRAME_PREFIX) {
+        return Err(RpcError::serialization_error("无效的压缩帧"));
+    }
+    Ok(data)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -181,5 +324,66 @@ mod tests {
         assert_eq!(msg.id, parsed.id);
         assert_eq!(msg.message_type, parsed.message_type);
     }
-}
 
+    #[test]
+    fn test_msgpack_serialization() {
+        let msg = RpcMessage::notification("node_resource_info", json!({"cpu_cores": 16, "cpu_usage": 12.5}));
+        let data = msg.to_msgpack().unwrap();
+        let parsed = RpcMessage::from_binary(&data).unwrap();
+        assert_eq!(parsed.id, msg.id);
+        assert_eq!(parsed.message_type, MessageType::Notification);
+        assert_eq!(parsed.payload, msg.payload);
+        assert!(parsed.error.is_none());
+
+        // 旧版本对端在二进制帧中发送的 JSON
+        let json = msg.to_json().unwrap();
+        assert_eq!(RpcMessage::from_binary(json.as_bytes()).unwrap().id, msg.id);
+
+        let err = RpcMessage::error_response("req-1", "TEST_ERROR", "失败", None);
+        let parsed = RpcMessage::from_msgpack(&err.to_msgpack().unwrap()).unwrap();
+        assert!(parsed.is_error());
+        assert!(parsed.payload.is_none());
+    }
+
+    #[test]
+    fn test_codec_fallback() {
+        assert_eq!(serde_json::from_value::<RpcCodec>(json!("msgpack")).unwrap(), RpcCodec::Msgpack);
+        // 无法识别的编码按 JSON 处理
+        assert_eq!(serde_json::from_value::<RpcCodec>(json!("cbor")).unwrap(), RpcCodec::Json);
+        assert_eq!("msgpack".parse(), Ok(RpcCodec::Msgpack));
+    }
+
+    #[test]
+    fn test_compressed_frame_round_trip() {
+        let log = "x".repeat(1024 * 1024);
+        let msg = RpcMessage::response("req-1", json!({"log": log}));
+
+        for codec in [RpcCodec::Json, RpcCodec::Msgpack] {
+            let data = match msg.encode_frame(codec, Some(DEFAULT_COMPRESS_THRESHOLD)).unwrap() {
+                EncodedFrame::Binary(data) => data,
+                EncodedFrame::Text(_) => panic!("大消息应压缩为二进制帧"),
+            };
+            assert_eq!(data[0], GZIP_FRAME_PREFIX);
+            assert!(data.len() < 64 * 1024);
+
+            let parsed = RpcMessage::from_binary(&data).unwrap();
+            assert_eq!(parsed.id, "req-1");
+            assert_eq!(parsed.payload, msg.payload);
+        }
+
+        // 对端不支持压缩时按原编码发送
+        assert!(matches!(
+            msg.encode_frame(RpcCodec::Json, None).unwrap(),
+            EncodedFrame::Text(_)
+        ));
+    }
+
+    #[test]
+    fn test_small_message_not_compressed() {
+        let msg = RpcMessage::request("get_vm_status", json!({"vm_id": "vm-1"}));
+        let frame = msg
+            .encode_frame(RpcCodec::Json, Some(DEFAULT_COMPRESS_THRESHOLD))
+            .unwrap();
+        assert_eq!(frame, EncodedFrame::Text(msg.to_json().unwrap()));
+    }
+}
@@ -7,8 +7,10 @@ pub mod error;
 pub mod types;
 pub mod client;
 pub mod server;
+pub mod stream;
 
-pub use message::{RpcMessage, MessageType};
+pub use message::{EncodedFrame, RpcCodec, RpcMessage, MessageType, DEFAULT_COMPRESS_THRESHOLD};
 pub use error::{RpcError, RpcErrorCode};
+pub use stream::{RpcStream, StreamFrame, StreamSender};
 pub use types::*;
 
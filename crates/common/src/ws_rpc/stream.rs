@@ -0,0 +1,161 @@
+/// 流式响应
+///
+/// 一个请求可以先收到若干条与请求 id 相同的 Stream 消息（中间结果），
+/// 最后以 Response 消息结束。发送端使用 StreamSender，接收端得到 RpcStream
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_util::Stream;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use super::{RpcError, RpcMessage};
+
+/// 流式响应的发送端，处理请求期间按请求 id 推送中间结果
+#[derive(Debug, Clone)]
+pub struct StreamSender {
+    request_id: String,
+    sender: mpsc::UnboundedSender<RpcMessage>,
+}
+
+impl StreamSender {
+    pub fn new(request_id: impl Into<String>, sender: mpsc::UnboundedSender<RpcMessage>) -> Self {
+        Self {
+            request_id: request_id.into(),
+            sender,
+        }
+    }
+
+    /// 对应的请求 id
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// 推送一条中间结果
+    pub fn send<T: Serialize>(&self, frame: &T) -> Result<(), RpcError> {
+        let payload = serde_json::to_value(frame)?;
+        self.sender
+            .send(RpcMessage::stream(self.request_id.clone(), payload))
+            .map_err(|_| RpcError::connection_closed())
+    }
+}
+
+/// 流式响应中的一帧
+#[derive(Debug)]
+pub enum StreamFrame {
+    /// 中间结果（Stream 消息的负载）
+    Data(serde_json::Value),
+    /// 最终响应，流随之结束
+    End(Result<RpcMessage, RpcError>),
+}
+
+/// 流式响应的接收端
+///
+/// 依次产出 Data 帧，最后产出一个 End 帧后结束；
+/// 发送端在给出最终响应前断开时以连接关闭错误结束
+pub struct RpcStream {
+    receiver: mpsc::UnboundedReceiver<StreamFrame>,
+    finished: bool,
+}
+
+impl RpcStream {
+    /// 创建一对发送通道与接收流
+    pub fn channel() -> (mpsc::UnboundedSender<StreamFrame>, Self) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (
+            tx,
+            Self {
+                receiver: rx,
+                finished: false,
+            },
+        )
+    }
+
+    /// 只包含最终响应的流，用于不支持中间结果的调用方式
+    pub fn completed(result: Result<RpcMessage, RpcError>) -> Self {
+        let (tx, stream) = Self::channel();
+        let _ = tx.send(StreamFrame::End(result));
+        stream
+    }
+
+    /// 逐帧处理中间结果，返回最终响应
+    pub async fn for_each_data(
+        mut self,
+        mut on_data: impl FnMut(serde_json::Value),
+    ) -> Result<RpcMessage, RpcError> {
+        use futures_util::StreamExt;
+
+        while let Some(frame) = self.next().await {
+            match frame {
+                StreamFrame::Data(payload) => on_data(payload),
+                StreamFrame::End(result) => return result,
+            }
+        }
+        Err(RpcError::connection_closed())
+    }
+}
+
+impl Stream for RpcStream {
+    type Item = StreamFrame;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.finished {
+            return Poll::Ready(None);
+        }
+
+        match self.receiver.poll_recv(cx) {
+            Poll::Ready(Some(StreamFrame::End(result))) => {
+                self.finished = true;
+                Poll::Ready(Some(StreamFrame::End(result)))
+            }
+            Poll::Ready(Some(frame)) => Poll::Ready(Some(frame)),
+            Poll::Ready(None) => {
+                self.finished = true;
+                Poll::Ready(Some(StreamFrame::End(Err(RpcError::connection_closed()))))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws_rpc::{MessageType, RpcErrorCode};
+    use futures_util::StreamExt;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_stream_frames_end_with_response() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sender = StreamSender::new("req-1", tx);
+        sender.send(&json!({"progress_percent": 50.0})).unwrap();
+        let msg = rx.recv().await.unwrap();
+        assert_eq!(msg.id, "req-1");
+        assert_eq!(msg.message_type, MessageType::Stream);
+
+        let (frames, stream) = RpcStream::channel();
+        frames.send(StreamFrame::Data(msg.payload.unwrap())).unwrap();
+        frames
+            .send(StreamFrame::End(Ok(RpcMessage::response("req-1", json!({})))))
+            .unwrap();
+
+        let mut data = Vec::new();
+        let response = stream.for_each_data(|payload| data.push(payload)).await.unwrap();
+        assert_eq!(response.id, "req-1");
+        assert_eq!(data, vec![json!({"progress_percent": 50.0})]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_closed_without_response() {
+        let (frames, mut stream) = RpcStream::channel();
+        drop(frames);
+
+        match stream.next().await {
+            Some(StreamFrame::End(Err(e))) => assert_eq!(e.code, RpcErrorCode::ConnectionClosed),
+            other => panic!("unexpected frame: {:?}", other),
+        }
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -2,22 +2,36 @@
 ///
 /// 对应原来 proto 中定义的消息类型
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+use super::message::RpcCodec;
 
 // ============================================================================
 // 心跳相关
 // ============================================================================
 
+/// Agent 定期发送的 ping 请求，兼作心跳
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct HeartbeatRequest {
-    pub node_id: String,
-    pub timestamp: i64,
+pub struct PingRequest {
+    /// Agent 发送时的时间戳（毫秒），Server 原样回显用于计算往返时延
+    pub timestamp_ms: i64,
+    pub hypervisor_connected: bool,
+    /// 上一次 ping 测得的往返时延（毫秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_rtt_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PongResponse {
+    /// 回显 PingRequest.timestamp_ms
+    pub timestamp_ms: i64,
+    pub server_time_ms: i64,
 }
 
+/// Server 等待响应超时后发送的 `cancel` 通知，Agent 据此中止仍在处理的请求
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct HeartbeatResponse {
-    pub ok: bool,
-    pub server_time: i64,
+pub struct CancelRequest {
+    pub request_id: String,
 }
 
 // ============================================================================
@@ -53,6 +67,77 @@ pub struct ResourceInfo {
     pub memory_usage_percent: f64,
 }
 
+// ============================================================================
+// 节点排空（计划内重启前处理运行中的虚拟机）
+// ============================================================================
+
+/// 宿主机重启时虚拟机的处理策略
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HostShutdownPolicy {
+    /// 优雅关机，超时后强制关机
+    #[default]
+    Shutdown,
+    /// 挂起到磁盘（libvirt managed save），下次启动时自动恢复
+    Suspend,
+}
+
+impl HostShutdownPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HostShutdownPolicy::Shutdown => "shutdown",
+            HostShutdownPolicy::Suspend => "suspend",
+        }
+    }
+}
+
+impl std::str::FromStr for HostShutdownPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shutdown" => Ok(HostShutdownPolicy::Shutdown),
+            "suspend" => Ok(HostShutdownPolicy::Suspend),
+            other => Err(format!("未知的宿主机关机策略: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainNodeRequest {
+    pub policy: HostShutdownPolicy,
+}
+
+/// 单台虚拟机的排空结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainedVm {
+    pub vm_id: String,
+    pub name: String,
+    /// suspended / shutdown / failed
+    pub action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainNodeResponse {
+    pub policy: HostShutdownPolicy,
+    pub vms: Vec<DrainedVm>,
+}
+
+/// 虚拟机的恢复状态（是否存在 managed save 镜像）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VmRestoreState {
+    pub vm_id: String,
+    pub name: String,
+    pub has_managed_save: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetRestoreStateResponse {
+    pub vms: Vec<VmRestoreState>,
+}
+
 // ============================================================================
 // 虚拟机管理
 // ============================================================================
@@ -76,6 +161,7 @@ pub struct CreateVmRequest {
 pub enum DiskBusType {
     Virtio,
     Scsi,
+    Sata,
     Ide,
 }
 
@@ -99,14 +185,418 @@ impl Default for DiskDeviceType {
     }
 }
 
+impl DiskBusType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiskBusType::Virtio => "virtio",
+            DiskBusType::Scsi => "scsi",
+            DiskBusType::Sata => "sata",
+            DiskBusType::Ide => "ide",
+        }
+    }
+}
+
+impl std::str::FromStr for DiskBusType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "virtio" => Ok(DiskBusType::Virtio),
+            "scsi" => Ok(DiskBusType::Scsi),
+            "sata" => Ok(DiskBusType::Sata),
+            "ide" => Ok(DiskBusType::Ide),
+            other => Err(format!("未知的磁盘总线类型: {}（可选 virtio/scsi/sata/ide）", other)),
+        }
+    }
+}
+
+impl DiskDeviceType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiskDeviceType::Disk => "disk",
+            DiskDeviceType::Cdrom => "cdrom",
+        }
+    }
+}
+
+impl std::str::FromStr for DiskDeviceType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disk" => Ok(DiskDeviceType::Disk),
+            "cdrom" => Ok(DiskDeviceType::Cdrom),
+            other => Err(format!("未知的磁盘设备类型: {}（可选 disk/cdrom）", other)),
+        }
+    }
+}
+
+/// 虚拟机固件类型
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FirmwareType {
+    /// 传统 BIOS（SeaBIOS）
+    #[default]
+    Bios,
+    /// UEFI（OVMF），支持安全启动
+    Uefi,
+}
+
+impl FirmwareType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FirmwareType::Bios => "bios",
+            FirmwareType::Uefi => "uefi",
+        }
+    }
+}
+
+impl std::str::FromStr for FirmwareType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bios" => Ok(FirmwareType::Bios),
+            "uefi" => Ok(FirmwareType::Uefi),
+            other => Err(format!("未知的固件类型: {}（可选 bios/uefi）", other)),
+        }
+    }
+}
+
+/// 虚拟机图形控制台协议
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphicsType {
+    #[default]
+    Vnc,
+    /// SPICE，支持剪贴板共享与 USB 重定向，Windows 桌面体验更好
+    Spice,
+}
+
+impl GraphicsType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GraphicsType::Vnc => "vnc",
+            GraphicsType::Spice => "spice",
+        }
+    }
+}
+
+impl std::str::FromStr for GraphicsType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vnc" => Ok(GraphicsType::Vnc),
+            "spice" => Ok(GraphicsType::Spice),
+            other => Err(format!("未知的控制台协议: {}（可选 vnc/spice）", other)),
+        }
+    }
+}
+
+/// cloud-init 配置（NoCloud 数据源）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CloudInitConfig {
+    /// user-data，通常以 `#cloud-config` 开头
+    #[serde(default)]
+    pub user_data: String,
+    /// meta-data，为空时由 Agent 按虚拟机 ID 和名称生成
+    #[serde(default)]
+    pub meta_data: String,
+    /// network-config，未提供时由 cloud-init 使用默认的 DHCP 配置
+    #[serde(default)]
+    pub network_config: Option<String>,
+}
+
+/// vCPU 绑定：vCPU 编号 -> 宿主机物理 CPU 编号
+pub type CpuPinning = BTreeMap<u32, u32>;
+
+/// 虚拟机内存在宿主机 NUMA 节点上的分配策略
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NumaMode {
+    /// 只从指定节点分配，节点内存不足时分配失败
+    #[default]
+    Strict,
+    /// 优先从指定节点分配，不足时回退到其他节点
+    Preferred,
+    /// 在指定节点间交错分配
+    Interleave,
+}
+
+impl NumaMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NumaMode::Strict => "strict",
+            NumaMode::Preferred => "preferred",
+            NumaMode::Interleave => "interleave",
+        }
+    }
+}
+
+/// 虚拟机 NUMA 配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct NumaConfig {
+    /// 内存绑定的宿主机 NUMA 节点，为空时不限制
+    #[serde(default)]
+    pub host_nodes: Vec<u32>,
+    #[serde(default)]
+    pub mode: NumaMode,
+    /// 暴露给客户机的 NUMA 节点数，vCPU 与内存在各节点间平均分配；0 表示不设置
+    #[serde(default)]
+    pub guest_nodes: u32,
+}
+
+/// 宿主机 NUMA 节点，由 Agent 随节点资源信息上报
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HostNumaNode {
+    pub id: u32,
+    /// 节点上的物理 CPU 编号
+    pub cpus: Vec<u32>,
+    pub memory_mb: u64,
+}
+
+/// 按宿主机 NUMA 拓扑校验 vCPU 绑定与 NUMA 配置
+///
+/// vcpu 为虚拟机可使用的 vCPU 数量，绑定的 vCPU 编号与客户机 NUMA 节点数都不能超过它
+pub fn validate_cpu_placement(
+    vcpu: u32,
+    pinning: Option<&CpuPinning>,
+    numa: Option<&NumaConfig>,
+    host: &[HostNumaNode],
+) -> Result<(), String> {
+    for (&guest_cpu, &host_cpu) in pinning.into_iter().flatten() {
+        if guest_cpu >= vcpu {
+            return Err(format!("vCPU {} 超出虚拟机的 vCPU 数量 {}", guest_cpu, vcpu));
+        }
+        if !host.iter().any(|node| node.cpus.contains(&host_cpu)) {
+            return Err(format!("vCPU {} 绑定的物理 CPU {} 不存在", guest_cpu, host_cpu));
+        }
+    }
+    if let Some(numa) = numa {
+        if let Some(missing) = numa
+            .host_nodes
+            .iter()
+            .find(|&&id| !host.iter().any(|node| node.id == id))
+        {
+            return Err(format!("宿主机 NUMA 节点 {} 不存在", missing));
+        }
+        if numa.guest_nodes > vcpu {
+            return Err(format!(
+                "客户机 NUMA 节点数 {} 不能超过 vCPU 数量 {}",
+                numa.guest_nodes, vcpu
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 虚拟机启动后 libvirt 实际分配的图形控制台（VNC 或 SPICE）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VncInfo {
+    pub port: u16,
+    /// 监听地址，0.0.0.0 / :: 表示监听节点所有地址
+    pub listen: String,
+    /// 控制台协议，旧版本 Agent 不上报时为 VNC
+    #[serde(default)]
+    pub protocol: GraphicsType,
+}
+
+impl VncInfo {
+    /// 是否监听所有地址（此时需要用节点 IP 访问）
+    pub fn listens_on_all(&self) -> bool {
+        matches!(self.listen.as_str(), "" | "0.0.0.0" | "::")
+    }
+}
+
+/// 允许的（总线, 设备）组合
+///
+/// virtio-blk 只能承载磁盘，光驱需挂在 scsi、sata 或 ide 总线上
+pub const ALLOWED_DISK_COMBINATIONS: &[(DiskBusType, DiskDeviceType)] = &[
+    (DiskBusType::Virtio, DiskDeviceType::Disk),
+    (DiskBusType::Scsi, DiskDeviceType::Disk),
+    (DiskBusType::Scsi, DiskDeviceType::Cdrom),
+    (DiskBusType::Sata, DiskDeviceType::Disk),
+    (DiskBusType::Sata, DiskDeviceType::Cdrom),
+    (DiskBusType::Ide, DiskDeviceType::Disk),
+    (DiskBusType::Ide, DiskDeviceType::Cdrom),
+];
+
+/// 校验总线与设备类型组合是否受支持
+pub fn validate_disk_combination(bus: &DiskBusType, device: &DiskDeviceType) -> Result<(), String> {
+    if ALLOWED_DISK_COMBINATIONS
+        .iter()
+        .any(|(b, d)| b == bus && d == device)
+    {
+        return Ok(());
+    }
+
+    let allowed: Vec<&str> = ALLOWED_DISK_COMBINATIONS
+        .iter()
+        .filter(|(_, d)| d == device)
+        .map(|(b, _)| b.as_str())
+        .collect();
+    Err(format!(
+        "不支持的磁盘组合: {} 总线 + {}（{} 可用总线: {}）",
+        bus.as_str(),
+        device.as_str(),
+        device.as_str(),
+        allowed.join("/")
+    ))
+}
+
+/// 按总线与序号生成设备名（vda、sdb、hdc ...）
+///
+/// 沿用原有规则：非 sata 总线上的光驱始终使用 hd 前缀
+pub fn disk_device_name(bus: &DiskBusType, device: &DiskDeviceType, idx: usize) -> String {
+    let prefix = match (bus, device) {
+        (DiskBusType::Sata, _) => "sd",
+        (_, DiskDeviceType::Cdrom) => "hd",
+        (DiskBusType::Virtio, DiskDeviceType::Disk) => "vd",
+        (DiskBusType::Scsi, DiskDeviceType::Disk) => "sd",
+        (DiskBusType::Ide, DiskDeviceType::Disk) => "hd",
+    };
+    format!("{}{}", prefix, (b'a' + idx as u8) as char)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiskSpec {
     pub volume_id: String,
     pub device: String, // 自动生成的设备名
     pub volume_path: String,
-    pub bus_type: DiskBusType,       // 总线类型: virtio, scsi, ide
+    pub bus_type: DiskBusType,       // 总线类型: virtio, scsi, sata, ide
     pub device_type: DiskDeviceType, // 设备类型: disk, cdrom
     pub format: String,              // 磁盘格式: qcow2, raw, vmdk 等
+    #[serde(flatten)]
+    pub limits: DiskIoLimits,
+}
+
+/// qcow2 链接克隆的基础镜像，生成域 XML 时写入 `<backingStore>`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackingStore {
+    pub path: String,
+    pub format: String,
+}
+
+/// 磁盘 I/O 限速，未设置的项不限速
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskIoLimits {
+    /// 每秒读写次数上限（total_iops_sec）
+    #[serde(default)]
+    pub iops_limit: Option<u64>,
+    /// 每秒读写字节数上限（total_bytes_sec）
+    #[serde(default)]
+    pub bps_limit: Option<u64>,
+}
+
+impl DiskIoLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.iops_limit.is_none() && self.bps_limit.is_none()
+    }
+
+    /// libvirt 把 0 视为不限速，为避免歧义要求不限速时留空
+    pub fn validate(&self) -> Result<(), String> {
+        if self.iops_limit == Some(0) || self.bps_limit == Some(0) {
+            return Err("I/O 限速值必须大于 0，不限速请留空".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 网卡带宽限速（KiB/s，对应 libvirt `<bandwidth>` 的 average），未设置的方向不限速
+///
+/// inbound 为进入虚拟机的流量，outbound 为虚拟机发出的流量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NicBandwidth {
+    #[serde(default)]
+    pub inbound_kbps: Option<u32>,
+    #[serde(default)]
+    pub outbound_kbps: Option<u32>,
+}
+
+impl NicBandwidth {
+    pub fn is_unlimited(&self) -> bool {
+        self.inbound_kbps.is_none() && self.outbound_kbps.is_none()
+    }
+
+    /// libvirt 把 0 视为取消限速，为避免歧义要求不限速时留空
+    pub fn validate(&self) -> Result<(), String> {
+        if self.inbound_kbps == Some(0) || self.outbound_kbps == Some(0) {
+            return Err("带宽限速值必须大于 0，不限速请留空".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 安全组规则的协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecurityGroupProtocol {
+    Tcp,
+    Udp,
+    Icmp,
+    /// 任意协议
+    Any,
+}
+
+/// 安全组入站放行规则
+///
+/// 安全组默认拒绝所有入站流量（已建立连接的回包除外），只放行与任一规则匹配的流量
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecurityGroupRule {
+    pub protocol: SecurityGroupProtocol,
+    /// 目的端口范围（仅 tcp / udp），未设置时放行所有端口；只设置 port_from 时为单个端口
+    #[serde(default)]
+    pub port_from: Option<u16>,
+    #[serde(default)]
+    pub port_to: Option<u16>,
+    /// 源地址网段（IPv4 或 IPv6），未设置时放行所有来源
+    #[serde(default)]
+    pub cidr: Option<String>,
+}
+
+impl SecurityGroupRule {
+    pub fn validate(&self) -> Result<(), String> {
+        let has_ports = self.port_from.is_some() || self.port_to.is_some();
+        if has_ports
+            && !matches!(self.protocol, SecurityGroupProtocol::Tcp | SecurityGroupProtocol::Udp)
+        {
+            return Err("只有 tcp / udp 规则可以指定端口".to_string());
+        }
+        match (self.port_from, self.port_to) {
+            (None, Some(_)) => return Err("指定 port_to 时必须同时指定 port_from".to_string()),
+            (Some(0), _) => return Err("端口必须大于 0".to_string()),
+            (Some(from), Some(to)) if from > to => {
+                return Err(format!("端口范围无效: {}-{}", from, to));
+            }
+            _ => {}
+        }
+        if let Some(cidr) = &self.cidr {
+            if parse_cidr(cidr).is_none() {
+                return Err(format!("无效的网段: {}", cidr));
+            }
+        }
+        Ok(())
+    }
+
+    /// 源地址网段是否为 IPv6
+    pub fn is_ipv6_source(&self) -> bool {
+        self.cidr
+            .as_deref()
+            .and_then(parse_cidr)
+            .is_some_and(|(ip, _)| ip.is_ipv6())
+    }
+}
+
+/// 解析 `地址/前缀长度` 形式的网段，省略前缀长度时视为单个地址
+fn parse_cidr(cidr: &str) -> Option<(std::net::IpAddr, u8)> {
+    let (ip, prefix) = match cidr.split_once('/') {
+        Some((ip, prefix)) => (ip.parse::<std::net::IpAddr>().ok()?, Some(prefix.parse::<u8>().ok()?)),
+        None => (cidr.parse::<std::net::IpAddr>().ok()?, None),
+    };
+    let max = if ip.is_ipv6() { 128 } else { 32 };
+    let prefix = prefix.unwrap_or(max);
+    (prefix <= max).then_some((ip, prefix))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -139,11 +629,32 @@ pub struct VmOperationResponse {
     pub message: String,
 }
 
+/// 调整虚拟机 vCPU 数量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResizeVmCpuRequest {
+    pub vm_id: String,
+    pub vcpu: u32,
+    /// 是否同时作用于运行中的虚拟机，为 false 时只修改持久化定义
+    pub live: bool,
+}
+
+/// 调整虚拟机内存
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResizeVmMemoryRequest {
+    pub vm_id: String,
+    pub memory_mb: u64,
+    /// 是否同时作用于运行中的虚拟机，为 false 时只修改持久化定义
+    pub live: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VmAsyncOperationRequest {
     pub vm_id: String,
     #[serde(default)]
     pub force: bool,
+    /// Server 创建的任务 ID，完成通知中原样带回
+    #[serde(default)]
+    pub task_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -152,6 +663,27 @@ pub struct VmAsyncOperationResponse {
     pub message: String,
 }
 
+/// 查询虚拟机实时状态（直接读取 libvirt，不经过数据库）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetVmStateRequest {
+    pub vm_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetVmStateResponse {
+    pub vm_id: String,
+    /// 虚拟机是否已在节点上定义，未定义时其余字段无意义
+    pub exists: bool,
+    /// libvirt 域状态: running, blocked, paused, shutdown, shutoff, crashed, pmsuspended, nostate
+    pub state: String,
+    pub vcpu: u32,
+    pub memory_mb: u64,
+    pub max_memory_mb: u64,
+    /// QEMU 进程已运行的秒数，虚拟机未运行时为空
+    #[serde(default)]
+    pub uptime_secs: Option<u64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VmInfo {
     pub vm_id: String,
@@ -160,7 +692,9 @@ pub struct VmInfo {
     pub state: String,
     pub vcpu: u32,
     pub memory_mb: u64,
+    #[serde(default)]
     pub disks: Vec<DiskInfo>,
+    #[serde(default)]
     pub networks: Vec<NetworkInterfaceInfo>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub usage: Option<ResourceUsage>,
@@ -201,6 +735,36 @@ pub struct ListVmsResponse {
     pub vms: Vec<VmInfo>,
 }
 
+/// 查询虚拟机运行时统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetVmStatsRequest {
+    pub vm_id: String,
+}
+
+/// 虚拟机运行时统计
+///
+/// 除 rss_kb 外均为虚拟机启动以来的累计值，速率由接收方按两次采样的差值计算
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VmStats {
+    pub vm_id: String,
+    /// 所有 vCPU 累计占用的 CPU 时间（纳秒）
+    pub cpu_time_ns: u64,
+    /// QEMU 进程的常驻内存（KiB）
+    pub rss_kb: u64,
+    pub disk_read_bytes: u64,
+    pub disk_write_bytes: u64,
+    pub net_rx_bytes: u64,
+    pub net_tx_bytes: u64,
+}
+
+/// Agent 定期推送的 vm_metrics 通知，包含节点上所有运行中虚拟机的统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VmMetricsReport {
+    pub node_id: String,
+    pub timestamp: i64,
+    pub vms: Vec<VmStats>,
+}
+
 // ============================================================================
 // 虚拟机迁移
 // ============================================================================
@@ -210,7 +774,109 @@ pub struct MigrateVmRequest {
     pub vm_id: String,
     pub target_node_id: String,
     pub target_node_address: String,
+    /// 目标节点的 libvirt URI（如 `qemu+ssh://10.0.0.2/system`），由 Server 按节点地址生成；
+    /// 旧版本 Server 未下发时 Agent 按 `target_node_address` 使用 qemu+tcp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_uri: Option<String>,
     pub live_migration: bool,
+    /// 热迁移带宽上限（MiB/s），不设置时不限速；Agent 会把过小的值提升到下限
+    #[serde(default, alias = "max_mbps", skip_serializing_if = "Option::is_none")]
+    pub max_bandwidth_mbps: Option<u64>,
+    /// 热迁移最终切换阶段允许的最大停机时间（毫秒），不设置时使用 QEMU 默认值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_downtime_ms: Option<u64>,
+    /// 热迁移超时后的降级策略
+    #[serde(default)]
+    pub fallback_policy: MigrationFallbackPolicy,
+    /// 热迁移超时时间（秒），超过后按 `fallback_policy` 处理
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub live_timeout_secs: Option<u64>,
+    /// 磁盘是否位于两端共享的存储上
+    #[serde(default)]
+    pub storage_mode: MigrationStorageMode,
+}
+
+/// 热迁移时磁盘的处理方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationStorageMode {
+    /// 磁盘位于共享存储（如 NFS），只迁移内存与设备状态
+    #[default]
+    SharedStorage,
+    /// 磁盘不共享，随内存一起通过网络复制到目标节点预先创建的同尺寸空白卷
+    CopyStorage,
+}
+
+impl MigrationStorageMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MigrationStorageMode::SharedStorage => "shared_storage",
+            MigrationStorageMode::CopyStorage => "copy_storage",
+        }
+    }
+}
+
+impl std::str::FromStr for MigrationStorageMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shared_storage" => Ok(MigrationStorageMode::SharedStorage),
+            "copy_storage" => Ok(MigrationStorageMode::CopyStorage),
+            other => Err(format!("未知的迁移存储方式: {}", other)),
+        }
+    }
+}
+
+/// 热迁移无法在限定时间内收敛时的降级策略
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationFallbackPolicy {
+    /// 不降级，持续热迁移直至完成或失败
+    #[default]
+    None,
+    /// 超时后挂起虚拟机，以暂停状态完成剩余内存拷贝，在目标节点恢复运行
+    Suspend,
+}
+
+impl MigrationFallbackPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MigrationFallbackPolicy::None => "none",
+            MigrationFallbackPolicy::Suspend => "suspend",
+        }
+    }
+}
+
+impl std::str::FromStr for MigrationFallbackPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(MigrationFallbackPolicy::None),
+            "suspend" => Ok(MigrationFallbackPolicy::Suspend),
+            other => Err(format!("未知的迁移降级策略: {}", other)),
+        }
+    }
+}
+
+/// 热迁移实际采用的方式，随迁移完成通知上报
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationMode {
+    /// 虚拟机全程运行
+    Live,
+    /// 超时后挂起虚拟机完成迁移
+    Suspended,
+}
+
+impl MigrationMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MigrationMode::Live => "live",
+            MigrationMode::Suspended => "suspended",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -222,6 +888,118 @@ pub struct MigrationProgress {
     pub completed: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// 热迁移完成时实际采用的方式
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub migration_mode: Option<MigrationMode>,
+    /// 热迁移进行中 libvirt 估算的剩余时间（秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remaining_secs: Option<u64>,
+}
+
+/// 虚拟机当前的迁移带宽上限
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationSpeedResponse {
+    pub vm_id: String,
+    /// 带宽上限（MiB/s），0 表示不限速
+    pub max_mbps: u64,
+}
+
+// ============================================================================
+// 客户机命令执行（qemu-guest-agent）
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuestExecRequest {
+    pub vm_id: String,
+    /// 客户机内可执行文件路径
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// 等待命令结束的超时时间（秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+impl GuestExecRequest {
+    /// 默认等待命令结束的时间（秒）
+    pub const DEFAULT_TIMEOUT_SECS: u64 = 60;
+    /// 最长等待时间（秒），Agent 对更大的值按该上限处理
+    pub const MAX_TIMEOUT_SECS: u64 = 600;
+}
+
+/// 命令执行过程中通过 Stream 消息推送的输出片段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuestExecOutput {
+    pub vm_id: String,
+    pub pid: i64,
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+    pub exited: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuestExecResponse {
+    pub vm_id: String,
+    pub pid: i64,
+    pub exited: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// 输出超过 guest agent 缓冲上限被截断
+    #[serde(default)]
+    pub truncated: bool,
+    /// 超时后命令仍在运行
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+/// 通过 qemu-guest-agent 查询客户机内的网卡与 IP 地址
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetGuestNetworkRequest {
+    pub vm_id: String,
+}
+
+/// 客户机内的网卡，地址为 CIDR 形式（如 10.0.0.5/24），不含回环地址
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuestNetworkInterface {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac_address: Option<String>,
+    pub ipv4_addresses: Vec<String>,
+    pub ipv6_addresses: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetGuestNetworkResponse {
+    pub vm_id: String,
+    pub interfaces: Vec<GuestNetworkInterface>,
+}
+
+/// 读取虚拟机在 libvirt 中的域 XML，运行中为实时配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetDomainXmlRequest {
+    pub vm_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetDomainXmlResponse {
+    pub vm_id: String,
+    pub xml: String,
+}
+
+/// 修改运行中虚拟机的控制台密码
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetConsolePasswordRequest {
+    pub vm_id: String,
+    pub password: String,
+    /// 密码有效期（秒），过期后不再接受新的控制台连接；不设置时长期有效
+    #[serde(default)]
+    pub valid_secs: Option<u64>,
 }
 
 // ============================================================================
@@ -238,6 +1016,131 @@ pub struct CreateVolumeRequest {
     pub pool_id: String, // 存储池ID，Agent会自动获取存储池信息
     #[serde(skip_serializing_if = "Option::is_none")]
     pub source: Option<String>, // 外部URL，用于下载初始数据
+    #[serde(default)]
+    pub compress: bool, // 是否使用 zstd 压缩（仅 qcow2）
+    /// 下载内容的校验和（例如：sha256:<hex>），为空时不校验
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// 下载 http(s) 源地址时附加的请求头
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_headers: Option<HashMap<String, String>>,
+    /// 下载 http(s) 源地址时使用的认证信息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_auth: Option<SourceAuth>,
+    /// 基础镜像存储卷 ID，指定时创建以其为 backing file 的 qcow2 链接克隆
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backing_volume_id: Option<String>,
+    /// 使用 LUKS 加密（仅 qcow2）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<VolumeEncryption>,
+}
+
+/// 存储卷 LUKS 加密的密钥来源
+///
+/// Debug 输出不包含口令
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VolumeEncryption {
+    /// 直接提供的口令
+    Passphrase { passphrase: String },
+    /// Agent 节点密钥目录（ENCRYPTION_KEY_DIR）下的密钥文件，文件内容即口令
+    KeyFile { key_id: String },
+}
+
+impl std::fmt::Debug for VolumeEncryption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VolumeEncryption::Passphrase { .. } => {
+                f.debug_struct("Passphrase").field("passphrase", &"***").finish()
+            }
+            VolumeEncryption::KeyFile { key_id } => {
+                f.debug_struct("KeyFile").field("key_id", key_id).finish()
+            }
+        }
+    }
+}
+
+/// 下载存储卷源文件的认证方式
+///
+/// Debug 输出不包含密码和令牌
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SourceAuth {
+    Basic { username: String, password: String },
+    Bearer { token: String },
+}
+
+impl std::fmt::Debug for SourceAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SourceAuth::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &"***")
+                .finish(),
+            SourceAuth::Bearer { .. } => f.debug_struct("Bearer").field("token", &"***").finish(),
+        }
+    }
+}
+
+/// 校验和算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl ChecksumAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Sha512 => "sha512",
+        }
+    }
+
+    /// 十六进制摘要的长度
+    fn hex_len(&self) -> usize {
+        match self {
+            ChecksumAlgorithm::Sha256 => 64,
+            ChecksumAlgorithm::Sha512 => 128,
+        }
+    }
+}
+
+/// 存储卷源文件的校验和，格式为 `<算法>:<十六进制摘要>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeChecksum {
+    pub algorithm: ChecksumAlgorithm,
+    /// 小写十六进制摘要
+    pub digest: String,
+}
+
+impl std::str::FromStr for VolumeChecksum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, digest) = s
+            .split_once(':')
+            .ok_or_else(|| format!("无效的校验和格式（应为 <算法>:<摘要>）: {}", s))?;
+        let algorithm = match algorithm.to_ascii_lowercase().as_str() {
+            "sha256" => ChecksumAlgorithm::Sha256,
+            "sha512" => ChecksumAlgorithm::Sha512,
+            other => return Err(format!("不支持的校验和算法: {}（支持 sha256、sha512）", other)),
+        };
+        if digest.len() != algorithm.hex_len() || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("无效的 {} 摘要: {}", algorithm.as_str(), digest));
+        }
+        Ok(Self {
+            algorithm,
+            digest: digest.to_ascii_lowercase(),
+        })
+    }
+}
+
+impl std::fmt::Display for VolumeChecksum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.algorithm.as_str(), self.digest)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -257,6 +1160,22 @@ pub struct CreateVolumeResponse {
     pub path: Option<String>,
 }
 
+/// 从 URL 创建存储卷期间通过 Stream 消息推送的进度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeCreateProgress {
+    pub volume_id: String,
+    /// 当前阶段：下载源文件（download）或 qemu-img 格式转换（convert）
+    pub stage: String,
+    /// 完成百分比，下载阶段服务端未返回 Content-Length 时为 0
+    pub progress_percent: f64,
+    /// 下载阶段已下载的字节数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downloaded_bytes: Option<u64>,
+    /// 下载阶段的文件总大小，未知时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_bytes: Option<u64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeleteVolumeRequest {
     pub volume_id: String,
@@ -323,12 +1242,42 @@ pub struct RestoreSnapshotResponse {
     pub message: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListVolumeSnapshotsRequest {
+    pub volume_id: String,
+    pub pool_id: String,
+}
+
+/// 磁盘文件中实际存在的快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeSnapshotInfo {
+    /// 快照标签（即创建时使用的 snapshot_id）
+    pub name: String,
+    /// 内存状态大小（字节），磁盘快照为 0
+    pub vm_state_size: u64,
+    /// 快照创建时间（Unix 时间戳，秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListVolumeSnapshotsResponse {
+    pub volume_id: String,
+    pub snapshots: Vec<VolumeSnapshotInfo>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CloneVolumeRequest {
     pub source_volume_id: String,
     pub target_volume_id: String,
     pub target_name: String,
     pub pool_id: String,
+    /// 从源卷的指定快照克隆，未指定时克隆当前数据
+    #[serde(default)]
+    pub snapshot_id: Option<String>,
+    /// 目标存储池（须与源存储池在同一节点），未指定时克隆到源存储池
+    #[serde(default)]
+    pub target_pool_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -339,6 +1288,129 @@ pub struct CloneVolumeResponse {
     pub path: Option<String>,
 }
 
+/// 导入已存在镜像的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeImportMode {
+    /// 在存储池中链接到原文件，不复制数据，删除卷时保留原文件
+    #[default]
+    Reference,
+    /// 将原文件完整复制为存储池中的新卷文件，原文件保持不变
+    Copy,
+}
+
+/// 把存储池中已存在的磁盘镜像登记为受管存储卷
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportVolumeRequest {
+    pub pool_id: String,
+    pub volume_id: String,
+    /// 镜像文件的绝对路径，须位于存储池目录内
+    pub path: String,
+    #[serde(default)]
+    pub mode: VolumeImportMode,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportVolumeResponse {
+    pub success: bool,
+    pub message: String,
+    pub path: String,
+    /// qemu-img info 检测到的镜像格式（qcow2 或 raw）
+    pub format: String,
+    /// 虚拟大小（GB，向上取整）
+    pub size_gb: u64,
+    /// 解析符号链接与 `..` 后的源镜像路径
+    #[serde(default)]
+    pub source_path: String,
+}
+
+/// 查询存储池底层存储的实际容量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetPoolUsageRequest {
+    pub pool_id: String,
+}
+
+/// 存储池的实际容量（字节）：NFS 为挂载点文件系统，LVM 为卷组
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetPoolUsageResponse {
+    pub pool_id: String,
+    pub total_bytes: u64,
+    pub used_bytes: u64,
+    pub available_bytes: u64,
+}
+
+/// 准备卷的下载导出：Agent 将卷转换为一致的副本，后续分段读取
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrepareVolumeExportRequest {
+    pub pool_id: String,
+    pub volume_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrepareVolumeExportResponse {
+    pub volume_id: String,
+    /// 导出副本格式，与源卷一致（qcow2 或 raw）
+    pub format: String,
+    /// 导出副本的字节数
+    pub size_bytes: u64,
+}
+
+/// 读取导出副本的一段数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadVolumeExportRequest {
+    pub pool_id: String,
+    pub volume_id: String,
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadVolumeExportResponse {
+    pub offset: u64,
+    /// base64 编码的数据
+    pub data: String,
+    /// 是否已读到副本末尾
+    pub eof: bool,
+}
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListOrphanedVolumesRequest {
+    pub pool_id: String,
+    /// 数据库中该存储池的全部卷 ID
+    pub known_volume_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphanedVolumeFile {
+    pub file_name: String,
+    pub path: String,
+    pub size_bytes: u64,
+    pub modified_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListOrphanedVolumesResponse {
+    pub pool_id: String,
+    pub orphans: Vec<OrphanedVolumeFile>,
+}
+
+/// 删除孤立文件（Agent 删除前会重新核对，仅删除仍为孤立状态的文件）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteOrphanedVolumesRequest {
+    pub pool_id: String,
+    pub known_volume_ids: Vec<String>,
+    pub file_names: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteOrphanedVolumesResponse {
+    pub pool_id: String,
+    pub deleted: Vec<String>,
+    /// 未删除的文件（已不再是孤立文件或删除失败）
+    pub skipped: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetVolumeInfoRequest {
     pub volume_id: String,
@@ -380,6 +1452,9 @@ pub struct CreateNetworkRequest {
     pub bridge_name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub vlan_id: Option<String>,
+    /// Bridge 与 VLAN 子接口的 MTU，未下发时为 1500
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -391,6 +1466,14 @@ pub struct CreateNetworkResponse {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeleteNetworkRequest {
     pub network_id: String,
+    /// 网络类型（bridge / ovs），未下发时按 bridge 处理
+    #[serde(default, rename = "type")]
+    pub network_type: Option<String>,
+    /// 未下发时按 VLAN ID 生成
+    #[serde(default)]
+    pub bridge_name: Option<String>,
+    #[serde(default)]
+    pub vlan_id: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -423,6 +1506,38 @@ pub struct DetachInterfaceResponse {
     pub message: String,
 }
 
+/// 托管网络的 DHCP/DNS 配置，Agent 在网络 Bridge 上以 dnsmasq 提供服务
+///
+/// 只下发静态租约（IP 分配记录中的 MAC/IP），不做动态分配，多个节点同时服务时应答一致
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpConfig {
+    pub network_id: String,
+    pub bridge_name: String,
+    /// IPv4 网段
+    pub cidr: String,
+    /// 网关地址，同时作为 dnsmasq 在 Bridge 上的监听地址
+    pub gateway: String,
+    #[serde(default)]
+    pub leases: Vec<DhcpLease>,
+}
+
+/// DHCP 静态租约
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpLease {
+    pub mac_address: String,
+    pub ip_address: String,
+    /// 虚拟机名称，同时注册为 DNS 主机名
+    #[serde(default)]
+    pub hostname: Option<String>,
+}
+
+/// 停止网络的 DHCP 服务（网络关闭 DHCP 时下发）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StopDhcpRequest {
+    pub network_id: String,
+    pub bridge_name: String,
+}
+
 // ============================================================================
 // 虚拟机存储卷管理
 // ============================================================================
@@ -435,6 +1550,12 @@ pub struct AttachVolumeRequest {
     pub bus_type: DiskBusType,
     pub device_type: DiskDeviceType,
     pub format: String,
+    #[serde(flatten)]
+    pub limits: DiskIoLimits,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backing: Option<BackingStore>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<VolumeEncryption>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -457,6 +1578,37 @@ pub struct DetachVolumeResponse {
     pub message: String,
 }
 
+/// 调整运行中虚拟机磁盘的 I/O 限速
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetVolumeIotuneRequest {
+    pub vm_id: String,
+    pub volume_id: String,
+    #[serde(flatten)]
+    pub limits: DiskIoLimits,
+}
+
+/// 调整运行中虚拟机网卡的带宽限速，网卡按 MAC 地址定位
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetNicBandwidthRequest {
+    pub vm_id: String,
+    pub mac_address: String,
+    #[serde(flatten)]
+    pub limits: NicBandwidth,
+}
+
+/// 将安全组规则下发到运行中虚拟机的网卡（按 MAC 地址定位）
+///
+/// security_group_id 为空表示网卡不再关联安全组，Agent 删除该网卡上的过滤规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplySecurityGroupRequest {
+    pub vm_id: String,
+    pub mac_address: String,
+    #[serde(default)]
+    pub security_group_id: Option<String>,
+    #[serde(default)]
+    pub rules: Vec<SecurityGroupRule>,
+}
+
 // ============================================================================
 // Agent 注册
 // ============================================================================
@@ -466,12 +1618,27 @@ pub struct RegisterRequest {
     pub node_id: String,
     pub hostname: String,
     pub ip_address: String,
+    /// Agent 希望使用的消息编码，旧版本 Agent 不声明时为 JSON
+    #[serde(default)]
+    pub codec: RpcCodec,
+    /// Agent 是否能解析 gzip 压缩帧，旧版本 Agent 不声明时不向其发送压缩帧
+    #[serde(default)]
+    pub compression: bool,
+    /// 注册共享密钥，Server 启用认证时必须与配置一致
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegisterResponse {
     pub success: bool,
     pub message: String,
+    /// 注册后双方使用的消息编码，旧版本 Server 不返回时为 JSON
+    #[serde(default)]
+    pub codec: RpcCodec,
+    /// Server 是否能解析 gzip 压缩帧，为 false 时 Agent 不发送压缩帧
+    #[serde(default)]
+    pub compression: bool,
 }
 
 // ============================================================================
@@ -487,6 +1654,25 @@ pub struct NodeResourceInfo {
     pub disk_total: u64,   // bytes
     pub hypervisor_type: Option<String>,
     pub hypervisor_version: Option<String>,
+    /// CPU 利用率（百分比），旧版本 Agent 不上报
+    #[serde(default)]
+    pub cpu_usage: Option<f32>,
+    #[serde(default)]
+    pub memory_used: Option<u64>, // bytes
+    #[serde(default)]
+    pub disk_used: Option<u64>, // bytes
+    /// 是否安装 swtpm，旧版本 Agent 不上报
+    #[serde(default)]
+    pub has_swtpm: Option<bool>,
+    /// 是否安装 Open vSwitch，旧版本 Agent 不上报
+    #[serde(default)]
+    pub has_ovs: Option<bool>,
+    /// 宿主机 NUMA 拓扑，旧版本 Agent 不上报
+    #[serde(default)]
+    pub numa_nodes: Option<Vec<HostNumaNode>>,
+    /// 已安装 QEMU 模拟器的架构，旧版本 Agent 不上报
+    #[serde(default)]
+    pub supported_architectures: Option<Vec<String>>,
     pub timestamp: i64,
 }
 
@@ -495,3 +1681,187 @@ pub struct NodeResourceInfoResponse {
     pub success: bool,
     pub message: String,
 }
+
+/// 节点主机级指标采样，Agent 按配置的间隔以 node_metrics 通知上报
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeMetricsSample {
+    pub node_id: String,
+    pub timestamp: i64,
+    /// CPU 利用率（百分比）
+    pub cpu_usage: f32,
+    /// 1 分钟平均负载
+    pub load_avg_1: f64,
+    pub memory_total: u64, // bytes
+    /// 可用内存（含可回收的缓存）
+    pub memory_free: u64, // bytes
+    pub disks: Vec<DiskUsageSample>,
+}
+
+/// 单个挂载点的磁盘使用情况
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiskUsageSample {
+    pub mount_point: String,
+    pub total: u64,     // bytes
+    pub available: u64, // bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_security_group_rule_validate() {
+        let rule = |protocol, port_from, port_to, cidr: Option<&str>| SecurityGroupRule {
+            protocol,
+            port_from,
+            port_to,
+            cidr: cidr.map(str::to_string),
+        };
+        assert!(rule(SecurityGroupProtocol::Tcp, Some(22), None, Some("10.0.0.0/8")).validate().is_ok());
+        assert!(rule(SecurityGroupProtocol::Udp, Some(1000), Some(2000), None).validate().is_ok());
+        assert!(rule(SecurityGroupProtocol::Any, None, None, Some("2001:db8::/32")).validate().is_ok());
+        assert!(rule(SecurityGroupProtocol::Icmp, Some(1), None, None).validate().is_err());
+        assert!(rule(SecurityGroupProtocol::Tcp, None, Some(80), None).validate().is_err());
+        assert!(rule(SecurityGroupProtocol::Tcp, Some(0), None, None).validate().is_err());
+        assert!(rule(SecurityGroupProtocol::Tcp, Some(443), Some(80), None).validate().is_err());
+        assert!(rule(SecurityGroupProtocol::Tcp, Some(80), None, Some("10.0.0.0/33")).validate().is_err());
+        assert!(rule(SecurityGroupProtocol::Tcp, Some(80), None, Some("bad")).validate().is_err());
+
+        assert!(rule(SecurityGroupProtocol::Any, None, None, Some("2001:db8::1")).is_ipv6_source());
+        assert!(!rule(SecurityGroupProtocol::Any, None, None, Some("10.0.0.1")).is_ipv6_source());
+        assert!(!rule(SecurityGroupProtocol::Any, None, None, None).is_ipv6_source());
+    }
+
+    #[test]
+    fn test_volume_checksum_parse() {
+        let digest = "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855";
+        let checksum: VolumeChecksum = format!("SHA256:{}", digest).parse().unwrap();
+        assert_eq!(checksum.algorithm, ChecksumAlgorithm::Sha256);
+        assert_eq!(checksum.to_string(), format!("sha256:{}", digest.to_ascii_lowercase()));
+
+        assert!(format!("sha512:{}", "ab".repeat(64)).parse::<VolumeChecksum>().is_ok());
+        assert!(digest.parse::<VolumeChecksum>().is_err());
+        assert!(format!("md5:{}", &digest[..32]).parse::<VolumeChecksum>().is_err());
+        assert!("sha256:abc".parse::<VolumeChecksum>().is_err());
+        assert!(format!("sha256:{}", "zz".repeat(32)).parse::<VolumeChecksum>().is_err());
+    }
+
+    #[test]
+    fn test_source_auth_serde_and_debug_hide_secrets() {
+        let auth: SourceAuth = serde_json::from_value(serde_json::json!({
+            "type": "basic",
+            "username": "alice",
+            "password": "s3cret"
+        }))
+        .unwrap();
+        assert_eq!(
+            auth,
+            SourceAuth::Basic { username: "alice".to_string(), password: "s3cret".to_string() }
+        );
+        assert!(!format!("{:?}", auth).contains("s3cret"));
+
+        let auth = SourceAuth::Bearer { token: "tok-123".to_string() };
+        assert_eq!(serde_json::to_value(&auth).unwrap()["type"], "bearer");
+        assert!(!format!("{:?}", auth).contains("tok-123"));
+    }
+
+    #[test]
+    fn test_volume_encryption_serde_and_debug_hide_passphrase() {
+        let encryption: VolumeEncryption = serde_json::from_value(serde_json::json!({
+            "type": "passphrase",
+            "passphrase": "correct horse"
+        }))
+        .unwrap();
+        assert!(!format!("{:?}", encryption).contains("correct horse"));
+
+        let encryption = VolumeEncryption::KeyFile { key_id: "tenant-a".to_string() };
+        assert_eq!(
+            serde_json::to_value(&encryption).unwrap(),
+            serde_json::json!({ "type": "key_file", "key_id": "tenant-a" })
+        );
+        assert!(format!("{:?}", encryption).contains("tenant-a"));
+    }
+
+    #[test]
+    fn test_disk_type_parsing_is_strict() {
+        assert_eq!("scsi".parse::<DiskBusType>(), Ok(DiskBusType::Scsi));
+        assert_eq!("cdrom".parse::<DiskDeviceType>(), Ok(DiskDeviceType::Cdrom));
+        assert_eq!("sata".parse::<DiskBusType>(), Ok(DiskBusType::Sata));
+        assert!("usb".parse::<DiskBusType>().is_err());
+        assert!("floppy".parse::<DiskDeviceType>().is_err());
+    }
+
+    #[test]
+    fn test_validate_disk_combination() {
+        assert!(validate_disk_combination(&DiskBusType::Virtio, &DiskDeviceType::Disk).is_ok());
+        assert!(validate_disk_combination(&DiskBusType::Ide, &DiskDeviceType::Cdrom).is_ok());
+
+        let err = validate_disk_combination(&DiskBusType::Virtio, &DiskDeviceType::Cdrom).unwrap_err();
+        assert!(err.contains("scsi/sata/ide"));
+    }
+
+    #[test]
+    fn test_validate_cpu_placement() {
+        let host = vec![
+            HostNumaNode { id: 0, cpus: vec![0, 1, 2, 3], memory_mb: 16384 },
+            HostNumaNode { id: 1, cpus: vec![4, 5, 6, 7], memory_mb: 16384 },
+        ];
+        let pinning: CpuPinning = [(0, 2), (1, 6)].into_iter().collect();
+        let numa = NumaConfig { host_nodes: vec![0, 1], mode: NumaMode::Interleave, guest_nodes: 2 };
+        assert!(validate_cpu_placement(2, Some(&pinning), Some(&numa), &host).is_ok());
+        assert!(validate_cpu_placement(2, None, None, &[]).is_ok());
+
+        let err = validate_cpu_placement(2, Some(&[(0, 8)].into_iter().collect()), None, &host).unwrap_err();
+        assert!(err.contains("物理 CPU 8 不存在"));
+        assert!(validate_cpu_placement(1, Some(&pinning), None, &host).is_err());
+
+        let numa = NumaConfig { host_nodes: vec![2], ..Default::default() };
+        assert!(validate_cpu_placement(2, None, Some(&numa), &host).is_err());
+        let numa = NumaConfig { guest_nodes: 4, ..Default::default() };
+        assert!(validate_cpu_placement(2, None, Some(&numa), &host).is_err());
+    }
+
+    #[test]
+    fn test_disk_device_name() {
+        assert_eq!(disk_device_name(&DiskBusType::Virtio, &DiskDeviceType::Disk, 0), "vda");
+        assert_eq!(disk_device_name(&DiskBusType::Sata, &DiskDeviceType::Disk, 1), "sdb");
+        assert_eq!(disk_device_name(&DiskBusType::Sata, &DiskDeviceType::Cdrom, 2), "sdc");
+        assert_eq!(disk_device_name(&DiskBusType::Ide, &DiskDeviceType::Cdrom, 3), "hdd");
+    }
+
+    #[test]
+    fn test_migrate_request_defaults() {
+        // 未携带迁移限制和降级策略的旧版请求
+        let req: MigrateVmRequest = serde_json::from_value(serde_json::json!({
+            "vm_id": "vm-1",
+            "target_node_id": "node-2",
+            "target_node_address": "10.0.0.2",
+            "live_migration": true
+        }))
+        .unwrap();
+        assert_eq!(req.fallback_policy, MigrationFallbackPolicy::None);
+        assert_eq!(req.live_timeout_secs, None);
+        assert_eq!(req.max_bandwidth_mbps, None);
+        assert_eq!(req.target_uri, None);
+        assert_eq!(req.storage_mode, MigrationStorageMode::SharedStorage);
+
+        let value = serde_json::to_value(&req).unwrap();
+        assert_eq!(value["fallback_policy"], "none");
+        assert!(value.get("live_timeout_secs").is_none());
+
+        assert_eq!("suspend".parse(), Ok(MigrationFallbackPolicy::Suspend));
+        assert_eq!("copy_storage".parse(), Ok(MigrationStorageMode::CopyStorage));
+        assert_eq!(serde_json::to_value(MigrationMode::Suspended).unwrap(), "suspended");
+
+        // max_mbps 是 max_bandwidth_mbps 的别名
+        let req: MigrateVmRequest = serde_json::from_value(serde_json::json!({
+            "vm_id": "vm-1",
+            "target_node_id": "node-2",
+            "target_node_address": "10.0.0.2",
+            "live_migration": true,
+            "max_mbps": 100
+        }))
+        .unwrap();
+        assert_eq!(req.max_bandwidth_mbps, Some(100));
+    }
+}
@@ -0,0 +1,13 @@
+-- ============================================================================
+-- 系统管理权限
+-- ============================================================================
+
+-- 系统配置权限（切换只读维护模式等）
+INSERT INTO permissions (name, description, resource, action) VALUES
+('系统配置', '切换只读维护模式等系统级配置', 'system', 'configure')
+ON CONFLICT DO NOTHING;
+
+-- 为超级管理员角色分配系统配置权限
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT 1, id FROM permissions WHERE resource = 'system' AND action = 'configure'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,5 @@
+-- 节点 IPMI 带外管理配置
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS ipmi_address VARCHAR(255);
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS ipmi_username VARCHAR(255);
+-- 仅服务端内部使用，不会通过 API 返回
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS ipmi_password TEXT;
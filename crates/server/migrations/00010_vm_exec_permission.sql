@@ -0,0 +1,12 @@
+-- ============================================================================
+-- 虚拟机命令执行权限
+-- ============================================================================
+
+-- 通过 qemu-guest-agent 在虚拟机内执行命令，权限较大，默认仅超级管理员拥有
+INSERT INTO permissions (name, description, resource, action) VALUES
+('执行虚拟机命令', '通过 guest agent 在虚拟机内执行命令', 'vm', 'exec')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT 1, id FROM permissions WHERE resource = 'vm' AND action = 'exec'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,2 @@
+-- 宿主机计划内重启时运行中虚拟机的处理策略：shutdown（优雅关机）/ suspend（挂起到磁盘）
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS shutdown_policy VARCHAR(20) NOT NULL DEFAULT 'shutdown';
@@ -0,0 +1,21 @@
+-- 亲和/反亲和组
+CREATE TABLE IF NOT EXISTS affinity_groups (
+    id VARCHAR(36) PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    policy VARCHAR(20) NOT NULL,  -- affinity: 尽量放在同一节点, anti-affinity: 必须分散在不同节点
+    description TEXT,
+
+    -- 时间戳
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- 亲和组成员
+CREATE TABLE IF NOT EXISTS affinity_group_members (
+    group_id VARCHAR(36) NOT NULL REFERENCES affinity_groups(id) ON DELETE CASCADE,
+    vm_id VARCHAR(36) NOT NULL REFERENCES vms(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (group_id, vm_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_affinity_group_members_vm_id ON affinity_group_members(vm_id);
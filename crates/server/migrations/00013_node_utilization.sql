@@ -0,0 +1,4 @@
+-- 节点资源利用率（由 Agent 定期上报，用于计算节点健康度与告警）
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS cpu_usage REAL;
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS memory_used BIGINT;
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS disk_used BIGINT;
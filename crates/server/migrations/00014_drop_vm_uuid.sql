@@ -0,0 +1,3 @@
+-- 虚拟机 id 即 libvirt 域 UUID，移除从未使用的冗余 uuid 列
+DROP INDEX IF EXISTS idx_vms_uuid;
+ALTER TABLE vms DROP COLUMN IF EXISTS uuid;
@@ -0,0 +1,18 @@
+-- Webhook 订阅：集群/节点状态越过阈值时向外部系统推送事件
+CREATE TABLE IF NOT EXISTS webhooks (
+    id VARCHAR(36) PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    url TEXT NOT NULL,
+    secret VARCHAR(255),               -- 非空时随请求以 X-Webhook-Secret 头发送
+    events JSONB NOT NULL DEFAULT '[]', -- 订阅的事件类型，"*" 表示全部
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+
+    -- 最近一次投递结果
+    last_status VARCHAR(20),           -- success, failed
+    last_error TEXT,
+    last_delivered_at TIMESTAMP WITH TIME ZONE,
+
+    -- 时间戳
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
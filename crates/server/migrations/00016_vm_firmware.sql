@@ -0,0 +1,2 @@
+-- 虚拟机固件类型：bios / uefi，已有虚拟机保持 BIOS 启动
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS firmware VARCHAR(10) NOT NULL DEFAULT 'bios';
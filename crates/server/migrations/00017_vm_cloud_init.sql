@@ -0,0 +1,2 @@
+-- 虚拟机 cloud-init 配置（user_data / meta_data / network_config）
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS cloud_init JSONB;
@@ -0,0 +1,3 @@
+-- 虚拟机 VNC 控制台端口与地址（Agent 启动虚拟机后上报）
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS vnc_port INTEGER;
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS vnc_host VARCHAR(255);
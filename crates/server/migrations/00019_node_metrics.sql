@@ -0,0 +1,13 @@
+-- 节点主机指标历史（Agent 定期上报 node_metrics，保留最近 24 小时）
+CREATE TABLE IF NOT EXISTS node_metrics (
+    id BIGSERIAL PRIMARY KEY,
+    node_id VARCHAR(36) NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+    sampled_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    cpu_usage REAL NOT NULL,            -- 百分比
+    load_avg DOUBLE PRECISION NOT NULL, -- 1 分钟平均负载
+    memory_total BIGINT NOT NULL,       -- bytes
+    memory_free BIGINT NOT NULL,        -- bytes
+    disks JSONB NOT NULL DEFAULT '[]'   -- [{mount_point, total, available}]
+);
+
+CREATE INDEX IF NOT EXISTS idx_node_metrics_node_sampled ON node_metrics(node_id, sampled_at);
@@ -0,0 +1,5 @@
+-- 虚拟机是否挂载模拟 TPM 2.0 设备（Windows 11 等要求）
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS tpm BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- 节点是否安装 swtpm（模拟 TPM 的后端），由 Agent 上报资源信息时更新
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS has_swtpm BOOLEAN NOT NULL DEFAULT FALSE;
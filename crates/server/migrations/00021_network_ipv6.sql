@@ -0,0 +1,6 @@
+-- 双栈网络的 IPv6 网段与网关（cidr 本身也可以是 IPv6 网段，此时为纯 IPv6 网络）
+ALTER TABLE networks ADD COLUMN IF NOT EXISTS cidr_v6 VARCHAR(50);
+ALTER TABLE networks ADD COLUMN IF NOT EXISTS gateway_v6 VARCHAR(45);
+
+-- IPv6 地址按需分配，同一网络内的地址不能重复
+CREATE UNIQUE INDEX IF NOT EXISTS idx_ip_allocations_network_ip ON ip_allocations(network_id, ip_address);
@@ -0,0 +1,2 @@
+-- 运维人员手动预留的静态 IP，虚拟机释放后仍保持预留，直到删除预留
+ALTER TABLE ip_allocations ADD COLUMN IF NOT EXISTS is_static BOOLEAN NOT NULL DEFAULT FALSE;
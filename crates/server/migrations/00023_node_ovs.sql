@@ -0,0 +1,2 @@
+-- 节点是否安装 Open vSwitch，由 Agent 上报资源信息时更新
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS has_ovs BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,14 @@
+-- 安全组：一组入站放行规则，由 Agent 以 nftables 规则下发到虚拟机的 tap 设备
+CREATE TABLE IF NOT EXISTS security_groups (
+    id VARCHAR(36) PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    description TEXT,
+    rules JSONB NOT NULL DEFAULT '[]', -- [{ "protocol": "tcp", "port_from": 22, "port_to": null, "cidr": "10.0.0.0/8" }]
+
+    -- 时间戳
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- 网络默认安全组，网卡未单独指定安全组时使用
+ALTER TABLE networks ADD COLUMN IF NOT EXISTS security_group_id VARCHAR(36) REFERENCES security_groups(id);
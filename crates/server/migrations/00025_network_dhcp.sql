@@ -0,0 +1,2 @@
+-- 托管 DHCP/DNS：Agent 在网络 Bridge 上启动 dnsmasq，按 IP 分配记录下发静态租约
+ALTER TABLE networks ADD COLUMN IF NOT EXISTS dhcp_enabled BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- qcow2 链接克隆：记录作为 backing file 的基础镜像卷，基础镜像仍有链接克隆时不允许删除
+ALTER TABLE volumes ADD COLUMN IF NOT EXISTS backing_volume_id VARCHAR(36) REFERENCES volumes(id) ON DELETE RESTRICT;
+CREATE INDEX IF NOT EXISTS idx_volumes_backing_volume_id ON volumes(backing_volume_id);
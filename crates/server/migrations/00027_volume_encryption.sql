@@ -0,0 +1,2 @@
+-- LUKS 加密存储卷：保存密钥来源（口令或 Agent 节点上的密钥文件 ID），启动虚拟机时随磁盘下发
+ALTER TABLE volumes ADD COLUMN IF NOT EXISTS encryption JSONB;
@@ -0,0 +1,2 @@
+-- 存储池实际占用：由 Server 定期向 Agent 查询底层存储（NFS 挂载点 / LVM 卷组）后刷新
+ALTER TABLE storage_pools ADD COLUMN IF NOT EXISTS used_gb BIGINT;
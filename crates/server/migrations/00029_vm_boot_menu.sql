@@ -0,0 +1,2 @@
+-- 启动时是否显示固件引导菜单；各磁盘的引导顺序保存在 vms.volumes 的 boot_order 字段中
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS boot_menu BOOLEAN NOT NULL DEFAULT FALSE;
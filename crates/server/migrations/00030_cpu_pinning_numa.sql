@@ -0,0 +1,6 @@
+-- 节点上报的宿主机 NUMA 拓扑，用于校验虚拟机的 CPU 绑定
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS numa_topology JSONB;
+
+-- 虚拟机 vCPU 绑定（vCPU -> 物理 CPU）与 NUMA 配置
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS cpu_pinning JSONB;
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS numa JSONB;
@@ -0,0 +1,11 @@
+-- 自定义域 XML，存在时 Agent 原样定义而不再按配置生成
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS raw_xml_override TEXT;
+
+-- 自定义域 XML 可挂载宿主机任意设备和文件，权限较大，默认仅超级管理员拥有
+INSERT INTO permissions (name, description, resource, action) VALUES
+('自定义虚拟机域 XML', '设置虚拟机的自定义 libvirt 域 XML', 'vm', 'xml')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT 1, id FROM permissions WHERE resource = 'vm' AND action = 'xml'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,3 @@
+-- 客户机架构与机器类型，未指定机器类型时由 Agent 按 libvirt capabilities 选择
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS arch VARCHAR(32) NOT NULL DEFAULT 'x86_64';
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS machine_type VARCHAR(64);
@@ -0,0 +1,5 @@
+-- 图形控制台协议：vnc / spice，下次启动生效
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS graphics VARCHAR(10) NOT NULL DEFAULT 'vnc';
+
+-- 运行中控制台实际使用的协议，与 vnc_port / vnc_host 一同由 Agent 上报
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS console_protocol VARCHAR(10);
@@ -0,0 +1,24 @@
+-- 快照策略：按 cron 表达式定期为存储卷或虚拟机的全部磁盘创建快照，并只保留最近 N 个
+CREATE TABLE IF NOT EXISTS snapshot_policies (
+    id VARCHAR(36) PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    target_type VARCHAR(10) NOT NULL,  -- volume, vm
+    target_id VARCHAR(36) NOT NULL,
+    schedule VARCHAR(100) NOT NULL,    -- cron 表达式（分 时 日 月 周，UTC）
+    keep_last INTEGER NOT NULL,        -- 每个卷保留的策略快照数量
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+
+    -- 最近一次执行结果，服务重启后据此计算下一次执行时间
+    last_run_at TIMESTAMP WITH TIME ZONE,
+    last_status VARCHAR(20),           -- success, failed
+    last_error TEXT,
+
+    -- 时间戳
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_snapshot_policies_target ON snapshot_policies(target_type, target_id);
+
+-- 受保护的快照不会被自动清理
+ALTER TABLE snapshots ADD COLUMN IF NOT EXISTS protected BOOLEAN NOT NULL DEFAULT FALSE;
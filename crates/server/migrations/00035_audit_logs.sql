@@ -0,0 +1,15 @@
+-- 审计日志：记录所有 API 写操作（谁、何时、对什么资源、结果如何）以及 Agent 上报的虚拟机状态变更
+-- 沿用 00007 创建的 audit_logs 表，补充操作来源与 HTTP 请求信息；不记录请求体，避免密码等敏感信息落库
+ALTER TABLE audit_logs ADD COLUMN IF NOT EXISTS actor VARCHAR(20) NOT NULL DEFAULT 'user'; -- user, system
+ALTER TABLE audit_logs ADD COLUMN IF NOT EXISTS method VARCHAR(10);   -- HTTP 方法，系统事件为空
+ALTER TABLE audit_logs ADD COLUMN IF NOT EXISTS path TEXT;            -- 不含查询参数，系统事件为空
+ALTER TABLE audit_logs ADD COLUMN IF NOT EXISTS status_code INTEGER;  -- HTTP 状态码，系统事件为空
+
+-- 审计日志包含所有用户的操作记录，默认仅超级管理员可查看
+INSERT INTO permissions (name, description, resource, action) VALUES
+('查看审计日志', '查看所有用户的写操作审计记录', 'audit', 'read')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT 1, id FROM permissions WHERE resource = 'audit' AND action = 'read'
+ON CONFLICT DO NOTHING;
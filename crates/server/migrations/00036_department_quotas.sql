@@ -0,0 +1,17 @@
+-- 部门资源配额：限制部门成员名下虚拟机的 vCPU、内存以及存储卷的数量与总容量，NULL 表示不限制
+ALTER TABLE departments ADD COLUMN IF NOT EXISTS max_vcpu INTEGER;
+ALTER TABLE departments ADD COLUMN IF NOT EXISTS max_memory_mb BIGINT;
+ALTER TABLE departments ADD COLUMN IF NOT EXISTS max_volumes INTEGER;
+ALTER TABLE departments ADD COLUMN IF NOT EXISTS max_volume_gb BIGINT;
+
+-- 资源归属用户，按部门成员汇总用量；早于本迁移创建的资源没有归属，不计入任何部门
+ALTER TABLE vms ADD COLUMN IF NOT EXISTS owner_id INTEGER REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE volumes ADD COLUMN IF NOT EXISTS owner_id INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_vms_owner_id ON vms(owner_id);
+CREATE INDEX IF NOT EXISTS idx_volumes_owner_id ON volumes(owner_id);
+
+COMMENT ON COLUMN departments.max_vcpu IS '部门成员虚拟机 vCPU 总数上限';
+COMMENT ON COLUMN departments.max_memory_mb IS '部门成员虚拟机内存总量上限（MB）';
+COMMENT ON COLUMN departments.max_volumes IS '部门成员存储卷数量上限';
+COMMENT ON COLUMN departments.max_volume_gb IS '部门成员存储卷总容量上限（GB）';
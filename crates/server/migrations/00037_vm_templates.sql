@@ -0,0 +1,14 @@
+-- 虚拟机模板：保存常用的虚拟机规格与系统盘基础镜像，从模板创建时以基础镜像为 backing file 创建系统盘
+CREATE TABLE IF NOT EXISTS vm_templates (
+    id VARCHAR(36) PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    base_volume_id VARCHAR(36) NOT NULL,  -- 基础镜像存储卷，删除后模板不可用但保留
+    spec JSONB NOT NULL,                  -- vcpu、内存、网卡、cloud-init 等虚拟机规格
+
+    -- 时间戳
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_vm_templates_name ON vm_templates(name);
@@ -0,0 +1,2 @@
+-- 节点已安装 QEMU 模拟器的架构（字符串数组），由 Agent 上报资源信息或按需刷新节点能力时更新
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS architectures JSONB;
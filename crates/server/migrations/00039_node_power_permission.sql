@@ -0,0 +1,12 @@
+-- ============================================================================
+-- 节点电源管理权限
+-- ============================================================================
+
+-- 配置 IPMI 凭据、查询和执行节点电源操作，默认仅超级管理员拥有
+INSERT INTO permissions (name, description, resource, action) VALUES
+('节点电源管理', '配置节点 IPMI 并执行开机、关机、重启等电源操作', 'node', 'power')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT 1, id FROM permissions WHERE resource = 'node' AND action = 'power'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,12 @@
+-- ============================================================================
+-- 虚拟机控制台访问权限
+-- ============================================================================
+
+-- 非所有者访问虚拟机控制台（VNC/SPICE）需要该权限，默认仅超级管理员拥有
+INSERT INTO permissions (name, description, resource, action) VALUES
+('访问虚拟机控制台', '访问非本人所有的虚拟机控制台', 'vm', 'console')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT 1, id FROM permissions WHERE resource = 'vm' AND action = 'console'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,126 @@
+/// 亲和组管理接口
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::Serialize;
+use validator::Validate;
+
+use crate::app_state::AppState;
+use crate::db::models::affinity_group::{AddAffinityMemberDto, CreateAffinityGroupDto};
+use crate::services::affinity_service::AffinityGroupService;
+
+/// API 错误响应
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+    message: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+        };
+
+        let body = Json(ErrorResponse {
+            error: status.canonical_reason().unwrap_or("Unknown").to_string(),
+            message,
+        });
+
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug)]
+enum ApiError {
+    NotFound(String),
+    BadRequest(String),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        ApiError::BadRequest(err.to_string())
+    }
+}
+
+/// 创建路由
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_groups).post(create_group))
+        .route("/:group_id", get(get_group).delete(delete_group))
+        .route("/:group_id/members", post(add_member))
+        .route("/:group_id/members/:vm_id", delete(remove_member))
+}
+
+/// 创建亲和组
+///
+/// POST /api/affinity-groups
+/// Body: { "name": "db-ha", "policy": "anti-affinity", "vm_ids": [] }
+async fn create_group(
+    State(state): State<AppState>,
+    Json(dto): Json<CreateAffinityGroupDto>,
+) -> Result<impl IntoResponse, ApiError> {
+    dto.validate()
+        .map_err(|e| ApiError::BadRequest(format!("验证失败: {}", e)))?;
+
+    let service = AffinityGroupService::new(state);
+    let group = service.create_group(dto).await?;
+    Ok((StatusCode::CREATED, Json(group)))
+}
+
+/// 获取亲和组列表
+async fn list_groups(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let service = AffinityGroupService::new(state);
+    Ok(Json(service.list_groups().await?))
+}
+
+/// 获取亲和组详情
+async fn get_group(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = AffinityGroupService::new(state);
+    let group = service
+        .get_group(&group_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("亲和组不存在".to_string()))?;
+    Ok(Json(group))
+}
+
+/// 删除亲和组
+async fn delete_group(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = AffinityGroupService::new(state);
+    service.delete_group(&group_id).await?;
+    Ok((StatusCode::NO_CONTENT, ()))
+}
+
+/// 添加亲和组成员
+///
+/// POST /api/affinity-groups/:group_id/members
+/// Body: { "vm_id": "..." }
+async fn add_member(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+    Json(dto): Json<AddAffinityMemberDto>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = AffinityGroupService::new(state);
+    Ok(Json(service.add_member(&group_id, &dto.vm_id).await?))
+}
+
+/// 移除亲和组成员
+async fn remove_member(
+    State(state): State<AppState>,
+    Path((group_id, vm_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = AffinityGroupService::new(state);
+    Ok(Json(service.remove_member(&group_id, &vm_id).await?))
+}
@@ -0,0 +1,128 @@
+/// 审计日志查询接口
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::api::utils::check_permission;
+use crate::app_state::AppState;
+use crate::db::models::audit_log::AuditLogFilter;
+use crate::extractors::AuthUser;
+use crate::services::audit_service::AuditService;
+
+/// API 错误响应
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+    message: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+        };
+
+        let body = Json(ErrorResponse {
+            error: status.canonical_reason().unwrap_or("Unknown").to_string(),
+            message,
+        });
+
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug)]
+enum ApiError {
+    BadRequest(String),
+    Forbidden(String),
+    Internal(String),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        ApiError::Internal(err.to_string())
+    }
+}
+
+/// 审计日志查询参数
+#[derive(Debug, Deserialize)]
+pub struct ListAuditLogsQuery {
+    #[serde(default = "default_page")]
+    pub page: usize,
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
+    /// 用户 ID 或用户名
+    pub user: Option<String>,
+    /// 资源类型，如 vms、volumes
+    pub resource: Option<String>,
+    /// 起止时间（RFC 3339）
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+fn default_page() -> usize {
+    1
+}
+
+fn default_page_size() -> usize {
+    20
+}
+
+/// 创建路由
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/", get(list_audit_logs))
+}
+
+fn parse_time(name: &str, value: Option<String>) -> Result<Option<DateTime<Utc>>, ApiError> {
+    value
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            DateTime::parse_from_rfc3339(&v)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|_| ApiError::BadRequest(format!("{} 不是有效的 RFC 3339 时间: {}", name, v)))
+        })
+        .transpose()
+}
+
+/// 查询审计日志
+///
+/// GET /api/audit?user=admin&resource=vms&from=2024-01-01T00:00:00Z&to=...&page=1&page_size=20
+///
+/// 需要 audit:read 权限
+async fn list_audit_logs(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Query(query): Query<ListAuditLogsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    check_permission(&state.sea_db(), claims.sub, "audit", "read")
+        .await
+        .map_err(|(status, Json(body))| {
+            let message = body["error"].as_str().unwrap_or("权限不足").to_string();
+            if status == StatusCode::FORBIDDEN {
+                ApiError::Forbidden(message)
+            } else {
+                ApiError::Internal(message)
+            }
+        })?;
+
+    let filter = AuditLogFilter {
+        user: query.user,
+        resource: query.resource,
+        from: parse_time("from", query.from)?,
+        to: parse_time("to", query.to)?,
+    };
+    let page = query.page.max(1);
+    let page_size = query.page_size.clamp(1, 200);
+
+    let service = AuditService::new(state);
+    Ok(Json(service.list_logs(filter, page, page_size).await?))
+}
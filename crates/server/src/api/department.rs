@@ -24,6 +24,8 @@ pub fn department_routes() -> Router<AppState> {
         .route("/:id", get(get_department))
         .route("/:id", put(update_department))
         .route("/:id", delete(delete_department))
+        .route("/:id/quota", put(update_department_quota))
+        .route("/:id/usage", get(get_department_usage))
 }
 
 /// 获取部门列表
@@ -131,6 +133,59 @@ async fn update_department(
     }
 }
 
+/// 设置部门资源配额
+async fn update_department_quota(
+    State(state): State<AppState>,
+    AuthUser(_claims): AuthUser,
+    Path(id): Path<i32>,
+    Json(dto): Json<UpdateDepartmentQuotaDto>,
+) -> Result<Json<ApiResponse<Model>>, (StatusCode, Json<ApiResponse<()>>)> {
+    // 验证输入
+    if let Err(e) = dto.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<()>::error(&format!("输入验证失败: {}", e))),
+        ));
+    }
+
+    match DepartmentService::update_quota(&state.sea_db(), id, dto).await {
+        Ok(department) => Ok(Json(ApiResponse::success(department))),
+        Err(e) if e.to_string().contains("不存在") => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<()>::error("部门不存在")),
+        )),
+        Err(e) => {
+            tracing::error!("设置部门配额失败: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<()>::error(&format!("设置部门配额失败: {}", e))),
+            ))
+        }
+    }
+}
+
+/// 获取部门资源用量
+async fn get_department_usage(
+    State(state): State<AppState>,
+    AuthUser(_claims): AuthUser,
+    Path(id): Path<i32>,
+) -> Result<Json<ApiResponse<DepartmentUsageDto>>, (StatusCode, Json<ApiResponse<()>>)> {
+    match DepartmentService::get_usage(&state.sea_db(), id).await {
+        Ok(usage) => Ok(Json(ApiResponse::success(usage))),
+        Err(e) if e.to_string().contains("不存在") => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<()>::error("部门不存在")),
+        )),
+        Err(e) => {
+            tracing::error!("获取部门资源用量失败: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<()>::error("获取部门资源用量失败")),
+            ))
+        }
+    }
+}
+
 /// 删除部门
 async fn delete_department(
     State(state): State<AppState>,
@@ -183,6 +238,10 @@ mod tests {
             description: Some("测试描述".to_string()),
             manager_id: Some(2),
             is_active: true,
+            max_vcpu: None,
+            max_memory_mb: None,
+            max_volumes: None,
+            max_volume_gb: None,
             created_at: chrono::Utc::now().into(),
             updated_at: chrono::Utc::now().into(),
         };
@@ -1,15 +1,23 @@
+pub mod affinity_groups;
+pub mod audit;
 pub mod auth;
 pub mod department;
 pub mod networks;
 pub mod nodes;
 pub mod permission;
 pub mod role;
+pub mod security_groups;
+pub mod snapshot_policies;
 pub mod snapshots;
 pub mod storage;
+pub mod system;
+pub mod tasks;
 pub mod user;
 pub mod user_department;
 pub mod utils;
+pub mod vm_templates;
 pub mod vms;
+pub mod webhooks;
 
 use axum::{middleware::from_fn, Router};
 
@@ -46,6 +54,10 @@ pub fn api_routes() -> Router<AppState> {
             nodes::node_routes().layer(from_fn(auth_middleware)),
         )
         .nest("/vms", vms::vm_routes().layer(from_fn(auth_middleware)))
+        .nest(
+            "/vm-templates",
+            vm_templates::routes().layer(from_fn(auth_middleware)),
+        )
         .nest(
             "/storage",
             storage::routes().layer(from_fn(auth_middleware)),
@@ -58,4 +70,26 @@ pub fn api_routes() -> Router<AppState> {
             "/storage",
             snapshots::routes().layer(from_fn(auth_middleware)),
         )
+        .nest(
+            "/snapshot-policies",
+            snapshot_policies::routes().layer(from_fn(auth_middleware)),
+        )
+        .nest(
+            "/affinity-groups",
+            affinity_groups::routes().layer(from_fn(auth_middleware)),
+        )
+        .nest(
+            "/security-groups",
+            security_groups::routes().layer(from_fn(auth_middleware)),
+        )
+        .nest(
+            "/system",
+            system::system_routes().layer(from_fn(auth_middleware)),
+        )
+        .nest("/tasks", tasks::routes().layer(from_fn(auth_middleware)))
+        .nest("/audit", audit::routes().layer(from_fn(auth_middleware)))
+        .nest(
+            "/webhooks",
+            webhooks::routes().layer(from_fn(auth_middleware)),
+        )
 }
@@ -10,6 +10,7 @@ use axum::{
 use serde::{Deserialize, Serialize};
 
 use crate::app_state::AppState;
+use crate::db::models::ip_allocation::CreateIpReservationDto;
 use crate::db::models::network::{CreateNetworkDto, UpdateNetworkDto};
 use crate::services::network_service::NetworkService;
 
@@ -25,6 +26,7 @@ impl IntoResponse for ApiError {
         let (status, message) = match self {
             ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg),
             ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
         };
 
@@ -41,9 +43,26 @@ impl IntoResponse for ApiError {
 enum ApiError {
     NotFound(String),
     BadRequest(String),
+    Conflict(String),
     Internal(String),
 }
 
+impl ApiError {
+    /// 按 IP 预留相关的错误信息区分状态码
+    fn from_reservation_error(err: anyhow::Error) -> Self {
+        let message = err.to_string();
+        if message.contains("不存在") {
+            ApiError::NotFound(message)
+        } else if message.contains("已被占用") || message.contains("正被虚拟机使用") {
+            ApiError::Conflict(message)
+        } else if message.contains("无效的") || message.contains("不在网络") || message.contains("网关") {
+            ApiError::BadRequest(message)
+        } else {
+            ApiError::Internal(message)
+        }
+    }
+}
+
 impl From<anyhow::Error> for ApiError {
     fn from(err: anyhow::Error) -> Self {
         ApiError::Internal(err.to_string())
@@ -102,6 +121,8 @@ pub fn routes() -> Router<AppState> {
         
         // IP 分配路由
         .route("/:network_id/ips", get(list_ip_allocations))
+        .route("/:network_id/reservations", post(create_reservation))
+        .route("/:network_id/reservations/:reservation_id", delete(delete_reservation))
 }
 
 // ==================== 网络接口 ====================
@@ -178,3 +199,30 @@ async fn list_ip_allocations(
     ).await?;
     Ok(Json(response))
 }
+
+/// 静态预留 IP
+async fn create_reservation(
+    State(state): State<AppState>,
+    Path(network_id): Path<String>,
+    Json(dto): Json<CreateIpReservationDto>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = NetworkService::new(state);
+    let reservation = service
+        .reserve_ip(&network_id, dto)
+        .await
+        .map_err(ApiError::from_reservation_error)?;
+    Ok((StatusCode::CREATED, Json(reservation)))
+}
+
+/// 删除静态预留
+async fn delete_reservation(
+    State(state): State<AppState>,
+    Path((network_id, reservation_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = NetworkService::new(state);
+    service
+        .release_reservation(&network_id, &reservation_id)
+        .await
+        .map_err(ApiError::from_reservation_error)?;
+    Ok((StatusCode::NO_CONTENT, ()))
+}
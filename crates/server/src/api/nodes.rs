@@ -3,7 +3,7 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    routing::get,
+    routing::{get, post, put},
     Json,
     Router,
 };
@@ -11,10 +11,19 @@ use serde::{Deserialize, Serialize};
 use validator::Validate;
 
 use crate::{
+    api::utils::check_permission,
     app_state::AppState, 
+    extractors::AuthUser,
     services::node_service::NodeService,
-    db::models::node::{CreateNodeDto, UpdateNodeDto, NodeResponse, NodeListResponse, NodeStatsResponse},
+    services::node_metrics_service::{self, NodeMetricsService},
+    db::models::node::{
+        CreateNodeDto, UpdateNodeDto, NodeResponse, NodeListResponse, NodeStatsResponse,
+        NodePowerDto, NodePowerResponse, UpdateNodeIpmiDto, UpdateNodeShutdownPolicyDto, DrainNodeDto,
+        NodeHealthResponse,
+    },
+    db::models::node_metric::NodeMetricsResponse,
 };
+use common::ws_rpc::{DrainNodeResponse, GetRestoreStateResponse};
 
 /// 节点路由
 pub fn node_routes() -> Router<AppState> {
@@ -22,6 +31,14 @@ pub fn node_routes() -> Router<AppState> {
         .route("/", get(list_nodes).post(create_node))
         .route("/stats", get(get_stats))
         .route("/:id", get(get_node).put(update_node).delete(delete_node))
+        .route("/:id/ipmi", put(set_node_ipmi))
+        .route("/:id/power", get(get_node_power).post(node_power_action))
+        .route("/:id/shutdown-policy", put(set_node_shutdown_policy))
+        .route("/:id/drain", post(drain_node))
+        .route("/:id/refresh", post(refresh_node))
+        .route("/:id/restore-state", get(get_node_restore_state))
+        .route("/:id/health", get(get_node_health))
+        .route("/:id/metrics", get(get_node_metrics))
 }
 
 /// 分页查询参数
@@ -42,6 +59,13 @@ fn default_page_size() -> usize {
     20
 }
 
+/// 指标历史查询参数
+#[derive(Debug, Deserialize)]
+pub struct NodeMetricsQuery {
+    /// 时间范围，如 30m、6h、24h，默认最近 1 小时
+    pub range: Option<String>,
+}
+
 /// 通用响应
 #[derive(Debug, Serialize)]
 pub struct ApiResponse {
@@ -189,3 +213,265 @@ pub async fn get_stats(
     }
 }
 
+
+/// 校验当前用户拥有 node:power 权限（IPMI 配置与电源管理）
+async fn require_power_permission(
+    state: &AppState,
+    user_id: i32,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    check_permission(&state.sea_db(), user_id, "node", "power")
+        .await
+        .map_err(|(status, Json(body))| {
+            (
+                status,
+                Json(ErrorResponse {
+                    success: false,
+                    error: body["error"].as_str().unwrap_or("权限不足").to_string(),
+                }),
+            )
+        })
+}
+
+/// 配置节点 IPMI
+///
+/// PUT /api/nodes/:id/ipmi
+/// Body: { "address": "10.0.0.10", "username": "ADMIN", "password": "***" }
+///
+/// 需要 node:power 权限
+pub async fn set_node_ipmi(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Path(id): Path<String>,
+    Json(dto): Json<UpdateNodeIpmiDto>,
+) -> Result<Json<NodeResponse>, (StatusCode, Json<ErrorResponse>)> {
+    require_power_permission(&state, claims.sub).await?;
+
+    if let Err(e) = dto.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                error: format!("验证失败: {}", e),
+            }),
+        ));
+    }
+
+    let service = NodeService::new(state);
+    match service.set_ipmi_config(&id, dto).await {
+        Ok(node) => Ok(Json(node)),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                error: format!("配置 IPMI 失败: {}", e),
+            }),
+        )),
+    }
+}
+
+/// 查询节点电源状态
+///
+/// GET /api/nodes/:id/power
+///
+/// 需要 node:power 权限
+pub async fn get_node_power(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<NodePowerResponse>, (StatusCode, Json<ErrorResponse>)> {
+    require_power_permission(&state, claims.sub).await?;
+
+    let service = NodeService::new(state);
+    match service.get_power_state(&id).await {
+        Ok(power) => Ok(Json(power)),
+        Err(e) => Err((
+            StatusCode::BAD_GATEWAY,
+            Json(ErrorResponse {
+                success: false,
+                error: format!("查询电源状态失败: {}", e),
+            }),
+        )),
+    }
+}
+
+/// 执行节点电源操作
+///
+/// POST /api/nodes/:id/power
+/// Body: { "action": "on" | "off" | "cycle", "force": false }
+///
+/// 需要 node:power 权限
+pub async fn node_power_action(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Path(id): Path<String>,
+    Json(dto): Json<NodePowerDto>,
+) -> Result<Json<NodePowerResponse>, (StatusCode, Json<ErrorResponse>)> {
+    require_power_permission(&state, claims.sub).await?;
+
+    let service = NodeService::new(state);
+    match service.power_action(&id, dto.action, dto.force).await {
+        Ok(power) => Ok(Json(power)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                success: false,
+                error: format!("电源操作失败: {}", e),
+            }),
+        )),
+    }
+}
+
+/// 配置节点关机策略
+///
+/// PUT /api/nodes/:id/shutdown-policy
+/// Body: { "policy": "shutdown" | "suspend" }
+///
+/// 需要 node:power 权限
+pub async fn set_node_shutdown_policy(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Path(id): Path<String>,
+    Json(dto): Json<UpdateNodeShutdownPolicyDto>,
+) -> Result<Json<NodeResponse>, (StatusCode, Json<ErrorResponse>)> {
+    require_power_permission(&state, claims.sub).await?;
+
+    let service = NodeService::new(state);
+    match service.set_shutdown_policy(&id, dto.policy).await {
+        Ok(node) => Ok(Json(node)),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                error: format!("配置关机策略失败: {}", e),
+            }),
+        )),
+    }
+}
+
+/// 排空节点（计划内重启前挂起或关闭所有运行中的虚拟机）
+///
+/// POST /api/nodes/:id/drain
+/// Body: { "policy": "shutdown" | "suspend" }，policy 可省略，默认使用节点配置
+///
+/// 需要 node:power 权限
+pub async fn drain_node(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Path(id): Path<String>,
+    dto: Option<Json<DrainNodeDto>>,
+) -> Result<Json<DrainNodeResponse>, (StatusCode, Json<ErrorResponse>)> {
+    require_power_permission(&state, claims.sub).await?;
+
+    let dto = dto.map(|Json(dto)| dto).unwrap_or_default();
+
+    let service = NodeService::new(state);
+    match service.drain_node(&id, dto.policy).await {
+        Ok(result) => Ok(Json(result)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                success: false,
+                error: format!("排空节点失败: {}", e),
+            }),
+        )),
+    }
+}
+
+/// 重新检测节点能力（虚拟化版本、swtpm、Open vSwitch、支持的架构）与资源信息
+///
+/// POST /api/nodes/:id/refresh
+pub async fn refresh_node(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<NodeResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let service = NodeService::new(state);
+    match service.refresh_capabilities(&id).await {
+        Ok(node) => Ok(Json(node)),
+        Err(e) => {
+            let status = if e.to_string().contains("节点不存在") {
+                StatusCode::NOT_FOUND
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+            Err((
+                status,
+                Json(ErrorResponse {
+                    success: false,
+                    error: format!("刷新节点能力失败: {}", e),
+                }),
+            ))
+        }
+    }
+}
+
+/// 查询节点上虚拟机的恢复状态
+///
+/// GET /api/nodes/:id/restore-state
+pub async fn get_node_restore_state(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<GetRestoreStateResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let service = NodeService::new(state);
+    match service.get_restore_state(&id).await {
+        Ok(result) => Ok(Json(result)),
+        Err(e) => Err((
+            StatusCode::BAD_GATEWAY,
+            Json(ErrorResponse {
+                success: false,
+                error: format!("查询恢复状态失败: {}", e),
+            }),
+        )),
+    }
+}
+
+/// 获取节点健康度
+pub async fn get_node_health(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<NodeHealthResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let service = NodeService::new(state);
+    match service.get_node_health(&id).await {
+        Ok(health) => Ok(Json(health)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                success: false,
+                error: format!("获取节点健康度失败: {}", e),
+            }),
+        )),
+    }
+}
+
+/// 获取节点主机指标历史
+///
+/// GET /api/nodes/:id/metrics?range=6h
+pub async fn get_node_metrics(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<NodeMetricsQuery>,
+) -> Result<Json<NodeMetricsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let range_secs = match query.range.as_deref() {
+        Some(range) => node_metrics_service::parse_range(range).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    success: false,
+                    error: e,
+                }),
+            )
+        })?,
+        None => node_metrics_service::DEFAULT_RANGE_SECS,
+    };
+
+    let service = NodeMetricsService::new(state);
+    match service.get_node_metrics(&id, range_secs).await {
+        Ok(metrics) => Ok(Json(metrics)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                success: false,
+                error: format!("获取节点指标失败: {}", e),
+            }),
+        )),
+    }
+}
@@ -0,0 +1,127 @@
+/// 安全组管理接口
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use validator::Validate;
+
+use crate::app_state::AppState;
+use crate::db::models::security_group::{CreateSecurityGroupDto, UpdateSecurityGroupDto};
+use crate::services::security_group_service::SecurityGroupService;
+
+/// API 错误响应
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+    message: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+        };
+
+        let body = Json(ErrorResponse {
+            error: status.canonical_reason().unwrap_or("Unknown").to_string(),
+            message,
+        });
+
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug)]
+enum ApiError {
+    NotFound(String),
+    BadRequest(String),
+    Conflict(String),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        let msg = err.to_string();
+        if msg.contains("不存在") {
+            ApiError::NotFound(msg)
+        } else if msg.contains("已存在") || msg.contains("正被") {
+            ApiError::Conflict(msg)
+        } else {
+            ApiError::BadRequest(msg)
+        }
+    }
+}
+
+/// 创建路由
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_groups).post(create_group))
+        .route(
+            "/:group_id",
+            get(get_group).put(update_group).delete(delete_group),
+        )
+}
+
+/// 创建安全组
+///
+/// POST /api/security-groups
+/// Body: { "name": "web", "rules": [{ "protocol": "tcp", "port_from": 443 }, { "protocol": "icmp" }] }
+async fn create_group(
+    State(state): State<AppState>,
+    Json(dto): Json<CreateSecurityGroupDto>,
+) -> Result<impl IntoResponse, ApiError> {
+    dto.validate()
+        .map_err(|e| ApiError::BadRequest(format!("验证失败: {}", e)))?;
+
+    let service = SecurityGroupService::new(state);
+    let group = service.create_group(dto).await?;
+    Ok((StatusCode::CREATED, Json(group)))
+}
+
+/// 获取安全组列表
+async fn list_groups(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let service = SecurityGroupService::new(state);
+    Ok(Json(service.list_groups().await?))
+}
+
+/// 获取安全组详情
+async fn get_group(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = SecurityGroupService::new(state);
+    Ok(Json(service.get_group(&group_id).await?))
+}
+
+/// 更新安全组
+///
+/// PUT /api/security-groups/:group_id
+///
+/// rules 整体替换，并同步到使用该安全组的运行中虚拟机
+async fn update_group(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+    Json(dto): Json<UpdateSecurityGroupDto>,
+) -> Result<impl IntoResponse, ApiError> {
+    dto.validate()
+        .map_err(|e| ApiError::BadRequest(format!("验证失败: {}", e)))?;
+
+    let service = SecurityGroupService::new(state);
+    Ok(Json(service.update_group(&group_id, dto).await?))
+}
+
+/// 删除安全组
+async fn delete_group(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = SecurityGroupService::new(state);
+    service.delete_group(&group_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,127 @@
+/// 快照策略管理接口
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use validator::Validate;
+
+use crate::app_state::AppState;
+use crate::db::models::snapshot_policy::{CreateSnapshotPolicyDto, UpdateSnapshotPolicyDto};
+use crate::services::snapshot_policy_service::SnapshotPolicyService;
+
+/// API 错误响应
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+    message: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+        };
+
+        let body = Json(ErrorResponse {
+            error: status.canonical_reason().unwrap_or("Unknown").to_string(),
+            message,
+        });
+
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug)]
+enum ApiError {
+    NotFound(String),
+    BadRequest(String),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        let msg = err.to_string();
+        if msg.contains("不存在") {
+            ApiError::NotFound(msg)
+        } else {
+            ApiError::BadRequest(msg)
+        }
+    }
+}
+
+/// 创建路由
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_policies).post(create_policy))
+        .route(
+            "/:id",
+            get(get_policy).put(update_policy).delete(delete_policy),
+        )
+}
+
+/// 创建快照策略
+///
+/// POST /api/snapshot-policies
+/// Body: { "name": "nightly", "target_type": "vm", "target_id": "...", "schedule": "0 2 * * *", "keep_last": 7 }
+async fn create_policy(
+    State(state): State<AppState>,
+    Json(dto): Json<CreateSnapshotPolicyDto>,
+) -> Result<impl IntoResponse, ApiError> {
+    dto.validate()
+        .map_err(|e| ApiError::BadRequest(format!("验证失败: {}", e)))?;
+
+    let service = SnapshotPolicyService::new(state);
+    let policy = service.create_policy(dto).await?;
+    Ok((StatusCode::CREATED, Json(policy)))
+}
+
+/// 获取快照策略列表
+///
+/// GET /api/snapshot-policies
+async fn list_policies(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let service = SnapshotPolicyService::new(state);
+    Ok(Json(service.list_policies().await?))
+}
+
+/// 获取快照策略详情
+///
+/// GET /api/snapshot-policies/:id
+async fn get_policy(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = SnapshotPolicyService::new(state);
+    Ok(Json(service.get_policy(&id).await?))
+}
+
+/// 更新快照策略
+///
+/// PUT /api/snapshot-policies/:id
+async fn update_policy(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(dto): Json<UpdateSnapshotPolicyDto>,
+) -> Result<impl IntoResponse, ApiError> {
+    dto.validate()
+        .map_err(|e| ApiError::BadRequest(format!("验证失败: {}", e)))?;
+
+    let service = SnapshotPolicyService::new(state);
+    Ok(Json(service.update_policy(&id, dto).await?))
+}
+
+/// 删除快照策略
+///
+/// DELETE /api/snapshot-policies/:id
+async fn delete_policy(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = SnapshotPolicyService::new(state);
+    service.delete_policy(&id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
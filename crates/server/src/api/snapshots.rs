@@ -79,6 +79,7 @@ pub fn routes() -> Router<AppState> {
         .route("/snapshots/:snapshot_id", put(update_snapshot))
         .route("/snapshots/:snapshot_id", delete(delete_snapshot))
         .route("/snapshots/:snapshot_id/restore", post(restore_snapshot))
+        .route("/volumes/:volume_id/disk-snapshots", get(list_disk_snapshots))
 }
 
 // ==================== 快照接口 ====================
@@ -137,7 +138,7 @@ async fn restore_snapshot(
     Path(snapshot_id): Path<String>,
 ) -> Result<impl IntoResponse, ApiError> {
     let service = SnapshotService::new(state);
-    service
+    let safety_snapshot_id = service
         .restore_snapshot(&snapshot_id)
         .await
         .map_err(|err| {
@@ -153,10 +154,14 @@ async fn restore_snapshot(
     #[derive(Serialize)]
     struct RestoreResponse {
         message: String,
+        /// 恢复前自动创建的安全快照，可用于撤销本次恢复
+        #[serde(skip_serializing_if = "Option::is_none")]
+        safety_snapshot_id: Option<String>,
     }
 
     Ok(Json(RestoreResponse {
         message: "快照恢复成功".to_string(),
+        safety_snapshot_id,
     }))
 }
 
@@ -179,3 +184,22 @@ async fn update_snapshot(
         })?;
     Ok(Json(snapshot))
 }
+
+/// 列出存储卷磁盘上的实际快照并与数据库对账
+async fn list_disk_snapshots(
+    State(state): State<AppState>,
+    Path(volume_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = SnapshotService::new(state);
+    let response = service
+        .list_disk_snapshots(&volume_id)
+        .await
+        .map_err(|err| {
+            if err.to_string().contains("不存在") {
+                ApiError::NotFound(err.to_string())
+            } else {
+                ApiError::from(err)
+            }
+        })?;
+    Ok(Json(response))
+}
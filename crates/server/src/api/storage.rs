@@ -1,7 +1,8 @@
 /// 存储管理接口
 use axum::{
+    body::Body,
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     routing::{delete, get, post, put},
     Json, Router,
@@ -9,10 +10,12 @@ use axum::{
 use serde::{Deserialize, Serialize};
 
 use crate::app_state::AppState;
-use crate::db::models::storage_pool::{CreateStoragePoolDto, UpdateStoragePoolDto};
+use crate::extractors::AuthUser;
+use crate::db::models::storage_pool::{CreateStoragePoolDto, PoolGcDto, UpdateStoragePoolDto};
 use crate::db::models::volume::{
-    CloneVolumeDto, CreateVolumeDto, ResizeVolumeDto, UpdateVolumeDto,
+    CloneVolumeDto, CreateVolumeDto, ImportVolumeDto, ResizeVolumeDto, UpdateVolumeDto,
 };
+use crate::services::department_service::QuotaExceeded;
 use crate::services::storage_service::StorageService;
 
 /// API 错误响应
@@ -20,20 +23,29 @@ use crate::services::storage_service::StorageService;
 struct ErrorResponse {
     error: String,
     message: String,
+    /// 结构化的错误明细（如部门配额不足时的用量）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
-            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
-            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
-            ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg),
-            ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+        let (status, message, details) = match self {
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg, None),
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg, None),
+            ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg, None),
+            ApiError::QuotaExceeded(err) => (
+                StatusCode::FORBIDDEN,
+                err.to_string(),
+                serde_json::to_value(&err).ok(),
+            ),
+            ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg, None),
         };
 
         let body = Json(ErrorResponse {
             error: status.canonical_reason().unwrap_or("Unknown").to_string(),
             message,
+            details,
         });
 
         (status, body).into_response()
@@ -45,12 +57,16 @@ enum ApiError {
     NotFound(String),
     BadRequest(String),
     Conflict(String),
+    QuotaExceeded(QuotaExceeded),
     Internal(String),
 }
 
 impl From<anyhow::Error> for ApiError {
     fn from(err: anyhow::Error) -> Self {
-        ApiError::Internal(err.to_string())
+        match err.downcast::<QuotaExceeded>() {
+            Ok(err) => ApiError::QuotaExceeded(err),
+            Err(err) => ApiError::Internal(err.to_string()),
+        }
     }
 }
 
@@ -94,14 +110,19 @@ pub fn routes() -> Router<AppState> {
         .route("/pools/:pool_id", get(get_storage_pool))
         .route("/pools/:pool_id", put(update_storage_pool))
         .route("/pools/:pool_id", delete(delete_storage_pool))
+        .route("/pools/reconcile", post(reconcile_all_storage_pools))
+        .route("/pools/:pool_id/reconcile", post(reconcile_storage_pool))
+        .route("/pools/:pool_id/gc", post(gc_storage_pool))
         // 存储卷路由
         .route("/volumes", post(create_volume))
         .route("/volumes", get(list_volumes))
+        .route("/volumes/import", post(import_volume))
         .route("/volumes/:volume_id", get(get_volume))
         .route("/volumes/:volume_id", put(update_volume))
         .route("/volumes/:volume_id", delete(delete_volume))
         .route("/volumes/:volume_id/resize", post(resize_volume))
         .route("/volumes/:volume_id/clone", post(clone_volume))
+        .route("/volumes/:volume_id/download", get(download_volume))
 }
 
 // ==================== 存储池接口 ====================
@@ -165,15 +186,106 @@ async fn delete_storage_pool(
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// 按存储卷大小重新计算存储池分配量
+async fn reconcile_storage_pool(
+    State(state): State<AppState>,
+    Path(pool_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = StorageService::new(state);
+    let pool = service.reconcile_pool_allocation(&pool_id).await.map_err(|err| {
+        if err.to_string().contains("存储池不存在") {
+            ApiError::NotFound(err.to_string())
+        } else {
+            ApiError::from(err)
+        }
+    })?;
+    Ok(Json(pool))
+}
+
+/// 重新计算所有存储池分配量
+async fn reconcile_all_storage_pools(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = StorageService::new(state);
+    let reconciled = service.reconcile_all_pool_allocations().await?;
+    Ok(Json(serde_json::json!({ "reconciled": reconciled })))
+}
+
+/// 存储池垃圾回收（报告并可选删除孤立文件）
+///
+/// POST /api/storage/pools/:pool_id/gc
+/// Body: { "delete": true, "confirm": "<pool_id>" }，不传 body 时仅报告
+async fn gc_storage_pool(
+    State(state): State<AppState>,
+    Path(pool_id): Path<String>,
+    dto: Option<Json<PoolGcDto>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let dto = dto.map(|Json(dto)| dto).unwrap_or_default();
+
+    let service = StorageService::new(state);
+    let result = service.gc_pool(&pool_id, dto).await.map_err(|err| {
+        let message = err.to_string();
+        if message.contains("存储池不存在") {
+            ApiError::NotFound(message)
+        } else if message.contains("confirm") || message.contains("未关联节点") {
+            ApiError::BadRequest(message)
+        } else {
+            ApiError::from(err)
+        }
+    })?;
+    Ok(Json(result))
+}
+
 // ==================== 存储卷接口 ====================
 
 /// 创建存储卷
 async fn create_volume(
     State(state): State<AppState>,
-    Json(dto): Json<CreateVolumeDto>,
+    auth: Option<AuthUser>,
+    Json(mut dto): Json<CreateVolumeDto>,
 ) -> Result<impl IntoResponse, ApiError> {
+    dto.owner_id = auth.map(|AuthUser(claims)| claims.sub);
     let service = StorageService::new(state);
-    let volume = service.create_volume(dto).await?;
+    let volume = service.create_volume(dto).await.map_err(|err| {
+        let message = err.to_string();
+        if ["校验", "源地址", "基础镜像", "链接克隆", "加密"]
+            .iter()
+            .any(|keyword| message.contains(keyword))
+        {
+            ApiError::BadRequest(message)
+        } else {
+            ApiError::from(err)
+        }
+    })?;
+    Ok((StatusCode::CREATED, Json(volume)))
+}
+
+/// 导入存储池中已存在的镜像
+///
+/// POST /api/storage/volumes/import
+/// Body: { "name": "ubuntu", "pool_id": "...", "path": "/mnt/nfs/seed/ubuntu.qcow2", "mode": "reference" }
+async fn import_volume(
+    State(state): State<AppState>,
+    auth: Option<AuthUser>,
+    Json(mut dto): Json<ImportVolumeDto>,
+) -> Result<impl IntoResponse, ApiError> {
+    dto.owner_id = auth.map(|AuthUser(claims)| claims.sub);
+    let service = StorageService::new(state);
+    let volume = service.import_volume(dto).await.map_err(|err| {
+        let message = err.to_string();
+        if message.contains("存储池不存在") {
+            ApiError::NotFound(message)
+        } else if message.contains("已被存储卷") {
+            ApiError::Conflict(message)
+        } else if ["绝对路径", "无法导入镜像", "未关联节点"]
+            .iter()
+            .any(|keyword| message.contains(keyword))
+        {
+            ApiError::BadRequest(message)
+        } else {
+            ApiError::from(err)
+        }
+    })?;
     Ok((StatusCode::CREATED, Json(volume)))
 }
 
@@ -223,8 +335,11 @@ async fn delete_volume(
 ) -> Result<impl IntoResponse, ApiError> {
     let service = StorageService::new(state);
     service.delete_volume(&volume_id).await.map_err(|err| {
-        if err.to_string().contains("存储卷正在被虚拟机使用，无法删除") {
-            ApiError::Conflict(err.to_string())
+        let message = err.to_string();
+        if message.contains("存储卷正在被虚拟机使用，无法删除")
+            || message.contains("链接克隆的基础镜像")
+        {
+            ApiError::Conflict(message)
         } else {
             ApiError::from(err)
         }
@@ -253,3 +368,133 @@ async fn clone_volume(
     let volume = service.clone_volume(dto).await?;
     Ok((StatusCode::CREATED, Json(volume)))
 }
+
+/// 下载存储卷
+///
+/// GET /api/storage/volumes/:volume_id/download
+/// 支持单个 `Range: bytes=...` 区间，用于断点续传
+async fn download_volume(
+    State(state): State<AppState>,
+    Path(volume_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let service = StorageService::new(state);
+    let download = service
+        .prepare_volume_download(&volume_id)
+        .await
+        .map_err(|err| {
+            let message = err.to_string();
+            if message.contains("存储卷不存在") {
+                ApiError::NotFound(message)
+            } else if message.contains("仅可下载") || message.contains("加密存储卷") {
+                ApiError::Conflict(message)
+            } else {
+                ApiError::from(err)
+            }
+        })?;
+
+    let size = download.size_bytes;
+    let range = headers.get(header::RANGE).and_then(|v| v.to_str().ok());
+    let (status, start, end) = match parse_byte_range(range, size) {
+        ByteRange::Full => (StatusCode::OK, 0, size),
+        ByteRange::Partial(start, end) => (StatusCode::PARTIAL_CONTENT, start, end),
+        ByteRange::Unsatisfiable => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", size))],
+            )
+                .into_response());
+        }
+    };
+
+    let mut builder = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, end - start)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", download.file_name),
+        );
+    if status == StatusCode::PARTIAL_CONTENT {
+        builder = builder.header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end - 1, size),
+        );
+    }
+
+    builder
+        .body(Body::from_stream(download.into_stream(start, end)))
+        .map_err(|e| ApiError::Internal(e.to_string()))
+}
+
+/// Range 请求头解析结果，区间为左闭右开
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Full,
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// 解析 `Range: bytes=start-end | start- | -suffix`
+///
+/// 格式错误或多区间请求按 RFC 9110 忽略，返回完整内容
+fn parse_byte_range(value: Option<&str>, size: u64) -> ByteRange {
+    let Some(spec) = value.and_then(|v| v.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    let (start, end) = if start.is_empty() {
+        // 后缀区间：最后 N 字节
+        match end.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(suffix) => (size.saturating_sub(suffix), size),
+            Err(_) => return ByteRange::Full,
+        }
+    } else {
+        let Ok(start) = start.parse::<u64>() else {
+            return ByteRange::Full;
+        };
+        if end.is_empty() {
+            (start, size)
+        } else {
+            match end.parse::<u64>() {
+                Ok(last) if last >= start => (start, last.saturating_add(1).min(size)),
+                _ => return ByteRange::Full,
+            }
+        }
+    };
+
+    if start >= size {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial(start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_byte_range() {
+        assert_eq!(parse_byte_range(None, 100), ByteRange::Full);
+        assert_eq!(parse_byte_range(Some("bytes=0-9"), 100), ByteRange::Partial(0, 10));
+        assert_eq!(parse_byte_range(Some("bytes=90-"), 100), ByteRange::Partial(90, 100));
+        assert_eq!(parse_byte_range(Some("bytes=-10"), 100), ByteRange::Partial(90, 100));
+        assert_eq!(parse_byte_range(Some("bytes=50-500"), 100), ByteRange::Partial(50, 100));
+        assert_eq!(parse_byte_range(Some("bytes=100-"), 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_byte_range(Some("bytes=-0"), 100), ByteRange::Unsatisfiable);
+        // 无法解析或多区间时忽略 Range
+        assert_eq!(parse_byte_range(Some("bytes=9-0"), 100), ByteRange::Full);
+        assert_eq!(parse_byte_range(Some("bytes=0-1,5-6"), 100), ByteRange::Full);
+        assert_eq!(parse_byte_range(Some("items=0-1"), 100), ByteRange::Full);
+    }
+}
@@ -0,0 +1,65 @@
+/// 系统管理接口
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Json,
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::{
+    api::utils::check_permission,
+    app_state::AppState,
+    extractors::AuthUser,
+};
+
+/// 切换维护模式请求
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceModeRequest {
+    pub read_only: bool,
+}
+
+pub fn system_routes() -> Router<AppState> {
+    Router::new()
+        .route("/maintenance", get(get_maintenance_mode).put(set_maintenance_mode))
+}
+
+/// 获取维护模式状态
+///
+/// GET /api/system/maintenance
+async fn get_maintenance_mode(
+    State(state): State<AppState>,
+    AuthUser(_claims): AuthUser,
+) -> Json<Value> {
+    Json(json!({
+        "read_only": state.is_read_only()
+    }))
+}
+
+/// 切换只读维护模式
+///
+/// PUT /api/system/maintenance
+/// Body: { "read_only": true }
+async fn set_maintenance_mode(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Json(req): Json<MaintenanceModeRequest>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    check_permission(&state.sea_db(), claims.sub, "system", "configure").await?;
+
+    state.set_read_only(req.read_only);
+    warn!(
+        "用户 {} 已{}只读维护模式",
+        claims.username,
+        if req.read_only { "开启" } else { "关闭" }
+    );
+
+    Ok(Json(json!({
+        "success": true,
+        "read_only": req.read_only
+    })))
+}
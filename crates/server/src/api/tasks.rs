@@ -0,0 +1,105 @@
+/// 任务查询接口
+///
+/// 异步操作（启动、停止、重启虚拟机）返回任务 ID，前端通过以下接口轮询结果
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::AppState;
+use crate::services::task_service::TaskService;
+
+/// API 错误响应
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+    message: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+        };
+
+        let body = Json(ErrorResponse {
+            error: status.canonical_reason().unwrap_or("Unknown").to_string(),
+            message,
+        });
+
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug)]
+enum ApiError {
+    NotFound(String),
+    Internal(String),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        let msg = err.to_string();
+        if msg.contains("不存在") {
+            ApiError::NotFound(msg)
+        } else {
+            ApiError::Internal(msg)
+        }
+    }
+}
+
+/// 任务查询参数
+#[derive(Debug, Deserialize)]
+pub struct ListTasksQuery {
+    #[serde(default = "default_page")]
+    pub page: usize,
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
+    /// 关联对象 ID（如虚拟机 ID）
+    pub target_id: Option<String>,
+    pub status: Option<String>,
+}
+
+fn default_page() -> usize {
+    1
+}
+
+fn default_page_size() -> usize {
+    20
+}
+
+/// 创建路由
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_tasks))
+        .route("/:id", get(get_task))
+}
+
+/// 获取任务列表
+///
+/// GET /api/tasks?target_id=&status=&page=&page_size=
+async fn list_tasks(
+    State(state): State<AppState>,
+    Query(query): Query<ListTasksQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let response = TaskService::new(state)
+        .list_tasks(query.page, query.page_size, query.target_id, query.status)
+        .await?;
+    Ok(Json(response))
+}
+
+/// 获取单个任务
+///
+/// GET /api/tasks/:id
+async fn get_task(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let task = TaskService::new(state).get_task(&id).await?;
+    Ok(Json(task))
+}
@@ -0,0 +1,127 @@
+/// 虚拟机模板管理接口
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use validator::Validate;
+
+use crate::app_state::AppState;
+use crate::db::models::vm_template::{CreateVmTemplateDto, UpdateVmTemplateDto};
+use crate::services::vm_template_service::VmTemplateService;
+
+/// API 错误响应
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+    message: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+        };
+
+        let body = Json(ErrorResponse {
+            error: status.canonical_reason().unwrap_or("Unknown").to_string(),
+            message,
+        });
+
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug)]
+enum ApiError {
+    NotFound(String),
+    BadRequest(String),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        let msg = err.to_string();
+        if msg.contains("模板不存在") {
+            ApiError::NotFound(msg)
+        } else {
+            ApiError::BadRequest(msg)
+        }
+    }
+}
+
+/// 创建路由
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_templates).post(create_template))
+        .route(
+            "/:id",
+            get(get_template).put(update_template).delete(delete_template),
+        )
+}
+
+/// 创建虚拟机模板
+///
+/// POST /api/vm-templates
+/// Body: { "name": "ubuntu-small", "base_volume_id": "...", "spec": { "vcpu": 2, "memory_mb": 2048, "networks": [...] } }
+async fn create_template(
+    State(state): State<AppState>,
+    Json(dto): Json<CreateVmTemplateDto>,
+) -> Result<impl IntoResponse, ApiError> {
+    dto.validate()
+        .map_err(|e| ApiError::BadRequest(format!("验证失败: {}", e)))?;
+
+    let service = VmTemplateService::new(state);
+    let template = service.create_template(dto).await?;
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+/// 获取虚拟机模板列表
+///
+/// GET /api/vm-templates
+async fn list_templates(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let service = VmTemplateService::new(state);
+    Ok(Json(service.list_templates().await?))
+}
+
+/// 获取虚拟机模板详情
+///
+/// GET /api/vm-templates/:id
+async fn get_template(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = VmTemplateService::new(state);
+    Ok(Json(service.get_template(&id).await?))
+}
+
+/// 更新虚拟机模板
+///
+/// PUT /api/vm-templates/:id
+async fn update_template(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(dto): Json<UpdateVmTemplateDto>,
+) -> Result<impl IntoResponse, ApiError> {
+    dto.validate()
+        .map_err(|e| ApiError::BadRequest(format!("验证失败: {}", e)))?;
+
+    let service = VmTemplateService::new(state);
+    Ok(Json(service.update_template(&id, dto).await?))
+}
+
+/// 删除虚拟机模板
+///
+/// DELETE /api/vm-templates/:id
+async fn delete_template(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = VmTemplateService::new(state);
+    service.delete_template(&id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
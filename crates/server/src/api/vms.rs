@@ -9,28 +9,54 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 
+use crate::api::utils::check_permission;
 use crate::app_state::AppState;
-use crate::db::models::vm::{CreateVmDto, UpdateVmDto, VmListResponse, VmResponse, AttachVolumeDto, DetachVolumeDto, VmDiskResponse};
-use crate::services::vm_service::VmService;
+use crate::db::models::vm_template::CreateVmFromTemplateDto;
+use crate::db::models::vm::{BatchVmOperationDto, BatchVmOperationResponse, ConsoleAccessResponse, CreateVmDto, UpdateVmDto, VmListResponse, VmResponse, AttachVolumeDto, DetachVolumeDto, SetVolumeIotuneDto, SetNicBandwidthDto, VmDiskResponse, RebuildVmDto, MigrateVmDto, GuestExecDto, CloneVmDto, VmLiveStateResponse, GuestNetworkResponse};
+use crate::auth::Claims;
+use crate::extractors::AuthUser;
+use crate::services::department_service::QuotaExceeded;
+use crate::services::scheduler_service::CapacityExceeded;
+use crate::services::vm_service::{VmService, VmStatusConflict};
+use crate::services::vm_template_service::VmTemplateService;
+use common::ws_rpc::{
+    GetDomainXmlResponse, GuestExecRequest, GuestExecResponse, MigrationFallbackPolicy, MigrationSpeedResponse, MigrationStorageMode,
+};
 
 /// API 错误响应
 #[derive(Debug, Serialize)]
 struct ErrorResponse {
     error: String,
     message: String,
+    /// 结构化的错误明细（如节点容量不足时的分配情况）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
-            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
-            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
-            ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+        let (status, message, details) = match self {
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg, None),
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg, None),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg, None),
+            ApiError::CapacityExceeded(err) => (
+                StatusCode::CONFLICT,
+                err.to_string(),
+                serde_json::to_value(&err).ok(),
+            ),
+            ApiError::QuotaExceeded(err) => (
+                StatusCode::FORBIDDEN,
+                err.to_string(),
+                serde_json::to_value(&err).ok(),
+            ),
+            ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg, None),
+            ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg, None),
         };
 
         let body = Json(ErrorResponse {
             error: status.canonical_reason().unwrap_or("Unknown").to_string(),
             message,
+            details,
         });
 
         (status, body).into_response()
@@ -41,12 +67,27 @@ impl IntoResponse for ApiError {
 pub(crate) enum ApiError {
     NotFound(String),
     BadRequest(String),
+    Forbidden(String),
+    CapacityExceeded(CapacityExceeded),
+    QuotaExceeded(QuotaExceeded),
+    Conflict(String),
     Internal(String),
 }
 
 impl From<anyhow::Error> for ApiError {
     fn from(err: anyhow::Error) -> Self {
-        ApiError::Internal(err.to_string())
+        let err = match err.downcast::<CapacityExceeded>() {
+            Ok(err) => return ApiError::CapacityExceeded(err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<QuotaExceeded>() {
+            Ok(err) => return ApiError::QuotaExceeded(err),
+            Err(err) => err,
+        };
+        match err.downcast::<VmStatusConflict>() {
+            Ok(err) => ApiError::Conflict(err.to_string()),
+            Err(err) => ApiError::Internal(err.to_string()),
+        }
     }
 }
 
@@ -78,12 +119,14 @@ fn default_page_size() -> usize {
     20
 }
 
-/// 迁移请求
+/// 启动虚拟机查询参数
 #[derive(Debug, Deserialize)]
-pub struct MigrateVmRequest {
-    pub target_node_id: String,
+pub struct StartVmQuery {
+    /// 安全模式：仅挂载系统盘和一块默认网卡，用于修复无法启动的配置
     #[serde(default)]
-    pub live: bool,
+    pub safe_mode: bool,
+    /// 仅本次启动从指定存储卷（如安装光盘）引导
+    pub boot_from: Option<String>,
 }
 
 /// 停止虚拟机请求
@@ -97,15 +140,49 @@ pub struct StopVmRequest {
 pub fn vm_routes() -> Router<AppState> {
     Router::new()
         .route("/", get(list_vms).post(create_vm))
+        .route("/batch", post(batch_operation))
+        .route("/from-template/:template_id", post(create_vm_from_template))
         .route("/:id", get(get_vm).put(update_vm).delete(delete_vm))
         .route("/:id/start", post(start_vm))
         .route("/:id/stop", post(stop_vm))
         .route("/:id/restart", post(restart_vm))
+        .route("/:id/pause", post(pause_vm))
+        .route("/:id/resume", post(resume_vm))
         .route("/:id/migrate", post(migrate_vm))
+        .route("/:id/migrate/abort", post(abort_migration))
+        .route("/:id/migrate/speed", get(get_migration_speed))
+        .route("/:id/rebuild", post(rebuild_vm))
+        .route("/:id/clone", post(clone_vm))
+        .route("/:id/exec", post(guest_exec))
+        .route("/:id/live-state", get(get_vm_live_state))
+        .route("/:id/guest-network", get(get_guest_network))
+        .route("/:id/domain-xml", get(get_domain_xml))
+        .route("/:id/console", post(request_console_access))
         .route("/:id/volumes", get(list_vm_volumes))
         .route("/:id/volumes/attach", post(attach_volume))
         .route("/:id/volumes/detach", post(detach_volume))
+        .route("/:id/volumes/iotune", post(set_volume_iotune))
         .route("/:id/networks", get(get_vm_networks))
+        .route("/:id/networks/bandwidth", post(set_nic_bandwidth))
+}
+
+/// 校验当前用户拥有 vm:<action> 权限
+async fn require_permission(
+    state: &AppState,
+    claims: Option<&Claims>,
+    action: &str,
+) -> Result<(), ApiError> {
+    let claims = claims.ok_or_else(|| ApiError::Forbidden("未认证".to_string()))?;
+    check_permission(&state.sea_db(), claims.sub, "vm", action)
+        .await
+        .map_err(|(status, Json(body))| {
+            let message = body["error"].as_str().unwrap_or("权限不足").to_string();
+            if status == StatusCode::FORBIDDEN {
+                ApiError::Forbidden(message)
+            } else {
+                ApiError::Internal(message)
+            }
+        })
 }
 
 /// 获取虚拟机列表
@@ -128,10 +205,18 @@ pub async fn list_vms(
 ///
 /// POST /api/vms
 /// Body: CreateVmDto
+///
+/// 设置 raw_xml_override 需要 vm:xml 权限
 pub async fn create_vm(
     State(state): State<AppState>,
-    Json(dto): Json<CreateVmDto>,
+    auth: Option<AuthUser>,
+    Json(mut dto): Json<CreateVmDto>,
 ) -> Result<(StatusCode, Json<VmResponse>), ApiError> {
+    dto.owner_id = auth.as_ref().map(|AuthUser(claims)| claims.sub);
+    if dto.raw_xml_override.is_some() {
+        require_permission(&state, auth.as_ref().map(|AuthUser(claims)| claims), "xml").await?;
+    }
+
     // 验证参数
     if dto.name.is_empty() {
         return Err(ApiError::BadRequest("虚拟机名称不能为空".to_string()));
@@ -158,6 +243,30 @@ pub async fn create_vm(
     Ok((StatusCode::CREATED, Json(result)))
 }
 
+/// 从模板创建虚拟机
+///
+/// POST /api/vms/from-template/:template_id
+/// Body: { "name": "web-1", "node_id": "...", "vcpu": 4 }，未指定的字段使用模板中的值
+pub async fn create_vm_from_template(
+    State(state): State<AppState>,
+    Path(template_id): Path<String>,
+    auth: Option<AuthUser>,
+    Json(mut dto): Json<CreateVmFromTemplateDto>,
+) -> Result<(StatusCode, Json<VmResponse>), ApiError> {
+    dto.owner_id = auth.map(|AuthUser(claims)| claims.sub);
+
+    let service = VmTemplateService::new(state);
+    let result = service
+        .create_vm(&template_id, dto)
+        .await
+        .map_err(|e| match ApiError::from(e) {
+            ApiError::Internal(msg) if msg.contains("模板不存在") => ApiError::NotFound(msg),
+            other => other,
+        })?;
+
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
 /// 获取单个虚拟机详情
 ///
 /// GET /api/vms/:id
@@ -175,15 +284,81 @@ pub async fn get_vm(
     Ok(Json(result))
 }
 
+/// 获取虚拟机实时状态
+///
+/// GET /api/vms/:id/live-state
+pub async fn get_vm_live_state(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<VmLiveStateResponse>, ApiError> {
+    let service = VmService::new(state.clone());
+    let live_state = service.get_vm_live_state(&id).await?;
+
+    Ok(Json(live_state))
+}
+
+/// 获取客户机内的网卡与 IP 地址（需要客户机运行 qemu-guest-agent）
+///
+/// GET /api/vms/:id/guest-network
+pub async fn get_guest_network(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<GuestNetworkResponse>, ApiError> {
+    let service = VmService::new(state.clone());
+    let network = service.get_guest_network(&id).await?;
+
+    Ok(Json(network))
+}
+
+/// 获取虚拟机在所在节点上的域 XML
+///
+/// GET /api/vms/:id/domain-xml
+pub async fn get_domain_xml(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<GetDomainXmlResponse>, ApiError> {
+    let service = VmService::new(state.clone());
+    let xml = service.get_domain_xml(&id).await?;
+
+    Ok(Json(xml))
+}
+
+/// 申请控制台访问，返回一次性密码和 WebSocket 代理地址
+///
+/// POST /api/vms/:id/console
+///
+/// 仅虚拟机所有者或拥有 vm:console 权限的用户可以申请
+pub async fn request_console_access(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<ConsoleAccessResponse>, ApiError> {
+    let service = VmService::new(state.clone());
+    if !service.can_access_console(&id, claims.sub).await? {
+        return Err(ApiError::Forbidden("无权访问该虚拟机控制台".to_string()));
+    }
+
+    let access = service.request_console_access(&id, &claims.username).await?;
+
+    Ok(Json(access))
+}
+
 /// 更新虚拟机
 ///
 /// PUT /api/vms/:id
 /// Body: UpdateVmDto
+///
+/// 设置 raw_xml_override 需要 vm:xml 权限
 pub async fn update_vm(
     State(state): State<AppState>,
+    auth: Option<AuthUser>,
     Path(id): Path<String>,
     Json(dto): Json<UpdateVmDto>,
 ) -> Result<Json<VmResponse>, ApiError> {
+    if dto.raw_xml_override.is_some() {
+        require_permission(&state, auth.as_ref().map(|AuthUser(claims)| claims), "xml").await?;
+    }
+
     let service = VmService::new(state.clone());
     let result = service.update_vm(&id, dto).await?;
 
@@ -203,19 +378,47 @@ pub async fn delete_vm(
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// 批量操作虚拟机
+///
+/// POST /api/vms/batch
+/// Body: { "vm_ids": ["..."], "action": "start" | "stop" | "restart" | "delete", "force": false }
+///
+/// 逐台返回结果，部分虚拟机失败时整体仍返回 200
+pub async fn batch_operation(
+    State(state): State<AppState>,
+    Json(dto): Json<BatchVmOperationDto>,
+) -> Result<Json<BatchVmOperationResponse>, ApiError> {
+    let service = VmService::new(state.clone());
+    let result = service
+        .batch_operation(dto)
+        .await
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    Ok(Json(result))
+}
+
 /// 启动虚拟机
 ///
-/// POST /api/vms/:id/start
+/// POST /api/vms/:id/start?safe_mode=true&boot_from=<volume_id>
+///
+/// 安全模式和 boot_from 只影响本次启动，不修改虚拟机保存的配置
 pub async fn start_vm(
     State(state): State<AppState>,
     Path(id): Path<String>,
+    Query(query): Query<StartVmQuery>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
     let service = VmService::new(state.clone());
-    service.start_vm(&id).await?;
+    let task_id = service.start_vm(&id, query.safe_mode, query.boot_from.as_deref()).await?;
 
+    let message = if query.safe_mode {
+        "虚拟机正在以安全模式启动"
+    } else {
+        "虚拟机启动成功"
+    };
     Ok(Json(serde_json::json!({
         "success": true,
-        "message": "虚拟机启动成功"
+        "message": message,
+        "task_id": task_id
     })))
 }
 
@@ -229,11 +432,12 @@ pub async fn stop_vm(
     Json(req): Json<StopVmRequest>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
     let service = VmService::new(state.clone());
-    service.stop_vm(&id, req.force).await?;
+    let task_id = service.stop_vm(&id, req.force).await?;
 
     Ok(Json(serde_json::json!({
         "success": true,
-        "message": "虚拟机停止成功"
+        "message": "虚拟机停止成功",
+        "task_id": task_id
     })))
 }
 
@@ -245,31 +449,91 @@ pub async fn restart_vm(
     Path(id): Path<String>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
     let service = VmService::new(state.clone());
-    service.restart_vm(&id).await?;
+    let task_id = service.restart_vm(&id).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "虚拟机重启中",
+        "task_id": task_id
+    })))
+}
+
+/// 暂停虚拟机
+///
+/// POST /api/vms/:id/pause
+pub async fn pause_vm(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let service = VmService::new(state.clone());
+    service.pause_vm(&id).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "虚拟机已暂停"
+    })))
+}
+
+/// 恢复已暂停的虚拟机
+///
+/// POST /api/vms/:id/resume
+pub async fn resume_vm(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let service = VmService::new(state.clone());
+    service.resume_vm(&id).await?;
 
     Ok(Json(serde_json::json!({
         "success": true,
-        "message": "虚拟机重启中"
+        "message": "虚拟机已恢复运行"
     })))
 }
 
 /// 迁移虚拟机
 ///
 /// POST /api/vms/:id/migrate
-/// Body: MigrateVmRequest
+/// Body: MigrateVmDto
 pub async fn migrate_vm(
     State(state): State<AppState>,
     Path(id): Path<String>,
-    Json(req): Json<MigrateVmRequest>,
+    Json(dto): Json<MigrateVmDto>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
-    if req.target_node_id.is_empty() {
+    if dto.target_node_id.is_empty() {
         return Err(ApiError::BadRequest("目标节点 ID 不能为空".to_string()));
     }
+    let has_live_options = dto.max_bandwidth_mbps.is_some()
+        || dto.max_downtime_ms.is_some()
+        || dto.fallback_policy != MigrationFallbackPolicy::None
+        || dto.live_timeout_secs.is_some()
+        || dto.storage_mode != MigrationStorageMode::SharedStorage;
+    if !dto.live && has_live_options {
+        return Err(ApiError::BadRequest(
+            "带宽、停机时间、超时降级及复制存储设置仅适用于热迁移".to_string(),
+        ));
+    }
+    if dto.max_bandwidth_mbps == Some(0) {
+        return Err(ApiError::BadRequest("迁移带宽上限必须大于 0".to_string()));
+    }
+    if dto.max_downtime_ms == Some(0) {
+        return Err(ApiError::BadRequest("最大停机时间必须大于 0".to_string()));
+    }
+    match (dto.fallback_policy, dto.live_timeout_secs) {
+        (MigrationFallbackPolicy::Suspend, None) | (MigrationFallbackPolicy::Suspend, Some(0)) => {
+            return Err(ApiError::BadRequest(
+                "降级策略为 suspend 时必须指定大于 0 的 live_timeout_secs".to_string(),
+            ));
+        }
+        (MigrationFallbackPolicy::None, Some(_)) => {
+            return Err(ApiError::BadRequest(
+                "live_timeout_secs 仅在降级策略为 suspend 时生效".to_string(),
+            ));
+        }
+        _ => {}
+    }
 
     let service = VmService::new(state.clone());
-    service
-        .migrate_vm(&id, &req.target_node_id, req.live)
-        .await?;
+    service.migrate_vm(&id, dto).await?;
 
     Ok(Json(serde_json::json!({
         "success": true,
@@ -277,6 +541,107 @@ pub async fn migrate_vm(
     })))
 }
 
+/// 查询虚拟机当前的迁移带宽上限
+///
+/// GET /api/vms/:id/migrate/speed
+pub async fn get_migration_speed(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<MigrationSpeedResponse>, ApiError> {
+    let service = VmService::new(state.clone());
+    let speed = service.get_migration_speed(&id).await?;
+
+    Ok(Json(speed))
+}
+
+/// 取消虚拟机迁移
+///
+/// POST /api/vms/:id/migrate/abort
+pub async fn abort_migration(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let service = VmService::new(state.clone());
+    service.abort_migration(&id).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "已取消迁移"
+    })))
+}
+
+/// 重装虚拟机系统盘
+///
+/// POST /api/vms/:id/rebuild
+/// Body: RebuildVmDto
+pub async fn rebuild_vm(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(dto): Json<RebuildVmDto>,
+) -> Result<Json<VmResponse>, ApiError> {
+    if dto.source.trim().is_empty() {
+        return Err(ApiError::BadRequest("镜像来源不能为空".to_string()));
+    }
+
+    let service = VmService::new(state.clone());
+    let result = service.rebuild_vm(&id, dto).await?;
+
+    Ok(Json(result))
+}
+
+/// 克隆虚拟机
+///
+/// POST /api/vms/:id/clone
+/// Body: CloneVmDto
+pub async fn clone_vm(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(dto): Json<CloneVmDto>,
+) -> Result<(StatusCode, Json<VmResponse>), ApiError> {
+    if dto.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("虚拟机名称不能为空".to_string()));
+    }
+
+    let service = VmService::new(state.clone());
+    let result = service.clone_vm(&id, dto).await?;
+
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
+/// 在虚拟机内执行命令
+///
+/// POST /api/vms/:id/exec
+/// Body: GuestExecDto
+///
+/// 需要 vm:exec 权限，虚拟机内须运行 qemu-guest-agent
+pub async fn guest_exec(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Path(id): Path<String>,
+    Json(dto): Json<GuestExecDto>,
+) -> Result<Json<GuestExecResponse>, ApiError> {
+    require_permission(&state, Some(&claims), "exec").await?;
+
+    if dto.command.trim().is_empty() {
+        return Err(ApiError::BadRequest("命令不能为空".to_string()));
+    }
+    if let Some(timeout_secs) = dto.timeout_secs {
+        if timeout_secs == 0 || timeout_secs > GuestExecRequest::MAX_TIMEOUT_SECS {
+            return Err(ApiError::BadRequest(format!(
+                "timeout_secs 须在 1 到 {} 秒之间",
+                GuestExecRequest::MAX_TIMEOUT_SECS
+            )));
+        }
+    }
+
+    let service = VmService::new(state.clone());
+    let result = service
+        .guest_exec(&id, dto, &claims.sub.to_string())
+        .await?;
+
+    Ok(Json(result))
+}
+
 /// 附加存储卷到虚拟机
 ///
 /// POST /api/vms/:id/volumes/attach
@@ -322,6 +687,29 @@ pub async fn detach_volume(
     })))
 }
 
+/// 调整虚拟机存储卷的 I/O 限速
+///
+/// POST /api/vms/:id/volumes/iotune
+/// Body: SetVolumeIotuneDto
+pub async fn set_volume_iotune(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(dto): Json<SetVolumeIotuneDto>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if dto.volume_id.is_empty() {
+        return Err(ApiError::BadRequest("存储卷 ID 不能为空".to_string()));
+    }
+    dto.limits.validate().map_err(ApiError::BadRequest)?;
+
+    let service = VmService::new(state.clone());
+    service.set_volume_iotune(&id, dto).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "存储卷 I/O 限速已更新"
+    })))
+}
+
 /// 获取虚拟机的所有存储卷
 ///
 /// GET /api/vms/:id/volumes
@@ -336,6 +724,29 @@ pub async fn list_vm_volumes(
 }
 
 
+/// 调整虚拟机网卡的带宽限速
+///
+/// POST /api/vms/:id/networks/bandwidth
+/// Body: SetNicBandwidthDto
+pub async fn set_nic_bandwidth(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(dto): Json<SetNicBandwidthDto>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if dto.mac_address.is_empty() {
+        return Err(ApiError::BadRequest("MAC 地址不能为空".to_string()));
+    }
+    dto.limits.validate().map_err(ApiError::BadRequest)?;
+
+    let service = VmService::new(state.clone());
+    service.set_nic_bandwidth(&id, dto).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "网卡带宽限速已更新"
+    })))
+}
+
 /// 获取虚拟机网络信息
 ///
 /// GET /api/vms/:id/networks
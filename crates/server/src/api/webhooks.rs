@@ -0,0 +1,179 @@
+/// Webhook 管理接口
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Serialize;
+use validator::Validate;
+
+use crate::api::utils::check_permission;
+use crate::app_state::AppState;
+use crate::db::models::webhook::{CreateWebhookDto, UpdateWebhookDto};
+use crate::extractors::AuthUser;
+use crate::services::webhook_service::WebhookService;
+
+/// API 错误响应
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+    message: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+        };
+
+        let body = Json(ErrorResponse {
+            error: status.canonical_reason().unwrap_or("Unknown").to_string(),
+            message,
+        });
+
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug)]
+enum ApiError {
+    NotFound(String),
+    BadRequest(String),
+    Forbidden(String),
+    Internal(String),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        let msg = err.to_string();
+        if msg.contains("不存在") {
+            ApiError::NotFound(msg)
+        } else {
+            ApiError::BadRequest(msg)
+        }
+    }
+}
+
+/// 创建路由
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_webhooks).post(create_webhook))
+        .route(
+            "/:id",
+            get(get_webhook).put(update_webhook).delete(delete_webhook),
+        )
+        .route("/:id/test", post(test_webhook))
+}
+
+/// Webhook 配置需要 system:configure 权限
+async fn require_configure(state: &AppState, user_id: i32) -> Result<(), ApiError> {
+    check_permission(&state.sea_db(), user_id, "system", "configure")
+        .await
+        .map_err(|(status, Json(body))| {
+            let message = body["error"].as_str().unwrap_or("权限不足").to_string();
+            if status == StatusCode::FORBIDDEN {
+                ApiError::Forbidden(message)
+            } else {
+                ApiError::Internal(message)
+            }
+        })
+}
+
+/// 创建 Webhook
+///
+/// POST /api/webhooks
+/// Body: { "name": "ops", "url": "https://...", "secret": "...", "events": ["node.offline"] }
+async fn create_webhook(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Json(dto): Json<CreateWebhookDto>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_configure(&state, claims.sub).await?;
+    dto.validate()
+        .map_err(|e| ApiError::BadRequest(format!("验证失败: {}", e)))?;
+
+    let service = WebhookService::new(state);
+    let webhook = service.create_webhook(dto).await?;
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+/// 获取 Webhook 列表
+///
+/// GET /api/webhooks
+async fn list_webhooks(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    require_configure(&state, claims.sub).await?;
+
+    let service = WebhookService::new(state);
+    Ok(Json(service.list_webhooks().await?))
+}
+
+/// 获取 Webhook 详情
+///
+/// GET /api/webhooks/:id
+async fn get_webhook(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_configure(&state, claims.sub).await?;
+
+    let service = WebhookService::new(state);
+    Ok(Json(service.get_webhook(&id).await?))
+}
+
+/// 更新 Webhook
+///
+/// PUT /api/webhooks/:id
+async fn update_webhook(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Path(id): Path<String>,
+    Json(dto): Json<UpdateWebhookDto>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_configure(&state, claims.sub).await?;
+    dto.validate()
+        .map_err(|e| ApiError::BadRequest(format!("验证失败: {}", e)))?;
+
+    let service = WebhookService::new(state);
+    Ok(Json(service.update_webhook(&id, dto).await?))
+}
+
+/// 删除 Webhook
+///
+/// DELETE /api/webhooks/:id
+async fn delete_webhook(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_configure(&state, claims.sub).await?;
+
+    let service = WebhookService::new(state);
+    service.delete_webhook(&id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 发送测试事件
+///
+/// POST /api/webhooks/:id/test
+///
+/// 同步等待一次投递（不重试），返回带最近投递结果的 Webhook
+async fn test_webhook(
+    State(state): State<AppState>,
+    AuthUser(claims): AuthUser,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_configure(&state, claims.sub).await?;
+
+    let service = WebhookService::new(state);
+    Ok(Json(service.send_test(&id).await?))
+}
@@ -1,7 +1,16 @@
 /// 应用全局状态
 
+use common::utils::BridgeNaming;
+use common::ws_rpc::{DiskBusType, DEFAULT_COMPRESS_THRESHOLD};
 use sea_orm::DatabaseConnection;
-use crate::ws::{AgentConnectionManager, FrontendConnectionManager};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use crate::config::{
+    AgentAuthTokens, NodeAlertThresholds, OvercommitRatios, PlacementStrategy, S3Settings,
+    SafetySnapshotPolicy, WebhookSettings,
+};
+use crate::services::secret_cipher::SecretCipher;
+use crate::ws::{AgentConnectionManager, AgentRpc, FrontendConnectionManager};
 
 /// 应用状态
 #[derive(Clone)]
@@ -10,19 +19,62 @@ pub struct AppState {
     pub sea_db: DatabaseConnection,
     /// Agent WebSocket 连接管理器
     pub agent_manager: AgentConnectionManager,
+    /// 服务层调用 Agent 的入口，默认即 agent_manager，测试中可替换
+    pub agent_rpc: Arc<dyn AgentRpc>,
     /// 前端 WebSocket 连接管理器
     pub frontend_manager: FrontendConnectionManager,
+    /// Bridge 命名规则（需与 Agent 配置保持一致）
+    pub bridge_naming: BridgeNaming,
+    /// 只读维护模式开关（开启时拒绝所有写操作）
+    pub read_only: Arc<AtomicBool>,
+    /// 节点告警阈值
+    pub node_alert_thresholds: NodeAlertThresholds,
+    /// 挂载存储卷的默认总线
+    pub default_disk_bus: DiskBusType,
+    /// 破坏性操作前的安全快照策略
+    pub safety_snapshot: SafetySnapshotPolicy,
+    /// Webhook 事件阈值与投递参数
+    pub webhook: WebhookSettings,
+    /// 未指定节点时的虚拟机放置策略
+    pub placement_strategy: PlacementStrategy,
+    /// 节点 vCPU 与内存超分比例
+    pub overcommit: OvercommitRatios,
+    /// 发往 Agent 的 RPC 消息压缩阈值（字节），0 表示不压缩
+    pub rpc_compress_threshold: usize,
+    /// Agent 注册共享密钥
+    pub agent_auth: AgentAuthTokens,
+    /// s3:// 源地址使用的对象存储参数
+    pub s3: Option<S3Settings>,
+    /// 入库凭据（IPMI 密码等）的加解密
+    pub secrets: SecretCipher,
 }
 
 impl AppState {
     pub fn new(
         sea_db: DatabaseConnection,
         agent_manager: AgentConnectionManager,
+        bridge_naming: BridgeNaming,
+        read_only: bool,
+        node_alert_thresholds: NodeAlertThresholds,
+        default_disk_bus: DiskBusType,
     ) -> Self {
         Self {
             sea_db,
+            agent_rpc: Arc::new(agent_manager.clone()),
             agent_manager,
             frontend_manager: FrontendConnectionManager::new(),
+            bridge_naming,
+            read_only: Arc::new(AtomicBool::new(read_only)),
+            node_alert_thresholds,
+            default_disk_bus,
+            safety_snapshot: SafetySnapshotPolicy::default(),
+            webhook: WebhookSettings::default(),
+            placement_strategy: PlacementStrategy::default(),
+            overcommit: OvercommitRatios::default(),
+            rpc_compress_threshold: DEFAULT_COMPRESS_THRESHOLD,
+            agent_auth: AgentAuthTokens::default(),
+            s3: None,
+            secrets: SecretCipher::new("change-me-in-production"),
         }
     }
 
@@ -36,9 +88,104 @@ impl AppState {
         self.agent_manager.clone()
     }
 
+    /// 替换 Agent RPC 实现（用于测试）
+    #[cfg(test)]
+    pub fn with_agent_rpc(mut self, agent_rpc: Arc<dyn AgentRpc>) -> Self {
+        self.agent_rpc = agent_rpc;
+        self
+    }
+
+    /// 设置安全快照策略
+    pub fn with_safety_snapshot(mut self, policy: SafetySnapshotPolicy) -> Self {
+        self.safety_snapshot = policy;
+        self
+    }
+
+    /// 设置 Webhook 参数
+    pub fn with_webhook_settings(mut self, settings: WebhookSettings) -> Self {
+        self.webhook = settings;
+        self
+    }
+
+    /// 设置虚拟机放置策略
+    pub fn with_placement_strategy(mut self, strategy: PlacementStrategy) -> Self {
+        self.placement_strategy = strategy;
+        self
+    }
+
+    /// 设置节点超分比例
+    pub fn with_overcommit_ratios(mut self, ratios: OvercommitRatios) -> Self {
+        self.overcommit = ratios;
+        self
+    }
+
+    /// 设置 RPC 消息压缩阈值
+    pub fn with_rpc_compress_threshold(mut self, threshold: usize) -> Self {
+        self.rpc_compress_threshold = threshold;
+        self
+    }
+
+    /// 设置 Agent 注册共享密钥
+    pub fn with_agent_auth(mut self, tokens: AgentAuthTokens) -> Self {
+        self.agent_auth = tokens;
+        self
+    }
+
+    /// 设置对象存储参数
+    pub fn with_s3_settings(mut self, settings: Option<S3Settings>) -> Self {
+        self.s3 = settings;
+        self
+    }
+
+    /// 设置入库凭据加密密钥
+    pub fn with_secret_key(mut self, key: &str) -> Self {
+        self.secrets = SecretCipher::new(key);
+        self
+    }
+
+    /// 获取 Agent RPC 调用入口
+    pub fn agent_rpc(&self) -> Arc<dyn AgentRpc> {
+        self.agent_rpc.clone()
+    }
+
     /// 获取前端连接管理器
     pub fn frontend_manager(&self) -> FrontendConnectionManager {
         self.frontend_manager.clone()
     }
+
+    /// 获取 Bridge 命名规则
+    pub fn bridge_naming(&self) -> &BridgeNaming {
+        &self.bridge_naming
+    }
+
+    /// 获取节点告警阈值
+    pub fn node_alert_thresholds(&self) -> NodeAlertThresholds {
+        self.node_alert_thresholds
+    }
+
+    /// 获取挂载存储卷的默认总线
+    pub fn default_disk_bus(&self) -> DiskBusType {
+        self.default_disk_bus.clone()
+    }
+
+    /// 获取安全快照策略
+    pub fn safety_snapshot(&self) -> SafetySnapshotPolicy {
+        self.safety_snapshot
+    }
+
+    /// 获取 Webhook 参数
+    pub fn webhook_settings(&self) -> WebhookSettings {
+        self.webhook
+    }
+
+    /// 是否处于只读维护模式
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+
+    /// 切换只读维护模式
+    pub fn set_read_only(&self, enabled: bool) {
+        self.read_only.store(enabled, Ordering::SeqCst);
+    }
 }
 
@@ -2,16 +2,49 @@
 
 pub mod models;
 
-use sea_orm::{Database, DatabaseConnection};
+use sea_orm::{ConnectOptions, Database, DatabaseConnection};
+use serde::Deserialize;
+use std::time::Duration;
 use tracing::info;
 
+/// 数据库连接选项
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct DbOptions {
+    /// 单条 SQL 的执行超时（毫秒），由 PostgreSQL 的 statement_timeout 强制，0 表示不限制
+    pub statement_timeout_ms: u64,
+    /// 执行时间超过该阈值（毫秒）的 SQL 以 warn 级别记录，附带耗时
+    pub slow_query_ms: u64,
+}
+
 /// 建立数据库连接 (SeaORM)
-pub async fn establish_connection(database_url: &str) -> Result<DatabaseConnection, anyhow::Error> {
+pub async fn establish_connection(
+    database_url: &str,
+    options: DbOptions,
+) -> Result<DatabaseConnection, anyhow::Error> {
     info!("正在连接数据库: {}", database_url);
 
-    let db = Database::connect(database_url).await?;
-    info!("数据库连接成功");
+    let mut opt = ConnectOptions::new(database_url);
+    // 普通语句降为 debug，慢查询由 sqlx 单独以 warn 级别输出
+    opt.sqlx_logging(true)
+        .sqlx_logging_level(log::LevelFilter::Debug)
+        .sqlx_slow_statements_logging_settings(
+            log::LevelFilter::Warn,
+            Duration::from_millis(options.slow_query_ms),
+        );
+
+    if options.statement_timeout_ms > 0 {
+        // 每个连接建立时设置会话级 statement_timeout，超时的查询由数据库中止并返回错误
+        let timeout = options.statement_timeout_ms.to_string();
+        opt.map_sqlx_postgres_opts(move |pg_opts| {
+            pg_opts.options([("statement_timeout", timeout.as_str())])
+        });
+    }
+
+    let db = Database::connect(opt).await?;
+    info!(
+        "数据库连接成功 (statement_timeout: {}ms, 慢查询阈值: {}ms)",
+        options.statement_timeout_ms, options.slow_query_ms
+    );
 
     Ok(db)
 }
-
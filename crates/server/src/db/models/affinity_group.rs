@@ -0,0 +1,106 @@
+/// 亲和组数据模型
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// 亲和组模型
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "affinity_groups")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    pub name: String,
+    pub policy: String,  // affinity, anti-affinity
+    pub description: Option<String>,
+
+    // 时间戳
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::affinity_group_member::Entity")]
+    Members,
+}
+
+impl Related<super::affinity_group_member::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Members.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// 亲和策略
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AffinityPolicy {
+    /// 亲和：优先放置在组内其他虚拟机所在的节点
+    Affinity,
+    /// 反亲和：不允许与组内其他虚拟机位于同一节点
+    AntiAffinity,
+}
+
+impl AffinityPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AffinityPolicy::Affinity => "affinity",
+            AffinityPolicy::AntiAffinity => "anti-affinity",
+        }
+    }
+}
+
+impl From<String> for AffinityPolicy {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "anti-affinity" => AffinityPolicy::AntiAffinity,
+            _ => AffinityPolicy::Affinity,
+        }
+    }
+}
+
+/// 创建亲和组 DTO
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CreateAffinityGroupDto {
+    #[validate(length(min = 1, max = 255))]
+    pub name: String,
+    pub policy: AffinityPolicy,
+    pub description: Option<String>,
+    /// 初始成员
+    #[serde(default)]
+    pub vm_ids: Vec<String>,
+}
+
+/// 添加亲和组成员 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddAffinityMemberDto {
+    pub vm_id: String,
+}
+
+/// 亲和组响应 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AffinityGroupResponse {
+    pub id: String,
+    pub name: String,
+    pub policy: String,
+    pub description: Option<String>,
+    pub vm_ids: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl AffinityGroupResponse {
+    pub fn new(group: Model, vm_ids: Vec<String>) -> Self {
+        Self {
+            id: group.id,
+            name: group.name,
+            policy: group.policy,
+            description: group.description,
+            vm_ids,
+            created_at: group.created_at.to_rfc3339(),
+            updated_at: group.updated_at.to_rfc3339(),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+/// 亲和组成员数据模型
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "affinity_group_members")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub group_id: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub vm_id: String,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::affinity_group::Entity",
+        from = "Column::GroupId",
+        to = "super::affinity_group::Column::Id"
+    )]
+    Group,
+    #[sea_orm(
+        belongs_to = "super::vm::Entity",
+        from = "Column::VmId",
+        to = "super::vm::Column::Id"
+    )]
+    Vm,
+}
+
+impl Related<super::affinity_group::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Group.def()
+    }
+}
+
+impl Related<super::vm::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Vm.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
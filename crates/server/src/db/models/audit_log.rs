@@ -0,0 +1,123 @@
+/// 审计日志数据模型
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+/// 审计日志模型
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "audit_logs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    pub actor: String, // user, system
+    pub user_id: Option<i32>,
+    pub username: Option<String>,
+    pub action: String,              // create, update, delete, start ...
+    pub target_type: Option<String>, // vms, volumes, nodes ...
+    pub target_id: Option<String>,
+    pub target_name: Option<String>,
+
+    // 详细信息
+    pub detail: Option<JsonValue>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub method: Option<String>,
+    pub path: Option<String>, // 不含查询参数
+    pub status_code: Option<i32>,
+
+    // 结果
+    pub success: bool,
+    pub error_message: Option<String>,
+
+    pub timestamp: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// 操作发起方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditActor {
+    /// 通过 API 发起的请求
+    User,
+    /// Agent 上报触发的状态变更
+    System,
+}
+
+impl AuditActor {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditActor::User => "user",
+            AuditActor::System => "system",
+        }
+    }
+}
+
+/// 审计日志查询条件
+#[derive(Debug, Default)]
+pub struct AuditLogFilter {
+    /// 用户 ID 或用户名
+    pub user: Option<String>,
+    /// 资源类型（target_type）
+    pub resource: Option<String>,
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// 审计日志响应 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogResponse {
+    pub id: String,
+    pub actor: String,
+    pub user_id: Option<i32>,
+    pub username: Option<String>,
+    pub action: String,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub target_name: Option<String>,
+    pub detail: Option<JsonValue>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub status_code: Option<i32>,
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub timestamp: String,
+}
+
+impl From<Model> for AuditLogResponse {
+    fn from(log: Model) -> Self {
+        Self {
+            id: log.id,
+            actor: log.actor,
+            user_id: log.user_id,
+            username: log.username,
+            action: log.action,
+            target_type: log.target_type,
+            target_id: log.target_id,
+            target_name: log.target_name,
+            detail: log.detail,
+            ip_address: log.ip_address,
+            user_agent: log.user_agent,
+            method: log.method,
+            path: log.path,
+            status_code: log.status_code,
+            success: log.success,
+            error_message: log.error_message,
+            timestamp: log.timestamp.to_rfc3339(),
+        }
+    }
+}
+
+/// 审计日志列表响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogListResponse {
+    pub logs: Vec<AuditLogResponse>,
+    pub total: usize,
+    pub page: usize,
+    pub page_size: usize,
+}
@@ -15,6 +15,13 @@ pub struct Model {
     pub description: Option<String>,
     pub manager_id: Option<i32>,
     pub is_active: bool,
+
+    // 资源配额（按部门成员名下的虚拟机和存储卷汇总），None 表示不限制
+    pub max_vcpu: Option<i32>,
+    pub max_memory_mb: Option<i64>,
+    pub max_volumes: Option<i32>,
+    pub max_volume_gb: Option<i64>,
+
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
 }
@@ -94,6 +101,41 @@ pub struct UpdateDepartmentDto {
     pub is_active: Option<bool>,
 }
 
+/// 设置部门资源配额，整体替换，字段为空表示不限制
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct UpdateDepartmentQuotaDto {
+    #[validate(range(min = 0, message = "vCPU 配额必须大于等于0"))]
+    pub max_vcpu: Option<i32>,
+
+    #[validate(range(min = 0, message = "内存配额必须大于等于0"))]
+    pub max_memory_mb: Option<i64>,
+
+    #[validate(range(min = 0, message = "存储卷数量配额必须大于等于0"))]
+    pub max_volumes: Option<i32>,
+
+    #[validate(range(min = 0, message = "存储卷容量配额必须大于等于0"))]
+    pub max_volume_gb: Option<i64>,
+}
+
+/// 单项资源的用量与上限
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct QuotaUsageDto {
+    pub used: i64,
+    /// None 表示不限制
+    pub limit: Option<i64>,
+}
+
+/// 部门资源用量，统计部门成员名下的虚拟机和存储卷（不含子部门）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DepartmentUsageDto {
+    pub department_id: i32,
+    pub department_name: String,
+    pub vcpu: QuotaUsageDto,
+    pub memory_mb: QuotaUsageDto,
+    pub volumes: QuotaUsageDto,
+    pub volume_gb: QuotaUsageDto,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DepartmentTreeDto {
     pub id: i32,
@@ -304,6 +346,10 @@ mod tests {
             description: Some("负责技术开发".to_string()),
             manager_id: Some(2),
             is_active: true,
+            max_vcpu: None,
+            max_memory_mb: None,
+            max_volumes: None,
+            max_volume_gb: None,
             created_at: chrono::Utc::now().into(),
             updated_at: chrono::Utc::now().into(),
         };
@@ -14,6 +14,8 @@ pub struct Model {
     pub mac_address: Option<String>,
     pub vm_id: Option<String>,
     pub status: String,  // available, allocated, reserved
+    /// 静态预留：由预留接口创建，虚拟机释放后恢复为 reserved 而不是删除
+    pub is_static: bool,
     
     // 时间戳
     pub allocated_at: Option<DateTimeWithTimeZone>,
@@ -91,6 +93,14 @@ pub struct CreateIpAllocationDto {
     pub status: Option<String>,
 }
 
+/// 静态 IP 预留请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateIpReservationDto {
+    pub ip_address: String,
+    /// 绑定的 MAC，占用该地址的网卡必须使用此 MAC
+    pub mac_address: Option<String>,
+}
+
 /// IP 分配响应 DTO
 #[derive(Debug, Serialize, Deserialize)]
 pub struct IpAllocationResponse {
@@ -101,6 +111,7 @@ pub struct IpAllocationResponse {
     pub vm_id: Option<String>,
     pub vm_name: Option<String>,
     pub status: String,
+    pub is_static: bool,
     pub allocated_at: Option<String>,
     pub created_at: String,
 }
@@ -115,6 +126,7 @@ impl From<Model> for IpAllocationResponse {
             vm_id: ip.vm_id,
             vm_name: None, // 将在服务层设置
             status: ip.status,
+            is_static: ip.is_static,
             allocated_at: ip.allocated_at.map(|dt| dt.to_rfc3339()),
             created_at: ip.created_at.to_rfc3339(),
         }
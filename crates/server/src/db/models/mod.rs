@@ -1,19 +1,27 @@
+pub mod affinity_group;
+pub mod affinity_group_member;
+pub mod audit_log;
 pub mod common;
 pub mod department;
 pub mod ip_allocation;
 pub mod network;
 pub mod node;
+pub mod node_metric;
 pub mod permission;
 pub mod role;
 pub mod role_permission;
+pub mod security_group;
 pub mod snapshot;
+pub mod snapshot_policy;
 pub mod storage_pool;
 pub mod task;
 pub mod user;
 pub mod user_department;
 pub mod user_role;
 pub mod vm;
+pub mod vm_template;
 pub mod volume;
+pub mod webhook;
 
 pub use common::*;
 pub use permission::*;
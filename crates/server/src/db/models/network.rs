@@ -16,8 +16,14 @@ pub struct Model {
     pub network_type: String,  // bridge, ovs, macvlan
     pub cidr: Option<String>,
     pub gateway: Option<String>,
+    pub cidr_v6: Option<String>,    // 双栈网络的 IPv6 网段
+    pub gateway_v6: Option<String>,
     pub mtu: Option<i32>,
     pub vlan_id: Option<i32>,
+    /// 默认安全组，网卡未单独指定时使用（仅 bridge 网络）
+    pub security_group_id: Option<String>,
+    /// 由节点上的 dnsmasq 提供 DHCP/DNS（仅 IPv4，需配置 cidr 与 gateway）
+    pub dhcp_enabled: bool,
     
     // 元数据
     pub metadata: Option<JsonValue>,
@@ -80,10 +86,19 @@ pub struct CreateNetworkDto {
     #[validate(length(min = 1, max = 50))]
     pub network_type: String,
     
+    /// IPv4 或 IPv6 网段
     pub cidr: Option<String>,
     pub gateway: Option<String>,
+    /// 双栈网络的 IPv6 网段，每块网卡额外分配一个 IPv6 地址
+    pub cidr_v6: Option<String>,
+    pub gateway_v6: Option<String>,
     pub mtu: Option<i32>,
     pub vlan_id: Option<i32>,
+    /// 默认安全组（仅 bridge 网络）
+    pub security_group_id: Option<String>,
+    /// 启用托管 DHCP/DNS
+    #[serde(default)]
+    pub dhcp_enabled: bool,
     pub metadata: Option<JsonValue>,
 }
 
@@ -95,7 +110,12 @@ pub struct UpdateNetworkDto {
     
     pub cidr: Option<String>,
     pub gateway: Option<String>,
+    pub cidr_v6: Option<String>,
+    pub gateway_v6: Option<String>,
     pub mtu: Option<i32>,
+    /// 传空字符串取消默认安全组
+    pub security_group_id: Option<String>,
+    pub dhcp_enabled: Option<bool>,
     pub metadata: Option<JsonValue>,
 }
 
@@ -107,8 +127,12 @@ pub struct NetworkResponse {
     pub network_type: String,
     pub cidr: Option<String>,
     pub gateway: Option<String>,
+    pub cidr_v6: Option<String>,
+    pub gateway_v6: Option<String>,
     pub mtu: Option<i32>,
     pub vlan_id: Option<i32>,
+    pub security_group_id: Option<String>,
+    pub dhcp_enabled: bool,
     pub metadata: Option<JsonValue>,
     pub created_at: String,
     pub updated_at: String,
@@ -122,8 +146,12 @@ impl From<Model> for NetworkResponse {
             network_type: network.network_type,
             cidr: network.cidr,
             gateway: network.gateway,
+            cidr_v6: network.cidr_v6,
+            gateway_v6: network.gateway_v6,
             mtu: network.mtu,
             vlan_id: network.vlan_id,
+            security_group_id: network.security_group_id,
+            dhcp_enabled: network.dhcp_enabled,
             metadata: network.metadata,
             created_at: network.created_at.to_rfc3339(),
             updated_at: network.updated_at.to_rfc3339(),
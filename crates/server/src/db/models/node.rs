@@ -2,6 +2,10 @@ use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
+use common::ws_rpc::{HostNumaNode, HostShutdownPolicy};
+
+use crate::config::NodeAlertThresholds;
+
 /// 节点模型
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "nodes")]
@@ -20,9 +24,35 @@ pub struct Model {
     pub memory_total: Option<i64>,
     pub disk_total: Option<i64>,
     
+    // 资源利用率（最近一次上报）
+    pub cpu_usage: Option<f32>,
+    pub memory_used: Option<i64>,
+    pub disk_used: Option<i64>,
+    
     // 元数据
     pub metadata: Option<serde_json::Value>,
     
+    // IPMI 带外管理配置（密码不对外序列化）
+    pub ipmi_address: Option<String>,
+    pub ipmi_username: Option<String>,
+    #[serde(skip_serializing)]
+    pub ipmi_password: Option<String>,
+    
+    // 宿主机重启时运行中虚拟机的处理策略（shutdown / suspend）
+    pub shutdown_policy: String,
+    
+    // 是否安装 swtpm，未安装的节点不能运行带 TPM 的虚拟机
+    pub has_swtpm: bool,
+    
+    // 是否安装 Open vSwitch，未安装的节点不能挂载 OVS 网络
+    pub has_ovs: bool,
+    
+    // 宿主机 NUMA 拓扑（HostNumaNode 数组的 JSON），旧版本 Agent 不上报
+    pub numa_topology: Option<serde_json::Value>,
+    
+    // 已安装 QEMU 模拟器的架构（字符串数组的 JSON），旧版本 Agent 不上报
+    pub architectures: Option<serde_json::Value>,
+    
     // 时间戳
     pub last_heartbeat: Option<DateTimeWithTimeZone>,
     pub created_at: DateTimeWithTimeZone,
@@ -34,6 +64,15 @@ pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
 
+impl Model {
+    /// 节点上报的 NUMA 拓扑，未上报时为 None
+    pub fn numa_nodes(&self) -> Option<Vec<HostNumaNode>> {
+        self.numa_topology
+            .as_ref()
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+}
+
 // 为了兼容现有代码，保留 Node 类型别名
 pub type Node = Model;
 
@@ -112,7 +151,17 @@ pub struct NodeResponse {
     pub cpu_threads: Option<i32>,
     pub memory_total: Option<i64>,
     pub disk_total: Option<i64>,
+    pub cpu_usage: Option<f32>,
+    pub memory_used: Option<i64>,
+    pub disk_used: Option<i64>,
     pub metadata: Option<serde_json::Value>,
+    pub ipmi_address: Option<String>,
+    pub ipmi_configured: bool,
+    pub shutdown_policy: String,
+    pub has_swtpm: bool,
+    pub has_ovs: bool,
+    pub numa_topology: Option<serde_json::Value>,
+    pub architectures: Option<serde_json::Value>,
     pub last_heartbeat: Option<String>,
     pub created_at: String,
     pub updated_at: String,
@@ -120,6 +169,7 @@ pub struct NodeResponse {
 
 impl From<Node> for NodeResponse {
     fn from(node: Node) -> Self {
+        let ipmi_configured = node.ipmi_address.is_some() && node.ipmi_username.is_some();
         Self {
             id: node.id,
             hostname: node.hostname,
@@ -131,7 +181,17 @@ impl From<Node> for NodeResponse {
             cpu_threads: node.cpu_threads,
             memory_total: node.memory_total,
             disk_total: node.disk_total,
+            cpu_usage: node.cpu_usage,
+            memory_used: node.memory_used,
+            disk_used: node.disk_used,
             metadata: node.metadata,
+            ipmi_address: node.ipmi_address,
+            ipmi_configured,
+            shutdown_policy: node.shutdown_policy,
+            has_swtpm: node.has_swtpm,
+            has_ovs: node.has_ovs,
+            numa_topology: node.numa_topology,
+            architectures: node.architectures,
             last_heartbeat: node.last_heartbeat.map(|dt| dt.to_rfc3339()),
             created_at: node.created_at.to_rfc3339(),
             updated_at: node.updated_at.to_rfc3339(),
@@ -139,6 +199,26 @@ impl From<Node> for NodeResponse {
     }
 }
 
+/// 节点健康度响应 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NodeHealthResponse {
+    pub node_id: String,
+    /// 健康分（0-100）
+    pub score: u8,
+    /// healthy / warning / critical
+    pub level: String,
+    pub online: bool,
+    pub status: String,
+    pub cpu_usage: Option<f64>,
+    pub memory_usage: Option<f64>,
+    pub disk_usage: Option<f64>,
+    /// Agent 最近一次测得的 ping 往返时延（毫秒）
+    pub rtt_ms: Option<u64>,
+    /// 扣分原因
+    pub issues: Vec<String>,
+    pub thresholds: NodeAlertThresholds,
+}
+
 /// 节点列表响应 DTO
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NodeListResponse {
@@ -159,6 +239,70 @@ pub struct NodeHeartbeatDto {
     pub hypervisor_version: Option<String>,
 }
 
+/// 配置节点 IPMI DTO
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct UpdateNodeIpmiDto {
+    #[validate(length(min = 1, max = 255))]
+    pub address: String,
+
+    #[validate(length(min = 1, max = 255))]
+    pub username: String,
+
+    #[validate(length(min = 1))]
+    pub password: String,
+}
+
+/// 节点电源操作
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NodePowerAction {
+    On,
+    Off,
+    Cycle,
+}
+
+impl NodePowerAction {
+    /// 对应 ipmitool chassis power 子命令
+    pub fn as_ipmi_command(&self) -> &'static str {
+        match self {
+            NodePowerAction::On => "on",
+            NodePowerAction::Off => "off",
+            NodePowerAction::Cycle => "cycle",
+        }
+    }
+}
+
+/// 节点电源操作 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NodePowerDto {
+    pub action: NodePowerAction,
+    /// 节点上仍有运行中的虚拟机时是否强制执行（off/cycle）
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// 节点电源状态响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NodePowerResponse {
+    pub node_id: String,
+    /// on / off / unknown
+    pub power_state: String,
+    pub message: Option<String>,
+}
+
+/// 配置节点关机策略 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateNodeShutdownPolicyDto {
+    pub policy: HostShutdownPolicy,
+}
+
+/// 排空节点 DTO
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DrainNodeDto {
+    /// 不指定时使用节点配置的关机策略
+    pub policy: Option<HostShutdownPolicy>,
+}
+
 /// 节点统计信息
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NodeStatsResponse {
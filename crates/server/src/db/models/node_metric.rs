@@ -0,0 +1,76 @@
+/// 节点主机指标历史数据模型
+
+use common::ws_rpc::DiskUsageSample;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+/// 节点指标采样
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "node_metrics")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub node_id: String,
+    pub sampled_at: DateTimeWithTimeZone,
+    pub cpu_usage: f32, // 百分比
+    pub load_avg: f64, // 1 分钟平均负载
+    pub memory_total: i64, // bytes
+    pub memory_free: i64, // bytes
+    pub disks: JsonValue, // DiskUsageSample 数组
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::node::Entity",
+        from = "Column::NodeId",
+        to = "super::node::Column::Id"
+    )]
+    Node,
+}
+
+impl Related<super::node::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Node.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// 指标历史中的一个点，降采样后为时间桶内的平均值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeMetricPoint {
+    /// Unix 时间戳（秒）
+    pub timestamp: i64,
+    pub cpu_usage: f64,
+    pub load_avg: f64,
+    pub memory_total: i64,
+    pub memory_free: i64,
+    /// 时间桶内最后一次采样的磁盘使用情况
+    pub disks: Vec<DiskUsageSample>,
+}
+
+impl From<Model> for NodeMetricPoint {
+    fn from(sample: Model) -> Self {
+        Self {
+            timestamp: sample.sampled_at.timestamp(),
+            cpu_usage: sample.cpu_usage as f64,
+            load_avg: sample.load_avg,
+            memory_total: sample.memory_total,
+            memory_free: sample.memory_free,
+            disks: serde_json::from_value(sample.disks).unwrap_or_default(),
+        }
+    }
+}
+
+/// 节点指标历史响应 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NodeMetricsResponse {
+    pub node_id: String,
+    /// 查询的时间范围（秒）
+    pub range_secs: i64,
+    /// 时间范围内的原始采样数
+    pub sample_count: usize,
+    pub points: Vec<NodeMetricPoint>,
+}
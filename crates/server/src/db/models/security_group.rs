@@ -0,0 +1,77 @@
+/// 安全组数据模型
+
+use common::ws_rpc::SecurityGroupRule;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use validator::Validate;
+
+/// 安全组模型
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "security_groups")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub rules: JsonValue, // SecurityGroupRule 数组
+
+    // 时间戳
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// 入站放行规则
+    pub fn rule_list(&self) -> Vec<SecurityGroupRule> {
+        serde_json::from_value(self.rules.clone()).unwrap_or_default()
+    }
+}
+
+/// 创建安全组 DTO
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CreateSecurityGroupDto {
+    #[validate(length(min = 1, max = 255))]
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub rules: Vec<SecurityGroupRule>,
+}
+
+/// 更新安全组 DTO，rules 整体替换
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct UpdateSecurityGroupDto {
+    #[validate(length(min = 1, max = 255))]
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub rules: Option<Vec<SecurityGroupRule>>,
+}
+
+/// 安全组响应 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityGroupResponse {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub rules: Vec<SecurityGroupRule>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<Model> for SecurityGroupResponse {
+    fn from(group: Model) -> Self {
+        Self {
+            rules: group.rule_list(),
+            id: group.id,
+            name: group.name,
+            description: group.description,
+            created_at: group.created_at.to_rfc3339(),
+            updated_at: group.updated_at.to_rfc3339(),
+        }
+    }
+}
@@ -15,6 +15,8 @@ pub struct Model {
     pub size_gb: Option<i64>,
     pub snapshot_tag: Option<String>, // qemu/libvirt 中的实际快照标签
     pub description: Option<String>,
+    /// 受保护的快照不会被快照策略或安全快照过期清理自动删除
+    pub protected: bool,
 
     // 元数据
     pub metadata: Option<JsonValue>,
@@ -42,6 +44,44 @@ impl Related<super::volume::Entity> for Entity {
 
 impl ActiveModelBehavior for ActiveModel {}
 
+impl Model {
+    /// 是否为破坏性操作前自动创建的安全快照
+    pub fn is_safety(&self) -> bool {
+        self.metadata_flag("safety")
+    }
+
+    /// 安全快照所在的卷是否为操作后保留下来的原卷（过期时连同卷一起清理）
+    pub fn retains_volume(&self) -> bool {
+        self.metadata_flag("retained_volume")
+    }
+
+    /// 创建该快照的快照策略 ID，手动创建的快照为 None
+    pub fn policy_id(&self) -> Option<&str> {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get("policy_id"))
+            .and_then(|v| v.as_str())
+    }
+
+    /// 在已有元数据上记录快照的一致性级别：`filesystem`（冻结客户机文件系统后创建）或 `crash`
+    pub fn metadata_with_consistency(&self, consistency: &str) -> JsonValue {
+        let mut metadata = match &self.metadata {
+            Some(JsonValue::Object(map)) => map.clone(),
+            _ => serde_json::Map::new(),
+        };
+        metadata.insert("consistency".to_string(), JsonValue::from(consistency));
+        JsonValue::Object(metadata)
+    }
+
+    fn metadata_flag(&self, key: &str) -> bool {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get(key))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    }
+}
+
 /// 快照状态枚举
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
@@ -63,6 +103,38 @@ impl SnapshotStatus {
     }
 }
 
+/// 触发安全快照的破坏性操作
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SafetyOperation {
+    RebuildVm,
+    ShrinkVolume,
+    RestoreSnapshot,
+}
+
+impl SafetyOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SafetyOperation::RebuildVm => "rebuild_vm",
+            SafetyOperation::ShrinkVolume => "shrink_volume",
+            SafetyOperation::RestoreSnapshot => "restore_snapshot",
+        }
+    }
+
+    /// 重装系统会替换系统盘，原盘需保留到安全快照过期才能回滚
+    pub fn retains_volume(&self) -> bool {
+        matches!(self, SafetyOperation::RebuildVm)
+    }
+
+    /// 写入安全快照记录的元数据
+    pub fn metadata(&self) -> JsonValue {
+        serde_json::json!({
+            "safety": true,
+            "operation": self.as_str(),
+            "retained_volume": self.retains_volume(),
+        })
+    }
+}
+
 /// 创建快照 DTO
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateSnapshotDto {
@@ -72,11 +144,12 @@ pub struct CreateSnapshotDto {
     pub metadata: Option<JsonValue>,
 }
 
-/// 更新快照 DTO（仅允许更新名称和描述）
+/// 更新快照 DTO（仅允许更新名称、描述和保护标记）
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateSnapshotDto {
     pub name: Option<String>,
     pub description: Option<String>,
+    pub protected: Option<bool>,
 }
 
 /// 快照响应 DTO
@@ -90,6 +163,7 @@ pub struct SnapshotResponse {
     pub size_gb: Option<i64>,
     pub snapshot_tag: Option<String>,
     pub description: Option<String>,
+    pub protected: bool,
     pub metadata: Option<JsonValue>,
     pub created_at: String,
     pub updated_at: String,
@@ -106,6 +180,7 @@ impl From<Model> for SnapshotResponse {
             size_gb: snapshot.size_gb,
             snapshot_tag: snapshot.snapshot_tag,
             description: snapshot.description,
+            protected: snapshot.protected,
             metadata: snapshot.metadata,
             created_at: snapshot.created_at.to_rfc3339(),
             updated_at: snapshot.updated_at.to_rfc3339(),
@@ -121,3 +196,38 @@ pub struct SnapshotListResponse {
     pub page: usize,
     pub page_size: usize,
 }
+
+/// 磁盘快照对账状态
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiskSnapshotState {
+    /// 磁盘与数据库一致
+    Synced,
+    /// 磁盘上存在，但数据库中没有记录
+    Orphan,
+    /// 数据库中存在，但磁盘上没有
+    Phantom,
+}
+
+/// 单个快照的对账结果
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiskSnapshotEntry {
+    /// 磁盘上的快照标签
+    pub tag: String,
+    pub state: DiskSnapshotState,
+    pub snapshot_id: Option<String>,
+    pub snapshot_name: Option<String>,
+    pub db_status: Option<String>,
+    pub vm_state_size: Option<u64>,
+    pub disk_created_at: Option<String>,
+}
+
+/// 存储卷磁盘快照对账响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VolumeDiskSnapshotsResponse {
+    pub volume_id: String,
+    pub synced: usize,
+    pub orphans: usize,
+    pub phantoms: usize,
+    pub snapshots: Vec<DiskSnapshotEntry>,
+}
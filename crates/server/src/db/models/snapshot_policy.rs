@@ -0,0 +1,132 @@
+/// 快照策略数据模型
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// 快照策略模型
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "snapshot_policies")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    pub name: String,
+    pub target_type: String, // volume, vm
+    pub target_id: String,
+    pub schedule: String, // cron 表达式（分 时 日 月 周，UTC）
+    pub keep_last: i32,
+    pub enabled: bool,
+
+    // 最近一次执行结果
+    pub last_run_at: Option<DateTimeWithTimeZone>,
+    pub last_status: Option<String>, // success, failed
+    pub last_error: Option<String>,
+
+    // 时间戳
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// 快照策略的作用对象
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotPolicyTarget {
+    /// 单个存储卷
+    Volume,
+    /// 虚拟机挂载的全部存储卷
+    Vm,
+}
+
+impl SnapshotPolicyTarget {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SnapshotPolicyTarget::Volume => "volume",
+            SnapshotPolicyTarget::Vm => "vm",
+        }
+    }
+}
+
+impl std::str::FromStr for SnapshotPolicyTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "volume" => Ok(SnapshotPolicyTarget::Volume),
+            "vm" => Ok(SnapshotPolicyTarget::Vm),
+            _ => Err(format!("无效的快照策略目标类型: {}", s)),
+        }
+    }
+}
+
+/// 创建快照策略 DTO
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CreateSnapshotPolicyDto {
+    #[validate(length(min = 1, max = 255))]
+    pub name: String,
+    pub target_type: SnapshotPolicyTarget,
+    pub target_id: String,
+    pub schedule: String,
+    #[validate(range(min = 1))]
+    pub keep_last: i32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 更新快照策略 DTO（作用对象创建后不可修改）
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct UpdateSnapshotPolicyDto {
+    #[validate(length(min = 1, max = 255))]
+    pub name: Option<String>,
+    pub schedule: Option<String>,
+    #[validate(range(min = 1))]
+    pub keep_last: Option<i32>,
+    pub enabled: Option<bool>,
+}
+
+/// 快照策略响应 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotPolicyResponse {
+    pub id: String,
+    pub name: String,
+    pub target_type: String,
+    pub target_id: String,
+    pub schedule: String,
+    pub keep_last: i32,
+    pub enabled: bool,
+    /// 下一次执行时间，策略停用时为 None
+    pub next_run_at: Option<String>,
+    pub last_run_at: Option<String>,
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<Model> for SnapshotPolicyResponse {
+    fn from(policy: Model) -> Self {
+        Self {
+            id: policy.id,
+            name: policy.name,
+            target_type: policy.target_type,
+            target_id: policy.target_id,
+            schedule: policy.schedule,
+            keep_last: policy.keep_last,
+            enabled: policy.enabled,
+            next_run_at: None,
+            last_run_at: policy.last_run_at.map(|t| t.to_rfc3339()),
+            last_status: policy.last_status,
+            last_error: policy.last_error,
+            created_at: policy.created_at.to_rfc3339(),
+            updated_at: policy.updated_at.to_rfc3339(),
+        }
+    }
+}
@@ -22,6 +22,8 @@ pub struct Model {
     pub capacity_gb: Option<i64>,
     pub allocated_gb: Option<i64>,
     pub available_gb: Option<i64>,
+    /// 底层存储的实际占用，由定期容量刷新任务写入
+    pub used_gb: Option<i64>,
     
     // 关联信息
     pub node_id: Option<String>,
@@ -115,6 +117,7 @@ pub struct StoragePoolResponse {
     pub capacity_gb: Option<i64>,
     pub allocated_gb: Option<i64>,
     pub available_gb: Option<i64>,
+    pub used_gb: Option<i64>,
     pub node_id: Option<String>,
     pub node_name: Option<String>,
     pub metadata: Option<JsonValue>,
@@ -133,6 +136,7 @@ impl From<Model> for StoragePoolResponse {
             capacity_gb: pool.capacity_gb,
             allocated_gb: pool.allocated_gb,
             available_gb: pool.available_gb,
+            used_gb: pool.used_gb,
             node_id: pool.node_id,
             node_name: None, // 需要单独查询节点名称
             metadata: pool.metadata,
@@ -151,3 +155,22 @@ pub struct StoragePoolListResponse {
     pub page_size: usize,
 }
 
+/// 存储池垃圾回收 DTO
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PoolGcDto {
+    /// 是否删除孤立文件；为 false 时仅报告
+    #[serde(default)]
+    pub delete: bool,
+    /// 删除时必须填写存储池 ID 以确认
+    pub confirm: Option<String>,
+}
+
+/// 存储池垃圾回收响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PoolGcResponse {
+    pub pool_id: String,
+    pub orphans: Vec<common::ws_rpc::OrphanedVolumeFile>,
+    pub orphaned_bytes: u64,
+    pub deleted: Vec<String>,
+    pub skipped: Vec<String>,
+}
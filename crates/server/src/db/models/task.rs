@@ -136,6 +136,20 @@ impl TaskType {
             TaskType::DeleteNetwork => "delete_network",
         }
     }
+
+    /// 任务关联对象的类型，写入 target_type 字段
+    pub fn target_type(&self) -> &'static str {
+        match self {
+            TaskType::CreateVm
+            | TaskType::DeleteVm
+            | TaskType::StartVm
+            | TaskType::StopVm
+            | TaskType::RestartVm
+            | TaskType::MigrateVm => "vm",
+            TaskType::CreateVolume | TaskType::DeleteVolume => "volume",
+            TaskType::CreateNetwork | TaskType::DeleteNetwork => "network",
+        }
+    }
 }
 
 /// 任务响应 DTO
@@ -148,6 +162,7 @@ pub struct TaskResponse {
     pub target_type: Option<String>,
     pub target_id: Option<String>,
     pub node_id: Option<String>,
+    pub result: Option<JsonValue>,
     pub error_message: Option<String>,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
@@ -165,6 +180,7 @@ impl From<Model> for TaskResponse {
             target_type: task.target_type,
             target_id: task.target_id,
             node_id: task.node_id,
+            result: task.result,
             error_message: task.error_message,
             created_at: task.created_at,
             updated_at: task.updated_at,
@@ -173,3 +189,12 @@ impl From<Model> for TaskResponse {
         }
     }
 }
+
+/// 任务列表响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskListResponse {
+    pub tasks: Vec<TaskResponse>,
+    pub total: usize,
+    pub page: usize,
+    pub page_size: usize,
+}
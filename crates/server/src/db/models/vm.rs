@@ -1,17 +1,22 @@
 /// 虚拟机数据模型
 
-use common::ws_rpc::types::{DiskBusType, DiskDeviceType};
+use common::ws_rpc::types::{
+    CloudInitConfig, CpuPinning, DiskBusType, DiskDeviceType, DiskIoLimits, FirmwareType,
+    GraphicsType, GuestNetworkInterface, MigrationFallbackPolicy, MigrationStorageMode, NicBandwidth, NumaConfig,
+    SourceAuth,
+};
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use serde_json::Value as JsonValue;
 
 /// 虚拟机模型
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "vms")]
 pub struct Model {
+    /// 虚拟机 ID，同时作为 libvirt 域 UUID，Agent 一律按此查找域
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: String,
-    pub uuid: Option<String>,
     pub name: String,
     pub node_id: Option<String>,
     pub status: String,
@@ -20,13 +25,40 @@ pub struct Model {
     pub vcpu: i32,
     pub memory_mb: i64,
     pub os_type: String,  // 操作系统类型: linux, windows
+    pub firmware: String,  // 固件类型: bios, uefi
+    /// 是否挂载模拟 TPM 2.0 设备，所在节点需安装 swtpm
+    pub tpm: bool,
+    /// 启动时显示固件引导菜单
+    pub boot_menu: bool,
+    /// vCPU 绑定（CpuPinning 的 JSON）
+    pub cpu_pinning: Option<JsonValue>,
+    /// NUMA 配置（NumaConfig 的 JSON）
+    pub numa: Option<JsonValue>,
+    /// 自定义域 XML，存在时 Agent 原样定义，不再按配置生成
+    pub raw_xml_override: Option<String>,
+    /// 客户机架构: x86_64, aarch64 等
+    pub arch: String,
+    /// 机器类型，为空时由 Agent 按 libvirt capabilities 选择
+    pub machine_type: Option<String>,
+    /// 图形控制台协议: vnc, spice
+    pub graphics: String,
     
     // 磁盘和网络配置 (JSON)
     pub volumes: Option<JsonValue>,
     pub network_interfaces: Option<JsonValue>,
+    /// cloud-init 配置（CloudInitConfig 的 JSON），启动时由 Agent 生成配置光盘
+    pub cloud_init: Option<JsonValue>,
+
+    // 图形控制台（启动后由 Agent 上报 libvirt 分配的端口，停止后清空）
+    pub vnc_port: Option<i32>,
+    pub vnc_host: Option<String>,
+    /// 端口对应的协议，修改 graphics 后在下次启动前与之不同
+    pub console_protocol: Option<String>,
     
     // 元数据
     pub metadata: Option<JsonValue>,
+    /// 创建虚拟机的用户，按其所属部门计算资源配额
+    pub owner_id: Option<i32>,
     
     // 时间戳
     pub created_at: DateTimeWithTimeZone,
@@ -51,6 +83,8 @@ pub enum VmStatus {
     Stopped,
     Paused,
     Migrating,
+    /// 正在重装系统盘，期间不允许启动、停止、迁移和删除
+    Rebuilding,
     Error,
 }
 
@@ -62,6 +96,7 @@ impl VmStatus {
             VmStatus::Stopped => "stopped",
             VmStatus::Paused => "paused",
             VmStatus::Migrating => "migrating",
+            VmStatus::Rebuilding => "rebuilding",
             VmStatus::Error => "error",
         }
     }
@@ -74,6 +109,7 @@ impl From<String> for VmStatus {
             "stopped" => VmStatus::Stopped,
             "paused" => VmStatus::Paused,
             "migrating" => VmStatus::Migrating,
+            "rebuilding" => VmStatus::Rebuilding,
             "error" => VmStatus::Error,
             _ => VmStatus::Stopped,
         }
@@ -84,13 +120,51 @@ impl From<String> for VmStatus {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateVmDto {
     pub name: String,
-    pub node_id: String,
+    /// 不指定时按放置策略从在线节点中自动选择
+    #[serde(default)]
+    pub node_id: Option<String>,
     pub vcpu: u32,
     pub memory_mb: u64,
     pub os_type: Option<String>,  // 操作系统类型，默认为 linux
+    /// 固件类型，默认为 BIOS；Windows 11 等需要安全启动的系统应使用 UEFI
+    #[serde(default)]
+    pub firmware: FirmwareType,
+    /// 挂载模拟 TPM 2.0 设备，Windows 11 需要同时使用 UEFI 固件
+    #[serde(default)]
+    pub tpm: bool,
+    /// 启动时显示固件引导菜单
+    #[serde(default)]
+    pub boot_menu: bool,
+    /// vCPU 绑定（vCPU 编号 -> 物理 CPU 编号），物理 CPU 须存在于所在节点
+    #[serde(default)]
+    pub cpu_pinning: Option<CpuPinning>,
+    /// NUMA 内存绑定与客户机 NUMA 拓扑
+    #[serde(default)]
+    pub numa: Option<NumaConfig>,
+    /// 自定义域 XML，name 与 uuid 须与虚拟机一致；磁盘、网卡等仍按配置准备
+    #[serde(default)]
+    pub raw_xml_override: Option<String>,
+    /// 客户机架构，默认为 x86_64，所在节点须安装对应的 QEMU 模拟器
+    #[serde(default)]
+    pub arch: Option<String>,
+    /// 机器类型（如 pc-q35-8.2、virt），不指定时使用节点 QEMU 的默认版本
+    #[serde(default)]
+    pub machine_type: Option<String>,
+    /// 图形控制台协议，默认 VNC；Windows 桌面建议使用 SPICE
+    #[serde(default)]
+    pub graphics: GraphicsType,
     pub disks: Option<Vec<DiskSpec>>,
     pub networks: Option<Vec<NetworkInterfaceSpec>>,
+    /// cloud-init 配置，用于注入主机名、SSH 公钥和网络配置
+    #[serde(default)]
+    pub cloud_init: Option<CloudInitConfig>,
     pub metadata: Option<JsonValue>,
+    /// 创建后加入的亲和组，放置节点需满足其规则
+    #[serde(default)]
+    pub affinity_group_ids: Vec<String>,
+    /// 归属用户，由 API 层按当前登录用户设置，存在时校验其所属部门的配额
+    #[serde(skip)]
+    pub owner_id: Option<i32>,
 }
 
 /// 更新 VM DTO
@@ -100,16 +174,100 @@ pub struct UpdateVmDto {
     pub vcpu: Option<u32>,
     pub memory_mb: Option<u64>,
     pub os_type: Option<String>,  // 操作系统类型
+    /// 下次启动时生效
+    pub tpm: Option<bool>,
+    /// 下次启动时生效
+    pub boot_menu: Option<bool>,
+    /// 下次启动时生效，传空表清除绑定
+    pub cpu_pinning: Option<CpuPinning>,
+    /// 下次启动时生效
+    pub numa: Option<NumaConfig>,
+    /// 下次启动时生效，传空字符串恢复按配置生成
+    pub raw_xml_override: Option<String>,
+    /// 下次启动时生效，传空字符串恢复自动选择
+    pub machine_type: Option<String>,
+    /// 下次启动时生效
+    pub graphics: Option<GraphicsType>,
     pub disks: Option<Vec<DiskSpec>>,
     pub networks: Option<Vec<NetworkInterfaceSpec>>,
+    /// 下次启动时生效
+    pub cloud_init: Option<CloudInitConfig>,
     pub metadata: Option<JsonValue>,
 }
 
+/// 迁移虚拟机 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MigrateVmDto {
+    pub target_node_id: String,
+    #[serde(default)]
+    pub live: bool,
+    /// 热迁移带宽上限（MiB/s），也可用 max_mbps 传入
+    #[serde(default, alias = "max_mbps")]
+    pub max_bandwidth_mbps: Option<u64>,
+    /// 热迁移最大停机时间（毫秒）
+    #[serde(default)]
+    pub max_downtime_ms: Option<u64>,
+    /// 热迁移超时后的降级策略
+    #[serde(default)]
+    pub fallback_policy: MigrationFallbackPolicy,
+    /// 热迁移超时时间（秒），降级策略为 suspend 时必填
+    #[serde(default)]
+    pub live_timeout_secs: Option<u64>,
+    /// 热迁移的磁盘处理方式，copy_storage 会在目标节点创建空白卷并复制磁盘数据
+    #[serde(default)]
+    pub storage_mode: MigrationStorageMode,
+}
+
+/// 重装系统盘 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RebuildVmDto {
+    /// 镜像来源 URL，新系统盘将从该镜像下载创建
+    pub source: String,
+    /// 镜像的校验和（例如：sha256:<hex>），下载后不一致则重装失败
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// 下载镜像时附加的请求头
+    #[serde(default)]
+    pub source_headers: Option<HashMap<String, String>>,
+    /// 下载镜像时使用的认证信息
+    #[serde(default)]
+    pub source_auth: Option<SourceAuth>,
+    /// 新系统盘大小（GB），默认与原系统盘一致
+    pub size_gb: Option<i64>,
+    /// 重装完成后是否自动启动，默认启动
+    #[serde(default = "default_rebuild_start")]
+    pub start: bool,
+}
+
+fn default_rebuild_start() -> bool {
+    true
+}
+
+/// 克隆虚拟机 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CloneVmDto {
+    pub name: String,
+    /// 从快照时间点克隆的磁盘：每个快照须属于源虚拟机的一块磁盘，
+    /// 未指定快照的磁盘克隆当前数据
+    #[serde(default)]
+    pub source_snapshot_ids: Vec<String>,
+}
+
+/// 执行客户机命令 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuestExecDto {
+    /// 客户机内可执行文件路径，如 /bin/sh
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// 等待命令结束的超时时间（秒），默认 60，最大 600
+    pub timeout_secs: Option<u64>,
+}
+
 /// VM 响应 DTO
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VmResponse {
     pub id: String,
-    pub uuid: Option<String>,
     pub name: String,
     pub node_id: Option<String>,
     pub node_name: Option<String>,
@@ -117,9 +275,23 @@ pub struct VmResponse {
     pub vcpu: i32,
     pub memory_mb: i64,
     pub os_type: String,  // 操作系统类型
+    pub firmware: String,  // 固件类型
+    pub tpm: bool,
+    pub boot_menu: bool,
+    pub cpu_pinning: Option<JsonValue>,
+    pub numa: Option<JsonValue>,
+    pub raw_xml_override: Option<String>,
+    pub arch: String,
+    pub machine_type: Option<String>,
+    pub graphics: String,
     pub volumes: Option<JsonValue>,
     pub network_interfaces: Option<JsonValue>,
+    pub cloud_init: Option<JsonValue>,
+    pub vnc_port: Option<i32>,
+    pub vnc_host: Option<String>,
+    pub console_protocol: Option<String>,
     pub metadata: Option<JsonValue>,
+    pub owner_id: Option<i32>,
     pub created_at: String,
     pub updated_at: String,
     pub started_at: Option<String>,
@@ -130,7 +302,6 @@ impl From<Vm> for VmResponse {
     fn from(vm: Vm) -> Self {
         VmResponse {
             id: vm.id,
-            uuid: vm.uuid,
             name: vm.name,
             node_id: vm.node_id,
             node_name: None, // 将在服务层设置
@@ -138,9 +309,23 @@ impl From<Vm> for VmResponse {
             vcpu: vm.vcpu,
             memory_mb: vm.memory_mb,
             os_type: vm.os_type,
+            firmware: vm.firmware,
+            tpm: vm.tpm,
+            boot_menu: vm.boot_menu,
+            cpu_pinning: vm.cpu_pinning,
+            numa: vm.numa,
+            raw_xml_override: vm.raw_xml_override,
+            arch: vm.arch,
+            machine_type: vm.machine_type,
+            graphics: vm.graphics,
             volumes: vm.volumes,
             network_interfaces: vm.network_interfaces,
+            cloud_init: vm.cloud_init,
+            vnc_port: vm.vnc_port,
+            vnc_host: vm.vnc_host,
+            console_protocol: vm.console_protocol,
             metadata: vm.metadata,
+            owner_id: vm.owner_id,
             created_at: vm.created_at.to_rfc3339(),
             updated_at: vm.updated_at.to_rfc3339(),
             started_at: vm.started_at.map(|t| t.to_rfc3339()),
@@ -163,8 +348,17 @@ pub struct VmListResponse {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DiskSpec {
     pub volume_id: String,
-    pub bus_type: DiskBusType,      // 总线类型: virtio, scsi, ide
+    pub bus_type: DiskBusType,      // 总线类型: virtio, scsi, sata, ide
     pub device_type: DiskDeviceType, // 设备类型: disk, cdrom
+    /// 临时盘：删除虚拟机时一并删除存储卷，而不是释放回存储池
+    #[serde(default)]
+    pub ephemeral: bool,
+    /// I/O 限速（iops_limit / bps_limit），未设置时不限速
+    #[serde(flatten)]
+    pub limits: DiskIoLimits,
+    /// 引导顺序（从 1 开始）；所有磁盘都未设置时从第一块磁盘引导
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_order: Option<u32>,
 }
 
 /// 网络接口规格
@@ -173,9 +367,18 @@ pub struct NetworkInterfaceSpec {
     pub network_id: String,
     pub mac_address: Option<String>,
     pub ip_address: Option<String>,
+    /// 双栈网络中额外分配的 IPv6 地址
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv6_address: Option<String>,
     pub model: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bridge_name: Option<String>,
+    /// 带宽限速（inbound_kbps / outbound_kbps），未设置时不限速
+    #[serde(flatten)]
+    pub bandwidth: NicBandwidth,
+    /// 网卡的安全组，未设置时使用所在网络的默认安全组
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security_group_id: Option<String>,
 }
 
 /// Attach Volume 请求
@@ -184,6 +387,26 @@ pub struct AttachVolumeDto {
     pub volume_id: String,
     pub bus_type: Option<DiskBusType>,      // 总线类型，默认为 virtio
     pub device_type: Option<DiskDeviceType>, // 设备类型，默认为 disk
+    #[serde(default)]
+    pub ephemeral: bool,                     // 是否随虚拟机删除
+    #[serde(flatten)]
+    pub limits: DiskIoLimits,
+}
+
+/// 调整磁盘 I/O 限速请求，未设置的项取消限速
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetVolumeIotuneDto {
+    pub volume_id: String,
+    #[serde(flatten)]
+    pub limits: DiskIoLimits,
+}
+
+/// 调整网卡带宽限速请求，网卡按 MAC 地址定位，未设置的方向取消限速
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetNicBandwidthDto {
+    pub mac_address: String,
+    #[serde(flatten)]
+    pub limits: NicBandwidth,
 }
 
 /// Detach Volume 请求
@@ -192,6 +415,87 @@ pub struct DetachVolumeDto {
     pub volume_id: String,
 }
 
+/// 虚拟机实时状态响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VmLiveStateResponse {
+    pub vm_id: String,
+    pub node_id: Option<String>,
+    /// 平台状态（running/stopped/paused/error ...）
+    pub status: String,
+    /// libvirt 域状态，数据来自数据库时为空
+    pub domain_state: Option<String>,
+    pub vcpu: i64,
+    pub memory_mb: i64,
+    pub uptime_secs: Option<u64>,
+    /// 节点不可达时回退到数据库记录，数据可能已过期
+    pub stale: bool,
+    /// 数据来源: agent 或 database
+    pub source: String,
+    pub checked_at: String,
+}
+
+/// 控制台访问响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConsoleAccessResponse {
+    pub vm_id: String,
+    /// 控制台协议: vnc, spice
+    pub protocol: String,
+    /// WebSocket 代理地址，需附加 `?token=<JWT>`
+    pub url: String,
+    /// 一次性控制台密码，过期后不能再用于建立新连接
+    pub password: String,
+    pub expires_at: String,
+}
+
+/// 批量操作的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchVmAction {
+    Start,
+    Stop,
+    Restart,
+    Delete,
+}
+
+/// 批量操作虚拟机 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchVmOperationDto {
+    pub vm_ids: Vec<String>,
+    pub action: BatchVmAction,
+    /// 仅对 stop 生效：强制关机
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// 单个虚拟机的批量操作结果
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchVmResult {
+    pub vm_id: String,
+    pub success: bool,
+    /// start/stop/restart 成功时的任务 ID
+    pub task_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// 批量操作响应，结果顺序与请求中的 vm_ids 一致（已去重）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchVmOperationResponse {
+    pub action: BatchVmAction,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BatchVmResult>,
+}
+
+/// 客户机网络信息响应（来自 qemu-guest-agent）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuestNetworkResponse {
+    pub vm_id: String,
+    /// 客户机内未安装或未运行 qemu-guest-agent 时为 false，interfaces 为空
+    pub guest_agent_available: bool,
+    pub interfaces: Vec<GuestNetworkInterface>,
+    pub checked_at: String,
+}
+
 /// VM磁盘信息响应
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VmDiskResponse {
@@ -200,6 +504,9 @@ pub struct VmDiskResponse {
     pub bootable: bool,
     pub bus_type: DiskBusType,      // 总线类型
     pub device_type: DiskDeviceType, // 设备类型
+    pub ephemeral: bool,
+    #[serde(flatten)]
+    pub limits: DiskIoLimits,
     pub volume_name: Option<String>,
     pub size_gb: Option<i64>,
     pub volume_type: Option<String>,
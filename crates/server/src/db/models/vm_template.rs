@@ -0,0 +1,135 @@
+/// 虚拟机模板数据模型
+
+use common::ws_rpc::types::{CloudInitConfig, DiskBusType, FirmwareType, GraphicsType};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use validator::Validate;
+
+use super::vm::NetworkInterfaceSpec;
+
+/// 虚拟机模板模型
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "vm_templates")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// 系统盘的基础镜像存储卷，从模板创建时以其为 backing file 创建链接克隆
+    pub base_volume_id: String,
+    /// 虚拟机规格（VmTemplateSpec 的 JSON）
+    pub spec: JsonValue,
+
+    // 时间戳
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// 模板保存的虚拟机规格，字段含义与 CreateVmDto 相同
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct VmTemplateSpec {
+    #[validate(range(min = 1))]
+    pub vcpu: u32,
+    #[validate(range(min = 1))]
+    pub memory_mb: u64,
+    #[serde(default)]
+    pub os_type: Option<String>,
+    #[serde(default)]
+    pub firmware: FirmwareType,
+    #[serde(default)]
+    pub tpm: bool,
+    #[serde(default)]
+    pub boot_menu: bool,
+    #[serde(default)]
+    pub arch: Option<String>,
+    #[serde(default)]
+    pub machine_type: Option<String>,
+    #[serde(default)]
+    pub graphics: GraphicsType,
+    /// 系统盘容量，不指定时与基础镜像相同
+    #[serde(default)]
+    pub root_disk_size_gb: Option<i64>,
+    /// 系统盘总线类型，不指定时使用 Server 的默认总线
+    #[serde(default)]
+    pub root_disk_bus: Option<DiskBusType>,
+    /// 网卡配置，创建时忽略其中的 MAC 与 IP，按网络重新分配
+    #[serde(default)]
+    pub networks: Vec<NetworkInterfaceSpec>,
+    #[serde(default)]
+    pub cloud_init: Option<CloudInitConfig>,
+    #[serde(default)]
+    pub affinity_group_ids: Vec<String>,
+    pub metadata: Option<JsonValue>,
+}
+
+/// 创建虚拟机模板 DTO
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CreateVmTemplateDto {
+    #[validate(length(min = 1, max = 255))]
+    pub name: String,
+    pub description: Option<String>,
+    pub base_volume_id: String,
+    #[validate]
+    pub spec: VmTemplateSpec,
+}
+
+/// 更新虚拟机模板 DTO，spec 整体替换
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct UpdateVmTemplateDto {
+    #[validate(length(min = 1, max = 255))]
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub base_volume_id: Option<String>,
+    #[validate]
+    pub spec: Option<VmTemplateSpec>,
+}
+
+/// 从模板创建虚拟机 DTO，未指定的字段使用模板中的值
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateVmFromTemplateDto {
+    pub name: String,
+    /// 不指定时按放置策略自动选择
+    #[serde(default)]
+    pub node_id: Option<String>,
+    #[serde(default)]
+    pub vcpu: Option<u32>,
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    #[serde(default)]
+    pub cloud_init: Option<CloudInitConfig>,
+    /// 归属用户，由 API 层按当前登录用户设置
+    #[serde(skip)]
+    pub owner_id: Option<i32>,
+}
+
+/// 虚拟机模板响应 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VmTemplateResponse {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub base_volume_id: String,
+    pub spec: JsonValue,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<Model> for VmTemplateResponse {
+    fn from(template: Model) -> Self {
+        Self {
+            id: template.id,
+            name: template.name,
+            description: template.description,
+            base_volume_id: template.base_volume_id,
+            spec: template.spec,
+            created_at: template.created_at.to_rfc3339(),
+            updated_at: template.updated_at.to_rfc3339(),
+        }
+    }
+}
@@ -1,8 +1,10 @@
 /// 存储卷数据模型
 
+use common::ws_rpc::{SourceAuth, VolumeEncryption, VolumeImportMode};
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use std::collections::HashMap;
 
 /// 存储卷模型
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
@@ -20,9 +22,16 @@ pub struct Model {
     
     // 关联信息
     pub vm_id: Option<String>,
+    /// 链接克隆的基础镜像卷
+    pub backing_volume_id: Option<String>,
+    /// LUKS 加密的密钥来源（VolumeEncryption），包含口令，不能出现在接口响应中
+    #[serde(skip_serializing)]
+    pub encryption: Option<JsonValue>,
     
     // 元数据
     pub metadata: Option<JsonValue>,
+    /// 创建存储卷的用户，按其所属部门计算资源配额
+    pub owner_id: Option<i32>,
     
     // 时间戳
     pub created_at: DateTimeWithTimeZone,
@@ -60,6 +69,13 @@ impl Related<super::vm::Entity> for Entity {
 
 impl ActiveModelBehavior for ActiveModel {}
 
+impl Model {
+    /// 是否为 LUKS 加密存储卷
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
+    }
+}
+
 /// 存储卷状态枚举
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
@@ -90,8 +106,31 @@ pub struct CreateVolumeDto {
     pub pool_id: String,
     pub size_gb: i64,
     pub volume_type: String,  // qcow2, raw
-    pub source: Option<String>,  // 外部URL，用于下载初始数据
+    pub source: Option<String>,  // 源地址：http(s)://、s3://（由 Server 签发预签名 URL）或 Agent 本地的 file://
+    /// 下载 http(s) 源地址时附加的请求头，不会保存
+    #[serde(default)]
+    pub source_headers: Option<HashMap<String, String>>,
+    /// 下载 http(s) 源地址时使用的认证信息（basic/bearer），不会保存
+    #[serde(default)]
+    pub source_auth: Option<SourceAuth>,
+    /// 使用 zstd 压缩（仅 qcow2），适合很少写入的模板/基础镜像，默认不压缩
+    #[serde(default)]
+    pub compress: bool,
+    /// 源文件的校验和（例如：sha256:<hex>），仅从 URL 创建时有效，下载后不一致则创建失败
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// 基础镜像存储卷：创建以其为 backing file 的 qcow2 链接克隆，只保存与基础镜像的差异数据。
+    /// 须与新卷位于同一存储池，不能与 source 同时指定
+    #[serde(default)]
+    pub backing_volume_id: Option<String>,
+    /// LUKS 加密（仅空白 qcow2 卷）：直接提供口令，或引用 Agent 节点 ENCRYPTION_KEY_DIR 下的密钥文件。
+    /// 加密卷暂不支持调整大小、克隆、下载、快照和热迁移
+    #[serde(default)]
+    pub encryption: Option<VolumeEncryption>,
     pub metadata: Option<JsonValue>,
+    /// 归属用户，由 API 层按当前登录用户设置，存在时校验其所属部门的配额
+    #[serde(skip)]
+    pub owner_id: Option<i32>,
 }
 
 /// 更新存储卷 DTO
@@ -115,6 +154,28 @@ pub struct ResizeVolumeDto {
 pub struct CloneVolumeDto {
     pub source_volume_id: String,
     pub target_name: String,
+    /// 从源卷的指定快照克隆（须为 available 状态），未指定时克隆当前数据
+    #[serde(default)]
+    pub source_snapshot_id: Option<String>,
+    /// 目标存储池，须与源存储池位于同一节点；未指定时克隆到源存储池
+    #[serde(default)]
+    pub target_pool_id: Option<String>,
+}
+
+/// 导入已有镜像 DTO
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportVolumeDto {
+    pub name: String,
+    pub pool_id: String,
+    /// 存储池目录内的镜像文件绝对路径，格式与容量由 Agent 通过 qemu-img info 检测
+    pub path: String,
+    /// reference（默认）只链接原文件，删除卷时保留原文件；copy 复制出独立的新文件
+    #[serde(default)]
+    pub mode: VolumeImportMode,
+    pub metadata: Option<JsonValue>,
+    /// 归属用户，由 API 层按当前登录用户设置，存在时校验其所属部门的配额
+    #[serde(skip)]
+    pub owner_id: Option<i32>,
 }
 
 /// 存储卷响应 DTO
@@ -132,13 +193,17 @@ pub struct VolumeResponse {
     pub node_name: Option<String>,  // 从存储池获取
     pub vm_id: Option<String>,
     pub vm_name: Option<String>,
+    pub backing_volume_id: Option<String>,
+    pub encrypted: bool,
     pub metadata: Option<JsonValue>,
+    pub owner_id: Option<i32>,
     pub created_at: String,
     pub updated_at: String,
 }
 
 impl From<Model> for VolumeResponse {
     fn from(volume: Model) -> Self {
+        let encrypted = volume.is_encrypted();
         Self {
             id: volume.id,
             name: volume.name,
@@ -152,7 +217,10 @@ impl From<Model> for VolumeResponse {
             node_name: None, // 需要从存储池获取
             vm_id: volume.vm_id,
             vm_name: None, // 将在服务层填充
+            encrypted,
+            backing_volume_id: volume.backing_volume_id,
             metadata: volume.metadata,
+            owner_id: volume.owner_id,
             created_at: volume.created_at.to_rfc3339(),
             updated_at: volume.updated_at.to_rfc3339(),
         }
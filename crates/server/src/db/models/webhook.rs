@@ -0,0 +1,206 @@
+/// Webhook 数据模型
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use validator::Validate;
+
+/// Webhook 模型
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "webhooks")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub secret: Option<String>,
+    pub events: JsonValue, // 事件类型数组，"*" 表示全部
+    pub enabled: bool,
+
+    // 最近一次投递结果
+    pub last_status: Option<String>, // success, failed
+    pub last_error: Option<String>,
+    pub last_delivered_at: Option<DateTimeWithTimeZone>,
+
+    // 时间戳
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// 订阅的事件类型
+    pub fn event_list(&self) -> Vec<String> {
+        serde_json::from_value(self.events.clone()).unwrap_or_default()
+    }
+
+    /// 是否订阅了该事件
+    pub fn subscribes(&self, event: WebhookEventType) -> bool {
+        self.event_list()
+            .iter()
+            .any(|e| e == "*" || e == event.as_str())
+    }
+}
+
+/// Webhook 事件类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WebhookEventType {
+    /// 节点心跳超时被标记为离线
+    #[serde(rename = "node.offline")]
+    NodeOffline,
+    /// 离线节点恢复在线
+    #[serde(rename = "node.online")]
+    NodeOnline,
+    /// 节点 libvirt 断开，进入错误状态
+    #[serde(rename = "node.error")]
+    NodeError,
+    /// 节点 CPU/内存/磁盘利用率超过告警阈值
+    #[serde(rename = "node.metric_alert")]
+    NodeMetricAlert,
+    /// 节点利用率恢复到阈值以下
+    #[serde(rename = "node.metric_resolved")]
+    NodeMetricResolved,
+    /// 集群内存利用率超过阈值
+    #[serde(rename = "cluster.memory_high")]
+    ClusterMemoryHigh,
+    /// 集群内存利用率恢复到阈值以下
+    #[serde(rename = "cluster.memory_resolved")]
+    ClusterMemoryResolved,
+    /// 手动触发的测试事件
+    #[serde(rename = "webhook.test")]
+    Test,
+}
+
+impl WebhookEventType {
+    pub const ALL: [WebhookEventType; 8] = [
+        WebhookEventType::NodeOffline,
+        WebhookEventType::NodeOnline,
+        WebhookEventType::NodeError,
+        WebhookEventType::NodeMetricAlert,
+        WebhookEventType::NodeMetricResolved,
+        WebhookEventType::ClusterMemoryHigh,
+        WebhookEventType::ClusterMemoryResolved,
+        WebhookEventType::Test,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEventType::NodeOffline => "node.offline",
+            WebhookEventType::NodeOnline => "node.online",
+            WebhookEventType::NodeError => "node.error",
+            WebhookEventType::NodeMetricAlert => "node.metric_alert",
+            WebhookEventType::NodeMetricResolved => "node.metric_resolved",
+            WebhookEventType::ClusterMemoryHigh => "cluster.memory_high",
+            WebhookEventType::ClusterMemoryResolved => "cluster.memory_resolved",
+            WebhookEventType::Test => "webhook.test",
+        }
+    }
+
+    /// 是否为合法的订阅项（事件类型或 "*"）
+    pub fn is_valid_subscription(name: &str) -> bool {
+        name == "*" || Self::ALL.iter().any(|e| e.as_str() == name)
+    }
+}
+
+/// 推送给外部系统的事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    /// 事件唯一 ID，重试时保持不变，接收方可据此去重
+    pub id: String,
+    pub event: WebhookEventType,
+    pub occurred_at: String,
+    /// cluster, node, webhook
+    pub resource_type: String,
+    pub resource_id: Option<String>,
+    pub message: String,
+    /// 事件相关数据，如指标名、当前值与阈值
+    pub data: JsonValue,
+}
+
+impl WebhookEvent {
+    pub fn new(
+        event: WebhookEventType,
+        resource_type: &str,
+        resource_id: Option<&str>,
+        message: impl Into<String>,
+        data: JsonValue,
+    ) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            event,
+            occurred_at: chrono::Utc::now().to_rfc3339(),
+            resource_type: resource_type.to_string(),
+            resource_id: resource_id.map(|s| s.to_string()),
+            message: message.into(),
+            data,
+        }
+    }
+}
+
+/// 创建 Webhook DTO
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CreateWebhookDto {
+    #[validate(length(min = 1, max = 255))]
+    pub name: String,
+    #[validate(url)]
+    pub url: String,
+    pub secret: Option<String>,
+    pub events: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 更新 Webhook DTO
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct UpdateWebhookDto {
+    #[validate(length(min = 1, max = 255))]
+    pub name: Option<String>,
+    #[validate(url)]
+    pub url: Option<String>,
+    /// 传空字符串清除密钥
+    pub secret: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+}
+
+/// Webhook 响应 DTO（不返回密钥）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookResponse {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub has_secret: bool,
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+    pub last_delivered_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<Model> for WebhookResponse {
+    fn from(webhook: Model) -> Self {
+        Self {
+            events: webhook.event_list(),
+            id: webhook.id,
+            name: webhook.name,
+            url: webhook.url,
+            has_secret: webhook.secret.is_some(),
+            enabled: webhook.enabled,
+            last_status: webhook.last_status,
+            last_error: webhook.last_error,
+            last_delivered_at: webhook.last_delivered_at.map(|t| t.to_rfc3339()),
+            created_at: webhook.created_at.to_rfc3339(),
+            updated_at: webhook.updated_at.to_rfc3339(),
+        }
+    }
+}
@@ -0,0 +1,2935 @@
+/// 虚拟机生命周期集成测试
+///
+/// 使用内存 SQLite（按实体建表）和 MockAgentRpc 启动虚拟机路由，
+/// 依次走完 创建 -> 启动 -> 停止 -> 删除，检查每一步的数据库状态与下发给 Agent 的通知
+
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use chrono::Utc;
+use common::utils::BridgeNaming;
+use common::ws_rpc::types::DiskBusType;
+use common::ws_rpc::{RegisterRequest, RegisterResponse, RpcMessage};
+use sea_orm::{
+    ActiveModelTrait, ConnectOptions, ConnectionTrait, Database, DatabaseConnection, EntityTrait,
+    Schema, Set,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use crate::app_state::AppState;
+use crate::config::{AgentAuthTokens, NodeAlertThresholds};
+use crate::db::models::{
+    affinity_group, affinity_group_member, audit_log, department, ip_allocation, network, node, permission,
+    role, role_permission, security_group, snapshot, snapshot_policy, storage_pool, task, user, user_department,
+    user_role, vm, vm_template, volume,
+};
+use crate::services::vm_service::VmService;
+use crate::ws::agent_rpc::mock::MockAgentRpc;
+use crate::ws::AgentConnectionManager;
+
+const NODE_ID: &str = "node-1";
+const POOL_ID: &str = "pool-1";
+const VOLUME_ID: &str = "vol-1";
+const NETWORK_ID: &str = "net-1";
+
+struct TestEnv {
+    db: DatabaseConnection,
+    agent: Arc<MockAgentRpc>,
+    state: AppState,
+    app: Router,
+}
+
+impl TestEnv {
+    async fn new() -> Self {
+        let db = sqlite_db().await;
+        seed(&db).await;
+
+        let agent = Arc::new(MockAgentRpc::new());
+        let state = AppState::new(
+            db.clone(),
+            AgentConnectionManager::new(),
+            BridgeNaming::new(BridgeNaming::DEFAULT_PREFIX).unwrap(),
+            false,
+            NodeAlertThresholds::default(),
+            DiskBusType::Virtio,
+        )
+        .with_agent_rpc(agent.clone());
+
+        // 认证中间件由 JWT 相关测试覆盖，这里直接挂载虚拟机、网络、任务、快照策略与虚拟机模板路由
+        let app = Router::new()
+            .nest("/api/vms", crate::api::vms::vm_routes())
+            .nest("/api/networks", crate::api::networks::routes())
+            .nest("/api/security-groups", crate::api::security_groups::routes())
+            .nest("/api/tasks", crate::api::tasks::routes())
+            .nest("/api/snapshot-policies", crate::api::snapshot_policies::routes())
+            .nest("/api/vm-templates", crate::api::vm_templates::routes())
+            .with_state(state.clone());
+
+        Self { db, agent, state, app }
+    }
+
+    async fn request(&self, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap();
+
+        let response = self.app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        };
+        (status, body)
+    }
+
+    /// 模拟 Agent 上报操作完成（与 ws 处理器收到 vm_operation_completed 时的处理一致）
+    async fn complete(&self, vm_id: &str, operation: &str) {
+        VmService::new(self.state.clone())
+            .handle_vm_operation_completed(vm_id, operation, true, "", None, None)
+            .await
+            .unwrap();
+    }
+
+    /// 经 ws 处理器投递一条 Agent 发来的通知，payload 与 Agent 实际发送的格式一致
+    async fn deliver(&self, method: &str, payload: Value) {
+        let (sender, _receiver) = tokio::sync::mpsc::unbounded_channel();
+        let connection = self
+            .state
+            .agent_manager()
+            .register(
+                NODE_ID.to_string(),
+                "compute-1".to_string(),
+                "10.0.0.11".to_string(),
+                sender,
+            )
+            .await;
+        crate::ws::handler::handle_notification(
+            common::ws_rpc::RpcMessage::notification(method, payload),
+            &connection,
+            &self.state,
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn vm(&self, id: &str) -> Option<vm::Model> {
+        vm::Entity::find_by_id(id.to_string()).one(&self.db).await.unwrap()
+    }
+
+    async fn volume(&self) -> volume::Model {
+        volume::Entity::find_by_id(VOLUME_ID.to_string())
+            .one(&self.db)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn ips(&self) -> Vec<ip_allocation::Model> {
+        ip_allocation::Entity::find().all(&self.db).await.unwrap()
+    }
+}
+
+/// 按实体建好表的内存 SQLite，供各服务测试共用
+pub(crate) async fn sqlite_db() -> DatabaseConnection {
+    // 内存库每个连接相互独立，限制为单连接保证所有查询看到同一份数据
+    let mut opt = ConnectOptions::new("sqlite::memory:");
+    opt.max_connections(1).sqlx_logging(false);
+    let db = Database::connect(opt).await.unwrap();
+    create_tables(&db).await;
+    db
+}
+
+async fn create_tables(db: &DatabaseConnection) {
+    let backend = db.get_database_backend();
+    let schema = Schema::new(backend);
+    // 按外键依赖顺序建表
+    let statements = [
+        schema.create_table_from_entity(node::Entity),
+        schema.create_table_from_entity(storage_pool::Entity),
+        schema.create_table_from_entity(security_group::Entity),
+        schema.create_table_from_entity(network::Entity),
+        schema.create_table_from_entity(vm::Entity),
+        schema.create_table_from_entity(volume::Entity),
+        schema.create_table_from_entity(snapshot::Entity),
+        schema.create_table_from_entity(snapshot_policy::Entity),
+        schema.create_table_from_entity(ip_allocation::Entity),
+        schema.create_table_from_entity(user::Entity),
+        schema.create_table_from_entity(department::Entity),
+        schema.create_table_from_entity(user_department::Entity),
+        schema.create_table_from_entity(task::Entity),
+        schema.create_table_from_entity(affinity_group::Entity),
+        schema.create_table_from_entity(affinity_group_member::Entity),
+        schema.create_table_from_entity(audit_log::Entity),
+        schema.create_table_from_entity(vm_template::Entity),
+        schema.create_table_from_entity(role::Entity),
+        schema.create_table_from_entity(permission::Entity),
+        schema.create_table_from_entity(user_role::Entity),
+        schema.create_table_from_entity(role_permission::Entity),
+    ];
+    for statement in statements {
+        db.execute(backend.build(&statement)).await.unwrap();
+    }
+    // 与迁移脚本一致，防止并发分配出重复地址
+    db.execute_unprepared(
+        "CREATE UNIQUE INDEX idx_ip_allocations_network_ip ON ip_allocations(network_id, ip_address)",
+    )
+    .await
+    .unwrap();
+}
+
+async fn seed(db: &DatabaseConnection) {
+    let now = Utc::now();
+
+    node::ActiveModel {
+        id: Set(NODE_ID.to_string()),
+        hostname: Set("compute-1".to_string()),
+        ip_address: Set("10.0.0.11".to_string()),
+        status: Set("online".to_string()),
+        hypervisor_type: Set(None),
+        hypervisor_version: Set(None),
+        cpu_cores: Set(Some(16)),
+        cpu_threads: Set(Some(32)),
+        memory_total: Set(Some(8 * 1024 * 1024 * 1024)),
+        disk_total: Set(None),
+        cpu_usage: Set(None),
+        memory_used: Set(None),
+        disk_used: Set(None),
+        metadata: Set(None),
+        ipmi_address: Set(None),
+        ipmi_username: Set(None),
+        ipmi_password: Set(None),
+        shutdown_policy: Set("shutdown".to_string()),
+        has_swtpm: Set(false),
+        has_ovs: Set(false),
+        numa_topology: Set(None),
+        architectures: Set(None),
+        last_heartbeat: Set(Some(now.into())),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+    }
+    .insert(db)
+    .await
+    .unwrap();
+
+    storage_pool::ActiveModel {
+        id: Set(POOL_ID.to_string()),
+        name: Set("nfs".to_string()),
+        pool_type: Set("nfs".to_string()),
+        status: Set("active".to_string()),
+        config: Set(json!({})),
+        capacity_gb: Set(Some(1000)),
+        allocated_gb: Set(Some(20)),
+        available_gb: Set(Some(980)),
+        used_gb: Set(None),
+        node_id: Set(Some(NODE_ID.to_string())),
+        metadata: Set(None),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+    }
+    .insert(db)
+    .await
+    .unwrap();
+
+    volume::ActiveModel {
+        id: Set(VOLUME_ID.to_string()),
+        name: Set("root".to_string()),
+        volume_type: Set("qcow2".to_string()),
+        size_gb: Set(20),
+        pool_id: Set(POOL_ID.to_string()),
+        path: Set(Some("/mnt/nfs/vol-1.qcow2".to_string())),
+        status: Set("available".to_string()),
+        vm_id: Set(None),
+        backing_volume_id: Set(None),
+        encryption: Set(None),
+        metadata: Set(None),
+        owner_id: Set(None),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+    }
+    .insert(db)
+    .await
+    .unwrap();
+
+    network::ActiveModel {
+        id: Set(NETWORK_ID.to_string()),
+        name: Set("prod".to_string()),
+        network_type: Set("bridge".to_string()),
+        cidr: Set(Some("192.168.100.0/24".to_string())),
+        gateway: Set(Some("192.168.100.1".to_string())),
+        cidr_v6: Set(None),
+        gateway_v6: Set(None),
+        mtu: Set(Some(1500)),
+        vlan_id: Set(Some(100)),
+        security_group_id: Set(None),
+        dhcp_enabled: Set(false),
+        metadata: Set(None),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+    }
+    .insert(db)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_vm_lifecycle() {
+    let env = TestEnv::new().await;
+
+    // 创建：只写数据库，不通知 Agent；存储卷被占用，IP 分配给虚拟机
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({
+                "name": "web-1",
+                "node_id": NODE_ID,
+                "vcpu": 2,
+                "memory_mb": 2048,
+                "disks": [{ "volume_id": VOLUME_ID, "bus_type": "virtio", "device_type": "disk" }],
+                "networks": [{ "network_id": NETWORK_ID, "model": "virtio" }]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+    assert_eq!(body["status"], "stopped");
+    assert_eq!(body["node_name"], "compute-1");
+    assert!(env.agent.notifications().is_empty());
+
+    let volume = env.volume().await;
+    assert_eq!(volume.status, "in-use");
+    assert_eq!(volume.vm_id.as_deref(), Some(vm_id.as_str()));
+
+    let ips = env.ips().await;
+    assert_eq!(ips[0].status, "allocated");
+    assert_eq!(ips[0].vm_id.as_deref(), Some(vm_id.as_str()));
+    let mac = ips[0].mac_address.clone().expect("分配 IP 时应记录 MAC");
+
+    // 启动：状态置为 starting，并携带磁盘路径、IP 和 VLAN 通知 Agent
+    let (status, _) = env
+        .request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(env.vm(&vm_id).await.unwrap().status, "starting");
+
+    let notifications = env.agent.notifications();
+    assert_eq!(notifications.len(), 1);
+    let start = &notifications[0];
+    assert_eq!(start.node_id, NODE_ID);
+    assert_eq!(start.method, "start_vm_async");
+    assert_eq!(start.payload["vm_id"], vm_id.as_str());
+    assert_eq!(start.payload["safe_mode"], false);
+    assert_eq!(start.payload["firmware"], "bios");
+    assert_eq!(start.payload["volumes"][0]["volume_path"], "/mnt/nfs/vol-1.qcow2");
+    assert_eq!(start.payload["volumes"][0]["format"], "qcow2");
+    let nic = &start.payload["networks"][0];
+    assert_eq!(nic["ip_address"], "192.168.100.2");
+    assert_eq!(nic["mac_address"], mac.as_str());
+    assert_eq!(nic["bridge_name"], "br-vlan100");
+    assert_eq!(nic["vlan_id"], 100);
+    assert_eq!(nic["mtu"], 1500);
+
+    env.complete(&vm_id, "start_vm").await;
+    let running = env.vm(&vm_id).await.unwrap();
+    assert_eq!(running.status, "running");
+    assert!(running.started_at.is_some());
+
+    // 运行中不允许删除
+    let (status, _) = env
+        .request(Method::DELETE, &format!("/api/vms/{}", vm_id), None)
+        .await;
+    assert!(!status.is_success());
+    assert!(env.vm(&vm_id).await.is_some());
+
+    // 停止
+    let (status, body) = env
+        .request(
+            Method::POST,
+            &format!("/api/vms/{}/stop", vm_id),
+            Some(json!({ "force": false })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(env.vm(&vm_id).await.unwrap().status, "stopping");
+
+    let notifications = env.agent.notifications();
+    assert_eq!(notifications.len(), 2);
+    assert_eq!(notifications[1].method, "stop_vm_async");
+    assert_eq!(
+        notifications[1].payload,
+        json!({ "vm_id": vm_id, "force": false, "task_id": body["task_id"] })
+    );
+
+    env.complete(&vm_id, "stop_vm").await;
+    let stopped = env.vm(&vm_id).await.unwrap();
+    assert_eq!(stopped.status, "stopped");
+    assert!(stopped.stopped_at.is_some());
+
+    // 删除：存储卷释放回池，IP 分配记录删除，虚拟机记录移除
+    let (status, _) = env
+        .request(Method::DELETE, &format!("/api/vms/{}", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(env.vm(&vm_id).await.is_none());
+
+    let volume = env.volume().await;
+    assert_eq!(volume.status, "available");
+    assert_eq!(volume.vm_id, None);
+
+    assert!(env.ips().await.is_empty());
+
+    let (status, _) = env
+        .request(Method::GET, &format!("/api/vms/{}", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // 删除不需要 Agent 参与
+    assert_eq!(env.agent.notifications().len(), 2);
+    assert!(env.agent.calls().is_empty());
+}
+
+#[tokio::test]
+async fn test_rebuilding_vm_rejects_lifecycle_operations() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({
+                "name": "web-1",
+                "node_id": NODE_ID,
+                "vcpu": 1,
+                "memory_mb": 1024,
+                "disks": [{ "volume_id": VOLUME_ID, "bus_type": "virtio", "device_type": "disk" }]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    let mut rebuilding: vm::ActiveModel = env.vm(&vm_id).await.unwrap().into();
+    rebuilding.status = Set("rebuilding".to_string());
+    rebuilding.update(&env.db).await.unwrap();
+
+    // 重装期间启动会引导即将被替换的系统盘，停止、重启、删除和再次重装同样拒绝
+    for (method, action, body) in [
+        (Method::POST, "/start", None),
+        (Method::POST, "/stop", Some(json!({ "force": false }))),
+        (Method::POST, "/restart", None),
+        (Method::DELETE, "", None),
+        (Method::POST, "/rebuild", Some(json!({ "source": "https://images.example.com/a.qcow2" }))),
+    ] {
+        let (status, body) = env.request(method, &format!("/api/vms/{}{}", vm_id, action), body).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{}: {}", action, body);
+    }
+    assert_eq!(env.vm(&vm_id).await.unwrap().status, "rebuilding");
+    assert!(env.agent.notifications().is_empty());
+    assert!(env.agent.calls().is_empty());
+}
+
+#[tokio::test]
+async fn test_create_vm_rejects_volume_in_use() {
+    let env = TestEnv::new().await;
+    let create = json!({
+        "name": "web-1",
+        "node_id": NODE_ID,
+        "vcpu": 1,
+        "memory_mb": 1024,
+        "disks": [{ "volume_id": VOLUME_ID, "bus_type": "virtio", "device_type": "disk" }]
+    });
+
+    let (status, _) = env.request(Method::POST, "/api/vms", Some(create.clone())).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    // 同一存储卷不能被第二台虚拟机使用，且不应留下虚拟机记录
+    let (status, _) = env.request(Method::POST, "/api/vms", Some(create)).await;
+    assert!(status.is_client_error() || status.is_server_error());
+    assert_eq!(vm::Entity::find().all(&env.db).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_vm_uses_static_ip_reservation() {
+    let env = TestEnv::new().await;
+
+    let (status, reservation) = env
+        .request(
+            Method::POST,
+            &format!("/api/networks/{}/reservations", NETWORK_ID),
+            Some(json!({ "ip_address": "192.168.100.20", "mac_address": "52:54:00:00:00:20" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", reservation);
+    let reservation_id = reservation["id"].as_str().unwrap().to_string();
+
+    let (status, _) = env
+        .request(
+            Method::POST,
+            &format!("/api/networks/{}/reservations", NETWORK_ID),
+            Some(json!({ "ip_address": "10.0.0.20" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // 显式指定预留的 IP，网卡沿用预留绑定的 MAC
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({
+                "name": "db-1",
+                "node_id": NODE_ID,
+                "vcpu": 1,
+                "memory_mb": 1024,
+                "networks": [{ "network_id": NETWORK_ID, "ip_address": "192.168.100.20", "model": "virtio" }]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+    let stored = env.vm(&vm_id).await.unwrap();
+    let nic = &stored.network_interfaces.unwrap()[0];
+    assert_eq!(nic["ip_address"], "192.168.100.20");
+    assert_eq!(nic["mac_address"], "52:54:00:00:00:20");
+
+    let (status, _) = env
+        .request(
+            Method::DELETE,
+            &format!("/api/networks/{}/reservations/{}", NETWORK_ID, reservation_id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // 删除虚拟机后地址仍保持预留
+    let (status, _) = env
+        .request(Method::DELETE, &format!("/api/vms/{}", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let ips = env.ips().await;
+    assert_eq!(ips.len(), 1);
+    assert_eq!(ips[0].status, "reserved");
+    assert_eq!(ips[0].vm_id, None);
+
+    let (status, _) = env
+        .request(
+            Method::DELETE,
+            &format!("/api/networks/{}/reservations/{}", NETWORK_ID, reservation_id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(env.ips().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_safe_mode_start_keeps_stored_config() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({
+                "name": "web-1",
+                "node_id": NODE_ID,
+                "vcpu": 1,
+                "memory_mb": 1024,
+                "disks": [{ "volume_id": VOLUME_ID, "bus_type": "virtio", "device_type": "disk" }]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+    let stored = env.vm(&vm_id).await.unwrap();
+
+    let (status, _) = env
+        .request(Method::POST, &format!("/api/vms/{}/start?safe_mode=true", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 完整配置照常下发，由 Agent 生成精简 XML；数据库中的配置不变
+    let notifications = env.agent.notifications();
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0].payload["safe_mode"], true);
+    assert_eq!(notifications[0].payload["volumes"][0]["volume_id"], VOLUME_ID);
+
+    let started = env.vm(&vm_id).await.unwrap();
+    assert_eq!(started.status, "starting");
+    assert_eq!(started.volumes, stored.volumes);
+    assert_eq!(started.network_interfaces, stored.network_interfaces);
+}
+
+#[tokio::test]
+async fn test_cloud_init_sent_on_start_and_cleaned_up_on_delete() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({
+                "name": "web-1",
+                "node_id": NODE_ID,
+                "vcpu": 1,
+                "memory_mb": 1024,
+                "cloud_init": { "user_data": "#cloud-config\nhostname: web-1\n" }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+    assert_eq!(body["cloud_init"]["user_data"], "#cloud-config\nhostname: web-1\n");
+
+    let (status, _) = env
+        .request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let start = &env.agent.notifications()[0];
+    assert_eq!(start.payload["cloud_init"]["user_data"], "#cloud-config\nhostname: web-1\n");
+    assert_eq!(start.payload["cloud_init"]["meta_data"], "");
+
+    // 启动失败后虚拟机回到非运行状态，可以删除；删除时通知 Agent 清理配置光盘
+    VmService::new(env.state.clone())
+        .handle_vm_operation_completed(&vm_id, "start_vm", false, "boot failed", None, None)
+        .await
+        .unwrap();
+    let (status, _) = env
+        .request(Method::DELETE, &format!("/api/vms/{}", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let notifications = env.agent.notifications();
+    let cleanup = notifications.last().unwrap();
+    assert_eq!(cleanup.method, "cleanup_vm_async");
+    assert_eq!(cleanup.node_id, NODE_ID);
+    assert_eq!(cleanup.payload["vm_id"], vm_id.as_str());
+}
+
+#[tokio::test]
+async fn test_reconcile_corrects_drifted_vm_status() {
+    let env = TestEnv::new().await;
+    let mut ids = Vec::new();
+    for name in ["web-1", "web-2", "web-3"] {
+        let (status, body) = env
+            .request(
+                Method::POST,
+                "/api/vms",
+                Some(json!({ "name": name, "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        ids.push(body["id"].as_str().unwrap().to_string());
+    }
+    let (running, stopped, starting) = (&ids[0], &ids[1], &ids[2]);
+
+    for id in [running, starting] {
+        let (status, _) = env
+            .request(Method::POST, &format!("/api/vms/{}/start", id), None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    env.complete(running, "start_vm").await;
+
+    // 数据库中 running 的虚拟机在节点上已不存在，stopped 的虚拟机实际在运行
+    env.agent.push(
+        "list_vms",
+        Ok(json!({
+            "vms": [
+                { "vm_id": stopped, "uuid": stopped, "name": "web-2", "state": "running", "vcpu": 1, "memory_mb": 1024 },
+                { "vm_id": starting, "uuid": starting, "name": "web-3", "state": "shutoff", "vcpu": 1, "memory_mb": 1024 }
+            ]
+        })),
+    );
+
+    let corrected = VmService::new(env.state.clone())
+        .reconcile_vm_states()
+        .await
+        .unwrap();
+    assert_eq!(corrected, 2);
+    assert_eq!(env.vm(running).await.unwrap().status, "stopped");
+    assert_eq!(env.vm(stopped).await.unwrap().status, "running");
+    // 过渡状态留给操作完成通知处理
+    assert_eq!(env.vm(starting).await.unwrap().status, "starting");
+
+    let calls = env.agent.calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].method, "list_vms");
+    assert_eq!(calls[0].node_id, NODE_ID);
+}
+
+#[tokio::test]
+async fn test_refresh_node_capabilities_updates_node_row() {
+    let env = TestEnv::new().await;
+    env.agent.push(
+        "refresh_capabilities",
+        Ok(json!({
+            "node_id": "agent-configured-id",
+            "cpu_cores": 16,
+            "cpu_threads": 32,
+            "memory_total": 68719476736u64,
+            "disk_total": 1099511627776u64,
+            "hypervisor_type": "kvm",
+            "hypervisor_version": "QEMU emulator version 8.2.0",
+            "has_swtpm": true,
+            "has_ovs": true,
+            "supported_architectures": ["x86_64", "aarch64"],
+            "timestamp": 0
+        })),
+    );
+
+    let node = crate::services::node_service::NodeService::new(env.state.clone())
+        .refresh_capabilities(NODE_ID)
+        .await
+        .unwrap();
+
+    assert_eq!(node.id, NODE_ID);
+    assert_eq!(node.hypervisor_version.as_deref(), Some("QEMU emulator version 8.2.0"));
+    assert_eq!(node.cpu_cores, Some(16));
+    assert!(node.has_swtpm);
+    assert!(node.has_ovs);
+    assert_eq!(node.architectures, Some(json!(["x86_64", "aarch64"])));
+    let calls = env.agent.calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].method, "refresh_capabilities");
+    assert_eq!(calls[0].node_id, NODE_ID);
+}
+
+#[tokio::test]
+async fn test_reconnect_reconciliation_resolves_transient_and_missing_vms() {
+    let env = TestEnv::new().await;
+    let mut ids = Vec::new();
+    for name in ["web-1", "web-2", "web-3", "web-4"] {
+        let (status, body) = env
+            .request(
+                Method::POST,
+                "/api/vms",
+                Some(json!({ "name": name, "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        ids.push(body["id"].as_str().unwrap().to_string());
+    }
+    let (starting, stopping, running, stopped) = (&ids[0], &ids[1], &ids[2], &ids[3]);
+
+    for id in [starting, stopping, running] {
+        let (status, _) = env
+            .request(Method::POST, &format!("/api/vms/{}/start", id), None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    env.complete(stopping, "start_vm").await;
+    env.complete(running, "start_vm").await;
+    let (status, _) = env
+        .request(Method::POST, &format!("/api/vms/{}/stop", stopping), Some(json!({ "force": false })))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(env.vm(starting).await.unwrap().status, "starting");
+    assert_eq!(env.vm(stopping).await.unwrap().status, "stopping");
+
+    // Agent 崩溃丢失了完成通知：starting 实际已运行，stopping 实际已关机，running 已不存在
+    env.agent.push(
+        "list_vms",
+        Ok(json!({
+            "vms": [
+                { "vm_id": starting, "uuid": starting, "name": "web-1", "state": "running", "vcpu": 1, "memory_mb": 1024 },
+                { "vm_id": stopping, "uuid": stopping, "name": "web-2", "state": "shutoff", "vcpu": 1, "memory_mb": 1024 }
+            ]
+        })),
+    );
+
+    let corrected = crate::services::node_service::NodeService::new(env.state.clone())
+        .reconcile_vms_on_reconnect(NODE_ID)
+        .await
+        .unwrap();
+
+    assert_eq!(corrected, 3);
+    assert_eq!(env.vm(starting).await.unwrap().status, "running");
+    assert_eq!(env.vm(stopping).await.unwrap().status, "stopped");
+    assert_eq!(env.vm(running).await.unwrap().status, "error");
+    // 从未启动的虚拟机在节点上未定义属于正常情况
+    assert_eq!(env.vm(stopped).await.unwrap().status, "stopped");
+    let calls = env.agent.calls();
+    let last = calls.last().unwrap();
+    assert_eq!(last.method, "list_vms");
+    assert_eq!(last.node_id, NODE_ID);
+}
+
+#[tokio::test]
+async fn test_vnc_port_recorded_on_start_and_cleared_on_stop() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+    assert_eq!(body["vnc_port"], Value::Null);
+
+    let service = VmService::new(env.state.clone());
+    let vnc = common::ws_rpc::VncInfo {
+        port: 5901,
+        listen: "0.0.0.0".to_string(),
+        protocol: common::ws_rpc::GraphicsType::Vnc,
+    };
+    service
+        .handle_vm_operation_completed(&vm_id, "start_vm", true, "", Some(vnc), None)
+        .await
+        .unwrap();
+
+    // 监听所有地址时通过节点 IP 访问
+    let (_, body) = env
+        .request(Method::GET, &format!("/api/vms/{}", vm_id), None)
+        .await;
+    assert_eq!(body["vnc_port"], 5901);
+    assert_eq!(body["vnc_host"], "10.0.0.11");
+    assert_eq!(body["console_protocol"], "vnc");
+
+    env.complete(&vm_id, "stop_vm").await;
+    let stopped = env.vm(&vm_id).await.unwrap();
+    assert_eq!(stopped.vnc_port, None);
+    assert_eq!(stopped.vnc_host, None);
+    assert_eq!(stopped.console_protocol, None);
+}
+
+#[tokio::test]
+async fn test_console_access_issues_one_time_password() {
+    use crate::auth::Claims;
+    use axum::middleware::{from_fn, Next};
+
+    let env = TestEnv::new().await;
+
+    async fn fake_auth(mut request: axum::extract::Request, next: Next) -> axum::response::Response {
+        let claims = Claims { sub: 7, username: "alice".to_string(), exp: 9999999999, iat: 0 };
+        request.extensions_mut().insert(claims);
+        next.run(request).await
+    }
+    let app = Router::new()
+        .nest("/api/vms", crate::api::vms::vm_routes().layer(from_fn(fake_auth)))
+        .with_state(env.state.clone());
+    let console = |uri: String| {
+        let app = app.clone();
+        async move {
+            let request = Request::builder().method(Method::POST).uri(uri).body(Body::empty()).unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<Value>(&bytes).unwrap_or(Value::Null))
+        }
+    };
+
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+    let uri = format!("/api/vms/{}/console", vm_id);
+
+    // 未登录、非所有者且无 vm:console 权限都不能访问控制台
+    let (status, _) = env.request(Method::POST, &uri, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = console(uri.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let mut owned: vm::ActiveModel = vm::Entity::find_by_id(vm_id.clone()).one(&env.db).await.unwrap().unwrap().into();
+    owned.owner_id = Set(Some(7));
+    owned.update(&env.db).await.unwrap();
+    // 虚拟机未运行
+    let (status, _) = console(uri.clone()).await;
+    assert!(!status.is_success());
+
+    // 每次启动都带随机密码，控制台不会无密码监听
+    env.request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    let start = env.agent.notifications().pop().unwrap();
+    let boot_password = start.payload["console_password"].as_str().unwrap().to_string();
+    assert_eq!(boot_password.len(), 8);
+    let vnc = json!({ "port": 5901, "listen": "0.0.0.0" });
+    VmService::new(env.state.clone())
+        .handle_vm_operation_completed(
+            &vm_id,
+            "start_vm",
+            true,
+            "",
+            Some(serde_json::from_value(vnc).unwrap()),
+            None,
+        )
+        .await
+        .unwrap();
+
+    env.agent.push("set_console_password", Ok(json!({ "vm_id": vm_id, "success": true })));
+    let (status, body) = console(uri.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["protocol"], "vnc");
+    assert_eq!(body["url"], format!("/ws/vnc/{}", vm_id));
+    let password = body["password"].as_str().unwrap();
+    assert_ne!(password, boot_password);
+
+    let call = env.agent.calls().pop().unwrap();
+    assert_eq!(call.method, "set_console_password");
+    assert_eq!(call.payload["password"], password);
+    assert_eq!(call.payload["valid_secs"], 60);
+}
+
+/// 创建用户并通过单独的角色授予指定权限
+async fn grant_permission(db: &DatabaseConnection, user_id: i32, resource: &str, action: &str) {
+    let now = Utc::now();
+    user::ActiveModel {
+        id: Set(user_id),
+        username: Set(format!("user-{}", user_id)),
+        email: Set(format!("user-{}@example.com", user_id)),
+        password_hash: Set(String::new()),
+        is_active: Set(true),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+    }
+    .insert(db)
+    .await
+    .unwrap();
+    let role = role::ActiveModel {
+        name: Set(format!("{}:{}", resource, action)),
+        description: Set(None),
+        is_active: Set(true),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+    let permission = permission::ActiveModel {
+        name: Set(format!("{}:{}", resource, action)),
+        description: Set(None),
+        resource: Set(resource.to_string()),
+        action: Set(action.to_string()),
+        is_active: Set(true),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+    user_role::ActiveModel {
+        user_id: Set(user_id),
+        role_id: Set(role.id),
+        created_at: Set(now.into()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+    role_permission::ActiveModel {
+        role_id: Set(role.id),
+        permission_id: Set(permission.id),
+        created_at: Set(now.into()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_guest_exec_rejects_out_of_range_timeout() {
+    use crate::auth::Claims;
+    use axum::middleware::{from_fn, Next};
+
+    let env = TestEnv::new().await;
+    grant_permission(&env.db, 7, "vm", "exec").await;
+
+    async fn fake_auth(mut request: axum::extract::Request, next: Next) -> axum::response::Response {
+        let claims = Claims { sub: 7, username: "alice".to_string(), exp: 9999999999, iat: 0 };
+        request.extensions_mut().insert(claims);
+        next.run(request).await
+    }
+    let app = Router::new()
+        .nest("/api/vms", crate::api::vms::vm_routes().layer(from_fn(fake_auth)))
+        .with_state(env.state.clone());
+
+    // 超大的超时会让 RPC 超时计算溢出，0 则无法等到任何输出
+    for timeout_secs in [0, 601, u64::MAX] {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/vms/vm-1/exec")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "command": "/bin/true", "timeout_secs": timeout_secs }).to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "timeout_secs={}", timeout_secs);
+    }
+    assert!(env.agent.calls().is_empty());
+}
+
+#[tokio::test]
+async fn test_node_drain_and_shutdown_policy_require_power_permission() {
+    use crate::auth::Claims;
+    use axum::middleware::{from_fn, Next};
+
+    let env = TestEnv::new().await;
+
+    async fn fake_auth(mut request: axum::extract::Request, next: Next) -> axum::response::Response {
+        let claims = Claims { sub: 7, username: "alice".to_string(), exp: 9999999999, iat: 0 };
+        request.extensions_mut().insert(claims);
+        next.run(request).await
+    }
+    let app = Router::new()
+        .nest("/api/nodes", crate::api::nodes::node_routes().layer(from_fn(fake_auth)))
+        .with_state(env.state.clone());
+    let send = |method: Method, uri: String, body: Value| {
+        let app = app.clone();
+        async move {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            app.oneshot(request).await.unwrap().status()
+        }
+    };
+    let policy_uri = format!("/api/nodes/{}/shutdown-policy", NODE_ID);
+    let drain_uri = format!("/api/nodes/{}/drain", NODE_ID);
+
+    // 排空会挂起或关闭节点上所有虚拟机，与开关机同样需要 node:power
+    assert_eq!(send(Method::PUT, policy_uri.clone(), json!({ "policy": "suspend" })).await, StatusCode::FORBIDDEN);
+    assert_eq!(send(Method::POST, drain_uri, json!({})).await, StatusCode::FORBIDDEN);
+    assert!(env.agent.calls().is_empty());
+
+    grant_permission(&env.db, 7, "node", "power").await;
+    assert_eq!(send(Method::PUT, policy_uri, json!({ "policy": "suspend" })).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_spice_graphics_sent_on_start_and_port_recorded() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({
+                "name": "win-1",
+                "node_id": NODE_ID,
+                "vcpu": 2,
+                "memory_mb": 4096,
+                "os_type": "windows",
+                "graphics": "spice"
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["graphics"], "spice");
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    let (status, body) = env
+        .request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let start = env.agent.notifications().pop().unwrap();
+    assert_eq!(start.payload["graphics"], "spice");
+
+    // Agent 上报的是实际分配的 SPICE 端口，之后改回 VNC 只影响下次启动
+    let spice = json!({ "port": 5902, "listen": "0.0.0.0", "protocol": "spice" });
+    VmService::new(env.state.clone())
+        .handle_vm_operation_completed(
+            &vm_id,
+            "start_vm",
+            true,
+            "",
+            Some(serde_json::from_value(spice).unwrap()),
+            None,
+        )
+        .await
+        .unwrap();
+    let (status, body) = env
+        .request(Method::PUT, &format!("/api/vms/{}", vm_id), Some(json!({ "graphics": "vnc" })))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let vm = env.vm(&vm_id).await.unwrap();
+    assert_eq!(vm.graphics, "vnc");
+    assert_eq!(vm.vnc_port, Some(5902));
+    assert_eq!(vm.console_protocol.as_deref(), Some("spice"));
+}
+
+#[tokio::test]
+async fn test_pause_and_resume_vm() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    // 未运行的虚拟机不能暂停，也不会调用 Agent
+    let (status, _) = env
+        .request(Method::POST, &format!("/api/vms/{}/pause", vm_id), None)
+        .await;
+    assert!(!status.is_success());
+    assert!(env.agent.calls().is_empty());
+
+    env.request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    env.complete(&vm_id, "start_vm").await;
+
+    env.agent
+        .push("pause_vm", Ok(json!({ "success": true, "message": "" })));
+    let (status, _) = env
+        .request(Method::POST, &format!("/api/vms/{}/pause", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(env.vm(&vm_id).await.unwrap().status, "paused");
+
+    // 暂停的虚拟机需要通过恢复操作继续运行
+    let (status, _) = env
+        .request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    assert!(!status.is_success());
+
+    env.agent
+        .push("resume_vm", Ok(json!({ "success": true, "message": "" })));
+    let (status, _) = env
+        .request(Method::POST, &format!("/api/vms/{}/resume", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(env.vm(&vm_id).await.unwrap().status, "running");
+
+    let calls = env.agent.calls();
+    let methods: Vec<&str> = calls.iter().map(|c| c.method.as_str()).collect();
+    assert_eq!(methods, vec!["pause_vm", "resume_vm"]);
+    assert_eq!(calls[0].payload["vm_id"], vm_id.as_str());
+}
+
+#[tokio::test]
+async fn test_abort_migration_calls_source_node() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    // 没有进行中的迁移时直接拒绝
+    let (status, _) = env
+        .request(Method::POST, &format!("/api/vms/{}/migrate/abort", vm_id), None)
+        .await;
+    assert!(!status.is_success());
+    assert!(env.agent.calls().is_empty());
+
+    let mut vm_active: vm::ActiveModel = env.vm(&vm_id).await.unwrap().into();
+    vm_active.status = Set("migrating".to_string());
+    vm_active.update(&env.db).await.unwrap();
+
+    env.agent
+        .push("abort_migration", Ok(json!({ "success": true, "message": "" })));
+    let (status, _) = env
+        .request(Method::POST, &format!("/api/vms/{}/migrate/abort", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let calls = env.agent.calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].method, "abort_migration");
+    assert_eq!(calls[0].node_id, NODE_ID);
+    assert_eq!(calls[0].payload["vm_id"], vm_id.as_str());
+    // 状态等源节点上报迁移失败后再恢复
+    assert_eq!(env.vm(&vm_id).await.unwrap().status, "migrating");
+}
+
+#[tokio::test]
+async fn test_migration_speed_cap() {
+    let env = TestEnv::new().await;
+
+    let source = node::Entity::find_by_id(NODE_ID.to_string())
+        .one(&env.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut target: node::ActiveModel = source.into();
+    target.id = Set("node-2".to_string());
+    target.hostname = Set("compute-2".to_string());
+    target.ip_address = Set("10.0.0.12".to_string());
+    target.insert(&env.db).await.unwrap();
+
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    let mut vm_active: vm::ActiveModel = env.vm(&vm_id).await.unwrap().into();
+    vm_active.status = Set("running".to_string());
+    vm_active.update(&env.db).await.unwrap();
+
+    // max_mbps 作为 max_bandwidth_mbps 的别名下发给源节点
+    env.agent
+        .push("migrate_vm", Ok(json!({ "success": true, "message": "" })));
+    let (status, body) = env
+        .request(
+            Method::POST,
+            &format!("/api/vms/{}/migrate", vm_id),
+            Some(json!({ "target_node_id": "node-2", "live": true, "max_mbps": 50 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(env.agent.calls()[0].payload["max_bandwidth_mbps"], 50);
+
+    env.agent.push(
+        "get_migration_speed",
+        Ok(json!({ "vm_id": vm_id, "max_mbps": 50 })),
+    );
+    let (status, body) = env
+        .request(Method::GET, &format!("/api/vms/{}/migrate/speed", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["max_mbps"], 50);
+
+    let calls = env.agent.calls();
+    assert_eq!(calls[1].method, "get_migration_speed");
+    assert_eq!(calls[1].node_id, NODE_ID);
+}
+
+#[tokio::test]
+async fn test_update_running_vm_resizes_live() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    // 已停止的虚拟机只修改数据库，下次启动生效
+    let (status, _) = env
+        .request(Method::PUT, &format!("/api/vms/{}", vm_id), Some(json!({ "vcpu": 2 })))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(env.agent.calls().is_empty());
+
+    env.request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    env.complete(&vm_id, "start_vm").await;
+
+    env.agent
+        .push("resize_vm_cpu", Ok(json!({ "success": true, "message": "" })));
+    env.agent
+        .push("resize_vm_memory", Ok(json!({ "success": true, "message": "" })));
+    let (status, body) = env
+        .request(
+            Method::PUT,
+            &format!("/api/vms/{}", vm_id),
+            Some(json!({ "vcpu": 4, "memory_mb": 2048, "name": "web-1" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let calls = env.agent.calls();
+    let methods: Vec<&str> = calls.iter().map(|c| c.method.as_str()).collect();
+    assert_eq!(methods, vec!["resize_vm_cpu", "resize_vm_memory"]);
+    assert_eq!(calls[0].payload["vcpu"], 4);
+    assert_eq!(calls[1].payload["memory_mb"], 2048);
+
+    let vm = env.vm(&vm_id).await.unwrap();
+    assert_eq!((vm.vcpu, vm.memory_mb), (4, 2048));
+
+    // Agent 调整失败时不修改数据库
+    let (status, _) = env
+        .request(Method::PUT, &format!("/api/vms/{}", vm_id), Some(json!({ "vcpu": 8 })))
+        .await;
+    assert!(!status.is_success());
+    assert_eq!(env.vm(&vm_id).await.unwrap().vcpu, 4);
+}
+
+#[tokio::test]
+async fn test_volume_iotune_sent_on_start_and_updated_live() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({
+                "name": "db-1",
+                "node_id": NODE_ID,
+                "vcpu": 2,
+                "memory_mb": 2048,
+                "disks": [{
+                    "volume_id": VOLUME_ID,
+                    "bus_type": "virtio",
+                    "device_type": "disk",
+                    "iops_limit": 500
+                }]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    env.request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    let start = env.agent.notifications().pop().unwrap();
+    assert_eq!(start.payload["volumes"][0]["iops_limit"], 500);
+    assert!(start.payload["volumes"][0]["bps_limit"].is_null());
+    env.complete(&vm_id, "start_vm").await;
+
+    // 限速值 0 在 libvirt 中表示不限速，接口直接拒绝
+    let (status, _) = env
+        .request(
+            Method::POST,
+            &format!("/api/vms/{}/volumes/iotune", vm_id),
+            Some(json!({ "volume_id": VOLUME_ID, "iops_limit": 0 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    env.agent
+        .push("set_volume_iotune", Ok(json!({ "success": true, "message": "" })));
+    let (status, body) = env
+        .request(
+            Method::POST,
+            &format!("/api/vms/{}/volumes/iotune", vm_id),
+            Some(json!({ "volume_id": VOLUME_ID, "bps_limit": 10485760 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let call = env.agent.calls().pop().unwrap();
+    assert_eq!(call.method, "set_volume_iotune");
+    assert_eq!(call.payload["volume_id"], VOLUME_ID);
+    assert_eq!(call.payload["bps_limit"], 10485760);
+
+    let vm = env.vm(&vm_id).await.unwrap();
+    let disks = vm.volumes.unwrap();
+    assert!(disks[0]["iops_limit"].is_null());
+    assert_eq!(disks[0]["bps_limit"], 10485760);
+}
+
+#[tokio::test]
+async fn test_nic_bandwidth_sent_on_start_and_updated_live() {
+    let env = TestEnv::new().await;
+    let vm_request = |inbound: u32| {
+        json!({
+            "name": "web-1",
+            "node_id": NODE_ID,
+            "vcpu": 2,
+            "memory_mb": 2048,
+            "networks": [{ "network_id": NETWORK_ID, "model": "virtio", "inbound_kbps": inbound }]
+        })
+    };
+
+    // 限速值 0 在 libvirt 中表示不限速，创建时直接拒绝
+    let (status, _) = env.request(Method::POST, "/api/vms", Some(vm_request(0))).await;
+    assert!(!status.is_success());
+
+    let (status, body) = env.request(Method::POST, "/api/vms", Some(vm_request(1000))).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+    let mac = env.vm(&vm_id).await.unwrap().network_interfaces.unwrap()[0]["mac_address"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    env.request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    let start = env.agent.notifications().pop().unwrap();
+    assert_eq!(start.payload["networks"][0]["inbound_kbps"], 1000);
+    assert!(start.payload["networks"][0]["outbound_kbps"].is_null());
+    env.complete(&vm_id, "start_vm").await;
+
+    let (status, _) = env
+        .request(
+            Method::POST,
+            &format!("/api/vms/{}/networks/bandwidth", vm_id),
+            Some(json!({ "mac_address": "52:54:00:00:00:01", "outbound_kbps": 500 })),
+        )
+        .await;
+    assert!(!status.is_success());
+
+    env.agent
+        .push("set_nic_bandwidth", Ok(json!({ "success": true, "message": "" })));
+    let (status, body) = env
+        .request(
+            Method::POST,
+            &format!("/api/vms/{}/networks/bandwidth", vm_id),
+            Some(json!({ "mac_address": mac.to_uppercase(), "outbound_kbps": 500 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let call = env.agent.calls().pop().unwrap();
+    assert_eq!(call.method, "set_nic_bandwidth");
+    assert_eq!(call.payload["outbound_kbps"], 500);
+    assert!(call.payload["inbound_kbps"].is_null());
+
+    let nics = env.vm(&vm_id).await.unwrap().network_interfaces.unwrap();
+    assert!(nics[0]["inbound_kbps"].is_null());
+    assert_eq!(nics[0]["outbound_kbps"], 500);
+}
+
+#[tokio::test]
+async fn test_security_group_sent_on_start_and_synced_live() {
+    let env = TestEnv::new().await;
+
+    // 只有 tcp / udp 规则可以指定端口
+    let (status, _) = env
+        .request(
+            Method::POST,
+            "/api/security-groups",
+            Some(json!({ "name": "web", "rules": [{ "protocol": "icmp", "port_from": 22 }] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/security-groups",
+            Some(json!({
+                "name": "web",
+                "rules": [{ "protocol": "tcp", "port_from": 22, "cidr": "10.0.0.0/8" }]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let group_id = body["id"].as_str().unwrap().to_string();
+
+    // 设为网络的默认安全组，网卡未单独指定时使用
+    let (status, body) = env
+        .request(
+            Method::PUT,
+            &format!("/api/networks/{}", NETWORK_ID),
+            Some(json!({ "security_group_id": group_id })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["security_group_id"], group_id.as_str());
+
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({
+                "name": "web-1",
+                "node_id": NODE_ID,
+                "vcpu": 2,
+                "memory_mb": 2048,
+                "networks": [{ "network_id": NETWORK_ID, "model": "virtio" }]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+    let mac = env.vm(&vm_id).await.unwrap().network_interfaces.unwrap()[0]["mac_address"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    env.request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    let start = env.agent.notifications().pop().unwrap();
+    let rules = &start.payload["networks"][0]["security_group_rules"];
+    assert_eq!(rules[0]["protocol"], "tcp");
+    assert_eq!(rules[0]["port_from"], 22);
+    assert_eq!(rules[0]["cidr"], "10.0.0.0/8");
+    env.complete(&vm_id, "start_vm").await;
+
+    // 修改规则后同步到运行中的虚拟机
+    env.agent
+        .push("apply_security_group", Ok(json!({ "success": true, "message": "" })));
+    let (status, body) = env
+        .request(
+            Method::PUT,
+            &format!("/api/security-groups/{}", group_id),
+            Some(json!({ "rules": [{ "protocol": "tcp", "port_from": 80, "port_to": 443 }] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let call = env.agent.calls().pop().unwrap();
+    assert_eq!(call.method, "apply_security_group");
+    assert_eq!(call.payload["vm_id"], vm_id.as_str());
+    assert_eq!(call.payload["mac_address"], mac.as_str());
+    assert_eq!(call.payload["security_group_id"], group_id.as_str());
+    assert_eq!(call.payload["rules"][0]["port_to"], 443);
+
+    // 仍被网络引用时不能删除
+    let (status, _) = env
+        .request(Method::DELETE, &format!("/api/security-groups/{}", group_id), None)
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // 取消网络的默认安全组后，Agent 删除该网卡上的过滤规则
+    env.agent
+        .push("apply_security_group", Ok(json!({ "success": true, "message": "" })));
+    let (status, _) = env
+        .request(
+            Method::PUT,
+            &format!("/api/networks/{}", NETWORK_ID),
+            Some(json!({ "security_group_id": "" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let call = env.agent.calls().pop().unwrap();
+    assert_eq!(call.method, "apply_security_group");
+    assert!(call.payload["security_group_id"].is_null());
+    assert_eq!(call.payload["rules"], json!([]));
+
+    let (status, _) = env
+        .request(Method::DELETE, &format!("/api/security-groups/{}", group_id), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_managed_dhcp_leases_follow_ip_allocation() {
+    let env = TestEnv::new().await;
+
+    let (status, body) = env
+        .request(
+            Method::PUT,
+            &format!("/api/networks/{}", NETWORK_ID),
+            Some(json!({ "dhcp_enabled": true })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["dhcp_enabled"], true);
+    let reload = env.agent.notifications().pop().unwrap();
+    assert_eq!(reload.method, "reload_dhcp");
+    assert_eq!(reload.node_id, NODE_ID);
+    assert_eq!(reload.payload["bridge_name"], "br-vlan100");
+    assert_eq!(reload.payload["gateway"], "192.168.100.1");
+    assert_eq!(reload.payload["leases"], json!([]));
+
+    // 创建虚拟机分配 IP 后刷新静态租约
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({
+                "name": "web-1",
+                "node_id": NODE_ID,
+                "vcpu": 1,
+                "memory_mb": 1024,
+                "networks": [{ "network_id": NETWORK_ID, "model": "virtio" }]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+    let nic = env.vm(&vm_id).await.unwrap().network_interfaces.unwrap()[0].clone();
+    let expected_lease = json!({
+        "mac_address": nic["mac_address"],
+        "ip_address": nic["ip_address"],
+        "hostname": "web-1"
+    });
+
+    let reload = env.agent.notifications().pop().unwrap();
+    assert_eq!(reload.method, "reload_dhcp");
+    assert_eq!(reload.payload["leases"], json!([expected_lease]));
+
+    // 启动时随网卡配置下发，Agent 在 Bridge 上启动 dnsmasq
+    env.request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    let start = env.agent.notifications().pop().unwrap();
+    assert_eq!(start.method, "start_vm_async");
+    assert_eq!(start.payload["networks"][0]["dhcp"]["cidr"], "192.168.100.0/24");
+    assert_eq!(start.payload["networks"][0]["dhcp"]["leases"], json!([expected_lease]));
+
+    // 关闭 DHCP 后通知节点停止服务
+    let (status, _) = env
+        .request(
+            Method::PUT,
+            &format!("/api/networks/{}", NETWORK_ID),
+            Some(json!({ "dhcp_enabled": false })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let stop = env.agent.notifications().pop().unwrap();
+    assert_eq!(stop.method, "stop_dhcp");
+    assert_eq!(stop.payload["bridge_name"], "br-vlan100");
+
+    // 托管 DHCP 需要网关作为 dnsmasq 的监听地址
+    let (status, _) = env
+        .request(
+            Method::POST,
+            "/api/networks",
+            Some(json!({
+                "name": "isolated",
+                "network_type": "bridge",
+                "cidr": "10.10.0.0/24",
+                "dhcp_enabled": true
+            })),
+        )
+        .await;
+    assert!(!status.is_success());
+}
+
+#[tokio::test]
+async fn test_tpm_requires_swtpm_on_node() {
+    let env = TestEnv::new().await;
+    let body = json!({
+        "name": "win-11",
+        "node_id": NODE_ID,
+        "vcpu": 4,
+        "memory_mb": 8192,
+        "os_type": "windows",
+        "firmware": "uefi",
+        "tpm": true
+    });
+
+    // 节点未上报 swtpm 时拒绝创建
+    let (status, _) = env
+        .request(Method::POST, "/api/vms", Some(body.clone()))
+        .await;
+    assert!(!status.is_success());
+    assert!(vm::Entity::find().all(&env.db).await.unwrap().is_empty());
+
+    let node = node::Entity::find_by_id(NODE_ID.to_string())
+        .one(&env.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut node_active: node::ActiveModel = node.into();
+    node_active.has_swtpm = Set(true);
+    node_active.update(&env.db).await.unwrap();
+
+    let (status, body) = env.request(Method::POST, "/api/vms", Some(body)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["tpm"], true);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    env.request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    let start = env.agent.notifications().pop().unwrap();
+    assert_eq!(start.payload["tpm"], true);
+}
+
+#[tokio::test]
+async fn test_start_boots_once_from_install_iso() {
+    let env = TestEnv::new().await;
+    let now = Utc::now();
+    volume::ActiveModel {
+        id: Set("vol-iso".to_string()),
+        name: Set("ubuntu-24.04.iso".to_string()),
+        volume_type: Set("raw".to_string()),
+        size_gb: Set(3),
+        pool_id: Set(POOL_ID.to_string()),
+        path: Set(Some("/mnt/nfs/vol-iso.raw".to_string())),
+        status: Set("available".to_string()),
+        vm_id: Set(None),
+        backing_volume_id: Set(None),
+        encryption: Set(None),
+        metadata: Set(None),
+        owner_id: Set(None),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+    }
+    .insert(&env.db)
+    .await
+    .unwrap();
+
+    let disks = |iso_order: u32| {
+        json!([
+            { "volume_id": VOLUME_ID, "bus_type": "virtio", "device_type": "disk", "boot_order": 1 },
+            { "volume_id": "vol-iso", "bus_type": "sata", "device_type": "cdrom", "boot_order": iso_order }
+        ])
+    };
+    let body = |disks: serde_json::Value| {
+        json!({
+            "name": "web-1",
+            "node_id": NODE_ID,
+            "vcpu": 1,
+            "memory_mb": 1024,
+            "boot_menu": true,
+            "disks": disks
+        })
+    };
+
+    // 引导顺序不能重复
+    let (status, _) = env
+        .request(Method::POST, "/api/vms", Some(body(disks(1))))
+        .await;
+    assert!(!status.is_success());
+
+    let (status, body) = env
+        .request(Method::POST, "/api/vms", Some(body(disks(2))))
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["boot_menu"], true);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    let (status, _) = env
+        .request(Method::POST, &format!("/api/vms/{}/start?boot_from=vol-missing", vm_id), None)
+        .await;
+    assert!(!status.is_success());
+    assert_eq!(env.vm(&vm_id).await.unwrap().status, "stopped");
+
+    // 仅本次从光盘引导，保存的引导顺序不变
+    let (status, body) = env
+        .request(Method::POST, &format!("/api/vms/{}/start?boot_from=vol-iso", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let start = env.agent.notifications().pop().unwrap();
+    assert_eq!(start.payload["boot_menu"], true);
+    assert_eq!(start.payload["volumes"][0]["boot_order"], 2);
+    assert_eq!(start.payload["volumes"][1]["boot_order"], 1);
+
+    let saved = env.vm(&vm_id).await.unwrap().volumes.unwrap();
+    assert_eq!(saved[0]["boot_order"], 1);
+    assert_eq!(saved[1]["boot_order"], 2);
+}
+
+#[tokio::test]
+async fn test_start_sends_arch_and_machine_type() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["arch"], "x86_64");
+    assert!(body["machine_type"].is_null());
+
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({
+                "name": "arm-1",
+                "node_id": NODE_ID,
+                "vcpu": 1,
+                "memory_mb": 1024,
+                "arch": "aarch64",
+                "machine_type": "virt-8.2"
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    let (status, body) = env
+        .request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let start = env.agent.notifications().pop().unwrap();
+    assert_eq!(start.payload["arch"], "aarch64");
+    assert_eq!(start.payload["machine_type"], "virt-8.2");
+
+    // 空字符串恢复为由 Agent 自动选择
+    let (status, body) = env
+        .request(Method::PUT, &format!("/api/vms/{}", vm_id), Some(json!({ "machine_type": "" })))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let vm = env.vm(&vm_id).await.unwrap();
+    assert_eq!(vm.arch, "aarch64");
+    assert_eq!(vm.machine_type, None);
+}
+
+#[tokio::test]
+async fn test_cpu_pinning_validated_against_node_topology() {
+    let env = TestEnv::new().await;
+    let body = |pcpu: u32| {
+        json!({
+            "name": "db-1",
+            "node_id": NODE_ID,
+            "vcpu": 2,
+            "memory_mb": 4096,
+            "cpu_pinning": { "0": 2, "1": pcpu },
+            "numa": { "host_nodes": [0], "mode": "strict" }
+        })
+    };
+
+    // 节点未上报 NUMA 拓扑时无法校验
+    let (status, _) = env.request(Method::POST, "/api/vms", Some(body(3))).await;
+    assert!(!status.is_success());
+
+    let node = node::Entity::find_by_id(NODE_ID.to_string())
+        .one(&env.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut node_active: node::ActiveModel = node.into();
+    node_active.numa_topology = Set(Some(json!([{ "id": 0, "cpus": [0, 1, 2, 3], "memory_mb": 16384 }])));
+    node_active.update(&env.db).await.unwrap();
+
+    let (status, _) = env.request(Method::POST, "/api/vms", Some(body(8))).await;
+    assert!(!status.is_success());
+    assert!(vm::Entity::find().all(&env.db).await.unwrap().is_empty());
+
+    let (status, body) = env.request(Method::POST, "/api/vms", Some(body(3))).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    // 减少 vCPU 后绑定的 vCPU 1 不再存在
+    let (status, _) = env
+        .request(Method::PUT, &format!("/api/vms/{}", vm_id), Some(json!({ "vcpu": 1 })))
+        .await;
+    assert!(!status.is_success());
+
+    env.request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    let start = env.agent.notifications().pop().unwrap();
+    assert_eq!(start.payload["cpu_pinning"], json!({ "0": 2, "1": 3 }));
+    assert_eq!(start.payload["numa"]["host_nodes"], json!([0]));
+}
+
+#[tokio::test]
+async fn test_domain_xml_override_and_inspection() {
+    let env = TestEnv::new().await;
+    let body = json!({
+        "name": "web-1",
+        "node_id": NODE_ID,
+        "vcpu": 1,
+        "memory_mb": 1024,
+        "raw_xml_override": "<domain type='kvm'><name>web-1</name></domain>"
+    });
+
+    // 自定义 XML 需要 vm:xml 权限
+    let (status, _) = env.request(Method::POST, "/api/vms", Some(body)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert!(body["raw_xml_override"].is_null());
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    let (status, _) = env
+        .request(
+            Method::PUT,
+            &format!("/api/vms/{}", vm_id),
+            Some(json!({ "raw_xml_override": "<domain/>" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let xml = format!("<domain type='kvm'><name>web-1</name><uuid>{}</uuid></domain>", vm_id);
+    env.agent.push("get_domain_xml", Ok(json!({ "vm_id": vm_id, "xml": xml })));
+    let (status, body) = env
+        .request(Method::GET, &format!("/api/vms/{}/domain-xml", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["xml"], xml);
+    assert_eq!(env.agent.calls().last().unwrap().payload["vm_id"], vm_id);
+}
+
+#[tokio::test]
+async fn test_guest_network_reports_missing_guest_agent() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+    let uri = format!("/api/vms/{}/guest-network", vm_id);
+
+    let (status, _) = env.request(Method::GET, &uri, None).await;
+    assert!(!status.is_success());
+
+    env.request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    env.complete(&vm_id, "start_vm").await;
+
+    env.agent.push(
+        "get_guest_network",
+        Err(common::ws_rpc::RpcError::new(
+            common::ws_rpc::RpcErrorCode::GuestAgentUnavailable,
+            "guest agent 未连接",
+        )),
+    );
+    let (status, body) = env.request(Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["guest_agent_available"], false);
+    assert_eq!(body["interfaces"], json!([]));
+
+    env.agent.push(
+        "get_guest_network",
+        Ok(json!({
+            "vm_id": vm_id,
+            "interfaces": [{
+                "name": "eth0",
+                "mac_address": "52:54:00:12:34:56",
+                "ipv4_addresses": ["10.0.0.5/24"],
+                "ipv6_addresses": []
+            }]
+        })),
+    );
+    let (status, body) = env.request(Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["guest_agent_available"], true);
+    assert_eq!(body["interfaces"][0]["ipv4_addresses"][0], "10.0.0.5/24");
+}
+
+#[tokio::test]
+async fn test_async_operations_tracked_as_tasks() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    let (status, body) = env
+        .request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let task_id = body["task_id"].as_str().unwrap().to_string();
+
+    // 任务 ID 随启动通知下发给 Agent
+    let notification = env.agent.notifications().pop().unwrap();
+    assert_eq!(notification.payload["task_id"], task_id.as_str());
+
+    let (_, task) = env.request(Method::GET, &format!("/api/tasks/{}", task_id), None).await;
+    assert_eq!(task["task_type"], "start_vm");
+    assert_eq!(task["status"], "running");
+    assert_eq!(task["target_id"], vm_id.as_str());
+
+    VmService::new(env.state.clone())
+        .handle_vm_operation_completed(&vm_id, "start_vm", true, "", None, Some(&task_id))
+        .await
+        .unwrap();
+    let (_, task) = env.request(Method::GET, &format!("/api/tasks/{}", task_id), None).await;
+    assert_eq!(task["status"], "completed");
+    assert_eq!(task["progress"], 100);
+    assert!(!task["completed_at"].is_null());
+
+    // 旧版本 Agent 不带回 task_id 时按虚拟机与操作类型匹配进行中的任务
+    let (_, body) = env
+        .request(Method::POST, &format!("/api/vms/{}/stop", vm_id), Some(json!({ "force": false })))
+        .await;
+    let stop_task_id = body["task_id"].as_str().unwrap().to_string();
+    VmService::new(env.state.clone())
+        .handle_vm_operation_completed(&vm_id, "stop_vm", false, "shutdown timed out", None, None)
+        .await
+        .unwrap();
+    let (_, task) = env.request(Method::GET, &format!("/api/tasks/{}", stop_task_id), None).await;
+    assert_eq!(task["status"], "failed");
+    assert_eq!(task["error_message"], "shutdown timed out");
+
+    let (status, body) = env
+        .request(Method::GET, &format!("/api/tasks?target_id={}", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["total"], 2);
+    assert_eq!(body["tasks"][0]["id"], stop_task_id.as_str());
+
+    let (status, _) = env.request(Method::GET, "/api/tasks/missing", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_agent_completion_notification_updates_vm_and_task() {
+    let env = TestEnv::new().await;
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    let (_, body) = env
+        .request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    let start_task_id = body["task_id"].as_str().unwrap().to_string();
+
+    // 旧版本 Agent 的完成通知不带 task_id，虚拟机状态仍需更新
+    env.deliver(
+        "vm_operation_completed",
+        json!({
+            "vm_id": vm_id,
+            "operation": "start_vm",
+            "success": true,
+            "message": "虚拟机启动成功",
+            "ip_conflicts": [],
+            "vnc": { "port": 5901, "listen": "0.0.0.0" }
+        }),
+    )
+    .await;
+    let vm = env.vm(&vm_id).await.unwrap();
+    assert_eq!(vm.status, "running");
+    assert_eq!(vm.vnc_port, Some(5901));
+    let (_, task) = env
+        .request(Method::GET, &format!("/api/tasks/{}", start_task_id), None)
+        .await;
+    assert_eq!(task["status"], "completed");
+
+    let (_, body) = env
+        .request(Method::POST, &format!("/api/vms/{}/stop", vm_id), Some(json!({ "force": false })))
+        .await;
+    let stop_task_id = body["task_id"].as_str().unwrap().to_string();
+
+    env.deliver(
+        "vm_operation_completed",
+        json!({
+            "vm_id": vm_id,
+            "operation": "stop_vm",
+            "task_id": stop_task_id,
+            "success": true,
+            "message": "虚拟机停止成功"
+        }),
+    )
+    .await;
+    assert_eq!(env.vm(&vm_id).await.unwrap().status, "stopped");
+    let (_, task) = env
+        .request(Method::GET, &format!("/api/tasks/{}", stop_task_id), None)
+        .await;
+    assert_eq!(task["status"], "completed");
+}
+
+#[tokio::test]
+async fn test_create_vm_without_node_is_placed_by_capacity() {
+    let env = TestEnv::new().await;
+
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "vcpu": 4, "memory_mb": 6144 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["node_id"], NODE_ID);
+
+    // 未启动的虚拟机同样占用容量：节点共 8192 MB，已分配 6144 MB；vCPU 按 4 倍超分共 128
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-2", "vcpu": 1, "memory_mb": 4096 })),
+        )
+        .await;
+    assert!(!status.is_success());
+    assert!(
+        body["message"].as_str().unwrap().contains("剩余 124 vCPU、2048 MB 内存"),
+        "{}",
+        body
+    );
+
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-3", "vcpu": 1, "memory_mb": 2048 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["node_id"], NODE_ID);
+}
+
+#[tokio::test]
+async fn test_overcommit_limits_create_and_start() {
+    let env = TestEnv::new().await;
+
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "db-1", "node_id": NODE_ID, "vcpu": 100, "memory_mb": 4096 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    // 32 线程按 4 倍超分共 128 vCPU，已分配 100
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "db-2", "node_id": NODE_ID, "vcpu": 29, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+    assert_eq!(body["details"]["node_id"], NODE_ID);
+    assert_eq!(body["details"]["requested_vcpu"], 29);
+    assert_eq!(body["details"]["vcpu_allocated"], 100);
+    assert_eq!(body["details"]["vcpu_capacity"], 128);
+    assert_eq!(body["details"]["memory_allocated_mb"], 4096);
+    assert_eq!(body["details"]["memory_capacity_mb"], 8192);
+    assert_eq!(vm::Entity::find().all(&env.db).await.unwrap().len(), 1);
+
+    // 节点内存缩小后，启动时按当前容量重新校验（虚拟机自身不计入已分配量）
+    let node = node::Entity::find_by_id(NODE_ID.to_string())
+        .one(&env.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut node_active: node::ActiveModel = node.into();
+    node_active.memory_total = Set(Some(2 * 1024 * 1024 * 1024));
+    node_active.update(&env.db).await.unwrap();
+
+    let (status, body) = env
+        .request(Method::POST, &format!("/api/vms/{}/start", vm_id), None)
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+    assert_eq!(body["details"]["requested_memory_mb"], 4096);
+    assert_eq!(body["details"]["memory_allocated_mb"], 0);
+    assert_eq!(body["details"]["memory_capacity_mb"], 2048);
+    assert!(env.agent.notifications().is_empty());
+    assert_eq!(env.vm(&vm_id).await.unwrap().status, "stopped");
+}
+
+#[tokio::test]
+async fn test_live_migration_updates_node_after_completion() {
+    let env = TestEnv::new().await;
+
+    let source = node::Entity::find_by_id(NODE_ID.to_string())
+        .one(&env.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut target: node::ActiveModel = source.into();
+    target.id = Set("node-2".to_string());
+    target.hostname = Set("compute-2".to_string());
+    target.ip_address = Set("10.0.0.12".to_string());
+    target.insert(&env.db).await.unwrap();
+
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 1024 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    let mut vm_active: vm::ActiveModel = env.vm(&vm_id).await.unwrap().into();
+    vm_active.status = Set("running".to_string());
+    vm_active.update(&env.db).await.unwrap();
+
+    env.agent
+        .push("migrate_vm", Ok(json!({ "success": true, "message": "" })));
+    let (status, body) = env
+        .request(
+            Method::POST,
+            &format!("/api/vms/{}/migrate", vm_id),
+            Some(json!({ "target_node_id": "node-2", "live": true })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    // 迁移请求发给源节点，目标节点的 libvirt URI 由节点地址生成
+    let calls = env.agent.calls();
+    assert_eq!(calls[0].method, "migrate_vm");
+    assert_eq!(calls[0].node_id, NODE_ID);
+    assert_eq!(calls[0].payload["target_uri"], "qemu+ssh://10.0.0.12/system");
+
+    let (sender, mut frontend) = tokio::sync::mpsc::unbounded_channel();
+    env.state
+        .frontend_manager()
+        .register("frontend-1".to_string(), None, sender)
+        .await;
+
+    // 进行中的进度只推送给前端，虚拟机仍记录在源节点
+    env.deliver(
+        "vm_migration_progress",
+        json!({
+            "vm_id": vm_id,
+            "stage": "migrating",
+            "progress_percent": 42.0,
+            "message": "正在执行热迁移，已传输 42%",
+            "completed": false,
+            "remaining_secs": 8
+        }),
+    )
+    .await;
+    match frontend.try_recv().unwrap() {
+        crate::ws::FrontendMessage::MigrationProgress {
+            vm_id: id,
+            progress_percent,
+            remaining_secs,
+            completed,
+            ..
+        } => {
+            assert_eq!(id, vm_id);
+            assert_eq!(progress_percent, 42.0);
+            assert_eq!(remaining_secs, Some(8));
+            assert!(!completed);
+        }
+        other => panic!("unexpected frontend message: {:?}", other),
+    }
+    let vm = env.vm(&vm_id).await.unwrap();
+    assert_eq!(vm.status, "migrating");
+    assert_eq!(vm.node_id.as_deref(), Some(NODE_ID));
+
+    env.deliver(
+        "vm_migration_progress",
+        json!({
+            "vm_id": vm_id,
+            "stage": "completed",
+            "progress_percent": 100.0,
+            "message": "虚拟机热迁移完成",
+            "completed": true,
+            "migration_mode": "live"
+        }),
+    )
+    .await;
+    let vm = env.vm(&vm_id).await.unwrap();
+    assert_eq!(vm.status, "running");
+    assert_eq!(vm.node_id.as_deref(), Some("node-2"));
+}
+
+#[tokio::test]
+async fn test_copy_storage_migration_prepares_and_cleans_target_volumes() {
+    let env = TestEnv::new().await;
+    let now = Utc::now();
+
+    let source = node::Entity::find_by_id(NODE_ID.to_string())
+        .one(&env.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut target: node::ActiveModel = source.into();
+    target.id = Set("node-2".to_string());
+    target.hostname = Set("compute-2".to_string());
+    target.ip_address = Set("10.0.0.12".to_string());
+    target.insert(&env.db).await.unwrap();
+
+    storage_pool::ActiveModel {
+        id: Set("pool-lvm".to_string()),
+        name: Set("local-lvm".to_string()),
+        pool_type: Set("lvm".to_string()),
+        status: Set("active".to_string()),
+        config: Set(json!({ "vg_name": "vg0" })),
+        capacity_gb: Set(Some(500)),
+        allocated_gb: Set(Some(10)),
+        available_gb: Set(Some(490)),
+        used_gb: Set(None),
+        node_id: Set(Some(NODE_ID.to_string())),
+        metadata: Set(None),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+    }
+    .insert(&env.db)
+    .await
+    .unwrap();
+    volume::ActiveModel {
+        id: Set("vol-lvm".to_string()),
+        name: Set("data".to_string()),
+        volume_type: Set("raw".to_string()),
+        size_gb: Set(10),
+        pool_id: Set("pool-lvm".to_string()),
+        path: Set(Some("/dev/vg0/vol-lvm".to_string())),
+        status: Set("available".to_string()),
+        vm_id: Set(None),
+        backing_volume_id: Set(None),
+        encryption: Set(None),
+        metadata: Set(None),
+        owner_id: Set(None),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+    }
+    .insert(&env.db)
+    .await
+    .unwrap();
+
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms",
+            Some(json!({
+                "name": "db-1",
+                "node_id": NODE_ID,
+                "vcpu": 1,
+                "memory_mb": 1024,
+                "disks": [{ "volume_id": "vol-lvm", "bus_type": "virtio", "device_type": "disk" }]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    let mut vm_active: vm::ActiveModel = env.vm(&vm_id).await.unwrap().into();
+    vm_active.status = Set("running".to_string());
+    vm_active.update(&env.db).await.unwrap();
+
+    // 复制存储只适用于热迁移
+    let (status, _) = env
+        .request(
+            Method::POST,
+            &format!("/api/vms/{}/migrate", vm_id),
+            Some(json!({ "target_node_id": "node-2", "storage_mode": "copy_storage" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    env.agent.push(
+        "create_volume",
+        Ok(json!({ "success": true, "message": "", "path": "/dev/vg0/vol-lvm" })),
+    );
+    env.agent
+        .push("migrate_vm", Ok(json!({ "success": true, "message": "" })));
+    let (status, body) = env
+        .request(
+            Method::POST,
+            &format!("/api/vms/{}/migrate", vm_id),
+            Some(json!({ "target_node_id": "node-2", "live": true, "storage_mode": "copy_storage" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    // 先在目标节点创建同尺寸空白卷，再由源节点发起迁移
+    let calls = env.agent.calls();
+    assert_eq!(calls[0].method, "create_volume");
+    assert_eq!(calls[0].node_id, "node-2");
+    assert_eq!(calls[0].payload["volume_id"], "vol-lvm");
+    assert_eq!(calls[0].payload["size_gb"], 10);
+    assert_eq!(calls[0].payload["format"], "raw");
+    assert_eq!(calls[1].method, "migrate_vm");
+    assert_eq!(calls[1].node_id, NODE_ID);
+    assert_eq!(calls[1].payload["storage_mode"], "copy_storage");
+
+    // 迁移失败时删除目标节点上的副本，虚拟机留在源节点
+    env.agent
+        .push("delete_volume", Ok(json!({ "success": true, "message": "" })));
+    env.deliver(
+        "vm_migration_progress",
+        json!({
+            "vm_id": vm_id,
+            "stage": "failed",
+            "progress_percent": 0.0,
+            "message": "热迁移失败",
+            "completed": true,
+            "error": "connection reset"
+        }),
+    )
+    .await;
+    let calls = env.agent.calls();
+    assert_eq!(calls[2].method, "delete_volume");
+    assert_eq!(calls[2].node_id, "node-2");
+    assert_eq!(calls[2].payload["volume_id"], "vol-lvm");
+
+    let vm = env.vm(&vm_id).await.unwrap();
+    assert_eq!(vm.status, "running");
+    assert_eq!(vm.node_id.as_deref(), Some(NODE_ID));
+    assert!(vm.metadata.unwrap().get("migration_storage_mode").is_none());
+}
+
+#[tokio::test]
+async fn test_agent_registration_requires_shared_secret() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let env = TestEnv::new().await;
+    let state = env.state.clone().with_agent_auth(AgentAuthTokens {
+        global: Some("cluster-secret".to_string()),
+        per_node: Default::default(),
+    });
+    let app = Router::new()
+        .route("/ws/agent", axum::routing::get(crate::ws::handle_agent_websocket))
+        .with_state(state);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let register = |node_id: &str, token: Option<&str>| {
+        let request = RegisterRequest {
+            node_id: node_id.to_string(),
+            hostname: "compute-9".to_string(),
+            ip_address: "10.0.0.19".to_string(),
+            codec: Default::default(),
+            compression: false,
+            token: token.map(str::to_string),
+        };
+        let msg = RpcMessage::request("register", serde_json::to_value(&request).unwrap());
+        Message::Text(msg.to_json().unwrap())
+    };
+
+    // 密钥错误：收到策略违规的关闭帧，不创建节点记录
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws/agent", addr))
+        .await
+        .unwrap();
+    ws.send(register("node-9", Some("wrong-secret"))).await.unwrap();
+    match ws.next().await {
+        Some(Ok(Message::Close(Some(frame)))) => assert_eq!(frame.code, CloseCode::Policy),
+        other => panic!("期望关闭帧，收到: {:?}", other),
+    }
+    assert!(node::Entity::find_by_id("node-9".to_string())
+        .one(&env.db)
+        .await
+        .unwrap()
+        .is_none());
+
+    // 密钥正确：注册成功
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws/agent", addr))
+        .await
+        .unwrap();
+    ws.send(register("node-9", Some("cluster-secret"))).await.unwrap();
+    let response = match ws.next().await {
+        Some(Ok(Message::Text(text))) => RpcMessage::from_json(&text).unwrap(),
+        other => panic!("期望注册响应，收到: {:?}", other),
+    };
+    let response: RegisterResponse = serde_json::from_value(response.payload.unwrap()).unwrap();
+    assert!(response.success);
+}
+
+#[tokio::test]
+async fn test_ping_echoes_timestamp_and_records_heartbeat() {
+    let env = TestEnv::new().await;
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let connection = env
+        .state
+        .agent_manager()
+        .register(
+            NODE_ID.to_string(),
+            "compute-1".to_string(),
+            "10.0.0.11".to_string(),
+            sender,
+        )
+        .await;
+
+    let ping = RpcMessage::request(
+        "ping",
+        json!({ "timestamp_ms": 1_700_000_000_123i64, "hypervisor_connected": false, "last_rtt_ms": 42 }),
+    );
+    let ping_id = ping.id.clone();
+    crate::ws::handler::handle_agent_request(ping, &connection, &env.state)
+        .await
+        .unwrap();
+
+    // 响应与请求 id 相同并原样回显时间戳
+    let pong = receiver.try_recv().unwrap();
+    assert_eq!(pong.id, ping_id);
+    assert!(pong.is_success());
+    assert_eq!(pong.payload.unwrap()["timestamp_ms"], 1_700_000_000_123i64);
+
+    assert_eq!(connection.rtt_ms().await, Some(42));
+    let node = node::Entity::find_by_id(NODE_ID.to_string())
+        .one(&env.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(node.status, "error");
+}
+
+#[tokio::test]
+async fn test_snapshot_policy_creates_and_prunes_snapshots() {
+    use crate::services::snapshot_policy_service::SnapshotPolicyService;
+
+    let env = TestEnv::new().await;
+
+    let (status, _) = env
+        .request(
+            Method::POST,
+            "/api/snapshot-policies",
+            Some(json!({ "name": "hourly", "target_type": "volume", "target_id": VOLUME_ID, "schedule": "0 25 * * *", "keep_last": 2 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, policy) = env
+        .request(
+            Method::POST,
+            "/api/snapshot-policies",
+            Some(json!({ "name": "hourly", "target_type": "volume", "target_id": VOLUME_ID, "schedule": "0 * * * *", "keep_last": 2 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", policy);
+    assert!(policy["next_run_at"].is_string());
+    let policy_id = policy["id"].as_str().unwrap().to_string();
+
+    // 策略之前创建的快照：最旧的可被清理，受保护的与手动创建的不参与
+    let now = Utc::now();
+    for (id, hours_ago, policy_snapshot, protected) in [
+        ("snap-oldest", 5, true, false),
+        ("snap-protected", 4, true, true),
+        ("snap-manual", 3, false, false),
+        ("snap-recent", 2, true, false),
+    ] {
+        let created_at = now - chrono::Duration::hours(hours_ago);
+        snapshot::ActiveModel {
+            id: Set(id.to_string()),
+            name: Set(id.to_string()),
+            volume_id: Set(VOLUME_ID.to_string()),
+            status: Set("available".to_string()),
+            size_gb: Set(Some(20)),
+            snapshot_tag: Set(Some(id.to_string())),
+            description: Set(None),
+            protected: Set(protected),
+            metadata: Set(policy_snapshot.then(|| json!({ "policy_id": policy_id }))),
+            created_at: Set(created_at.into()),
+            updated_at: Set(created_at.into()),
+        }
+        .insert(&env.db)
+        .await
+        .unwrap();
+    }
+
+    let service = SnapshotPolicyService::new(env.state.clone());
+    let run_at = now + chrono::Duration::hours(2);
+    assert_eq!(service.run_due_policies(run_at).await.unwrap(), 1);
+
+    let notifications = env.agent.notifications();
+    let created: Vec<_> = notifications
+        .iter()
+        .filter(|n| n.method == "create_snapshot_async")
+        .collect();
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].payload["volume_id"], VOLUME_ID);
+    let deleted: Vec<_> = notifications
+        .iter()
+        .filter(|n| n.method == "delete_snapshot_async")
+        .map(|n| n.payload["snapshot_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(deleted, ["snap-oldest"]);
+
+    let (_, policy) = env
+        .request(Method::GET, &format!("/api/snapshot-policies/{}", policy_id), None)
+        .await;
+    assert_eq!(policy["last_status"], "success");
+
+    // 执行时间已持久化，同一时刻不会重复执行
+    assert_eq!(service.run_due_policies(run_at).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_snapshot_policy_scheduler_pauses_in_read_only_mode() {
+    use crate::services::snapshot_policy_service::SnapshotPolicyService;
+
+    let env = TestEnv::new().await;
+    let (status, policy) = env
+        .request(
+            Method::POST,
+            "/api/snapshot-policies",
+            Some(json!({ "name": "hourly", "target_type": "volume", "target_id": VOLUME_ID, "schedule": "0 * * * *", "keep_last": 2 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", policy);
+
+    let service = SnapshotPolicyService::new(env.state.clone());
+    let run_at = Utc::now() + chrono::Duration::hours(2);
+
+    // 维护只读期间到期的策略不执行，也不记录执行时间
+    env.state.set_read_only(true);
+    assert_eq!(service.run_scheduled_tick(run_at).await.unwrap(), 0);
+    assert!(env.agent.notifications().is_empty());
+    assert!(snapshot::Entity::find().all(&env.db).await.unwrap().is_empty());
+    let stored = snapshot_policy::Entity::find().one(&env.db).await.unwrap().unwrap();
+    assert!(stored.last_run_at.is_none());
+
+    env.state.set_read_only(false);
+    assert_eq!(service.run_scheduled_tick(run_at).await.unwrap(), 1);
+    assert!(env.agent.notifications().iter().any(|n| n.method == "create_snapshot_async"));
+}
+
+#[tokio::test]
+async fn test_batch_operation_reports_per_vm_results() {
+    let env = TestEnv::new().await;
+
+    let mut vm_ids = Vec::new();
+    for (name, disks) in [
+        ("web-1", json!([{ "volume_id": VOLUME_ID, "bus_type": "virtio", "device_type": "disk" }])),
+        ("web-2", json!([])),
+    ] {
+        let (status, body) = env
+            .request(
+                Method::POST,
+                "/api/vms",
+                Some(json!({ "name": name, "node_id": NODE_ID, "vcpu": 1, "memory_mb": 512, "disks": disks })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        vm_ids.push(body["id"].as_str().unwrap().to_string());
+    }
+
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms/batch",
+            Some(json!({ "vm_ids": [vm_ids[0]], "action": "start" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["succeeded"], 1);
+    assert!(body["results"][0]["task_id"].is_string());
+    env.complete(&vm_ids[0], "start_vm").await;
+
+    // 运行中的虚拟机与不存在的虚拟机删除失败，不影响其余虚拟机；重复的 ID 只执行一次
+    let (status, body) = env
+        .request(
+            Method::POST,
+            "/api/vms/batch",
+            Some(json!({ "vm_ids": [vm_ids[0], vm_ids[1], "vm-missing", vm_ids[1]], "action": "delete" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["succeeded"], 1);
+    assert_eq!(body["failed"], 2);
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0]["vm_id"], vm_ids[0].as_str());
+    assert_eq!(results[0]["success"], false);
+    assert!(results[0]["error"].as_str().unwrap().contains("正在运行"));
+    assert_eq!(results[1]["success"], true);
+    assert_eq!(results[2]["success"], false);
+    assert!(env.vm(&vm_ids[0]).await.is_some());
+    assert!(env.vm(&vm_ids[1]).await.is_none());
+
+    let (status, _) = env
+        .request(Method::POST, "/api/vms/batch", Some(json!({ "vm_ids": [], "action": "stop" })))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_audit_log_records_mutations_and_agent_completions() {
+    use crate::auth::Claims;
+    use crate::db::models::audit_log::AuditLogFilter;
+    use crate::services::audit_service::AuditService;
+    use axum::middleware::{from_fn, from_fn_with_state, Next};
+
+    let env = TestEnv::new().await;
+
+    // 代替 auth_middleware：在请求和响应上附带 Claims
+    async fn fake_auth(mut request: axum::extract::Request, next: Next) -> axum::response::Response {
+        let claims = Claims { sub: 7, username: "alice".to_string(), exp: 9999999999, iat: 0 };
+        request.extensions_mut().insert(claims.clone());
+        let mut response = next.run(request).await;
+        response.extensions_mut().insert(claims);
+        response
+    }
+    let app = Router::new()
+        .nest(
+            "/api",
+            Router::new()
+                .nest("/vms", crate::api::vms::vm_routes().layer(from_fn(fake_auth)))
+                .layer(from_fn_with_state(env.state.clone(), crate::middleware::audit_middleware)),
+        )
+        .with_state(env.state.clone());
+    let send = |method: Method, uri: String, body: Value| {
+        let app = app.clone();
+        async move {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            app.oneshot(request).await.unwrap().status()
+        }
+    };
+
+    let status = send(
+        Method::POST,
+        "/api/vms".to_string(),
+        json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 1, "memory_mb": 512, "disks": [], "root_password": "hunter2" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let vm_id = vm::Entity::find().one(&env.db).await.unwrap().unwrap().id;
+    assert_eq!(send(Method::GET, "/api/vms".to_string(), Value::Null).await, StatusCode::OK);
+    assert_eq!(
+        send(Method::POST, format!("/api/vms/{}/start", vm_id), Value::Null).await,
+        StatusCode::OK
+    );
+    env.complete(&vm_id, "start_vm").await;
+    assert!(!send(Method::DELETE, format!("/api/vms/{}", vm_id), Value::Null).await.is_success());
+
+    let service = AuditService::new(env.state.clone());
+    let logs = service.list_logs(AuditLogFilter::default(), 1, 20).await.unwrap();
+    // GET 不记录；按时间倒序
+    assert_eq!(logs.total, 4);
+    let entries: Vec<_> = logs
+        .logs
+        .iter()
+        .rev()
+        .map(|l| (l.actor.as_str(), l.action.as_str(), l.success))
+        .collect();
+    assert_eq!(
+        entries,
+        [("user", "create", true), ("user", "start", true), ("system", "start_vm", true), ("user", "delete", false)]
+    );
+    let create = logs.logs.last().unwrap();
+    assert_eq!(create.user_id, Some(7));
+    assert_eq!(create.path.as_deref(), Some("/api/vms"));
+    assert!(!serde_json::to_string(&logs).unwrap().contains("hunter2"));
+    let delete = &logs.logs[0];
+    assert_eq!(delete.target_type.as_deref(), Some("vms"));
+    assert_eq!(delete.target_id.as_deref(), Some(vm_id.as_str()));
+    assert_eq!(delete.status_code, Some(500));
+
+    let filter = AuditLogFilter { user: Some("alice".to_string()), ..Default::default() };
+    assert_eq!(service.list_logs(filter, 1, 20).await.unwrap().total, 3);
+    let filter = AuditLogFilter { resource: Some("vms".to_string()), from: Some(Utc::now() + chrono::Duration::hours(1)), ..Default::default() };
+    assert_eq!(service.list_logs(filter, 1, 20).await.unwrap().total, 0);
+}
+
+#[tokio::test]
+async fn test_department_quota_limits_vm_and_volume_creation() {
+    use crate::auth::Claims;
+    use axum::middleware::{from_fn, Next};
+
+    let env = TestEnv::new().await;
+    let now = Utc::now();
+
+    async fn fake_auth(mut request: axum::extract::Request, next: Next) -> axum::response::Response {
+        let claims = Claims { sub: 7, username: "alice".to_string(), exp: 9999999999, iat: 0 };
+        request.extensions_mut().insert(claims);
+        next.run(request).await
+    }
+    let app = Router::new()
+        .nest("/api/vms", crate::api::vms::vm_routes())
+        .nest("/api/storage", crate::api::storage::routes())
+        .nest("/api/departments", crate::api::department::department_routes())
+        .layer(from_fn(fake_auth))
+        .with_state(env.state.clone());
+    let send = |method: Method, uri: String, body: Option<Value>| {
+        let app = app.clone();
+        async move {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<Value>(&bytes).unwrap_or(Value::Null))
+        }
+    };
+
+    user::ActiveModel {
+        id: Set(7),
+        username: Set("alice".to_string()),
+        email: Set("alice@example.com".to_string()),
+        password_hash: Set(String::new()),
+        is_active: Set(true),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+    }
+    .insert(&env.db)
+    .await
+    .unwrap();
+    let dept = department::ActiveModel {
+        name: Set("研发部".to_string()),
+        code: Set("RD".to_string()),
+        parent_id: Set(None),
+        level: Set(1),
+        sort_order: Set(0),
+        description: Set(None),
+        manager_id: Set(None),
+        is_active: Set(true),
+        max_vcpu: Set(None),
+        max_memory_mb: Set(None),
+        max_volumes: Set(None),
+        max_volume_gb: Set(None),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+        ..Default::default()
+    }
+    .insert(&env.db)
+    .await
+    .unwrap();
+    user_department::ActiveModel {
+        user_id: Set(7),
+        department_id: Set(dept.id),
+        position: Set(None),
+        is_primary: Set(true),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+        ..Default::default()
+    }
+    .insert(&env.db)
+    .await
+    .unwrap();
+    // 已有的存储卷归属 alice，计入部门用量
+    let mut root: volume::ActiveModel = env.volume().await.into();
+    root.owner_id = Set(Some(7));
+    root.update(&env.db).await.unwrap();
+
+    let (status, _) = send(
+        Method::PUT,
+        format!("/api/departments/{}/quota", dept.id),
+        Some(json!({ "max_vcpu": 4, "max_memory_mb": 4096, "max_volumes": 2, "max_volume_gb": 30 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let vm = |vcpu: u32| json!({ "name": "web", "node_id": NODE_ID, "vcpu": vcpu, "memory_mb": 2048, "disks": [] });
+    let (status, body) = send(Method::POST, "/api/vms".to_string(), Some(vm(2))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["owner_id"], 7);
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    let (status, body) = send(Method::POST, "/api/vms".to_string(), Some(vm(4))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["details"]["resource"], "vcpu");
+    assert_eq!(body["details"]["current"], 2);
+    assert_eq!(body["details"]["requested"], 4);
+    assert_eq!(body["details"]["limit"], 4);
+
+    // 修改配置只按增加的部分计入配额，不能借此绕过限制
+    let (status, body) = send(Method::PUT, format!("/api/vms/{}", vm_id), Some(json!({ "vcpu": 6 }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["details"]["resource"], "vcpu");
+    assert_eq!(body["details"]["requested"], 4);
+    let (status, body) = send(Method::PUT, format!("/api/vms/{}", vm_id), Some(json!({ "memory_mb": 8192 }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["details"]["resource"], "memory_mb");
+    let (status, body) = send(Method::PUT, format!("/api/vms/{}", vm_id), Some(json!({ "memory_mb": 4096 }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, _) = send(Method::PUT, format!("/api/vms/{}", vm_id), Some(json!({ "memory_mb": 2048 }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(
+        Method::POST,
+        format!("/api/storage/volumes/{}/resize", VOLUME_ID),
+        Some(json!({ "new_size_gb": 40 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["details"]["resource"], "volume_gb");
+    assert!(env.agent.calls().iter().all(|c| c.method != "resize_volume"));
+
+    let (status, body) = send(
+        Method::POST,
+        "/api/storage/volumes".to_string(),
+        Some(json!({ "name": "data", "pool_id": POOL_ID, "size_gb": 20, "volume_type": "qcow2" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["details"]["resource"], "volume_gb");
+    assert_eq!(body["details"]["current"], 20);
+    assert!(env.agent.calls().iter().all(|c| c.method != "create_volume"));
+
+    let (status, body) = send(Method::GET, format!("/api/departments/{}/usage", dept.id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let usage = &body["data"];
+    assert_eq!(usage["vcpu"], json!({ "used": 2, "limit": 4 }));
+    assert_eq!(usage["memory_mb"], json!({ "used": 2048, "limit": 4096 }));
+    assert_eq!(usage["volumes"], json!({ "used": 1, "limit": 2 }));
+    assert_eq!(usage["volume_gb"], json!({ "used": 20, "limit": 30 }));
+}
+
+#[tokio::test]
+async fn test_create_vm_from_template() {
+    let env = TestEnv::new().await;
+    env.agent.push(
+        "create_volume",
+        Ok(json!({ "success": true, "message": "ok", "path": "/mnt/nfs/web-1-root.qcow2" })),
+    );
+
+    let (status, template) = env
+        .request(
+            Method::POST,
+            "/api/vm-templates",
+            Some(json!({
+                "name": "ubuntu-small",
+                "base_volume_id": VOLUME_ID,
+                "spec": {
+                    "vcpu": 2,
+                    "memory_mb": 2048,
+                    "root_disk_size_gb": 30,
+                    "networks": [{ "network_id": NETWORK_ID, "model": "virtio" }],
+                    "metadata": { "role": "web" }
+                }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let template_id = template["id"].as_str().unwrap().to_string();
+
+    // 调用方覆盖名称与 vCPU，其余使用模板中的值
+    let (status, vm) = env
+        .request(
+            Method::POST,
+            &format!("/api/vms/from-template/{}", template_id),
+            Some(json!({ "name": "web-1", "node_id": NODE_ID, "vcpu": 4 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", vm);
+    assert_eq!(vm["vcpu"], 4);
+    assert_eq!(vm["memory_mb"], 2048);
+    assert_eq!(vm["metadata"]["role"], "web");
+    assert_eq!(vm["metadata"]["template_id"], template_id.as_str());
+    assert!(vm["network_interfaces"][0]["ip_address"].is_string());
+
+    let create = env.agent.calls().into_iter().find(|c| c.method == "create_volume").unwrap();
+    assert_eq!(create.payload["backing_volume_id"], VOLUME_ID);
+    assert_eq!(create.payload["size_gb"], 30);
+    let disk = &vm["volumes"][0];
+    assert_eq!(disk["boot_order"], 1);
+    assert_eq!(disk["ephemeral"], true);
+    let root = volume::Entity::find_by_id(disk["volume_id"].as_str().unwrap().to_string())
+        .one(&env.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(root.vm_id.as_deref(), vm["id"].as_str());
+    assert_eq!(root.backing_volume_id.as_deref(), Some(VOLUME_ID));
+
+    let (status, _) = env
+        .request(Method::POST, "/api/vms/from-template/missing", Some(json!({ "name": "web-2" })))
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // 基础镜像被删除后不再创建系统盘
+    let mut active: vm_template::ActiveModel = vm_template::Entity::find_by_id(template_id.clone())
+        .one(&env.db)
+        .await
+        .unwrap()
+        .unwrap()
+        .into();
+    active.base_volume_id = Set("vol-gone".to_string());
+    active.update(&env.db).await.unwrap();
+    let (status, body) = env
+        .request(
+            Method::POST,
+            &format!("/api/vms/from-template/{}", template_id),
+            Some(json!({ "name": "web-2" })),
+        )
+        .await;
+    assert!(!status.is_success());
+    assert!(body["message"].as_str().unwrap().contains("vol-gone"));
+    assert_eq!(env.agent.calls().iter().filter(|c| c.method == "create_volume").count(), 1);
+}
@@ -13,7 +13,11 @@ mod services;
 mod utils;
 mod ws;
 
+#[cfg(test)]
+mod lifecycle_tests;
+
 use axum::{
+    middleware::from_fn_with_state,
     routing::get,
     Router,
 };
@@ -49,10 +53,11 @@ async fn main() -> anyhow::Result<()> {
     
     // 加载配置
     let cfg = config::Config::from_env()?;
+    cfg.validate()?;
     info!("✅ 配置加载成功");
 
     // 建立数据库连接 (SeaORM) - 用于用户、角色、权限等管理
-    let sea_db = establish_connection(&cfg.database_url)
+    let sea_db = establish_connection(&cfg.database_url, cfg.db_options)
         .await
         .expect("SeaORM 数据库连接失败");
     info!("✅ SeaORM 数据库连接成功");
@@ -61,13 +66,63 @@ async fn main() -> anyhow::Result<()> {
     let agent_manager = AgentConnectionManager::new();
     info!("✅ Agent 连接管理器初始化成功");
 
+    // Bridge 命名规则（与 Agent 共用 common 中的实现）
+    let bridge_naming = common::utils::BridgeNaming::new(cfg.bridge_name_prefix.clone())
+        .map_err(|e| anyhow::anyhow!("Bridge 命名配置无效: {}", e))?;
+
     // 创建应用状态
-    let app_state = AppState::new(sea_db, agent_manager.clone());
+    let app_state = AppState::new(
+        sea_db,
+        agent_manager.clone(),
+        bridge_naming,
+        cfg.read_only_mode,
+        cfg.node_alert_thresholds,
+        cfg.default_disk_bus.clone(),
+    )
+    .with_safety_snapshot(cfg.safety_snapshot)
+    .with_webhook_settings(cfg.webhook)
+    .with_placement_strategy(cfg.placement_strategy)
+    .with_overcommit_ratios(cfg.overcommit)
+    .with_rpc_compress_threshold(cfg.rpc_compress_threshold)
+    .with_agent_auth(cfg.agent_auth)
+    .with_s3_settings(cfg.s3)
+    .with_secret_key(&cfg.secret_encryption_key);
+    if cfg.read_only_mode {
+        info!("⚠️ 服务以只读维护模式启动，所有写操作将被拒绝");
+    }
 
     // 启动心跳监控（3分钟超时，每30秒检查一次）
     agent_manager.start_heartbeat_monitor_with_db_update(180, 30, app_state.clone());
     info!("✅ 心跳监控任务已启动（3分钟超时检测）");
 
+    // 每分钟按 libvirt 实际状态对账一次虚拟机状态
+    services::vm_service::VmService::start_vm_state_reconciler(app_state.clone(), 60);
+    info!("✅ 虚拟机状态对账任务已启动");
+
+    // 每 5 分钟向 Agent 查询一次存储池底层存储的实际容量
+    services::storage_service::StorageService::start_pool_usage_refresher(app_state.clone(), 300);
+
+    // 每小时清理一次超过 24 小时的节点指标历史
+    services::node_metrics_service::NodeMetricsService::start_node_metrics_pruner(app_state.clone(), 3600);
+
+    // 启用安全快照时每小时清理一次过期的安全快照
+    if cfg.safety_snapshot.enabled {
+        services::snapshot_service::SnapshotService::start_safety_snapshot_pruner(
+            app_state.clone(),
+            3600,
+        );
+        info!(
+            "✅ 安全快照已启用（保留 {} 小时）",
+            cfg.safety_snapshot.retention_hours
+        );
+    }
+
+    // 每分钟检查一次到期的快照策略
+    services::snapshot_policy_service::SnapshotPolicyService::start_snapshot_policy_scheduler(
+        app_state.clone(),
+        60,
+    );
+
     // 设置CORS
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -80,7 +135,12 @@ async fn main() -> anyhow::Result<()> {
         .route("/health", get(health_handler))
         .route("/ws/agent", get(ws::handle_agent_websocket))
         .route("/ws/frontend", get(ws::handle_frontend_websocket))
-        .nest("/api", api::api_routes())
+        .route("/ws/vnc/:vm_id", get(ws::handle_vnc_websocket))
+        .nest(
+            "/api",
+            api::api_routes().layer(from_fn_with_state(app_state.clone(), middleware::audit_middleware)),
+        )
+        .layer(from_fn_with_state(app_state.clone(), middleware::read_only_middleware))
         .layer(cors)
         .layer(TraceLayer::new_for_http())
         .with_state(app_state.clone());
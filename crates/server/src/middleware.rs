@@ -1,12 +1,88 @@
 use axum::{
-    extract::Request,
-    http::{header, StatusCode},
+    extract::{OriginalUri, Request, State},
+    http::{header, Method, StatusCode},
     middleware::Next,
     response::{Response, IntoResponse},
 };
 use serde_json::json;
 
+use crate::app_state::AppState;
 use crate::auth::{AuthService, Claims};
+use crate::services::audit_service::AuditService;
+
+/// 只读维护模式下仍允许的写操作路径（登录、刷新令牌、切换维护模式）
+const READ_ONLY_EXEMPT_PATHS: &[&str] = &[
+    "/api/auth/login",
+    "/api/auth/refresh",
+    "/api/system/maintenance",
+];
+
+/// 只读维护模式中间件
+///
+/// 开启只读模式时，除 GET/HEAD/OPTIONS 和白名单路径外的请求一律返回 503，
+/// Agent 与前端 WebSocket 均通过 GET 升级，不受影响。
+pub async fn read_only_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.is_read_only() && is_mutating_request(request.method(), request.uri().path()) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            axum::response::Json(json!({
+                "error": "系统处于只读维护模式，暂不允许写操作"
+            })),
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+/// 判断请求是否为需要在只读模式下拒绝的写操作
+fn is_mutating_request(method: &Method, path: &str) -> bool {
+    let read_method = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+    !read_method && !READ_ONLY_EXEMPT_PATHS.contains(&path.trim_end_matches('/'))
+}
+
+/// 审计中间件
+///
+/// 挂在 `/api` 路由上，非 GET/HEAD/OPTIONS 请求完成后记录操作者、方法、路径、目标、User-Agent 与响应状态码。
+/// 操作者取自 auth_middleware 附加到响应上的 Claims；不读取请求体，密码等敏感字段不会落库
+pub async fn audit_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    if matches!(method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.run(request).await;
+    }
+
+    // 嵌套路由内看到的是去掉前缀的路径，审计记录完整路径
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|uri| uri.path().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|ua| ua.to_string());
+
+    let response = next.run(request).await;
+    AuditService::new(state)
+        .record_request(
+            response.extensions().get::<Claims>(),
+            &method,
+            &path,
+            user_agent.as_deref(),
+            response.status().as_u16(),
+        )
+        .await;
+    response
+}
 
 pub async fn auth_middleware(
     mut request: Request,
@@ -23,9 +99,11 @@ pub async fn auth_middleware(
         if let Ok(token) = AuthService::extract_token_from_header(auth_header) {
             // 验证JWT令牌
             if let Ok(claims) = AuthService::verify_token(&token) {
-                // 将claims添加到请求扩展中
-                request.extensions_mut().insert(claims);
-                return Ok(next.run(request).await);
+                // 将claims添加到请求扩展中，响应上也附带一份供审计中间件识别操作者
+                request.extensions_mut().insert(claims.clone());
+                let mut response = next.run(request).await;
+                response.extensions_mut().insert(claims);
+                return Ok(response);
             }
         }
     }
@@ -165,6 +243,17 @@ mod tests {
         assert!(claims.exp > claims.iat);
     }
 
+    #[test]
+    fn test_read_only_mutating_request() {
+        // 只读模式下仅拦截写操作，白名单路径放行
+        assert!(!is_mutating_request(&Method::GET, "/api/vms"));
+        assert!(!is_mutating_request(&Method::OPTIONS, "/api/vms"));
+        assert!(is_mutating_request(&Method::POST, "/api/vms"));
+        assert!(is_mutating_request(&Method::DELETE, "/api/vms/1"));
+        assert!(!is_mutating_request(&Method::POST, "/api/auth/login"));
+        assert!(!is_mutating_request(&Method::PUT, "/api/system/maintenance/"));
+    }
+
     #[test]
     fn test_status_codes() {
         // 测试HTTP状态码
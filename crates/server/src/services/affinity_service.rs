@@ -0,0 +1,185 @@
+/// 亲和组管理服务
+
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::db::models::affinity_group::{
+    ActiveModel as AffinityGroupActiveModel, AffinityGroupResponse, AffinityPolicy,
+    Column as AffinityGroupColumn, CreateAffinityGroupDto, Entity as AffinityGroupEntity,
+    Model as AffinityGroupModel,
+};
+use crate::db::models::affinity_group_member::{
+    ActiveModel as AffinityMemberActiveModel, Column as AffinityMemberColumn,
+    Entity as AffinityMemberEntity,
+};
+use crate::db::models::vm::Entity as VmEntity;
+
+pub struct AffinityGroupService {
+    state: AppState,
+}
+
+impl AffinityGroupService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// 创建亲和组
+    pub async fn create_group(&self, dto: CreateAffinityGroupDto) -> anyhow::Result<AffinityGroupResponse> {
+        let db = &self.state.sea_db();
+
+        let exists = AffinityGroupEntity::find()
+            .filter(AffinityGroupColumn::Name.eq(&dto.name))
+            .one(db)
+            .await?;
+        if exists.is_some() {
+            return Err(anyhow::anyhow!("亲和组名称已存在: {}", dto.name));
+        }
+
+        let now = Utc::now();
+        let group = AffinityGroupActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            name: Set(dto.name),
+            policy: Set(dto.policy.as_str().to_string()),
+            description: Set(dto.description),
+            created_at: Set(now.into()),
+            updated_at: Set(now.into()),
+        }
+        .insert(db)
+        .await?;
+
+        info!("创建亲和组 {} ({})", group.name, group.policy);
+
+        for vm_id in &dto.vm_ids {
+            self.add_member(&group.id, vm_id).await?;
+        }
+
+        self.to_response(group).await
+    }
+
+    /// 获取亲和组列表
+    pub async fn list_groups(&self) -> anyhow::Result<Vec<AffinityGroupResponse>> {
+        let groups = AffinityGroupEntity::find()
+            .order_by_asc(AffinityGroupColumn::Name)
+            .all(&self.state.sea_db())
+            .await?;
+
+        let mut responses = Vec::with_capacity(groups.len());
+        for group in groups {
+            responses.push(self.to_response(group).await?);
+        }
+        Ok(responses)
+    }
+
+    /// 获取亲和组详情
+    pub async fn get_group(&self, id: &str) -> anyhow::Result<Option<AffinityGroupResponse>> {
+        match AffinityGroupEntity::find_by_id(id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+        {
+            Some(group) => Ok(Some(self.to_response(group).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 删除亲和组（成员关系级联删除）
+    pub async fn delete_group(&self, id: &str) -> anyhow::Result<()> {
+        let result = AffinityGroupEntity::delete_by_id(id.to_string())
+            .exec(&self.state.sea_db())
+            .await?;
+
+        if result.rows_affected == 0 {
+            return Err(anyhow::anyhow!("亲和组不存在"));
+        }
+        Ok(())
+    }
+
+    /// 添加成员
+    ///
+    /// 反亲和组中若已有成员与该虚拟机位于同一节点则拒绝，需先迁移
+    pub async fn add_member(&self, group_id: &str, vm_id: &str) -> anyhow::Result<AffinityGroupResponse> {
+        let db = &self.state.sea_db();
+
+        let group = self.find_group(group_id).await?;
+        let vm = VmEntity::find_by_id(vm_id.to_string())
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机 {} 不存在", vm_id))?;
+
+        let member_ids = self.member_ids(group_id).await?;
+        if member_ids.iter().any(|id| id == vm_id) {
+            return self.to_response(group).await;
+        }
+
+        if AffinityPolicy::from(group.policy.clone()) == AffinityPolicy::AntiAffinity {
+            if let Some(node_id) = &vm.node_id {
+                let conflicts: Vec<String> = VmEntity::find()
+                    .filter(crate::db::models::vm::Column::Id.is_in(member_ids))
+                    .all(db)
+                    .await?
+                    .into_iter()
+                    .filter(|member| member.node_id.as_ref() == Some(node_id))
+                    .map(|member| member.name)
+                    .collect();
+
+                if !conflicts.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "虚拟机 {} 与反亲和组「{}」成员 {} 位于同一节点 {}，请先迁移",
+                        vm.name,
+                        group.name,
+                        conflicts.join(", "),
+                        node_id
+                    ));
+                }
+            }
+        }
+
+        AffinityMemberActiveModel {
+            group_id: Set(group_id.to_string()),
+            vm_id: Set(vm_id.to_string()),
+            created_at: Set(Utc::now().into()),
+        }
+        .insert(db)
+        .await?;
+
+        info!("虚拟机 {} 加入亲和组 {}", vm_id, group.name);
+        self.to_response(group).await
+    }
+
+    /// 移除成员
+    pub async fn remove_member(&self, group_id: &str, vm_id: &str) -> anyhow::Result<AffinityGroupResponse> {
+        let group = self.find_group(group_id).await?;
+
+        AffinityMemberEntity::delete_many()
+            .filter(AffinityMemberColumn::GroupId.eq(group_id))
+            .filter(AffinityMemberColumn::VmId.eq(vm_id))
+            .exec(&self.state.sea_db())
+            .await?;
+
+        self.to_response(group).await
+    }
+
+    async fn find_group(&self, id: &str) -> anyhow::Result<AffinityGroupModel> {
+        AffinityGroupEntity::find_by_id(id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("亲和组不存在"))
+    }
+
+    async fn member_ids(&self, group_id: &str) -> anyhow::Result<Vec<String>> {
+        Ok(AffinityMemberEntity::find()
+            .filter(AffinityMemberColumn::GroupId.eq(group_id))
+            .all(&self.state.sea_db())
+            .await?
+            .into_iter()
+            .map(|m| m.vm_id)
+            .collect())
+    }
+
+    async fn to_response(&self, group: AffinityGroupModel) -> anyhow::Result<AffinityGroupResponse> {
+        let vm_ids = self.member_ids(&group.id).await?;
+        Ok(AffinityGroupResponse::new(group, vm_ids))
+    }
+}
@@ -0,0 +1,221 @@
+/// 审计日志服务
+
+use anyhow::Result;
+use axum::http::Method;
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set,
+};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::auth::Claims;
+use crate::db::models::audit_log::{
+    ActiveModel as AuditLogActiveModel, AuditActor, AuditLogFilter, AuditLogListResponse,
+    AuditLogResponse, Column as AuditLogColumn, Entity as AuditLogEntity,
+};
+
+/// 写操作的目标：资源类型、资源 ID 与动作
+#[derive(Debug, PartialEq, Eq)]
+pub struct AuditTarget {
+    pub target_type: String,
+    pub target_id: Option<String>,
+    pub action: String,
+}
+
+pub struct AuditService {
+    state: AppState,
+}
+
+impl AuditService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// 记录一次 API 写操作，`path` 为不含查询参数的请求路径
+    ///
+    /// 审计写入失败只记录日志，不影响请求结果
+    pub async fn record_request(
+        &self,
+        claims: Option<&Claims>,
+        method: &Method,
+        path: &str,
+        user_agent: Option<&str>,
+        status_code: u16,
+    ) {
+        let target = audit_target(method, path);
+        let log = AuditLogActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            actor: Set(AuditActor::User.as_str().to_string()),
+            user_id: Set(claims.map(|c| c.sub)),
+            username: Set(claims.map(|c| c.username.clone())),
+            action: Set(target.action),
+            target_type: Set(Some(target.target_type)),
+            target_id: Set(target.target_id),
+            target_name: Set(None),
+            detail: Set(None),
+            ip_address: Set(None),
+            user_agent: Set(user_agent.map(|ua| ua.to_string())),
+            method: Set(Some(method.to_string())),
+            path: Set(Some(path.to_string())),
+            status_code: Set(Some(status_code as i32)),
+            success: Set(status_code < 400),
+            error_message: Set(None),
+            timestamp: Set(Utc::now().into()),
+        };
+        if let Err(e) = log.insert(&self.state.sea_db()).await {
+            warn!("写入审计日志失败: {} {}: {}", method, path, e);
+        }
+    }
+
+    /// 记录 Agent 上报触发的状态变更（actor 为 system），失败时消息写入 error_message
+    pub async fn record_system(
+        &self,
+        target_type: &str,
+        target_id: &str,
+        action: &str,
+        success: bool,
+        detail: &str,
+    ) {
+        let message = Some(detail.to_string()).filter(|d| !d.is_empty());
+        let log = AuditLogActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            actor: Set(AuditActor::System.as_str().to_string()),
+            user_id: Set(None),
+            username: Set(None),
+            action: Set(action.to_string()),
+            target_type: Set(Some(target_type.to_string())),
+            target_id: Set(Some(target_id.to_string())),
+            target_name: Set(None),
+            detail: Set(message.as_ref().map(|m| serde_json::json!({ "message": m }))),
+            ip_address: Set(None),
+            user_agent: Set(None),
+            method: Set(None),
+            path: Set(None),
+            status_code: Set(None),
+            success: Set(success),
+            error_message: Set(message.filter(|_| !success)),
+            timestamp: Set(Utc::now().into()),
+        };
+        if let Err(e) = log.insert(&self.state.sea_db()).await {
+            warn!("写入审计日志失败: {} {} {}: {}", target_type, target_id, action, e);
+        }
+    }
+
+    /// 分页查询审计日志，按时间倒序
+    pub async fn list_logs(
+        &self,
+        filter: AuditLogFilter,
+        page: usize,
+        page_size: usize,
+    ) -> Result<AuditLogListResponse> {
+        let db = &self.state.sea_db();
+        let mut query = AuditLogEntity::find();
+
+        if let Some(user) = filter.user.filter(|u| !u.is_empty()) {
+            let mut condition = Condition::any().add(AuditLogColumn::Username.eq(user.clone()));
+            if let Ok(user_id) = user.parse::<i32>() {
+                condition = condition.add(AuditLogColumn::UserId.eq(user_id));
+            }
+            query = query.filter(condition);
+        }
+        if let Some(resource) = filter.resource.filter(|r| !r.is_empty()) {
+            query = query.filter(AuditLogColumn::TargetType.eq(resource));
+        }
+        if let Some(from) = filter.from {
+            query = query.filter(AuditLogColumn::Timestamp.gte(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(AuditLogColumn::Timestamp.lte(to));
+        }
+
+        let total = query.clone().count(db).await? as usize;
+        let logs = query
+            .order_by_desc(AuditLogColumn::Timestamp)
+            .offset((page.saturating_sub(1) * page_size) as u64)
+            .limit(page_size as u64)
+            .all(db)
+            .await?;
+
+        Ok(AuditLogListResponse {
+            logs: logs.into_iter().map(AuditLogResponse::from).collect(),
+            total,
+            page,
+            page_size,
+        })
+    }
+}
+
+/// 从请求路径推断操作目标
+///
+/// 路径形如 `/api/<资源>[/<ID>][/<子操作>...]`，`/api/storage` 下以第二段为资源；
+/// 只有 UUID 或数字会被视为资源 ID，没有子操作时按 HTTP 方法得到 create/update/delete
+pub fn audit_target(method: &Method, path: &str) -> AuditTarget {
+    let mut segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    if segments.first() == Some(&"api") {
+        segments.remove(0);
+    }
+    if segments.len() > 1 && segments[0] == "storage" {
+        segments.remove(0);
+    }
+
+    let target_type = segments.first().copied().unwrap_or_default().to_string();
+    let mut rest = segments.get(1..).unwrap_or_default();
+    let target_id = match rest.first() {
+        Some(id) if is_resource_id(id) => {
+            rest = &rest[1..];
+            Some(id.to_string())
+        }
+        _ => None,
+    };
+
+    let action = if rest.is_empty() {
+        match *method {
+            Method::POST => "create",
+            Method::PUT | Method::PATCH => "update",
+            Method::DELETE => "delete",
+            _ => "other",
+        }
+        .to_string()
+    } else {
+        rest.join("/")
+    };
+
+    AuditTarget { target_type, target_id, action }
+}
+
+fn is_resource_id(segment: &str) -> bool {
+    uuid::Uuid::parse_str(segment).is_ok() || segment.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(method: Method, path: &str) -> (String, Option<String>, String) {
+        let t = audit_target(&method, path);
+        (t.target_type, t.target_id, t.action)
+    }
+
+    #[test]
+    fn test_audit_target() {
+        let id = "3f2b8c1e-4a5d-4e6f-8a9b-0c1d2e3f4a5b";
+        let s = |v: &str| v.to_string();
+
+        assert_eq!(target(Method::POST, "/api/vms"), (s("vms"), None, s("create")));
+        assert_eq!(target(Method::PUT, &format!("/api/vms/{}", id)), (s("vms"), Some(s(id)), s("update")));
+        assert_eq!(
+            target(Method::POST, &format!("/api/vms/{}/volumes/attach", id)),
+            (s("vms"), Some(s(id)), s("volumes/attach"))
+        );
+        assert_eq!(target(Method::POST, "/api/vms/batch"), (s("vms"), None, s("batch")));
+        assert_eq!(target(Method::DELETE, "/api/users/12"), (s("users"), Some(s("12")), s("delete")));
+        assert_eq!(
+            target(Method::POST, &format!("/api/storage/volumes/{}/resize", id)),
+            (s("volumes"), Some(s(id)), s("resize"))
+        );
+        assert_eq!(target(Method::POST, "/api/auth/login"), (s("auth"), None, s("login")));
+    }
+}
@@ -0,0 +1,192 @@
+/// cron 调度表达式
+///
+/// 支持标准的 5 字段格式（分 时 日 月 周，按 UTC 计算），字段可使用 `*`、数字、
+/// 范围 `a-b`、列表 `a,b` 和步长 `*/n` / `a-b/n`，星期中 0 和 7 都表示周日；
+/// 另支持 `@hourly`、`@daily`、`@weekly`、`@monthly` 简写
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Timelike, Utc};
+
+/// 查找下一次触发时间时最多向后搜索的天数，超过则认为表达式不会触发（如 2 月 30 日）
+const MAX_SEARCH_DAYS: i64 = 366 * 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    weekdays: Vec<u32>,
+    /// 日与星期字段都有限制时按 cron 惯例满足其一即可
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl std::str::FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expr = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "无效的调度表达式: {}（应为 5 个字段: 分 时 日 月 周）",
+                s
+            ));
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7, "星期")?;
+        // 7 与 0 都表示周日
+        if weekdays.contains(&7) {
+            weekdays.retain(|d| *d != 7);
+            if !weekdays.contains(&0) {
+                weekdays.insert(0, 0);
+            }
+        }
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59, "分钟")?,
+            hours: parse_field(fields[1], 0, 23, "小时")?,
+            days: parse_field(fields[2], 1, 31, "日")?,
+            months: parse_field(fields[3], 1, 12, "月")?,
+            weekdays,
+            days_restricted: fields[2] != "*",
+            weekdays_restricted: fields[4] != "*",
+        })
+    }
+}
+
+impl CronSchedule {
+    /// `after` 之后（不含）的下一次触发时间，表达式永不触发时返回 None
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        // 从下一个整分钟开始
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut date = start.date_naive();
+        let last_date = date + Duration::days(MAX_SEARCH_DAYS);
+
+        while date <= last_date {
+            if self.months.contains(&date.month()) && self.day_matches(date) {
+                // 起始日只考虑起始时间之后的时刻
+                let earliest = if date == start.date_naive() {
+                    (start.hour(), start.minute())
+                } else {
+                    (0, 0)
+                };
+                if let Some((hour, minute)) = self.first_time_from(earliest) {
+                    let time = date.and_hms_opt(hour, minute, 0)?;
+                    return Some(Utc.from_utc_datetime(&time));
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = self.days.contains(&date.day());
+        let weekday = self.weekdays.contains(&date.weekday().num_days_from_sunday());
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        }
+    }
+
+    /// 当天不早于 `(hour, minute)` 的第一个触发时刻
+    fn first_time_from(&self, (hour, minute): (u32, u32)) -> Option<(u32, u32)> {
+        self.hours
+            .iter()
+            .filter(|h| **h >= hour)
+            .find_map(|h| {
+                let min_minute = if *h == hour { minute } else { 0 };
+                self.minutes
+                    .iter()
+                    .find(|m| **m >= min_minute)
+                    .map(|m| (*h, *m))
+            })
+    }
+}
+
+/// 解析单个字段，返回升序去重后的取值
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<Vec<u32>, String> {
+    let invalid = || format!("调度表达式的{}字段无效: {}（取值范围 {}-{}）", name, field, min, max);
+    let number = |s: &str| -> Result<u32, String> {
+        s.parse::<u32>()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or_else(invalid)
+    };
+
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u32>().ok().filter(|s| *s > 0).ok_or_else(invalid)?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                // `5/15` 表示从 5 开始每 15 个单位
+                None if step > 1 => (number(range)?, max),
+                None => {
+                    let n = number(range)?;
+                    (n, n)
+                }
+            },
+        };
+        if start > end {
+            return Err(invalid());
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn next(expr: &str, after: &str) -> Option<String> {
+        let schedule: CronSchedule = expr.parse().unwrap();
+        schedule.next_after(at(after)).map(|t| t.to_rfc3339())
+    }
+
+    #[test]
+    fn test_next_after() {
+        // 每天 02:30，当天已过则顺延到次日
+        assert_eq!(next("30 2 * * *", "2026-10-17T01:00:00Z").as_deref(), Some("2026-10-17T02:30:00+00:00"));
+        assert_eq!(next("30 2 * * *", "2026-10-17T02:30:00Z").as_deref(), Some("2026-10-18T02:30:00+00:00"));
+        assert_eq!(next("@daily", "2026-12-31T23:59:30Z").as_deref(), Some("2027-01-01T00:00:00+00:00"));
+        assert_eq!(next("*/15 * * * *", "2026-10-17T08:16:00Z").as_deref(), Some("2026-10-17T08:30:00+00:00"));
+        // 2026-10-17 是周六，下一个周日（7 与 0 等价）
+        assert_eq!(next("0 3 * * 7", "2026-10-17T08:00:00Z").as_deref(), Some("2026-10-18T03:00:00+00:00"));
+        assert_eq!(next("0 0 1-5/2 2 *", "2026-10-17T08:00:00Z").as_deref(), Some("2027-02-01T00:00:00+00:00"));
+        // 日与星期都有限制时满足其一即可
+        assert_eq!(next("0 0 20 * 1", "2026-10-17T08:00:00Z").as_deref(), Some("2026-10-19T00:00:00+00:00"));
+        assert_eq!(next("0 0 30 2 *", "2026-10-17T08:00:00Z"), None);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_expressions() {
+        for expr in ["", "* * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+            assert!(expr.parse::<CronSchedule>().is_err(), "{}", expr);
+        }
+    }
+}
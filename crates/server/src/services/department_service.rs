@@ -1,8 +1,57 @@
 use anyhow::Result;
 use sea_orm::*;
-use crate::db::models::department::{Entity, Model, ActiveModel, Column, CreateDepartmentDto, UpdateDepartmentDto, DepartmentTreeDto};
+use serde::Serialize;
+use std::fmt;
+use crate::db::models::department::{
+    Entity, Model, ActiveModel, Column, CreateDepartmentDto, UpdateDepartmentDto, DepartmentTreeDto,
+    DepartmentUsageDto, QuotaUsageDto, UpdateDepartmentQuotaDto,
+};
+use crate::db::models::user_department::{Column as UserDepartmentColumn, Entity as UserDepartmentEntity};
+use crate::db::models::vm::{Column as VmColumn, Entity as VmEntity};
+use crate::db::models::volume::{Column as VolumeColumn, Entity as VolumeEntity};
 use crate::services::UserDepartmentService;
 
+/// 新建或扩容资源需要新增占用的配额
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuotaRequest {
+    pub vcpu: i64,
+    pub memory_mb: i64,
+    pub volumes: i64,
+    pub volume_gb: i64,
+}
+
+/// 部门资源配额不足，API 层返回 403 并附带用量明细供前端展示
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaExceeded {
+    pub department_id: i32,
+    pub department_name: String,
+    /// 超出配额的资源: vcpu, memory_mb, volumes, volume_gb
+    pub resource: String,
+    /// 部门成员当前的用量
+    pub current: i64,
+    pub requested: i64,
+    pub limit: i64,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, unit) = match self.resource.as_str() {
+            "vcpu" => ("vCPU", " 个"),
+            "memory_mb" => ("内存", " MB"),
+            "volumes" => ("存储卷数量", " 个"),
+            "volume_gb" => ("存储卷容量", " GB"),
+            other => (other, ""),
+        };
+        write!(
+            f,
+            "部门 {} 的{}配额不足：已使用 {}{}，本次需要 {}{}，上限 {}{}",
+            self.department_name, name, self.current, unit, self.requested, unit, self.limit, unit
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
 pub struct DepartmentService;
 
 impl DepartmentService {
@@ -240,6 +289,126 @@ impl DepartmentService {
         Ok(tree)
     }
 
+    /// 设置部门资源配额
+    pub async fn update_quota(db: &DatabaseConnection, id: i32, dto: UpdateDepartmentQuotaDto) -> Result<Model> {
+        let department = Entity::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("部门不存在"))?;
+
+        let mut department: ActiveModel = department.into();
+        department.max_vcpu = Set(dto.max_vcpu);
+        department.max_memory_mb = Set(dto.max_memory_mb);
+        department.max_volumes = Set(dto.max_volumes);
+        department.max_volume_gb = Set(dto.max_volume_gb);
+
+        let result = department.update(db).await?;
+        Ok(result)
+    }
+
+    /// 获取部门当前的资源用量
+    pub async fn get_usage(db: &DatabaseConnection, id: i32) -> Result<DepartmentUsageDto> {
+        let department = Entity::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("部门不存在"))?;
+        Self::department_usage(db, &department).await
+    }
+
+    /// 校验用户所属各部门的配额能否容纳新建的资源，超出时返回 QuotaExceeded
+    ///
+    /// 用户属于多个部门时每个部门都须满足；上级部门的配额不会向下约束
+    pub async fn check_quota(db: &DatabaseConnection, user_id: i32, request: QuotaRequest) -> Result<()> {
+        let department_ids: Vec<i32> = UserDepartmentEntity::find()
+            .filter(UserDepartmentColumn::UserId.eq(user_id))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|m| m.department_id)
+            .collect();
+        if department_ids.is_empty() {
+            return Ok(());
+        }
+
+        let departments = Entity::find()
+            .filter(Column::Id.is_in(department_ids))
+            .all(db)
+            .await?;
+
+        for department in departments {
+            if department.max_vcpu.is_none()
+                && department.max_memory_mb.is_none()
+                && department.max_volumes.is_none()
+                && department.max_volume_gb.is_none()
+            {
+                continue;
+            }
+
+            let usage = Self::department_usage(db, &department).await?;
+            let checks = [
+                ("vcpu", &usage.vcpu, request.vcpu),
+                ("memory_mb", &usage.memory_mb, request.memory_mb),
+                ("volumes", &usage.volumes, request.volumes),
+                ("volume_gb", &usage.volume_gb, request.volume_gb),
+            ];
+            for (resource, quota, requested) in checks {
+                match quota.limit {
+                    Some(limit) if requested > 0 && quota.used + requested > limit => {
+                        return Err(QuotaExceeded {
+                            department_id: department.id,
+                            department_name: department.name.clone(),
+                            resource: resource.to_string(),
+                            current: quota.used,
+                            requested,
+                            limit,
+                        }
+                        .into());
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 汇总部门成员名下的虚拟机与存储卷用量
+    async fn department_usage(db: &DatabaseConnection, department: &Model) -> Result<DepartmentUsageDto> {
+        let member_ids: Vec<i32> = UserDepartmentEntity::find()
+            .filter(UserDepartmentColumn::DepartmentId.eq(department.id))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|m| m.user_id)
+            .collect();
+
+        let (mut vcpu, mut memory_mb, mut volumes, mut volume_gb) = (0i64, 0i64, 0i64, 0i64);
+        if !member_ids.is_empty() {
+            let vms = VmEntity::find()
+                .filter(VmColumn::OwnerId.is_in(member_ids.clone()))
+                .all(db)
+                .await?;
+            vcpu = vms.iter().map(|vm| vm.vcpu as i64).sum();
+            memory_mb = vms.iter().map(|vm| vm.memory_mb).sum();
+
+            let owned_volumes = VolumeEntity::find()
+                .filter(VolumeColumn::OwnerId.is_in(member_ids))
+                .all(db)
+                .await?;
+            volumes = owned_volumes.len() as i64;
+            volume_gb = owned_volumes.iter().map(|v| v.size_gb).sum();
+        }
+
+        Ok(DepartmentUsageDto {
+            department_id: department.id,
+            department_name: department.name.clone(),
+            vcpu: QuotaUsageDto { used: vcpu, limit: department.max_vcpu.map(i64::from) },
+            memory_mb: QuotaUsageDto { used: memory_mb, limit: department.max_memory_mb },
+            volumes: QuotaUsageDto { used: volumes, limit: department.max_volumes.map(i64::from) },
+            volume_gb: QuotaUsageDto { used: volume_gb, limit: department.max_volume_gb },
+        })
+    }
+
     /// 检查是否为后代部门
     async fn is_descendant(department_id: i32, ancestor_id: i32, db: &DatabaseConnection) -> Result<bool> {
         let mut current_id = department_id;
@@ -420,6 +589,10 @@ mod tests {
                 description: Some("公司根部门".to_string()),
                 manager_id: Some(1),
                 is_active: true,
+                max_vcpu: None,
+                max_memory_mb: None,
+                max_volumes: None,
+                max_volume_gb: None,
                 created_at: chrono::Utc::now().into(),
                 updated_at: chrono::Utc::now().into(),
             },
@@ -433,6 +606,10 @@ mod tests {
                 description: Some("负责技术开发".to_string()),
                 manager_id: Some(2),
                 is_active: true,
+                max_vcpu: None,
+                max_memory_mb: None,
+                max_volumes: None,
+                max_volume_gb: None,
                 created_at: chrono::Utc::now().into(),
                 updated_at: chrono::Utc::now().into(),
             },
@@ -446,6 +623,10 @@ mod tests {
                 description: Some("负责人事管理".to_string()),
                 manager_id: Some(3),
                 is_active: true,
+                max_vcpu: None,
+                max_memory_mb: None,
+                max_volumes: None,
+                max_volume_gb: None,
                 created_at: chrono::Utc::now().into(),
                 updated_at: chrono::Utc::now().into(),
             },
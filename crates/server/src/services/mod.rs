@@ -1,12 +1,23 @@
+pub mod affinity_service;
+pub mod audit_service;
+pub mod cron_schedule;
 pub mod department_service;
 pub mod network_service;
+pub mod node_metrics_service;
 pub mod node_service;
+pub mod s3_presign;
+pub mod scheduler_service;
+pub mod secret_cipher;
+pub mod security_group_service;
+pub mod snapshot_policy_service;
 pub mod snapshot_service;
 pub mod storage_service;
 pub mod task_service;
 pub mod user_department_service;
 pub mod user_service;
 pub mod vm_service;
+pub mod vm_template_service;
+pub mod webhook_service;
 
 pub use department_service::*;
 pub use user_department_service::*;
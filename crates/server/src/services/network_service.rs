@@ -2,20 +2,110 @@
 
 use chrono::Utc;
 use uuid::Uuid;
-use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Set};
-use tracing::{error, info};
-use std::net::Ipv4Addr;
+use common::utils::validate_mac_address;
+use common::ws_rpc::{DhcpConfig, DhcpLease, StopDhcpRequest};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set, SqlErr, TransactionTrait,
+};
+use tracing::{error, info, warn};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use crate::db::models::network::{
     CreateNetworkDto, UpdateNetworkDto, NetworkListResponse, NetworkResponse,
-    Entity as NetworkEntity, Column as NetworkColumn, ActiveModel as NetworkActiveModel,
+    Entity as NetworkEntity, Column as NetworkColumn, ActiveModel as NetworkActiveModel, Model as NetworkModel,
 };
 use crate::db::models::ip_allocation::{
-    IpAllocationListResponse, IpAllocationResponse, IpAllocationStatus,
+    CreateIpReservationDto, IpAllocationListResponse, IpAllocationResponse, IpAllocationStatus,
     Entity as IpAllocationEntity, Column as IpAllocationColumn, ActiveModel as IpAllocationActiveModel,
+    Model as IpAllocationModel,
 };
+use crate::db::models::node::{Column as NodeColumn, Entity as NodeEntity, NodeStatus};
 use crate::db::models::vm::Entity as VmEntity;
 use crate::app_state::AppState;
+use crate::services::security_group_service::SecurityGroupService;
+use crate::ws::FrontendMessage;
+
+/// 解析后的 CIDR 网段
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cidr {
+    V4(Ipv4Addr, u8),
+    V6(Ipv6Addr, u8),
+}
+
+impl Cidr {
+    fn parse(cidr: &str) -> anyhow::Result<Self> {
+        let (addr, prefix_len) = cidr
+            .split_once('/')
+            .ok_or_else(|| anyhow::anyhow!("无效的 CIDR 格式"))?;
+        let prefix_len: u8 = prefix_len.parse()?;
+        match addr.parse::<IpAddr>()? {
+            IpAddr::V4(addr) if prefix_len <= 32 => Ok(Cidr::V4(addr, prefix_len)),
+            IpAddr::V6(addr) if prefix_len <= 128 => Ok(Cidr::V6(addr, prefix_len)),
+            _ => Err(anyhow::anyhow!("无效的 CIDR 前缀长度: {}", cidr)),
+        }
+    }
+
+    fn is_ipv6(&self) -> bool {
+        matches!(self, Cidr::V6(..))
+    }
+
+    /// 网段内第 index 个主机地址（从 1 开始），超出网段时返回 None
+    ///
+    /// IPv4 不含网络地址和广播地址；IPv6 不含子网路由器任播地址（主机位全 0）
+    fn host(&self, index: u128) -> Option<IpAddr> {
+        if index == 0 {
+            return None;
+        }
+        match *self {
+            Cidr::V4(addr, prefix_len) => {
+                let host_bits = 32 - prefix_len as u32;
+                let hosts = (1u128 << host_bits).saturating_sub(2);
+                if index > hosts {
+                    return None;
+                }
+                let network = u32::from(addr) & u32::MAX.checked_shl(host_bits).unwrap_or(0);
+                Some(IpAddr::V4(Ipv4Addr::from(network + index as u32)))
+            }
+            Cidr::V6(addr, prefix_len) => {
+                let host_bits = 128 - prefix_len as u32;
+                let max_index = u128::MAX.checked_shr(prefix_len as u32).unwrap_or(0);
+                if index > max_index {
+                    return None;
+                }
+                let network = u128::from(addr) & u128::MAX.checked_shl(host_bits).unwrap_or(0);
+                Some(IpAddr::V6(Ipv6Addr::from(network + index)))
+            }
+        }
+    }
+
+    /// 地址是否为网段内可分配的主机地址
+    fn contains(&self, ip: IpAddr) -> bool {
+        let index = match (*self, ip) {
+            (Cidr::V4(addr, prefix_len), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+                if u32::from(ip) & mask != u32::from(addr) & mask {
+                    return false;
+                }
+                (u32::from(ip) & !mask) as u128
+            }
+            (Cidr::V6(addr, prefix_len), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+                if u128::from(ip) & mask != u128::from(addr) & mask {
+                    return false;
+                }
+                u128::from(ip) & !mask
+            }
+            _ => return false,
+        };
+        self.host(index) == Some(ip)
+    }
+}
+
+/// 并发分配同一地址冲突时的最大重试次数
+const ALLOCATE_RETRIES: usize = 5;
 
 pub struct NetworkService {
     state: AppState,
@@ -26,8 +116,46 @@ impl NetworkService {
         Self { state }
     }
 
+    /// 发送 IP 分配变化到前端，action 为 allocated、reserved 或 released
+    async fn notify_ip_update(&self, allocation: &IpAllocationModel, action: &str) {
+        let frontend_msg = FrontendMessage::NetworkIpUpdate {
+            network_id: allocation.network_id.clone(),
+            ip_address: allocation.ip_address.clone(),
+            action: action.to_string(),
+            vm_id: allocation.vm_id.clone(),
+            mac_address: allocation.mac_address.clone(),
+        };
+
+        let count = self.state.frontend_manager().broadcast(frontend_msg).await;
+        if count > 0 {
+            info!(
+                "已向 {} 个前端连接发送网络 {} 的 IP {} 变化: {}",
+                count, allocation.network_id, allocation.ip_address, action
+            );
+        }
+    }
+
     /// 创建网络
     pub async fn create_network(&self, dto: CreateNetworkDto) -> anyhow::Result<NetworkResponse> {
+        // IP 池不预先生成记录，分配时按 CIDR 计算下一个空闲地址
+        if let Some(ref cidr) = dto.cidr {
+            Cidr::parse(cidr)?;
+        }
+        if let Some(ref cidr_v6) = dto.cidr_v6 {
+            if !Cidr::parse(cidr_v6)?.is_ipv6() {
+                return Err(anyhow::anyhow!("cidr_v6 必须是 IPv6 网段"));
+            }
+        }
+        if dto.dhcp_enabled {
+            Self::validate_dhcp(dto.cidr.as_deref(), dto.gateway.as_deref())?;
+        }
+        let security_group_id = dto.security_group_id.filter(|id| !id.is_empty());
+        if let Some(ref group_id) = security_group_id {
+            SecurityGroupService::new(self.state.clone())
+                .ensure_assignable(group_id, &dto.network_type)
+                .await?;
+        }
+
         let network_id = Uuid::new_v4().to_string();
         let now = Utc::now();
 
@@ -37,8 +165,12 @@ impl NetworkService {
             network_type: Set(dto.network_type.clone()),
             cidr: Set(dto.cidr.clone()),
             gateway: Set(dto.gateway.clone()),
+            cidr_v6: Set(dto.cidr_v6.clone()),
+            gateway_v6: Set(dto.gateway_v6.clone()),
             mtu: Set(dto.mtu.or(Some(1500))),
             vlan_id: Set(dto.vlan_id),
+            security_group_id: Set(security_group_id),
+            dhcp_enabled: Set(dto.dhcp_enabled),
             metadata: Set(dto.metadata),
             created_at: Set(now.into()),
             updated_at: Set(now.into()),
@@ -46,13 +178,6 @@ impl NetworkService {
 
         let network = network_active.insert(&self.state.sea_db()).await?;
 
-        // 如果提供了 CIDR，初始化 IP 池
-        if let Some(ref cidr) = dto.cidr {
-            if let Err(e) = self.initialize_ip_pool(&network_id, cidr, dto.gateway.as_deref()).await {
-                error!("初始化 IP 池失败: {}", e);
-            }
-        }
-
         // 注意：网络基础设施（Bridge、VLAN 子接口）将在 VM 创建时按需在节点上自动创建
         // Server 端只负责维护网络元数据，不进行实际的网络配置
         info!("网络元数据已保存，实际网络配置将在 VM 创建时按需创建");
@@ -60,67 +185,6 @@ impl NetworkService {
         Ok(NetworkResponse::from(network))
     }
 
-    /// 初始化 IP 池
-    async fn initialize_ip_pool(&self, network_id: &str, cidr: &str, gateway: Option<&str>) -> anyhow::Result<()> {
-        // 解析 CIDR
-        let parts: Vec<&str> = cidr.split('/').collect();
-        if parts.len() != 2 {
-            return Err(anyhow::anyhow!("无效的 CIDR 格式"));
-        }
-
-        let base_ip: Ipv4Addr = parts[0].parse()?;
-        let prefix_len: u8 = parts[1].parse()?;
-
-        if prefix_len > 30 {
-            // 网络太小，不创建 IP 池
-            return Ok(());
-        }
-
-        // 计算网络中可用的 IP 数量
-        let host_bits = 32 - prefix_len;
-        let total_ips = 2u32.pow(host_bits as u32);
-
-        // 最多创建 254 个 IP（避免大网络创建过多记录）
-        let max_ips = std::cmp::min(total_ips - 2, 254); // 减去网络地址和广播地址
-
-        let base_u32 = u32::from(base_ip);
-        let db = &self.state.sea_db();
-
-        for i in 1..=max_ips {
-            let ip_u32 = base_u32 + i;
-            let ip = Ipv4Addr::from(ip_u32);
-            let ip_str = ip.to_string();
-
-            // 跳过网关 IP
-            if let Some(gw) = gateway {
-                if gw == &ip_str {
-                    continue;
-                }
-            }
-
-            let allocation_id = Uuid::new_v4().to_string();
-            let now = Utc::now();
-
-            let allocation_active = IpAllocationActiveModel {
-                id: Set(allocation_id),
-                network_id: Set(network_id.to_string()),
-                ip_address: Set(ip_str),
-                mac_address: Set(None),
-                vm_id: Set(None),
-                status: Set(IpAllocationStatus::Available.as_str().to_string()),
-                allocated_at: Set(None),
-                created_at: Set(now.into()),
-            };
-
-            if let Err(e) = allocation_active.insert(db).await {
-                error!("创建 IP 分配记录失败: {}", e);
-            }
-        }
-
-        info!("为网络 {} 初始化了 {} 个 IP 地址", network_id, max_ips);
-        Ok(())
-    }
-
     /// 获取网络列表
     pub async fn list_networks(
         &self,
@@ -176,7 +240,10 @@ impl NetworkService {
             .await?
             .ok_or_else(|| anyhow::anyhow!("网络不存在"))?;
 
-        let mut network_active: NetworkActiveModel = network.into();
+        let network_type = network.network_type.clone();
+        let dhcp_was_enabled = network.dhcp_enabled;
+        let dhcp_changed = dto.dhcp_enabled.is_some() || dto.cidr.is_some() || dto.gateway.is_some();
+        let mut network_active: NetworkActiveModel = network.clone().into();
 
         if let Some(name) = dto.name {
             network_active.name = Set(name);
@@ -187,16 +254,63 @@ impl NetworkService {
         if let Some(gateway) = dto.gateway {
             network_active.gateway = Set(Some(gateway));
         }
+        if let Some(cidr_v6) = dto.cidr_v6 {
+            if !Cidr::parse(&cidr_v6)?.is_ipv6() {
+                return Err(anyhow::anyhow!("cidr_v6 必须是 IPv6 网段"));
+            }
+            network_active.cidr_v6 = Set(Some(cidr_v6));
+        }
+        if let Some(gateway_v6) = dto.gateway_v6 {
+            network_active.gateway_v6 = Set(Some(gateway_v6));
+        }
         if let Some(mtu) = dto.mtu {
             network_active.mtu = Set(Some(mtu));
         }
+        let security_group_changed = dto.security_group_id.is_some();
+        if let Some(security_group_id) = dto.security_group_id {
+            let security_group_id = Some(security_group_id).filter(|id| !id.is_empty());
+            if let Some(ref group_id) = security_group_id {
+                SecurityGroupService::new(self.state.clone())
+                    .ensure_assignable(group_id, &network_type)
+                    .await?;
+            }
+            network_active.security_group_id = Set(security_group_id);
+        }
+        if let Some(dhcp_enabled) = dto.dhcp_enabled {
+            network_active.dhcp_enabled = Set(dhcp_enabled);
+        }
         if let Some(metadata) = dto.metadata {
             network_active.metadata = Set(Some(metadata));
         }
+        if *network_active.dhcp_enabled.as_ref() {
+            Self::validate_dhcp(network_active.cidr.as_ref().as_deref(), network_active.gateway.as_ref().as_deref())?;
+        }
 
         network_active.updated_at = Set(Utc::now().into());
 
         let updated_network = network_active.update(db).await?;
+
+        if dhcp_changed {
+            if updated_network.dhcp_enabled {
+                self.reload_dhcp(network_id).await?;
+            } else if dhcp_was_enabled {
+                let request = StopDhcpRequest {
+                    network_id: network_id.to_string(),
+                    bridge_name: self.bridge_name(&updated_network),
+                };
+                self.notify_online_nodes("stop_dhcp", serde_json::to_value(&request)?).await?;
+            }
+        }
+
+        // 网络默认安全组变化时，同步到该网络上未单独指定安全组的运行中网卡
+        if security_group_changed {
+            SecurityGroupService::new(self.state.clone())
+                .sync_running_vms(|interface, network| {
+                    network.id == network_id && interface.security_group_id.is_none()
+                })
+                .await?;
+        }
+
         Ok(NetworkResponse::from(updated_network))
     }
 
@@ -243,24 +357,399 @@ impl NetworkService {
     }
 
     /// 分配 IP 地址（预留状态，不设置 vm_id）
+    ///
+    /// 从网络的主网段（cidr，IPv4 或 IPv6）中分配
     pub async fn allocate_ip(&self, network_id: &str) -> anyhow::Result<IpAllocationResponse> {
+        let network = self.find_network(network_id).await?;
+        let cidr = network.cidr.as_deref().map(Cidr::parse).transpose()?;
+        self.allocate_from_pool(network_id, cidr, network.gateway.as_deref()).await
+    }
+
+    /// 为双栈网络分配 IPv6 地址（预留状态，不设置 vm_id）
+    ///
+    /// 网络未配置 cidr_v6 时返回 None
+    pub async fn allocate_ipv6(&self, network_id: &str) -> anyhow::Result<Option<IpAllocationResponse>> {
+        let network = self.find_network(network_id).await?;
+        let cidr = match network.cidr_v6.as_deref() {
+            Some(cidr) => Cidr::parse(cidr)?,
+            None => return Ok(None),
+        };
+        self.allocate_from_pool(network_id, Some(cidr), network.gateway_v6.as_deref())
+            .await
+            .map(Some)
+    }
+
+    /// 启用 DHCP 的网络必须有 IPv4 网段和网段内的网关，网关同时作为 dnsmasq 的监听地址
+    fn validate_dhcp(cidr: Option<&str>, gateway: Option<&str>) -> anyhow::Result<()> {
+        let cidr = cidr
+            .map(Cidr::parse)
+            .transpose()?
+            .filter(|cidr| !cidr.is_ipv6())
+            .ok_or_else(|| anyhow::anyhow!("启用 DHCP 需要配置 IPv4 网段"))?;
+        let gateway: IpAddr = gateway
+            .and_then(|gw| gw.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("启用 DHCP 需要配置网关地址"))?;
+        if !cidr.contains(gateway) {
+            return Err(anyhow::anyhow!("网关 {} 不在网段内", gateway));
+        }
+        Ok(())
+    }
+
+    fn bridge_name(&self, network: &NetworkModel) -> String {
+        self.state
+            .bridge_naming()
+            .bridge_name(network.vlan_id.map(|id| id as u32))
+    }
+
+    /// 网络的 DHCP 配置，未启用 DHCP 时返回 None
+    ///
+    /// 静态租约取自带 MAC 的 IPv4 分配记录（包括绑定了 MAC 的静态预留），主机名为虚拟机名称
+    pub async fn dhcp_config(&self, network: &NetworkModel) -> anyhow::Result<Option<DhcpConfig>> {
+        if !network.dhcp_enabled {
+            return Ok(None);
+        }
+        let (Some(cidr), Some(gateway)) = (network.cidr.clone(), network.gateway.clone()) else {
+            return Ok(None);
+        };
+
+        let db = &self.state.sea_db();
+        let allocations = IpAllocationEntity::find()
+            .filter(IpAllocationColumn::NetworkId.eq(&network.id))
+            .filter(IpAllocationColumn::MacAddress.is_not_null())
+            .order_by_asc(IpAllocationColumn::IpAddress)
+            .all(db)
+            .await?;
+
+        let mut leases = Vec::with_capacity(allocations.len());
+        for allocation in allocations {
+            if !allocation.ip_address.parse::<IpAddr>().is_ok_and(|ip| ip.is_ipv4()) {
+                continue;
+            }
+            let hostname = match allocation.vm_id.as_deref() {
+                Some(vm_id) => VmEntity::find_by_id(vm_id).one(db).await?.map(|vm| vm.name),
+                None => None,
+            };
+            leases.push(DhcpLease {
+                mac_address: allocation.mac_address.unwrap_or_default(),
+                ip_address: allocation.ip_address,
+                hostname,
+            });
+        }
+
+        Ok(Some(DhcpConfig {
+            network_id: network.id.clone(),
+            bridge_name: self.bridge_name(network),
+            cidr,
+            gateway,
+            leases,
+        }))
+    }
+
+    /// IP 分配变化后通知在线节点刷新网络的 DHCP 静态租约
+    ///
+    /// 只有已在该网络上运行 dnsmasq 的节点会处理，节点离线时在下次启动虚拟机时同步
+    pub async fn reload_dhcp(&self, network_id: &str) -> anyhow::Result<()> {
+        let network = self.find_network(network_id).await?;
+        let Some(config) = self.dhcp_config(&network).await? else {
+            return Ok(());
+        };
+        self.notify_online_nodes("reload_dhcp", serde_json::to_value(&config)?).await
+    }
+
+    async fn notify_online_nodes(&self, method: &str, payload: serde_json::Value) -> anyhow::Result<()> {
+        let nodes = NodeEntity::find()
+            .filter(NodeColumn::Status.eq(NodeStatus::Online.as_str()))
+            .all(&self.state.sea_db())
+            .await?;
+        for node in nodes {
+            if let Err(e) = self.state.agent_rpc().notify(&node.id, method, payload.clone()).await {
+                warn!("通知节点 {} {} 失败: {}", node.hostname, method, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn find_network(&self, network_id: &str) -> anyhow::Result<NetworkModel> {
+        NetworkEntity::find_by_id(network_id)
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("网络不存在"))
+    }
+
+    /// 从指定网段分配一个地址
+    ///
+    /// 旧版本预先生成的 available 记录优先复用，其余地址按需计算并插入
+    async fn allocate_from_pool(
+        &self,
+        network_id: &str,
+        cidr: Option<Cidr>,
+        gateway: Option<&str>,
+    ) -> anyhow::Result<IpAllocationResponse> {
+        let db = &self.state.sea_db();
+        let ipv6 = cidr.is_some_and(|cidr| cidr.is_ipv6());
+
+        // 查找可用的 IP（双栈网络中 IPv4 与 IPv6 记录共存，按地址族区分）
+        let query = IpAllocationEntity::find()
+            .filter(IpAllocationColumn::NetworkId.eq(network_id))
+            .filter(IpAllocationColumn::Status.eq(IpAllocationStatus::Available.as_str()));
+        let query = if ipv6 {
+            query.filter(IpAllocationColumn::IpAddress.like("%:%"))
+        } else {
+            query.filter(IpAllocationColumn::IpAddress.not_like("%:%"))
+        };
+
+        if let Some(available_ip) = query.one(db).await? {
+            // 更新为预留状态，不设置 vm_id
+            let mut ip_active: IpAllocationActiveModel = available_ip.into();
+            ip_active.status = Set(IpAllocationStatus::Reserved.as_str().to_string());
+            ip_active.allocated_at = Set(Some(Utc::now().into()));
+
+            let updated_ip = ip_active.update(db).await?;
+            self.notify_ip_update(&updated_ip, "allocated").await;
+            return Ok(IpAllocationResponse::from(updated_ip));
+        }
+
+        let cidr = cidr.ok_or_else(|| anyhow::anyhow!("网络中没有可用的 IP 地址"))?;
+        for attempt in 1..=ALLOCATE_RETRIES {
+            match Self::try_allocate_on_demand(db, network_id, cidr, gateway).await {
+                Ok(Some(allocation)) => {
+                    self.notify_ip_update(&allocation, "allocated").await;
+                    return Ok(IpAllocationResponse::from(allocation));
+                }
+                Ok(None) => return Err(anyhow::anyhow!("网络中没有可用的 IP 地址")),
+                // 并发分配选中了同一地址，唯一索引拒绝后一个插入，重新计算
+                Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                    warn!("网络 {} 分配 IP 冲突，重试第 {} 次", network_id, attempt);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(anyhow::anyhow!("网络 {} 分配 IP 冲突次数过多，请稍后重试", network_id))
+    }
+
+    /// 在事务中找到网段内第一个未被记录的地址并创建预留记录，网段已满时返回 None
+    async fn try_allocate_on_demand(
+        db: &DatabaseConnection,
+        network_id: &str,
+        cidr: Cidr,
+        gateway: Option<&str>,
+    ) -> Result<Option<IpAllocationModel>, DbErr> {
+        let txn = db.begin().await?;
+
+        let mut used: HashSet<IpAddr> = IpAllocationEntity::find()
+            .select_only()
+            .column(IpAllocationColumn::IpAddress)
+            .filter(IpAllocationColumn::NetworkId.eq(network_id))
+            .into_tuple::<String>()
+            .all(&txn)
+            .await?
+            .iter()
+            .filter_map(|ip| ip.parse().ok())
+            .collect();
+        // 跳过网关 IP
+        used.extend(gateway.and_then(|gw| gw.parse::<IpAddr>().ok()));
+
+        let ip = match (1u128..)
+            .map_while(|index| cidr.host(index))
+            .find(|ip| !used.contains(ip))
+        {
+            Some(ip) => ip,
+            None => return Ok(None),
+        };
+
+        let now = Utc::now();
+        let allocation_active = IpAllocationActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            network_id: Set(network_id.to_string()),
+            ip_address: Set(ip.to_string()),
+            mac_address: Set(None),
+            vm_id: Set(None),
+            status: Set(IpAllocationStatus::Reserved.as_str().to_string()),
+            is_static: Set(false),
+            allocated_at: Set(Some(now.into())),
+            created_at: Set(now.into()),
+        };
+
+        let allocation = allocation_active.insert(&txn).await?;
+        txn.commit().await?;
+        Ok(Some(allocation))
+    }
+
+    /// 校验地址属于网络的 IPv4 或 IPv6 网段，且不是网关
+    fn ensure_in_network(network: &NetworkModel, ip: IpAddr) -> anyhow::Result<()> {
+        let ranges = [
+            (network.cidr.as_deref(), network.gateway.as_deref()),
+            (network.cidr_v6.as_deref(), network.gateway_v6.as_deref()),
+        ];
+        for (cidr, gateway) in ranges {
+            let Some(cidr) = cidr else { continue };
+            if !Cidr::parse(cidr)?.contains(ip) {
+                continue;
+            }
+            if gateway.and_then(|gw| gw.parse::<IpAddr>().ok()) == Some(ip) {
+                return Err(anyhow::anyhow!("IP {} 是网络网关，无法使用", ip));
+            }
+            return Ok(());
+        }
+        Err(anyhow::anyhow!("IP {} 不在网络 {} 的网段内", ip, network.name))
+    }
+
+    /// 为虚拟机占用指定 IP（预留状态，不设置 vm_id）
+    ///
+    /// 静态预留的地址直接占用（绑定了 MAC 时网卡必须使用该 MAC），网段内未被记录的地址按需创建记录
+    pub async fn claim_ip(
+        &self,
+        network_id: &str,
+        ip_address: &str,
+        mac_address: Option<&str>,
+    ) -> anyhow::Result<IpAllocationResponse> {
+        let db = &self.state.sea_db();
+        let network = self.find_network(network_id).await?;
+        let ip: IpAddr = ip_address
+            .parse()
+            .map_err(|_| anyhow::anyhow!("无效的 IP 地址: {}", ip_address))?;
+        Self::ensure_in_network(&network, ip)?;
+
+        let existing = IpAllocationEntity::find()
+            .filter(IpAllocationColumn::NetworkId.eq(network_id))
+            .filter(IpAllocationColumn::IpAddress.eq(ip.to_string()))
+            .one(db)
+            .await?;
+
+        let now = Utc::now();
+        let allocation = match existing {
+            Some(reservation) if reservation.is_static => {
+                if let (Some(reserved_mac), Some(mac)) = (reservation.mac_address.as_deref(), mac_address) {
+                    if !reserved_mac.eq_ignore_ascii_case(mac) {
+                        return Err(anyhow::anyhow!("IP {} 已绑定 MAC {}，与网卡 MAC 不一致", ip, reserved_mac));
+                    }
+                }
+                // 条件更新，避免两台虚拟机同时占用同一个预留地址
+                let result = IpAllocationEntity::update_many()
+                    .col_expr(IpAllocationColumn::AllocatedAt, Expr::value(now))
+                    .filter(IpAllocationColumn::Id.eq(&reservation.id))
+                    .filter(IpAllocationColumn::AllocatedAt.is_null())
+                    .exec(db)
+                    .await?;
+                if result.rows_affected == 0 {
+                    return Err(anyhow::anyhow!("IP {} 已被占用", ip));
+                }
+                IpAllocationEntity::find_by_id(&reservation.id)
+                    .one(db)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("IP 分配记录不存在"))?
+            }
+            Some(_) => return Err(anyhow::anyhow!("IP {} 已被占用", ip)),
+            None => {
+                let allocation_active = IpAllocationActiveModel {
+                    id: Set(Uuid::new_v4().to_string()),
+                    network_id: Set(network_id.to_string()),
+                    ip_address: Set(ip.to_string()),
+                    mac_address: Set(None),
+                    vm_id: Set(None),
+                    status: Set(IpAllocationStatus::Reserved.as_str().to_string()),
+                    is_static: Set(false),
+                    allocated_at: Set(Some(now.into())),
+                    created_at: Set(now.into()),
+                };
+                allocation_active.insert(db).await.map_err(|e| Self::conflict_error(e, ip))?
+            }
+        };
+
+        self.notify_ip_update(&allocation, "allocated").await;
+        Ok(IpAllocationResponse::from(allocation))
+    }
+
+    /// 静态预留指定 IP
+    ///
+    /// 预留的地址不会被自动分配，只有创建虚拟机时显式指定该 IP 才能使用
+    pub async fn reserve_ip(
+        &self,
+        network_id: &str,
+        dto: CreateIpReservationDto,
+    ) -> anyhow::Result<IpAllocationResponse> {
+        let db = &self.state.sea_db();
+        let network = self.find_network(network_id).await?;
+        let ip: IpAddr = dto
+            .ip_address
+            .parse()
+            .map_err(|_| anyhow::anyhow!("无效的 IP 地址: {}", dto.ip_address))?;
+        Self::ensure_in_network(&network, ip)?;
+
+        if let Some(ref mac) = dto.mac_address {
+            if !validate_mac_address(mac) {
+                return Err(anyhow::anyhow!("无效的 MAC 地址: {}", mac));
+            }
+        }
+
+        let in_use = IpAllocationEntity::find()
+            .filter(IpAllocationColumn::NetworkId.eq(network_id))
+            .filter(IpAllocationColumn::IpAddress.eq(ip.to_string()))
+            .count(db)
+            .await?;
+        if in_use > 0 {
+            return Err(anyhow::anyhow!("IP {} 已被占用", ip));
+        }
+
+        let reservation_active = IpAllocationActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            network_id: Set(network_id.to_string()),
+            ip_address: Set(ip.to_string()),
+            mac_address: Set(dto.mac_address.map(|mac| mac.to_lowercase())),
+            vm_id: Set(None),
+            status: Set(IpAllocationStatus::Reserved.as_str().to_string()),
+            is_static: Set(true),
+            allocated_at: Set(None),
+            created_at: Set(Utc::now().into()),
+        };
+        let reservation = reservation_active
+            .insert(db)
+            .await
+            .map_err(|e| Self::conflict_error(e, ip))?;
+
+        info!("网络 {} 静态预留 IP {}", network_id, ip);
+        self.notify_ip_update(&reservation, "reserved").await;
+        if reservation.mac_address.is_some() {
+            if let Err(e) = self.reload_dhcp(network_id).await {
+                warn!("刷新网络 {} 的 DHCP 租约失败: {}", network_id, e);
+            }
+        }
+        Ok(IpAllocationResponse::from(reservation))
+    }
+
+    /// 删除静态预留，地址回到网段中供自动分配
+    pub async fn release_reservation(&self, network_id: &str, reservation_id: &str) -> anyhow::Result<()> {
         let db = &self.state.sea_db();
 
-        // 查找可用的 IP
-        let available_ip = IpAllocationEntity::find()
+        let reservation = IpAllocationEntity::find_by_id(reservation_id)
             .filter(IpAllocationColumn::NetworkId.eq(network_id))
-            .filter(IpAllocationColumn::Status.eq(IpAllocationStatus::Available.as_str()))
+            .filter(IpAllocationColumn::IsStatic.eq(true))
             .one(db)
             .await?
-            .ok_or_else(|| anyhow::anyhow!("网络中没有可用的 IP 地址"))?;
+            .ok_or_else(|| anyhow::anyhow!("预留记录不存在"))?;
 
-        // 更新为预留状态，不设置 vm_id
-        let mut ip_active: IpAllocationActiveModel = available_ip.into();
-        ip_active.status = Set(IpAllocationStatus::Reserved.as_str().to_string());
-        ip_active.allocated_at = Set(Some(Utc::now().into()));
+        if reservation.vm_id.is_some() || reservation.allocated_at.is_some() {
+            return Err(anyhow::anyhow!("预留 IP {} 正被虚拟机使用", reservation.ip_address));
+        }
 
-        let updated_ip = ip_active.update(db).await?;
-        Ok(IpAllocationResponse::from(updated_ip))
+        IpAllocationEntity::delete_by_id(reservation_id).exec(db).await?;
+        info!("网络 {} 删除静态预留 IP {}", network_id, reservation.ip_address);
+        self.notify_ip_update(&reservation, "released").await;
+        if reservation.mac_address.is_some() {
+            if let Err(e) = self.reload_dhcp(network_id).await {
+                warn!("刷新网络 {} 的 DHCP 租约失败: {}", network_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// 唯一索引冲突转换为地址已被占用
+    fn conflict_error(err: DbErr, ip: IpAddr) -> anyhow::Error {
+        if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) {
+            anyhow::anyhow!("IP {} 已被占用", ip)
+        } else {
+            err.into()
+        }
     }
 
     /// 更新 IP 分配的 vm_id（VM 创建成功后调用）
@@ -283,10 +772,13 @@ impl NetworkService {
         ip_active.status = Set(IpAllocationStatus::Allocated.as_str().to_string());
 
         let updated_ip = ip_active.update(db).await?;
+        self.notify_ip_update(&updated_ip, "allocated").await;
         Ok(IpAllocationResponse::from(updated_ip))
     }
 
     /// 释放 IP 地址
+    ///
+    /// 直接删除分配记录，地址回到网段中供后续分配；静态预留的地址恢复为预留状态
     pub async fn release_ip(&self, network_id: &str, vm_id: &str) -> anyhow::Result<()> {
         let db = &self.state.sea_db();
 
@@ -298,15 +790,36 @@ impl NetworkService {
             .await?;
 
         for ip in allocated_ips {
-            let mut ip_active: IpAllocationActiveModel = ip.into();
+            self.release(db, ip).await?;
+        }
+
+        Ok(())
+    }
+
+    /// 按分配记录释放单个 IP
+    ///
+    /// 同一网络上挂载多块网卡时，虚拟机在该网络中持有多个 IP，需按记录逐个释放
+    pub async fn release_ip_allocation(&self, ip_allocation_id: &str) -> anyhow::Result<()> {
+        let db = &self.state.sea_db();
+
+        let ip = IpAllocationEntity::find_by_id(ip_allocation_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("IP 分配记录不存在"))?;
+        self.release(db, ip).await
+    }
+
+    async fn release(&self, db: &DatabaseConnection, ip: IpAllocationModel) -> anyhow::Result<()> {
+        if !ip.is_static {
+            IpAllocationEntity::delete_by_id(&ip.id).exec(db).await?;
+        } else {
+            let mut ip_active: IpAllocationActiveModel = ip.clone().into();
             ip_active.vm_id = Set(None);
-            ip_active.mac_address = Set(None);
-            ip_active.status = Set(IpAllocationStatus::Available.as_str().to_string());
+            ip_active.status = Set(IpAllocationStatus::Reserved.as_str().to_string());
             ip_active.allocated_at = Set(None);
-
             ip_active.update(db).await?;
         }
-
+        self.notify_ip_update(&ip, "released").await;
         Ok(())
     }
 
@@ -362,3 +875,270 @@ impl NetworkService {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NodeAlertThresholds;
+    use crate::ws::AgentConnectionManager;
+    use common::utils::BridgeNaming;
+    use common::ws_rpc::DiskBusType;
+
+    async fn service() -> NetworkService {
+        let db = crate::lifecycle_tests::sqlite_db().await;
+        NetworkService::new(AppState::new(
+            db,
+            AgentConnectionManager::new(),
+            BridgeNaming::new(BridgeNaming::DEFAULT_PREFIX).unwrap(),
+            false,
+            NodeAlertThresholds::default(),
+            DiskBusType::Virtio,
+        ))
+    }
+
+    fn network_dto(cidr: &str, gateway: &str, v6: Option<(&str, &str)>) -> CreateNetworkDto {
+        CreateNetworkDto {
+            name: "prod".to_string(),
+            network_type: "bridge".to_string(),
+            cidr: Some(cidr.to_string()),
+            gateway: Some(gateway.to_string()),
+            cidr_v6: v6.map(|(cidr, _)| cidr.to_string()),
+            gateway_v6: v6.map(|(_, gw)| gw.to_string()),
+            mtu: None,
+            vlan_id: None,
+            security_group_id: None,
+            dhcp_enabled: false,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_cidr_host() {
+        let v4 = Cidr::parse("192.168.1.0/30").unwrap();
+        assert_eq!(v4.host(1), Some("192.168.1.1".parse().unwrap()));
+        assert_eq!(v4.host(2), Some("192.168.1.2".parse().unwrap()));
+        assert_eq!(v4.host(3), None); // 广播地址
+
+        let v6 = Cidr::parse("2001:db8::17/126").unwrap();
+        assert!(v6.is_ipv6());
+        assert_eq!(v6.host(0), None);
+        assert_eq!(v6.host(1), Some("2001:db8::15".parse().unwrap()));
+        assert_eq!(v6.host(3), Some("2001:db8::17".parse().unwrap()));
+        assert_eq!(v6.host(4), None);
+
+        assert!(Cidr::parse("2001:db8::/129").is_err());
+        assert!(Cidr::parse("2001:db8::").is_err());
+        assert!(Cidr::parse("10.0.0.0/33").is_err());
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        let v4 = Cidr::parse("192.168.1.0/24").unwrap();
+        assert!(v4.contains("192.168.1.1".parse().unwrap()));
+        assert!(v4.contains("192.168.1.254".parse().unwrap()));
+        assert!(!v4.contains("192.168.1.0".parse().unwrap()));
+        assert!(!v4.contains("192.168.1.255".parse().unwrap()));
+        assert!(!v4.contains("192.168.2.1".parse().unwrap()));
+        assert!(!v4.contains("fd00::1".parse().unwrap()));
+
+        let v6 = Cidr::parse("fd00::/64").unwrap();
+        assert!(v6.contains("fd00::ffff".parse().unwrap()));
+        assert!(!v6.contains("fd00::".parse().unwrap()));
+        assert!(!v6.contains("fd00:0:0:1::1".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_ipv4_network_allocates_on_demand() {
+        let service = service().await;
+        let network = service
+            .create_network(network_dto("10.0.0.0/16", "10.0.0.1", None))
+            .await
+            .unwrap();
+
+        // 不预先创建地址记录
+        let pool = service.list_ip_allocations(&network.id, 1, 10, None).await.unwrap();
+        assert_eq!(pool.total, 0);
+
+        // 跳过网关，按顺序分配
+        let first = service.allocate_ip(&network.id).await.unwrap();
+        let second = service.allocate_ip(&network.id).await.unwrap();
+        assert_eq!(first.ip_address, "10.0.0.2");
+        assert_eq!(first.status, "reserved");
+        assert_eq!(second.ip_address, "10.0.0.3");
+
+        // 释放即删除记录，空出的地址优先分配
+        service.release_ip_allocation(&first.id).await.unwrap();
+        let pool = service.list_ip_allocations(&network.id, 1, 10, None).await.unwrap();
+        assert_eq!(pool.total, 1);
+        let reused = service.allocate_ip(&network.id).await.unwrap();
+        assert_eq!(reused.ip_address, "10.0.0.2");
+        assert!(service.release_ip_allocation("missing").await.is_err());
+
+        // 超过 /24 的网段也能分配到第 254 个之后的地址
+        for _ in 0..300 {
+            service.allocate_ip(&network.id).await.unwrap();
+        }
+        let pool = service.list_ip_allocations(&network.id, 1, 10, None).await.unwrap();
+        assert_eq!(pool.total, 302);
+    }
+
+    #[tokio::test]
+    async fn test_ip_changes_broadcast_to_frontend() {
+        let service = service().await;
+        let network = service
+            .create_network(network_dto("10.0.0.0/24", "10.0.0.1", None))
+            .await
+            .unwrap();
+        let (sender, mut frontend) = tokio::sync::mpsc::unbounded_channel();
+        service
+            .state
+            .frontend_manager()
+            .register("frontend-1".to_string(), None, sender)
+            .await;
+
+        let ip = service.allocate_ip(&network.id).await.unwrap();
+        service.release_ip_allocation(&ip.id).await.unwrap();
+        let claimed = service.claim_ip(&network.id, "10.0.0.2", None).await.unwrap();
+        assert_eq!(claimed.ip_address, ip.ip_address);
+
+        let mut events = Vec::new();
+        while let Ok(msg) = frontend.try_recv() {
+            match msg {
+                FrontendMessage::NetworkIpUpdate { network_id, ip_address, action, .. } => {
+                    assert_eq!(network_id, network.id);
+                    assert_eq!(ip_address, "10.0.0.2");
+                    events.push(action);
+                }
+                other => panic!("unexpected frontend message: {:?}", other),
+            }
+        }
+        assert_eq!(events, vec!["allocated", "released", "allocated"]);
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_network_allocates_on_demand() {
+        let service = service().await;
+        let network = service
+            .create_network(network_dto("2001:db8::/64", "2001:db8::1", None))
+            .await
+            .unwrap();
+
+        // 跳过网关，按顺序分配
+        let first = service.allocate_ip(&network.id).await.unwrap();
+        let second = service.allocate_ip(&network.id).await.unwrap();
+        assert_eq!(first.ip_address, "2001:db8::2");
+        assert_eq!(first.status, "reserved");
+        assert_eq!(second.ip_address, "2001:db8::3");
+
+        // 释放后地址回到网段中，重新分配
+        service.release_ip_allocation(&first.id).await.unwrap();
+        let reused = service.allocate_ip(&network.id).await.unwrap();
+        assert_eq!(reused.ip_address, "2001:db8::2");
+    }
+
+    #[tokio::test]
+    async fn test_dual_stack_network_allocates_both_families() {
+        let service = service().await;
+        let network = service
+            .create_network(network_dto(
+                "10.0.0.0/29",
+                "10.0.0.1",
+                Some(("fd00::/64", "fd00::1")),
+            ))
+            .await
+            .unwrap();
+
+        let v4 = service.allocate_ip(&network.id).await.unwrap();
+        let v6 = service.allocate_ipv6(&network.id).await.unwrap().unwrap();
+        assert_eq!(v4.ip_address, "10.0.0.2");
+        assert_eq!(v6.ip_address, "fd00::2");
+
+        // IPv4 池耗尽后不会拿到 IPv6 地址
+        for _ in 0..4 {
+            service.allocate_ip(&network.id).await.unwrap();
+        }
+        assert!(service.allocate_ip(&network.id).await.is_err());
+
+        // 纯 IPv4 网络没有 IPv6 地址
+        let v4_only = service
+            .create_network(network_dto("10.1.0.0/24", "10.1.0.1", None))
+            .await
+            .unwrap();
+        assert!(service.allocate_ipv6(&v4_only.id).await.unwrap().is_none());
+
+        // cidr_v6 必须是 IPv6 网段
+        assert!(service
+            .create_network(network_dto("10.2.0.0/24", "10.2.0.1", Some(("10.3.0.0/24", "10.3.0.1"))))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_static_reservation() {
+        let service = service().await;
+        let network = service
+            .create_network(network_dto("10.0.0.0/24", "10.0.0.1", Some(("fd00::/64", "fd00::1"))))
+            .await
+            .unwrap();
+        let reserve = |ip: &str, mac: Option<&str>| CreateIpReservationDto {
+            ip_address: ip.to_string(),
+            mac_address: mac.map(str::to_string),
+        };
+
+        let reservation = service
+            .reserve_ip(&network.id, reserve("10.0.0.2", Some("52:54:00:AA:BB:CC")))
+            .await
+            .unwrap();
+        assert!(reservation.is_static);
+        assert_eq!(reservation.status, "reserved");
+        assert_eq!(reservation.mac_address.as_deref(), Some("52:54:00:aa:bb:cc"));
+        service.reserve_ip(&network.id, reserve("fd00::10", None)).await.unwrap();
+
+        // 网段外、网关、重复预留均被拒绝
+        for (ip, expected) in [
+            ("10.0.1.2", "不在网络"),
+            ("10.0.0.255", "不在网络"),
+            ("10.0.0.1", "网关"),
+            ("10.0.0.2", "已被占用"),
+            ("not-an-ip", "无效的 IP"),
+        ] {
+            let err = service.reserve_ip(&network.id, reserve(ip, None)).await.unwrap_err();
+            assert!(err.to_string().contains(expected), "{}: {}", ip, err);
+        }
+
+        // 自动分配跳过预留地址
+        let auto = service.allocate_ip(&network.id).await.unwrap();
+        assert_eq!(auto.ip_address, "10.0.0.3");
+
+        // 显式占用预留地址，MAC 必须与预留一致，且只能被占用一次
+        let err = service
+            .claim_ip(&network.id, "10.0.0.2", Some("52:54:00:00:00:01"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("MAC"), "{}", err);
+        let claimed = service.claim_ip(&network.id, "10.0.0.2", None).await.unwrap();
+        assert_eq!(claimed.id, reservation.id);
+        assert!(service.claim_ip(&network.id, "10.0.0.2", None).await.is_err());
+        assert!(service.claim_ip(&network.id, "10.0.0.3", None).await.is_err());
+
+        // 占用中的预留不能删除；释放后恢复为预留而不是删除
+        assert!(service.release_reservation(&network.id, &reservation.id).await.is_err());
+        service.release_ip_allocation(&claimed.id).await.unwrap();
+        let released = IpAllocationEntity::find_by_id(&reservation.id)
+            .one(&service.state.sea_db())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(released.status, "reserved");
+        assert_eq!(released.allocated_at, None);
+        assert_eq!(released.mac_address.as_deref(), Some("52:54:00:aa:bb:cc"));
+
+        // 未预留的网段内地址也可以显式占用
+        let explicit = service.claim_ip(&network.id, "10.0.0.50", None).await.unwrap();
+        assert!(!explicit.is_static);
+
+        service.release_reservation(&network.id, &reservation.id).await.unwrap();
+        assert!(service.release_reservation(&network.id, &reservation.id).await.is_err());
+        assert!(service.release_reservation(&network.id, &auto.id).await.is_err());
+    }
+}
@@ -0,0 +1,237 @@
+/// 节点主机指标历史服务
+///
+/// 保存 Agent 上报的 node_metrics 采样（保留最近 24 小时），按时间范围查询时降采样，
+/// 无论采样间隔多密，单次返回的点数都不超过 MAX_POINTS
+
+use chrono::{Duration as ChronoDuration, Utc};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::app_state::AppState;
+use crate::db::models::node::Entity as NodeEntity;
+use crate::db::models::node_metric::{
+    ActiveModel as NodeMetricActiveModel, Column as NodeMetricColumn, Entity as NodeMetricEntity,
+    NodeMetricPoint, NodeMetricsResponse,
+};
+use common::ws_rpc::NodeMetricsSample;
+
+/// 指标历史保留时长（秒）
+pub const RETENTION_SECS: i64 = 24 * 3600;
+
+/// 未指定范围时查询最近 1 小时
+pub const DEFAULT_RANGE_SECS: i64 = 3600;
+
+/// 单次查询最多返回的点数
+const MAX_POINTS: usize = 500;
+
+pub struct NodeMetricsService {
+    state: AppState,
+}
+
+impl NodeMetricsService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// 保存一次采样
+    ///
+    /// 采样时间使用 Server 接收时间，避免节点时钟偏差导致历史错位
+    pub async fn record_sample(&self, sample: &NodeMetricsSample) -> anyhow::Result<()> {
+        let metric = NodeMetricActiveModel {
+            node_id: Set(sample.node_id.clone()),
+            sampled_at: Set(Utc::now().into()),
+            cpu_usage: Set(sample.cpu_usage),
+            load_avg: Set(sample.load_avg_1),
+            memory_total: Set(sample.memory_total as i64),
+            memory_free: Set(sample.memory_free as i64),
+            disks: Set(serde_json::to_value(&sample.disks)?),
+            ..Default::default()
+        };
+        metric.insert(&self.state.sea_db()).await?;
+        Ok(())
+    }
+
+    /// 查询节点最近 range_secs 秒的指标历史
+    pub async fn get_node_metrics(
+        &self,
+        node_id: &str,
+        range_secs: i64,
+    ) -> anyhow::Result<NodeMetricsResponse> {
+        let db = &self.state.sea_db();
+
+        NodeEntity::find_by_id(node_id.to_string())
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("节点不存在"))?;
+
+        let end = Utc::now();
+        let start = end - ChronoDuration::seconds(range_secs);
+
+        let samples = NodeMetricEntity::find()
+            .filter(NodeMetricColumn::NodeId.eq(node_id))
+            .filter(NodeMetricColumn::SampledAt.gte(start))
+            .order_by_asc(NodeMetricColumn::SampledAt)
+            .all(db)
+            .await?;
+
+        let sample_count = samples.len();
+        let points = samples.into_iter().map(NodeMetricPoint::from).collect();
+
+        Ok(NodeMetricsResponse {
+            node_id: node_id.to_string(),
+            range_secs,
+            sample_count,
+            points: downsample(points, start.timestamp(), end.timestamp(), MAX_POINTS),
+        })
+    }
+
+    /// 删除超过保留时长的采样，返回删除的条数
+    pub async fn prune_expired(&self) -> anyhow::Result<u64> {
+        let cutoff = Utc::now() - ChronoDuration::seconds(RETENTION_SECS);
+        let result = NodeMetricEntity::delete_many()
+            .filter(NodeMetricColumn::SampledAt.lt(cutoff))
+            .exec(&self.state.sea_db())
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    /// 启动后台任务，定期清理过期的指标历史
+    pub fn start_node_metrics_pruner(state: AppState, check_interval_secs: u64) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(check_interval_secs));
+
+            loop {
+                interval.tick().await;
+
+                match NodeMetricsService::new(state.clone()).prune_expired().await {
+                    Ok(0) => {}
+                    Ok(pruned) => info!("节点指标清理: 已删除 {} 条过期采样", pruned),
+                    Err(e) => error!("节点指标清理失败: {}", e),
+                }
+            }
+        });
+    }
+}
+
+/// 解析查询范围，如 `30m`、`6h`、`1d`，纯数字按秒处理；不能超过保留时长
+pub fn parse_range(range: &str) -> Result<i64, String> {
+    let range = range.trim();
+    let (value, unit) = match range.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => range.split_at(pos),
+        None => (range, "s"),
+    };
+
+    let value: i64 = value
+        .parse()
+        .map_err(|_| format!("无效的时间范围: {}", range))?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(format!("无效的时间范围单位: {}（可选 s/m/h/d）", unit)),
+    };
+
+    let secs = value.saturating_mul(multiplier);
+    if secs <= 0 || secs > RETENTION_SECS {
+        return Err(format!("时间范围必须在 1 秒到 {} 小时之间", RETENTION_SECS / 3600));
+    }
+    Ok(secs)
+}
+
+/// 按时间桶降采样：把 [start, end] 均分为不超过 max_points 个桶，
+/// 桶内 CPU、负载和可用内存取平均，总内存与磁盘取桶内最后一次采样
+fn downsample(
+    points: Vec<NodeMetricPoint>,
+    start: i64,
+    end: i64,
+    max_points: usize,
+) -> Vec<NodeMetricPoint> {
+    if points.len() <= max_points || max_points == 0 {
+        return points;
+    }
+
+    let span = (end - start).max(1);
+    let bucket_secs = (span + max_points as i64 - 1) / max_points as i64;
+
+    let mut result = Vec::with_capacity(max_points);
+    let mut bucket: Vec<NodeMetricPoint> = Vec::new();
+    let mut bucket_index = None;
+
+    for point in points {
+        let index = ((point.timestamp - start).max(0) / bucket_secs).min(max_points as i64 - 1);
+        if bucket_index != Some(index) && !bucket.is_empty() {
+            result.push(merge_bucket(std::mem::take(&mut bucket)));
+        }
+        bucket_index = Some(index);
+        bucket.push(point);
+    }
+    if !bucket.is_empty() {
+        result.push(merge_bucket(bucket));
+    }
+
+    result
+}
+
+fn merge_bucket(bucket: Vec<NodeMetricPoint>) -> NodeMetricPoint {
+    let count = bucket.len() as f64;
+    let cpu_usage = bucket.iter().map(|p| p.cpu_usage).sum::<f64>() / count;
+    let load_avg = bucket.iter().map(|p| p.load_avg).sum::<f64>() / count;
+    let memory_free = (bucket.iter().map(|p| p.memory_free as f64).sum::<f64>() / count) as i64;
+    let timestamp = bucket[0].timestamp;
+
+    let last = bucket.into_iter().last().expect("时间桶不为空");
+    NodeMetricPoint {
+        timestamp,
+        cpu_usage,
+        load_avg,
+        memory_total: last.memory_total,
+        memory_free,
+        disks: last.disks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(timestamp: i64, cpu_usage: f64) -> NodeMetricPoint {
+        NodeMetricPoint {
+            timestamp,
+            cpu_usage,
+            load_avg: 1.0,
+            memory_total: 8192,
+            memory_free: 4096,
+            disks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("30m"), Ok(1800));
+        assert_eq!(parse_range("6h"), Ok(6 * 3600));
+        assert_eq!(parse_range("1d"), Ok(RETENTION_SECS));
+        assert_eq!(parse_range("90"), Ok(90));
+        assert!(parse_range("2d").is_err());
+        assert!(parse_range("0h").is_err());
+        assert!(parse_range("1w").is_err());
+        assert!(parse_range("h").is_err());
+    }
+
+    #[test]
+    fn test_downsample_caps_point_count() {
+        // 24 小时内每 10 秒一个采样
+        let points: Vec<_> = (0..8640).map(|i| point(i * 10, (i % 2) as f64 * 100.0)).collect();
+        let result = downsample(points, 0, 86400, 500);
+
+        assert!(result.len() <= 500);
+        assert!(result.len() >= 490);
+        assert!(result.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+        // 桶内 0 与 100 交替，平均值为 50 附近
+        assert!((result[0].cpu_usage - 50.0).abs() < 5.0);
+
+        let sparse = vec![point(0, 10.0), point(60, 20.0)];
+        assert_eq!(downsample(sparse.clone(), 0, 3600, 500), sparse);
+    }
+}
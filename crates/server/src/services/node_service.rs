@@ -2,13 +2,51 @@
 
 use chrono::Utc;
 use uuid::Uuid;
+use sea_orm::sea_query::Expr;
 use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Set};
 
+use crate::config::NodeAlertThresholds;
 use crate::db::models::node::{
-    CreateNodeDto, UpdateNodeDto, NodeResponse, NodeListResponse, NodeStatus, NodeStatsResponse, Entity as NodeEntity, Column as NodeColumn, 
-    ActiveModel as NodeActiveModel,
+    CreateNodeDto, NodeHealthResponse, UpdateNodeDto, NodeResponse, NodeListResponse, NodeStatus, NodeStatsResponse, Entity as NodeEntity, Column as NodeColumn, 
+    ActiveModel as NodeActiveModel, Model as NodeModel, NodePowerAction, NodePowerResponse, UpdateNodeIpmiDto,
 };
+use crate::db::models::vm::ActiveModel as VmActiveModel;
+use crate::db::models::webhook::{WebhookEvent, WebhookEventType};
+use crate::services::vm_service::domain_state_to_status;
+use crate::services::webhook_service::WebhookService;
+use crate::ws::FrontendMessage;
+use common::ws_rpc::types::{ListVmsRequest, ListVmsResponse};
+use common::ws_rpc::{DrainNodeRequest, DrainNodeResponse, GetRestoreStateResponse, HostShutdownPolicy, NodeResourceInfo};
+use crate::db::models::vm::{Column as VmColumn, Entity as VmEntity, VmStatus};
 use crate::app_state::AppState;
+use crate::ws::agent_manager::AgentConnection;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// ipmitool 单次调用超时时间
+const IPMI_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 排空节点超时时间（Agent 逐台处理，优雅关机单台最多等待 30 秒）
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// 操作完成通知到达前虚拟机所处的过渡状态，Agent 崩溃时通知可能丢失
+const TRANSIENT_VM_STATUSES: [&str; 3] = ["starting", "stopping", "restarting"];
+
+/// 重连对账等待新连接注册完成的最长时间
+const RECONNECT_READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 刷新节点能力的超时，Agent 需要执行 qemu --version 等命令并采样 CPU 利用率
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 心跳超过该时长视为连接不活跃（Agent 默认每 30 秒发送一次心跳）
+const HEARTBEAT_STALE_SECS: u64 = 90;
+
+/// 利用率超过该值开始扣分（百分比）
+const USAGE_PENALTY_START: f64 = 70.0;
+
+/// 单项利用率最多扣分
+const USAGE_PENALTY_MAX: f64 = 25.0;
 
 pub struct NodeService {
     state: AppState,
@@ -74,7 +112,18 @@ impl NodeService {
             cpu_threads: Set(None),
             memory_total: Set(None),
             disk_total: Set(None),
+            cpu_usage: Set(None),
+            memory_used: Set(None),
+            disk_used: Set(None),
             metadata: Set(dto.metadata.clone()),
+            ipmi_address: Set(None),
+            ipmi_username: Set(None),
+            ipmi_password: Set(None),
+            has_swtpm: Set(false),
+            has_ovs: Set(false),
+            numa_topology: Set(None),
+            architectures: Set(None),
+            shutdown_policy: Set(HostShutdownPolicy::default().as_str().to_string()),
             last_heartbeat: Set(None),
             created_at: Set((*now).into()),
             updated_at: Set((*now).into()),
@@ -244,7 +293,7 @@ impl NodeService {
     }
 
     /// 更新节点心跳
-    pub async fn update_heartbeat(&self, id: &str) -> anyhow::Result<()> {
+    pub async fn update_heartbeat(&self, id: &str, hypervisor_connected: bool) -> anyhow::Result<()> {
         let db = &self.state.sea_db();
 
         // 查询节点
@@ -254,65 +303,286 @@ impl NodeService {
             .ok_or_else(|| anyhow::anyhow!("节点不存在"))?;
 
         let now = Utc::now();
+        let previous_status = node.status.clone();
+        let status = heartbeat_status(&previous_status, hypervisor_connected);
         let mut node_active: NodeActiveModel = node.into();
 
-        // 更新心跳时间和资源信息
+        // 更新心跳时间和状态
         node_active.last_heartbeat = Set(Some(now.into()));
-        node_active.status = Set(NodeStatus::Online.as_str().to_string());
+        node_active.status = Set(status.to_string());
 
         // 更新数据库
         node_active.update(db).await?;
 
+        if status != previous_status {
+            let message = if hypervisor_connected {
+                "节点已恢复".to_string()
+            } else {
+                "libvirt 未连接，节点降级运行".to_string()
+            };
+            tracing::warn!("节点 {} 状态变更: {} -> {}（{}）", id, previous_status, status, message);
+
+            let event_type = if status == NodeStatus::Error.as_str() {
+                Some(WebhookEventType::NodeError)
+            } else if previous_status == NodeStatus::Offline.as_str() {
+                Some(WebhookEventType::NodeOnline)
+            } else {
+                None
+            };
+            if let Some(event_type) = event_type {
+                WebhookService::new(self.state.clone()).emit(WebhookEvent::new(
+                    event_type,
+                    "node",
+                    Some(id),
+                    message.clone(),
+                    serde_json::json!({ "previous_status": previous_status, "status": status }),
+                ));
+            }
+
+            self.state
+                .frontend_manager()
+                .broadcast(FrontendMessage::NodeStatusUpdate {
+                    node_id: id.to_string(),
+                    status: status.to_string(),
+                    message: Some(message),
+                })
+                .await;
+        }
+
         Ok(())
     }
 
     /// 更新节点资源信息
-    pub async fn update_node_resource_info(
-        &self,
-        id: &str,
-        cpu_cores: u32,
-        cpu_threads: u32,
-        memory_total: u64,
-        disk_total: u64,
-        hypervisor_type: Option<String>,
-        hypervisor_version: Option<String>,
-    ) -> anyhow::Result<()> {
+    ///
+    /// 利用率越过告警阈值（或恢复）时向前端推送 NodeAlert
+    pub async fn update_node_resource_info(&self, info: &NodeResourceInfo) -> anyhow::Result<()> {
         let db = &self.state.sea_db();
 
         // 查询节点
-        let node = NodeEntity::find_by_id(id.to_string())
+        let node = NodeEntity::find_by_id(info.node_id.clone())
             .one(db)
             .await?
             .ok_or_else(|| anyhow::anyhow!("节点不存在"))?;
 
+        let previous_usage = NodeUsage::from_node(&node);
+        let previous_memory = (node.memory_used, node.memory_total);
+        let previous_status = node.status.clone();
         let now = Utc::now();
         let mut node_active: NodeActiveModel = node.into();
 
         // 更新资源信息
-        node_active.cpu_cores = Set(Some(cpu_cores as i32));
-        node_active.cpu_threads = Set(Some(cpu_threads as i32));
-        node_active.memory_total = Set(Some(memory_total as i64));
-        node_active.disk_total = Set(Some(disk_total as i64));
+        node_active.cpu_cores = Set(Some(info.cpu_cores as i32));
+        node_active.cpu_threads = Set(Some(info.cpu_threads as i32));
+        node_active.memory_total = Set(Some(info.memory_total as i64));
+        node_active.disk_total = Set(Some(info.disk_total as i64));
+
+        // 更新利用率（旧版本 Agent 不上报，保持原值）
+        if let Some(cpu_usage) = info.cpu_usage {
+            node_active.cpu_usage = Set(Some(cpu_usage));
+        }
+        if let Some(memory_used) = info.memory_used {
+            node_active.memory_used = Set(Some(memory_used as i64));
+        }
+        if let Some(disk_used) = info.disk_used {
+            node_active.disk_used = Set(Some(disk_used as i64));
+        }
+        if let Some(has_swtpm) = info.has_swtpm {
+            node_active.has_swtpm = Set(has_swtpm);
+        }
+        if let Some(has_ovs) = info.has_ovs {
+            node_active.has_ovs = Set(has_ovs);
+        }
+        if let Some(ref numa_nodes) = info.numa_nodes {
+            node_active.numa_topology = Set(Some(serde_json::to_value(numa_nodes)?));
+        }
+        if let Some(ref architectures) = info.supported_architectures {
+            node_active.architectures = Set(Some(serde_json::to_value(architectures)?));
+        }
         
         // 更新虚拟化信息（如果提供）
-        if let Some(hypervisor_type) = hypervisor_type {
+        if let Some(hypervisor_type) = info.hypervisor_type.clone() {
             node_active.hypervisor_type = Set(Some(hypervisor_type));
         }
-        if let Some(hypervisor_version) = hypervisor_version {
+        if let Some(hypervisor_version) = info.hypervisor_version.clone() {
             node_active.hypervisor_version = Set(Some(hypervisor_version));
         }
         
-        // 更新心跳时间和状态
+        // 更新心跳时间；降级与维护状态由心跳维护，这里只把离线节点置为在线
         node_active.last_heartbeat = Set(Some(now.into()));
-        node_active.status = Set(NodeStatus::Online.as_str().to_string());
+        if previous_status == NodeStatus::Offline.as_str() {
+            node_active.status = Set(NodeStatus::Online.as_str().to_string());
+        }
         node_active.updated_at = Set(now.into());
 
         // 更新数据库
-        node_active.update(db).await?;
+        let node = node_active.update(db).await?;
+        let webhooks = WebhookService::new(self.state.clone());
+
+        if previous_status == NodeStatus::Offline.as_str() {
+            webhooks.emit(WebhookEvent::new(
+                WebhookEventType::NodeOnline,
+                "node",
+                Some(&node.id),
+                format!("节点 {} 已恢复在线", node.hostname),
+                serde_json::json!({ "previous_status": previous_status, "status": node.status }),
+            ));
+        }
+
+        let thresholds = self.state.node_alert_thresholds();
+        let crossings = threshold_crossings(previous_usage, NodeUsage::from_node(&node), &thresholds);
+        for crossing in crossings {
+            let message = if crossing.resolved {
+                format!(
+                    "节点 {} {}利用率已恢复至 {:.1}%（阈值 {:.0}%）",
+                    node.hostname, crossing.label, crossing.value, crossing.threshold
+                )
+            } else {
+                format!(
+                    "节点 {} {}利用率 {:.1}% 超过阈值 {:.0}%",
+                    node.hostname, crossing.label, crossing.value, crossing.threshold
+                )
+            };
+
+            if crossing.resolved {
+                tracing::info!("{}", message);
+            } else {
+                tracing::warn!("{}", message);
+            }
+
+            webhooks.emit(WebhookEvent::new(
+                if crossing.resolved {
+                    WebhookEventType::NodeMetricResolved
+                } else {
+                    WebhookEventType::NodeMetricAlert
+                },
+                "node",
+                Some(&node.id),
+                message.clone(),
+                serde_json::json!({
+                    "metric": crossing.metric,
+                    "value": crossing.value,
+                    "threshold": crossing.threshold,
+                }),
+            ));
+
+            self.state
+                .frontend_manager()
+                .broadcast(FrontendMessage::NodeAlert {
+                    node_id: node.id.clone(),
+                    metric: crossing.metric.to_string(),
+                    value: crossing.value,
+                    threshold: crossing.threshold,
+                    resolved: crossing.resolved,
+                    message,
+                })
+                .await;
+        }
+
+        self.check_cluster_memory(&node, previous_memory).await?;
+
+        Ok(())
+    }
+
+    /// 本节点上报后集群内存利用率越过（或恢复）阈值时通知 Webhook
+    ///
+    /// 将本节点替换为上报前的数值即可得到上一次的集群利用率，无需额外保存状态
+    async fn check_cluster_memory(
+        &self,
+        node: &NodeModel,
+        previous_memory: (Option<i64>, Option<i64>),
+    ) -> anyhow::Result<()> {
+        let nodes = NodeEntity::find()
+            .filter(NodeColumn::Status.ne(NodeStatus::Offline.as_str()))
+            .all(&self.state.sea_db())
+            .await?;
 
+        let current = cluster_memory_percent(nodes.iter().map(|n| (n.memory_used, n.memory_total)));
+        let previous = cluster_memory_percent(nodes.iter().map(|n| {
+            if n.id == node.id {
+                previous_memory
+            } else {
+                (n.memory_used, n.memory_total)
+            }
+        }));
+
+        let threshold = self.state.webhook_settings().cluster_memory_percent;
+        let Some(value) = current else {
+            return Ok(());
+        };
+        let was_over = previous.map_or(false, |p| p >= threshold);
+        let is_over = value >= threshold;
+        if was_over == is_over {
+            return Ok(());
+        }
+
+        let (event_type, message) = if is_over {
+            (
+                WebhookEventType::ClusterMemoryHigh,
+                format!("集群内存利用率 {:.1}% 超过阈值 {:.0}%", value, threshold),
+            )
+        } else {
+            (
+                WebhookEventType::ClusterMemoryResolved,
+                format!("集群内存利用率已恢复至 {:.1}%（阈值 {:.0}%）", value, threshold),
+            )
+        };
+        tracing::warn!("{}", message);
+
+        WebhookService::new(self.state.clone()).emit(WebhookEvent::new(
+            event_type,
+            "cluster",
+            None,
+            message,
+            serde_json::json!({
+                "metric": "memory",
+                "value": value,
+                "threshold": threshold,
+                "node_count": nodes.len(),
+            }),
+        ));
         Ok(())
     }
 
+    /// 获取节点健康度
+    ///
+    /// 综合资源利用率、连接存活与节点状态计算 0-100 的健康分
+    pub async fn get_node_health(&self, id: &str) -> anyhow::Result<NodeHealthResponse> {
+        let node = NodeEntity::find_by_id(id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("节点不存在"))?;
+
+        let connection = self.state.agent_manager().get(id).await;
+        let (heartbeat_elapsed, rtt_ms) = match &connection {
+            Some(conn) => (Some(conn.heartbeat_elapsed().await), conn.rtt_ms().await),
+            None => (None, None),
+        };
+
+        let usage = NodeUsage::from_node(&node);
+        let thresholds = self.state.node_alert_thresholds();
+        let (score, level, issues) = evaluate_health(
+            &node.status,
+            connection.is_some(),
+            heartbeat_elapsed,
+            usage,
+            &thresholds,
+        );
+
+        Ok(NodeHealthResponse {
+            node_id: node.id,
+            score,
+            level: level.to_string(),
+            online: connection.is_some(),
+            status: node.status,
+            cpu_usage: usage.cpu,
+            memory_usage: usage.memory,
+            disk_usage: usage.disk,
+            rtt_ms,
+            issues,
+            thresholds,
+        })
+    }
+
     /// 检查并更新超时的节点状态
     /// 将超过指定时间（秒）未收到心跳的在线节点标记为离线
     pub async fn check_and_update_timeout_nodes(&self, timeout_secs: u64) -> anyhow::Result<Vec<String>> {
@@ -341,6 +611,16 @@ impl NodeService {
             
             tracing::warn!("节点心跳超时，已标记为离线: node_id={}, last_heartbeat={:?}", 
                           node.id, node.last_heartbeat);
+
+            WebhookService::new(self.state.clone()).emit(WebhookEvent::new(
+                WebhookEventType::NodeOffline,
+                "node",
+                Some(&node.id),
+                format!("节点 {} 心跳超时，已标记为离线", node.hostname),
+                serde_json::json!({
+                    "last_heartbeat": node.last_heartbeat.map(|t| t.to_rfc3339()),
+                }),
+            ));
         }
 
         if !updated_node_ids.is_empty() {
@@ -350,6 +630,415 @@ impl NodeService {
         Ok(updated_node_ids)
     }
 
+    /// 配置节点 IPMI
+    ///
+    /// 保存前先查询一次电源状态，确认 BMC 可达且凭据有效
+    pub async fn set_ipmi_config(&self, id: &str, dto: UpdateNodeIpmiDto) -> anyhow::Result<NodeResponse> {
+        let db = &self.state.sea_db();
+
+        let node = NodeEntity::find_by_id(id.to_string())
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("节点不存在"))?;
+
+        // 密码只以密文形式保存和传递
+        let password = self.state.secrets.encrypt(&dto.password)?;
+        let output = self
+            .run_ipmitool(&dto.address, &dto.username, &password, &["chassis", "power", "status"])
+            .await
+            .map_err(|e| anyhow::anyhow!("IPMI 不可达或凭据无效: {}", e))?;
+        tracing::info!("节点 {} IPMI 验证通过: {}", id, output.trim());
+
+        let mut node_active: NodeActiveModel = node.into();
+        node_active.ipmi_address = Set(Some(dto.address));
+        node_active.ipmi_username = Set(Some(dto.username));
+        node_active.ipmi_password = Set(Some(password));
+        node_active.updated_at = Set(Utc::now().into());
+
+        let updated_node = node_active.update(db).await?;
+        Ok(NodeResponse::from(updated_node))
+    }
+
+    /// 查询节点电源状态
+    pub async fn get_power_state(&self, id: &str) -> anyhow::Result<NodePowerResponse> {
+        let node = self.find_node(id).await?;
+        let (address, username, password) = Self::ipmi_credentials(&node)?;
+
+        let output = self.run_ipmitool(address, username, password, &["chassis", "power", "status"]).await?;
+
+        Ok(NodePowerResponse {
+            node_id: node.id,
+            power_state: Self::parse_power_state(&output).to_string(),
+            message: Some(output.trim().to_string()),
+        })
+    }
+
+    /// 执行节点电源操作（on/off/cycle）
+    ///
+    /// 关机或重启时，若节点上仍有运行中的虚拟机，需要 force 才会执行
+    pub async fn power_action(
+        &self,
+        id: &str,
+        action: NodePowerAction,
+        force: bool,
+    ) -> anyhow::Result<NodePowerResponse> {
+        let db = &self.state.sea_db();
+        let node = self.find_node(id).await?;
+        let (address, username, password) = Self::ipmi_credentials(&node)?;
+
+        if action != NodePowerAction::On && !force {
+            let running_vms = VmEntity::find()
+                .filter(VmColumn::NodeId.eq(id))
+                .filter(VmColumn::Status.eq(VmStatus::Running.as_str()))
+                .count(db)
+                .await?;
+
+            if running_vms > 0 {
+                return Err(anyhow::anyhow!(
+                    "节点上还有 {} 台运行中的虚拟机，请先迁移或停止，或使用 force 强制执行",
+                    running_vms
+                ));
+            }
+        }
+
+        let output = self.run_ipmitool(
+            address,
+            username,
+            password,
+            &["chassis", "power", action.as_ipmi_command()],
+        )
+        .await?;
+
+        tracing::warn!("节点 {} 已执行电源操作 {:?}: {}", id, action, output.trim());
+
+        let power_state = match action {
+            NodePowerAction::Off => "off",
+            NodePowerAction::On | NodePowerAction::Cycle => "on",
+        };
+
+        Ok(NodePowerResponse {
+            node_id: node.id,
+            power_state: power_state.to_string(),
+            message: Some(output.trim().to_string()),
+        })
+    }
+
+    /// 配置节点关机策略
+    pub async fn set_shutdown_policy(
+        &self,
+        id: &str,
+        policy: HostShutdownPolicy,
+    ) -> anyhow::Result<NodeResponse> {
+        let node = self.find_node(id).await?;
+
+        let mut node_active: NodeActiveModel = node.into();
+        node_active.shutdown_policy = Set(policy.as_str().to_string());
+        node_active.updated_at = Set(Utc::now().into());
+
+        let updated_node = node_active.update(&self.state.sea_db()).await?;
+        Ok(NodeResponse::from(updated_node))
+    }
+
+    /// 排空节点
+    ///
+    /// 计划内重启前调用，Agent 按策略挂起或关闭节点上所有运行中的虚拟机，
+    /// 处理成功的虚拟机在数据库中标记为已停止（挂起的虚拟机下次启动时自动恢复）
+    pub async fn drain_node(
+        &self,
+        id: &str,
+        policy: Option<HostShutdownPolicy>,
+    ) -> anyhow::Result<DrainNodeResponse> {
+        let db = &self.state.sea_db();
+        let node = self.find_node(id).await?;
+
+        let policy = match policy {
+            Some(policy) => policy,
+            None => node
+                .shutdown_policy
+                .parse()
+                .map_err(|e: String| anyhow::anyhow!(e))?,
+        };
+
+        tracing::warn!("开始排空节点 {}，策略: {}", id, policy.as_str());
+
+        let response_msg = self
+            .state
+            .agent_manager()
+            .call(
+                id,
+                "drain_node",
+                serde_json::to_value(&DrainNodeRequest { policy })?,
+                DRAIN_TIMEOUT,
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("排空节点失败: {}", e))?;
+
+        let response: DrainNodeResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+
+        for drained in response.vms.iter().filter(|vm| vm.action != "failed") {
+            let vm = match VmEntity::find_by_id(drained.vm_id.clone()).one(db).await? {
+                Some(vm) if vm.node_id.as_deref() == Some(id) => vm,
+                _ => continue,
+            };
+
+            let mut vm_active: VmActiveModel = vm.into();
+            vm_active.status = Set(VmStatus::Stopped.as_str().to_string());
+            vm_active.updated_at = Set(Utc::now().into());
+            vm_active.update(db).await?;
+
+            let message = if drained.action == "suspended" {
+                "宿主机维护，虚拟机已挂起到磁盘，启动后自动恢复"
+            } else {
+                "宿主机维护，虚拟机已关机"
+            };
+            self.state
+                .frontend_manager()
+                .broadcast(FrontendMessage::VmStatusUpdate {
+                    vm_id: drained.vm_id.clone(),
+                    status: VmStatus::Stopped.as_str().to_string(),
+                    message: Some(message.to_string()),
+                })
+                .await;
+        }
+
+        let failed = response.vms.iter().filter(|vm| vm.action == "failed").count();
+        tracing::warn!(
+            "节点 {} 排空完成: 共 {} 台，失败 {} 台",
+            id,
+            response.vms.len(),
+            failed
+        );
+
+        Ok(response)
+    }
+
+    /// 让 Agent 重新检测节点能力与资源信息，并更新节点记录
+    ///
+    /// 节点能力只在 Agent 注册时上报，升级 QEMU 或安装 swtpm、Open vSwitch 后调用即可生效，无需重启 Agent
+    pub async fn refresh_capabilities(&self, id: &str) -> anyhow::Result<NodeResponse> {
+        self.find_node(id).await?;
+
+        let response_msg = self
+            .state
+            .agent_rpc()
+            .call(id, "refresh_capabilities", serde_json::json!({}), REFRESH_TIMEOUT)
+            .await
+            .map_err(|e| anyhow::anyhow!("调用 Agent 失败: {}", e))?;
+
+        let mut info: NodeResourceInfo = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+        // 以请求的节点为准，Agent 配置的节点 ID 与记录不一致时也不会更新到其他节点
+        info.node_id = id.to_string();
+        self.update_node_resource_info(&info).await?;
+
+        tracing::info!(
+            "节点 {} 能力已刷新: {}",
+            id,
+            info.hypervisor_version.as_deref().unwrap_or("unknown")
+        );
+        self.get_node(id).await
+    }
+
+    /// 查询节点上虚拟机的恢复状态（是否存在挂起镜像）
+    pub async fn get_restore_state(&self, id: &str) -> anyhow::Result<GetRestoreStateResponse> {
+        self.find_node(id).await?;
+
+        let response_msg = self
+            .state
+            .agent_manager()
+            .call(id, "get_restore_state", serde_json::json!({}), Duration::from_secs(30))
+            .await
+            .map_err(|e| anyhow::anyhow!("查询恢复状态失败: {}", e))?;
+
+        Ok(serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?)
+    }
+
+    /// Agent 重连后在后台对账节点上的虚拟机状态
+    ///
+    /// 注册阶段新连接尚未生效，调用 Agent 会发到旧连接（或没有连接），因此等到管理器中的连接
+    /// 不再是 `previous` 后才开始对账
+    pub fn spawn_reconnect_reconciliation(
+        state: AppState,
+        node_id: String,
+        previous: Option<Arc<AgentConnection>>,
+    ) {
+        tokio::spawn(async move {
+            let deadline = tokio::time::Instant::now() + RECONNECT_READY_TIMEOUT;
+            loop {
+                match state.agent_manager().get(&node_id).await {
+                    Some(current) if previous.as_ref().is_none_or(|p| !Arc::ptr_eq(p, &current)) => break,
+                    _ if tokio::time::Instant::now() >= deadline => {
+                        tracing::warn!("节点 {} 重连后连接未就绪，跳过虚拟机状态对账", node_id);
+                        return;
+                    }
+                    _ => tokio::time::sleep(Duration::from_millis(200)).await,
+                }
+            }
+
+            match NodeService::new(state).reconcile_vms_on_reconnect(&node_id).await {
+                Ok(0) => {}
+                Ok(corrected) => tracing::info!("节点 {} 重连对账: 已纠正 {} 台虚拟机", node_id, corrected),
+                Err(e) => tracing::error!("节点 {} 重连对账失败: {}", node_id, e),
+            }
+        });
+    }
+
+    /// 按 libvirt 中的实际状态修复节点上虚拟机的状态漂移，返回纠正的虚拟机数量
+    ///
+    /// 处于过渡状态（starting/stopping/restarting）的虚拟机更新为实际状态；数据库中分配到本节点、
+    /// 应已在 libvirt 中定义（运行、暂停或过渡状态）但节点上不存在的虚拟机标记为 error。
+    /// 已停止的虚拟机可能从未启动过，节点上未定义是正常情况，不做处理
+    pub async fn reconcile_vms_on_reconnect(&self, node_id: &str) -> anyhow::Result<usize> {
+        let db = &self.state.sea_db();
+
+        let response_msg = self
+            .state
+            .agent_rpc()
+            .call(
+                node_id,
+                "list_vms",
+                serde_json::to_value(&ListVmsRequest { node_id: node_id.to_string() })?,
+                Duration::from_secs(30),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("查询节点虚拟机列表失败: {}", e))?;
+        let response: ListVmsResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+        let domain_states: HashMap<String, String> = response
+            .vms
+            .into_iter()
+            .map(|domain| (domain.vm_id, domain.state))
+            .collect();
+
+        let mut statuses = TRANSIENT_VM_STATUSES.to_vec();
+        statuses.extend([VmStatus::Running.as_str(), VmStatus::Paused.as_str()]);
+        let vms = VmEntity::find()
+            .filter(VmColumn::NodeId.eq(node_id))
+            .filter(VmColumn::Status.is_in(statuses))
+            .all(db)
+            .await?;
+
+        let mut corrected = 0;
+        for vm in vms {
+            let (actual, message) = match domain_states.get(&vm.id) {
+                Some(state) if TRANSIENT_VM_STATUSES.contains(&vm.status.as_str()) => {
+                    (domain_state_to_status(state), "Agent 重连后已按节点实际状态纠正")
+                }
+                // 稳定状态的偏差由定期对账处理
+                Some(_) => continue,
+                None => (VmStatus::Error, "Agent 重连后发现节点上不存在该虚拟机"),
+            };
+            if vm.status == actual.as_str() {
+                continue;
+            }
+
+            // 仅在状态未被其它操作改动时更新
+            let result = VmEntity::update_many()
+                .col_expr(VmColumn::Status, Expr::value(actual.as_str()))
+                .col_expr(VmColumn::UpdatedAt, Expr::value(Utc::now()))
+                .filter(VmColumn::Id.eq(vm.id.clone()))
+                .filter(VmColumn::Status.eq(vm.status.clone()))
+                .exec(db)
+                .await?;
+            if result.rows_affected == 0 {
+                continue;
+            }
+
+            tracing::warn!(
+                "重连对账: 虚拟机 {} 数据库状态为 {}，节点 {} 实际为 {:?}，已更新为 {}",
+                vm.id,
+                vm.status,
+                node_id,
+                domain_states.get(&vm.id),
+                actual.as_str()
+            );
+            self.state
+                .frontend_manager()
+                .broadcast(FrontendMessage::VmStatusUpdate {
+                    vm_id: vm.id.clone(),
+                    status: actual.as_str().to_string(),
+                    message: Some(message.to_string()),
+                })
+                .await;
+            corrected += 1;
+        }
+
+        Ok(corrected)
+    }
+
+    /// 查询节点模型
+    async fn find_node(&self, id: &str) -> anyhow::Result<NodeModel> {
+        NodeEntity::find_by_id(id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("节点不存在"))
+    }
+
+    /// 提取节点 IPMI 凭据，密码为加密后的密文
+    fn ipmi_credentials(node: &NodeModel) -> anyhow::Result<(&str, &str, &str)> {
+        match (&node.ipmi_address, &node.ipmi_username, &node.ipmi_password) {
+            (Some(address), Some(username), Some(password)) => Ok((address, username, password)),
+            _ => Err(anyhow::anyhow!("节点未配置 IPMI")),
+        }
+    }
+
+    /// 调用 ipmitool
+    ///
+    /// `encrypted_password` 为入库的密文，仅在此处解密；
+    /// 密码通过 IPMI_PASSWORD 环境变量（-E）传递，避免出现在进程参数列表中
+    async fn run_ipmitool(
+        &self,
+        address: &str,
+        username: &str,
+        encrypted_password: &str,
+        args: &[&str],
+    ) -> anyhow::Result<String> {
+        let password = self.state.secrets.decrypt(encrypted_password)?;
+        let mut cmd = tokio::process::Command::new("ipmitool");
+        cmd.args(["-I", "lanplus", "-H", address, "-U", username, "-E"])
+            .args(args)
+            .env("IPMI_PASSWORD", &password)
+            .kill_on_drop(true);
+
+        let output = tokio::time::timeout(IPMI_TIMEOUT, cmd.output())
+            .await
+            .map_err(|_| anyhow::anyhow!("ipmitool 执行超时"))?
+            .map_err(|e| anyhow::anyhow!("执行 ipmitool 失败: {}", e))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("ipmitool 返回错误: {}", stderr.trim()));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// 解析 `chassis power status` 输出（例如：Chassis Power is on）
+    fn parse_power_state(output: &str) -> &'static str {
+        let output = output.to_lowercase();
+        if output.contains("power is on") {
+            "on"
+        } else if output.contains("power is off") {
+            "off"
+        } else {
+            "unknown"
+        }
+    }
+
     /// 获取节点统计信息
     pub async fn get_stats(&self) -> anyhow::Result<NodeStatsResponse> {
         let db = &self.state.sea_db();
@@ -397,3 +1086,230 @@ impl NodeService {
         })
     }
 }
+
+/// 根据心跳计算节点状态
+///
+/// libvirt 未连接时为 error（降级）；恢复连接或重新上线时回到 online；维护状态保持不变
+fn heartbeat_status(current: &str, hypervisor_connected: bool) -> &'static str {
+    if !hypervisor_connected {
+        NodeStatus::Error.as_str()
+    } else if current == NodeStatus::Maintenance.as_str() {
+        NodeStatus::Maintenance.as_str()
+    } else {
+        NodeStatus::Online.as_str()
+    }
+}
+
+/// 节点各项资源利用率（百分比）
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct NodeUsage {
+    cpu: Option<f64>,
+    memory: Option<f64>,
+    disk: Option<f64>,
+}
+
+impl NodeUsage {
+    fn from_node(node: &NodeModel) -> Self {
+        Self {
+            cpu: node.cpu_usage.map(|v| v as f64),
+            memory: usage_percent(node.memory_used, node.memory_total),
+            disk: usage_percent(node.disk_used, node.disk_total),
+        }
+    }
+
+    /// (指标名, 展示名, 利用率, 阈值)
+    fn metrics(&self, thresholds: &NodeAlertThresholds) -> [(&'static str, &'static str, Option<f64>, f64); 3] {
+        [
+            ("cpu", "CPU", self.cpu, thresholds.cpu_percent),
+            ("memory", "内存", self.memory, thresholds.memory_percent),
+            ("disk", "磁盘", self.disk, thresholds.disk_percent),
+        ]
+    }
+}
+
+fn usage_percent(used: Option<i64>, total: Option<i64>) -> Option<f64> {
+    match (used, total) {
+        (Some(used), Some(total)) if total > 0 => Some(used as f64 / total as f64 * 100.0),
+        _ => None,
+    }
+}
+
+/// 集群内存利用率（百分比），汇总所有上报了内存数据的节点
+fn cluster_memory_percent(
+    nodes: impl IntoIterator<Item = (Option<i64>, Option<i64>)>,
+) -> Option<f64> {
+    let (used, total) = nodes
+        .into_iter()
+        .fold((0i64, 0i64), |(used_sum, total_sum), memory| match memory {
+            (Some(used), Some(total)) => (used_sum + used, total_sum + total),
+            _ => (used_sum, total_sum),
+        });
+    usage_percent(Some(used), Some(total))
+}
+
+/// 计算健康分，返回 (分数, 等级, 扣分原因)
+///
+/// 离线直接为 0；错误状态扣 50，心跳不活跃扣 20；
+/// 各项利用率超过 70% 后线性扣分，满载扣 25
+fn evaluate_health(
+    status: &str,
+    online: bool,
+    heartbeat_elapsed: Option<u64>,
+    usage: NodeUsage,
+    thresholds: &NodeAlertThresholds,
+) -> (u8, &'static str, Vec<String>) {
+    if !online || status == NodeStatus::Offline.as_str() {
+        return (0, "critical", vec!["节点离线".to_string()]);
+    }
+
+    let mut score = 100.0;
+    let mut issues = Vec::new();
+    let mut exceeded = false;
+
+    let is_error = status == NodeStatus::Error.as_str();
+    if is_error {
+        score -= 50.0;
+        issues.push("节点处于错误状态".to_string());
+    }
+
+    if let Some(elapsed) = heartbeat_elapsed.filter(|e| *e > HEARTBEAT_STALE_SECS) {
+        score -= 20.0;
+        issues.push(format!("心跳已 {} 秒未更新", elapsed));
+    }
+
+    for (_, label, value, threshold) in usage.metrics(thresholds) {
+        let Some(value) = value else {
+            issues.push(format!("未上报{}利用率", label));
+            continue;
+        };
+
+        if value > USAGE_PENALTY_START {
+            let ratio = ((value - USAGE_PENALTY_START) / (100.0 - USAGE_PENALTY_START)).min(1.0);
+            score -= ratio * USAGE_PENALTY_MAX;
+        }
+        if value >= threshold {
+            exceeded = true;
+            issues.push(format!("{}利用率 {:.1}% 超过阈值 {:.0}%", label, value, threshold));
+        }
+    }
+
+    let score = score.clamp(0.0, 100.0).round() as u8;
+    let level = if is_error || score < 40 {
+        "critical"
+    } else if exceeded || score < 70 {
+        "warning"
+    } else {
+        "healthy"
+    };
+
+    (score, level, issues)
+}
+
+/// 越过或恢复阈值的指标
+#[derive(Debug, Clone, PartialEq)]
+struct ThresholdCrossing {
+    metric: &'static str,
+    label: &'static str,
+    value: f64,
+    threshold: f64,
+    resolved: bool,
+}
+
+/// 比较前后两次利用率，只在状态变化时产生告警，避免每次上报重复推送
+fn threshold_crossings(
+    previous: NodeUsage,
+    current: NodeUsage,
+    thresholds: &NodeAlertThresholds,
+) -> Vec<ThresholdCrossing> {
+    let before = previous.metrics(thresholds);
+    current
+        .metrics(thresholds)
+        .into_iter()
+        .zip(before)
+        .filter_map(|((metric, label, value, threshold), (_, _, prev, _))| {
+            let value = value?;
+            let was_over = prev.map_or(false, |p| p >= threshold);
+            let is_over = value >= threshold;
+            (was_over != is_over).then_some(ThresholdCrossing {
+                metric,
+                label,
+                value,
+                threshold,
+                resolved: !is_over,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(cpu: f64, memory: f64, disk: f64) -> NodeUsage {
+        NodeUsage {
+            cpu: Some(cpu),
+            memory: Some(memory),
+            disk: Some(disk),
+        }
+    }
+
+    #[test]
+    fn test_evaluate_health() {
+        let t = NodeAlertThresholds::default();
+
+        let (score, level, issues) = evaluate_health("online", true, Some(10), usage(20.0, 50.0, 40.0), &t);
+        assert_eq!((score, level), (100, "healthy"));
+        assert!(issues.is_empty());
+
+        // 内存 95%：扣 (95-70)/30*25 ≈ 21 分，并超过阈值
+        let (score, level, issues) = evaluate_health("online", true, Some(10), usage(20.0, 95.0, 40.0), &t);
+        assert_eq!((score, level), (79, "warning"));
+        assert!(issues[0].contains("内存"));
+
+        let (score, level, _) = evaluate_health("online", false, None, usage(20.0, 50.0, 40.0), &t);
+        assert_eq!((score, level), (0, "critical"));
+
+        let (score, level, _) = evaluate_health("error", true, Some(200), usage(20.0, 50.0, 40.0), &t);
+        assert_eq!((score, level), (30, "critical"));
+    }
+
+    #[test]
+    fn test_heartbeat_status() {
+        assert_eq!(heartbeat_status("online", false), "error");
+        assert_eq!(heartbeat_status("error", true), "online");
+        assert_eq!(heartbeat_status("offline", true), "online");
+        assert_eq!(heartbeat_status("maintenance", true), "maintenance");
+    }
+
+    #[test]
+    fn test_threshold_crossings() {
+        let t = NodeAlertThresholds::default();
+
+        // 首次上报即超过阈值
+        let crossings = threshold_crossings(NodeUsage::default(), usage(10.0, 92.0, 10.0), &t);
+        assert_eq!(crossings.len(), 1);
+        assert_eq!(crossings[0].metric, "memory");
+        assert!(!crossings[0].resolved);
+
+        // 持续超过阈值不重复告警
+        assert!(threshold_crossings(usage(10.0, 92.0, 10.0), usage(10.0, 95.0, 10.0), &t).is_empty());
+
+        // 恢复
+        let crossings = threshold_crossings(usage(10.0, 95.0, 10.0), usage(10.0, 60.0, 10.0), &t);
+        assert_eq!(crossings.len(), 1);
+        assert!(crossings[0].resolved);
+    }
+
+    #[test]
+    fn test_cluster_memory_percent() {
+        let gib = 1024 * 1024 * 1024;
+        let nodes = [
+            (Some(6 * gib), Some(8 * gib)),
+            (Some(2 * gib), Some(8 * gib)),
+            // 未上报内存的节点不参与计算
+            (None, Some(8 * gib)),
+        ];
+        assert_eq!(cluster_memory_percent(nodes), Some(50.0));
+        assert_eq!(cluster_memory_percent([(None, None)]), None);
+    }
+}
@@ -0,0 +1,535 @@
+/// 虚拟机放置调度服务
+///
+/// 根据亲和组规则在候选节点中选择放置节点：
+/// 反亲和为硬约束（违反的节点直接排除），亲和为软约束（满足的节点优先）。
+/// 创建虚拟机未指定节点时，先按剩余 vCPU 与内存和放置策略筛选排序候选节点。
+/// 节点容量按超分比例放大后计算
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use common::ws_rpc::{validate_cpu_placement, CpuPinning, NumaConfig};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::app_state::AppState;
+use crate::config::{OvercommitRatios, PlacementStrategy};
+use crate::db::models::affinity_group::{AffinityPolicy, Entity as AffinityGroupEntity, Column as AffinityGroupColumn};
+use crate::db::models::affinity_group_member::{Entity as AffinityMemberEntity, Column as AffinityMemberColumn};
+use crate::db::models::node::{
+    Column as NodeColumn, Entity as NodeEntity, Model as NodeModel, NodeStatus,
+};
+use crate::db::models::vm::{Entity as VmEntity, Column as VmColumn, Model as VmModel, VmStatus};
+
+/// 由单个亲和组推导出的放置约束
+#[derive(Debug, Clone)]
+struct PlacementRule {
+    group_name: String,
+    policy: AffinityPolicy,
+    /// 节点 ID -> 该节点上的组内其他虚拟机名称
+    occupied: HashMap<String, Vec<String>>,
+}
+
+pub struct SchedulerService {
+    state: AppState,
+}
+
+impl SchedulerService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// 从候选节点中选择放置节点
+    ///
+    /// - vm_id: 已存在的虚拟机（迁移时），其所属亲和组自动参与计算
+    /// - group_ids: 额外参与计算的亲和组（创建虚拟机时尚未加入组）
+    ///
+    /// 没有节点满足规则时返回的错误中包含阻止放置的规则
+    pub async fn select_node(
+        &self,
+        vm_id: Option<&str>,
+        group_ids: &[String],
+        candidates: &[String],
+    ) -> anyhow::Result<String> {
+        if candidates.is_empty() {
+            return Err(anyhow::anyhow!("没有可用的候选节点"));
+        }
+
+        let rules = self.load_rules(vm_id, group_ids).await?;
+        let ranked = rank_candidates(candidates, &rules).map_err(|e| anyhow::anyhow!(e))?;
+        let selected = ranked[0].clone();
+
+        for rule in rules.iter().filter(|r| r.policy == AffinityPolicy::Affinity) {
+            if !rule.occupied.is_empty() && !rule.occupied.contains_key(&selected) {
+                warn!("节点 {} 不满足亲和组「{}」，该规则为软约束，继续放置", selected, rule.group_name);
+            }
+        }
+
+        info!("调度选择节点: {}", selected);
+        Ok(selected)
+    }
+
+    /// 为新建虚拟机自动选择放置节点
+    ///
+    /// 候选为在线且已上报 vCPU 与内存总量的节点（需要 TPM 时仅限安装了 swtpm 的节点，
+    /// 配置了 CPU 绑定或 NUMA 时仅限拓扑中存在对应 CPU 和 NUMA 节点的节点），
+    /// 已分配量包含放置在节点上但尚未启动的虚拟机；按放置策略排序后再应用亲和组规则
+    pub async fn place_new_vm(
+        &self,
+        vcpu: u32,
+        memory_mb: u64,
+        tpm: bool,
+        cpu_pinning: Option<&CpuPinning>,
+        numa: Option<&NumaConfig>,
+        group_ids: &[String],
+    ) -> anyhow::Result<String> {
+        let cpu_placement = cpu_pinning.is_some() || numa.is_some();
+        let db = &self.state.sea_db();
+
+        let nodes = NodeEntity::find()
+            .filter(NodeColumn::Status.eq(NodeStatus::Online.as_str()))
+            .all(db)
+            .await?;
+        let vms = VmEntity::find().all(db).await?;
+
+        let capacities: Vec<NodeCapacity> = nodes
+            .iter()
+            .filter(|node| !tpm || node.has_swtpm)
+            .filter(|node| {
+                !cpu_placement
+                    || node.numa_nodes().is_some_and(|host| {
+                        validate_cpu_placement(vcpu, cpu_pinning, numa, &host).is_ok()
+                    })
+            })
+            .filter_map(|node| node_capacity(node, &vms, None, self.state.overcommit))
+            .collect();
+
+        // 轮转从最近创建的虚拟机所在节点之后开始
+        let last_node_id = vms
+            .iter()
+            .filter(|vm| vm.node_id.is_some())
+            .max_by_key(|vm| vm.created_at)
+            .and_then(|vm| vm.node_id.as_deref());
+
+        let strategy = self.state.placement_strategy;
+        let ordered = order_by_strategy(
+            &capacities,
+            vcpu as i64,
+            memory_mb as i64,
+            strategy,
+            last_node_id,
+        )
+        .map_err(|e| {
+            let tpm_requirement = if tpm { "、swtpm" } else { "" };
+            let cpu_requirement = if cpu_placement { "、可满足的 CPU 绑定" } else { "" };
+            anyhow::anyhow!(
+                "没有可放置虚拟机的节点（需要 {} vCPU、{} MB 内存{}{}）: {}",
+                vcpu,
+                memory_mb,
+                tpm_requirement,
+                cpu_requirement,
+                e
+            )
+        })?;
+
+        info!("放置策略 {} 的候选节点: {:?}", strategy.as_str(), ordered);
+        self.select_node(None, group_ids, &ordered).await
+    }
+
+    /// 校验节点在超分比例下能否容纳虚拟机
+    ///
+    /// vm_id 为已存在的虚拟机（启动时），其自身不计入已分配量。
+    /// 节点未上报 vCPU 或内存总量时无法计算容量，跳过校验
+    pub async fn check_capacity(
+        &self,
+        node_id: &str,
+        vm_id: Option<&str>,
+        vcpu: u32,
+        memory_mb: u64,
+    ) -> anyhow::Result<()> {
+        let db = &self.state.sea_db();
+
+        let node = NodeEntity::find_by_id(node_id.to_string())
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("节点 {} 不存在", node_id))?;
+        let vms = VmEntity::find().all(db).await?;
+
+        let ratios = self.state.overcommit;
+        let Some(capacity) = node_capacity(&node, &vms, vm_id, ratios) else {
+            warn!("节点 {} 未上报 vCPU 或内存总量，跳过超分校验", node_id);
+            return Ok(());
+        };
+
+        if capacity.fits(vcpu as i64, memory_mb as i64) {
+            return Ok(());
+        }
+
+        let err = CapacityExceeded {
+            node_id: capacity.node_id,
+            requested_vcpu: vcpu as i64,
+            requested_memory_mb: memory_mb as i64,
+            vcpu_allocated: capacity.vcpu_allocated,
+            vcpu_capacity: capacity.vcpu_total,
+            memory_allocated_mb: capacity.memory_allocated_mb,
+            memory_capacity_mb: capacity.memory_total_mb,
+            cpu_overcommit_ratio: ratios.cpu,
+            memory_overcommit_ratio: ratios.memory,
+        };
+        warn!("{}", err);
+        Err(err.into())
+    }
+
+    /// 加载虚拟机相关的亲和组规则
+    async fn load_rules(&self, vm_id: Option<&str>, group_ids: &[String]) -> anyhow::Result<Vec<PlacementRule>> {
+        let db = &self.state.sea_db();
+
+        let mut ids: HashSet<String> = group_ids.iter().cloned().collect();
+        if let Some(vm_id) = vm_id {
+            let memberships = AffinityMemberEntity::find()
+                .filter(AffinityMemberColumn::VmId.eq(vm_id))
+                .all(db)
+                .await?;
+            ids.extend(memberships.into_iter().map(|m| m.group_id));
+        }
+
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let groups = AffinityGroupEntity::find()
+            .filter(AffinityGroupColumn::Id.is_in(ids.iter().cloned()))
+            .all(db)
+            .await?;
+
+        if groups.len() != ids.len() {
+            let found: HashSet<&String> = groups.iter().map(|g| &g.id).collect();
+            let missing: Vec<&String> = ids.iter().filter(|id| !found.contains(id)).collect();
+            return Err(anyhow::anyhow!("亲和组不存在: {:?}", missing));
+        }
+
+        let mut rules = Vec::with_capacity(groups.len());
+        for group in groups {
+            let member_ids: Vec<String> = AffinityMemberEntity::find()
+                .filter(AffinityMemberColumn::GroupId.eq(&group.id))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| m.vm_id)
+                .filter(|id| Some(id.as_str()) != vm_id)
+                .collect();
+
+            let mut occupied: HashMap<String, Vec<String>> = HashMap::new();
+            if !member_ids.is_empty() {
+                let members = VmEntity::find()
+                    .filter(VmColumn::Id.is_in(member_ids))
+                    .all(db)
+                    .await?;
+
+                for member in members {
+                    for node_id in occupied_nodes(&member) {
+                        occupied.entry(node_id).or_default().push(member.name.clone());
+                    }
+                }
+            }
+
+            rules.push(PlacementRule {
+                group_name: group.name,
+                policy: AffinityPolicy::from(group.policy),
+                occupied,
+            });
+        }
+
+        Ok(rules)
+    }
+}
+
+/// 虚拟机占用的节点，迁移中的虚拟机同时占用目标节点
+fn occupied_nodes(vm: &VmModel) -> Vec<String> {
+    let mut nodes: Vec<String> = vm.node_id.iter().cloned().collect();
+
+    if vm.status == VmStatus::Migrating.as_str() {
+        if let Some(target) = vm
+            .metadata
+            .as_ref()
+            .and_then(|m| m.get("migration_target_node_id"))
+            .and_then(|v| v.as_str())
+        {
+            nodes.push(target.to_string());
+        }
+    }
+
+    nodes
+}
+
+/// 节点 vCPU 或内存超出超分上限，API 层返回 409 并附带分配明细供前端展示
+#[derive(Debug, Clone, Serialize)]
+pub struct CapacityExceeded {
+    pub node_id: String,
+    pub requested_vcpu: i64,
+    pub requested_memory_mb: i64,
+    /// 节点上其他虚拟机已分配的 vCPU
+    pub vcpu_allocated: i64,
+    /// 线程数乘以 vCPU 超分比例
+    pub vcpu_capacity: i64,
+    pub memory_allocated_mb: i64,
+    /// 内存总量乘以内存超分比例
+    pub memory_capacity_mb: i64,
+    pub cpu_overcommit_ratio: f64,
+    pub memory_overcommit_ratio: f64,
+}
+
+impl fmt::Display for CapacityExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "节点 {} 资源不足：需要 {} vCPU、{} MB 内存，已分配 {}/{} vCPU（超分比例 {}）、{}/{} MB 内存（超分比例 {}）",
+            self.node_id,
+            self.requested_vcpu,
+            self.requested_memory_mb,
+            self.vcpu_allocated,
+            self.vcpu_capacity,
+            self.cpu_overcommit_ratio,
+            self.memory_allocated_mb,
+            self.memory_capacity_mb,
+            self.memory_overcommit_ratio
+        )
+    }
+}
+
+impl std::error::Error for CapacityExceeded {}
+
+/// 按超分比例计算节点容量，已分配量为占用该节点的虚拟机（不含 exclude_vm_id）之和
+///
+/// 节点未上报 vCPU 或内存总量时返回 None
+fn node_capacity(
+    node: &NodeModel,
+    vms: &[VmModel],
+    exclude_vm_id: Option<&str>,
+    ratios: OvercommitRatios,
+) -> Option<NodeCapacity> {
+    let vcpu_physical = node.cpu_threads.or(node.cpu_cores)? as f64;
+    let memory_physical_mb = (node.memory_total? / (1024 * 1024)) as f64;
+
+    let (vcpu_allocated, memory_allocated_mb) = vms
+        .iter()
+        .filter(|vm| Some(vm.id.as_str()) != exclude_vm_id)
+        .filter(|vm| occupied_nodes(vm).contains(&node.id))
+        .fold((0, 0), |(cpu, memory), vm| {
+            (cpu + vm.vcpu as i64, memory + vm.memory_mb)
+        });
+
+    Some(NodeCapacity {
+        node_id: node.id.clone(),
+        vcpu_total: (vcpu_physical * ratios.cpu).floor() as i64,
+        memory_total_mb: (memory_physical_mb * ratios.memory).floor() as i64,
+        vcpu_allocated,
+        memory_allocated_mb,
+    })
+}
+
+/// 节点的 vCPU 与内存容量（已按超分比例放大）及已分配量（内存单位 MB）
+#[derive(Debug, Clone)]
+struct NodeCapacity {
+    node_id: String,
+    vcpu_total: i64,
+    memory_total_mb: i64,
+    vcpu_allocated: i64,
+    memory_allocated_mb: i64,
+}
+
+impl NodeCapacity {
+    fn fits(&self, vcpu: i64, memory_mb: i64) -> bool {
+        self.vcpu_allocated + vcpu <= self.vcpu_total
+            && self.memory_allocated_mb + memory_mb <= self.memory_total_mb
+    }
+
+    /// 放置后 vCPU 与内存分配率的平均值
+    fn load_after(&self, vcpu: i64, memory_mb: i64) -> f64 {
+        let cpu = (self.vcpu_allocated + vcpu) as f64 / self.vcpu_total as f64;
+        let memory = (self.memory_allocated_mb + memory_mb) as f64 / self.memory_total_mb as f64;
+        (cpu + memory) / 2.0
+    }
+}
+
+/// 按放置策略排列剩余资源足够的节点
+///
+/// least-allocated 按放置后的分配率升序；round-robin 按节点 ID 排序，
+/// 从上一次放置节点之后的节点开始。没有节点容纳得下时返回各节点的剩余量
+fn order_by_strategy(
+    capacities: &[NodeCapacity],
+    vcpu: i64,
+    memory_mb: i64,
+    strategy: PlacementStrategy,
+    last_node_id: Option<&str>,
+) -> Result<Vec<String>, String> {
+    if capacities.is_empty() {
+        return Err("没有在线且已上报资源信息的节点".to_string());
+    }
+
+    let mut fitting: Vec<&NodeCapacity> = capacities
+        .iter()
+        .filter(|c| c.fits(vcpu, memory_mb))
+        .collect();
+    if fitting.is_empty() {
+        let free: Vec<String> = capacities
+            .iter()
+            .map(|c| {
+                format!(
+                    "节点 {} 剩余 {} vCPU、{} MB 内存",
+                    c.node_id,
+                    c.vcpu_total - c.vcpu_allocated,
+                    c.memory_total_mb - c.memory_allocated_mb
+                )
+            })
+            .collect();
+        return Err(free.join("; "));
+    }
+
+    fitting.sort_by_key(|c| c.node_id.as_str());
+    match strategy {
+        // 稳定排序，分配率相同时保持节点 ID 顺序
+        PlacementStrategy::LeastAllocated => fitting.sort_by(|a, b| {
+            a.load_after(vcpu, memory_mb)
+                .total_cmp(&b.load_after(vcpu, memory_mb))
+        }),
+        PlacementStrategy::RoundRobin => {
+            if let Some(last) = last_node_id {
+                let start = fitting
+                    .iter()
+                    .position(|c| c.node_id.as_str() > last)
+                    .unwrap_or(0);
+                fitting.rotate_left(start);
+            }
+        }
+    }
+
+    Ok(fitting.into_iter().map(|c| c.node_id.clone()).collect())
+}
+
+/// 按规则过滤并排序候选节点
+///
+/// 排除违反反亲和的节点，剩余节点按满足的亲和组数量降序（同分保持原顺序）；
+/// 全部被排除时返回每个节点被哪条规则阻止
+fn rank_candidates(candidates: &[String], rules: &[PlacementRule]) -> Result<Vec<String>, String> {
+    let mut blocked = Vec::new();
+    let mut allowed: Vec<(usize, &String)> = Vec::new();
+
+    for node_id in candidates {
+        let violation = rules
+            .iter()
+            .filter(|r| r.policy == AffinityPolicy::AntiAffinity)
+            .find_map(|r| r.occupied.get(node_id).map(|vms| (r, vms)));
+
+        match violation {
+            Some((rule, vms)) => blocked.push(format!(
+                "节点 {} 违反反亲和组「{}」（已有成员: {}）",
+                node_id,
+                rule.group_name,
+                vms.join(", ")
+            )),
+            None => {
+                let score = rules
+                    .iter()
+                    .filter(|r| r.policy == AffinityPolicy::Affinity && r.occupied.contains_key(node_id))
+                    .count();
+                allowed.push((score, node_id));
+            }
+        }
+    }
+
+    if allowed.is_empty() {
+        return Err(format!("没有满足亲和规则的节点: {}", blocked.join("; ")));
+    }
+
+    allowed.sort_by_key(|a| std::cmp::Reverse(a.0));
+    Ok(allowed.into_iter().map(|(_, node_id)| node_id.clone()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, policy: AffinityPolicy, occupied: &[(&str, &str)]) -> PlacementRule {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        for (node, vm) in occupied {
+            map.entry(node.to_string()).or_default().push(vm.to_string());
+        }
+        PlacementRule {
+            group_name: name.to_string(),
+            policy,
+            occupied: map,
+        }
+    }
+
+    fn nodes(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn capacity(node_id: &str, vcpu: (i64, i64), memory_mb: (i64, i64)) -> NodeCapacity {
+        NodeCapacity {
+            node_id: node_id.to_string(),
+            vcpu_total: vcpu.1,
+            memory_total_mb: memory_mb.1,
+            vcpu_allocated: vcpu.0,
+            memory_allocated_mb: memory_mb.0,
+        }
+    }
+
+    #[test]
+    fn test_rank_candidates() {
+        let rules = vec![
+            rule("db-ha", AffinityPolicy::AntiAffinity, &[("node-a", "db-1")]),
+            rule("web", AffinityPolicy::Affinity, &[("node-c", "web-1")]),
+        ];
+
+        // 反亲和排除 node-a，亲和优先 node-c
+        assert_eq!(
+            rank_candidates(&nodes(&["node-a", "node-b", "node-c"]), &rules).unwrap(),
+            nodes(&["node-c", "node-b"])
+        );
+
+        // 全部被排除时返回阻止放置的规则
+        let err = rank_candidates(&nodes(&["node-a"]), &rules).unwrap_err();
+        assert!(err.contains("db-ha"));
+        assert!(err.contains("db-1"));
+
+        // 无规则时保持原顺序
+        assert_eq!(
+            rank_candidates(&nodes(&["node-b", "node-a"]), &[]).unwrap(),
+            nodes(&["node-b", "node-a"])
+        );
+    }
+
+    #[test]
+    fn test_order_by_strategy() {
+        let capacities = vec![
+            capacity("node-a", (14, 16), (8192, 32768)),
+            capacity("node-b", (4, 16), (4096, 32768)),
+            capacity("node-c", (0, 16), (0, 16384)),
+        ];
+
+        // node-a 剩余 vCPU 不足，node-c 内存较小但空闲，放置后分配率最低
+        assert_eq!(
+            order_by_strategy(&capacities, 4, 4096, PlacementStrategy::LeastAllocated, None).unwrap(),
+            nodes(&["node-c", "node-b"])
+        );
+
+        // 轮转从上次放置节点之后开始，到末尾后回到开头
+        assert_eq!(
+            order_by_strategy(&capacities, 1, 1024, PlacementStrategy::RoundRobin, Some("node-b")).unwrap(),
+            nodes(&["node-c", "node-a", "node-b"])
+        );
+        assert_eq!(
+            order_by_strategy(&capacities, 1, 1024, PlacementStrategy::RoundRobin, Some("node-c")).unwrap(),
+            nodes(&["node-a", "node-b", "node-c"])
+        );
+
+        // 没有节点容纳得下时列出各节点剩余量
+        let err = order_by_strategy(&capacities, 17, 1024, PlacementStrategy::LeastAllocated, None)
+            .unwrap_err();
+        assert!(err.contains("节点 node-b 剩余 12 vCPU、28672 MB 内存"));
+        assert!(order_by_strategy(&[], 1, 1024, PlacementStrategy::RoundRobin, None).is_err());
+    }
+}
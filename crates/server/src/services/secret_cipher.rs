@@ -0,0 +1,98 @@
+/// 敏感配置加密
+///
+/// IPMI 密码、LUKS 口令等需要原样取回的凭据使用 AES-256-GCM 加密后入库，
+/// 密钥由 SECRET_ENCRYPTION_KEY 经 SHA-256 派生，只保存在 Server 进程内
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+/// 密文前缀，用于区分加密前写入的明文旧数据
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+/// AES-GCM 随机数长度
+const NONCE_LEN: usize = 12;
+
+#[derive(Clone)]
+pub struct SecretCipher {
+    cipher: Aes256Gcm,
+}
+
+impl SecretCipher {
+    pub fn new(key: &str) -> Self {
+        let digest = Sha256::digest(key.as_bytes());
+        Self {
+            cipher: Aes256Gcm::new(&digest),
+        }
+    }
+
+    /// 加密为 `enc:v1:<base64(nonce || ciphertext)>`
+    pub fn encrypt(&self, plaintext: &str) -> anyhow::Result<String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| anyhow::anyhow!("加密敏感配置失败"))?;
+
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        Ok(format!(
+            "{}{}",
+            ENCRYPTED_PREFIX,
+            base64::engine::general_purpose::STANDARD.encode(data)
+        ))
+    }
+
+    /// 解密 encrypt 的输出；没有密文前缀的旧数据按明文原样返回
+    pub fn decrypt(&self, stored: &str) -> anyhow::Result<String> {
+        let Some(encoded) = stored.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| anyhow::anyhow!("敏感配置密文格式无效: {}", e))?;
+        if data.len() < NONCE_LEN {
+            return Err(anyhow::anyhow!("敏感配置密文格式无效"));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("解密敏感配置失败，请确认 SECRET_ENCRYPTION_KEY 未变更"))?;
+
+        String::from_utf8(plaintext).map_err(|e| anyhow::anyhow!("敏感配置解密结果无效: {}", e))
+    }
+}
+
+impl std::fmt::Debug for SecretCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretCipher")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_roundtrip_and_legacy_plaintext() {
+        let cipher = SecretCipher::new("test-key");
+
+        let stored = cipher.encrypt("ipmi-pass").unwrap();
+        assert!(stored.starts_with(ENCRYPTED_PREFIX));
+        assert!(!stored.contains("ipmi-pass"));
+        assert_eq!(cipher.decrypt(&stored).unwrap(), "ipmi-pass");
+        // 同一明文每次加密结果不同
+        assert_ne!(cipher.encrypt("ipmi-pass").unwrap(), stored);
+
+        assert_eq!(cipher.decrypt("legacy-plain").unwrap(), "legacy-plain");
+    }
+
+    #[test]
+    fn test_decrypt_rejects_other_key() {
+        let stored = SecretCipher::new("key-a").encrypt("secret").unwrap();
+        assert!(SecretCipher::new("key-b").decrypt(&stored).is_err());
+    }
+}
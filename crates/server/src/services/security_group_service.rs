@@ -0,0 +1,274 @@
+/// 安全组管理服务
+///
+/// 安全组只维护规则，实际过滤由 Agent 以 nftables 规则挂在虚拟机 tap 设备上完成：
+/// 启动时随网卡配置下发，运行中修改规则或关联关系时通过 apply_security_group 在线生效
+
+use chrono::Utc;
+use common::ws_rpc::{ApplySecurityGroupRequest, SecurityGroupRule};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::db::models::network::{
+    Column as NetworkColumn, Entity as NetworkEntity, Model as NetworkModel,
+};
+use crate::db::models::security_group::{
+    ActiveModel as SecurityGroupActiveModel, Column as SecurityGroupColumn,
+    CreateSecurityGroupDto, Entity as SecurityGroupEntity, Model as SecurityGroupModel,
+    SecurityGroupResponse, UpdateSecurityGroupDto,
+};
+use crate::db::models::vm::{
+    Column as VmColumn, Entity as VmEntity, NetworkInterfaceSpec, VmStatus,
+};
+
+pub struct SecurityGroupService {
+    state: AppState,
+}
+
+impl SecurityGroupService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// 创建安全组
+    pub async fn create_group(&self, dto: CreateSecurityGroupDto) -> anyhow::Result<SecurityGroupResponse> {
+        validate_rules(&dto.rules)?;
+        self.ensure_name_available(&dto.name).await?;
+
+        let now = Utc::now();
+        let group = SecurityGroupActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            name: Set(dto.name),
+            description: Set(dto.description),
+            rules: Set(serde_json::to_value(&dto.rules)?),
+            created_at: Set(now.into()),
+            updated_at: Set(now.into()),
+        }
+        .insert(&self.state.sea_db())
+        .await?;
+
+        info!("创建安全组 {}（{} 条规则）", group.name, dto.rules.len());
+        Ok(SecurityGroupResponse::from(group))
+    }
+
+    /// 获取安全组列表
+    pub async fn list_groups(&self) -> anyhow::Result<Vec<SecurityGroupResponse>> {
+        let groups = SecurityGroupEntity::find()
+            .order_by_asc(SecurityGroupColumn::Name)
+            .all(&self.state.sea_db())
+            .await?;
+        Ok(groups.into_iter().map(SecurityGroupResponse::from).collect())
+    }
+
+    /// 获取安全组详情
+    pub async fn get_group(&self, id: &str) -> anyhow::Result<SecurityGroupResponse> {
+        Ok(SecurityGroupResponse::from(self.find(id).await?))
+    }
+
+    /// 更新安全组
+    ///
+    /// 规则变化时同步到所有使用该安全组的运行中虚拟机
+    pub async fn update_group(
+        &self,
+        id: &str,
+        dto: UpdateSecurityGroupDto,
+    ) -> anyhow::Result<SecurityGroupResponse> {
+        let group = self.find(id).await?;
+        if let Some(ref name) = dto.name {
+            if *name != group.name {
+                self.ensure_name_available(name).await?;
+            }
+        }
+
+        let rules_changed = dto.rules.is_some();
+        let mut active: SecurityGroupActiveModel = group.into();
+        if let Some(name) = dto.name {
+            active.name = Set(name);
+        }
+        if let Some(description) = dto.description {
+            active.description = Set(Some(description));
+        }
+        if let Some(rules) = dto.rules {
+            validate_rules(&rules)?;
+            active.rules = Set(serde_json::to_value(&rules)?);
+        }
+        active.updated_at = Set(Utc::now().into());
+        let group = active.update(&self.state.sea_db()).await?;
+
+        if rules_changed {
+            self.sync_running_vms(|interface, network| {
+                effective_group_id(interface, network) == Some(group.id.as_str())
+            })
+            .await?;
+        }
+
+        Ok(SecurityGroupResponse::from(group))
+    }
+
+    /// 删除安全组，仍被网络或虚拟机网卡引用时拒绝
+    pub async fn delete_group(&self, id: &str) -> anyhow::Result<()> {
+        let db = &self.state.sea_db();
+        let group = self.find(id).await?;
+
+        if let Some(network) = NetworkEntity::find()
+            .filter(NetworkColumn::SecurityGroupId.eq(id))
+            .one(db)
+            .await?
+        {
+            return Err(anyhow::anyhow!("安全组正被网络 {} 使用，无法删除", network.name));
+        }
+        for vm in VmEntity::find().all(db).await? {
+            if interfaces_of(&vm.network_interfaces)
+                .iter()
+                .any(|i| i.security_group_id.as_deref() == Some(id))
+            {
+                return Err(anyhow::anyhow!("安全组正被虚拟机 {} 使用，无法删除", vm.name));
+            }
+        }
+
+        SecurityGroupEntity::delete_by_id(id.to_string()).exec(db).await?;
+        info!("安全组 {} 已删除", group.name);
+        Ok(())
+    }
+
+    /// 检查安全组可以关联到该类型网络上的网卡
+    ///
+    /// 过滤规则挂在 Linux Bridge 的 bridge netfilter 上，OVS 与 macvlan 的流量不经过，只支持 bridge 网络
+    pub async fn ensure_assignable(&self, group_id: &str, network_type: &str) -> anyhow::Result<()> {
+        self.find(group_id).await?;
+        if network_type != "bridge" {
+            return Err(anyhow::anyhow!("安全组只支持 bridge 类型的网络，当前网络类型: {}", network_type));
+        }
+        Ok(())
+    }
+
+    /// 网卡实际生效的安全组规则，未关联安全组时返回 None
+    pub async fn effective_rules(
+        &self,
+        interface: &NetworkInterfaceSpec,
+        network: &NetworkModel,
+    ) -> anyhow::Result<Option<Vec<SecurityGroupRule>>> {
+        let Some(group_id) = effective_group_id(interface, network) else {
+            return Ok(None);
+        };
+        match SecurityGroupEntity::find_by_id(group_id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+        {
+            Some(group) => Ok(Some(group.rule_list())),
+            None => {
+                warn!("网卡关联的安全组 {} 不存在，忽略", group_id);
+                Ok(None)
+            }
+        }
+    }
+
+    /// 将最新的安全组规则下发到运行中虚拟机上受影响的网卡
+    ///
+    /// 节点离线或 Agent 下发失败只记录日志，下次启动时规则会随网卡配置重新下发
+    pub async fn sync_running_vms<F>(&self, affected: F) -> anyhow::Result<()>
+    where
+        F: Fn(&NetworkInterfaceSpec, &NetworkModel) -> bool,
+    {
+        let db = &self.state.sea_db();
+        let vms = VmEntity::find()
+            .filter(VmColumn::Status.eq(VmStatus::Running.as_str()))
+            .all(db)
+            .await?;
+
+        let mut networks: HashMap<String, Option<NetworkModel>> = HashMap::new();
+        for vm in vms {
+            let Some(node_id) = vm.node_id.as_deref() else { continue };
+            for interface in interfaces_of(&vm.network_interfaces) {
+                let Some(mac_address) = interface.mac_address.clone() else { continue };
+                if !networks.contains_key(&interface.network_id) {
+                    let network = NetworkEntity::find_by_id(&interface.network_id).one(db).await?;
+                    networks.insert(interface.network_id.clone(), network);
+                }
+                let Some(network) = networks[&interface.network_id].as_ref() else { continue };
+                if !affected(&interface, network) {
+                    continue;
+                }
+
+                let rules = self.effective_rules(&interface, network).await?;
+                let request = ApplySecurityGroupRequest {
+                    vm_id: vm.id.clone(),
+                    mac_address: mac_address.clone(),
+                    security_group_id: rules
+                        .as_ref()
+                        .and(effective_group_id(&interface, network))
+                        .map(str::to_string),
+                    rules: rules.unwrap_or_default(),
+                };
+                match self
+                    .state
+                    .agent_rpc()
+                    .call(
+                        node_id,
+                        "apply_security_group",
+                        serde_json::to_value(&request)?,
+                        Duration::from_secs(30),
+                    )
+                    .await
+                {
+                    Ok(_) => info!("虚拟机 {} 网卡 {} 的安全组规则已更新", vm.name, mac_address),
+                    Err(e) => warn!(
+                        "虚拟机 {} 网卡 {} 的安全组规则下发失败，将在下次启动时生效: {}",
+                        vm.name, mac_address, e
+                    ),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn ensure_name_available(&self, name: &str) -> anyhow::Result<()> {
+        let exists = SecurityGroupEntity::find()
+            .filter(SecurityGroupColumn::Name.eq(name))
+            .one(&self.state.sea_db())
+            .await?;
+        if exists.is_some() {
+            return Err(anyhow::anyhow!("安全组名称已存在: {}", name));
+        }
+        Ok(())
+    }
+
+    async fn find(&self, id: &str) -> anyhow::Result<SecurityGroupModel> {
+        SecurityGroupEntity::find_by_id(id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("安全组 {} 不存在", id))
+    }
+}
+
+/// 网卡生效的安全组：网卡单独指定的优先，否则使用网络的默认安全组；非 bridge 网络不生效
+pub fn effective_group_id<'a>(
+    interface: &'a NetworkInterfaceSpec,
+    network: &'a NetworkModel,
+) -> Option<&'a str> {
+    if network.network_type != "bridge" {
+        return None;
+    }
+    interface
+        .security_group_id
+        .as_deref()
+        .or(network.security_group_id.as_deref())
+}
+
+fn validate_rules(rules: &[SecurityGroupRule]) -> anyhow::Result<()> {
+    for (index, rule) in rules.iter().enumerate() {
+        rule.validate()
+            .map_err(|e| anyhow::anyhow!("第 {} 条规则无效: {}", index + 1, e))?;
+    }
+    Ok(())
+}
+
+fn interfaces_of(network_interfaces: &Option<serde_json::Value>) -> Vec<NetworkInterfaceSpec> {
+    network_interfaces
+        .as_ref()
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
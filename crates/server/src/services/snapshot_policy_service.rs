@@ -0,0 +1,332 @@
+/// 快照策略服务
+///
+/// 调度器每分钟检查一次已启用的策略，以最近一次执行时间（从未执行则为创建时间）
+/// 为起点计算下一次触发时间，到期即执行。执行时间持久化在数据库中，服务重启后
+/// 错过的触发只补执行一次
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::db::models::snapshot::{
+    Column as SnapshotColumn, CreateSnapshotDto, Entity as SnapshotEntity, SnapshotStatus,
+};
+use crate::db::models::snapshot_policy::{
+    ActiveModel as SnapshotPolicyActiveModel, Column as SnapshotPolicyColumn,
+    CreateSnapshotPolicyDto, Entity as SnapshotPolicyEntity, Model as SnapshotPolicyModel,
+    SnapshotPolicyResponse, SnapshotPolicyTarget, UpdateSnapshotPolicyDto,
+};
+use crate::db::models::vm::Entity as VmEntity;
+use crate::db::models::volume::{
+    Column as VolumeColumn, Entity as VolumeEntity, Model as VolumeModel,
+};
+use crate::services::cron_schedule::CronSchedule;
+use crate::services::snapshot_service::SnapshotService;
+
+pub struct SnapshotPolicyService {
+    state: AppState,
+}
+
+impl SnapshotPolicyService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// 创建快照策略
+    pub async fn create_policy(
+        &self,
+        dto: CreateSnapshotPolicyDto,
+    ) -> Result<SnapshotPolicyResponse> {
+        parse_schedule(&dto.schedule)?;
+        self.ensure_target_exists(dto.target_type, &dto.target_id)
+            .await?;
+
+        let now = Utc::now();
+        let policy = SnapshotPolicyActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            name: Set(dto.name),
+            target_type: Set(dto.target_type.as_str().to_string()),
+            target_id: Set(dto.target_id),
+            schedule: Set(dto.schedule.trim().to_string()),
+            keep_last: Set(dto.keep_last),
+            enabled: Set(dto.enabled),
+            last_run_at: Set(None),
+            last_status: Set(None),
+            last_error: Set(None),
+            created_at: Set(now.into()),
+            updated_at: Set(now.into()),
+        }
+        .insert(&self.state.sea_db())
+        .await?;
+
+        info!(
+            "创建快照策略 {}: {} {} ({}，保留 {} 个)",
+            policy.name, policy.target_type, policy.target_id, policy.schedule, policy.keep_last
+        );
+        Ok(to_response(policy))
+    }
+
+    /// 获取快照策略列表
+    pub async fn list_policies(&self) -> Result<Vec<SnapshotPolicyResponse>> {
+        let policies = SnapshotPolicyEntity::find()
+            .order_by_asc(SnapshotPolicyColumn::Name)
+            .all(&self.state.sea_db())
+            .await?;
+        Ok(policies.into_iter().map(to_response).collect())
+    }
+
+    /// 获取快照策略详情
+    pub async fn get_policy(&self, id: &str) -> Result<SnapshotPolicyResponse> {
+        Ok(to_response(self.find(id).await?))
+    }
+
+    /// 更新快照策略
+    pub async fn update_policy(
+        &self,
+        id: &str,
+        dto: UpdateSnapshotPolicyDto,
+    ) -> Result<SnapshotPolicyResponse> {
+        let policy = self.find(id).await?;
+        let mut active: SnapshotPolicyActiveModel = policy.into();
+
+        if let Some(name) = dto.name {
+            active.name = Set(name);
+        }
+        if let Some(schedule) = dto.schedule {
+            parse_schedule(&schedule)?;
+            active.schedule = Set(schedule.trim().to_string());
+        }
+        if let Some(keep_last) = dto.keep_last {
+            active.keep_last = Set(keep_last);
+        }
+        if let Some(enabled) = dto.enabled {
+            active.enabled = Set(enabled);
+        }
+        active.updated_at = Set(Utc::now().into());
+
+        let policy = active.update(&self.state.sea_db()).await?;
+        Ok(to_response(policy))
+    }
+
+    /// 删除快照策略（已创建的快照保留）
+    pub async fn delete_policy(&self, id: &str) -> Result<()> {
+        let result = SnapshotPolicyEntity::delete_by_id(id.to_string())
+            .exec(&self.state.sea_db())
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(anyhow!("快照策略不存在"));
+        }
+        Ok(())
+    }
+
+    /// 执行所有到期的快照策略，返回执行的策略数量
+    pub async fn run_due_policies(&self, now: DateTime<Utc>) -> Result<usize> {
+        let policies = SnapshotPolicyEntity::find()
+            .filter(SnapshotPolicyColumn::Enabled.eq(true))
+            .all(&self.state.sea_db())
+            .await?;
+
+        let mut executed = 0;
+        for policy in policies {
+            match next_run_at(&policy) {
+                Some(next) if next <= now => {}
+                _ => continue,
+            }
+
+            let result = self.run_policy(&policy).await;
+            if let Err(e) = &result {
+                warn!("快照策略 {} 执行失败: {}", policy.name, e);
+            }
+
+            let mut active: SnapshotPolicyActiveModel = policy.into();
+            active.last_run_at = Set(Some(now.into()));
+            active.last_status = Set(Some(if result.is_ok() { "success" } else { "failed" }.to_string()));
+            active.last_error = Set(result.err().map(|e| e.to_string()));
+            active.update(&self.state.sea_db()).await?;
+            executed += 1;
+        }
+
+        Ok(executed)
+    }
+
+    /// 为策略的每个目标卷创建快照，并清理超出保留数量的旧快照
+    async fn run_policy(&self, policy: &SnapshotPolicyModel) -> Result<()> {
+        let volumes = self.target_volumes(policy).await?;
+        let snapshot_service = SnapshotService::new(self.state.clone());
+        let name = format!("{}-{}", policy.name, Utc::now().format("%Y%m%d%H%M%S"));
+
+        let mut errors = Vec::new();
+        for volume in volumes {
+            let dto = CreateSnapshotDto {
+                name: name.clone(),
+                volume_id: volume.id.clone(),
+                description: Some(format!("由快照策略 {} 自动创建", policy.name)),
+                metadata: Some(serde_json::json!({ "policy_id": policy.id })),
+            };
+            if let Err(e) = snapshot_service.create_snapshot(dto).await {
+                errors.push(format!("存储卷 {}: {}", volume.name, e));
+                continue;
+            }
+            if let Err(e) = self.prune_snapshots(policy, &volume.id).await {
+                errors.push(format!("清理存储卷 {} 的旧快照失败: {}", volume.name, e));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(errors.join("; ")))
+        }
+    }
+
+    /// 按创建时间从旧到新删除该策略在卷上超出保留数量的快照，受保护的快照不参与计数也不会被删除
+    ///
+    /// 创建中的快照计入保留数量，但只删除已可用的快照
+    async fn prune_snapshots(&self, policy: &SnapshotPolicyModel, volume_id: &str) -> Result<usize> {
+        let snapshots = SnapshotEntity::find()
+            .filter(SnapshotColumn::VolumeId.eq(volume_id))
+            .filter(SnapshotColumn::Protected.eq(false))
+            .filter(SnapshotColumn::Status.is_in([
+                SnapshotStatus::Creating.as_str(),
+                SnapshotStatus::Available.as_str(),
+            ]))
+            .order_by_desc(SnapshotColumn::CreatedAt)
+            .all(&self.state.sea_db())
+            .await?;
+
+        let expired: Vec<_> = snapshots
+            .into_iter()
+            .filter(|snapshot| snapshot.policy_id() == Some(policy.id.as_str()))
+            .skip(policy.keep_last.max(1) as usize)
+            .filter(|snapshot| snapshot.status == SnapshotStatus::Available.as_str())
+            .collect();
+
+        let snapshot_service = SnapshotService::new(self.state.clone());
+        for snapshot in &expired {
+            info!(
+                "快照策略 {} 清理超出保留数量的快照 {} ({})",
+                policy.name, snapshot.name, snapshot.id
+            );
+            snapshot_service.delete_snapshot(&snapshot.id).await?;
+        }
+        Ok(expired.len())
+    }
+
+    /// 策略作用的存储卷；虚拟机策略跳过不支持快照的 raw 与加密卷
+    async fn target_volumes(&self, policy: &SnapshotPolicyModel) -> Result<Vec<VolumeModel>> {
+        let db = &self.state.sea_db();
+        let target = policy
+            .target_type
+            .parse::<SnapshotPolicyTarget>()
+            .map_err(|e| anyhow!(e))?;
+
+        match target {
+            SnapshotPolicyTarget::Volume => {
+                let volume = VolumeEntity::find_by_id(&policy.target_id)
+                    .one(db)
+                    .await?
+                    .ok_or_else(|| anyhow!("存储卷不存在"))?;
+                Ok(vec![volume])
+            }
+            SnapshotPolicyTarget::Vm => {
+                VmEntity::find_by_id(&policy.target_id)
+                    .one(db)
+                    .await?
+                    .ok_or_else(|| anyhow!("虚拟机不存在"))?;
+                let volumes: Vec<_> = VolumeEntity::find()
+                    .filter(VolumeColumn::VmId.eq(&policy.target_id))
+                    .order_by_asc(VolumeColumn::CreatedAt)
+                    .all(db)
+                    .await?
+                    .into_iter()
+                    .filter(|v| v.volume_type.to_lowercase() != "raw" && !v.is_encrypted())
+                    .collect();
+                if volumes.is_empty() {
+                    return Err(anyhow!("虚拟机没有可创建快照的存储卷"));
+                }
+                Ok(volumes)
+            }
+        }
+    }
+
+    async fn ensure_target_exists(&self, target: SnapshotPolicyTarget, target_id: &str) -> Result<()> {
+        let db = &self.state.sea_db();
+        let exists = match target {
+            SnapshotPolicyTarget::Volume => {
+                VolumeEntity::find_by_id(target_id).one(db).await?.is_some()
+            }
+            SnapshotPolicyTarget::Vm => VmEntity::find_by_id(target_id).one(db).await?.is_some(),
+        };
+        if !exists {
+            return Err(anyhow!(
+                "快照策略目标 {} {} 不存在",
+                target.as_str(),
+                target_id
+            ));
+        }
+        Ok(())
+    }
+
+    async fn find(&self, id: &str) -> Result<SnapshotPolicyModel> {
+        SnapshotPolicyEntity::find_by_id(id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow!("快照策略不存在"))
+    }
+
+    /// 调度任务的一次检查：维护只读模式下不创建或清理快照，到期策略留到退出只读后执行
+    pub async fn run_scheduled_tick(&self, now: DateTime<Utc>) -> Result<usize> {
+        if self.state.is_read_only() {
+            debug!("快照策略调度: 处于维护只读模式，跳过本次检查");
+            return Ok(0);
+        }
+        self.run_due_policies(now).await
+    }
+
+    /// 启动快照策略调度任务
+    pub fn start_snapshot_policy_scheduler(state: AppState, check_interval_secs: u64) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(check_interval_secs));
+
+            loop {
+                interval.tick().await;
+
+                match SnapshotPolicyService::new(state.clone())
+                    .run_scheduled_tick(Utc::now())
+                    .await
+                {
+                    Ok(0) => {}
+                    Ok(executed) => info!("快照策略调度: 已执行 {} 个到期策略", executed),
+                    Err(e) => error!("快照策略调度失败: {}", e),
+                }
+            }
+        });
+    }
+}
+
+fn parse_schedule(schedule: &str) -> Result<CronSchedule> {
+    schedule.parse().map_err(|e: String| anyhow!(e))
+}
+
+/// 策略的下一次执行时间，以最近一次执行时间（从未执行则为创建时间）为起点
+fn next_run_at(policy: &SnapshotPolicyModel) -> Option<DateTime<Utc>> {
+    let schedule = parse_schedule(&policy.schedule).ok()?;
+    let last = policy.last_run_at.unwrap_or(policy.created_at);
+    schedule.next_after(last.with_timezone(&Utc))
+}
+
+fn to_response(policy: SnapshotPolicyModel) -> SnapshotPolicyResponse {
+    let next = policy
+        .enabled
+        .then(|| next_run_at(&policy))
+        .flatten()
+        .map(|t| t.to_rfc3339());
+    SnapshotPolicyResponse {
+        next_run_at: next,
+        ..SnapshotPolicyResponse::from(policy)
+    }
+}
@@ -10,15 +10,24 @@ use uuid::Uuid;
 use crate::app_state::AppState;
 use crate::db::models::snapshot::{
     ActiveModel as SnapshotActiveModel, Column as SnapshotColumn, CreateSnapshotDto,
-    Entity as SnapshotEntity, SnapshotListResponse, SnapshotResponse, SnapshotStatus,
-    UpdateSnapshotDto,
+    DiskSnapshotEntry, DiskSnapshotState, Entity as SnapshotEntity, Model as SnapshotModel,
+    SafetyOperation, SnapshotListResponse, SnapshotResponse, SnapshotStatus, UpdateSnapshotDto,
+    VolumeDiskSnapshotsResponse,
 };
 use crate::db::models::storage_pool::Entity as StoragePoolEntity;
+use crate::db::models::vm::{Entity as VmEntity, VmStatus};
 use crate::db::models::volume::Entity as VolumeEntity;
+use crate::services::storage_service::StorageService;
 use crate::ws::frontend_handler::FrontendMessage;
+use common::ws_rpc::{ListVolumeSnapshotsRequest, ListVolumeSnapshotsResponse, VolumeSnapshotInfo};
+use std::time::Duration;
 
 use tracing::{error, info, warn};
 
+/// 等待安全快照创建完成的轮询间隔与超时
+const SAFETY_SNAPSHOT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const SAFETY_SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(600);
+
 pub struct SnapshotService {
     state: AppState,
 }
@@ -64,6 +73,7 @@ impl SnapshotService {
         if volume.volume_type.to_lowercase() == "raw" {
             return Err(anyhow!("raw 格式的存储卷暂不支持创建快照"));
         }
+        StorageService::ensure_not_encrypted(&volume, "创建快照")?;
 
         // 查找存储池以获取节点信息
         let pool = StoragePoolEntity::find_by_id(&volume.pool_id)
@@ -73,6 +83,16 @@ impl SnapshotService {
 
         let node_id = pool.node_id.ok_or_else(|| anyhow!("存储池未关联节点"))?;
 
+        // 卷挂载在运行中的虚拟机上时，Agent 在快照前通过 guest agent 冻结客户机文件系统
+        let running_vm_id = match volume.vm_id.as_deref() {
+            Some(vm_id) => VmEntity::find_by_id(vm_id.to_string())
+                .one(db)
+                .await?
+                .filter(|vm| vm.status == VmStatus::Running.as_str())
+                .map(|vm| vm.id),
+            None => None,
+        };
+
         // 创建快照记录，状态为creating
         let snapshot_id = Uuid::new_v4().to_string();
         let now = Utc::now();
@@ -85,6 +105,7 @@ impl SnapshotService {
             size_gb: Set(Some(volume.size_gb)),
             snapshot_tag: Set(None),
             description: Set(dto.description.clone()),
+            protected: Set(false),
             metadata: Set(dto.metadata.clone()),
             created_at: Set(now.into()),
             updated_at: Set(now.into()),
@@ -102,11 +123,12 @@ impl SnapshotService {
             "volume_id": dto.volume_id,
             "snapshot_name": dto.name,
             "pool_id": volume.pool_id,
+            "vm_id": running_vm_id,
         });
 
         // 异步通知 Agent 创建快照，不等待结果
         self.state
-            .agent_manager()
+            .agent_rpc()
             .notify(&node_id, "create_snapshot_async", request)
             .await
             .map_err(|e| anyhow!("发送创建快照通知失败: {}", e))?;
@@ -159,7 +181,7 @@ impl SnapshotService {
 
         // 异步通知 Agent 删除快照，不等待结果
         self.state
-            .agent_manager()
+            .agent_rpc()
             .notify(&node_id, "delete_snapshot_async", request)
             .await
             .map_err(|e| anyhow!("发送删除快照通知失败: {}", e))?;
@@ -169,7 +191,9 @@ impl SnapshotService {
     }
 
     /// 恢复快照（异步操作，不等待 Agent 响应）
-    pub async fn restore_snapshot(&self, snapshot_id: &str) -> Result<()> {
+    ///
+    /// 启用安全快照时先为卷的当前状态创建安全快照，返回其 ID
+    pub async fn restore_snapshot(&self, snapshot_id: &str) -> Result<Option<String>> {
         let db = &self.state.sea_db();
 
         // 查找快照
@@ -190,6 +214,9 @@ impl SnapshotService {
                 "存储卷正在被虚拟机使用，需要先停止虚拟机才能恢复快照"
             ));
         }
+        StorageService::new(self.state.clone())
+            .ensure_no_linked_clones(&volume.id, "恢复快照")
+            .await?;
 
         // 查找存储池以获取节点信息
         let pool = StoragePoolEntity::find_by_id(&volume.pool_id)
@@ -199,6 +226,10 @@ impl SnapshotService {
 
         let node_id = pool.node_id.ok_or_else(|| anyhow!("存储池未关联节点"))?;
 
+        let safety_snapshot_id = self
+            .create_safety_snapshot(&volume.id, SafetyOperation::RestoreSnapshot)
+            .await?;
+
         // 更新快照状态为 restoring（使用 available 状态表示正在恢复）
         let mut snapshot_active: SnapshotActiveModel = snapshot.clone().into();
         let now = Utc::now();
@@ -219,13 +250,153 @@ impl SnapshotService {
 
         // 异步通知 Agent 恢复快照，不等待结果
         self.state
-            .agent_manager()
+            .agent_rpc()
             .notify(&node_id, "restore_snapshot_async", request)
             .await
             .map_err(|e| anyhow!("发送恢复快照通知失败: {}", e))?;
 
         info!("快照 {} 恢复通知已发送给 Agent", snapshot_id);
-        Ok(())
+        Ok(safety_snapshot_id)
+    }
+
+    /// 破坏性操作前为存储卷创建安全快照，并等待快照可用
+    ///
+    /// 未启用安全快照或卷为 raw 格式时返回 None；快照创建失败时返回错误，调用方应中止操作
+    pub async fn create_safety_snapshot(
+        &self,
+        volume_id: &str,
+        operation: SafetyOperation,
+    ) -> Result<Option<String>> {
+        if !self.state.safety_snapshot().enabled {
+            return Ok(None);
+        }
+
+        let db = &self.state.sea_db();
+        let volume = VolumeEntity::find_by_id(volume_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow!("存储卷不存在"))?;
+
+        if volume.volume_type.to_lowercase() == "raw" {
+            warn!(
+                "存储卷 {} 为 raw 格式，{} 前无法创建安全快照",
+                volume_id,
+                operation.as_str()
+            );
+            return Ok(None);
+        }
+        if volume.is_encrypted() {
+            warn!(
+                "存储卷 {} 已加密，{} 前无法创建安全快照",
+                volume_id,
+                operation.as_str()
+            );
+            return Ok(None);
+        }
+
+        let name = format!(
+            "safety-{}-{}",
+            operation.as_str(),
+            Utc::now().format("%Y%m%d%H%M%S")
+        );
+        let snapshot = self
+            .create_snapshot(CreateSnapshotDto {
+                name,
+                volume_id: volume_id.to_string(),
+                description: Some(format!("{} 前自动创建的安全快照", operation.as_str())),
+                metadata: Some(operation.metadata()),
+            })
+            .await
+            .map_err(|e| anyhow!("创建安全快照失败，已取消操作: {}", e))?;
+
+        self.wait_for_snapshot(&snapshot.id).await?;
+        info!(
+            "存储卷 {} 的安全快照 {} 已就绪 ({})",
+            volume_id,
+            snapshot.id,
+            operation.as_str()
+        );
+        Ok(Some(snapshot.id))
+    }
+
+    /// 轮询快照状态直到可用
+    async fn wait_for_snapshot(&self, snapshot_id: &str) -> Result<()> {
+        let db = &self.state.sea_db();
+        let deadline = tokio::time::Instant::now() + SAFETY_SNAPSHOT_TIMEOUT;
+
+        loop {
+            let snapshot = SnapshotEntity::find_by_id(snapshot_id)
+                .one(db)
+                .await?
+                .ok_or_else(|| anyhow!("安全快照 {} 不存在", snapshot_id))?;
+
+            if snapshot.status == SnapshotStatus::Available.as_str() {
+                return Ok(());
+            }
+            if snapshot.status == SnapshotStatus::Error.as_str() {
+                return Err(anyhow!("安全快照 {} 创建失败，已取消操作", snapshot_id));
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(anyhow!("等待安全快照 {} 超时，已取消操作", snapshot_id));
+            }
+            tokio::time::sleep(SAFETY_SNAPSHOT_POLL_INTERVAL).await;
+        }
+    }
+
+    /// 清理超过保留时长的安全快照，返回清理数量
+    ///
+    /// 重装系统保留下来的原系统盘若仍未被挂载，连同快照一起删除
+    pub async fn prune_safety_snapshots(&self) -> Result<usize> {
+        let db = &self.state.sea_db();
+        let retention_hours = self.state.safety_snapshot().retention_hours as i64;
+        let cutoff = Utc::now() - chrono::Duration::hours(retention_hours);
+
+        let expired: Vec<SnapshotModel> = SnapshotEntity::find()
+            .filter(SnapshotColumn::Status.eq(SnapshotStatus::Available.as_str()))
+            .filter(SnapshotColumn::CreatedAt.lt(cutoff))
+            .filter(SnapshotColumn::Protected.eq(false))
+            .all(db)
+            .await?
+            .into_iter()
+            .filter(|snapshot| snapshot.is_safety())
+            .collect();
+
+        let mut pruned = 0;
+        for snapshot in expired {
+            let result = match VolumeEntity::find_by_id(&snapshot.volume_id).one(db).await? {
+                Some(volume) if snapshot.retains_volume() && volume.vm_id.is_none() => {
+                    info!("安全快照 {} 已过期，删除保留的原卷 {}", snapshot.id, volume.id);
+                    StorageService::new(self.state.clone())
+                        .delete_volume(&volume.id)
+                        .await
+                }
+                _ => self.delete_snapshot(&snapshot.id).await,
+            };
+
+            match result {
+                Ok(()) => pruned += 1,
+                Err(e) => warn!("清理安全快照 {} 失败: {}", snapshot.id, e),
+            }
+        }
+
+        Ok(pruned)
+    }
+
+    /// 启动安全快照定期清理任务
+    pub fn start_safety_snapshot_pruner(state: AppState, check_interval_secs: u64) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(check_interval_secs));
+
+            loop {
+                interval.tick().await;
+
+                match SnapshotService::new(state.clone()).prune_safety_snapshots().await {
+                    Ok(0) => {}
+                    Ok(pruned) => info!("安全快照清理: 已清理 {} 个过期快照", pruned),
+                    Err(e) => error!("安全快照清理失败: {}", e),
+                }
+            }
+        });
     }
 
     /// 处理 Agent 的快照操作完成通知
@@ -235,6 +406,7 @@ impl SnapshotService {
         operation: &str,
         success: bool,
         message: &str,
+        consistency: Option<&str>,
     ) -> Result<()> {
         let db = &self.state.sea_db();
         let now = Utc::now();
@@ -245,6 +417,7 @@ impl SnapshotService {
             .await?
             .ok_or_else(|| anyhow!("快照不存在"))?;
 
+        let consistent_metadata = consistency.map(|c| snapshot.metadata_with_consistency(c));
         let mut snapshot_active: SnapshotActiveModel = snapshot.into();
 
         match operation {
@@ -256,6 +429,15 @@ impl SnapshotService {
                         let snapshot_tag = message.replace("snapshot_tag:", "").trim().to_string();
                         snapshot_active.snapshot_tag = Set(Some(snapshot_tag));
                     }
+                    if let Some(metadata) = consistent_metadata {
+                        if consistency == Some("crash") {
+                            warn!(
+                                "快照 {} 创建时未能冻结客户机文件系统，仅为崩溃一致性",
+                                snapshot_id
+                            );
+                        }
+                        snapshot_active.metadata = Set(Some(metadata));
+                    }
                     self.notify_snapshot_status_update(
                         snapshot_id,
                         "available",
@@ -403,7 +585,7 @@ impl SnapshotService {
         Ok(response)
     }
 
-    /// 更新快照（仅允许更新名称和描述）
+    /// 更新快照（仅允许更新名称、描述和保护标记）
     pub async fn update_snapshot(
         &self,
         snapshot_id: &str,
@@ -418,13 +600,16 @@ impl SnapshotService {
 
         let mut snapshot_active: SnapshotActiveModel = snapshot.clone().into();
 
-        // 仅允许更新名称和描述
+        // 仅允许更新名称、描述和保护标记
         if let Some(name) = dto.name {
             snapshot_active.name = Set(name);
         }
         if let Some(description) = dto.description {
             snapshot_active.description = Set(Some(description));
         }
+        if let Some(protected) = dto.protected {
+            snapshot_active.protected = Set(protected);
+        }
 
         snapshot_active.updated_at = Set(Utc::now().into());
 
@@ -440,4 +625,221 @@ impl SnapshotService {
         info!("快照 {} 已更新", snapshot_id);
         Ok(response)
     }
+
+    /// 列出存储卷磁盘上的实际快照，并与数据库记录对账
+    ///
+    /// 磁盘上存在但数据库没有的标记为 orphan，数据库有但磁盘上没有的标记为 phantom
+    pub async fn list_disk_snapshots(&self, volume_id: &str) -> Result<VolumeDiskSnapshotsResponse> {
+        let db = &self.state.sea_db();
+
+        let volume = VolumeEntity::find_by_id(volume_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow!("存储卷不存在"))?;
+
+        let pool = StoragePoolEntity::find_by_id(&volume.pool_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow!("存储池不存在"))?;
+
+        let node_id = pool.node_id.ok_or_else(|| anyhow!("存储池未关联节点"))?;
+
+        let request = ListVolumeSnapshotsRequest {
+            volume_id: volume_id.to_string(),
+            pool_id: volume.pool_id.clone(),
+        };
+
+        let response_msg = self
+            .state
+            .agent_rpc()
+            .call(
+                &node_id,
+                "list_volume_snapshots",
+                serde_json::to_value(&request)?,
+                Duration::from_secs(30),
+            )
+            .await
+            .map_err(|e| anyhow!("WebSocket RPC 调用失败: {}", e))?;
+
+        let result: ListVolumeSnapshotsResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow!("响应无数据"))?,
+        )?;
+
+        let db_snapshots = SnapshotEntity::find()
+            .filter(SnapshotColumn::VolumeId.eq(volume_id))
+            .all(db)
+            .await?;
+
+        let snapshots = Self::reconcile_disk_snapshots(result.snapshots, db_snapshots);
+        let count = |state: DiskSnapshotState| snapshots.iter().filter(|s| s.state == state).count();
+
+        let response = VolumeDiskSnapshotsResponse {
+            volume_id: volume_id.to_string(),
+            synced: count(DiskSnapshotState::Synced),
+            orphans: count(DiskSnapshotState::Orphan),
+            phantoms: count(DiskSnapshotState::Phantom),
+            snapshots,
+        };
+
+        if response.orphans > 0 || response.phantoms > 0 {
+            warn!(
+                "存储卷 {} 快照与数据库不一致: orphans={}, phantoms={}",
+                volume_id, response.orphans, response.phantoms
+            );
+        }
+
+        Ok(response)
+    }
+
+    /// 按快照标签匹配磁盘快照与数据库记录
+    ///
+    /// 仍在创建中的记录尚未落盘，不计为 phantom
+    fn reconcile_disk_snapshots(
+        disk_snapshots: Vec<VolumeSnapshotInfo>,
+        db_snapshots: Vec<SnapshotModel>,
+    ) -> Vec<DiskSnapshotEntry> {
+        let mut db_by_tag: std::collections::HashMap<String, SnapshotModel> = db_snapshots
+            .into_iter()
+            .map(|s| (s.snapshot_tag.clone().unwrap_or_else(|| s.id.clone()), s))
+            .collect();
+
+        let mut entries = Vec::new();
+
+        for disk in disk_snapshots {
+            let disk_created_at = disk
+                .created_at
+                .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                .map(|dt| dt.to_rfc3339());
+
+            let db_snapshot = db_by_tag.remove(&disk.name);
+            entries.push(DiskSnapshotEntry {
+                state: if db_snapshot.is_some() {
+                    DiskSnapshotState::Synced
+                } else {
+                    DiskSnapshotState::Orphan
+                },
+                snapshot_id: db_snapshot.as_ref().map(|s| s.id.clone()),
+                snapshot_name: db_snapshot.as_ref().map(|s| s.name.clone()),
+                db_status: db_snapshot.map(|s| s.status),
+                tag: disk.name,
+                vm_state_size: Some(disk.vm_state_size),
+                disk_created_at,
+            });
+        }
+
+        for (tag, snapshot) in db_by_tag {
+            if snapshot.status == SnapshotStatus::Creating.as_str() {
+                continue;
+            }
+            entries.push(DiskSnapshotEntry {
+                tag,
+                state: DiskSnapshotState::Phantom,
+                snapshot_id: Some(snapshot.id),
+                snapshot_name: Some(snapshot.name),
+                db_status: Some(snapshot.status),
+                vm_state_size: None,
+                disk_created_at: None,
+            });
+        }
+
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn db_snapshot(id: &str, tag: Option<&str>, status: SnapshotStatus) -> SnapshotModel {
+        let now = Utc::now();
+        SnapshotModel {
+            id: id.to_string(),
+            name: format!("name-{}", id),
+            volume_id: "vol-1".to_string(),
+            status: status.as_str().to_string(),
+            size_gb: Some(10),
+            snapshot_tag: tag.map(|t| t.to_string()),
+            description: None,
+            protected: false,
+            metadata: None,
+            created_at: now.into(),
+            updated_at: now.into(),
+        }
+    }
+
+    fn disk_snapshot(name: &str) -> VolumeSnapshotInfo {
+        VolumeSnapshotInfo {
+            name: name.to_string(),
+            vm_state_size: 0,
+            created_at: Some(1700000000),
+        }
+    }
+
+    #[test]
+    fn test_reconcile_disk_snapshots() {
+        let entries = SnapshotService::reconcile_disk_snapshots(
+            vec![disk_snapshot("snap-1"), disk_snapshot("leftover")],
+            vec![
+                db_snapshot("snap-1", Some("snap-1"), SnapshotStatus::Available),
+                // 没有 snapshot_tag 时以 id 作为标签
+                db_snapshot("snap-2", None, SnapshotStatus::Available),
+                // 创建中的记录不算 phantom
+                db_snapshot("snap-3", None, SnapshotStatus::Creating),
+            ],
+        );
+
+        let state_of = |tag: &str| entries.iter().find(|e| e.tag == tag).map(|e| e.state.clone());
+        assert_eq!(entries.len(), 3);
+        assert_eq!(state_of("snap-1"), Some(DiskSnapshotState::Synced));
+        assert_eq!(state_of("leftover"), Some(DiskSnapshotState::Orphan));
+        assert_eq!(state_of("snap-2"), Some(DiskSnapshotState::Phantom));
+        assert_eq!(state_of("snap-3"), None);
+    }
+
+    #[tokio::test]
+    async fn test_safety_snapshot_skipped_when_disabled() {
+        use crate::config::NodeAlertThresholds;
+        use crate::ws::AgentConnectionManager;
+        use common::utils::BridgeNaming;
+        use common::ws_rpc::DiskBusType;
+
+        let db = crate::lifecycle_tests::sqlite_db().await;
+        let state = AppState::new(
+            db.clone(),
+            AgentConnectionManager::new(),
+            BridgeNaming::new(BridgeNaming::DEFAULT_PREFIX).unwrap(),
+            false,
+            NodeAlertThresholds::default(),
+            DiskBusType::Virtio,
+        );
+
+        let result = SnapshotService::new(state)
+            .create_safety_snapshot("vol-1", SafetyOperation::RestoreSnapshot)
+            .await
+            .unwrap();
+
+        assert_eq!(result, None);
+        assert!(SnapshotEntity::find().all(&db).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_safety_snapshot_metadata() {
+        let mut snapshot = db_snapshot("snap-1", None, SnapshotStatus::Available);
+        assert!(!snapshot.is_safety());
+
+        snapshot.metadata = Some(SafetyOperation::RebuildVm.metadata());
+        assert!(snapshot.is_safety());
+        assert!(snapshot.retains_volume());
+
+        snapshot.metadata = Some(SafetyOperation::RestoreSnapshot.metadata());
+        assert!(snapshot.is_safety());
+        assert!(!snapshot.retains_volume());
+
+        // 记录一致性级别时保留已有元数据
+        let metadata = snapshot.metadata_with_consistency("crash");
+        assert_eq!(metadata["consistency"], "crash");
+        assert_eq!(metadata["safety"], true);
+    }
 }
@@ -1,30 +1,48 @@
 /// 存储管理服务
 use chrono::Utc;
+use sea_orm::sea_query::{Alias, Expr, Func, Query, SimpleExpr};
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
-    QuerySelect, Set,
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait, UpdateMany,
 };
 use uuid::Uuid;
 
 use crate::app_state::AppState;
 use crate::db::models::storage_pool::{
     ActiveModel as StoragePoolActiveModel, Column as StoragePoolColumn, CreateStoragePoolDto,
-    Entity as StoragePoolEntity, StoragePoolListResponse, StoragePoolResponse,
-    UpdateStoragePoolDto,
+    Entity as StoragePoolEntity, PoolGcDto, PoolGcResponse, StoragePoolListResponse,
+    StoragePoolResponse, UpdateStoragePoolDto,
 };
+use crate::db::models::snapshot::{Entity as SnapshotEntity, SafetyOperation, SnapshotStatus};
 use crate::db::models::vm::Entity as VmEntity;
 use crate::db::models::volume::{
     ActiveModel as VolumeActiveModel, CloneVolumeDto, Column as VolumeColumn, CreateVolumeDto,
-    Entity as VolumeEntity, ResizeVolumeDto, UpdateVolumeDto, VolumeListResponse, VolumeResponse,
-    VolumeStatus,
+    Entity as VolumeEntity, ImportVolumeDto, Model as VolumeModel, ResizeVolumeDto, UpdateVolumeDto,
+    VolumeListResponse, VolumeResponse, VolumeStatus,
 };
+use crate::services::department_service::{DepartmentService, QuotaRequest};
+use crate::services::s3_presign::{self, S3Object};
+use crate::services::snapshot_service::SnapshotService;
+use crate::ws::{AgentRpc, FrontendMessage};
 use common::ws_rpc::{
-    CloneVolumeRequest, CloneVolumeResponse, CreateVolumeRequest, CreateVolumeResponse,
-    DeleteVolumeRequest, DeleteVolumeResponse, ResizeVolumeRequest, ResizeVolumeResponse,
-    SnapshotVolumeRequest,
+    BackingStore, CloneVolumeRequest, CloneVolumeResponse, CreateVolumeRequest, CreateVolumeResponse,
+    DeleteOrphanedVolumesRequest, DeleteOrphanedVolumesResponse, DeleteVolumeRequest,
+    DeleteVolumeResponse, GetPoolUsageRequest, GetPoolUsageResponse, ImportVolumeRequest, ImportVolumeResponse, ListOrphanedVolumesRequest, ListOrphanedVolumesResponse,
+    PrepareVolumeExportRequest, PrepareVolumeExportResponse, ReadVolumeExportRequest,
+    ReadVolumeExportResponse, ResizeVolumeRequest, ResizeVolumeResponse, RpcError, RpcErrorCode,
+    SnapshotVolumeRequest, StreamFrame, VolumeChecksum, VolumeCreateProgress, VolumeEncryption,
 };
+use common::utils::redact_url;
+use futures::{Stream, StreamExt};
+use reqwest::header::{HeaderName, HeaderValue};
+use std::sync::Arc;
 use std::time::Duration;
-use tracing::warn;
+use tracing::{error, info, warn};
+
+/// 下载时每次向 Agent 读取的字节数
+const DOWNLOAD_CHUNK_BYTES: u64 = 2 * 1024 * 1024;
+
+const GIB: u64 = 1024 * 1024 * 1024;
 
 pub struct StorageService {
     state: AppState,
@@ -35,6 +53,30 @@ impl StorageService {
         Self { state }
     }
 
+    /// 发送存储卷状态更新到前端
+    async fn notify_volume_status_update(
+        &self,
+        volume_id: &str,
+        status: &str,
+        size_gb: Option<i64>,
+        message: Option<&str>,
+    ) {
+        let frontend_msg = FrontendMessage::VolumeStatusUpdate {
+            volume_id: volume_id.to_string(),
+            status: status.to_string(),
+            size_gb,
+            message: message.map(|s| s.to_string()),
+        };
+
+        let count = self.state.frontend_manager().broadcast(frontend_msg).await;
+        if count > 0 {
+            info!(
+                "已向 {} 个前端连接发送存储卷 {} 状态更新: {}",
+                count, volume_id, status
+            );
+        }
+    }
+
     /// 创建存储池
     pub async fn create_storage_pool(
         &self,
@@ -52,6 +94,7 @@ impl StorageService {
             capacity_gb: Set(dto.capacity_gb),
             allocated_gb: Set(Some(0)),
             available_gb: Set(dto.capacity_gb),
+            used_gb: Set(None),
             node_id: Set(dto.node_id),
             metadata: Set(dto.metadata),
             created_at: Set(now.into()),
@@ -209,7 +252,35 @@ impl StorageService {
         let volume_id = Uuid::new_v4().to_string();
         let now = Utc::now();
 
-        // 构建metadata，包含source信息
+        if dto.compress && dto.volume_type != "qcow2" {
+            return Err(anyhow::anyhow!("仅 qcow2 格式的存储卷支持压缩"));
+        }
+        let checksum = match dto.checksum.as_deref() {
+            Some(_) if dto.source.is_none() => {
+                return Err(anyhow::anyhow!("校验和仅适用于从 URL 创建的存储卷"));
+            }
+            Some(checksum) => Some(
+                checksum
+                    .parse::<VolumeChecksum>()
+                    .map_err(|e| anyhow::anyhow!(e))?
+                    .to_string(),
+            ),
+            None => None,
+        };
+        // 先校验源地址，s3:// 地址签发失败时不留下卷记录
+        let source_url = self.resolve_source(&dto)?;
+        if let Some(backing_volume_id) = &dto.backing_volume_id {
+            self.check_backing_volume(&dto, backing_volume_id).await?;
+        }
+        if dto.encryption.is_some() {
+            Self::check_encryption(&dto)?;
+        }
+        if let Some(owner_id) = dto.owner_id {
+            let request = QuotaRequest { volumes: 1, volume_gb: dto.size_gb, ..Default::default() };
+            DepartmentService::check_quota(db, owner_id, request).await?;
+        }
+
+        // 构建metadata，包含source信息（保存用户填写的地址，不含认证信息与预签名参数）
         let mut metadata = dto
             .metadata
             .unwrap_or_else(|| serde_json::Value::Object(serde_json::Map::new()));
@@ -221,6 +292,19 @@ impl StorageService {
                 );
             }
         }
+        if dto.compress {
+            if let Some(metadata_obj) = metadata.as_object_mut() {
+                metadata_obj.insert("compressed".to_string(), serde_json::Value::Bool(true));
+            }
+        }
+        if let Some(checksum) = &checksum {
+            if let Some(metadata_obj) = metadata.as_object_mut() {
+                metadata_obj.insert(
+                    "checksum".to_string(),
+                    serde_json::Value::String(checksum.clone()),
+                );
+            }
+        }
 
         // 先在数据库中创建记录
         let volume_active = VolumeActiveModel {
@@ -232,12 +316,21 @@ impl StorageService {
             path: Set(None),
             status: Set(VolumeStatus::Creating.as_str().to_string()),
             vm_id: Set(None),
+            backing_volume_id: Set(dto.backing_volume_id.clone()),
+            encryption: Set(dto.encryption.as_ref().map(|e| self.seal_encryption(e)).transpose()?),
             metadata: Set(Some(metadata)),
+            owner_id: Set(dto.owner_id),
             created_at: Set(now.into()),
             updated_at: Set(now.into()),
         };
 
-        let mut volume = volume_active.insert(db).await?;
+        // 卷记录与存储池分配量在同一事务中更新
+        let txn = db.begin().await?;
+        let mut volume = volume_active.insert(&txn).await?;
+        Self::adjust_pool_allocation(&txn, &dto.pool_id, dto.size_gb).await?;
+        txn.commit().await?;
+        self.notify_volume_status_update(&volume.id, &volume.status, Some(volume.size_gb), None)
+            .await;
 
         // 调用 Agent 创建实际的存储卷
         if let Some(node_id) = &pool.node_id {
@@ -248,32 +341,35 @@ impl StorageService {
                 storage_type: pool.pool_type.clone(),
                 format: dto.volume_type.clone(),
                 pool_id: pool.id.clone(),   // Agent会自动获取存储池信息
-                source: dto.source.clone(), // 传递外部URL
+                source: source_url,
+                compress: dto.compress,
+                checksum,
+                source_headers: dto.source_headers.clone(),
+                source_auth: dto.source_auth.clone(),
+                backing_volume_id: dto.backing_volume_id.clone(),
+                encryption: dto.encryption.clone(),
             };
 
-            // 使用 WebSocket RPC 调用 Agent 创建存储卷
-
-            let response_msg = self
-                .state
-                .agent_manager()
-                .call(
-                    node_id,
-                    "create_volume",
-                    serde_json::to_value(&request)?,
-                    Duration::from_secs(120), // 存储卷创建可能需要较长时间
-                )
-                .await
-                .map_err(|e| anyhow::anyhow!("WebSocket RPC 调用失败: {}", e))?;
-
-            let result: CreateVolumeResponse = serde_json::from_value(
-                response_msg
-                    .payload
-                    .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
-            )?;
-
-            if !result.success {
-                return Err(anyhow::anyhow!("Agent 创建存储卷失败: {}", result.message));
-            }
+            let result = match self.create_volume_on_agent(node_id, &request).await {
+                Ok(result) => result,
+                Err(e) => {
+                    // 创建失败的卷标记为 error，保留记录供用户查看后删除
+                    VolumeEntity::update_many()
+                        .col_expr(VolumeColumn::Status, Expr::value(VolumeStatus::Error.as_str()))
+                        .col_expr(VolumeColumn::UpdatedAt, Expr::value(Utc::now()))
+                        .filter(VolumeColumn::Id.eq(&volume_id))
+                        .exec(db)
+                        .await?;
+                    self.notify_volume_status_update(
+                        &volume_id,
+                        VolumeStatus::Error.as_str(),
+                        None,
+                        Some(&e.to_string()),
+                    )
+                    .await;
+                    return Err(e);
+                }
+            };
 
             // 更新卷状态和路径
             let mut volume_active: VolumeActiveModel = volume.into();
@@ -283,6 +379,8 @@ impl StorageService {
             }
             volume_active.updated_at = Set(Utc::now().into());
             volume = volume_active.update(db).await?;
+            self.notify_volume_status_update(&volume.id, &volume.status, Some(volume.size_gb), None)
+                .await;
         }
 
         Ok(VolumeResponse::from(volume))
@@ -447,6 +545,11 @@ impl StorageService {
             .one(db)
             .await?
             .ok_or_else(|| anyhow::anyhow!("存储卷不存在"))?;
+        Self::ensure_not_encrypted(&volume, "调整大小")?;
+        if let Some(owner_id) = volume.owner_id {
+            let request = QuotaRequest { volume_gb: dto.new_size_gb - volume.size_gb, ..Default::default() };
+            DepartmentService::check_quota(db, owner_id, request).await?;
+        }
 
         // 获取存储池信息以获取节点ID
         let pool = StoragePoolEntity::find_by_id(&volume.pool_id)
@@ -454,6 +557,15 @@ impl StorageService {
             .await?
             .ok_or_else(|| anyhow::anyhow!("存储池不存在"))?;
 
+        // 缩容会截断卷末尾的数据，按配置先创建安全快照
+        let safety_snapshot_id = if dto.new_size_gb < volume.size_gb {
+            SnapshotService::new(self.state.clone())
+                .create_safety_snapshot(volume_id, SafetyOperation::ShrinkVolume)
+                .await?
+        } else {
+            None
+        };
+
         // 调用 Agent 调整存储卷大小
         if let Some(node_id) = &pool.node_id {
             let request = ResizeVolumeRequest {
@@ -465,7 +577,7 @@ impl StorageService {
 
             let response_msg = self
                 .state
-                .agent_manager()
+                .agent_rpc()
                 .call(
                     node_id,
                     "resize_volume",
@@ -473,7 +585,7 @@ impl StorageService {
                     Duration::from_secs(60),
                 )
                 .await
-                .map_err(|e| anyhow::anyhow!("WebSocket RPC 调用失败: {}", e))?;
+                .map_err(storage_rpc_error)?;
 
             let result: ResizeVolumeResponse = serde_json::from_value(
                 response_msg
@@ -489,12 +601,33 @@ impl StorageService {
             }
         }
 
-        // 更新数据库中的大小
+        // 更新数据库中的大小，并按差值调整存储池分配量
+        let delta_gb = dto.new_size_gb - volume.size_gb;
+        let pool_id = volume.pool_id.clone();
+        let metadata = volume.metadata.clone();
         let mut volume_active: VolumeActiveModel = volume.into();
         volume_active.size_gb = Set(dto.new_size_gb);
         volume_active.updated_at = Set(Utc::now().into());
+        if let Some(snapshot_id) = safety_snapshot_id {
+            let mut metadata = metadata.unwrap_or_else(|| serde_json::json!({}));
+            if let Some(obj) = metadata.as_object_mut() {
+                obj.insert("safety_snapshot_id".to_string(), snapshot_id.into());
+            }
+            volume_active.metadata = Set(Some(metadata));
+        }
+
+        let txn = db.begin().await?;
+        let updated_volume = volume_active.update(&txn).await?;
+        Self::adjust_pool_allocation(&txn, &pool_id, delta_gb).await?;
+        txn.commit().await?;
+        self.notify_volume_status_update(
+            &updated_volume.id,
+            &updated_volume.status,
+            Some(updated_volume.size_gb),
+            None,
+        )
+        .await;
 
-        let updated_volume = volume_active.update(db).await?;
         Ok(VolumeResponse::from(updated_volume))
     }
 
@@ -511,6 +644,7 @@ impl StorageService {
         if volume.vm_id.is_some() {
             return Err(anyhow::anyhow!("存储卷正在被虚拟机使用，无法删除"));
         }
+        self.ensure_no_linked_clones(volume_id, "删除").await?;
 
         // 获取存储池信息以获取节点ID
         let pool = StoragePoolEntity::find_by_id(&volume.pool_id)
@@ -529,7 +663,7 @@ impl StorageService {
 
             let response_msg = self
                 .state
-                .agent_manager()
+                .agent_rpc()
                 .call(
                     node_id,
                     "delete_volume",
@@ -537,7 +671,7 @@ impl StorageService {
                     Duration::from_secs(60),
                 )
                 .await
-                .map_err(|e| anyhow::anyhow!("WebSocket RPC 调用失败: {}", e))?;
+                .map_err(storage_rpc_error)?;
 
             let result: DeleteVolumeResponse = serde_json::from_value(
                 response_msg
@@ -553,9 +687,136 @@ impl StorageService {
             }
         }
 
-        // 从数据库中删除
-        VolumeEntity::delete_by_id(volume_id).exec(db).await?;
+        // 从数据库中删除，同时释放存储池分配量
+        let txn = db.begin().await?;
+        VolumeEntity::delete_by_id(volume_id).exec(&txn).await?;
+        Self::adjust_pool_allocation(&txn, &volume.pool_id, -volume.size_gb).await?;
+        txn.commit().await?;
+        self.notify_volume_status_update(volume_id, "deleted", None, None).await;
+
+        Ok(())
+    }
+
+    /// 在指定节点上创建与存储卷同尺寸、同格式的空白副本
+    ///
+    /// 用于复制存储迁移：libvirt 按源磁盘路径把数据写入目标节点上的同名卷，
+    /// 因此副本路径必须与原卷一致。副本不单独建库记录，也不计入存储池分配量
+    pub async fn create_blank_copy_on_node(&self, volume_id: &str, node_id: &str) -> anyhow::Result<()> {
+        let db = &self.state.sea_db();
+
+        let volume = VolumeEntity::find_by_id(volume_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("存储卷不存在"))?;
+        let pool = StoragePoolEntity::find_by_id(&volume.pool_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("存储池不存在"))?;
+
+        // NFS 卷在目标节点上就是同一个文件，复制会覆盖正在使用的磁盘
+        if pool.pool_type == "nfs" {
+            return Err(anyhow::anyhow!(
+                "存储卷 {} 位于共享存储池 {}，复制存储迁移仅支持节点本地存储",
+                volume_id,
+                pool.name
+            ));
+        }
+
+        let request = CreateVolumeRequest {
+            volume_id: volume.id.clone(),
+            name: volume.name.clone(),
+            size_gb: volume.size_gb as u64,
+            storage_type: pool.pool_type.clone(),
+            format: volume.volume_type.clone(),
+            pool_id: pool.id.clone(),
+            source: None,
+            compress: false,
+            checksum: None,
+            source_headers: None,
+            source_auth: None,
+            backing_volume_id: None,
+            encryption: None,
+        };
+
+        let response_msg = self
+            .state
+            .agent_rpc()
+            .call(
+                node_id,
+                "create_volume",
+                serde_json::to_value(&request)?,
+                Duration::from_secs(120),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("在节点 {} 创建存储卷 {} 失败: {}", node_id, volume_id, e))?;
+
+        let result: CreateVolumeResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+        if !result.success {
+            return Err(anyhow::anyhow!(
+                "在节点 {} 创建存储卷 {} 失败: {}",
+                node_id,
+                volume_id,
+                result.message
+            ));
+        }
+
+        if result.path.is_some() && result.path != volume.path {
+            if let Err(e) = self.delete_copy_on_node(volume_id, node_id).await {
+                warn!("清理节点 {} 上的存储卷副本 {} 失败: {}", node_id, volume_id, e);
+            }
+            return Err(anyhow::anyhow!(
+                "节点 {} 上的存储卷 {} 路径与源节点不一致: {:?} != {:?}",
+                node_id,
+                volume_id,
+                result.path,
+                volume.path
+            ));
+        }
+
+        info!("已在节点 {} 创建存储卷 {} 的空白副本", node_id, volume_id);
+        Ok(())
+    }
 
+    /// 删除 create_blank_copy_on_node 在指定节点上创建的副本，不修改数据库记录
+    pub async fn delete_copy_on_node(&self, volume_id: &str, node_id: &str) -> anyhow::Result<()> {
+        let volume = VolumeEntity::find_by_id(volume_id)
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("存储卷不存在"))?;
+
+        let request = DeleteVolumeRequest {
+            volume_id: volume.id.clone(),
+            pool_id: volume.pool_id.clone(),
+        };
+        let response_msg = self
+            .state
+            .agent_rpc()
+            .call(
+                node_id,
+                "delete_volume",
+                serde_json::to_value(&request)?,
+                Duration::from_secs(60),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("删除节点 {} 上的存储卷 {} 失败: {}", node_id, volume_id, e))?;
+
+        let result: DeleteVolumeResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+        if !result.success {
+            return Err(anyhow::anyhow!(
+                "删除节点 {} 上的存储卷 {} 失败: {}",
+                node_id,
+                volume_id,
+                result.message
+            ));
+        }
         Ok(())
     }
 
@@ -568,16 +829,66 @@ impl StorageService {
             .one(db)
             .await?
             .ok_or_else(|| anyhow::anyhow!("源存储卷不存在"))?;
+        Self::ensure_not_encrypted(&source_volume, "克隆")?;
 
-        // 克隆必须在同一存储池内
-        let target_pool_id = source_volume.pool_id.clone();
+        // 从快照克隆时校验快照属于源卷且可用，快照标签即 qcow2 内部快照名
+        let source_snapshot = match &dto.source_snapshot_id {
+            Some(snapshot_id) => {
+                let snapshot = SnapshotEntity::find_by_id(snapshot_id)
+                    .one(db)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("快照 {} 不存在", snapshot_id))?;
+
+                if snapshot.volume_id != source_volume.id {
+                    return Err(anyhow::anyhow!(
+                        "快照 {} 不属于存储卷 {}",
+                        snapshot_id,
+                        source_volume.id
+                    ));
+                }
+                if snapshot.status != SnapshotStatus::Available.as_str() {
+                    return Err(anyhow::anyhow!(
+                        "快照 {} 状态不可用: {}",
+                        snapshot_id,
+                        snapshot.status
+                    ));
+                }
+                Some(snapshot)
+            }
+            None => None,
+        };
+        let size_gb = source_snapshot
+            .as_ref()
+            .and_then(|s| s.size_gb)
+            .unwrap_or(source_volume.size_gb);
+        // 克隆卷归属源卷的所有者
+        if let Some(owner_id) = source_volume.owner_id {
+            let request = QuotaRequest { volumes: 1, volume_gb: size_gb, ..Default::default() };
+            DepartmentService::check_quota(db, owner_id, request).await?;
+        }
 
-        // 检查存储池是否存在
-        let target_pool = StoragePoolEntity::find_by_id(&target_pool_id)
+        // 检查源存储池是否存在
+        let source_pool = StoragePoolEntity::find_by_id(&source_volume.pool_id)
             .one(db)
             .await?
             .ok_or_else(|| anyhow::anyhow!("存储池不存在"))?;
 
+        // 未指定目标存储池时克隆到源存储池；跨池克隆由同一节点上的 Agent 完成拷贝
+        let target_pool = match dto.target_pool_id.as_deref() {
+            Some(pool_id) if pool_id != source_pool.id => {
+                let pool = StoragePoolEntity::find_by_id(pool_id)
+                    .one(db)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("目标存储池不存在"))?;
+                if pool.node_id.is_none() || pool.node_id != source_pool.node_id {
+                    return Err(anyhow::anyhow!("跨存储池克隆要求目标存储池与源存储池位于同一节点"));
+                }
+                pool
+            }
+            _ => source_pool.clone(),
+        };
+        let target_pool_id = target_pool.id.clone();
+
         let target_volume_id = Uuid::new_v4().to_string();
         let now = Utc::now();
 
@@ -586,20 +897,30 @@ impl StorageService {
             id: Set(target_volume_id.clone()),
             name: Set(dto.target_name.clone()),
             volume_type: Set(source_volume.volume_type.clone()),
-            size_gb: Set(source_volume.size_gb),
+            size_gb: Set(size_gb),
             pool_id: Set(target_pool_id.clone()),
             path: Set(None),
             status: Set(VolumeStatus::Creating.as_str().to_string()),
             vm_id: Set(None),
+            backing_volume_id: Set(None),
+            encryption: Set(None),
             metadata: Set(Some(serde_json::json!({
                 "source_volume_id": dto.source_volume_id,
+                "source_snapshot_id": dto.source_snapshot_id,
+                "source_pool_id": source_pool.id,
                 "cloned_at": now.to_rfc3339()
             }))),
+            owner_id: Set(source_volume.owner_id),
             created_at: Set(now.into()),
             updated_at: Set(now.into()),
         };
 
-        let mut target_volume = target_volume_active.insert(db).await?;
+        let txn = db.begin().await?;
+        let mut target_volume = target_volume_active.insert(&txn).await?;
+        Self::adjust_pool_allocation(&txn, &target_pool_id, size_gb).await?;
+        txn.commit().await?;
+        self.notify_volume_status_update(&target_volume.id, &target_volume.status, Some(size_gb), None)
+            .await;
 
         // 调用 Agent 克隆存储卷
         if let Some(node_id) = &target_pool.node_id {
@@ -607,13 +928,17 @@ impl StorageService {
                 source_volume_id: dto.source_volume_id.clone(),
                 target_volume_id: target_volume_id.clone(),
                 target_name: dto.target_name.clone(),
-                pool_id: target_pool_id.clone(),
+                pool_id: source_pool.id.clone(),
+                target_pool_id: (target_pool_id != source_pool.id).then(|| target_pool_id.clone()),
+                snapshot_id: source_snapshot
+                    .as_ref()
+                    .map(|s| s.snapshot_tag.clone().unwrap_or_else(|| s.id.clone())),
             };
 
             // 使用 WebSocket RPC 调用 Agent 克隆存储卷
             let response_msg = self
                 .state
-                .agent_manager()
+                .agent_rpc()
                 .call(
                     node_id,
                     "clone_volume",
@@ -621,7 +946,7 @@ impl StorageService {
                     Duration::from_secs(300), // 克隆可能需要较长时间
                 )
                 .await
-                .map_err(|e| anyhow::anyhow!("WebSocket RPC 调用失败: {}", e))?;
+                .map_err(storage_rpc_error)?;
 
             let result: CloneVolumeResponse = serde_json::from_value(
                 response_msg
@@ -630,10 +955,21 @@ impl StorageService {
             )?;
 
             if !result.success {
-                // 克隆失败，删除数据库记录
+                // 克隆失败，删除数据库记录并释放分配量
+                let txn = db.begin().await?;
                 VolumeEntity::delete_by_id(&target_volume_id)
-                    .exec(db)
+                    .exec(&txn)
+                    .await?;
+                Self::adjust_pool_allocation(&txn, &target_pool_id, -size_gb)
                     .await?;
+                txn.commit().await?;
+                self.notify_volume_status_update(
+                    &target_volume_id,
+                    "deleted",
+                    None,
+                    Some(&format!("克隆失败: {}", result.message)),
+                )
+                .await;
                 return Err(anyhow::anyhow!("Agent 克隆存储卷失败: {}", result.message));
             }
 
@@ -645,8 +981,1669 @@ impl StorageService {
             }
             target_volume_active.updated_at = Set(Utc::now().into());
             target_volume = target_volume_active.update(db).await?;
+            self.notify_volume_status_update(&target_volume.id, &target_volume.status, Some(size_gb), None)
+                .await;
         }
 
         Ok(VolumeResponse::from(target_volume))
     }
+
+    /// 导入存储池中已存在的镜像文件
+    ///
+    /// Agent 检测格式与虚拟大小后才写入卷记录；已被其他存储卷使用（卷文件本身或引用导入的原文件）
+    /// 的路径拒绝导入
+    pub async fn import_volume(&self, dto: ImportVolumeDto) -> anyhow::Result<VolumeResponse> {
+        let db = &self.state.sea_db();
+
+        let pool = StoragePoolEntity::find_by_id(&dto.pool_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("存储池不存在"))?;
+        let node_id = pool
+            .node_id
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("存储池未关联节点"))?;
+
+        if !dto.path.starts_with('/') {
+            return Err(anyhow::anyhow!("镜像路径必须为绝对路径: {}", dto.path));
+        }
+        Self::ensure_image_unused(db, &dto.pool_id, &dto.path).await?;
+        if let Some(owner_id) = dto.owner_id {
+            let request = QuotaRequest { volumes: 1, ..Default::default() };
+            DepartmentService::check_quota(db, owner_id, request).await?;
+        }
+
+        let volume_id = Uuid::new_v4().to_string();
+        let request = ImportVolumeRequest {
+            pool_id: pool.id.clone(),
+            volume_id: volume_id.clone(),
+            path: dto.path.clone(),
+            mode: dto.mode,
+        };
+        let response_msg = self
+            .state
+            .agent_rpc()
+            .call(
+                &node_id,
+                "import_volume",
+                serde_json::to_value(&request)?,
+                Duration::from_secs(300), // 复制方式需要完整拷贝镜像
+            )
+            .await
+            .map_err(|e| {
+                if e.code == RpcErrorCode::InvalidParams {
+                    anyhow::anyhow!("无法导入镜像: {}", e.message)
+                } else {
+                    storage_rpc_error(e)
+                }
+            })?;
+        let result: ImportVolumeResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+
+        // 请求路径可能经由符号链接或 `..` 指向已登记的镜像，按 Agent 解析出的真实路径再查一次；
+        // 镜像容量也由 Agent 检测后才能得知，超出配额时同样删除刚导入的卷
+        // 旧版 Agent 不返回解析后的路径，沿用请求路径
+        let source_path = if result.source_path.is_empty() { dto.path.clone() } else { result.source_path };
+        let mut checked = Self::ensure_image_unused(db, &pool.id, &source_path).await;
+        if let (Ok(()), Some(owner_id)) = (&checked, dto.owner_id) {
+            let request = QuotaRequest { volumes: 1, volume_gb: result.size_gb as i64, ..Default::default() };
+            checked = DepartmentService::check_quota(db, owner_id, request).await;
+        }
+        if let Err(e) = checked {
+            let request = DeleteVolumeRequest { volume_id: volume_id.clone(), pool_id: pool.id.clone() };
+            if let Err(rpc_error) = self
+                .state
+                .agent_rpc()
+                .call(&node_id, "delete_volume", serde_json::to_value(&request)?, Duration::from_secs(60))
+                .await
+            {
+                warn!("删除未能登记的导入卷 {} 失败: {}", volume_id, rpc_error);
+            }
+            return Err(e);
+        }
+
+        let mut metadata = dto
+            .metadata
+            .unwrap_or_else(|| serde_json::Value::Object(serde_json::Map::new()));
+        if let Some(metadata_obj) = metadata.as_object_mut() {
+            metadata_obj.insert("import_path".to_string(), serde_json::json!(source_path));
+            metadata_obj.insert("import_mode".to_string(), serde_json::to_value(dto.mode)?);
+        }
+
+        let now = Utc::now();
+        let size_gb = result.size_gb as i64;
+        let volume_active = VolumeActiveModel {
+            id: Set(volume_id),
+            name: Set(dto.name),
+            volume_type: Set(result.format),
+            size_gb: Set(size_gb),
+            pool_id: Set(pool.id.clone()),
+            path: Set(Some(result.path)),
+            status: Set(VolumeStatus::Available.as_str().to_string()),
+            vm_id: Set(None),
+            backing_volume_id: Set(None),
+            encryption: Set(None),
+            metadata: Set(Some(metadata)),
+            owner_id: Set(dto.owner_id),
+            created_at: Set(now.into()),
+            updated_at: Set(now.into()),
+        };
+
+        let txn = db.begin().await?;
+        let volume = volume_active.insert(&txn).await?;
+        Self::adjust_pool_allocation(&txn, &pool.id, size_gb).await?;
+        txn.commit().await?;
+
+        info!("镜像 {} 已导入为存储卷 {}", dto.path, volume.id);
+        self.notify_volume_status_update(&volume.id, &volume.status, Some(size_gb), None)
+            .await;
+        Ok(VolumeResponse::from(volume))
+    }
+
+    /// 镜像文件不能是存储池中已有卷的文件，也不能已被其他卷导入
+    async fn ensure_image_unused<C: ConnectionTrait>(conn: &C, pool_id: &str, path: &str) -> anyhow::Result<()> {
+        let pool_volumes = VolumeEntity::find()
+            .filter(VolumeColumn::PoolId.eq(pool_id))
+            .all(conn)
+            .await?;
+        if let Some(existing) = pool_volumes.iter().find(|v| {
+            v.path.as_deref() == Some(path)
+                || v.metadata.as_ref().and_then(|m| m["import_path"].as_str()) == Some(path)
+        }) {
+            return Err(anyhow::anyhow!("镜像 {} 已被存储卷 {} 使用", path, existing.id));
+        }
+        Ok(())
+    }
+
+    /// 准备存储卷下载
+    ///
+    /// 仅允许 available 状态的卷；Agent 先转换出一致的导出副本，
+    /// 之后按需分段读取，避免读到正在被写入的卷文件
+    pub async fn prepare_volume_download(&self, volume_id: &str) -> anyhow::Result<VolumeDownload> {
+        let db = &self.state.sea_db();
+
+        let volume = VolumeEntity::find_by_id(volume_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("存储卷不存在"))?;
+        Self::ensure_not_encrypted(&volume, "下载")?;
+
+        if volume.status != VolumeStatus::Available.as_str() {
+            return Err(anyhow::anyhow!(
+                "存储卷当前状态为 {}，仅可下载 available 状态的存储卷",
+                volume.status
+            ));
+        }
+
+        let pool = StoragePoolEntity::find_by_id(&volume.pool_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("存储池不存在"))?;
+        let node_id = pool
+            .node_id
+            .ok_or_else(|| anyhow::anyhow!("存储池未关联节点"))?;
+
+        let request = PrepareVolumeExportRequest {
+            pool_id: volume.pool_id.clone(),
+            volume_id: volume.id.clone(),
+        };
+
+        // 首次导出需要完整转换卷文件，耗时与卷大小相关
+        let response_msg = self
+            .state
+            .agent_rpc()
+            .call(
+                &node_id,
+                "prepare_volume_export",
+                serde_json::to_value(&request)?,
+                Duration::from_secs(3600),
+            )
+            .await
+            .map_err(storage_rpc_error)?;
+
+        let export: PrepareVolumeExportResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+
+        info!(
+            "存储卷 {} 导出副本已就绪: {} 字节 ({})",
+            volume.id, export.size_bytes, export.format
+        );
+
+        // 文件名放在 Content-Disposition 中，只保留 ASCII 安全字符
+        let safe_name: String = volume
+            .name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' })
+            .collect();
+
+        Ok(VolumeDownload {
+            file_name: format!("{}.{}", safe_name, export.format),
+            size_bytes: export.size_bytes,
+            node_id,
+            pool_id: volume.pool_id,
+            volume_id: volume.id,
+            agent_rpc: self.state.agent_rpc(),
+        })
+    }
+
+    /// 存储池垃圾回收：找出磁盘上没有对应卷记录的文件，按需删除
+    ///
+    /// 删除需要 confirm 与存储池 ID 一致；Agent 删除前会再次核对，
+    /// 属于已知卷（含快照文件）或被引用为 backing file 的文件不会被删除
+    pub async fn gc_pool(&self, pool_id: &str, dto: PoolGcDto) -> anyhow::Result<PoolGcResponse> {
+        let db = &self.state.sea_db();
+
+        let pool = StoragePoolEntity::find_by_id(pool_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("存储池不存在"))?;
+
+        if dto.delete && dto.confirm.as_deref() != Some(pool_id) {
+            return Err(anyhow::anyhow!("删除孤立文件需要在 confirm 中填写存储池 ID 以确认"));
+        }
+
+        let node_id = pool
+            .node_id
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("存储池未关联节点"))?;
+
+        let known_volume_ids: Vec<String> = VolumeEntity::find()
+            .filter(VolumeColumn::PoolId.eq(pool_id))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|v| v.id)
+            .collect();
+
+        let request = ListOrphanedVolumesRequest {
+            pool_id: pool_id.to_string(),
+            known_volume_ids: known_volume_ids.clone(),
+        };
+        let response_msg = self
+            .state
+            .agent_rpc()
+            .call(
+                &node_id,
+                "list_orphaned_volumes",
+                serde_json::to_value(&request)?,
+                Duration::from_secs(120),
+            )
+            .await
+            .map_err(storage_rpc_error)?;
+
+        let listed: ListOrphanedVolumesResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+
+        let orphaned_bytes = listed.orphans.iter().map(|o| o.size_bytes).sum();
+        let mut response = PoolGcResponse {
+            pool_id: pool_id.to_string(),
+            orphans: listed.orphans,
+            orphaned_bytes,
+            deleted: Vec::new(),
+            skipped: Vec::new(),
+        };
+
+        if !dto.delete || response.orphans.is_empty() {
+            return Ok(response);
+        }
+
+        let request = DeleteOrphanedVolumesRequest {
+            pool_id: pool_id.to_string(),
+            known_volume_ids,
+            file_names: response.orphans.iter().map(|o| o.file_name.clone()).collect(),
+        };
+        let response_msg = self
+            .state
+            .agent_rpc()
+            .call(
+                &node_id,
+                "delete_orphaned_volumes",
+                serde_json::to_value(&request)?,
+                Duration::from_secs(120),
+            )
+            .await
+            .map_err(storage_rpc_error)?;
+
+        let deleted: DeleteOrphanedVolumesResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+
+        info!(
+            "存储池 {} 垃圾回收完成: 删除 {} 个文件，跳过 {} 个",
+            pool_id,
+            deleted.deleted.len(),
+            deleted.skipped.len()
+        );
+
+        response.deleted = deleted.deleted;
+        response.skipped = deleted.skipped;
+        Ok(response)
+    }
+
+    /// 按存储卷实际大小重新计算存储池的已分配/可用容量
+    pub async fn reconcile_pool_allocation(
+        &self,
+        pool_id: &str,
+    ) -> anyhow::Result<StoragePoolResponse> {
+        let db = &self.state.sea_db();
+
+        let result = Self::reconcile_pool_update()
+            .filter(StoragePoolColumn::Id.eq(pool_id))
+            .exec(db)
+            .await?;
+
+        if result.rows_affected == 0 {
+            return Err(anyhow::anyhow!("存储池不存在"));
+        }
+
+        let pool = StoragePoolEntity::find_by_id(pool_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("存储池不存在"))?;
+
+        Ok(StoragePoolResponse::from(pool))
+    }
+
+    /// 重新计算所有存储池的已分配/可用容量，返回修正的存储池数量
+    pub async fn reconcile_all_pool_allocations(&self) -> anyhow::Result<u64> {
+        let db = &self.state.sea_db();
+
+        // 只更新计数不一致的存储池（按卷求和的结果不会为 NULL）
+        let result = Self::reconcile_pool_update()
+            .filter(
+                Condition::any()
+                    .add(Expr::col(StoragePoolColumn::AllocatedGb).is_null())
+                    .add(Expr::col(StoragePoolColumn::AllocatedGb).ne(pool_volume_total()))
+                    .add(
+                        Condition::all()
+                            .add(Expr::col(StoragePoolColumn::CapacityGb).is_not_null())
+                            .add(
+                                Condition::any()
+                                    .add(Expr::col(StoragePoolColumn::AvailableGb).is_null())
+                                    .add(
+                                        Expr::col(StoragePoolColumn::AvailableGb).ne(
+                                            Expr::col(StoragePoolColumn::CapacityGb)
+                                                .sub(pool_volume_total()),
+                                        ),
+                                    ),
+                            ),
+                    ),
+            )
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+
+    /// 按存储卷大小之和重算存储池分配量的 UPDATE，由调用方追加过滤条件
+    fn reconcile_pool_update() -> UpdateMany<StoragePoolEntity> {
+        let available = Expr::case(
+            Expr::col(StoragePoolColumn::CapacityGb).is_null(),
+            Expr::col(StoragePoolColumn::AvailableGb),
+        )
+        .finally(Expr::col(StoragePoolColumn::CapacityGb).sub(pool_volume_total()));
+
+        StoragePoolEntity::update_many()
+            .col_expr(StoragePoolColumn::AllocatedGb, pool_volume_total())
+            .col_expr(StoragePoolColumn::AvailableGb, available.into())
+            .col_expr(StoragePoolColumn::UpdatedAt, Expr::value(Utc::now()))
+    }
+
+    /// 调用 Agent 创建存储卷，从 URL 创建时 Agent 流式推送的转换进度转发给前端
+    async fn create_volume_on_agent(
+        &self,
+        node_id: &str,
+        request: &CreateVolumeRequest,
+    ) -> anyhow::Result<CreateVolumeResponse> {
+        let mut stream = self
+            .state
+            .agent_rpc()
+            .open_stream(
+                node_id,
+                "create_volume",
+                serde_json::to_value(request)?,
+                Duration::from_secs(120), // 存储卷创建可能需要较长时间
+            )
+            .await
+            .map_err(storage_rpc_error)?;
+
+        let frontend_manager = self.state.frontend_manager();
+        let response = loop {
+            match stream.next().await {
+                Some(StreamFrame::Data(payload)) => {
+                    match serde_json::from_value::<VolumeCreateProgress>(payload) {
+                        Ok(progress) => {
+                            frontend_manager
+                                .broadcast(FrontendMessage::VolumeProgress {
+                                    volume_id: progress.volume_id,
+                                    stage: progress.stage,
+                                    progress_percent: progress.progress_percent,
+                                    downloaded_bytes: progress.downloaded_bytes,
+                                    total_bytes: progress.total_bytes,
+                                })
+                                .await;
+                        }
+                        Err(e) => warn!("解析存储卷创建进度失败: {}", e),
+                    }
+                }
+                Some(StreamFrame::End(result)) => break result,
+                None => break Err(RpcError::connection_closed()),
+            }
+        };
+        let response_msg = response.map_err(storage_rpc_error)?;
+
+        let result: CreateVolumeResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+
+        if !result.success {
+            return Err(anyhow::anyhow!("Agent 创建存储卷失败: {}", result.message));
+        }
+        Ok(result)
+    }
+
+    /// 校验链接克隆的基础镜像
+    ///
+    /// 基础镜像须与新卷位于同一存储池且处于空闲状态（运行中写入会破坏链接克隆），
+    /// 只支持一层 backing 链，新卷容量不能小于基础镜像
+    async fn check_backing_volume(&self, dto: &CreateVolumeDto, backing_volume_id: &str) -> anyhow::Result<()> {
+        if dto.source.is_some() {
+            return Err(anyhow::anyhow!("基础镜像与源地址不能同时指定"));
+        }
+        if dto.volume_type != "qcow2" {
+            return Err(anyhow::anyhow!("链接克隆仅支持 qcow2 格式"));
+        }
+
+        let backing = VolumeEntity::find_by_id(backing_volume_id)
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("基础镜像存储卷 {} 不存在", backing_volume_id))?;
+        if backing.pool_id != dto.pool_id {
+            return Err(anyhow::anyhow!("基础镜像存储卷必须与新存储卷位于同一存储池"));
+        }
+        if backing.status != VolumeStatus::Available.as_str() || backing.vm_id.is_some() {
+            return Err(anyhow::anyhow!("基础镜像存储卷状态不可用: {}", backing.status));
+        }
+        if backing.backing_volume_id.is_some() {
+            return Err(anyhow::anyhow!("基础镜像存储卷本身是链接克隆，不能再作为基础镜像"));
+        }
+        if backing.is_encrypted() {
+            return Err(anyhow::anyhow!("加密存储卷不能作为基础镜像"));
+        }
+        if dto.size_gb < backing.size_gb {
+            return Err(anyhow::anyhow!(
+                "链接克隆的容量（{}GB）不能小于基础镜像（{}GB）",
+                dto.size_gb,
+                backing.size_gb
+            ));
+        }
+        Ok(())
+    }
+
+    /// 加密仅支持空白 qcow2 卷：导入、压缩与链接克隆的数据写入都无法在创建时加密
+    fn check_encryption(dto: &CreateVolumeDto) -> anyhow::Result<()> {
+        if dto.volume_type != "qcow2" {
+            return Err(anyhow::anyhow!("仅 qcow2 格式的存储卷支持加密"));
+        }
+        if dto.source.is_some() || dto.compress || dto.backing_volume_id.is_some() {
+            return Err(anyhow::anyhow!("加密存储卷不能从源地址、压缩或基础镜像创建"));
+        }
+        Ok(())
+    }
+
+    /// 入库的加密参数：口令经 SECRET_ENCRYPTION_KEY 加密保存，密钥文件引用原样保存
+    fn seal_encryption(&self, encryption: &VolumeEncryption) -> anyhow::Result<serde_json::Value> {
+        let sealed = match encryption {
+            VolumeEncryption::Passphrase { passphrase } => VolumeEncryption::Passphrase {
+                passphrase: self.state.secrets.encrypt(passphrase)?,
+            },
+            VolumeEncryption::KeyFile { key_id } => VolumeEncryption::KeyFile { key_id: key_id.clone() },
+        };
+        Ok(serde_json::to_value(sealed)?)
+    }
+
+    /// 下发给 Agent 的加密参数，口令解密后仅用于本次请求
+    pub fn volume_encryption(&self, volume: &VolumeModel) -> anyhow::Result<Option<VolumeEncryption>> {
+        let Some(value) = &volume.encryption else {
+            return Ok(None);
+        };
+        let encryption = match serde_json::from_value(value.clone())? {
+            VolumeEncryption::Passphrase { passphrase } => VolumeEncryption::Passphrase {
+                passphrase: self.state.secrets.decrypt(&passphrase)?,
+            },
+            key_file => key_file,
+        };
+        Ok(Some(encryption))
+    }
+
+    /// 加密卷的数据需要口令才能读写，服务端发起的离线磁盘操作无法处理
+    pub fn ensure_not_encrypted(volume: &VolumeModel, action: &str) -> anyhow::Result<()> {
+        if volume.is_encrypted() {
+            return Err(anyhow::anyhow!("加密存储卷 {} 不支持{}", volume.id, action));
+        }
+        Ok(())
+    }
+
+    /// 链接克隆的基础镜像路径与格式，用于生成域 XML 的 `<backingStore>`；普通卷返回 None
+    pub async fn backing_store(&self, volume: &VolumeModel) -> anyhow::Result<Option<BackingStore>> {
+        let Some(backing_volume_id) = &volume.backing_volume_id else {
+            return Ok(None);
+        };
+        let backing = VolumeEntity::find_by_id(backing_volume_id)
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("存储卷 {} 的基础镜像 {} 不存在", volume.id, backing_volume_id))?;
+        let path = backing
+            .path
+            .ok_or_else(|| anyhow::anyhow!("基础镜像存储卷缺少路径: {}", backing.id))?;
+        Ok(Some(BackingStore { path, format: backing.volume_type }))
+    }
+
+    /// 检查存储卷不是任何链接克隆的基础镜像
+    ///
+    /// 基础镜像被删除或写入都会使链接克隆损坏，`action` 用于错误提示
+    pub async fn ensure_no_linked_clones(&self, volume_id: &str, action: &str) -> anyhow::Result<()> {
+        let clones = VolumeEntity::find()
+            .filter(VolumeColumn::BackingVolumeId.eq(volume_id))
+            .count(&self.state.sea_db())
+            .await?;
+        if clones > 0 {
+            return Err(anyhow::anyhow!(
+                "存储卷是 {} 个链接克隆的基础镜像，无法{}",
+                clones,
+                action
+            ));
+        }
+        Ok(())
+    }
+
+    /// 校验源地址及其认证参数，返回下发给 Agent 的地址
+    ///
+    /// s3:// 地址签发为预签名 URL，Agent 无需对象存储密钥；file:// 地址由 Agent 按允许的目录校验
+    fn resolve_source(&self, dto: &CreateVolumeDto) -> anyhow::Result<Option<String>> {
+        let has_credentials = dto.source_auth.is_some()
+            || dto.source_headers.as_ref().is_some_and(|headers| !headers.is_empty());
+        let Some(source) = dto.source.as_deref() else {
+            if has_credentials {
+                return Err(anyhow::anyhow!("未指定源地址时不能设置认证信息与请求头"));
+            }
+            return Ok(None);
+        };
+
+        let scheme = source
+            .split_once("://")
+            .map(|(scheme, _)| scheme.to_ascii_lowercase())
+            .unwrap_or_default();
+        match scheme.as_str() {
+            "http" | "https" => {
+                for (name, value) in dto.source_headers.iter().flatten() {
+                    HeaderName::from_bytes(name.as_bytes())
+                        .map_err(|_| anyhow::anyhow!("无效的源地址请求头名称: {}", name))?;
+                    HeaderValue::from_str(value)
+                        .map_err(|_| anyhow::anyhow!("源地址请求头 {} 的值无效", name))?;
+                }
+                Ok(Some(source.to_string()))
+            }
+            "s3" | "file" if has_credentials => {
+                Err(anyhow::anyhow!("仅 http(s) 源地址支持认证信息与自定义请求头"))
+            }
+            "file" => Ok(Some(source.to_string())),
+            "s3" => {
+                let settings = self
+                    .state
+                    .s3
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("未配置对象存储（S3_ACCESS_KEY），不支持 s3:// 源地址"))?;
+                let object: S3Object = source.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                Ok(Some(s3_presign::presign_get(settings, &object, Utc::now())?))
+            }
+            _ => Err(anyhow::anyhow!(
+                "不支持的源地址: {}（支持 http、https、s3、file）",
+                redact_url(source)
+            )),
+        }
+    }
+
+    /// 向存储池所在节点查询底层存储的实际容量并写回存储池
+    ///
+    /// capacity_gb 与 used_gb 取 Agent 上报的实际值，available_gb 仍为 capacity_gb 减去已分配量，
+    /// 与创建/删除卷时的调整保持一致；节点离线或查询失败的存储池保留原值。返回刷新的存储池数量
+    pub async fn refresh_pool_usage(&self) -> anyhow::Result<usize> {
+        let db = &self.state.sea_db();
+        let agent_rpc = self.state.agent_rpc();
+
+        let pools = StoragePoolEntity::find()
+            .filter(StoragePoolColumn::NodeId.is_not_null())
+            .all(db)
+            .await?;
+
+        let mut refreshed = 0;
+        for pool in pools {
+            let Some(node_id) = pool.node_id.as_deref() else {
+                continue;
+            };
+            if !agent_rpc.is_online(node_id).await {
+                continue;
+            }
+
+            let request = GetPoolUsageRequest { pool_id: pool.id.clone() };
+            let usage = match agent_rpc
+                .call(node_id, "get_pool_usage", serde_json::to_value(&request)?, Duration::from_secs(30))
+                .await
+                .map_err(storage_rpc_error)
+                .and_then(|msg| {
+                    let payload = msg.payload.ok_or_else(|| anyhow::anyhow!("响应无数据"))?;
+                    Ok(serde_json::from_value::<GetPoolUsageResponse>(payload)?)
+                }) {
+                Ok(usage) => usage,
+                Err(e) => {
+                    warn!("查询存储池 {} 的容量失败: {}", pool.id, e);
+                    continue;
+                }
+            };
+
+            let capacity_gb = (usage.total_bytes / GIB) as i64;
+            let available = Expr::val(capacity_gb).sub(Expr::expr(Func::coalesce([
+                Expr::col(StoragePoolColumn::AllocatedGb).into(),
+                Expr::val(0i64).into(),
+            ])));
+            StoragePoolEntity::update_many()
+                .col_expr(StoragePoolColumn::CapacityGb, Expr::value(capacity_gb))
+                .col_expr(StoragePoolColumn::UsedGb, Expr::value((usage.used_bytes / GIB) as i64))
+                .col_expr(StoragePoolColumn::AvailableGb, available)
+                .col_expr(StoragePoolColumn::UpdatedAt, Expr::value(Utc::now()))
+                .filter(StoragePoolColumn::Id.eq(&pool.id))
+                .exec(db)
+                .await?;
+            refreshed += 1;
+        }
+
+        Ok(refreshed)
+    }
+
+    /// 启动存储池容量定期刷新任务
+    pub fn start_pool_usage_refresher(state: AppState, check_interval_secs: u64) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(check_interval_secs));
+
+            loop {
+                interval.tick().await;
+
+                if let Err(e) = StorageService::new(state.clone()).refresh_pool_usage().await {
+                    error!("刷新存储池容量失败: {}", e);
+                }
+            }
+        });
+    }
+
+    /// 原子地调整存储池分配量（delta_gb 为正表示占用，为负表示释放）
+    ///
+    /// 使用单条 UPDATE 在数据库端完成读-改-写，并发创建/删除卷时不会丢失更新
+    async fn adjust_pool_allocation<C: ConnectionTrait>(
+        conn: &C,
+        pool_id: &str,
+        delta_gb: i64,
+    ) -> anyhow::Result<()> {
+        if delta_gb == 0 {
+            return Ok(());
+        }
+
+        // allocated_gb 不会低于 0
+        let allocated = || {
+            Expr::expr(Func::greatest([
+                Expr::expr(Func::coalesce([
+                    Expr::col(StoragePoolColumn::AllocatedGb).into(),
+                    Expr::val(0i64).into(),
+                ]))
+                .add(delta_gb),
+                Expr::val(0i64).into(),
+            ]))
+        };
+        let available = Expr::case(
+            Expr::col(StoragePoolColumn::CapacityGb).is_null(),
+            Expr::col(StoragePoolColumn::AvailableGb),
+        )
+        .finally(Expr::col(StoragePoolColumn::CapacityGb).sub(allocated()));
+
+        StoragePoolEntity::update_many()
+            .col_expr(StoragePoolColumn::AllocatedGb, allocated().into())
+            .col_expr(StoragePoolColumn::AvailableGb, available.into())
+            .col_expr(StoragePoolColumn::UpdatedAt, Expr::value(Utc::now()))
+            .filter(StoragePoolColumn::Id.eq(pool_id))
+            .exec(conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Agent 返回的存储操作错误，缺少外部命令与命令超时单独给出提示
+fn storage_rpc_error(e: RpcError) -> anyhow::Error {
+    match e.code {
+        RpcErrorCode::ChecksumMismatch => anyhow::anyhow!("源文件校验失败: {}", e.message),
+        RpcErrorCode::ToolNotFound => {
+            anyhow::anyhow!("节点缺少存储工具，请安装 qemu-img 或 lvm2: {}", e.message)
+        }
+        RpcErrorCode::Timeout => anyhow::anyhow!("存储操作超时: {}", e.message),
+        _ => anyhow::anyhow!("WebSocket RPC 调用失败: {}", e),
+    }
+}
+
+/// 存储池内所有存储卷大小之和（关联子查询，没有卷时为 0）
+fn pool_volume_total() -> SimpleExpr {
+    let total = Query::select()
+        .expr(Func::cast_as(
+            Func::coalesce([
+                Func::sum(Expr::col((VolumeEntity, VolumeColumn::SizeGb))).into(),
+                Expr::val(0i64).into(),
+            ]),
+            Alias::new("BIGINT"),
+        ))
+        .from(VolumeEntity)
+        .and_where(
+            Expr::col((VolumeEntity, VolumeColumn::PoolId))
+                .equals((StoragePoolEntity, StoragePoolColumn::Id)),
+        )
+        .to_owned();
+
+    SimpleExpr::SubQuery(None, Box::new(total.into_sub_query_statement()))
+}
+
+/// 已就绪的存储卷下载
+pub struct VolumeDownload {
+    /// 建议的下载文件名
+    pub file_name: String,
+    /// 导出副本总字节数
+    pub size_bytes: u64,
+    node_id: String,
+    pool_id: String,
+    volume_id: String,
+    agent_rpc: Arc<dyn AgentRpc>,
+}
+
+impl VolumeDownload {
+    /// 读取 [start, end) 区间的数据流
+    ///
+    /// 每个分段单独发起一次 RPC，下游消费完上一段后才读取下一段
+    pub fn into_stream(
+        self,
+        start: u64,
+        end: u64,
+    ) -> impl Stream<Item = anyhow::Result<Vec<u8>>> + Send + 'static {
+        futures::stream::try_unfold((self, start), move |(download, offset)| async move {
+            if offset >= end {
+                return Ok(None);
+            }
+
+            let length = (end - offset).min(DOWNLOAD_CHUNK_BYTES);
+            let data = download.read_chunk(offset, length).await?;
+            if data.is_empty() {
+                return Err(anyhow::anyhow!("导出副本在偏移 {} 处提前结束", offset));
+            }
+
+            let next = offset + data.len() as u64;
+            Ok(Some((data, (download, next))))
+        })
+    }
+
+    async fn read_chunk(&self, offset: u64, length: u64) -> anyhow::Result<Vec<u8>> {
+        use base64::Engine;
+
+        let request = ReadVolumeExportRequest {
+            pool_id: self.pool_id.clone(),
+            volume_id: self.volume_id.clone(),
+            offset,
+            length,
+        };
+
+        let response_msg = self
+            .agent_rpc
+            .call(
+                &self.node_id,
+                "read_volume_export",
+                serde_json::to_value(&request)?,
+                Duration::from_secs(60),
+            )
+            .await
+            .map_err(storage_rpc_error)?;
+
+        let chunk: ReadVolumeExportResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+
+        base64::engine::general_purpose::STANDARD
+            .decode(chunk.data)
+            .map_err(|e| anyhow::anyhow!("下载数据解码失败: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{NodeAlertThresholds, S3Settings};
+    use crate::db::models::node;
+    use crate::db::models::storage_pool::Model as StoragePoolModel;
+    use crate::db::models::volume::Model as VolumeModel;
+    use crate::ws::agent_rpc::mock::MockAgentRpc;
+    use crate::ws::AgentConnectionManager;
+    use common::utils::BridgeNaming;
+    use common::ws_rpc::{DiskBusType, RpcError, SourceAuth, VolumeEncryption, VolumeImportMode};
+    use std::collections::HashMap;
+    use sea_orm::{DatabaseConnection, IntoActiveModel};
+
+    fn pool(id: &str, node_id: &str) -> StoragePoolModel {
+        let now = Utc::now();
+        StoragePoolModel {
+            id: id.to_string(),
+            name: format!("pool-{}", id),
+            pool_type: "nfs".to_string(),
+            status: "active".to_string(),
+            config: serde_json::json!({ "mount_path": "/mnt/nfs" }),
+            capacity_gb: Some(1000),
+            allocated_gb: Some(0),
+            available_gb: Some(1000),
+            used_gb: None,
+            node_id: Some(node_id.to_string()),
+            metadata: None,
+            created_at: now.into(),
+            updated_at: now.into(),
+        }
+    }
+
+    fn volume(id: &str, pool_id: &str, size_gb: i64, status: VolumeStatus) -> VolumeModel {
+        let now = Utc::now();
+        VolumeModel {
+            id: id.to_string(),
+            name: format!("vol-{}", id),
+            volume_type: "qcow2".to_string(),
+            size_gb,
+            pool_id: pool_id.to_string(),
+            path: None,
+            status: status.as_str().to_string(),
+            vm_id: None,
+            backing_volume_id: None,
+            encryption: None,
+            metadata: None,
+            owner_id: None,
+            created_at: now.into(),
+            updated_at: now.into(),
+        }
+    }
+
+    /// 内存库中写入存储池（及其所在节点）与存储卷
+    async fn db_with(pools: Vec<StoragePoolModel>, volumes: Vec<VolumeModel>) -> DatabaseConnection {
+        let db = crate::lifecycle_tests::sqlite_db().await;
+        let now = Utc::now();
+        for pool in pools {
+            let node_id = pool.node_id.clone().unwrap();
+            if node::Entity::find_by_id(node_id.clone()).one(&db).await.unwrap().is_none() {
+                node::ActiveModel {
+                    id: Set(node_id.clone()),
+                    hostname: Set(format!("host-{}", node_id)),
+                    ip_address: Set("10.0.0.11".to_string()),
+                    status: Set("online".to_string()),
+                    hypervisor_type: Set(None),
+                    hypervisor_version: Set(None),
+                    cpu_cores: Set(None),
+                    cpu_threads: Set(None),
+                    memory_total: Set(None),
+                    disk_total: Set(None),
+                    cpu_usage: Set(None),
+                    memory_used: Set(None),
+                    disk_used: Set(None),
+                    metadata: Set(None),
+                    ipmi_address: Set(None),
+                    ipmi_username: Set(None),
+                    ipmi_password: Set(None),
+                    shutdown_policy: Set("shutdown".to_string()),
+                    has_swtpm: Set(false),
+                    has_ovs: Set(false),
+                    numa_topology: Set(None),
+                    architectures: Set(None),
+                    last_heartbeat: Set(Some(now.into())),
+                    created_at: Set(now.into()),
+                    updated_at: Set(now.into()),
+                }
+                .insert(&db)
+                .await
+                .unwrap();
+            }
+            pool.into_active_model().insert(&db).await.unwrap();
+        }
+        for volume in volumes {
+            volume.into_active_model().insert(&db).await.unwrap();
+        }
+        db
+    }
+
+    fn service(db: DatabaseConnection, agent: Arc<MockAgentRpc>) -> StorageService {
+        let state = AppState::new(
+            db,
+            AgentConnectionManager::new(),
+            BridgeNaming::new(BridgeNaming::DEFAULT_PREFIX).unwrap(),
+            false,
+            NodeAlertThresholds::default(),
+            DiskBusType::Virtio,
+        )
+        .with_agent_rpc(agent);
+        StorageService::new(state)
+    }
+
+    fn create_dto() -> CreateVolumeDto {
+        CreateVolumeDto {
+            name: "data".to_string(),
+            pool_id: "p1".to_string(),
+            size_gb: 20,
+            volume_type: "qcow2".to_string(),
+            source: None,
+            source_headers: None,
+            source_auth: None,
+            compress: false,
+            checksum: None,
+            backing_volume_id: None,
+            encryption: None,
+            metadata: None,
+            owner_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_volume_marks_available_with_agent_path() {
+        let db = db_with(vec![pool("p1", "n1")], vec![]).await;
+        let agent = Arc::new(MockAgentRpc::new().respond(
+            "create_volume",
+            serde_json::json!({ "success": true, "message": "ok", "path": "/mnt/nfs/v1.qcow2" }),
+        ));
+
+        let result = service(db.clone(), agent.clone()).create_volume(create_dto()).await.unwrap();
+
+        assert_eq!(result.status, "available");
+        assert_eq!(result.path.as_deref(), Some("/mnt/nfs/v1.qcow2"));
+        let pool = StoragePoolEntity::find_by_id("p1".to_string())
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(pool.allocated_gb, Some(20));
+        assert_eq!(pool.available_gb, Some(980));
+        let calls = agent.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].node_id, "n1");
+        assert_eq!(calls[0].method, "create_volume");
+        assert_eq!(calls[0].payload["pool_id"], "p1");
+        assert_eq!(calls[0].payload["size_gb"], 20);
+    }
+
+    #[tokio::test]
+    async fn test_import_volume_records_detected_image_and_rejects_tracked_path() {
+        let mut imported = volume("seeded", "p1", 10, VolumeStatus::Available);
+        imported.metadata = Some(serde_json::json!({ "import_path": "/mnt/nfs/seed/debian.qcow2" }));
+        let mut managed = volume("managed", "p1", 10, VolumeStatus::Available);
+        managed.path = Some("/mnt/nfs/managed.qcow2".to_string());
+        let db = db_with(vec![pool("p1", "n1")], vec![imported, managed]).await;
+        let imported_response = |source_path: &str| {
+            serde_json::json!({
+                "success": true,
+                "message": "ok",
+                "path": "/mnt/nfs/v1.qcow2",
+                "format": "qcow2",
+                "size_gb": 8,
+                "source_path": source_path
+            })
+        };
+        let agent = Arc::new(
+            MockAgentRpc::new()
+                .respond("import_volume", imported_response("/mnt/nfs/seed/ubuntu.qcow2"))
+                .respond("import_volume", imported_response("/mnt/nfs/seed/debian.qcow2"))
+                .respond("delete_volume", serde_json::json!({ "success": true, "message": "ok" })),
+        );
+        let service = service(db.clone(), agent.clone());
+        let import = |path: &str| ImportVolumeDto {
+            name: "ubuntu".to_string(),
+            pool_id: "p1".to_string(),
+            path: path.to_string(),
+            mode: VolumeImportMode::Reference,
+            metadata: None,
+            owner_id: None,
+        };
+
+        for path in ["/mnt/nfs/seed/debian.qcow2", "/mnt/nfs/managed.qcow2", "seed/ubuntu.qcow2"] {
+            assert!(service.import_volume(import(path)).await.is_err());
+        }
+        assert!(agent.calls().is_empty());
+
+        let volume = service.import_volume(import("/mnt/nfs/links/../seed//ubuntu.qcow2")).await.unwrap();
+
+        assert_eq!(volume.status, "available");
+        assert_eq!(volume.volume_type, "qcow2");
+        assert_eq!(volume.size_gb, 8);
+        assert_eq!(volume.path.as_deref(), Some("/mnt/nfs/v1.qcow2"));
+        assert_eq!(volume.metadata.as_ref().unwrap()["import_mode"], "reference");
+        assert_eq!(volume.metadata.as_ref().unwrap()["import_path"], "/mnt/nfs/seed/ubuntu.qcow2");
+        let calls = agent.calls();
+        assert_eq!(calls[0].method, "import_volume");
+        assert_eq!(calls[0].payload["path"], "/mnt/nfs/links/../seed//ubuntu.qcow2");
+        assert_eq!(calls[0].payload["volume_id"], volume.id.as_str());
+
+        // 符号链接指向已导入的镜像：Agent 解析出真实路径后被拒绝，并删除刚登记的卷文件
+        let err = service.import_volume(import("/mnt/nfs/links/debian.qcow2")).await.unwrap_err();
+        assert!(err.to_string().contains("已被存储卷 seeded 使用"), "{}", err);
+        let calls = agent.calls();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[2].method, "delete_volume");
+        assert_eq!(calls[2].payload["volume_id"], calls[1].payload["volume_id"]);
+        let pool = StoragePoolEntity::find_by_id("p1".to_string()).one(&db).await.unwrap().unwrap();
+        assert_eq!(pool.allocated_gb, Some(8));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_pool_usage_updates_capacity_and_keeps_allocation() {
+        let mut p1 = pool("p1", "n1");
+        p1.allocated_gb = Some(20);
+        let db = db_with(vec![p1, pool("p2", "n1")], vec![]).await;
+        let agent = Arc::new(
+            MockAgentRpc::new()
+                .respond(
+                    "get_pool_usage",
+                    serde_json::json!({
+                        "pool_id": "p1",
+                        "total_bytes": 500 * GIB,
+                        "used_bytes": 120 * GIB + 1,
+                        "available_bytes": 380 * GIB
+                    }),
+                )
+                .fail("get_pool_usage", RpcError::new(RpcErrorCode::StorageError, "statvfs failed")),
+        );
+
+        let refreshed = service(db.clone(), agent.clone()).refresh_pool_usage().await.unwrap();
+
+        assert_eq!(refreshed, 1);
+        assert_eq!(agent.calls().len(), 2);
+        let p1 = StoragePoolEntity::find_by_id("p1".to_string()).one(&db).await.unwrap().unwrap();
+        assert_eq!(p1.capacity_gb, Some(500));
+        assert_eq!(p1.used_gb, Some(120));
+        assert_eq!(p1.allocated_gb, Some(20));
+        assert_eq!(p1.available_gb, Some(480));
+        // 查询失败的存储池保留原值
+        let p2 = StoragePoolEntity::find_by_id("p2".to_string()).one(&db).await.unwrap().unwrap();
+        assert_eq!(p2.capacity_gb, Some(1000));
+        assert_eq!(p2.used_gb, None);
+    }
+
+    #[tokio::test]
+    async fn test_create_volume_from_url_forwards_progress() {
+        let db = db_with(vec![pool("p1", "n1")], vec![]).await;
+        let agent = Arc::new(MockAgentRpc::new().respond(
+            "create_volume",
+            serde_json::json!({ "success": true, "message": "ok", "path": "/mnt/nfs/v1.qcow2" }),
+        ));
+        agent.push_stream(
+            "create_volume",
+            vec![
+                serde_json::json!({ "volume_id": "v1", "stage": "download", "progress_percent": 0.0, "downloaded_bytes": 4096 }),
+                serde_json::json!({ "volume_id": "v1", "stage": "convert", "progress_percent": 35.0 }),
+                serde_json::json!({ "volume_id": "v1", "stage": "convert", "progress_percent": 100.0 }),
+            ],
+        );
+
+        let service = service(db, agent.clone());
+        let (sender, mut frontend) = tokio::sync::mpsc::unbounded_channel();
+        service
+            .state
+            .frontend_manager()
+            .register("frontend-1".to_string(), None, sender)
+            .await;
+
+        let dto = CreateVolumeDto {
+            source: Some("http://images.example.com/ubuntu.img".to_string()),
+            ..create_dto()
+        };
+        let result = service.create_volume(dto).await.unwrap();
+
+        assert_eq!(result.status, "available");
+        assert_eq!(
+            agent.calls()[0].payload["source"],
+            "http://images.example.com/ubuntu.img"
+        );
+        let mut frames = Vec::new();
+        let mut statuses = Vec::new();
+        while let Ok(msg) = frontend.try_recv() {
+            match msg {
+                FrontendMessage::VolumeProgress {
+                    volume_id,
+                    stage,
+                    progress_percent,
+                    downloaded_bytes,
+                    total_bytes,
+                } => {
+                    assert_eq!(volume_id, "v1");
+                    frames.push((stage, progress_percent, downloaded_bytes, total_bytes));
+                }
+                FrontendMessage::VolumeStatusUpdate { volume_id, status, size_gb, .. } => {
+                    assert_eq!(volume_id, result.id);
+                    assert_eq!(size_gb, Some(20));
+                    statuses.push(status);
+                }
+                other => panic!("unexpected frontend message: {:?}", other),
+            }
+        }
+        // 服务端未返回 Content-Length 时下载进度只有字节数
+        assert_eq!(
+            frames,
+            vec![
+                ("download".to_string(), 0.0, Some(4096), None),
+                ("convert".to_string(), 35.0, None, None),
+                ("convert".to_string(), 100.0, None, None),
+            ]
+        );
+        assert_eq!(statuses, vec!["creating", "available"]);
+    }
+
+    #[tokio::test]
+    async fn test_create_volume_surfaces_agent_error() {
+        let db = db_with(vec![pool("p1", "n1")], vec![]).await;
+        let agent = Arc::new(
+            MockAgentRpc::new().fail("create_volume", RpcError::internal_error("磁盘已满")),
+        );
+
+        let service = service(db.clone(), agent);
+        let (sender, mut frontend) = tokio::sync::mpsc::unbounded_channel();
+        service
+            .state
+            .frontend_manager()
+            .register("frontend-1".to_string(), None, sender)
+            .await;
+
+        let err = service.create_volume(create_dto()).await.unwrap_err();
+
+        assert!(err.to_string().contains("磁盘已满"));
+        let volume = VolumeEntity::find().one(&db).await.unwrap().unwrap();
+        assert_eq!(volume.status, "error");
+        let mut statuses = Vec::new();
+        while let Ok(msg) = frontend.try_recv() {
+            if let FrontendMessage::VolumeStatusUpdate { status, message, .. } = msg {
+                statuses.push((status, message.is_some()));
+            }
+        }
+        assert_eq!(
+            statuses,
+            vec![("creating".to_string(), false), ("error".to_string(), true)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_volume_checksum() {
+        let db = db_with(vec![pool("p1", "n1")], vec![]).await;
+        let agent = Arc::new(MockAgentRpc::new().fail(
+            "create_volume",
+            RpcError::new(RpcErrorCode::ChecksumMismatch, "expected sha256:aa.., got sha256:bb.."),
+        ));
+        let service = service(db, agent.clone());
+        let digest = "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855";
+
+        // 校验和只能用于从 URL 创建，格式错误时不调用 Agent
+        let err = service
+            .create_volume(CreateVolumeDto {
+                checksum: Some(format!("sha256:{}", digest)),
+                ..create_dto()
+            })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("校验和"));
+        let err = service
+            .create_volume(CreateVolumeDto {
+                source: Some("http://images.example.com/ubuntu.img".to_string()),
+                checksum: Some("md5:abc".to_string()),
+                ..create_dto()
+            })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("校验和"));
+        assert!(agent.calls().is_empty());
+
+        let err = service
+            .create_volume(CreateVolumeDto {
+                source: Some("http://images.example.com/ubuntu.img".to_string()),
+                checksum: Some(format!("SHA256:{}", digest)),
+                ..create_dto()
+            })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("源文件校验失败"));
+        assert_eq!(
+            agent.calls()[0].payload["checksum"],
+            format!("sha256:{}", digest.to_ascii_lowercase())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_volume_resolves_source_and_credentials() {
+        let db = db_with(vec![pool("p1", "n1")], vec![]).await;
+        let created = serde_json::json!({ "success": true, "message": "ok", "path": "/mnt/nfs/v1.qcow2" });
+        let agent = Arc::new(
+            MockAgentRpc::new()
+                .respond("create_volume", created.clone())
+                .respond("create_volume", created),
+        );
+        let bearer = || Some(SourceAuth::Bearer { token: "tok-123".to_string() });
+
+        // 校验失败时不调用 Agent
+        for dto in [
+            CreateVolumeDto { source_auth: bearer(), ..create_dto() },
+            CreateVolumeDto {
+                source: Some("file:///var/lib/easy-vm-cloud/images/a.img".to_string()),
+                source_auth: bearer(),
+                ..create_dto()
+            },
+            CreateVolumeDto { source: Some("ftp://images.example.com/a.img".to_string()), ..create_dto() },
+            CreateVolumeDto { source: Some("s3://images/a.qcow2".to_string()), ..create_dto() },
+        ] {
+            let err = service(db.clone(), agent.clone()).create_volume(dto).await.unwrap_err();
+            assert!(err.to_string().contains("源地址"), "{}", err);
+        }
+        assert!(agent.calls().is_empty());
+
+        // http(s) 源地址的请求头与认证信息原样下发，但不保存
+        let volume = service(db.clone(), agent.clone())
+            .create_volume(CreateVolumeDto {
+                source: Some("https://registry.example.com/ubuntu.qcow2".to_string()),
+                source_headers: Some(HashMap::from([("X-Project".to_string(), "base".to_string())])),
+                source_auth: bearer(),
+                ..create_dto()
+            })
+            .await
+            .unwrap();
+        let payload = &agent.calls()[0].payload;
+        assert_eq!(payload["source_auth"], serde_json::json!({ "type": "bearer", "token": "tok-123" }));
+        assert_eq!(payload["source_headers"]["X-Project"], "base");
+        assert!(!volume.metadata.unwrap().to_string().contains("tok-123"));
+
+        // s3:// 源地址签发为预签名 URL，卷元数据中保留原地址
+        let state = service(db, agent.clone()).state.with_s3_settings(Some(S3Settings {
+            endpoint: "http://minio.local:9000".to_string(),
+            region: "us-east-1".to_string(),
+            access_key: "minio".to_string(),
+            secret_key: "minio-secret".to_string(),
+            path_style: true,
+            presign_expires_secs: 3600,
+        }));
+        let volume = StorageService::new(state)
+            .create_volume(CreateVolumeDto {
+                source: Some("s3://images/ubuntu.qcow2".to_string()),
+                ..create_dto()
+            })
+            .await
+            .unwrap();
+        let source = agent.calls()[1].payload["source"].as_str().unwrap().to_string();
+        assert!(source.starts_with("http://minio.local:9000/images/ubuntu.qcow2?X-Amz-Algorithm="));
+        assert!(!source.contains("minio-secret"));
+        assert_eq!(volume.metadata.unwrap()["source"], "s3://images/ubuntu.qcow2");
+    }
+
+    #[tokio::test]
+    async fn test_create_linked_clone_validates_backing_volume() {
+        let mut chained = volume("chained", "p1", 10, VolumeStatus::Available);
+        chained.backing_volume_id = Some("base".to_string());
+        let db = db_with(
+            vec![pool("p1", "n1"), pool("p2", "n1")],
+            vec![
+                volume("base", "p1", 10, VolumeStatus::Available),
+                volume("other-pool", "p2", 10, VolumeStatus::Available),
+                volume("busy", "p1", 10, VolumeStatus::InUse),
+                chained,
+            ],
+        )
+        .await;
+        let agent = Arc::new(MockAgentRpc::new().respond(
+            "create_volume",
+            serde_json::json!({ "success": true, "message": "ok", "path": "/mnt/nfs/v1.qcow2" }),
+        ));
+        let service = service(db.clone(), agent.clone());
+        let clone_of = |id: &str| CreateVolumeDto { backing_volume_id: Some(id.to_string()), ..create_dto() };
+
+        for dto in [
+            clone_of("missing"),
+            clone_of("other-pool"),
+            clone_of("busy"),
+            clone_of("chained"),
+            CreateVolumeDto { size_gb: 5, ..clone_of("base") },
+            CreateVolumeDto { volume_type: "raw".to_string(), ..clone_of("base") },
+            CreateVolumeDto { source: Some("https://images.example.com/a.qcow2".to_string()), ..clone_of("base") },
+        ] {
+            assert!(service.create_volume(dto).await.is_err());
+        }
+        assert!(agent.calls().is_empty());
+
+        let clone = service.create_volume(clone_of("base")).await.unwrap();
+
+        assert_eq!(clone.backing_volume_id.as_deref(), Some("base"));
+        let calls = agent.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].payload["backing_volume_id"], "base");
+    }
+
+    #[tokio::test]
+    async fn test_create_encrypted_volume_forwards_spec_but_hides_it() {
+        let db = db_with(vec![pool("p1", "n1")], vec![]).await;
+        let agent = Arc::new(MockAgentRpc::new().respond(
+            "create_volume",
+            serde_json::json!({ "success": true, "message": "ok", "path": "/mnt/nfs/v1.qcow2" }),
+        ));
+        let service = service(db.clone(), agent.clone());
+        let encrypted = || CreateVolumeDto {
+            encryption: Some(VolumeEncryption::Passphrase { passphrase: "s3cret".to_string() }),
+            ..create_dto()
+        };
+
+        for dto in [
+            CreateVolumeDto { volume_type: "raw".to_string(), ..encrypted() },
+            CreateVolumeDto { compress: true, ..encrypted() },
+            CreateVolumeDto { source: Some("https://images.example.com/a.qcow2".to_string()), ..encrypted() },
+        ] {
+            assert!(service.create_volume(dto).await.is_err());
+        }
+        assert!(agent.calls().is_empty());
+
+        let volume = service.create_volume(encrypted()).await.unwrap();
+
+        assert!(volume.encrypted);
+        assert!(!serde_json::to_string(&volume).unwrap().contains("s3cret"));
+        let calls = agent.calls();
+        assert_eq!(calls[0].payload["encryption"]["passphrase"], "s3cret");
+
+        // 口令加密入库，下发前解密
+        let stored = VolumeEntity::find_by_id(&volume.id).one(&db).await.unwrap().unwrap();
+        assert!(!stored.encryption.as_ref().unwrap().to_string().contains("s3cret"));
+        assert_eq!(
+            service.volume_encryption(&stored).unwrap(),
+            Some(VolumeEncryption::Passphrase { passphrase: "s3cret".to_string() })
+        );
+        assert!(StorageService::ensure_not_encrypted(&stored, "克隆").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delete_volume_refuses_base_with_linked_clones() {
+        let mut clone = volume("clone", "p1", 20, VolumeStatus::Available);
+        clone.backing_volume_id = Some("base".to_string());
+        let db = db_with(
+            vec![pool("p1", "n1")],
+            vec![volume("base", "p1", 10, VolumeStatus::Available), clone],
+        )
+        .await;
+        let deleted = serde_json::json!({ "success": true, "message": "ok" });
+        let agent = Arc::new(
+            MockAgentRpc::new()
+                .respond("delete_volume", deleted.clone())
+                .respond("delete_volume", deleted),
+        );
+        let service = service(db.clone(), agent.clone());
+
+        let err = service.delete_volume("base").await.unwrap_err();
+        assert!(err.to_string().contains("链接克隆的基础镜像"), "{}", err);
+        assert!(agent.calls().is_empty());
+
+        // 链接克隆删除后基础镜像即可删除
+        service.delete_volume("clone").await.unwrap();
+        service.delete_volume("base").await.unwrap();
+        assert_eq!(agent.calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_create_volume_rejects_compressed_raw_without_agent_call() {
+        let db = db_with(vec![pool("p1", "n1")], vec![]).await;
+        let agent = Arc::new(MockAgentRpc::new());
+
+        let dto = CreateVolumeDto {
+            volume_type: "raw".to_string(),
+            compress: true,
+            ..create_dto()
+        };
+        let err = service(db, agent.clone()).create_volume(dto).await.unwrap_err();
+
+        assert!(err.to_string().contains("qcow2"));
+        assert!(agent.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resize_volume_updates_size_after_agent_success() {
+        let db = db_with(
+            vec![pool("p1", "n1")],
+            vec![volume("v1", "p1", 20, VolumeStatus::Available)],
+        )
+        .await;
+        let agent = Arc::new(MockAgentRpc::new().respond(
+            "resize_volume",
+            serde_json::json!({ "success": true, "message": "ok" }),
+        ));
+
+        let result = service(db, agent.clone())
+            .resize_volume("v1", ResizeVolumeDto { new_size_gb: 50 })
+            .await
+            .unwrap();
+
+        assert_eq!(result.size_gb, 50);
+        assert_eq!(agent.calls()[0].payload["new_size_gb"], 50);
+    }
+
+    #[tokio::test]
+    async fn test_resize_volume_keeps_db_when_agent_reports_failure() {
+        let db = db_with(
+            vec![pool("p1", "n1")],
+            vec![volume("v1", "p1", 20, VolumeStatus::Available)],
+        )
+        .await;
+        let agent = Arc::new(MockAgentRpc::new().respond(
+            "resize_volume",
+            serde_json::json!({ "success": false, "message": "不支持缩容" }),
+        ));
+
+        let err = service(db.clone(), agent)
+            .resize_volume("v1", ResizeVolumeDto { new_size_gb: 10 })
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("不支持缩容"));
+        let volume = VolumeEntity::find_by_id("v1".to_string()).one(&db).await.unwrap().unwrap();
+        assert_eq!(volume.size_gb, 20);
+    }
+
+    #[tokio::test]
+    async fn test_resize_volume_on_offline_node_keeps_db() {
+        let db = db_with(
+            vec![pool("p1", "n1")],
+            vec![volume("v1", "p1", 20, VolumeStatus::Available)],
+        )
+        .await;
+        let agent = Arc::new(MockAgentRpc::offline());
+
+        let err = service(db.clone(), agent.clone())
+            .resize_volume("v1", ResizeVolumeDto { new_size_gb: 40 })
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("节点离线: n1"), "{}", err);
+        assert_eq!(agent.calls()[0].method, "resize_volume");
+        let volume = VolumeEntity::find_by_id("v1".to_string()).one(&db).await.unwrap().unwrap();
+        assert_eq!(volume.size_gb, 20);
+        assert_eq!(volume.status, "available");
+    }
+
+    #[tokio::test]
+    async fn test_resize_volume_explains_missing_tool_and_timeout() {
+        let db = db_with(
+            vec![pool("p1", "n1")],
+            vec![volume("v1", "p1", 20, VolumeStatus::Available)],
+        )
+        .await;
+        let agent = Arc::new(
+            MockAgentRpc::new()
+                .fail("resize_volume", RpcError::new(RpcErrorCode::ToolNotFound, "命令不存在: qemu-img"))
+                .fail("resize_volume", RpcError::timeout("qemu-img did not finish within 300s")),
+        );
+        let service = service(db, agent);
+
+        let err = service.resize_volume("v1", ResizeVolumeDto { new_size_gb: 50 }).await.unwrap_err();
+        assert!(err.to_string().starts_with("节点缺少存储工具"));
+        let err = service.resize_volume("v1", ResizeVolumeDto { new_size_gb: 50 }).await.unwrap_err();
+        assert!(err.to_string().starts_with("存储操作超时"));
+    }
+
+    #[tokio::test]
+    async fn test_clone_volume_into_source_pool() {
+        let db = db_with(
+            vec![pool("p1", "n1")],
+            vec![volume("v1", "p1", 20, VolumeStatus::Available)],
+        )
+        .await;
+        let agent = Arc::new(MockAgentRpc::new().respond(
+            "clone_volume",
+            serde_json::json!({ "success": true, "message": "ok", "path": "/mnt/nfs/v2.qcow2" }),
+        ));
+
+        let result = service(db, agent.clone())
+            .clone_volume(CloneVolumeDto {
+                source_volume_id: "v1".to_string(),
+                target_name: "copy".to_string(),
+                source_snapshot_id: None,
+                target_pool_id: None,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(result.status, "available");
+        let payload = &agent.calls()[0].payload;
+        assert_eq!(payload["source_volume_id"], "v1");
+        assert_eq!(payload["pool_id"], "p1");
+        assert!(payload["target_pool_id"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_clone_volume_failure_removes_target_record() {
+        let db = db_with(
+            vec![pool("p1", "n1")],
+            vec![volume("v1", "p1", 20, VolumeStatus::Available)],
+        )
+        .await;
+        let agent = Arc::new(MockAgentRpc::new().respond(
+            "clone_volume",
+            serde_json::json!({ "success": false, "message": "源卷不存在" }),
+        ));
+
+        let err = service(db.clone(), agent)
+            .clone_volume(CloneVolumeDto {
+                source_volume_id: "v1".to_string(),
+                target_name: "copy".to_string(),
+                source_snapshot_id: None,
+                target_pool_id: None,
+            })
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("源卷不存在"));
+        // 目标卷记录已删除，只剩源卷
+        let volumes = VolumeEntity::find().all(&db).await.unwrap();
+        assert_eq!(volumes.len(), 1);
+        assert_eq!(volumes[0].id, "v1");
+    }
+
+    #[tokio::test]
+    async fn test_clone_volume_rejects_target_pool_on_other_node() {
+        let db = db_with(
+            vec![pool("p1", "n1"), pool("p2", "n2")],
+            vec![volume("v1", "p1", 20, VolumeStatus::Available)],
+        )
+        .await;
+        let agent = Arc::new(MockAgentRpc::new());
+
+        let err = service(db, agent.clone())
+            .clone_volume(CloneVolumeDto {
+                source_volume_id: "v1".to_string(),
+                target_name: "copy".to_string(),
+                source_snapshot_id: None,
+                target_pool_id: Some("p2".to_string()),
+            })
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("同一节点"));
+        assert!(agent.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_pool_allocation_sums_volume_sizes() {
+        let db = db_with(
+            vec![pool("p1", "n1")],
+            vec![
+                volume("v1", "p1", 20, VolumeStatus::Available),
+                volume("v2", "p1", 30, VolumeStatus::InUse),
+            ],
+        )
+        .await;
+        let service = service(db, Arc::new(MockAgentRpc::new()));
+
+        let result = service.reconcile_pool_allocation("p1").await.unwrap();
+
+        assert_eq!(result.allocated_gb, Some(50));
+        assert_eq!(result.available_gb, Some(950));
+        let err = service.reconcile_pool_allocation("missing").await.unwrap_err();
+        assert!(err.to_string().contains("存储池不存在"));
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_all_pool_allocations_updates_only_drifted_pools() {
+        // p1 计数与卷不一致，p2 没有卷且计数正确
+        let db = db_with(
+            vec![pool("p1", "n1"), pool("p2", "n1")],
+            vec![volume("v1", "p1", 20, VolumeStatus::Available)],
+        )
+        .await;
+        let service = service(db.clone(), Arc::new(MockAgentRpc::new()));
+
+        assert_eq!(service.reconcile_all_pool_allocations().await.unwrap(), 1);
+
+        let p1 = StoragePoolEntity::find_by_id("p1".to_string()).one(&db).await.unwrap().unwrap();
+        assert_eq!(p1.allocated_gb, Some(20));
+        assert_eq!(p1.available_gb, Some(980));
+        let p2 = StoragePoolEntity::find_by_id("p2".to_string()).one(&db).await.unwrap().unwrap();
+        assert_eq!(p2.allocated_gb, Some(0));
+        assert_eq!(p2.available_gb, Some(1000));
+        // 再次对账时已经一致
+        assert_eq!(service.reconcile_all_pool_allocations().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_prepare_volume_download_rejects_in_use_volume() {
+        let db = db_with(
+            vec![pool("p1", "n1")],
+            vec![volume("v1", "p1", 20, VolumeStatus::InUse)],
+        )
+        .await;
+        let agent = Arc::new(MockAgentRpc::new());
+
+        let err = service(db, agent.clone())
+            .prepare_volume_download("v1")
+            .await
+            .err()
+            .unwrap();
+
+        assert!(err.to_string().contains("仅可下载"));
+        assert!(agent.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_volume_download_streams_requested_range() {
+        use base64::Engine;
+        use futures::TryStreamExt;
+
+        let db = db_with(
+            vec![pool("p1", "n1")],
+            vec![volume("v1", "p1", 20, VolumeStatus::Available)],
+        )
+        .await;
+        let agent = Arc::new(
+            MockAgentRpc::new()
+                .respond(
+                    "prepare_volume_export",
+                    serde_json::json!({ "volume_id": "v1", "format": "qcow2", "size_bytes": 100 }),
+                )
+                .respond(
+                    "read_volume_export",
+                    serde_json::json!({
+                        "offset": 10,
+                        "data": base64::engine::general_purpose::STANDARD.encode([7u8; 20]),
+                        "eof": false,
+                    }),
+                ),
+        );
+
+        let download = service(db, agent.clone())
+            .prepare_volume_download("v1")
+            .await
+            .unwrap();
+        assert_eq!(download.file_name, "vol-v1.qcow2");
+        assert_eq!(download.size_bytes, 100);
+
+        let chunks: Vec<Vec<u8>> = download.into_stream(10, 30).try_collect().await.unwrap();
+        assert_eq!(chunks, vec![vec![7u8; 20]]);
+
+        let calls = agent.calls();
+        assert_eq!(calls[1].method, "read_volume_export");
+        assert_eq!(calls[1].payload["offset"], 10);
+        assert_eq!(calls[1].payload["length"], 20);
+    }
 }
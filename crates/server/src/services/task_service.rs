@@ -1,11 +1,20 @@
 /// 任务管理服务
+///
+/// 异步操作下发给 Agent 前创建任务记录，任务 ID 随通知一起发送，
+/// Agent 在完成通知中带回，Server 据此更新任务状态，前端可轮询任务接口获取结果
 
 use chrono::Utc;
-use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set,
+};
 use tracing::info;
+use uuid::Uuid;
 
-use crate::db::models::task::{Entity as TaskEntity, Column as TaskColumn, ActiveModel as TaskActiveModel};
-use crate::db::models::vm::{Entity as VmEntity, ActiveModel as VmActiveModel};
+use crate::db::models::task::{
+    ActiveModel as TaskActiveModel, Column as TaskColumn, Entity as TaskEntity, TaskListResponse,
+    TaskResponse, TaskStatus, TaskType,
+};
 use crate::app_state::AppState;
 use crate::ws::FrontendMessage;
 
@@ -18,17 +27,97 @@ impl TaskService {
         Self { state }
     }
 
+    /// 创建任务，返回任务 ID
+    ///
+    /// 任务在通知发出前创建，直接置为 running；通知发送失败时由调用方标记为失败
+    pub async fn create_task(&self, task_type: TaskType, target_id: &str) -> anyhow::Result<String> {
+        let db = &self.state.sea_db();
+        let now = Utc::now();
+
+        let task = TaskActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            task_type: Set(task_type.as_str().to_string()),
+            status: Set(TaskStatus::Running.as_str().to_string()),
+            progress: Set(0),
+            payload: Set(serde_json::json!({})),
+            result: Set(None),
+            error_message: Set(None),
+            target_type: Set(Some(task_type.target_type().to_string())),
+            target_id: Set(Some(target_id.to_string())),
+            node_id: Set(None),
+            retry_count: Set(0),
+            max_retries: Set(0),
+            created_by: Set(None),
+            created_at: Set(now.into()),
+            updated_at: Set(now.into()),
+            started_at: Set(Some(now.into())),
+            completed_at: Set(None),
+        }
+        .insert(db)
+        .await?;
+
+        info!(
+            "创建任务: task_id={}, task_type={}, target_id={}",
+            task.id, task.task_type, target_id
+        );
+        Ok(task.id)
+    }
+
+    /// 获取单个任务
+    pub async fn get_task(&self, task_id: &str) -> anyhow::Result<TaskResponse> {
+        let task = TaskEntity::find_by_id(task_id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("任务不存在: {}", task_id))?;
+        Ok(task.into())
+    }
+
+    /// 分页查询任务，按创建时间倒序，可按关联对象和状态过滤
+    pub async fn list_tasks(
+        &self,
+        page: usize,
+        page_size: usize,
+        target_id: Option<String>,
+        status: Option<String>,
+    ) -> anyhow::Result<TaskListResponse> {
+        let db = &self.state.sea_db();
+        let page = page.max(1);
+
+        let mut query = TaskEntity::find();
+        if let Some(target_id) = target_id {
+            query = query.filter(TaskColumn::TargetId.eq(target_id));
+        }
+        if let Some(status) = status {
+            query = query.filter(TaskColumn::Status.eq(status));
+        }
+
+        let total = query.clone().count(db).await? as usize;
+        let tasks = query
+            .order_by_desc(TaskColumn::CreatedAt)
+            .offset(((page - 1) * page_size) as u64)
+            .limit(page_size as u64)
+            .all(db)
+            .await?;
+
+        Ok(TaskListResponse {
+            tasks: tasks.into_iter().map(TaskResponse::from).collect(),
+            total,
+            page,
+            page_size,
+        })
+    }
+
     /// 更新任务状态
     pub async fn update_task_status(
         &self,
         task_id: &str,
-        status: &str,
+        status: TaskStatus,
         progress: Option<i32>,
         result: Option<serde_json::Value>,
         error_message: Option<String>,
     ) -> anyhow::Result<()> {
         let db = &self.state.sea_db();
-        
+
         // 查找任务
         let task = TaskEntity::find_by_id(task_id.to_string())
             .one(db)
@@ -37,177 +126,99 @@ impl TaskService {
 
         // 更新任务状态
         let mut task_active: TaskActiveModel = task.into();
-        task_active.status = Set(status.to_string());
+        task_active.status = Set(status.as_str().to_string());
         task_active.updated_at = Set(Utc::now().into());
-        
+
         if let Some(progress) = progress {
             task_active.progress = Set(progress);
         }
-        
+
         if let Some(result) = result {
             task_active.result = Set(Some(result));
         }
-        
-        if let Some(error_message) = error_message {
+
+        if let Some(error_message) = error_message.clone() {
             task_active.error_message = Set(Some(error_message));
         }
 
         // 设置完成时间
-        if status == "completed" || status == "failed" {
+        if status == TaskStatus::Completed || status == TaskStatus::Failed {
             task_active.completed_at = Set(Some(Utc::now().into()));
         }
 
         task_active.update(db).await?;
-        
-        info!("任务状态已更新: task_id={}, status={}", task_id, status);
+
+        info!("任务状态已更新: task_id={}, status={}", task_id, status.as_str());
+
+        let frontend_msg = FrontendMessage::TaskStatusUpdate {
+            task_id: task_id.to_string(),
+            status: status.as_str().to_string(),
+            progress,
+            message: error_message,
+        };
+        self.state.frontend_manager().broadcast(frontend_msg).await;
+
         Ok(())
     }
 
-    /// 根据虚拟机ID和操作类型查找任务
+    /// 记录任务结果：成功置为 completed，失败置为 failed 并保存错误信息
+    pub async fn finish_task(&self, task_id: &str, success: bool, message: &str) -> anyhow::Result<()> {
+        let (status, error_message) = if success {
+            (TaskStatus::Completed, None)
+        } else {
+            (TaskStatus::Failed, Some(message.to_string()))
+        };
+        let result = serde_json::json!({
+            "success": success,
+            "message": message
+        });
+
+        self.update_task_status(task_id, status, Some(100), Some(result), error_message)
+            .await
+    }
+
+    /// 根据虚拟机ID和操作类型查找进行中的任务
     pub async fn find_task_by_vm_operation(
         &self,
         vm_id: &str,
         operation: &str,
     ) -> anyhow::Result<Option<String>> {
         let db = &self.state.sea_db();
-        
+
         let task = TaskEntity::find()
             .filter(TaskColumn::TargetId.eq(vm_id))
             .filter(TaskColumn::TaskType.eq(operation))
-            .filter(TaskColumn::Status.ne("completed"))
-            .filter(TaskColumn::Status.ne("failed"))
+            .filter(TaskColumn::Status.is_in([
+                TaskStatus::Pending.as_str(),
+                TaskStatus::Running.as_str(),
+            ]))
+            .order_by_desc(TaskColumn::CreatedAt)
             .one(db)
             .await?;
 
         Ok(task.map(|t| t.id))
     }
 
-    /// 更新虚拟机状态
-    pub async fn update_vm_status(
-        &self,
-        vm_id: &str,
-        status: &str,
-    ) -> anyhow::Result<()> {
-        let db = &self.state.sea_db();
-        
-        // 查找虚拟机
-        let vm = VmEntity::find_by_id(vm_id.to_string())
-            .one(db)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在: {}", vm_id))?;
-
-        // 更新虚拟机状态
-        let mut vm_active: VmActiveModel = vm.into();
-        vm_active.status = Set(status.to_string());
-        vm_active.updated_at = Set(Utc::now().into());
-        
-        // 根据状态设置相应的时间戳
-        match status {
-            "stopped" => {
-                vm_active.stopped_at = Set(Some(Utc::now().into()));
-            }
-            "running" => {
-                vm_active.started_at = Set(Some(Utc::now().into()));
-            }
-            _ => {}
-        }
-
-        vm_active.update(db).await?;
-        
-        info!("虚拟机状态已更新: vm_id={}, status={}", vm_id, status);
-        
-        // 发送状态更新通知给前端
-        let frontend_msg = FrontendMessage::VmStatusUpdate {
-            vm_id: vm_id.to_string(),
-            status: status.to_string(),
-            message: Some(format!("虚拟机状态已更新为: {}", status)),
-        };
-        
-        let count = self.state.frontend_manager().broadcast(frontend_msg).await;
-        if count > 0 {
-            info!("已向 {} 个前端连接发送 VM {} 状态更新: {}", count, vm_id, status);
-        }
-        
-        Ok(())
-    }
-
-    /// 处理虚拟机操作完成通知
-    pub async fn handle_vm_operation_completed(
-        &self,
-        vm_id: &str,
-        operation: &str,
-        success: bool,
-        message: &str,
-    ) -> anyhow::Result<()> {
-        // 查找对应的任务
-        if let Some(task_id) = self.find_task_by_vm_operation(vm_id, operation).await? {
-            // 更新任务状态
-            let status = if success { "completed" } else { "failed" };
-            let result = serde_json::json!({
-                "success": success,
-                "message": message
-            });
-            
-            self.update_task_status(
-                &task_id,
-                status,
-                Some(100),
-                Some(result),
-                if success { None } else { Some(message.to_string()) },
-            ).await?;
-        }
-
-        // 更新虚拟机状态
-        let vm_status = match (operation, success) {
-            ("stop_vm", true) => "stopped",
-            ("stop_vm", false) => "error",
-            ("start_vm", true) => "running",
-            ("start_vm", false) => "error",
-            ("restart_vm", true) => "running",
-            ("restart_vm", false) => "error",
-            _ => "error",
-        };
-
-        self.update_vm_status(vm_id, vm_status).await?;
-        
-        Ok(())
-    }
-
-    /// 通过 task_id 处理虚拟机操作完成通知
-    pub async fn handle_vm_operation_completed_by_task_id(
+    /// 处理虚拟机操作完成通知对应的任务
+    ///
+    /// 旧版本 Agent 不会带回 task_id，此时按虚拟机与操作类型查找进行中的任务；
+    /// 没有对应任务的操作（如挂载存储卷）直接忽略
+    pub async fn complete_vm_operation(
         &self,
-        task_id: &str,
+        task_id: Option<&str>,
         vm_id: &str,
         operation: &str,
         success: bool,
         message: &str,
     ) -> anyhow::Result<()> {
-        // 更新任务状态
-        let status = if success { "completed" } else { "failed" };
-        let result = serde_json::json!({
-            "success": success,
-            "message": message
-        });
-        
-        self.update_task_status(
-            task_id,
-            status,
-            Some(100),
-            Some(result),
-            if success { None } else { Some(message.to_string()) },
-        ).await?;
-
-        // 更新虚拟机状态
-        let vm_status = match (operation, success) {
-            ("stop_vm", true) => "stopped",
-            ("stop_vm", false) => "error",
-            ("start_vm", true) => "running",
-            ("start_vm", false) => "error",
-            _ => "error",
+        let task_id = match task_id {
+            Some(task_id) => Some(task_id.to_string()),
+            None => self.find_task_by_vm_operation(vm_id, operation).await?,
         };
 
-        self.update_vm_status(vm_id, vm_status).await?;
-        
+        if let Some(task_id) = task_id {
+            self.finish_task(&task_id, success, message).await?;
+        }
         Ok(())
     }
 }
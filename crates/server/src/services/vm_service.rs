@@ -1,26 +1,78 @@
 /// 虚拟机管理服务
 use chrono::Utc;
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
-    QuerySelect, Set,
+    prelude::Expr, ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set,
 };
 use uuid::Uuid;
 
 use crate::app_state::AppState;
+use crate::auth::RbacService;
 use crate::db::models::network::Entity as NetworkEntity;
 use crate::db::models::node::Entity as NodeEntity;
 use crate::db::models::vm::{
-    ActiveModel as VmActiveModel, AttachVolumeDto, Column as VmColumn, CreateVmDto,
-    DetachVolumeDto, DiskSpec, Entity as VmEntity, NetworkInterfaceSpec, UpdateVmDto,
-    VmDiskResponse, VmListResponse, VmResponse, VmStatus,
+    ActiveModel as VmActiveModel, AttachVolumeDto, BatchVmAction, BatchVmOperationDto, BatchVmOperationResponse,
+    BatchVmResult, CloneVmDto, Column as VmColumn, ConsoleAccessResponse, CreateVmDto,
+    DetachVolumeDto, DiskSpec, Entity as VmEntity, GuestExecDto, GuestNetworkResponse, MigrateVmDto, Model as VmModel,
+    NetworkInterfaceSpec, RebuildVmDto, SetNicBandwidthDto, SetVolumeIotuneDto, UpdateVmDto, VmDiskResponse, VmListResponse,
+    VmLiveStateResponse, VmResponse, VmStatus,
 };
+use crate::db::models::snapshot::{Entity as SnapshotEntity, SafetyOperation, SnapshotStatus};
+use crate::db::models::task::TaskType;
 use crate::db::models::volume::{
-    ActiveModel as VolumeActiveModel, Column as VolumeColumn, Entity as VolumeEntity,
+    ActiveModel as VolumeActiveModel, CloneVolumeDto, Column as VolumeColumn, CreateVolumeDto,
+    Entity as VolumeEntity,
 };
+use crate::services::affinity_service::AffinityGroupService;
+use crate::services::audit_service::AuditService;
+use crate::services::department_service::{DepartmentService, QuotaRequest};
 use crate::services::network_service::NetworkService;
+use crate::services::scheduler_service::SchedulerService;
+use crate::services::security_group_service::{effective_group_id, SecurityGroupService};
+use crate::services::snapshot_service::SnapshotService;
+use crate::services::storage_service::StorageService;
+use crate::services::task_service::TaskService;
 use crate::ws::FrontendMessage;
+use common::ws_rpc::{
+    disk_device_name, validate_cpu_placement, validate_disk_combination, CpuPinning, DiskBusType,
+    MigrationMode, MigrationProgress, MigrationStorageMode, NumaConfig, VncInfo,
+};
 use tracing::{debug, error, info, warn};
 
+/// 打开控制台时下发的一次性密码的有效期（秒）
+const CONSOLE_PASSWORD_VALID_SECS: i64 = 60;
+
+/// 单次批量操作的虚拟机数量上限与同时执行的数量
+const MAX_BATCH_SIZE: usize = 200;
+const BATCH_CONCURRENCY: usize = 8;
+
+/// 虚拟机当前状态不允许执行该操作（如重装期间启动），API 层返回 409
+#[derive(Debug, Clone)]
+pub struct VmStatusConflict {
+    pub vm_id: String,
+    pub status: String,
+    pub operation: &'static str,
+}
+
+impl std::fmt::Display for VmStatusConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "虚拟机 {} 当前状态为 {}，无法{}", self.vm_id, self.status, self.operation)
+    }
+}
+
+impl std::error::Error for VmStatusConflict {}
+
+impl VmStatusConflict {
+    fn new(vm: &VmModel, operation: &'static str) -> Self {
+        Self { vm_id: vm.id.clone(), status: vm.status.clone(), operation }
+    }
+}
+
+/// 启动和删除只允许关机或出错的虚拟机，其余状态都有进行中的操作
+fn is_settled(vm: &VmModel) -> bool {
+    vm.status == VmStatus::Stopped.as_str() || vm.status == VmStatus::Error.as_str()
+}
+
 pub struct VmService {
     state: AppState,
 }
@@ -81,7 +133,12 @@ impl VmService {
 
         // 验证volumes存在并且可用
         if let Some(ref disks) = dto.disks {
+            validate_boot_order(disks)?;
             for disk in disks {
+                validate_disk_combination(&disk.bus_type, &disk.device_type)
+                    .and_then(|_| disk.limits.validate())
+                    .map_err(|e| anyhow::anyhow!("存储卷 {}: {}", disk.volume_id, e))?;
+
                 let volume = VolumeEntity::find_by_id(&disk.volume_id)
                     .one(db)
                     .await?
@@ -94,10 +151,72 @@ impl VmService {
                 if volume.vm_id.is_some() {
                     return Err(anyhow::anyhow!("存储卷 {} 已被其他虚拟机使用", disk.volume_id));
                 }
+                StorageService::new(self.state.clone())
+                    .ensure_no_linked_clones(&disk.volume_id, "挂载到虚拟机")
+                    .await?;
+            }
+        }
+
+        if let Some(owner_id) = dto.owner_id {
+            let request = QuotaRequest {
+                vcpu: dto.vcpu as i64,
+                memory_mb: dto.memory_mb as i64,
+                ..Default::default()
+            };
+            DepartmentService::check_quota(db, owner_id, request).await?;
+        }
+
+        // 未指定节点时按放置策略自动选择，否则校验指定节点满足亲和组规则
+        let scheduler = SchedulerService::new(self.state.clone());
+        let node_id = match dto.node_id.clone() {
+            Some(node_id) => {
+                scheduler
+                    .select_node(None, &dto.affinity_group_ids, std::slice::from_ref(&node_id))
+                    .await?;
+                if dto.tpm {
+                    self.ensure_node_supports_tpm(&node_id).await?;
+                }
+                self.ensure_node_supports_cpu_placement(
+                    &node_id,
+                    dto.vcpu,
+                    dto.cpu_pinning.as_ref(),
+                    dto.numa.as_ref(),
+                )
+                .await?;
+                scheduler
+                    .check_capacity(&node_id, None, dto.vcpu, dto.memory_mb)
+                    .await?;
+                node_id
+            }
+            None => {
+                scheduler
+                    .place_new_vm(
+                        dto.vcpu,
+                        dto.memory_mb,
+                        dto.tpm,
+                        dto.cpu_pinning.as_ref(),
+                        dto.numa.as_ref(),
+                        &dto.affinity_group_ids,
+                    )
+                    .await?
+            }
+        };
+
+        // 同一网络可挂载多块网卡，但指定的 MAC 地址不能重复
+        if let Some(ref networks) = dto.networks {
+            for network in networks {
+                network.bandwidth.validate()
+                    .map_err(|e| anyhow::anyhow!("网络 {}: {}", network.network_id, e))?;
+            }
+            let mut macs = std::collections::HashSet::new();
+            for mac in networks.iter().filter_map(|n| n.mac_address.as_ref()) {
+                if !macs.insert(mac.to_lowercase()) {
+                    return Err(anyhow::anyhow!("网卡 MAC 地址重复: {}", mac));
+                }
             }
         }
 
-        // 验证网络并分配 IP 地址
+        // 验证网络并分配 IP 地址（每块网卡独立分配 IP 与 MAC）
         let mut network_interfaces_with_ip = Vec::new();
         let mut ip_allocations = Vec::new(); // 保存 IP 分配记录信息
         if let Some(ref networks) = dto.networks {
@@ -109,16 +228,36 @@ impl VmService {
                     .one(db)
                     .await?
                     .ok_or_else(|| anyhow::anyhow!("网络 {} 不存在", network_spec.network_id))?;
+                if network.network_type == "ovs" {
+                    self.ensure_node_supports_ovs(&node_id).await?;
+                }
+                if let Some(ref group_id) = network_spec.security_group_id {
+                    SecurityGroupService::new(self.state.clone())
+                        .ensure_assignable(group_id, &network.network_type)
+                        .await?;
+                }
 
-                // 为 VM 预留 IP（不设置 vm_id）
-                let ip_allocation = network_service
-                    .allocate_ip(&network_spec.network_id)
-                    .await?;
+                // 为 VM 预留 IP（不设置 vm_id），双栈网络再预留一个 IPv6 地址
+                // 网卡显式指定了 IP（如静态预留的地址）时占用该地址，否则自动分配
+                let requested_mac = network_spec.mac_address.as_deref();
+                let ip_allocation = match network_spec.ip_address.as_deref() {
+                    Some(ip) => network_service.claim_ip(&network_spec.network_id, ip, requested_mac).await?,
+                    None => network_service.allocate_ip(&network_spec.network_id).await?,
+                };
+                let ipv6_allocation = match network_spec.ipv6_address.as_deref() {
+                    Some(ip) => Some(network_service.claim_ip(&network_spec.network_id, ip, requested_mac).await?),
+                    None => network_service.allocate_ipv6(&network_spec.network_id).await?,
+                };
                 
                 info!("为 VM {} 在网络 {} 预留 IP: {}", vm_id, network.name, ip_allocation.ip_address);
+                if let Some(ref ipv6) = ipv6_allocation {
+                    info!("为 VM {} 在网络 {} 预留 IPv6: {}", vm_id, network.name, ipv6.ip_address);
+                }
                 
-                // 生成 MAC 地址（如果未提供）
+                // 生成 MAC 地址（如果未提供），静态预留绑定了 MAC 时沿用预留的 MAC
                 let mac_address = network_spec.mac_address.clone()
+                    .or_else(|| ip_allocation.mac_address.clone())
+                    .or_else(|| ipv6_allocation.as_ref().and_then(|ip| ip.mac_address.clone()))
                     .unwrap_or_else(|| Self::generate_mac_address());
 
                 // 创建带 IP 的网络接口配置
@@ -126,28 +265,37 @@ impl VmService {
                     network_id: network_spec.network_id.clone(),
                     mac_address: Some(mac_address.clone()),
                     ip_address: Some(ip_allocation.ip_address.clone()),
+                    ipv6_address: ipv6_allocation.as_ref().map(|ip| ip.ip_address.clone()),
                     model: network_spec.model.clone(),
-                    bridge_name: Some(match network.vlan_id {
-                        Some(vlan_id) => format!("br-vlan{}", vlan_id),
-                        None => "br-default".to_string(),
-                    }),
+                    bandwidth: network_spec.bandwidth,
+                    security_group_id: network_spec.security_group_id.clone(),
+                    bridge_name: Some(
+                        self.state
+                            .bridge_naming()
+                            .bridge_name(network.vlan_id.map(|id| id as u32)),
+                    ),
                 };
 
                 network_interfaces_with_ip.push(network_with_ip);
 
                 // 更新 IP 分配记录，添加 MAC 地址
                 use crate::db::models::ip_allocation::{Entity as IpAllocationEntity, ActiveModel as IpAllocationActiveModel};
-                let ip_record = IpAllocationEntity::find_by_id(&ip_allocation.id)
-                    .one(db)
-                    .await?
-                    .ok_or_else(|| anyhow::anyhow!("IP 分配记录不存在"))?;
-
-                let mut ip_active: IpAllocationActiveModel = ip_record.into();
-                ip_active.mac_address = Set(Some(mac_address));
-                ip_active.update(db).await?;
+                for allocation in std::iter::once(ip_allocation).chain(ipv6_allocation) {
+                    // 静态预留记录保留预留时绑定的 MAC，释放后不残留虚拟机的 MAC
+                    if !allocation.is_static {
+                        let ip_record = IpAllocationEntity::find_by_id(&allocation.id)
+                            .one(db)
+                            .await?
+                            .ok_or_else(|| anyhow::anyhow!("IP 分配记录不存在"))?;
+
+                        let mut ip_active: IpAllocationActiveModel = ip_record.into();
+                        ip_active.mac_address = Set(Some(mac_address.clone()));
+                        ip_active.update(db).await?;
+                    }
 
-                // 保存 IP 分配记录信息，用于后续更新 vm_id
-                ip_allocations.push(ip_allocation);
+                    // 保存 IP 分配记录信息，用于后续更新 vm_id
+                    ip_allocations.push(allocation);
+                }
             }
         }
 
@@ -171,15 +319,28 @@ impl VmService {
         let vm_active = VmActiveModel {
             id: Set(vm_id.clone()),
             name: Set(dto.name.clone()),
-            node_id: Set(Some(dto.node_id.clone())),
+            node_id: Set(Some(node_id.clone())),
             status: Set(VmStatus::Stopped.as_str().to_string()),
             vcpu: Set(dto.vcpu as i32),
             memory_mb: Set(dto.memory_mb as i64),
             os_type: Set(os_type),
+            firmware: Set(dto.firmware.as_str().to_string()),
+            tpm: Set(dto.tpm),
+            boot_menu: Set(dto.boot_menu),
+            cpu_pinning: Set(dto.cpu_pinning.as_ref().map(serde_json::to_value).transpose()?),
+            numa: Set(dto.numa.as_ref().map(serde_json::to_value).transpose()?),
+            raw_xml_override: Set(dto.raw_xml_override.clone().filter(|xml| !xml.trim().is_empty())),
+            arch: Set(dto.arch.clone().unwrap_or_else(|| "x86_64".to_string())),
+            machine_type: Set(dto.machine_type.clone().filter(|machine| !machine.trim().is_empty())),
+            graphics: Set(dto.graphics.as_str().to_string()),
             volumes: Set(volumes_json),
             network_interfaces: Set(network_interfaces_json),
+            cloud_init: Set(dto.cloud_init.as_ref().map(serde_json::to_value).transpose()?),
+            vnc_port: Set(None),
+            vnc_host: Set(None),
+            console_protocol: Set(None),
             metadata: Set(dto.metadata.clone()),
-            uuid: Set(None),
+            owner_id: Set(dto.owner_id),
             created_at: Set(now.into()),
             updated_at: Set(now.into()),
             started_at: Set(None),
@@ -211,13 +372,26 @@ impl VmService {
             if let Err(e) = network_service.update_ip_vm_id(&ip_allocation.id, &vm_id).await {
                 error!("更新 IP 分配 vm_id 失败: {}", e);
                 // 如果更新失败，释放预留的 IP
-                if let Err(release_err) = network_service.release_ip(&ip_allocation.network_id, &vm_id).await {
+                if let Err(release_err) = network_service.release_ip_allocation(&ip_allocation.id).await {
                     error!("释放预留 IP 失败: {}", release_err);
                 }
             } else {
                 info!("成功更新 IP {} 的 vm_id 为 {}", ip_allocation.ip_address, vm_id);
             }
         }
+        for network_id in Self::distinct_network_ids(&network_interfaces_with_ip) {
+            if let Err(e) = network_service.reload_dhcp(&network_id).await {
+                warn!("刷新网络 {} 的 DHCP 租约失败: {}", network_id, e);
+            }
+        }
+
+        // 加入亲和组
+        let affinity_service = AffinityGroupService::new(self.state.clone());
+        for group_id in &dto.affinity_group_ids {
+            if let Err(e) = affinity_service.add_member(group_id, &vm_id).await {
+                warn!("虚拟机 {} 加入亲和组 {} 失败: {}", vm_id, group_id, e);
+            }
+        }
 
         // 按照 vms.md 流程：仅保存到数据库，不调用 agent
         info!("虚拟机 {} 创建成功，已保存到数据库", vm_id);
@@ -297,6 +471,48 @@ impl VmService {
             .await?
             .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
 
+        // vCPU 数量或 CPU 绑定变化时按所在节点的拓扑重新校验，空配置视为清除
+        let cpu_pinning = match dto.cpu_pinning.clone() {
+            Some(pinning) => Some(pinning).filter(|p| !p.is_empty()),
+            None => parse_json(&vm.cpu_pinning),
+        };
+        let numa = match dto.numa.clone() {
+            Some(numa) => Some(numa).filter(|n| *n != NumaConfig::default()),
+            None => parse_json(&vm.numa),
+        };
+        if dto.vcpu.is_some() || dto.cpu_pinning.is_some() || dto.numa.is_some() {
+            if let Some(node_id) = &vm.node_id {
+                self.ensure_node_supports_cpu_placement(
+                    node_id,
+                    dto.vcpu.unwrap_or(vm.vcpu as u32),
+                    cpu_pinning.as_ref(),
+                    numa.as_ref(),
+                )
+                .await?;
+            }
+        }
+
+        // 只有增加的 vCPU 和内存计入配额
+        if let Some(owner_id) = vm.owner_id {
+            let request = QuotaRequest {
+                vcpu: dto.vcpu.map_or(0, |vcpu| vcpu as i64 - vm.vcpu as i64),
+                memory_mb: dto.memory_mb.map_or(0, |memory_mb| memory_mb as i64 - vm.memory_mb),
+                ..Default::default()
+            };
+            DepartmentService::check_quota(db, owner_id, request).await?;
+        }
+
+        // 运行中的虚拟机先在线调整 vCPU 和内存，成功后再写入数据库
+        if vm.status == VmStatus::Running.as_str() {
+            self.apply_live_resize(&vm, dto.vcpu, dto.memory_mb).await?;
+        }
+
+        if dto.tpm == Some(true) && !vm.tpm {
+            if let Some(node_id) = &vm.node_id {
+                self.ensure_node_supports_tpm(node_id).await?;
+            }
+        }
+
         // 转换为 ActiveModel
         let mut vm_active: VmActiveModel = vm.into();
 
@@ -313,14 +529,58 @@ impl VmService {
         if let Some(os_type) = dto.os_type {
             vm_active.os_type = Set(os_type);
         }
+        if let Some(tpm) = dto.tpm {
+            vm_active.tpm = Set(tpm);
+        }
+        if let Some(boot_menu) = dto.boot_menu {
+            vm_active.boot_menu = Set(boot_menu);
+        }
+        if dto.cpu_pinning.is_some() {
+            vm_active.cpu_pinning = Set(cpu_pinning.as_ref().map(serde_json::to_value).transpose()?);
+        }
+        if dto.numa.is_some() {
+            vm_active.numa = Set(numa.as_ref().map(serde_json::to_value).transpose()?);
+        }
+        if let Some(raw_xml_override) = dto.raw_xml_override {
+            vm_active.raw_xml_override = Set(Some(raw_xml_override).filter(|xml| !xml.trim().is_empty()));
+        }
+        if let Some(machine_type) = dto.machine_type {
+            vm_active.machine_type = Set(Some(machine_type).filter(|machine| !machine.trim().is_empty()));
+        }
+        if let Some(graphics) = dto.graphics {
+            vm_active.graphics = Set(graphics.as_str().to_string());
+        }
         if let Some(disks) = dto.disks {
+            validate_boot_order(&disks)?;
+            for disk in &disks {
+                validate_disk_combination(&disk.bus_type, &disk.device_type)
+                    .and_then(|_| disk.limits.validate())
+                    .map_err(|e| anyhow::anyhow!("存储卷 {}: {}", disk.volume_id, e))?;
+            }
             let volumes_json = serde_json::to_value(disks)?;
             vm_active.volumes = Set(Some(volumes_json));
         }
         if let Some(networks) = dto.networks {
+            for network in &networks {
+                network.bandwidth.validate()
+                    .map_err(|e| anyhow::anyhow!("网络 {}: {}", network.network_id, e))?;
+                if let Some(ref group_id) = network.security_group_id {
+                    let network_type = NetworkEntity::find_by_id(&network.network_id)
+                        .one(db)
+                        .await?
+                        .map(|n| n.network_type)
+                        .ok_or_else(|| anyhow::anyhow!("网络 {} 不存在", network.network_id))?;
+                    SecurityGroupService::new(self.state.clone())
+                        .ensure_assignable(group_id, &network_type)
+                        .await?;
+                }
+            }
             let networks_json = serde_json::to_value(networks)?;
             vm_active.network_interfaces = Set(Some(networks_json));
         }
+        if let Some(cloud_init) = dto.cloud_init {
+            vm_active.cloud_init = Set(Some(serde_json::to_value(cloud_init)?));
+        }
         if let Some(metadata) = dto.metadata {
             vm_active.metadata = Set(Some(metadata));
         }
@@ -333,6 +593,110 @@ impl VmService {
         Ok(self.vm_to_response(vm).await)
     }
 
+    /// 校验节点已安装 swtpm，可以运行带 TPM 的虚拟机
+    async fn ensure_node_supports_tpm(&self, node_id: &str) -> anyhow::Result<()> {
+        let node = NodeEntity::find_by_id(node_id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("节点不存在"))?;
+
+        if !node.has_swtpm {
+            return Err(anyhow::anyhow!("节点 {} 未安装 swtpm，不支持 TPM", node.hostname));
+        }
+        Ok(())
+    }
+
+    /// 校验 vCPU 绑定与 NUMA 配置能在节点上实现，未配置时不检查
+    async fn ensure_node_supports_cpu_placement(
+        &self,
+        node_id: &str,
+        vcpu: u32,
+        cpu_pinning: Option<&CpuPinning>,
+        numa: Option<&NumaConfig>,
+    ) -> anyhow::Result<()> {
+        if cpu_pinning.is_none() && numa.is_none() {
+            return Ok(());
+        }
+        let node = NodeEntity::find_by_id(node_id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("节点不存在"))?;
+
+        let host = node
+            .numa_nodes()
+            .ok_or_else(|| anyhow::anyhow!("节点 {} 未上报 NUMA 拓扑，无法校验 CPU 绑定", node.hostname))?;
+        validate_cpu_placement(vcpu, cpu_pinning, numa, &host)
+            .map_err(|e| anyhow::anyhow!("节点 {}: {}", node.hostname, e))
+    }
+
+    /// 校验节点已安装 Open vSwitch，可以挂载 OVS 网络
+    async fn ensure_node_supports_ovs(&self, node_id: &str) -> anyhow::Result<()> {
+        let node = NodeEntity::find_by_id(node_id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("节点不存在"))?;
+
+        if !node.has_ovs {
+            return Err(anyhow::anyhow!("节点 {} 未安装 Open vSwitch，不支持 OVS 网络", node.hostname));
+        }
+        Ok(())
+    }
+
+    /// 在线调整运行中虚拟机的 vCPU 和内存，只下发有变化的项
+    async fn apply_live_resize(
+        &self,
+        vm: &VmModel,
+        vcpu: Option<u32>,
+        memory_mb: Option<u64>,
+    ) -> anyhow::Result<()> {
+        let vcpu = vcpu.filter(|v| *v as i32 != vm.vcpu);
+        let memory_mb = memory_mb.filter(|m| *m as i64 != vm.memory_mb);
+        if vcpu.is_none() && memory_mb.is_none() {
+            return Ok(());
+        }
+
+        let node_id = vm.node_id.as_deref().ok_or_else(|| anyhow::anyhow!("虚拟机未关联节点"))?;
+        let agent_rpc = self.state.agent_rpc();
+
+        if let Some(vcpu) = vcpu {
+            let request = common::ws_rpc::ResizeVmCpuRequest {
+                vm_id: vm.id.clone(),
+                vcpu,
+                live: true,
+            };
+            agent_rpc
+                .call(
+                    node_id,
+                    "resize_vm_cpu",
+                    serde_json::to_value(&request)?,
+                    std::time::Duration::from_secs(30),
+                )
+                .await
+                .map_err(|e| anyhow::anyhow!("在线调整 vCPU 失败: {}", e))?;
+            info!("虚拟机 {} vCPU 已在线调整为 {}", vm.id, vcpu);
+        }
+
+        if let Some(memory_mb) = memory_mb {
+            let request = common::ws_rpc::ResizeVmMemoryRequest {
+                vm_id: vm.id.clone(),
+                memory_mb,
+                live: true,
+            };
+            agent_rpc
+                .call(
+                    node_id,
+                    "resize_vm_memory",
+                    serde_json::to_value(&request)?,
+                    std::time::Duration::from_secs(30),
+                )
+                .await
+                .map_err(|e| anyhow::anyhow!("在线调整内存失败: {}", e))?;
+            info!("虚拟机 {} 内存已在线调整为 {}MiB", vm.id, memory_mb);
+        }
+
+        Ok(())
+    }
+
     /// 删除虚拟机
     ///
     /// 按照 vms.md 流程：
@@ -350,23 +714,40 @@ impl VmService {
             if vm.status == VmStatus::Running.as_str() {
                 return Err(anyhow::anyhow!("无法删除正在运行的虚拟机，请先停止"));
             }
+            if !is_settled(&vm) {
+                return Err(VmStatusConflict::new(&vm, "删除").into());
+            }
 
             // 释放 VM 的所有 IP 地址
             let network_service = NetworkService::new(self.state.clone());
 
-            // 从网络接口配置中获取所有网络 ID
+            // 从网络接口配置中获取所有网络 ID（同一网络可能挂载多块网卡，release_ip 会释放该网络下的全部 IP）
             if let Some(ref network_interfaces) = vm.network_interfaces {
                 if let Ok(interfaces) = serde_json::from_value::<Vec<NetworkInterfaceSpec>>(network_interfaces.clone()) {
-                    for interface in interfaces {
-                        if let Err(e) = network_service.release_ip(&interface.network_id, id).await {
-                            warn!("释放 VM {} 在网络 {} 的 IP 失败: {}", id, interface.network_id, e);
+                    for network_id in Self::distinct_network_ids(&interfaces) {
+                        if let Err(e) = network_service.release_ip(&network_id, id).await {
+                            warn!("释放 VM {} 在网络 {} 的 IP 失败: {}", id, network_id, e);
                         } else {
-                            info!("成功释放 VM {} 在网络 {} 的 IP", id, interface.network_id);
+                            info!("成功释放 VM {} 在网络 {} 的 IP", id, network_id);
+                            if let Err(e) = network_service.reload_dhcp(&network_id).await {
+                                warn!("刷新网络 {} 的 DHCP 租约失败: {}", network_id, e);
+                            }
                         }
                     }
                 }
             }
 
+            // 临时盘随虚拟机删除，其余存储卷释放回存储池
+            let ephemeral_ids: std::collections::HashSet<String> = vm
+                .volumes
+                .as_ref()
+                .and_then(|v| serde_json::from_value::<Vec<DiskSpec>>(v.clone()).ok())
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|disk| disk.ephemeral)
+                .map(|disk| disk.volume_id)
+                .collect();
+
             // 清理关联的volumes - 将vm_id设置为null，状态改为available
             let now = Utc::now();
             let volumes = VolumeEntity::find()
@@ -388,19 +769,99 @@ impl VmService {
                 .await?;
 
             info!("虚拟机 {} 已从数据库删除", id);
+
+            // 通知 Agent 删除配置光盘；Agent 离线时文件残留，不影响删除结果
+            if let (Some(node_id), Some(_)) = (&vm.node_id, &vm.cloud_init) {
+                if let Err(e) = self
+                    .state
+                    .agent_rpc()
+                    .notify(node_id, "cleanup_vm_async", serde_json::json!({ "vm_id": id }))
+                    .await
+                {
+                    warn!("通知节点 {} 清理虚拟机 {} 的配置光盘失败: {}", node_id, id, e);
+                }
+            }
+
+            // 删除临时盘；失败时保留为 available 状态，由用户手动清理
+            let storage_service = StorageService::new(self.state.clone());
+            for volume_id in ephemeral_ids {
+                match storage_service.delete_volume(&volume_id).await {
+                    Ok(()) => info!("已删除虚拟机 {} 的临时盘 {}", id, volume_id),
+                    Err(e) => warn!("删除虚拟机 {} 的临时盘 {} 失败，已保留为可用状态: {}", id, volume_id, e),
+                }
+            }
+
             Ok(())
         } else {
             Err(anyhow::anyhow!("虚拟机不存在"))
         }
     }
 
+    /// 网卡所在的网络 ID（去重），同一网络可挂载多块网卡
+    fn distinct_network_ids(interfaces: &[NetworkInterfaceSpec]) -> Vec<String> {
+        let mut network_ids: Vec<String> = interfaces.iter().map(|i| i.network_id.clone()).collect();
+        network_ids.sort();
+        network_ids.dedup();
+        network_ids
+    }
+
+    /// 构造启动通知中的网卡配置
+    ///
+    /// 附带网络当前的 VLAN ID（无 VLAN 时为 null）、网络类型、MTU 和 DHCP 配置，Agent 据此创建缺失的 Bridge，
+    /// 无需从 Bridge 名称推断；网络已被删除时不附带，由 Agent 按自身配置处理
+    async fn start_networks(&self, vm: &VmModel) -> anyhow::Result<Vec<serde_json::Value>> {
+        let interfaces: Vec<NetworkInterfaceSpec> = vm
+            .network_interfaces
+            .as_ref()
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        let mut networks = Vec::with_capacity(interfaces.len());
+        for interface in interfaces {
+            let network = NetworkEntity::find_by_id(&interface.network_id)
+                .one(&self.state.sea_db())
+                .await?;
+            let mut value = serde_json::to_value(&interface)?;
+            if let Some(network) = network {
+                value["vlan_id"] = serde_json::json!(network.vlan_id);
+                value["network_type"] = serde_json::json!(network.network_type);
+                value["mtu"] = serde_json::json!(network.mtu);
+                // Agent 在网卡启动后按规则为其 tap 设备生成过滤规则
+                if let Some(rules) = SecurityGroupService::new(self.state.clone())
+                    .effective_rules(&interface, &network)
+                    .await?
+                {
+                    value["security_group_rules"] = serde_json::json!(rules);
+                }
+                // 托管 DHCP 的网络由 Agent 在 Bridge 上启动 dnsmasq
+                if let Some(dhcp) = NetworkService::new(self.state.clone()).dhcp_config(&network).await? {
+                    value["dhcp"] = serde_json::json!(dhcp);
+                }
+            }
+            networks.push(value);
+        }
+        Ok(networks)
+    }
+
     /// 启动虚拟机
     ///
     /// 按照 vms.md 流程：
     /// API -> Server记录DB -> UI提示进行中
     /// --(notify)-> agent 重新define xml，启动虚拟机 --(notify)-> Server更新db记录 -> UI提示完成
     /// Agent需要重新define xml，确保虚拟机配置与数据库一致。
-    pub async fn start_vm(&self, id: &str) -> anyhow::Result<()> {
+    ///
+    /// safe_mode 为 true 时 Agent 只挂载系统盘、一块默认网卡和串口控制台，
+    /// 数据库中的配置保持不变，下次正常启动即恢复完整配置
+    ///
+    /// boot_from 指定本次启动优先引导的存储卷（如安装光盘），同样不修改保存的引导顺序
+    ///
+    /// 返回任务 ID，可通过任务接口查询启动结果
+    pub async fn start_vm(
+        &self,
+        id: &str,
+        safe_mode: bool,
+        boot_from: Option<&str>,
+    ) -> anyhow::Result<String> {
         let db = &self.state.sea_db();
 
         // 查询 VM 信息
@@ -412,46 +873,87 @@ impl VmService {
         if vm.status == VmStatus::Running.as_str() {
             return Err(anyhow::anyhow!("虚拟机已经在运行中"));
         }
+        if vm.status == VmStatus::Paused.as_str() {
+            return Err(anyhow::anyhow!("虚拟机已暂停，请使用恢复操作"));
+        }
+        if !is_settled(&vm) {
+            return Err(VmStatusConflict::new(&vm, "启动").into());
+        }
 
         // 通知 Agent 所需的字段从 Model 读取，避免 ActiveValue 参与序列化
         let node_id = vm.node_id.clone().ok_or_else(|| anyhow::anyhow!("虚拟机未关联节点"))?;
+        SchedulerService::new(self.state.clone())
+            .check_capacity(&node_id, Some(id), vm.vcpu as u32, vm.memory_mb as u64)
+            .await?;
+        // 安全模式不下发 CPU 绑定，无需校验
+        if !safe_mode {
+            self.ensure_node_supports_cpu_placement(
+                &node_id,
+                vm.vcpu as u32,
+                parse_json::<CpuPinning>(&vm.cpu_pinning).as_ref(),
+                parse_json::<NumaConfig>(&vm.numa).as_ref(),
+            )
+            .await?;
+        }
         // 组装 Agent 所需的磁盘信息（DiskConfig）
+        let disks: Vec<DiskSpec> = vm
+            .volumes
+            .as_ref()
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        let boot_orders = boot_orders(&disks, boot_from)?;
         let mut vm_start_volumes = Vec::new();
-        if let Some(ref volumes_json) = vm.volumes {
-            if let Ok(volumes) = serde_json::from_value::<Vec<DiskSpec>>(volumes_json.clone()) {
-                for v in volumes {
-                    // 查询 volume 以获取路径与格式
-                    let vol = VolumeEntity::find_by_id(&v.volume_id)
-                        .one(db)
-                        .await?
-                        .ok_or_else(|| anyhow::anyhow!(format!("存储卷不存在: {}", v.volume_id)))?;
-
-                    let volume_path = vol.path.ok_or_else(|| anyhow::anyhow!(format!("存储卷缺少路径: {}", v.volume_id)))?;
-                    let format = vol.volume_type;
-
-                    let volume_value = serde_json::json!({
-                        "volume_id": v.volume_id,
-                        "volume_path": volume_path,
-                        "bus_type": v.bus_type,
-                        "device_type": v.device_type,
-                        "format": format
-                    });
-                    vm_start_volumes.push(volume_value);
-                }
-            }
+        for v in disks {
+            // 查询 volume 以获取路径与格式
+            let vol = VolumeEntity::find_by_id(&v.volume_id)
+                .one(db)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!(format!("存储卷不存在: {}", v.volume_id)))?;
+
+            let storage = StorageService::new(self.state.clone());
+            let backing = storage.backing_store(&vol).await?;
+            let encryption = storage.volume_encryption(&vol)?;
+            let volume_path = vol.path.ok_or_else(|| anyhow::anyhow!(format!("存储卷缺少路径: {}", v.volume_id)))?;
+            let format = vol.volume_type;
+
+            let volume_value = serde_json::json!({
+                "volume_id": v.volume_id,
+                "volume_path": volume_path,
+                "bus_type": v.bus_type,
+                "device_type": v.device_type,
+                "format": format,
+                "iops_limit": v.limits.iops_limit,
+                "bps_limit": v.limits.bps_limit,
+                "backing": backing,
+                "encryption": encryption,
+                "boot_order": boot_orders.get(&v.volume_id)
+            });
+            vm_start_volumes.push(volume_value);
         }
 
-        let start_request = serde_json::json!({
+        let mut start_request = serde_json::json!({
             "vm_id": id,
             "name": vm.name,
             "vcpu": vm.vcpu,
             "memory_mb": vm.memory_mb,
             "os_type": vm.os_type,
+            "firmware": vm.firmware,
+            "tpm": vm.tpm,
+            "boot_menu": vm.boot_menu,
+            "cpu_pinning": vm.cpu_pinning,
+            "numa": vm.numa,
+            "raw_xml_override": vm.raw_xml_override,
+            "arch": vm.arch,
+            "machine_type": vm.machine_type,
+            "graphics": vm.graphics,
+            // 每次启动使用随机密码且不保存，需通过控制台访问接口获取一次性密码
+            "console_password": Self::generate_console_password(),
+            "cloud_init": vm.cloud_init,
             // 新字段：按 Agent 期望结构提供的磁盘数组
             "volumes": vm_start_volumes,
-            // 先保持原有网络结构，后续再转换为 Agent 期望的 NetworkConfig
-            "networks": vm.network_interfaces,
-            "metadata": vm.metadata
+            "networks": self.start_networks(&vm).await?,
+            "metadata": vm.metadata,
+            "safe_mode": safe_mode
         });
 
         // 更新数据库状态为"启动中"
@@ -461,17 +963,42 @@ impl VmService {
         vm_active.updated_at = Set(now.into());
         vm_active.update(db).await?;
 
+        let task_id = TaskService::new(self.state.clone())
+            .create_task(TaskType::StartVm, id)
+            .await?;
+        start_request["task_id"] = serde_json::json!(task_id);
+
         // 异步通知 Agent，不等待结果
-        self.state.agent_manager()
-            .notify(
-                &node_id,
-                "start_vm_async",
-                start_request,
-            )
+        self.notify_with_task(&node_id, "start_vm_async", start_request, &task_id)
             .await
             .map_err(|e| anyhow::anyhow!("发送启动通知失败: {}", e))?;
 
-        info!("虚拟机 {} 启动通知已发送给 Agent", id);
+        if safe_mode {
+            info!("虚拟机 {} 安全模式启动通知已发送给 Agent", id);
+        } else {
+            info!("虚拟机 {} 启动通知已发送给 Agent", id);
+        }
+        Ok(task_id)
+    }
+
+    /// 发送带任务 ID 的异步通知，发送失败时将任务标记为失败
+    async fn notify_with_task(
+        &self,
+        node_id: &str,
+        method: &str,
+        payload: serde_json::Value,
+        task_id: &str,
+    ) -> anyhow::Result<()> {
+        if let Err(e) = self.state.agent_rpc().notify(node_id, method, payload).await {
+            let message = format!("发送通知失败: {}", e);
+            if let Err(e) = TaskService::new(self.state.clone())
+                .finish_task(task_id, false, &message)
+                .await
+            {
+                warn!("更新任务 {} 状态失败: {}", task_id, e);
+            }
+            return Err(anyhow::anyhow!(e.to_string()));
+        }
         Ok(())
     }
 
@@ -481,7 +1008,9 @@ impl VmService {
     /// API -> Server记录DB -> UI提示进行中
     /// --(notify)-> agent 关机并undefine xml --(notify)-> Server更新db记录 -> UI提示完成
     /// 关机需要区是否为强制关机模式。在非强制失败后，自用使用强制关机。
-    pub async fn stop_vm(&self, id: &str, force: bool) -> anyhow::Result<()> {
+    ///
+    /// 返回任务 ID，可通过任务接口查询停止结果
+    pub async fn stop_vm(&self, id: &str, force: bool) -> anyhow::Result<String> {
         let db = &self.state.sea_db();
 
         // 查询 VM 信息
@@ -493,22 +1022,25 @@ impl VmService {
         if vm.status == VmStatus::Stopped.as_str() {
             return Err(anyhow::anyhow!("虚拟机已经停止"));
         }
+        if vm.status == VmStatus::Rebuilding.as_str() {
+            return Err(VmStatusConflict::new(&vm, "停止").into());
+        }
 
         // 通知 Agent 所需字段从 Model 读取
         let node_id = vm.node_id.clone().ok_or_else(|| anyhow::anyhow!("虚拟机未关联节点"))?;
         
+        let task_id = TaskService::new(self.state.clone())
+            .create_task(TaskType::StopVm, id)
+            .await?;
+
         let stop_request = serde_json::json!({
             "vm_id": id,
-            "force": force
+            "force": force,
+            "task_id": task_id
         });
 
         // 异步通知 Agent，不等待结果
-        self.state.agent_manager()
-            .notify(
-                &node_id,
-                "stop_vm_async",
-                stop_request,
-            )
+        self.notify_with_task(&node_id, "stop_vm_async", stop_request, &task_id)
             .await
             .map_err(|e| anyhow::anyhow!("发送停止通知失败: {}", e))?;
 
@@ -521,6 +1053,74 @@ impl VmService {
         vm_active.updated_at = Set(now.into());
         vm_active.update(db).await?;
 
+        Ok(task_id)
+    }
+
+    /// 暂停虚拟机
+    ///
+    /// libvirt suspend 在毫秒级完成，直接同步调用 Agent，成功后状态置为 paused
+    pub async fn pause_vm(&self, id: &str) -> anyhow::Result<()> {
+        self.switch_paused(id, VmStatus::Running, VmStatus::Paused, "pause_vm")
+            .await
+    }
+
+    /// 恢复已暂停的虚拟机，成功后状态置为 running
+    pub async fn resume_vm(&self, id: &str) -> anyhow::Result<()> {
+        self.switch_paused(id, VmStatus::Paused, VmStatus::Running, "resume_vm")
+            .await
+    }
+
+    async fn switch_paused(
+        &self,
+        id: &str,
+        from: VmStatus,
+        to: VmStatus,
+        method: &str,
+    ) -> anyhow::Result<()> {
+        let db = &self.state.sea_db();
+
+        let vm = VmEntity::find_by_id(id.to_string())
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+
+        if vm.status != from.as_str() {
+            return Err(anyhow::anyhow!(
+                "虚拟机当前状态为 {}，只有 {} 状态的虚拟机可以执行该操作",
+                vm.status,
+                from.as_str()
+            ));
+        }
+
+        let node_id = vm.node_id.clone().ok_or_else(|| anyhow::anyhow!("虚拟机未关联节点"))?;
+
+        let request = common::ws_rpc::VmOperationRequest {
+            vm_id: id.to_string(),
+            force: false,
+        };
+        self.state
+            .agent_rpc()
+            .call(
+                &node_id,
+                method,
+                serde_json::to_value(&request)?,
+                std::time::Duration::from_secs(30),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("WebSocket RPC 调用失败: {}", e))?;
+
+        let mut vm_active: VmActiveModel = vm.into();
+        vm_active.status = Set(to.as_str().to_string());
+        vm_active.updated_at = Set(Utc::now().into());
+        vm_active.update(db).await?;
+
+        let message = match to {
+            VmStatus::Paused => "虚拟机已暂停",
+            _ => "虚拟机已恢复运行",
+        };
+        self.notify_vm_status_update(id, to.as_str(), Some(message)).await;
+
+        info!("虚拟机 {} 状态已切换为 {}", id, to.as_str());
         Ok(())
     }
 
@@ -529,7 +1129,9 @@ impl VmService {
     /// 按照 vms.md 流程：
     /// API -> Server记录DB -> UI提示进行中
     /// --(notify)-> agent 尝试软关机并启动，否则强制关机并启动 --(notify)-> Server更新db记录 -> UI提示完成
-    pub async fn restart_vm(&self, id: &str) -> anyhow::Result<()> {
+    ///
+    /// 返回任务 ID，可通过任务接口查询重启结果
+    pub async fn restart_vm(&self, id: &str) -> anyhow::Result<String> {
         let db = &self.state.sea_db();
 
         // 查询 VM 信息
@@ -537,6 +1139,9 @@ impl VmService {
             .one(db)
             .await?
             .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+        if vm.status == VmStatus::Rebuilding.as_str() {
+            return Err(VmStatusConflict::new(&vm, "重启").into());
+        }
 
         // 通知 Agent 所需字段
         let node_id = vm
@@ -544,10 +1149,15 @@ impl VmService {
             .clone()
             .ok_or_else(|| anyhow::anyhow!("虚拟机未关联节点"))?;
 
+        let task_id = TaskService::new(self.state.clone())
+            .create_task(TaskType::RestartVm, id)
+            .await?;
+
         let request = serde_json::json!({
             "vm_id": id,
             // 先走软关机，失败由 Agent 端自动执行强制关机
-            "force": false
+            "force": false,
+            "task_id": task_id
         });
 
         // 更新数据库状态为"重启中"
@@ -558,24 +1168,21 @@ impl VmService {
         vm_active.update(db).await?;
 
         // 异步通知 Agent，不等待结果
-        self.state
-            .agent_manager()
-            .notify(&node_id, "restart_vm_async", request)
+        self.notify_with_task(&node_id, "restart_vm_async", request, &task_id)
             .await
             .map_err(|e| anyhow::anyhow!("发送重启通知失败: {}", e))?;
 
         info!("虚拟机 {} 重启通知已发送给 Agent", id);
-        Ok(())
+        Ok(task_id)
     }
 
     /// 迁移虚拟机
-    pub async fn migrate_vm(
-        &self,
-        id: &str,
-        target_node_id: &str,
-        live: bool,
-    ) -> anyhow::Result<()> {
+    ///
+    /// 热迁移时可限制带宽和停机时间，并可指定超时后挂起虚拟机完成迁移
+    pub async fn migrate_vm(&self, id: &str, dto: MigrateVmDto) -> anyhow::Result<()> {
         let db = &self.state.sea_db();
+        let target_node_id = dto.target_node_id.as_str();
+        let live = dto.live;
 
         // 查询 VM 信息
         let vm = VmEntity::find_by_id(id.to_string())
@@ -592,7 +1199,7 @@ impl VmService {
             return Err(anyhow::anyhow!("源节点和目标节点相同"));
         }
 
-        // 检查虚拟机状态
+        // 检查虚拟机状态（重装中的虚拟机既不是关机也不是运行状态）
         if !live && vm.status != VmStatus::Stopped.as_str() {
             return Err(anyhow::anyhow!("冷迁移要求虚拟机必须处于关机状态"));
         }
@@ -612,6 +1219,35 @@ impl VmService {
             return Err(anyhow::anyhow!("目标节点不在线"));
         }
 
+        if vm.tpm && !target_node.has_swtpm {
+            return Err(anyhow::anyhow!("目标节点未安装 swtpm，不支持 TPM 虚拟机"));
+        }
+        self.ensure_node_supports_cpu_placement(
+            target_node_id,
+            vm.vcpu as u32,
+            parse_json::<CpuPinning>(&vm.cpu_pinning).as_ref(),
+            parse_json::<NumaConfig>(&vm.numa).as_ref(),
+        )
+        .await?;
+
+        // 加密磁盘的 libvirt secret 只在源节点上定义，目标节点无法打开磁盘
+        if live {
+            self.ensure_no_encrypted_volumes(&vm).await?;
+        }
+
+        // 目标节点不能违反虚拟机所属亲和组的规则
+        SchedulerService::new(self.state.clone())
+            .select_node(Some(id), &[], &[target_node_id.to_string()])
+            .await?;
+
+        // 复制存储迁移需要目标节点上预先存在同尺寸的空白卷，libvirt 只负责复制数据
+        let storage_mode = dto.storage_mode;
+        let copied_volumes = if storage_mode == MigrationStorageMode::CopyStorage {
+            self.create_migration_target_volumes(&vm, target_node_id).await?
+        } else {
+            Vec::new()
+        };
+
         // 更新状态为迁移中，并将目标节点ID存储到 metadata 中
         let now = Utc::now();
         let mut vm_active: VmActiveModel = vm.clone().into();
@@ -623,26 +1259,36 @@ impl VmService {
         if let Some(obj) = metadata.as_object_mut() {
             obj.insert("migration_target_node_id".to_string(), serde_json::json!(target_node_id));
             obj.insert("migration_is_live".to_string(), serde_json::json!(live));
+            obj.insert(
+                "migration_storage_mode".to_string(),
+                serde_json::json!(storage_mode.as_str()),
+            );
         }
         vm_active.metadata = Set(Some(metadata));
         
         vm_active.update(db).await?;
 
         // 发送迁移请求到源节点 Agent
-        let agent_manager = &self.state.agent_manager;
+        let agent_rpc = self.state.agent_rpc();
 
         let migrate_req = common::ws_rpc::types::MigrateVmRequest {
             vm_id: id.to_string(),
             target_node_id: target_node_id.to_string(),
             target_node_address: target_node.ip_address.clone(),
+            target_uri: Some(migration_uri(&target_node.ip_address)),
             live_migration: live,
+            max_bandwidth_mbps: dto.max_bandwidth_mbps,
+            max_downtime_ms: dto.max_downtime_ms,
+            fallback_policy: dto.fallback_policy,
+            live_timeout_secs: dto.live_timeout_secs,
+            storage_mode,
         };
 
         let payload = serde_json::to_value(migrate_req)
             .map_err(|e| anyhow::anyhow!("序列化迁移请求失败: {}", e))?;
 
         // 发送 RPC 请求到源节点
-        match agent_manager
+        match agent_rpc
             .call(
                 &source_node_id,
                 "migrate_vm",
@@ -661,6 +1307,9 @@ impl VmService {
                 Ok(())
             }
             Err(e) => {
+                self.remove_migration_target_volumes(&copied_volumes, target_node_id)
+                    .await;
+
                 // 迁移失败，恢复原状态
                 let vm = VmEntity::find_by_id(id.to_string())
                     .one(db)
@@ -678,17 +1327,607 @@ impl VmService {
         }
     }
 
-    /// 附加存储卷到虚拟机
-    ///
-    /// 按照 vms.md 流程：
-    /// API -> Server记录DB -> UI提示进行中
-    /// --(notify)-> agent 热挂载磁盘，并标记持久 --(notify)-> Server更新db记录 -> UI提示完成
-    /// 若虚拟机未开机，则不需要调用agent，仅更新db即可。开机会重新define。
-    pub async fn attach_volume(&self, vm_id: &str, dto: AttachVolumeDto) -> anyhow::Result<()> {
-        let db = &self.state.sea_db();
-        let now = Utc::now();
+    /// 查询虚拟机所在节点上生效的迁移带宽上限
+    pub async fn get_migration_speed(
+        &self,
+        id: &str,
+    ) -> anyhow::Result<common::ws_rpc::MigrationSpeedResponse> {
+        let vm = VmEntity::find_by_id(id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
 
-        // 检查虚拟机是否存在
+        let node_id = vm
+            .node_id
+            .ok_or_else(|| anyhow::anyhow!("虚拟机未分配节点"))?;
+
+        let request = common::ws_rpc::VmOperationRequest {
+            vm_id: id.to_string(),
+            force: false,
+        };
+        let response = self
+            .state
+            .agent_rpc()
+            .call(
+                &node_id,
+                "get_migration_speed",
+                serde_json::to_value(&request)?,
+                std::time::Duration::from_secs(10),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("查询迁移带宽上限失败: {}", e))?;
+
+        Ok(serde_json::from_value(
+            response
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?)
+    }
+
+    /// 复制存储迁移需要复制的存储卷，光驱镜像只读且不随迁移复制
+    fn migration_copy_volume_ids(vm: &VmModel) -> Vec<String> {
+        let disks: Vec<DiskSpec> = vm
+            .volumes
+            .as_ref()
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        disks
+            .into_iter()
+            .filter(|d| d.device_type == common::ws_rpc::types::DiskDeviceType::Disk)
+            .map(|d| d.volume_id)
+            .collect()
+    }
+
+    /// 在目标节点创建虚拟机磁盘的空白副本，返回已创建的存储卷 ID
+    ///
+    /// 任一磁盘创建失败时删除已创建的副本，避免目标节点残留空卷
+    async fn create_migration_target_volumes(
+        &self,
+        vm: &VmModel,
+        target_node_id: &str,
+    ) -> anyhow::Result<Vec<String>> {
+        let storage_service = StorageService::new(self.state.clone());
+        let mut created = Vec::new();
+        for volume_id in Self::migration_copy_volume_ids(vm) {
+            if let Err(e) = storage_service
+                .create_blank_copy_on_node(&volume_id, target_node_id)
+                .await
+            {
+                self.remove_migration_target_volumes(&created, target_node_id)
+                    .await;
+                return Err(e);
+            }
+            created.push(volume_id);
+        }
+        Ok(created)
+    }
+
+    /// 虚拟机的磁盘中不能有加密存储卷
+    async fn ensure_no_encrypted_volumes(&self, vm: &VmModel) -> anyhow::Result<()> {
+        let disks: Vec<DiskSpec> = vm
+            .volumes
+            .as_ref()
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        let volume_ids: Vec<String> = disks.into_iter().map(|d| d.volume_id).collect();
+        let encrypted = VolumeEntity::find()
+            .filter(VolumeColumn::Id.is_in(volume_ids))
+            .filter(VolumeColumn::Encryption.is_not_null())
+            .count(&self.state.sea_db())
+            .await?;
+        if encrypted > 0 {
+            return Err(anyhow::anyhow!("虚拟机挂载了加密存储卷，暂不支持热迁移"));
+        }
+        Ok(())
+    }
+
+    /// 复制存储迁移失败后删除目标节点上的磁盘副本，删除失败只记录日志
+    async fn remove_migration_target_volumes(&self, volume_ids: &[String], target_node_id: &str) {
+        let storage_service = StorageService::new(self.state.clone());
+        for volume_id in volume_ids {
+            if let Err(e) = storage_service
+                .delete_copy_on_node(volume_id, target_node_id)
+                .await
+            {
+                warn!("清理目标节点 {} 上的存储卷副本 {} 失败: {}", target_node_id, volume_id, e);
+            }
+        }
+    }
+
+    /// 取消正在进行的迁移
+    ///
+    /// 迁移作业运行在源节点上，迁移完成前虚拟机仍记录在源节点；
+    /// 作业中止后源节点上报迁移失败，状态由 handle_vm_migration_progress 恢复
+    pub async fn abort_migration(&self, id: &str) -> anyhow::Result<()> {
+        let vm = VmEntity::find_by_id(id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+
+        if vm.status != VmStatus::Migrating.as_str() {
+            return Err(anyhow::anyhow!("虚拟机当前没有进行中的迁移"));
+        }
+
+        let source_node_id = vm
+            .node_id
+            .ok_or_else(|| anyhow::anyhow!("虚拟机未分配节点"))?;
+
+        let request = common::ws_rpc::VmOperationRequest {
+            vm_id: id.to_string(),
+            force: false,
+        };
+        self.state
+            .agent_rpc()
+            .call(
+                &source_node_id,
+                "abort_migration",
+                serde_json::to_value(&request)?,
+                std::time::Duration::from_secs(30),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("取消迁移失败: {}", e))?;
+
+        info!("虚拟机 {} 的迁移已取消，等待源节点上报迁移结果", id);
+        Ok(())
+    }
+
+    /// 重装虚拟机系统盘
+    ///
+    /// 仅允许对已停止的虚拟机操作：从指定镜像创建新的系统盘替换原系统盘（原系统盘被删除，
+    /// 启用安全快照时保留到快照过期），名称、IP、MAC 以及数据盘保持不变，完成后按需走正常启动流程。
+    pub async fn rebuild_vm(&self, id: &str, dto: RebuildVmDto) -> anyhow::Result<VmResponse> {
+        let db = &self.state.sea_db();
+
+        let vm = VmEntity::find_by_id(id.to_string())
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+
+        // 重装系统要求虚拟机必须处于关机状态
+        if vm.status != VmStatus::Stopped.as_str() {
+            return Err(VmStatusConflict::new(&vm, "重装系统").into());
+        }
+
+        let mut disks: Vec<DiskSpec> = vm
+            .volumes
+            .as_ref()
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        // 第一个磁盘设备即为启动盘（与 list_vm_volumes 的约定一致）
+        let root_index = disks
+            .iter()
+            .position(|d| d.device_type == common::ws_rpc::types::DiskDeviceType::Disk)
+            .ok_or_else(|| anyhow::anyhow!("虚拟机没有可重装的系统盘"))?;
+
+        let old_volume = VolumeEntity::find_by_id(&disks[root_index].volume_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("系统盘存储卷不存在: {}", disks[root_index].volume_id))?;
+
+        let size_gb = dto.size_gb.unwrap_or(old_volume.size_gb);
+        if size_gb < old_volume.size_gb {
+            return Err(anyhow::anyhow!(
+                "新系统盘大小不能小于原系统盘: {}GB < {}GB",
+                size_gb,
+                old_volume.size_gb
+            ));
+        }
+
+        // 仅在仍处于关机状态时标记为重装中，并发的启动、迁移或另一次重装只有一个能成功
+        let result = VmEntity::update_many()
+            .col_expr(VmColumn::Status, Expr::value(VmStatus::Rebuilding.as_str()))
+            .col_expr(VmColumn::UpdatedAt, Expr::value(Utc::now()))
+            .filter(VmColumn::Id.eq(id))
+            .filter(VmColumn::Status.eq(VmStatus::Stopped.as_str()))
+            .exec(db)
+            .await?;
+        if result.rows_affected == 0 {
+            let status = VmEntity::find_by_id(id.to_string())
+                .one(db)
+                .await?
+                .map(|vm| vm.status)
+                .unwrap_or_default();
+            return Err(VmStatusConflict { vm_id: id.to_string(), status, operation: "重装系统" }.into());
+        }
+        self.notify_vm_status_update(id, VmStatus::Rebuilding.as_str(), Some("正在重装系统盘"))
+            .await;
+
+        // 按配置为原系统盘创建安全快照，原盘保留到快照过期以便回滚
+        let safety_snapshot_id = match SnapshotService::new(self.state.clone())
+            .create_safety_snapshot(&old_volume.id, SafetyOperation::RebuildVm)
+            .await
+        {
+            Ok(snapshot_id) => snapshot_id,
+            Err(e) => {
+                self.finish_rebuild(id, "创建安全快照失败，重装已取消").await?;
+                return Err(e);
+            }
+        };
+
+        // 从镜像创建新的系统盘
+        let storage_service = StorageService::new(self.state.clone());
+        let new_volume = match storage_service
+            .create_volume(CreateVolumeDto {
+                name: old_volume.name.clone(),
+                pool_id: old_volume.pool_id.clone(),
+                size_gb,
+                volume_type: old_volume.volume_type.clone(),
+                source: Some(dto.source.clone()),
+                compress: false,
+                checksum: dto.checksum.clone(),
+                source_headers: dto.source_headers.clone(),
+                source_auth: dto.source_auth.clone(),
+                backing_volume_id: None,
+                encryption: None,
+                owner_id: old_volume.owner_id,
+                metadata: Some(serde_json::json!({
+                    "rebuild_of": old_volume.id,
+                    "rebuild_vm_id": id,
+                    "safety_snapshot_id": safety_snapshot_id,
+                })),
+            })
+            .await
+        {
+            Ok(volume) => volume,
+            Err(e) => {
+                error!("虚拟机 {} 重装失败，创建系统盘出错: {}", id, e);
+                self.finish_rebuild(id, "重装系统失败").await?;
+                return Err(anyhow::anyhow!("创建新系统盘失败: {}", e));
+            }
+        };
+
+        // 替换磁盘列表中的系统盘，保留总线与设备类型
+        disks[root_index].volume_id = new_volume.id.clone();
+
+        let now = Utc::now();
+        let mut vm_active: VmActiveModel = vm.into();
+        vm_active.volumes = Set(Some(serde_json::to_value(&disks)?));
+        vm_active.status = Set(VmStatus::Stopped.as_str().to_string());
+        vm_active.updated_at = Set(now.into());
+        let vm = vm_active.update(db).await?;
+
+        if let Some(volume) = VolumeEntity::find_by_id(&new_volume.id).one(db).await? {
+            let mut volume_active: VolumeActiveModel = volume.into();
+            volume_active.vm_id = Set(Some(id.to_string()));
+            volume_active.status = Set("in-use".to_string());
+            volume_active.updated_at = Set(now.into());
+            volume_active.update(db).await?;
+        }
+
+        // 解除原系统盘关联后删除
+        let old_volume_id = old_volume.id.clone();
+        let mut volume_active: VolumeActiveModel = old_volume.into();
+        volume_active.vm_id = Set(None);
+        volume_active.status = Set("available".to_string());
+        volume_active.updated_at = Set(now.into());
+        volume_active.update(db).await?;
+
+        match &safety_snapshot_id {
+            Some(snapshot_id) => info!(
+                "保留原系统盘 {}，安全快照 {} 过期后自动清理",
+                old_volume_id, snapshot_id
+            ),
+            None => {
+                if let Err(e) = storage_service.delete_volume(&old_volume_id).await {
+                    warn!("删除原系统盘 {} 失败，需要手动清理: {}", old_volume_id, e);
+                }
+            }
+        }
+
+        info!(
+            "虚拟机 {} 系统盘已重装: {} -> {}",
+            id, old_volume_id, new_volume.id
+        );
+        self.notify_vm_status_update(id, VmStatus::Stopped.as_str(), Some("系统盘重装完成"))
+            .await;
+
+        if dto.start {
+            self.start_vm(id, false, None).await?;
+        }
+
+        Ok(self.vm_to_response(vm).await)
+    }
+
+    /// 重装中止时恢复为关机状态
+    async fn finish_rebuild(&self, id: &str, message: &str) -> anyhow::Result<()> {
+        VmEntity::update_many()
+            .col_expr(VmColumn::Status, Expr::value(VmStatus::Stopped.as_str()))
+            .col_expr(VmColumn::UpdatedAt, Expr::value(Utc::now()))
+            .filter(VmColumn::Id.eq(id))
+            .filter(VmColumn::Status.eq(VmStatus::Rebuilding.as_str()))
+            .exec(&self.state.sea_db())
+            .await?;
+        self.notify_vm_status_update(id, VmStatus::Stopped.as_str(), Some(message)).await;
+        Ok(())
+    }
+
+    /// 克隆虚拟机
+    ///
+    /// 磁盘逐块克隆到源卷所在存储池，指定快照的磁盘从快照时间点创建（用于恢复到黄金镜像）；
+    /// 光驱不随克隆复制，网卡沿用源虚拟机的网络并重新分配 IP 与 MAC
+    pub async fn clone_vm(&self, id: &str, dto: CloneVmDto) -> anyhow::Result<VmResponse> {
+        let db = &self.state.sea_db();
+
+        let vm = VmEntity::find_by_id(id.to_string())
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+
+        let node_id = vm
+            .node_id
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("源虚拟机未分配节点"))?;
+
+        let disks: Vec<DiskSpec> = vm
+            .volumes
+            .as_ref()
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        let disks: Vec<DiskSpec> = disks
+            .into_iter()
+            .filter(|d| d.device_type == common::ws_rpc::types::DiskDeviceType::Disk)
+            .collect();
+
+        // 校验快照：存在、可用、属于源虚拟机的磁盘，且每块磁盘最多一个
+        let mut snapshot_by_volume: std::collections::HashMap<String, String> =
+            std::collections::HashMap::new();
+        let mut snapshot_sizes: std::collections::HashMap<String, i64> = std::collections::HashMap::new();
+        for snapshot_id in &dto.source_snapshot_ids {
+            let snapshot = SnapshotEntity::find_by_id(snapshot_id)
+                .one(db)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("快照 {} 不存在", snapshot_id))?;
+
+            if snapshot.status != SnapshotStatus::Available.as_str() {
+                return Err(anyhow::anyhow!("快照 {} 状态不可用: {}", snapshot_id, snapshot.status));
+            }
+            if !disks.iter().any(|d| d.volume_id == snapshot.volume_id) {
+                return Err(anyhow::anyhow!("快照 {} 不属于虚拟机 {} 的磁盘", snapshot_id, id));
+            }
+            if snapshot_by_volume
+                .insert(snapshot.volume_id.clone(), snapshot.id.clone())
+                .is_some()
+            {
+                return Err(anyhow::anyhow!("磁盘 {} 指定了多个快照", snapshot.volume_id));
+            }
+            if let Some(size_gb) = snapshot.size_gb {
+                snapshot_sizes.insert(snapshot.volume_id.clone(), size_gb);
+            }
+        }
+
+        // 克隆当前数据时需要关机以保证磁盘一致
+        let clones_current_data = disks
+            .iter()
+            .any(|d| !snapshot_by_volume.contains_key(&d.volume_id));
+        if clones_current_data && vm.status != VmStatus::Stopped.as_str() {
+            return Err(anyhow::anyhow!(
+                "克隆当前数据要求源虚拟机处于关机状态，或为所有磁盘指定快照"
+            ));
+        }
+
+        // 克隆出的虚拟机和磁盘都归属源虚拟机的所有者，克隆前一次性校验配额
+        if let Some(owner_id) = vm.owner_id {
+            let mut request = QuotaRequest {
+                vcpu: vm.vcpu as i64,
+                memory_mb: vm.memory_mb,
+                volumes: disks.len() as i64,
+                ..Default::default()
+            };
+            for disk in &disks {
+                let size_gb = match snapshot_sizes.get(&disk.volume_id) {
+                    Some(size_gb) => *size_gb,
+                    None => VolumeEntity::find_by_id(&disk.volume_id)
+                        .one(db)
+                        .await?
+                        .map_or(0, |v| v.size_gb),
+                };
+                request.volume_gb += size_gb;
+            }
+            DepartmentService::check_quota(db, owner_id, request).await?;
+        }
+
+        // 逐块克隆磁盘，失败时清理已克隆的卷
+        let storage_service = StorageService::new(self.state.clone());
+        let mut cloned_disks: Vec<DiskSpec> = Vec::with_capacity(disks.len());
+        for (index, disk) in disks.iter().enumerate() {
+            let result = storage_service
+                .clone_volume(CloneVolumeDto {
+                    source_volume_id: disk.volume_id.clone(),
+                    target_name: format!("{}-disk{}", dto.name, index),
+                    source_snapshot_id: snapshot_by_volume.get(&disk.volume_id).cloned(),
+                    target_pool_id: None,
+                })
+                .await;
+
+            match result {
+                Ok(volume) => cloned_disks.push(DiskSpec {
+                    volume_id: volume.id,
+                    bus_type: disk.bus_type.clone(),
+                    device_type: disk.device_type.clone(),
+                    ephemeral: disk.ephemeral,
+                    limits: disk.limits,
+                    boot_order: disk.boot_order,
+                }),
+                Err(e) => {
+                    self.delete_cloned_volumes(&storage_service, &cloned_disks).await;
+                    return Err(anyhow::anyhow!("克隆磁盘 {} 失败: {}", disk.volume_id, e));
+                }
+            }
+        }
+
+        let networks: Option<Vec<NetworkInterfaceSpec>> = vm
+            .network_interfaces
+            .as_ref()
+            .and_then(|v| serde_json::from_value::<Vec<NetworkInterfaceSpec>>(v.clone()).ok())
+            .map(|interfaces| {
+                interfaces
+                    .into_iter()
+                    .map(|interface| NetworkInterfaceSpec {
+                        network_id: interface.network_id,
+                        mac_address: None,
+                        ip_address: None,
+                        ipv6_address: None,
+                        model: interface.model,
+                        bridge_name: None,
+                        bandwidth: interface.bandwidth,
+                        security_group_id: interface.security_group_id,
+                    })
+                    .collect()
+            });
+
+        let create_dto = CreateVmDto {
+            name: dto.name.clone(),
+            node_id: Some(node_id),
+            vcpu: vm.vcpu as u32,
+            memory_mb: vm.memory_mb as u64,
+            os_type: Some(vm.os_type.clone()),
+            firmware: vm.firmware.parse().unwrap_or_default(),
+            tpm: vm.tpm,
+            boot_menu: vm.boot_menu,
+            cpu_pinning: parse_json(&vm.cpu_pinning),
+            numa: parse_json(&vm.numa),
+            // 自定义 XML 中的 name 与 uuid 属于源虚拟机
+            raw_xml_override: None,
+            arch: Some(vm.arch.clone()),
+            machine_type: vm.machine_type.clone(),
+            graphics: vm.graphics.parse().unwrap_or_default(),
+            disks: Some(cloned_disks.clone()),
+            networks,
+            cloud_init: vm
+                .cloud_init
+                .clone()
+                .and_then(|v| serde_json::from_value(v).ok()),
+            metadata: Some(serde_json::json!({
+                "cloned_from_vm_id": id,
+                "source_snapshot_ids": dto.source_snapshot_ids,
+            })),
+            affinity_group_ids: Vec::new(),
+            owner_id: vm.owner_id,
+        };
+
+        match self.create_vm(create_dto).await {
+            Ok(response) => {
+                info!("虚拟机 {} 已克隆为 {} ({})", id, response.id, dto.name);
+                Ok(response)
+            }
+            Err(e) => {
+                self.delete_cloned_volumes(&storage_service, &cloned_disks).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// 删除克隆失败时已创建的卷
+    async fn delete_cloned_volumes(&self, storage_service: &StorageService, disks: &[DiskSpec]) {
+        for disk in disks {
+            if let Err(e) = storage_service.delete_volume(&disk.volume_id).await {
+                warn!("清理克隆卷 {} 失败，需要手动清理: {}", disk.volume_id, e);
+            }
+        }
+    }
+
+    /// 在虚拟机内执行命令（通过 qemu-guest-agent）
+    ///
+    /// 等待命令结束后返回退出码和完整输出；执行过程中 Agent 推送的输出片段
+    /// 会实时转发给发起命令的用户
+    pub async fn guest_exec(
+        &self,
+        vm_id: &str,
+        dto: GuestExecDto,
+        user_id: &str,
+    ) -> anyhow::Result<common::ws_rpc::GuestExecResponse> {
+        let db = &self.state.sea_db();
+
+        let vm = VmEntity::find_by_id(vm_id.to_string())
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+
+        if vm.status != VmStatus::Running.as_str() {
+            return Err(anyhow::anyhow!("虚拟机未运行，无法执行命令"));
+        }
+
+        let node_id = vm
+            .node_id
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("虚拟机未关联节点"))?;
+
+        let timeout_secs = dto
+            .timeout_secs
+            .unwrap_or(common::ws_rpc::GuestExecRequest::DEFAULT_TIMEOUT_SECS)
+            .min(common::ws_rpc::GuestExecRequest::MAX_TIMEOUT_SECS);
+        let request = common::ws_rpc::GuestExecRequest {
+            vm_id: vm_id.to_string(),
+            command: dto.command,
+            args: dto.args,
+            timeout_secs: Some(timeout_secs),
+        };
+
+        info!(
+            "用户 {} 在虚拟机 {} 中执行命令: {}",
+            user_id, vm_id, request.command
+        );
+
+        // 转发输出片段到前端
+        let (stream_tx, mut stream_rx) = tokio::sync::mpsc::unbounded_channel();
+        let frontend_manager = self.state.frontend_manager();
+        let target_user = user_id.to_string();
+        let forward_task = tokio::spawn(async move {
+            while let Some(payload) = stream_rx.recv().await {
+                match serde_json::from_value::<common::ws_rpc::GuestExecOutput>(payload) {
+                    Ok(output) => {
+                        let msg = FrontendMessage::GuestExecOutput {
+                            vm_id: output.vm_id,
+                            pid: output.pid,
+                            stdout: output.stdout,
+                            stderr: output.stderr,
+                            exited: output.exited,
+                        };
+                        frontend_manager.send_to_user(&target_user, msg).await;
+                    }
+                    Err(e) => warn!("解析客户机命令输出失败: {}", e),
+                }
+            }
+        });
+
+        // 额外预留时间给 Agent 的轮询与网络往返
+        let result = self
+            .state
+            .agent_manager()
+            .call_streaming(
+                &node_id,
+                "guest_exec",
+                serde_json::to_value(&request)?,
+                std::time::Duration::from_secs(timeout_secs.saturating_add(30)),
+                stream_tx,
+            )
+            .await;
+
+        // 请求结束后订阅被移除，发送端随之释放，转发任务自然结束
+        let _ = forward_task.await;
+
+        let response_msg = result.map_err(|e| anyhow::anyhow!("执行客户机命令失败: {}", e))?;
+        let response: common::ws_rpc::GuestExecResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+
+        Ok(response)
+    }
+
+    /// 附加存储卷到虚拟机
+    ///
+    /// 按照 vms.md 流程：
+    /// API -> Server记录DB -> UI提示进行中
+    /// --(notify)-> agent 热挂载磁盘，并标记持久 --(notify)-> Server更新db记录 -> UI提示完成
+    /// 若虚拟机未开机，则不需要调用agent，仅更新db即可。开机会重新define。
+    pub async fn attach_volume(&self, vm_id: &str, dto: AttachVolumeDto) -> anyhow::Result<()> {
+        let db = &self.state.sea_db();
+        let now = Utc::now();
+
+        // 检查虚拟机是否存在
         let vm = VmEntity::find_by_id(vm_id.to_string())
             .one(db)
             .await?
@@ -707,6 +1946,19 @@ impl VmService {
         if volume.vm_id.is_some() {
             return Err(anyhow::anyhow!("存储卷已被其他虚拟机使用"));
         }
+        let storage_service = StorageService::new(self.state.clone());
+        storage_service.ensure_no_linked_clones(&volume.id, "挂载到虚拟机").await?;
+        let backing = storage_service.backing_store(&volume).await?;
+        let encryption = storage_service.volume_encryption(&volume)?;
+
+        // 未指定总线时使用配置的默认总线，并校验总线与设备类型组合
+        let bus_type = dto.bus_type.clone().unwrap_or_else(|| self.state.default_disk_bus());
+        let device_type = dto.device_type.clone().unwrap_or_default();
+        validate_disk_combination(&bus_type, &device_type).map_err(|e| anyhow::anyhow!(e))?;
+        dto.limits.validate().map_err(|e| anyhow::anyhow!(e))?;
+        if bus_type == DiskBusType::Sata && vm.status == VmStatus::Running.as_str() {
+            return Err(anyhow::anyhow!("SATA 总线不支持热插拔，请先关闭虚拟机再挂载"));
+        }
 
         // 获取当前的磁盘列表
         let mut disks: Vec<DiskSpec> = vm.volumes
@@ -717,8 +1969,11 @@ impl VmService {
         // 添加新磁盘
         disks.push(DiskSpec {
             volume_id: dto.volume_id.clone(),
-            bus_type: dto.bus_type.clone().unwrap_or_default(),
-            device_type: dto.device_type.clone().unwrap_or_default(),
+            bus_type: bus_type.clone(),
+            device_type: device_type.clone(),
+            ephemeral: dto.ephemeral,
+            limits: dto.limits,
+            boot_order: None,
         });
 
         // 更新虚拟机的磁盘列表
@@ -748,13 +2003,17 @@ impl VmService {
                     "vm_id": vm_id,
                     "volume_id": dto.volume_id,
                     "volume_path": volume_path,
-                    "bus_type": dto.bus_type.clone().unwrap_or_default(),
-                    "device_type": dto.device_type.clone().unwrap_or_default(),
-                    "format": volume_type
+                    "bus_type": bus_type,
+                    "device_type": device_type,
+                    "format": volume_type,
+                    "iops_limit": dto.limits.iops_limit,
+                    "bps_limit": dto.limits.bps_limit,
+                    "backing": backing,
+                    "encryption": encryption
                 });
 
                 // 异步通知 Agent，不等待结果
-                self.state.agent_manager()
+                self.state.agent_rpc()
                     .notify(
                         node_id,
                         "attach_volume_async",
@@ -841,7 +2100,7 @@ impl VmService {
                 });
 
                 // 异步通知 Agent，不等待结果
-                self.state.agent_manager()
+                self.state.agent_rpc()
                     .notify(
                         node_id,
                         "detach_volume_async",
@@ -859,6 +2118,534 @@ impl VmService {
         Ok(())
     }
 
+    /// 调整虚拟机磁盘的 I/O 限速
+    ///
+    /// 虚拟机运行中时先同步调用 Agent 在线生效，成功后再写入数据库；
+    /// 未运行时只更新数据库，下次启动 define 时生效
+    pub async fn set_volume_iotune(&self, vm_id: &str, dto: SetVolumeIotuneDto) -> anyhow::Result<()> {
+        dto.limits.validate().map_err(|e| anyhow::anyhow!(e))?;
+
+        let db = &self.state.sea_db();
+        let vm = VmEntity::find_by_id(vm_id.to_string())
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+
+        let mut disks: Vec<DiskSpec> = vm.volumes
+            .as_ref()
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        let disk = disks
+            .iter_mut()
+            .find(|d| d.volume_id == dto.volume_id)
+            .ok_or_else(|| anyhow::anyhow!("存储卷未挂载到此虚拟机"))?;
+        disk.limits = dto.limits;
+
+        if vm.status == VmStatus::Running.as_str() {
+            let node_id = vm.node_id.as_deref().ok_or_else(|| anyhow::anyhow!("虚拟机未关联节点"))?;
+            let request = common::ws_rpc::SetVolumeIotuneRequest {
+                vm_id: vm_id.to_string(),
+                volume_id: dto.volume_id.clone(),
+                limits: dto.limits,
+            };
+            self.state.agent_rpc()
+                .call(
+                    node_id,
+                    "set_volume_iotune",
+                    serde_json::to_value(&request)?,
+                    std::time::Duration::from_secs(30),
+                )
+                .await
+                .map_err(|e| anyhow::anyhow!("在线调整磁盘 I/O 限速失败: {}", e))?;
+            info!("虚拟机 {} 存储卷 {} I/O 限速已在线调整", vm_id, dto.volume_id);
+        }
+
+        let mut vm_active: VmActiveModel = vm.into();
+        vm_active.volumes = Set(Some(serde_json::to_value(&disks)?));
+        vm_active.updated_at = Set(Utc::now().into());
+        vm_active.update(db).await?;
+
+        Ok(())
+    }
+
+    /// 调整虚拟机网卡的带宽限速
+    ///
+    /// 与磁盘 I/O 限速相同：运行中时先同步调用 Agent 在线生效，成功后再写入数据库；
+    /// 未运行时只更新数据库，下次启动 define 时生效
+    pub async fn set_nic_bandwidth(&self, vm_id: &str, dto: SetNicBandwidthDto) -> anyhow::Result<()> {
+        dto.limits.validate().map_err(|e| anyhow::anyhow!(e))?;
+
+        let db = &self.state.sea_db();
+        let vm = VmEntity::find_by_id(vm_id.to_string())
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+
+        let mut interfaces: Vec<NetworkInterfaceSpec> = vm.network_interfaces
+            .as_ref()
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        let interface = interfaces
+            .iter_mut()
+            .find(|i| i.mac_address.as_deref().is_some_and(|mac| mac.eq_ignore_ascii_case(&dto.mac_address)))
+            .ok_or_else(|| anyhow::anyhow!("网卡不存在: {}", dto.mac_address))?;
+        interface.bandwidth = dto.limits;
+
+        if vm.status == VmStatus::Running.as_str() {
+            let node_id = vm.node_id.as_deref().ok_or_else(|| anyhow::anyhow!("虚拟机未关联节点"))?;
+            let request = common::ws_rpc::SetNicBandwidthRequest {
+                vm_id: vm_id.to_string(),
+                mac_address: dto.mac_address.clone(),
+                limits: dto.limits,
+            };
+            self.state.agent_rpc()
+                .call(
+                    node_id,
+                    "set_nic_bandwidth",
+                    serde_json::to_value(&request)?,
+                    std::time::Duration::from_secs(30),
+                )
+                .await
+                .map_err(|e| anyhow::anyhow!("在线调整网卡带宽限速失败: {}", e))?;
+            info!("虚拟机 {} 网卡 {} 带宽限速已在线调整", vm_id, dto.mac_address);
+        }
+
+        let mut vm_active: VmActiveModel = vm.into();
+        vm_active.network_interfaces = Set(Some(serde_json::to_value(&interfaces)?));
+        vm_active.updated_at = Set(Utc::now().into());
+        vm_active.update(db).await?;
+
+        Ok(())
+    }
+
+    /// 通过 qemu-guest-agent 查询客户机内的网卡与 IP 地址
+    ///
+    /// 客户机未安装 guest agent 属于常见情况，返回 guest_agent_available=false 而不是错误
+    pub async fn get_guest_network(&self, vm_id: &str) -> anyhow::Result<GuestNetworkResponse> {
+        let vm = VmEntity::find_by_id(vm_id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+
+        if vm.status != VmStatus::Running.as_str() {
+            return Err(anyhow::anyhow!("虚拟机未运行，无法查询客户机网络"));
+        }
+        let node_id = vm.node_id.as_deref().ok_or_else(|| anyhow::anyhow!("虚拟机未关联节点"))?;
+
+        let request = common::ws_rpc::GetGuestNetworkRequest {
+            vm_id: vm_id.to_string(),
+        };
+        let result = self
+            .state
+            .agent_rpc()
+            .call(
+                node_id,
+                "get_guest_network",
+                serde_json::to_value(&request)?,
+                std::time::Duration::from_secs(30),
+            )
+            .await;
+
+        let checked_at = Utc::now().to_rfc3339();
+        let response_msg = match result {
+            Ok(msg) => msg,
+            Err(e) if e.code == common::ws_rpc::RpcErrorCode::GuestAgentUnavailable => {
+                debug!("虚拟机 {} 的 guest agent 不可用: {}", vm_id, e.message);
+                return Ok(GuestNetworkResponse {
+                    vm_id: vm_id.to_string(),
+                    guest_agent_available: false,
+                    interfaces: Vec::new(),
+                    checked_at,
+                });
+            }
+            Err(e) => return Err(anyhow::anyhow!("查询客户机网络失败: {}", e)),
+        };
+
+        let response: common::ws_rpc::GetGuestNetworkResponse = serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?;
+
+        Ok(GuestNetworkResponse {
+            vm_id: response.vm_id,
+            guest_agent_available: true,
+            interfaces: response.interfaces,
+            checked_at,
+        })
+    }
+
+    /// 读取虚拟机在所在节点 libvirt 中的域 XML，用于排查配置
+    pub async fn get_domain_xml(&self, vm_id: &str) -> anyhow::Result<common::ws_rpc::GetDomainXmlResponse> {
+        let vm = VmEntity::find_by_id(vm_id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+        let node_id = vm.node_id.as_deref().ok_or_else(|| anyhow::anyhow!("虚拟机未关联节点"))?;
+
+        let request = common::ws_rpc::GetDomainXmlRequest {
+            vm_id: vm_id.to_string(),
+        };
+        let response_msg = self
+            .state
+            .agent_rpc()
+            .call(
+                node_id,
+                "get_domain_xml",
+                serde_json::to_value(&request)?,
+                std::time::Duration::from_secs(30),
+            )
+            .await
+            .map_err(|e| match e.code {
+                common::ws_rpc::RpcErrorCode::VmNotFound => {
+                    anyhow::anyhow!("虚拟机未在节点上定义，请先启动虚拟机")
+                }
+                _ => anyhow::anyhow!("读取域 XML 失败: {}", e),
+            })?;
+
+        Ok(serde_json::from_value(
+            response_msg
+                .payload
+                .ok_or_else(|| anyhow::anyhow!("响应无数据"))?,
+        )?)
+    }
+
+    /// 批量启动、停止、重启或删除虚拟机
+    ///
+    /// 每个虚拟机分别调用对应的单个操作并做相同的校验（如运行中的虚拟机不能删除），
+    /// 最多同时执行 BATCH_CONCURRENCY 个；单个失败只记录在结果中，不影响其他虚拟机
+    pub async fn batch_operation(&self, dto: BatchVmOperationDto) -> anyhow::Result<BatchVmOperationResponse> {
+        use futures::StreamExt;
+
+        let mut vm_ids = dto.vm_ids;
+        let mut seen = std::collections::HashSet::new();
+        vm_ids.retain(|id| seen.insert(id.clone()));
+        if vm_ids.is_empty() {
+            return Err(anyhow::anyhow!("虚拟机列表不能为空"));
+        }
+        if vm_ids.len() > MAX_BATCH_SIZE {
+            return Err(anyhow::anyhow!("单次最多操作 {} 台虚拟机", MAX_BATCH_SIZE));
+        }
+
+        let action = dto.action;
+        let force = dto.force;
+        let results: Vec<BatchVmResult> = futures::stream::iter(vm_ids)
+            .map(|vm_id| async move {
+                let result = match action {
+                    BatchVmAction::Start => self.start_vm(&vm_id, false, None).await.map(Some),
+                    BatchVmAction::Stop => self.stop_vm(&vm_id, force).await.map(Some),
+                    BatchVmAction::Restart => self.restart_vm(&vm_id).await.map(Some),
+                    BatchVmAction::Delete => self.delete_vm(&vm_id).await.map(|_| None),
+                };
+                match result {
+                    Ok(task_id) => BatchVmResult { vm_id, success: true, task_id, error: None },
+                    Err(e) => {
+                        warn!("批量操作 {:?} 虚拟机 {} 失败: {}", action, vm_id, e);
+                        BatchVmResult { vm_id, success: false, task_id: None, error: Some(e.to_string()) }
+                    }
+                }
+            })
+            .buffered(BATCH_CONCURRENCY)
+            .collect()
+            .await;
+
+        let succeeded = results.iter().filter(|r| r.success).count();
+        info!("批量操作 {:?}: {} 台成功，{} 台失败", action, succeeded, results.len() - succeeded);
+        Ok(BatchVmOperationResponse {
+            action,
+            succeeded,
+            failed: results.len() - succeeded,
+            results,
+        })
+    }
+
+    /// 虚拟机所有者或拥有 vm:console 权限的用户才能访问控制台
+    pub async fn can_access_console(&self, vm_id: &str, user_id: i32) -> anyhow::Result<bool> {
+        let db = self.state.sea_db();
+        let vm = VmEntity::find_by_id(vm_id.to_string())
+            .one(&db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+        if vm.owner_id == Some(user_id) {
+            return Ok(true);
+        }
+
+        Ok(RbacService::check_permission(&db, user_id, "vm", "console").await?)
+    }
+
+    /// 申请控制台访问
+    ///
+    /// 为运行中的虚拟机生成一次性控制台密码并下发到所在节点，密码在
+    /// CONSOLE_PASSWORD_VALID_SECS 秒后失效，已建立的控制台连接不受影响
+    pub async fn request_console_access(&self, vm_id: &str, username: &str) -> anyhow::Result<ConsoleAccessResponse> {
+        let vm = VmEntity::find_by_id(vm_id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+        if vm.status != VmStatus::Running.as_str() {
+            return Err(anyhow::anyhow!("虚拟机未运行"));
+        }
+        if vm.vnc_port.is_none() {
+            return Err(anyhow::anyhow!("虚拟机没有可用的控制台"));
+        }
+        let node_id = vm.node_id.as_deref().ok_or_else(|| anyhow::anyhow!("虚拟机未关联节点"))?;
+
+        let password = Self::generate_console_password();
+        let request = common::ws_rpc::SetConsolePasswordRequest {
+            vm_id: vm_id.to_string(),
+            password: password.clone(),
+            valid_secs: Some(CONSOLE_PASSWORD_VALID_SECS as u64),
+        };
+        self.state
+            .agent_rpc()
+            .call(
+                node_id,
+                "set_console_password",
+                serde_json::to_value(&request)?,
+                std::time::Duration::from_secs(30),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("设置控制台密码失败: {}", e))?;
+
+        info!("用户 {} 申请虚拟机 {} 的控制台访问", username, vm_id);
+        let expires_at = Utc::now() + chrono::Duration::seconds(CONSOLE_PASSWORD_VALID_SECS);
+        Ok(ConsoleAccessResponse {
+            vm_id: vm_id.to_string(),
+            // 旧版本 Agent 不上报协议，只可能是 VNC
+            protocol: vm.console_protocol.unwrap_or_else(|| "vnc".to_string()),
+            url: format!("/ws/vnc/{}", vm_id),
+            password,
+            expires_at: expires_at.to_rfc3339(),
+        })
+    }
+
+    /// 获取虚拟机实时状态
+    ///
+    /// 直接向所在节点查询 libvirt，节点离线或查询失败时回退到数据库记录并标记为过期
+    pub async fn get_vm_live_state(&self, vm_id: &str) -> anyhow::Result<VmLiveStateResponse> {
+        let vm = VmEntity::find_by_id(vm_id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
+
+        let now = Utc::now();
+        if let Some(node_id) = &vm.node_id {
+            match self.query_agent_vm_state(node_id, vm_id).await {
+                Ok(live) if live.exists => {
+                    return Ok(VmLiveStateResponse {
+                        vm_id: vm.id.clone(),
+                        node_id: vm.node_id.clone(),
+                        status: domain_state_to_status(&live.state).as_str().to_string(),
+                        domain_state: Some(live.state),
+                        vcpu: live.vcpu as i64,
+                        memory_mb: live.memory_mb as i64,
+                        uptime_secs: live.uptime_secs,
+                        stale: false,
+                        source: "agent".to_string(),
+                        checked_at: now.to_rfc3339(),
+                    });
+                }
+                // 节点上没有定义该虚拟机，说明它确实未运行
+                Ok(live) => {
+                    return Ok(VmLiveStateResponse {
+                        vm_id: vm.id.clone(),
+                        node_id: vm.node_id.clone(),
+                        status: VmStatus::Stopped.as_str().to_string(),
+                        domain_state: Some(live.state),
+                        vcpu: vm.vcpu as i64,
+                        memory_mb: vm.memory_mb,
+                        uptime_secs: None,
+                        stale: false,
+                        source: "agent".to_string(),
+                        checked_at: now.to_rfc3339(),
+                    });
+                }
+                Err(e) => {
+                    warn!("查询虚拟机 {} 实时状态失败，回退到数据库记录: {}", vm_id, e);
+                }
+            }
+        }
+
+        // 回退：以数据库记录为准，运行时长按 started_at 估算
+        let uptime_secs = if vm.status == VmStatus::Running.as_str() {
+            vm.started_at
+                .map(|started| (now - started.with_timezone(&Utc)).num_seconds().max(0) as u64)
+        } else {
+            None
+        };
+
+        Ok(VmLiveStateResponse {
+            vm_id: vm.id,
+            node_id: vm.node_id,
+            status: vm.status,
+            domain_state: None,
+            vcpu: vm.vcpu as i64,
+            memory_mb: vm.memory_mb,
+            uptime_secs,
+            stale: true,
+            source: "database".to_string(),
+            checked_at: now.to_rfc3339(),
+        })
+    }
+
+    async fn query_agent_vm_state(
+        &self,
+        node_id: &str,
+        vm_id: &str,
+    ) -> anyhow::Result<common::ws_rpc::types::GetVmStateResponse> {
+        let agent_rpc = self.state.agent_rpc();
+        if !agent_rpc.is_online(node_id).await {
+            return Err(anyhow::anyhow!("节点 {} 不在线", node_id));
+        }
+
+        let request = common::ws_rpc::types::GetVmStateRequest {
+            vm_id: vm_id.to_string(),
+        };
+        let response = agent_rpc
+            .call(
+                node_id,
+                "get_vm_state",
+                serde_json::to_value(&request)?,
+                std::time::Duration::from_secs(10),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("WebSocket RPC 调用失败: {}", e))?;
+
+        let payload = response
+            .payload
+            .ok_or_else(|| anyhow::anyhow!("响应无数据"))?;
+        Ok(serde_json::from_value(payload)?)
+    }
+
+    /// 按 libvirt 中的实际状态纠正数据库中的虚拟机状态，返回纠正的虚拟机数量
+    ///
+    /// 只处理 running/stopped/paused/error 这些稳定状态，starting、migrating 等过渡状态
+    /// 由对应操作的完成通知更新；节点上未定义的虚拟机视为已停止
+    pub async fn reconcile_vm_states(&self) -> anyhow::Result<usize> {
+        let db = &self.state.sea_db();
+        let stable_statuses = [
+            VmStatus::Running,
+            VmStatus::Stopped,
+            VmStatus::Paused,
+            VmStatus::Error,
+        ]
+        .map(|status| status.as_str());
+
+        let node_ids: std::collections::BTreeSet<String> = VmEntity::find()
+            .filter(VmColumn::Status.is_in(stable_statuses))
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|vm| vm.node_id)
+            .collect();
+
+        let agent_rpc = self.state.agent_rpc();
+        let mut corrected = 0;
+        for node_id in node_ids {
+            if !agent_rpc.is_online(&node_id).await {
+                continue;
+            }
+
+            let domains = match self.query_agent_vm_list(&node_id).await {
+                Ok(response) => response.vms,
+                Err(e) => {
+                    warn!("状态对账: 查询节点 {} 的虚拟机列表失败: {}", node_id, e);
+                    continue;
+                }
+            };
+            let domain_states: std::collections::HashMap<String, String> = domains
+                .into_iter()
+                .map(|domain| (domain.vm_id, domain.state))
+                .collect();
+
+            // 在拿到 Agent 结果之后再读数据库，缩小与其它操作交错的窗口
+            let vms = VmEntity::find()
+                .filter(VmColumn::NodeId.eq(node_id.clone()))
+                .filter(VmColumn::Status.is_in(stable_statuses))
+                .all(db)
+                .await?;
+
+            let now: sea_orm::prelude::DateTimeWithTimeZone = Utc::now().into();
+            for vm in vms {
+                let actual = domain_states
+                    .get(&vm.id)
+                    .map(|state| domain_state_to_status(state))
+                    .unwrap_or(VmStatus::Stopped);
+                if vm.status == actual.as_str() {
+                    continue;
+                }
+
+                // 仅在状态未被其它操作改动时更新
+                let result = VmEntity::update_many()
+                    .col_expr(VmColumn::Status, Expr::value(actual.as_str()))
+                    .col_expr(VmColumn::UpdatedAt, Expr::value(now))
+                    .filter(VmColumn::Id.eq(vm.id.clone()))
+                    .filter(VmColumn::Status.eq(vm.status.clone()))
+                    .exec(db)
+                    .await?;
+                if result.rows_affected == 0 {
+                    continue;
+                }
+
+                warn!(
+                    "状态对账: 虚拟机 {} 数据库状态为 {}，节点 {} 实际为 {:?}，已纠正为 {}",
+                    vm.id,
+                    vm.status,
+                    node_id,
+                    domain_states.get(&vm.id),
+                    actual.as_str()
+                );
+                self.notify_vm_status_update(&vm.id, actual.as_str(), Some("已按节点实际状态纠正"))
+                    .await;
+                corrected += 1;
+            }
+        }
+
+        Ok(corrected)
+    }
+
+    /// 启动虚拟机状态定期对账任务
+    pub fn start_vm_state_reconciler(state: AppState, check_interval_secs: u64) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(check_interval_secs));
+
+            loop {
+                interval.tick().await;
+
+                match VmService::new(state.clone()).reconcile_vm_states().await {
+                    Ok(0) => {}
+                    Ok(corrected) => info!("虚拟机状态对账: 已纠正 {} 台虚拟机", corrected),
+                    Err(e) => error!("虚拟机状态对账失败: {}", e),
+                }
+            }
+        });
+    }
+
+    async fn query_agent_vm_list(
+        &self,
+        node_id: &str,
+    ) -> anyhow::Result<common::ws_rpc::types::ListVmsResponse> {
+        let request = common::ws_rpc::types::ListVmsRequest {
+            node_id: node_id.to_string(),
+        };
+        let response = self
+            .state
+            .agent_rpc()
+            .call(
+                node_id,
+                "list_vms",
+                serde_json::to_value(&request)?,
+                std::time::Duration::from_secs(30),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("WebSocket RPC 调用失败: {}", e))?;
+
+        let payload = response
+            .payload
+            .ok_or_else(|| anyhow::anyhow!("响应无数据"))?;
+        Ok(serde_json::from_value(payload)?)
+    }
+
     /// 获取虚拟机的所有存储卷
     pub async fn list_vm_volumes(&self, vm_id: &str) -> anyhow::Result<Vec<VmDiskResponse>> {
         let db = &self.state.sea_db();
@@ -878,15 +2665,8 @@ impl VmService {
         let mut result = Vec::new();
 
         for (idx, disk) in disks.iter().enumerate() {
-            // 自动生成设备名
-            let device_name = match disk.device_type {
-                common::ws_rpc::types::DiskDeviceType::Disk => {
-                    format!("vd{}", (b'a' + idx as u8) as char)
-                }
-                common::ws_rpc::types::DiskDeviceType::Cdrom => {
-                    format!("hd{}", (b'a' + idx as u8) as char)
-                }
-            };
+            // 自动生成设备名，与 Agent 生成的域 XML 保持一致
+            let device_name = disk_device_name(&disk.bus_type, &disk.device_type, idx);
 
             // 查询volume详细信息
             if let Some(volume) = VolumeEntity::find_by_id(&disk.volume_id).one(db).await? {
@@ -896,6 +2676,8 @@ impl VmService {
                     bootable: idx == 0, // 第一个磁盘默认为启动盘
                     bus_type: disk.bus_type.clone(),
                     device_type: disk.device_type.clone(),
+                    ephemeral: disk.ephemeral,
+                    limits: disk.limits,
                     volume_name: Some(volume.name),
                     size_gb: Some(volume.size_gb),
                     volume_type: Some(volume.volume_type),
@@ -909,6 +2691,8 @@ impl VmService {
                     bootable: idx == 0, // 第一个磁盘默认为启动盘
                     bus_type: disk.bus_type.clone(),
                     device_type: disk.device_type.clone(),
+                    ephemeral: disk.ephemeral,
+                    limits: disk.limits,
                     volume_name: None,
                     size_gb: None,
                     volume_type: None,
@@ -938,33 +2722,48 @@ impl VmService {
 
         let mut result = Vec::new();
 
-        for interface in network_interfaces {
+        // 同一网络可能对应多块网卡，以 index（网卡在配置中的顺序）区分
+        for (index, interface) in network_interfaces.into_iter().enumerate() {
             // 查询网络详细信息
             if let Some(network) = NetworkEntity::find_by_id(&interface.network_id).one(db).await? {
                 let network_info = serde_json::json!({
+                    "index": index,
                     "network_id": interface.network_id,
                     "network_name": network.name,
                     "ip_address": interface.ip_address,
+                    "ipv6_address": interface.ipv6_address,
                     "mac_address": interface.mac_address,
                     "model": interface.model,
                     "bridge_name": interface.bridge_name,
+                    "inbound_kbps": interface.bandwidth.inbound_kbps,
+                    "outbound_kbps": interface.bandwidth.outbound_kbps,
+                    "security_group_id": effective_group_id(&interface, &network),
                     "network_type": network.network_type,
                     "cidr": network.cidr,
-                    "vlan_id": network.vlan_id
+                    "cidr_v6": network.cidr_v6,
+                    "vlan_id": network.vlan_id,
+                    "mtu": network.mtu
                 });
                 result.push(network_info);
             } else {
                 // 网络不存在，返回基本信息
                 let network_info = serde_json::json!({
+                    "index": index,
                     "network_id": interface.network_id,
                     "network_name": "未知网络",
                     "ip_address": interface.ip_address,
+                    "ipv6_address": interface.ipv6_address,
                     "mac_address": interface.mac_address,
                     "model": interface.model,
                     "bridge_name": interface.bridge_name,
+                    "inbound_kbps": interface.bandwidth.inbound_kbps,
+                    "outbound_kbps": interface.bandwidth.outbound_kbps,
+                    "security_group_id": interface.security_group_id,
                     "network_type": null,
                     "cidr": null,
-                    "vlan_id": null
+                    "cidr_v6": null,
+                    "vlan_id": null,
+                    "mtu": null
                 });
                 result.push(network_info);
             }
@@ -974,7 +2773,17 @@ impl VmService {
     }
 
     /// 处理 Agent 的虚拟机操作完成通知
-    pub async fn handle_vm_operation_completed(&self, vm_id: &str, operation: &str, success: bool, message: &str) -> anyhow::Result<()> {
+    ///
+    /// 启动/重启成功时 Agent 会附带 libvirt 实际分配的 VNC 端口
+    pub async fn handle_vm_operation_completed(
+        &self,
+        vm_id: &str,
+        operation: &str,
+        success: bool,
+        message: &str,
+        vnc: Option<VncInfo>,
+        task_id: Option<&str>,
+    ) -> anyhow::Result<()> {
         let db = &self.state.sea_db();
         let now = Utc::now();
 
@@ -984,14 +2793,26 @@ impl VmService {
             .await?
             .ok_or_else(|| anyhow::anyhow!("虚拟机不存在"))?;
 
+        let vnc_host = match &vnc {
+            Some(vnc) => self.resolve_vnc_host(vm.node_id.as_deref(), vnc).await,
+            None => None,
+        };
+        let console_protocol = vnc.as_ref().map(|vnc| vnc.protocol.as_str().to_string());
+        let vnc_port = vnc.map(|vnc| vnc.port as i32);
+
         let mut vm_active: VmActiveModel = vm.into();
 
         match operation {
             "start_vm" => {
+                vm_active.vnc_port = Set(vnc_port);
+                vm_active.vnc_host = Set(vnc_host);
+                vm_active.console_protocol = Set(console_protocol);
                 if success {
                     vm_active.status = Set(VmStatus::Running.as_str().to_string());
                     vm_active.started_at = Set(Some(now.into()));
-                    self.notify_vm_status_update(vm_id, "running", Some("虚拟机启动成功")).await;
+                    // Agent 检测到 IP 冲突等告警时会附带在消息中
+                    let message = if message.is_empty() { "虚拟机启动成功" } else { message };
+                    self.notify_vm_status_update(vm_id, "running", Some(message)).await;
                 } else {
                     vm_active.status = Set(VmStatus::Stopped.as_str().to_string());
                     self.notify_vm_status_update(vm_id, "stopped", Some(&format!("虚拟机启动失败: {}", message))).await;
@@ -1001,6 +2822,9 @@ impl VmService {
                 if success {
                     vm_active.status = Set(VmStatus::Stopped.as_str().to_string());
                     vm_active.stopped_at = Set(Some(now.into()));
+                    vm_active.vnc_port = Set(None);
+                    vm_active.vnc_host = Set(None);
+                    vm_active.console_protocol = Set(None);
                     self.notify_vm_status_update(vm_id, "stopped", Some("虚拟机停止成功")).await;
                 } else {
                     // 停止失败，保持当前状态
@@ -1008,6 +2832,9 @@ impl VmService {
                 }
             }
             "restart_vm" => {
+                vm_active.vnc_port = Set(vnc_port);
+                vm_active.vnc_host = Set(vnc_host);
+                vm_active.console_protocol = Set(console_protocol);
                 if success {
                     vm_active.status = Set(VmStatus::Running.as_str().to_string());
                     vm_active.started_at = Set(Some(now.into()));
@@ -1041,27 +2868,51 @@ impl VmService {
         vm_active.update(db).await?;
 
         info!("虚拟机 {} 操作 {} 完成: success={}, message={}", vm_id, operation, success, message);
+
+        // 任务状态只用于查询进度，更新失败不影响虚拟机状态
+        if let Err(e) = TaskService::new(self.state.clone())
+            .complete_vm_operation(task_id, vm_id, operation, success, message)
+            .await
+        {
+            warn!("更新虚拟机 {} 操作 {} 的任务状态失败: {}", vm_id, operation, e);
+        }
+        AuditService::new(self.state.clone())
+            .record_system("vms", vm_id, operation, success, message)
+            .await;
         Ok(())
     }
 
     /// 处理虚拟机迁移进度通知
     pub async fn handle_vm_migration_progress(
         &self,
-        vm_id: &str,
-        stage: &str,
-        progress_percent: f64,
-        message: &str,
-        completed: bool,
-        error: Option<&str>,
+        progress: &MigrationProgress,
     ) -> anyhow::Result<()> {
         let db = &self.state.sea_db();
         let now = Utc::now();
+        let vm_id = progress.vm_id.as_str();
+        let stage = progress.stage.as_str();
+        let progress_percent = progress.progress_percent;
+        let message = progress.message.as_str();
+        let completed = progress.completed;
+        let error = progress.error.as_deref();
 
         info!(
             "虚拟机迁移进度: vm_id={}, stage={}, progress={}%, completed={}, message={}",
             vm_id, stage, progress_percent, completed, message
         );
 
+        self.state
+            .frontend_manager()
+            .broadcast(FrontendMessage::MigrationProgress {
+                vm_id: vm_id.to_string(),
+                stage: stage.to_string(),
+                progress_percent,
+                message: message.to_string(),
+                completed,
+                remaining_secs: progress.remaining_secs,
+            })
+            .await;
+
         // 查询虚拟机信息
         let vm = VmEntity::find_by_id(vm_id.to_string())
             .one(db)
@@ -1087,9 +2938,28 @@ impl VmService {
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false);
 
+            let storage_mode = vm
+                .metadata
+                .as_ref()
+                .and_then(|m| m.get("migration_storage_mode"))
+                .and_then(|v| v.as_str())
+                .and_then(|s| s.parse::<MigrationStorageMode>().ok())
+                .unwrap_or_default();
+
             if let Some(error_msg) = error {
                 // 迁移失败
                 error!("虚拟机迁移失败: vm_id={}, error={}", vm_id, error_msg);
+
+                // 复制存储迁移失败时目标节点上的磁盘副本已无用
+                if let (MigrationStorageMode::CopyStorage, Some(target_node)) =
+                    (storage_mode, target_node_id.as_deref())
+                {
+                    self.remove_migration_target_volumes(
+                        &Self::migration_copy_volume_ids(&vm),
+                        target_node,
+                    )
+                    .await;
+                }
                 
                 // 恢复原状态（保持原节点）
                 // 如果是热迁移失败，虚拟机可能还在原节点运行
@@ -1106,6 +2976,11 @@ impl VmService {
             } else {
                 // 迁移成功
                 info!("虚拟机迁移成功: vm_id={}, target_node={:?}", vm_id, target_node_id);
+
+                // 原节点上的 VNC 端口已失效，目标节点的端口在下次启动时上报
+                vm_active.vnc_port = Set(None);
+                vm_active.vnc_host = Set(None);
+                vm_active.console_protocol = Set(None);
                 
                 // 更新节点ID到目标节点
                 if let Some(target_node) = target_node_id {
@@ -1115,10 +2990,14 @@ impl VmService {
                 
                 // 更新虚拟机状态
                 if is_live {
-                    // 热迁移成功，虚拟机在目标节点运行
+                    // 热迁移成功，虚拟机在目标节点运行（降级为挂起迁移时也由目标节点恢复运行）
                     vm_active.status = Set(VmStatus::Running.as_str().to_string());
                     vm_active.started_at = Set(Some(now.into()));
-                    self.notify_vm_status_update(vm_id, "running", Some("虚拟机热迁移完成")).await;
+                    let status_message = match progress.migration_mode {
+                        Some(MigrationMode::Suspended) => "虚拟机迁移完成（热迁移超时，已降级为挂起迁移）",
+                        _ => "虚拟机热迁移完成",
+                    };
+                    self.notify_vm_status_update(vm_id, "running", Some(status_message)).await;
                 } else {
                     // 冷迁移成功，虚拟机在目标节点但处于停止状态
                     vm_active.status = Set(VmStatus::Stopped.as_str().to_string());
@@ -1131,22 +3010,46 @@ impl VmService {
             if let Some(obj) = metadata.as_object_mut() {
                 obj.remove("migration_target_node_id");
                 obj.remove("migration_is_live");
+                obj.remove("migration_storage_mode");
             }
             vm_active.metadata = Set(Some(metadata));
             vm_active.updated_at = Set(now.into());
             
             vm_active.update(db).await?;
         } else {
-            // 迁移进行中，只记录日志，不更新状态（状态仍然是 migrating）
+            // 迁移进行中，进度已推送给前端，不更新状态（状态仍然是 migrating）
             debug!("虚拟机迁移进行中: vm_id={}, stage={}, progress={}%", vm_id, stage, progress_percent);
-            
-            // 可选：发送进度通知给前端
-            // 这里可以根据需要添加前端通知逻辑
         }
 
         Ok(())
     }
 
+    /// VNC 访问地址：监听所有地址时使用节点 IP
+    async fn resolve_vnc_host(&self, node_id: Option<&str>, vnc: &VncInfo) -> Option<String> {
+        if !vnc.listens_on_all() {
+            return Some(vnc.listen.clone());
+        }
+
+        match NodeEntity::find_by_id(node_id?.to_string())
+            .one(&self.state.sea_db())
+            .await
+        {
+            Ok(node) => node.map(|node| node.ip_address),
+            Err(e) => {
+                warn!("查询节点 {:?} 失败，无法确定 VNC 地址: {}", node_id, e);
+                None
+            }
+        }
+    }
+
+    /// 生成控制台密码
+    ///
+    /// VNC 认证只使用密码的前 8 个字符，更长的密码没有意义
+    fn generate_console_password() -> String {
+        use rand::distributions::{Alphanumeric, DistString};
+        Alphanumeric.sample_string(&mut rand::thread_rng(), 8)
+    }
+
     /// 生成 MAC 地址
     /// 使用标准的 VM MAC 地址前缀 52:54:00（QEMU/KVM 使用的前缀）
     fn generate_mac_address() -> String {
@@ -1160,3 +3063,72 @@ impl VmService {
         )
     }
 }
+
+/// 解析以 JSON 保存的配置列，缺失或格式不符时为 None
+fn parse_json<T: serde::de::DeserializeOwned>(value: &Option<serde_json::Value>) -> Option<T> {
+    value.as_ref().and_then(|v| serde_json::from_value(v.clone()).ok())
+}
+
+/// 引导顺序从 1 开始且不能重复
+fn validate_boot_order(disks: &[DiskSpec]) -> anyhow::Result<()> {
+    let mut seen = std::collections::HashSet::new();
+    for disk in disks {
+        if let Some(order) = disk.boot_order {
+            if order == 0 {
+                return Err(anyhow::anyhow!("存储卷 {}: 引导顺序必须从 1 开始", disk.volume_id));
+            }
+            if !seen.insert(order) {
+                return Err(anyhow::anyhow!("引导顺序 {} 重复", order));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 本次启动下发给 Agent 的引导顺序（存储卷 ID -> 顺序）
+///
+/// boot_from 指定的存储卷排在最前，其余按保存的 boot_order 排列；都未设置时保持
+/// 从第一块磁盘引导，安装系统后重启仍能回到系统盘。未指定 boot_from 且未设置
+/// 引导顺序时返回空表，由固件按默认顺序引导
+fn boot_orders(disks: &[DiskSpec], boot_from: Option<&str>) -> anyhow::Result<std::collections::HashMap<String, u32>> {
+    let mut ordered: Vec<&DiskSpec> = disks.iter().filter(|d| d.boot_order.is_some()).collect();
+    ordered.sort_by_key(|d| d.boot_order);
+
+    if let Some(boot_from) = boot_from {
+        let first = disks
+            .iter()
+            .find(|d| d.volume_id == boot_from)
+            .ok_or_else(|| anyhow::anyhow!("引导设备 {} 未挂载到虚拟机", boot_from))?;
+        if ordered.is_empty() {
+            ordered.extend(disks.iter().find(|d| d.device_type == common::ws_rpc::types::DiskDeviceType::Disk));
+        }
+        ordered.retain(|d| d.volume_id != boot_from);
+        ordered.insert(0, first);
+    }
+
+    Ok(ordered
+        .into_iter()
+        .zip(1..)
+        .map(|(disk, order)| (disk.volume_id.clone(), order))
+        .collect())
+}
+
+/// 源节点 libvirtd 连接目标节点使用的 URI，节点间需配置 SSH 免密登录
+fn migration_uri(address: &str) -> String {
+    if address.contains(':') {
+        format!("qemu+ssh://[{}]/system", address)
+    } else {
+        format!("qemu+ssh://{}/system", address)
+    }
+}
+
+/// libvirt 域状态映射为平台的虚拟机状态
+pub(crate) fn domain_state_to_status(state: &str) -> VmStatus {
+    match state {
+        // shutdown 表示正在关机，进程仍在运行
+        "running" | "blocked" | "shutdown" => VmStatus::Running,
+        "paused" | "pmsuspended" => VmStatus::Paused,
+        "shutoff" => VmStatus::Stopped,
+        _ => VmStatus::Error,
+    }
+}
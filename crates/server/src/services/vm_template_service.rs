@@ -0,0 +1,250 @@
+/// 虚拟机模板服务
+///
+/// 模板保存虚拟机规格与系统盘的基础镜像。从模板创建虚拟机时先以基础镜像为 backing file
+/// 创建 qcow2 链接克隆作为系统盘，再按模板规格（叠加调用方覆盖的字段）创建虚拟机
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use common::ws_rpc::types::{DiskDeviceType, DiskIoLimits};
+use sea_orm::{ActiveModelTrait, EntityTrait, QueryOrder, Set};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::db::models::network::Entity as NetworkEntity;
+use crate::db::models::vm::{CreateVmDto, DiskSpec, NetworkInterfaceSpec, VmResponse};
+use crate::db::models::vm_template::{
+    ActiveModel as VmTemplateActiveModel, Column as VmTemplateColumn, CreateVmFromTemplateDto,
+    CreateVmTemplateDto, Entity as VmTemplateEntity, Model as VmTemplateModel,
+    UpdateVmTemplateDto, VmTemplateResponse, VmTemplateSpec,
+};
+use crate::db::models::volume::{CreateVolumeDto, Entity as VolumeEntity, Model as VolumeModel};
+use crate::services::storage_service::StorageService;
+use crate::services::vm_service::VmService;
+
+pub struct VmTemplateService {
+    state: AppState,
+}
+
+impl VmTemplateService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// 创建虚拟机模板
+    pub async fn create_template(&self, dto: CreateVmTemplateDto) -> Result<VmTemplateResponse> {
+        self.check_references(&dto.base_volume_id, &dto.spec).await?;
+
+        let now = Utc::now();
+        let template = VmTemplateActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            name: Set(dto.name),
+            description: Set(dto.description),
+            base_volume_id: Set(dto.base_volume_id),
+            spec: Set(serde_json::to_value(&dto.spec)?),
+            created_at: Set(now.into()),
+            updated_at: Set(now.into()),
+        }
+        .insert(&self.state.sea_db())
+        .await?;
+
+        info!("创建虚拟机模板 {} ({})", template.name, template.id);
+        Ok(template.into())
+    }
+
+    /// 获取虚拟机模板列表
+    pub async fn list_templates(&self) -> Result<Vec<VmTemplateResponse>> {
+        let templates = VmTemplateEntity::find()
+            .order_by_asc(VmTemplateColumn::Name)
+            .all(&self.state.sea_db())
+            .await?;
+        Ok(templates.into_iter().map(VmTemplateResponse::from).collect())
+    }
+
+    /// 获取虚拟机模板详情
+    pub async fn get_template(&self, id: &str) -> Result<VmTemplateResponse> {
+        Ok(self.find(id).await?.into())
+    }
+
+    /// 更新虚拟机模板
+    pub async fn update_template(&self, id: &str, dto: UpdateVmTemplateDto) -> Result<VmTemplateResponse> {
+        let template = self.find(id).await?;
+
+        if dto.base_volume_id.is_some() || dto.spec.is_some() {
+            let spec = match &dto.spec {
+                Some(spec) => spec.clone(),
+                None => parse_spec(&template)?,
+            };
+            let base_volume_id = dto.base_volume_id.as_deref().unwrap_or(&template.base_volume_id);
+            self.check_references(base_volume_id, &spec).await?;
+        }
+
+        let mut active: VmTemplateActiveModel = template.into();
+        if let Some(name) = dto.name {
+            active.name = Set(name);
+        }
+        if let Some(description) = dto.description {
+            active.description = Set(Some(description));
+        }
+        if let Some(base_volume_id) = dto.base_volume_id {
+            active.base_volume_id = Set(base_volume_id);
+        }
+        if let Some(spec) = dto.spec {
+            active.spec = Set(serde_json::to_value(&spec)?);
+        }
+        active.updated_at = Set(Utc::now().into());
+
+        let template = active.update(&self.state.sea_db()).await?;
+        Ok(template.into())
+    }
+
+    /// 删除虚拟机模板（已创建的虚拟机与基础镜像保留）
+    pub async fn delete_template(&self, id: &str) -> Result<()> {
+        let result = VmTemplateEntity::delete_by_id(id.to_string())
+            .exec(&self.state.sea_db())
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(anyhow!("虚拟机模板不存在"));
+        }
+        Ok(())
+    }
+
+    /// 从模板创建虚拟机
+    ///
+    /// 系统盘为基础镜像的链接克隆，与基础镜像位于同一存储池，随虚拟机一同删除；
+    /// 创建虚拟机失败时删除已创建的系统盘
+    pub async fn create_vm(&self, template_id: &str, dto: CreateVmFromTemplateDto) -> Result<VmResponse> {
+        if dto.name.is_empty() {
+            return Err(anyhow!("虚拟机名称不能为空"));
+        }
+
+        let template = self.find(template_id).await?;
+        let spec = parse_spec(&template)?;
+        // 模板创建后基础镜像或网络可能已被删除
+        let base = self.check_references(&template.base_volume_id, &spec).await?;
+
+        let storage_service = StorageService::new(self.state.clone());
+        let root = storage_service
+            .create_volume(CreateVolumeDto {
+                name: format!("{}-root", dto.name),
+                pool_id: base.pool_id.clone(),
+                size_gb: spec.root_disk_size_gb.unwrap_or(base.size_gb),
+                volume_type: "qcow2".to_string(),
+                source: None,
+                source_headers: None,
+                source_auth: None,
+                compress: false,
+                checksum: None,
+                backing_volume_id: Some(base.id.clone()),
+                encryption: None,
+                metadata: Some(serde_json::json!({ "template_id": template.id })),
+                owner_id: dto.owner_id,
+            })
+            .await
+            .map_err(|e| anyhow!("创建系统盘失败: {}", e))?;
+
+        let networks: Vec<NetworkInterfaceSpec> = spec
+            .networks
+            .into_iter()
+            .map(|interface| NetworkInterfaceSpec {
+                mac_address: None,
+                ip_address: None,
+                ipv6_address: None,
+                bridge_name: None,
+                ..interface
+            })
+            .collect();
+
+        let mut metadata = spec.metadata.unwrap_or_else(|| serde_json::json!({}));
+        if let Some(obj) = metadata.as_object_mut() {
+            obj.insert("template_id".to_string(), serde_json::json!(template.id));
+        }
+
+        let create_dto = CreateVmDto {
+            name: dto.name.clone(),
+            node_id: dto.node_id,
+            vcpu: dto.vcpu.unwrap_or(spec.vcpu),
+            memory_mb: dto.memory_mb.unwrap_or(spec.memory_mb),
+            os_type: spec.os_type,
+            firmware: spec.firmware,
+            tpm: spec.tpm,
+            boot_menu: spec.boot_menu,
+            cpu_pinning: None,
+            numa: None,
+            raw_xml_override: None,
+            arch: spec.arch,
+            machine_type: spec.machine_type,
+            graphics: spec.graphics,
+            disks: Some(vec![DiskSpec {
+                volume_id: root.id.clone(),
+                bus_type: spec.root_disk_bus.unwrap_or_else(|| self.state.default_disk_bus()),
+                device_type: DiskDeviceType::Disk,
+                ephemeral: true,
+                limits: DiskIoLimits::default(),
+                boot_order: Some(1),
+            }]),
+            networks: Some(networks).filter(|n| !n.is_empty()),
+            cloud_init: dto.cloud_init.or(spec.cloud_init),
+            metadata: Some(metadata),
+            affinity_group_ids: spec.affinity_group_ids,
+            owner_id: dto.owner_id,
+        };
+
+        match VmService::new(self.state.clone()).create_vm(create_dto).await {
+            Ok(vm) => {
+                info!("从模板 {} 创建虚拟机 {} ({})", template.name, vm.name, vm.id);
+                Ok(vm)
+            }
+            Err(e) => {
+                if let Err(err) = storage_service.delete_volume(&root.id).await {
+                    warn!("清理模板系统盘 {} 失败，需要手动清理: {}", root.id, err);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// 校验模板引用的基础镜像与网络存在，返回基础镜像存储卷
+    async fn check_references(&self, base_volume_id: &str, spec: &VmTemplateSpec) -> Result<VolumeModel> {
+        let db = &self.state.sea_db();
+        let base = VolumeEntity::find_by_id(base_volume_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow!("基础镜像存储卷 {} 不存在", base_volume_id))?;
+        if base.volume_type != "qcow2" {
+            return Err(anyhow!("基础镜像存储卷必须为 qcow2 格式"));
+        }
+        if base.backing_volume_id.is_some() || base.is_encrypted() {
+            return Err(anyhow!("链接克隆或加密的存储卷不能作为基础镜像"));
+        }
+        if let Some(size_gb) = spec.root_disk_size_gb {
+            if size_gb < base.size_gb {
+                return Err(anyhow!(
+                    "系统盘容量（{}GB）不能小于基础镜像（{}GB）",
+                    size_gb,
+                    base.size_gb
+                ));
+            }
+        }
+
+        for network in &spec.networks {
+            NetworkEntity::find_by_id(&network.network_id)
+                .one(db)
+                .await?
+                .ok_or_else(|| anyhow!("网络 {} 不存在", network.network_id))?;
+        }
+        Ok(base)
+    }
+
+    async fn find(&self, id: &str) -> Result<VmTemplateModel> {
+        VmTemplateEntity::find_by_id(id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow!("虚拟机模板不存在"))
+    }
+}
+
+fn parse_spec(template: &VmTemplateModel) -> Result<VmTemplateSpec> {
+    serde_json::from_value(template.spec.clone())
+        .map_err(|e| anyhow!("虚拟机模板 {} 的规格无效: {}", template.name, e))
+}
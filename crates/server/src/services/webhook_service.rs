@@ -0,0 +1,330 @@
+/// Webhook 管理与事件投递服务
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::db::models::webhook::{
+    ActiveModel as WebhookActiveModel, Column as WebhookColumn, CreateWebhookDto,
+    Entity as WebhookEntity, Model as WebhookModel, UpdateWebhookDto, WebhookEvent,
+    WebhookEventType, WebhookResponse,
+};
+
+/// 所有投递共享的 HTTP 客户端（复用连接池）
+static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(reqwest::Client::new);
+
+/// 首次重试前的等待时间，之后每次翻倍
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+pub struct WebhookService {
+    state: AppState,
+}
+
+impl WebhookService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// 创建 Webhook
+    pub async fn create_webhook(&self, dto: CreateWebhookDto) -> anyhow::Result<WebhookResponse> {
+        validate_events(&dto.events)?;
+
+        let now = Utc::now();
+        let webhook = WebhookActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            name: Set(dto.name),
+            url: Set(dto.url),
+            secret: Set(dto.secret.filter(|s| !s.is_empty())),
+            events: Set(serde_json::to_value(&dto.events)?),
+            enabled: Set(dto.enabled),
+            last_status: Set(None),
+            last_error: Set(None),
+            last_delivered_at: Set(None),
+            created_at: Set(now.into()),
+            updated_at: Set(now.into()),
+        }
+        .insert(&self.state.sea_db())
+        .await?;
+
+        info!("创建 Webhook {} -> {} ({:?})", webhook.name, webhook.url, dto.events);
+        Ok(WebhookResponse::from(webhook))
+    }
+
+    /// 获取 Webhook 列表
+    pub async fn list_webhooks(&self) -> anyhow::Result<Vec<WebhookResponse>> {
+        let webhooks = WebhookEntity::find()
+            .order_by_asc(WebhookColumn::Name)
+            .all(&self.state.sea_db())
+            .await?;
+        Ok(webhooks.into_iter().map(WebhookResponse::from).collect())
+    }
+
+    /// 获取 Webhook 详情
+    pub async fn get_webhook(&self, id: &str) -> anyhow::Result<WebhookResponse> {
+        Ok(WebhookResponse::from(self.find(id).await?))
+    }
+
+    /// 更新 Webhook
+    pub async fn update_webhook(
+        &self,
+        id: &str,
+        dto: UpdateWebhookDto,
+    ) -> anyhow::Result<WebhookResponse> {
+        let webhook = self.find(id).await?;
+        let mut active: WebhookActiveModel = webhook.into();
+
+        if let Some(name) = dto.name {
+            active.name = Set(name);
+        }
+        if let Some(url) = dto.url {
+            active.url = Set(url);
+        }
+        if let Some(secret) = dto.secret {
+            active.secret = Set(Some(secret).filter(|s| !s.is_empty()));
+        }
+        if let Some(events) = dto.events {
+            validate_events(&events)?;
+            active.events = Set(serde_json::to_value(&events)?);
+        }
+        if let Some(enabled) = dto.enabled {
+            active.enabled = Set(enabled);
+        }
+        active.updated_at = Set(Utc::now().into());
+
+        let webhook = active.update(&self.state.sea_db()).await?;
+        Ok(WebhookResponse::from(webhook))
+    }
+
+    /// 删除 Webhook
+    pub async fn delete_webhook(&self, id: &str) -> anyhow::Result<()> {
+        let result = WebhookEntity::delete_by_id(id.to_string())
+            .exec(&self.state.sea_db())
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(anyhow::anyhow!("Webhook 不存在"));
+        }
+        Ok(())
+    }
+
+    /// 向指定 Webhook 发送测试事件并等待投递结果（不重试）
+    pub async fn send_test(&self, id: &str) -> anyhow::Result<WebhookResponse> {
+        let webhook = self.find(id).await?;
+        let event = WebhookEvent::new(
+            WebhookEventType::Test,
+            "webhook",
+            Some(&webhook.id),
+            "Easy VM Cloud Webhook 测试事件",
+            serde_json::json!({}),
+        );
+
+        let timeout = Duration::from_secs(self.state.webhook_settings().timeout_secs);
+        let result = post_event(&webhook, &event, timeout).await;
+        let updated = record_delivery(&self.state, &webhook.id, result).await?;
+        Ok(WebhookResponse::from(updated))
+    }
+
+    /// 投递事件到所有订阅了该事件的已启用 Webhook
+    ///
+    /// 在后台任务中完成，不阻塞调用方；失败按指数退避重试
+    pub fn emit(&self, event: WebhookEvent) {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let webhooks = match WebhookEntity::find()
+                .filter(WebhookColumn::Enabled.eq(true))
+                .all(&state.sea_db())
+                .await
+            {
+                Ok(webhooks) => webhooks,
+                Err(e) => {
+                    warn!("查询 Webhook 失败，事件 {} 未投递: {}", event.event.as_str(), e);
+                    return;
+                }
+            };
+
+            for webhook in webhooks.into_iter().filter(|w| w.subscribes(event.event)) {
+                tokio::spawn(deliver(state.clone(), webhook, event.clone()));
+            }
+        });
+    }
+
+    async fn find(&self, id: &str) -> anyhow::Result<WebhookModel> {
+        WebhookEntity::find_by_id(id.to_string())
+            .one(&self.state.sea_db())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Webhook 不存在"))
+    }
+}
+
+/// 校验订阅的事件类型
+fn validate_events(events: &[String]) -> anyhow::Result<()> {
+    if events.is_empty() {
+        return Err(anyhow::anyhow!("至少需要订阅一个事件类型"));
+    }
+    if let Some(unknown) = events
+        .iter()
+        .find(|e| !WebhookEventType::is_valid_subscription(e))
+    {
+        return Err(anyhow::anyhow!("未知的事件类型: {}", unknown));
+    }
+    Ok(())
+}
+
+/// 第 attempt 次重试前的等待时间（attempt 从 1 开始）
+fn retry_delay(attempt: u32) -> Duration {
+    RETRY_BASE_DELAY * 2u32.saturating_pow(attempt.saturating_sub(1))
+}
+
+/// 投递单个事件，失败按指数退避重试，最终结果记录到 Webhook
+async fn deliver(state: AppState, webhook: WebhookModel, event: WebhookEvent) {
+    let settings = state.webhook_settings();
+    let timeout = Duration::from_secs(settings.timeout_secs);
+
+    let mut attempt = 0;
+    let result = loop {
+        let result = post_event(&webhook, &event, timeout).await;
+        if result.is_ok() || attempt >= settings.max_retries {
+            break result;
+        }
+
+        attempt += 1;
+        let delay = retry_delay(attempt);
+        warn!(
+            "Webhook {} 投递事件 {} 失败，{:?} 后第 {} 次重试: {}",
+            webhook.name,
+            event.id,
+            delay,
+            attempt,
+            result.as_ref().unwrap_err()
+        );
+        tokio::time::sleep(delay).await;
+    };
+
+    match &result {
+        Ok(()) => info!("Webhook {} 已投递事件 {} ({})", webhook.name, event.id, event.event.as_str()),
+        Err(e) => warn!("Webhook {} 投递事件 {} 最终失败: {}", webhook.name, event.id, e),
+    }
+
+    if let Err(e) = record_delivery(&state, &webhook.id, result).await {
+        warn!("记录 Webhook {} 投递结果失败: {}", webhook.name, e);
+    }
+}
+
+/// 发送一次 POST 请求，非 2xx 响应视为失败
+async fn post_event(
+    webhook: &WebhookModel,
+    event: &WebhookEvent,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let mut request = HTTP_CLIENT
+        .post(&webhook.url)
+        .timeout(timeout)
+        .header("X-Webhook-Event", event.event.as_str())
+        .header("X-Webhook-Delivery", &event.id)
+        .json(event);
+    if let Some(secret) = &webhook.secret {
+        request = request.header("X-Webhook-Secret", secret);
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("请求失败: {}", e))?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!("接收方返回 {}", response.status()));
+    }
+    Ok(())
+}
+
+/// 记录最近一次投递结果
+async fn record_delivery(
+    state: &AppState,
+    webhook_id: &str,
+    result: anyhow::Result<()>,
+) -> anyhow::Result<WebhookModel> {
+    let db = state.sea_db();
+    let webhook = WebhookEntity::find_by_id(webhook_id.to_string())
+        .one(&db)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Webhook 不存在"))?;
+
+    let mut active: WebhookActiveModel = webhook.into();
+    match result {
+        Ok(()) => {
+            active.last_status = Set(Some("success".to_string()));
+            active.last_error = Set(None);
+        }
+        Err(e) => {
+            active.last_status = Set(Some("failed".to_string()));
+            active.last_error = Set(Some(e.to_string()));
+        }
+    }
+    active.last_delivered_at = Set(Some(Utc::now().into()));
+    Ok(active.update(&db).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook(events: serde_json::Value) -> WebhookModel {
+        let now = Utc::now();
+        WebhookModel {
+            id: "w1".to_string(),
+            name: "ops".to_string(),
+            url: "http://example.com/hook".to_string(),
+            secret: None,
+            events,
+            enabled: true,
+            last_status: None,
+            last_error: None,
+            last_delivered_at: None,
+            created_at: now.into(),
+            updated_at: now.into(),
+        }
+    }
+
+    #[test]
+    fn test_subscribes() {
+        let hook = webhook(serde_json::json!(["node.offline", "cluster.memory_high"]));
+        assert!(hook.subscribes(WebhookEventType::NodeOffline));
+        assert!(hook.subscribes(WebhookEventType::ClusterMemoryHigh));
+        assert!(!hook.subscribes(WebhookEventType::NodeOnline));
+
+        let all = webhook(serde_json::json!(["*"]));
+        assert!(all.subscribes(WebhookEventType::NodeMetricAlert));
+    }
+
+    #[test]
+    fn test_validate_events() {
+        assert!(validate_events(&["node.offline".to_string(), "*".to_string()]).is_ok());
+        assert!(validate_events(&[]).is_err());
+        assert!(validate_events(&["node.exploded".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_retry_delay_doubles() {
+        assert_eq!(retry_delay(1), Duration::from_secs(1));
+        assert_eq!(retry_delay(2), Duration::from_secs(2));
+        assert_eq!(retry_delay(4), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_event_schema() {
+        let event = WebhookEvent::new(
+            WebhookEventType::NodeMetricAlert,
+            "node",
+            Some("n1"),
+            "内存利用率过高",
+            serde_json::json!({ "metric": "memory", "value": 91.5, "threshold": 90.0 }),
+        );
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "node.metric_alert");
+        assert_eq!(json["resource_type"], "node");
+        assert_eq!(json["resource_id"], "n1");
+        assert_eq!(json["data"]["metric"], "memory");
+    }
+}
@@ -2,7 +2,8 @@
 /// 
 /// 负责管理所有 Agent 的 WebSocket 连接
 
-use common::ws_rpc::{RpcMessage, RpcError, RpcErrorCode};
+use common::utils::redact_payload;
+use common::ws_rpc::{CancelRequest, RpcMessage, RpcError, RpcErrorCode, RpcStream, StreamFrame};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
@@ -28,9 +29,15 @@ pub struct AgentConnection {
     
     /// 最后心跳时间
     pub last_heartbeat: Arc<RwLock<std::time::Instant>>,
+
+    /// Agent 最近一次上报的 ping 往返时延（毫秒）
+    pub last_rtt_ms: Arc<RwLock<Option<u64>>>,
     
     /// 等待响应的请求 Map: request_id -> response_sender
     pending_requests: Arc<RwLock<HashMap<String, PendingRequest>>>,
+
+    /// 流式数据订阅 Map: request_id -> stream_sender
+    stream_listeners: Arc<RwLock<HashMap<String, mpsc::UnboundedSender<StreamFrame>>>>,
 }
 
 impl AgentConnection {
@@ -41,14 +48,25 @@ impl AgentConnection {
         payload: serde_json::Value,
         timeout: Duration,
     ) -> Result<RpcMessage, RpcError> {
-        let method_str = method.into();
-        let msg = RpcMessage::request(&method_str, payload.clone());
+        let msg = RpcMessage::request(method, payload);
+        self.send_request(msg, timeout).await
+    }
+
+    /// 发送已构造的请求消息并等待响应
+    async fn send_request(
+        &self,
+        msg: RpcMessage,
+        timeout: Duration,
+    ) -> Result<RpcMessage, RpcError> {
         let request_id = msg.id.clone();
         
         // 📤 打印发送的请求
         info!("📤 [Server -> Agent] 发送请求: node={}, method={}, id={}", 
-              self.node_id, method_str, request_id);
-        debug!("📤 请求内容: {}", serde_json::to_string_pretty(&payload).unwrap_or_default());
+              self.node_id, msg.method.as_deref().unwrap_or_default(), request_id);
+        if let Some(ref payload) = msg.payload {
+            // 请求中可能包含下载源的认证信息，脱敏后再输出
+            debug!("📤 请求内容: {}", serde_json::to_string_pretty(&redact_payload(payload)).unwrap_or_default());
+        }
         
         // 创建响应接收器
         let (tx, rx) = oneshot::channel();
@@ -77,14 +95,78 @@ impl AgentConnection {
                 Err(RpcError::new(RpcErrorCode::InternalError, "响应通道被关闭"))
             }
             Err(_) => {
-                // 超时，移除待处理请求
-                let mut pending = self.pending_requests.write().await;
-                pending.remove(&request_id);
+                // 超时，移除待处理请求并通知 Agent 中止处理，迟到的响应将被忽略
+                self.pending_requests.write().await.remove(&request_id);
+                let cancel = CancelRequest {
+                    request_id: request_id.clone(),
+                };
+                if let Ok(payload) = serde_json::to_value(&cancel) {
+                    let _ = self.sender.send(RpcMessage::notification("cancel", payload));
+                }
                 Err(RpcError::timeout(format!("请求超时: {}", request_id)))
             }
         }
     }
     
+    /// 发送 RPC 请求，以流的形式返回 Agent 推送的中间结果与最终响应
+    ///
+    /// Agent 发送的 Stream 消息 id 与请求 id 相同；WebSocket 按序投递，
+    /// 所有中间结果都先于最终响应进入流，timeout 限制的是整个请求
+    pub async fn open_stream(
+        self: &Arc<Self>,
+        method: impl Into<String>,
+        payload: serde_json::Value,
+        timeout: Duration,
+    ) -> RpcStream {
+        let msg = RpcMessage::request(method, payload);
+        let request_id = msg.id.clone();
+        let (frames, stream) = RpcStream::channel();
+
+        {
+            let mut listeners = self.stream_listeners.write().await;
+            listeners.insert(request_id.clone(), frames.clone());
+        }
+
+        let connection = self.clone();
+        tokio::spawn(async move {
+            let result = connection.send_request(msg, timeout).await;
+            connection.stream_listeners.write().await.remove(&request_id);
+            let _ = frames.send(StreamFrame::End(result));
+        });
+
+        stream
+    }
+
+    /// 发送 RPC 请求并等待响应，期间 Agent 推送的流式数据转发到 stream_tx
+    pub async fn call_streaming(
+        self: &Arc<Self>,
+        method: impl Into<String>,
+        payload: serde_json::Value,
+        timeout: Duration,
+        stream_tx: mpsc::UnboundedSender<serde_json::Value>,
+    ) -> Result<RpcMessage, RpcError> {
+        self.open_stream(method, payload, timeout)
+            .await
+            .for_each_data(|payload| {
+                let _ = stream_tx.send(payload);
+            })
+            .await
+    }
+
+    /// 处理收到的流式消息（由 WebSocket handler 调用）
+    pub async fn handle_stream(&self, msg: RpcMessage) {
+        let listeners = self.stream_listeners.read().await;
+        match (listeners.get(&msg.id), msg.payload) {
+            (Some(tx), Some(payload)) => {
+                if tx.send(StreamFrame::Data(payload)).is_err() {
+                    debug!("流式数据订阅者已关闭: {}", msg.id);
+                }
+            }
+            (None, _) => debug!("收到未订阅的流式消息: {}", msg.id),
+            _ => {}
+        }
+    }
+
     /// 处理收到的响应消息（由 WebSocket handler 调用）
     pub async fn handle_response(&self, response: RpcMessage) {
         let request_id = response.id.clone();
@@ -112,6 +194,10 @@ impl AgentConnection {
             let result = if let Some(error_info) = response.error {
                 // 将错误代码字符串转换回 RpcErrorCode
                 let error_code = match error_info.code.as_str() {
+                    "GUEST_AGENT_UNAVAILABLE" => RpcErrorCode::GuestAgentUnavailable,
+                    "CHECKSUM_MISMATCH" => RpcErrorCode::ChecksumMismatch,
+                    "TOOL_NOT_FOUND" => RpcErrorCode::ToolNotFound,
+                    "TIMEOUT" => RpcErrorCode::Timeout,
                     code if code.starts_with("VM_") => RpcErrorCode::VmOperationFailed,
                     code if code.starts_with("VOLUME_") => RpcErrorCode::StorageError,
                     code if code.starts_with("NETWORK_") => RpcErrorCode::NetworkError,
@@ -155,6 +241,17 @@ impl AgentConnection {
         let last_heartbeat = self.last_heartbeat.read().await;
         last_heartbeat.elapsed().as_secs()
     }
+
+    /// 记录 Agent 上报的往返时延
+    pub async fn update_rtt(&self, rtt_ms: u64) {
+        let mut last_rtt_ms = self.last_rtt_ms.write().await;
+        *last_rtt_ms = Some(rtt_ms);
+    }
+
+    /// 获取最近一次往返时延（毫秒），尚未上报时为 None
+    pub async fn rtt_ms(&self) -> Option<u64> {
+        *self.last_rtt_ms.read().await
+    }
 }
 
 /// Agent 连接管理器
@@ -186,7 +283,9 @@ impl AgentConnectionManager {
             ip_address,
             sender,
             last_heartbeat: Arc::new(RwLock::new(std::time::Instant::now())),
+            last_rtt_ms: Arc::new(RwLock::new(None)),
             pending_requests: Arc::new(RwLock::new(HashMap::new())),
+            stream_listeners: Arc::new(RwLock::new(HashMap::new())),
         });
 
         let mut connections = self.connections.write().await;
@@ -269,6 +368,35 @@ impl AgentConnectionManager {
         connection.call(method, payload, timeout).await
     }
 
+    /// 向指定节点发送 RPC 请求，返回包含中间结果与最终响应的流
+    pub async fn open_stream(
+        &self,
+        node_id: &str,
+        method: impl Into<String>,
+        payload: serde_json::Value,
+        timeout: Duration,
+    ) -> Result<RpcStream, RpcError> {
+        let connection = self.get(node_id).await
+            .ok_or_else(|| RpcError::node_not_found(node_id))?;
+
+        Ok(connection.open_stream(method, payload, timeout).await)
+    }
+
+    /// 向指定节点发送 RPC 请求，并订阅该请求的流式数据
+    pub async fn call_streaming(
+        &self,
+        node_id: &str,
+        method: impl Into<String>,
+        payload: serde_json::Value,
+        timeout: Duration,
+        stream_tx: mpsc::UnboundedSender<serde_json::Value>,
+    ) -> Result<RpcMessage, RpcError> {
+        let connection = self.get(node_id).await
+            .ok_or_else(|| RpcError::node_not_found(node_id))?;
+
+        connection.call_streaming(method, payload, timeout, stream_tx).await
+    }
+
     /// 向指定节点发送通知
     pub async fn notify(
         &self,
@@ -363,3 +491,73 @@ impl Default for AgentConnectionManager {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_open_stream_yields_frames_then_response() {
+        let manager = AgentConnectionManager::new();
+        let (sender, mut agent) = mpsc::unbounded_channel();
+        let connection = manager
+            .register("node-1".to_string(), "compute-1".to_string(), "10.0.0.11".to_string(), sender)
+            .await;
+
+        let mut stream = manager
+            .open_stream("node-1", "create_volume", json!({}), Duration::from_secs(5))
+            .await
+            .unwrap();
+        let request = agent.recv().await.unwrap();
+        assert_eq!(request.method.as_deref(), Some("create_volume"));
+
+        connection
+            .handle_stream(RpcMessage::stream(request.id.clone(), json!({"progress_percent": 40.0})))
+            .await;
+        connection
+            .handle_response(RpcMessage::response(request.id.clone(), json!({"success": true})))
+            .await;
+
+        match stream.next().await {
+            Some(StreamFrame::Data(payload)) => assert_eq!(payload["progress_percent"], 40.0),
+            other => panic!("unexpected frame: {:?}", other),
+        }
+        match stream.next().await {
+            Some(StreamFrame::End(Ok(response))) => assert_eq!(response.id, request.id),
+            other => panic!("unexpected frame: {:?}", other),
+        }
+        assert!(stream.next().await.is_none());
+
+        // 请求结束后订阅被移除，迟到的流式消息被忽略
+        assert!(connection.stream_listeners.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_call_timeout_sends_cancel_and_drops_pending_request() {
+        let manager = AgentConnectionManager::new();
+        let (sender, mut agent) = mpsc::unbounded_channel();
+        let connection = manager
+            .register("node-1".to_string(), "compute-1".to_string(), "10.0.0.11".to_string(), sender)
+            .await;
+
+        let err = connection
+            .call("clone_volume", json!({}), Duration::from_millis(20))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, RpcErrorCode::Timeout);
+
+        let request = agent.recv().await.unwrap();
+        let cancel = agent.recv().await.unwrap();
+        assert_eq!(cancel.method.as_deref(), Some("cancel"));
+        assert_eq!(cancel.payload.unwrap()["request_id"], request.id);
+        assert!(connection.pending_requests.read().await.is_empty());
+
+        // 迟到的响应不会影响后续请求
+        connection
+            .handle_response(RpcMessage::response(request.id, json!({"success": true})))
+            .await;
+        assert!(connection.pending_requests.read().await.is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+/// Agent RPC 抽象
+///
+/// 服务层通过该 trait 调用 Agent，生产环境由 AgentConnectionManager 实现，
+/// 测试中可替换为返回预设响应的 MockAgentRpc
+
+use async_trait::async_trait;
+use common::ws_rpc::{RpcError, RpcMessage, RpcStream};
+use std::time::Duration;
+
+use super::AgentConnectionManager;
+
+#[async_trait]
+pub trait AgentRpc: Send + Sync {
+    /// 向指定节点发送 RPC 请求并等待响应
+    async fn call(
+        &self,
+        node_id: &str,
+        method: &str,
+        payload: serde_json::Value,
+        timeout: Duration,
+    ) -> Result<RpcMessage, RpcError>;
+
+    /// 向指定节点发送通知（不等待响应）
+    async fn notify(
+        &self,
+        node_id: &str,
+        method: &str,
+        payload: serde_json::Value,
+    ) -> Result<(), RpcError>;
+
+    /// 节点是否在线
+    async fn is_online(&self, node_id: &str) -> bool;
+
+    /// 向指定节点发送请求，以流的形式接收中间结果，流以最终响应结束
+    ///
+    /// 默认实现没有中间结果，等待 call 返回后直接结束
+    async fn open_stream(
+        &self,
+        node_id: &str,
+        method: &str,
+        payload: serde_json::Value,
+        timeout: Duration,
+    ) -> Result<RpcStream, RpcError> {
+        Ok(RpcStream::completed(
+            self.call(node_id, method, payload, timeout).await,
+        ))
+    }
+}
+
+#[async_trait]
+impl AgentRpc for AgentConnectionManager {
+    async fn call(
+        &self,
+        node_id: &str,
+        method: &str,
+        payload: serde_json::Value,
+        timeout: Duration,
+    ) -> Result<RpcMessage, RpcError> {
+        AgentConnectionManager::call(self, node_id, method, payload, timeout).await
+    }
+
+    async fn notify(
+        &self,
+        node_id: &str,
+        method: &str,
+        payload: serde_json::Value,
+    ) -> Result<(), RpcError> {
+        AgentConnectionManager::notify(self, node_id, method, payload).await
+    }
+
+    async fn is_online(&self, node_id: &str) -> bool {
+        AgentConnectionManager::is_online(self, node_id).await
+    }
+
+    async fn open_stream(
+        &self,
+        node_id: &str,
+        method: &str,
+        payload: serde_json::Value,
+        timeout: Duration,
+    ) -> Result<RpcStream, RpcError> {
+        AgentConnectionManager::open_stream(self, node_id, method, payload, timeout).await
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use common::ws_rpc::StreamFrame;
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::Mutex;
+
+    /// 记录的一次调用（call 或 notify）
+    #[derive(Debug, Clone)]
+    pub struct RecordedCall {
+        pub node_id: String,
+        pub method: String,
+        pub payload: serde_json::Value,
+    }
+
+    /// 按方法名返回预设响应的 Agent，未预设的 call 返回 method_not_found
+    #[derive(Default)]
+    pub struct MockAgentRpc {
+        responses: Mutex<HashMap<String, VecDeque<Result<serde_json::Value, RpcError>>>>,
+        stream_frames: Mutex<HashMap<String, Vec<serde_json::Value>>>,
+        calls: Mutex<Vec<RecordedCall>>,
+        notifications: Mutex<Vec<RecordedCall>>,
+        offline: bool,
+    }
+
+    impl MockAgentRpc {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// 所有节点均离线：call/notify 返回 node_offline
+        pub fn offline() -> Self {
+            Self {
+                offline: true,
+                ..Self::default()
+            }
+        }
+
+        /// 为方法追加一次成功响应
+        pub fn respond(self, method: &str, payload: serde_json::Value) -> Self {
+            self.push(method, Ok(payload));
+            self
+        }
+
+        /// 为方法追加一次 RPC 错误
+        pub fn fail(self, method: &str, error: RpcError) -> Self {
+            self.push(method, Err(error));
+            self
+        }
+
+        /// 为方法追加一次响应，响应内容依赖测试中途生成的 ID 时直接在共享的实例上调用
+        pub fn push(&self, method: &str, response: Result<serde_json::Value, RpcError>) {
+            self.responses
+                .lock()
+                .unwrap()
+                .entry(method.to_string())
+                .or_default()
+                .push_back(response);
+        }
+
+        /// 为方法预设 open_stream 在最终响应前推送的中间结果
+        pub fn push_stream(&self, method: &str, frames: Vec<serde_json::Value>) {
+            self.stream_frames
+                .lock()
+                .unwrap()
+                .insert(method.to_string(), frames);
+        }
+
+        /// 已发生的 call 记录
+        pub fn calls(&self) -> Vec<RecordedCall> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        /// 已发生的 notify 记录
+        pub fn notifications(&self) -> Vec<RecordedCall> {
+            self.notifications.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl AgentRpc for MockAgentRpc {
+        async fn call(
+            &self,
+            node_id: &str,
+            method: &str,
+            payload: serde_json::Value,
+            _timeout: Duration,
+        ) -> Result<RpcMessage, RpcError> {
+            self.calls.lock().unwrap().push(RecordedCall {
+                node_id: node_id.to_string(),
+                method: method.to_string(),
+                payload,
+            });
+
+            if self.offline {
+                return Err(RpcError::node_offline(node_id));
+            }
+
+            let response = self
+                .responses
+                .lock()
+                .unwrap()
+                .get_mut(method)
+                .and_then(|queue| queue.pop_front())
+                .unwrap_or_else(|| Err(RpcError::method_not_found(method)));
+
+            response.map(|payload| RpcMessage::response("mock", payload))
+        }
+
+        async fn notify(
+            &self,
+            node_id: &str,
+            method: &str,
+            payload: serde_json::Value,
+        ) -> Result<(), RpcError> {
+            if self.offline {
+                return Err(RpcError::node_offline(node_id));
+            }
+
+            self.notifications.lock().unwrap().push(RecordedCall {
+                node_id: node_id.to_string(),
+                method: method.to_string(),
+                payload,
+            });
+            Ok(())
+        }
+
+        async fn is_online(&self, _node_id: &str) -> bool {
+            !self.offline
+        }
+
+        async fn open_stream(
+            &self,
+            node_id: &str,
+            method: &str,
+            payload: serde_json::Value,
+            timeout: Duration,
+        ) -> Result<RpcStream, RpcError> {
+            let result = self.call(node_id, method, payload, timeout).await;
+            let frames = self.stream_frames.lock().unwrap().remove(method);
+
+            let (tx, stream) = RpcStream::channel();
+            for frame in frames.unwrap_or_default() {
+                let _ = tx.send(StreamFrame::Data(frame));
+            }
+            let _ = tx.send(StreamFrame::End(result));
+            Ok(stream)
+        }
+    }
+}
@@ -44,6 +44,15 @@ pub enum FrontendMessage {
         status: String,
         message: Option<String>,
     },
+    /// 节点告警（资源利用率越过阈值或恢复）
+    NodeAlert {
+        node_id: String,
+        metric: String, // cpu, memory, disk
+        value: f64,
+        threshold: f64,
+        resolved: bool,
+        message: String,
+    },
     /// 快照状态更新
     SnapshotStatusUpdate {
         snapshot_id: String,
@@ -57,6 +66,46 @@ pub enum FrontendMessage {
         progress: Option<i32>,
         message: Option<String>,
     },
+    /// 虚拟机迁移进度，完成（成功或失败）时 completed 为 true
+    MigrationProgress {
+        vm_id: String,
+        stage: String,
+        progress_percent: f64,
+        message: String,
+        completed: bool,
+        remaining_secs: Option<u64>,
+    },
+    /// 从 URL 创建存储卷的进度，下载阶段附带字节数（总大小未知时 total_bytes 为空）
+    VolumeProgress {
+        volume_id: String,
+        stage: String,
+        progress_percent: f64,
+        downloaded_bytes: Option<u64>,
+        total_bytes: Option<u64>,
+    },
+    /// 存储卷状态更新（creating、available、error、deleted），size_gb 为变更后的容量
+    VolumeStatusUpdate {
+        volume_id: String,
+        status: String,
+        size_gb: Option<i64>,
+        message: Option<String>,
+    },
+    /// 网络 IP 分配变化，action 为 allocated 或 released
+    NetworkIpUpdate {
+        network_id: String,
+        ip_address: String,
+        action: String,
+        vm_id: Option<String>,
+        mac_address: Option<String>,
+    },
+    /// 客户机命令输出（仅发送给发起命令的用户）
+    GuestExecOutput {
+        vm_id: String,
+        pid: i64,
+        stdout: String,
+        stderr: String,
+        exited: bool,
+    },
     /// 系统通知
     SystemNotification {
         title: String,
@@ -124,6 +173,8 @@ impl FrontendConnectionManager {
     }
 
     /// 向所有连接广播消息
+    ///
+    /// 每个连接使用无界通道，发送不会等待客户端读取，慢速客户端不会阻塞调用方
     pub async fn broadcast(&self, message: FrontendMessage) -> usize {
         let connections = self.connections.read().await;
         let mut count = 0;
@@ -2,12 +2,15 @@
 ///
 /// 处理与 Agent 的 WebSocket 连接和消息
 use super::AgentConnectionManager;
+use crate::services::node_metrics_service::NodeMetricsService;
 use crate::services::node_service::NodeService;
-use axum::extract::ws::{Message as AxumWsMessage, WebSocket};
+use axum::extract::ws::{close_code, CloseFrame, Message as AxumWsMessage, WebSocket};
 use axum::extract::{State, WebSocketUpgrade};
 use axum::response::IntoResponse;
 use common::ws_rpc::{
-    MessageType, NodeResourceInfo, RegisterRequest, RegisterResponse, RpcMessage,
+    EncodedFrame, MessageType, MigrationMode, MigrationProgress, NodeMetricsSample,
+    NodeResourceInfo, PingRequest, PongResponse, RegisterRequest, RegisterResponse, RpcCodec,
+    RpcMessage,
 };
 use futures_util::{SinkExt, StreamExt};
 use tokio::sync::mpsc;
@@ -36,20 +39,32 @@ async fn handle_agent_connection(
     let (tx, mut rx) = mpsc::unbounded_channel::<RpcMessage>();
 
     // 等待注册消息
-    let (node_id, hostname, ip_address) =
+    let (node_id, hostname, ip_address, codec, compression) =
         match wait_for_registration(&mut ws_receiver, &state).await {
             Ok(info) => info,
-            Err(e) => {
+            Err(RegistrationError::Unauthorized(node_id)) => {
+                warn!("Agent 注册认证失败，拒绝连接: node_id={}", node_id);
+                let _ = ws_sender
+                    .send(AxumWsMessage::Close(Some(CloseFrame {
+                        code: close_code::POLICY,
+                        reason: "注册认证失败".into(),
+                    })))
+                    .await;
+                return;
+            }
+            Err(RegistrationError::Invalid(e)) => {
                 error!("Agent 注册失败: {}", e);
                 let _ = ws_sender.close().await;
                 return;
             }
         };
 
-    // 发送注册成功响应
+    // 发送注册成功响应，Server 支持所有编码，直接采用 Agent 声明的编码
     let register_response = RegisterResponse {
         success: true,
         message: "注册成功".to_string(),
+        codec,
+        compression: true,
     };
 
     let response_msg = RpcMessage::response(
@@ -57,7 +72,8 @@ async fn handle_agent_connection(
         serde_json::to_value(&register_response).unwrap(),
     );
 
-    if let Err(e) = send_message(&mut ws_sender, response_msg).await {
+    // 注册响应仍使用 JSON，旧版本 Agent 才能解析
+    if let Err(e) = send_message(&mut ws_sender, response_msg, RpcCodec::Json, None).await {
         error!("发送注册响应失败: {}", e);
         return;
     }
@@ -73,14 +89,20 @@ async fn handle_agent_connection(
         .await;
 
     info!(
-        "Agent 已连接并注册: node_id={}, hostname={}, ip={}",
-        node_id, hostname, ip_address
+        "Agent 已连接并注册: node_id={}, hostname={}, ip={}, codec={}",
+        node_id,
+        hostname,
+        ip_address,
+        codec.as_str()
     );
 
+    // 旧版本 Agent 无法解析压缩帧，只向声明支持的 Agent 发送
+    let compress_threshold = compression.then_some(state.rpc_compress_threshold);
+
     // 创建消息发送任务
     let mut send_task = tokio::spawn(async move {
         while let Some(msg) = rx.recv().await {
-            if let Err(e) = send_message(&mut ws_sender, msg).await {
+            if let Err(e) = send_message(&mut ws_sender, msg, codec, compress_threshold).await {
                 error!("发送消息失败: {}", e);
                 break;
             }
@@ -124,11 +146,31 @@ async fn handle_agent_connection(
     info!("Agent 连接已关闭: {}", node_id);
 }
 
+/// 注册失败原因
+enum RegistrationError {
+    /// 共享密钥校验失败（携带节点 ID）
+    Unauthorized(String),
+    /// 注册消息缺失或无效
+    Invalid(String),
+}
+
+impl From<String> for RegistrationError {
+    fn from(message: String) -> Self {
+        RegistrationError::Invalid(message)
+    }
+}
+
+impl From<&str> for RegistrationError {
+    fn from(message: &str) -> Self {
+        RegistrationError::Invalid(message.to_string())
+    }
+}
+
 /// 等待并处理注册消息
 async fn wait_for_registration(
     receiver: &mut futures_util::stream::SplitStream<WebSocket>,
     state: &crate::app_state::AppState,
-) -> Result<(String, String, String), String> {
+) -> Result<(String, String, String, RpcCodec, bool), RegistrationError> {
     // 等待第一条消息（应该是注册请求）
     match tokio::time::timeout(std::time::Duration::from_secs(10), receiver.next()).await {
         Ok(Some(Ok(msg))) => {
@@ -137,11 +179,11 @@ async fn wait_for_registration(
 
             // 验证是否是注册请求
             if rpc_msg.message_type != MessageType::Request {
-                return Err("期望收到注册请求".to_string());
+                return Err("期望收到注册请求".into());
             }
 
             if rpc_msg.method.as_deref() != Some("register") {
-                return Err(format!("期望 register 方法，收到: {:?}", rpc_msg.method));
+                return Err(format!("期望 register 方法，收到: {:?}", rpc_msg.method).into());
             }
 
             // 解析注册信息
@@ -149,6 +191,14 @@ async fn wait_for_registration(
             let register_req: RegisterRequest =
                 serde_json::from_value(payload).map_err(|e| format!("解析注册信息失败: {}", e))?;
 
+            // 校验共享密钥，通过前不触碰数据库
+            if !state
+                .agent_auth
+                .verify(&register_req.node_id, register_req.token.as_deref())
+            {
+                return Err(RegistrationError::Unauthorized(register_req.node_id));
+            }
+
             // 检查并创建节点
             let node_service = NodeService::new(state.clone());
 
@@ -182,11 +232,18 @@ async fn wait_for_registration(
                                     "创建节点失败: node_id={}, error={}",
                                     register_req.node_id, e
                                 );
-                                return Err(format!("创建节点失败: {}", e));
+                                return Err(format!("创建节点失败: {}", e).into());
                             }
                         }
                     } else {
                         info!("节点已存在，更新连接: node_id={}", register_req.node_id);
+                        // Agent 崩溃期间的完成通知可能丢失，重连后按 libvirt 实际状态对账
+                        let previous = state.agent_manager().get(&register_req.node_id).await;
+                        NodeService::spawn_reconnect_reconciliation(
+                            state.clone(),
+                            register_req.node_id.clone(),
+                            previous,
+                        );
                     }
                 }
                 Err(e) => {
@@ -194,7 +251,7 @@ async fn wait_for_registration(
                         "检查节点存在性失败: node_id={}, error={}",
                         register_req.node_id, e
                     );
-                    return Err(format!("检查节点失败: {}", e));
+                    return Err(format!("检查节点失败: {}", e).into());
                 }
             }
 
@@ -202,11 +259,13 @@ async fn wait_for_registration(
                 register_req.node_id,
                 register_req.hostname,
                 register_req.ip_address,
+                register_req.codec,
+                register_req.compression,
             ))
         }
-        Ok(Some(Err(e))) => Err(format!("接收注册消息错误: {}", e)),
-        Ok(None) => Err("连接已关闭".to_string()),
-        Err(_) => Err("等待注册消息超时".to_string()),
+        Ok(Some(Err(e))) => Err(format!("接收注册消息错误: {}", e).into()),
+        Ok(None) => Err("连接已关闭".into()),
+        Err(_) => Err("等待注册消息超时".into()),
     }
 }
 
@@ -236,15 +295,16 @@ async fn handle_incoming_message(
             Ok(())
         }
         MessageType::Stream => {
-            // 流式数据
+            // 流式数据 - 转发给该请求的订阅者
             debug!("收到流式消息: {}", rpc_msg.id);
+            connection.handle_stream(rpc_msg).await;
             Ok(())
         }
     }
 }
 
 /// 处理通知消息
-async fn handle_notification(
+pub(crate) async fn handle_notification(
     msg: RpcMessage,
     connection: &super::agent_manager::AgentConnection,
     state: &crate::app_state::AppState,
@@ -253,22 +313,18 @@ async fn handle_notification(
 
     match method {
         "heartbeat" => {
-            // 更新心跳时间
-            connection.update_heartbeat().await;
+            // 不支持 ping 的旧版本 Agent 仍以通知发送心跳
             debug!("收到心跳: node_id={}", connection.node_id);
 
-            // 调用NodeService更新节点最后心跳时间
-            let node_service = NodeService::new(state.clone());
-
-            if let Err(e) = node_service.update_heartbeat(&connection.node_id).await {
-                error!(
-                    "更新节点心跳失败: node_id={}, error={}",
-                    connection.node_id, e
-                );
-            } else {
-                debug!("成功更新节点心跳: node_id={}", connection.node_id);
-            }
+            // 旧版本 Agent 不上报 libvirt 连接状态，视为已连接
+            let hypervisor_connected = msg
+                .payload
+                .as_ref()
+                .and_then(|p| p.get("hypervisor_connected"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true);
 
+            record_heartbeat(connection, state, hypervisor_connected).await;
             Ok(())
         }
         "node_status_update" => {
@@ -297,6 +353,15 @@ async fn handle_notification(
             debug!("收到节点资源信息上报: node_id={}", connection.node_id);
             handle_node_resource_info(msg, connection, &state).await
         }
+        "node_metrics" => {
+            debug!("收到节点主机指标: node_id={}", connection.node_id);
+            handle_node_metrics(msg, connection, state).await
+        }
+        "vm_metrics" => {
+            // 暂不持久化，仅避免被当作未知通知告警
+            debug!("收到虚拟机运行时指标: node_id={}", connection.node_id);
+            Ok(())
+        }
         _ => {
             warn!("未知的通知方法: {}", method);
             Ok(())
@@ -304,8 +369,58 @@ async fn handle_notification(
     }
 }
 
+/// 更新连接与节点的最后心跳时间
+async fn record_heartbeat(
+    connection: &super::agent_manager::AgentConnection,
+    state: &crate::app_state::AppState,
+    hypervisor_connected: bool,
+) {
+    connection.update_heartbeat().await;
+
+    // 调用NodeService更新节点最后心跳时间
+    let node_service = NodeService::new(state.clone());
+
+    if let Err(e) = node_service.update_heartbeat(&connection.node_id, hypervisor_connected).await {
+        error!(
+            "更新节点心跳失败: node_id={}, error={}",
+            connection.node_id, e
+        );
+    } else {
+        debug!("成功更新节点心跳: node_id={}", connection.node_id);
+    }
+}
+
+/// 处理 Agent 的 ping 请求：记录心跳并回显时间戳，供 Agent 计算往返时延
+async fn handle_ping(
+    msg: RpcMessage,
+    connection: &super::agent_manager::AgentConnection,
+    state: &crate::app_state::AppState,
+) -> Result<(), String> {
+    let payload = msg.payload.unwrap_or(serde_json::Value::Null);
+    let ping: PingRequest =
+        serde_json::from_value(payload).map_err(|e| format!("解析 ping 请求失败: {}", e))?;
+
+    // 先回复再更新数据库，避免数据库延迟计入往返时延
+    let pong = PongResponse {
+        timestamp_ms: ping.timestamp_ms,
+        server_time_ms: chrono::Utc::now().timestamp_millis(),
+    };
+    let payload = serde_json::to_value(&pong).map_err(|e| format!("序列化 pong 响应失败: {}", e))?;
+    connection
+        .sender
+        .send(RpcMessage::response(msg.id, payload))
+        .map_err(|_| "发送 pong 响应失败".to_string())?;
+
+    if let Some(rtt_ms) = ping.last_rtt_ms {
+        debug!("节点往返时延: node_id={}, rtt={}ms", connection.node_id, rtt_ms);
+        connection.update_rtt(rtt_ms).await;
+    }
+    record_heartbeat(connection, state, ping.hypervisor_connected).await;
+    Ok(())
+}
+
 /// 处理 Agent 发起的请求
-async fn handle_agent_request(
+pub(crate) async fn handle_agent_request(
     msg: RpcMessage,
     connection: &super::agent_manager::AgentConnection,
     state: &crate::app_state::AppState,
@@ -313,6 +428,7 @@ async fn handle_agent_request(
     let method = msg.method.as_deref().ok_or("请求消息缺少方法名")?;
 
     match method {
+        "ping" => handle_ping(msg, connection, state).await,
         "get_storage_pool_info" => {
             // 处理获取存储池信息请求
             handle_get_storage_pool_info(msg, connection, &state).await
@@ -345,25 +461,31 @@ fn parse_websocket_message(ws_msg: AxumWsMessage) -> Result<RpcMessage, String>
             RpcMessage::from_json(&text).map_err(|e| format!("解析 JSON 失败: {}", e))
         }
         AxumWsMessage::Binary(data) => {
-            let text = String::from_utf8(data).map_err(|e| format!("二进制转字符串失败: {}", e))?;
-            RpcMessage::from_json(&text).map_err(|e| format!("解析 JSON 失败: {}", e))
+            RpcMessage::from_binary(&data).map_err(|e| format!("解析二进制消息失败: {}", e))
         }
         AxumWsMessage::Close(_) => Err("连接关闭".to_string()),
         _ => Err("不支持的消息类型".to_string()),
     }
 }
 
-/// 发送 RPC 消息
+/// 按协商的编码发送 RPC 消息：JSON 使用文本帧，MessagePack 使用二进制帧，
+/// 超过压缩阈值的消息 gzip 压缩后以二进制帧发送
 async fn send_message(
     sender: &mut futures_util::stream::SplitSink<WebSocket, AxumWsMessage>,
     msg: RpcMessage,
+    codec: RpcCodec,
+    compress_threshold: Option<usize>,
 ) -> Result<(), String> {
-    let json = msg
-        .to_json()
-        .map_err(|e| format!("序列化消息失败: {}", e))?;
+    let ws_msg = match msg
+        .encode_frame(codec, compress_threshold)
+        .map_err(|e| format!("序列化消息失败: {}", e))?
+    {
+        EncodedFrame::Text(text) => AxumWsMessage::Text(text),
+        EncodedFrame::Binary(data) => AxumWsMessage::Binary(data),
+    };
 
     sender
-        .send(AxumWsMessage::Text(json))
+        .send(ws_msg)
         .await
         .map_err(|e| format!("发送 WebSocket 消息失败: {}", e))?;
 
@@ -407,11 +529,25 @@ async fn handle_vm_operation_completed(
         vm_id, operation, success, message
     );
 
+    if let Some(conflicts) = payload.get("ip_conflicts").and_then(|v| v.as_array()) {
+        if !conflicts.is_empty() {
+            warn!("虚拟机 {} 启动时检测到 IP 冲突: {:?}", vm_id, conflicts);
+        }
+    }
+
+    // 旧版本 Agent 不上报 VNC 端口
+    let vnc: Option<common::ws_rpc::VncInfo> = payload
+        .get("vnc")
+        .and_then(|v| serde_json::from_value(v.clone()).ok());
+
+    // Server 下发通知时附带的任务 ID，旧版本 Agent 不带回
+    let task_id = payload.get("task_id").and_then(|v| v.as_str());
+
     // 使用虚拟机服务处理操作完成通知
     let vm_service = crate::services::vm_service::VmService::new(state.clone());
 
     if let Err(e) = vm_service
-        .handle_vm_operation_completed(&vm_id, &operation, success, &message)
+        .handle_vm_operation_completed(&vm_id, &operation, success, &message, vnc, task_id)
         .await
     {
         error!("处理虚拟机操作完成通知失败: {}", e);
@@ -457,6 +593,9 @@ async fn handle_snapshot_operation_completed(
         .unwrap_or("")
         .to_string();
 
+    // 快照创建时 Agent 上报的一致性级别（卷挂载在运行中的虚拟机上时才有）
+    let consistency = payload.get("consistency").and_then(|v| v.as_str());
+
     info!(
         "快照操作完成: snapshot_id={}, operation={}, success={}, message={}",
         snapshot_id, operation, success, message
@@ -466,7 +605,7 @@ async fn handle_snapshot_operation_completed(
     let snapshot_service = crate::services::snapshot_service::SnapshotService::new(state.clone());
 
     if let Err(e) = snapshot_service
-        .handle_snapshot_operation_completed(&snapshot_id, &operation, success, &message)
+        .handle_snapshot_operation_completed(&snapshot_id, &operation, success, &message, consistency)
         .await
     {
         error!("处理快照操作完成通知失败: {}", e);
@@ -577,25 +716,33 @@ async fn handle_vm_migration_progress(
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
 
+    // 旧版本 Agent 不上报迁移方式
+    let migration_mode: Option<MigrationMode> = payload
+        .get("migration_mode")
+        .and_then(|v| serde_json::from_value(v.clone()).ok());
+
+    let remaining_secs: Option<u64> = payload.get("remaining_secs").and_then(|v| v.as_u64());
+
     info!(
-        "虚拟机迁移进度: vm_id={}, stage={}, progress={}%, completed={}, message={}",
-        vm_id, stage, progress_percent, completed, message
+        "虚拟机迁移进度: vm_id={}, stage={}, progress={}%, completed={}, mode={:?}, message={}",
+        vm_id, stage, progress_percent, completed, migration_mode, message
     );
 
+    let progress = MigrationProgress {
+        vm_id: vm_id.clone(),
+        stage: stage.clone(),
+        progress_percent,
+        message,
+        completed,
+        error,
+        migration_mode,
+        remaining_secs,
+    };
+
     // 使用虚拟机服务处理迁移进度通知
     let vm_service = crate::services::vm_service::VmService::new(state.clone());
 
-    if let Err(e) = vm_service
-        .handle_vm_migration_progress(
-            &vm_id,
-            &stage,
-            progress_percent,
-            &message,
-            completed,
-            error.as_deref(),
-        )
-        .await
-    {
+    if let Err(e) = vm_service.handle_vm_migration_progress(&progress).await {
         error!("处理虚拟机迁移进度通知失败: {}", e);
         return Err(format!("处理虚拟机迁移进度通知失败: {}", e));
     }
@@ -626,18 +773,7 @@ async fn handle_node_resource_info(
     // 使用节点服务更新节点资源信息
     let node_service = NodeService::new(state.clone());
 
-    match node_service
-        .update_node_resource_info(
-            &resource_info.node_id,
-            resource_info.cpu_cores,
-            resource_info.cpu_threads,
-            resource_info.memory_total,
-            resource_info.disk_total,
-            resource_info.hypervisor_type,
-            resource_info.hypervisor_version,
-        )
-        .await
-    {
+    match node_service.update_node_resource_info(&resource_info).await {
         Ok(_) => {
             info!("成功更新节点资源信息: node_id={}", resource_info.node_id);
         }
@@ -651,3 +787,23 @@ async fn handle_node_resource_info(
 
     Ok(())
 }
+
+/// 处理节点主机指标上报，保存到指标历史
+async fn handle_node_metrics(
+    msg: RpcMessage,
+    connection: &super::agent_manager::AgentConnection,
+    state: &crate::app_state::AppState,
+) -> Result<(), String> {
+    let payload = msg.payload.ok_or("通知消息缺少负载")?;
+
+    let mut sample: NodeMetricsSample =
+        serde_json::from_value(payload).map_err(|e| format!("解析节点主机指标失败: {}", e))?;
+    // 以连接注册的节点为准
+    sample.node_id = connection.node_id.clone();
+
+    if let Err(e) = NodeMetricsService::new(state.clone()).record_sample(&sample).await {
+        error!("保存节点主机指标失败: node_id={}, error={}", sample.node_id, e);
+    }
+
+    Ok(())
+}
@@ -3,10 +3,14 @@
 /// 管理与 Agent 和前端客户端的 WebSocket 连接
 
 pub mod agent_manager;
+pub mod agent_rpc;
 pub mod handler;
 pub mod frontend_handler;
+pub mod vnc_proxy;
 
 pub use agent_manager::AgentConnectionManager;
+pub use agent_rpc::AgentRpc;
 pub use handler::handle_agent_websocket;
 pub use frontend_handler::{FrontendConnectionManager, handle_frontend_websocket, FrontendMessage};
+pub use vnc_proxy::handle_vnc_websocket;
 
@@ -0,0 +1,192 @@
+/// 图形控制台 WebSocket 代理
+///
+/// 浏览器中的 noVNC（RFB 协议）或 spice-html5（SPICE 协议）通过 WebSocket 传输数据，
+/// Server 连接虚拟机所在节点上 libvirt 分配的控制台端口，在两端之间原样转发字节，
+/// 因此两种协议共用同一个代理
+use axum::extract::ws::{CloseFrame, Message as AxumWsMessage, WebSocket};
+use axum::extract::{Path, Query, State, WebSocketUpgrade};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures_util::{SinkExt, StreamExt};
+use sea_orm::EntityTrait;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{debug, info, warn};
+
+use crate::app_state::AppState;
+use crate::auth::AuthService;
+use crate::db::models::vm::{Entity as VmEntity, VmStatus};
+use crate::services::vm_service::VmService;
+
+/// 连接节点 VNC 端口的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 单次从 VNC 端口读取的最大字节数
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// 浏览器无法为 WebSocket 设置请求头，JWT 令牌通过查询参数传递
+#[derive(Debug, Deserialize)]
+pub struct VncProxyQuery {
+    pub token: Option<String>,
+}
+
+/// WebSocket 升级处理器
+///
+/// GET /ws/vnc/:vm_id?token=<JWT>
+///
+/// 仅虚拟机所有者或拥有 vm:console 权限的用户可以连接
+pub async fn handle_vnc_websocket(
+    Path(vm_id): Path<String>,
+    Query(query): Query<VncProxyQuery>,
+    State(state): State<AppState>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let claims = match query.token.as_deref().map(AuthService::verify_token) {
+        Some(Ok(claims)) => claims,
+        _ => {
+            return error_response(StatusCode::UNAUTHORIZED, "认证失败，请提供有效的JWT令牌");
+        }
+    };
+
+    let (target, protocol) = match vnc_target(&state, &vm_id).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+
+    match VmService::new(state.clone()).can_access_console(&vm_id, claims.sub).await {
+        Ok(true) => {}
+        Ok(false) => {
+            warn!("用户 {} 无权访问虚拟机 {} 的控制台", claims.username, vm_id);
+            return error_response(StatusCode::FORBIDDEN, "无权访问该虚拟机控制台");
+        }
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+
+    // 升级前先连上 VNC 端口，连接失败时仍能返回明确的 HTTP 错误
+    let tcp = match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&target)).await {
+        Ok(Ok(tcp)) => tcp,
+        Ok(Err(e)) => {
+            warn!("连接虚拟机 {} 的 VNC 端口 {} 失败: {}", vm_id, target, e);
+            return error_response(StatusCode::BAD_GATEWAY, "无法连接虚拟机 VNC 控制台");
+        }
+        Err(_) => {
+            warn!("连接虚拟机 {} 的 VNC 端口 {} 超时", vm_id, target);
+            return error_response(StatusCode::GATEWAY_TIMEOUT, "连接虚拟机 VNC 控制台超时");
+        }
+    };
+
+    info!(
+        "用户 {} 打开虚拟机 {} 的 {} 控制台: {}",
+        claims.username, vm_id, protocol, target
+    );
+
+    // noVNC 会协商 binary 子协议，客户端未请求时不影响升级
+    ws.protocols(["binary"])
+        .on_upgrade(move |socket| proxy_vnc(socket, tcp, vm_id))
+}
+
+/// 查询虚拟机的控制台地址和协议，不可用时返回对应的错误响应
+async fn vnc_target(state: &AppState, vm_id: &str) -> Result<(String, String), Response> {
+    let vm = VmEntity::find_by_id(vm_id.to_string())
+        .one(&state.sea_db())
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "虚拟机不存在"))?;
+
+    if vm.status != VmStatus::Running.as_str() {
+        return Err(error_response(StatusCode::CONFLICT, "虚拟机未运行"));
+    }
+
+    // 旧版本 Agent 不上报协议，只可能是 VNC
+    let protocol = vm.console_protocol.unwrap_or_else(|| "vnc".to_string());
+    match (vm.vnc_host, vm.vnc_port) {
+        (Some(host), Some(port)) => Ok((format_target(&host, port), protocol)),
+        _ => Err(error_response(StatusCode::CONFLICT, "虚拟机没有可用的 VNC 控制台")),
+    }
+}
+
+/// 拼接 TCP 连接地址，IPv6 地址需要加方括号
+fn format_target(host: &str, port: i32) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// 在浏览器 WebSocket 与 VNC TCP 连接之间转发数据，任一端断开即关闭另一端
+async fn proxy_vnc(socket: WebSocket, tcp: TcpStream, vm_id: String) {
+    let (mut ws_sender, mut ws_receiver) = socket.split();
+    let (mut tcp_reader, mut tcp_writer) = tcp.into_split();
+
+    // 浏览器 -> VNC
+    let mut upstream = tokio::spawn(async move {
+        while let Some(result) = ws_receiver.next().await {
+            let data = match result {
+                Ok(AxumWsMessage::Binary(data)) => data,
+                Ok(AxumWsMessage::Text(text)) => text.into_bytes(),
+                Ok(AxumWsMessage::Close(_)) => break,
+                // Ping/Pong 由 axum 自动应答
+                Ok(_) => continue,
+                Err(e) => {
+                    debug!("接收 VNC WebSocket 消息错误: {}", e);
+                    break;
+                }
+            };
+            if tcp_writer.write_all(&data).await.is_err() {
+                break;
+            }
+        }
+        let _ = tcp_writer.shutdown().await;
+    });
+
+    // VNC -> 浏览器
+    let mut downstream = tokio::spawn(async move {
+        let mut buf = vec![0u8; READ_BUFFER_SIZE];
+        loop {
+            let n = match tcp_reader.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            if ws_sender
+                .send(AxumWsMessage::Binary(buf[..n].to_vec()))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+        let _ = ws_sender
+            .send(AxumWsMessage::Close(Some(CloseFrame {
+                code: axum::extract::ws::close_code::NORMAL,
+                reason: "VNC 连接已关闭".into(),
+            })))
+            .await;
+    });
+
+    tokio::select! {
+        _ = &mut upstream => downstream.abort(),
+        _ = &mut downstream => upstream.abort(),
+    }
+
+    info!("虚拟机 {} 的 VNC 控制台连接已关闭", vm_id);
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_target() {
+        assert_eq!(format_target("10.0.0.11", 5901), "10.0.0.11:5901");
+        assert_eq!(format_target("fd00::11", 5901), "[fd00::11]:5901");
+    }
+}
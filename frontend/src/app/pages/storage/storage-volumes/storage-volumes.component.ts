@@ -1,4 +1,4 @@
-import { Component, OnInit } from '@angular/core';
+import { Component, OnDestroy, OnInit } from '@angular/core';
 import { CommonModule } from '@angular/common';
 import { Router } from '@angular/router';
 import { NzTableModule } from 'ng-zorro-antd/table';
@@ -17,6 +17,8 @@ import { NzPopconfirmModule } from 'ng-zorro-antd/popconfirm';
 import { NzDescriptionsModule } from 'ng-zorro-antd/descriptions';
 import { NzDropDownModule } from 'ng-zorro-antd/dropdown';
 import { FormsModule } from '@angular/forms';
+import { Subject } from 'rxjs';
+import { takeUntil } from 'rxjs/operators';
 import {
   StorageService,
   StorageVolume,
@@ -26,6 +28,7 @@ import {
   UpdateStorageVolumeRequest,
   PaginatedResponse,
 } from '../../../services/storage.service';
+import { WebSocketService } from '../../../services/websocket.service';
 
 @Component({
   selector: 'app-storage-volumes',
@@ -51,7 +54,7 @@ import {
   templateUrl: './storage-volumes.component.html',
   styleUrls: ['./storage-volumes.component.scss'],
 })
-export class StorageVolumesComponent implements OnInit {
+export class StorageVolumesComponent implements OnInit, OnDestroy {
   storageVolumes: StorageVolume[] = [];
   storagePools: StoragePool[] = [];
   nodes: Node[] = [];
@@ -59,8 +62,18 @@ export class StorageVolumesComponent implements OnInit {
   isModalVisible = false;
   isDetailModalVisible = false;
   isCloneModalVisible = false;
+  isImportModalVisible = false;
   isResizeModalVisible = false;
   isEditMode = false;
+  isCreating = false;
+  // 从 URL 创建时 Agent 推送的下载与格式转换进度
+  createProgress: {
+    stage: string;
+    percent: number;
+    downloadedBytes?: number | null;
+    totalBytes?: number | null;
+  } | null = null;
+  private destroy$ = new Subject<void>();
   currentVolume: StorageVolume | null = null;
   selectedVolume: StorageVolume | null = null;
   cloneSourceVolume: StorageVolume | null = null;
@@ -87,13 +100,24 @@ export class StorageVolumesComponent implements OnInit {
     pool_id: null as number | null,
     size_gb: 20,
     volume_type: 'qcow2' as 'qcow2' | 'raw',
-    dataSource: 'blank' as 'blank' | 'url', // 数据源选择
+    dataSource: 'blank' as 'blank' | 'url' | 'backing', // 数据源选择
     source: null as string | null, // 外部URL
+    backing_volume_id: null as number | null, // 链接克隆的基础镜像卷
+    passphrase: '', // LUKS 加密口令，留空表示不加密
+  };
+
+  // 导入镜像表单数据
+  importFormData = {
+    name: '',
+    pool_id: null as number | null,
+    path: '',
+    mode: 'reference' as 'reference' | 'copy',
   };
 
   // 克隆表单数据
-  cloneFormData = {
+  cloneFormData: { targetName: string; targetPoolId: number | null } = {
     targetName: '',
+    targetPoolId: null,
   };
 
   // 扩容表单数据
@@ -105,10 +129,29 @@ export class StorageVolumesComponent implements OnInit {
     private storageService: StorageService,
     private message: NzMessageService,
     private router: Router,
+    private websocketService: WebSocketService,
   ) {}
 
   ngOnInit(): void {
     this.loadStorageVolumes();
+
+    this.websocketService.volumeProgress$
+      .pipe(takeUntil(this.destroy$))
+      .subscribe((progress) => {
+        if (this.isCreating) {
+          this.createProgress = {
+            stage: progress.stage,
+            percent: progress.progress_percent,
+            downloadedBytes: progress.downloaded_bytes,
+            totalBytes: progress.total_bytes,
+          };
+        }
+      });
+  }
+
+  ngOnDestroy(): void {
+    this.destroy$.next();
+    this.destroy$.complete();
   }
 
   loadStorageVolumes(page: number = 1): void {
@@ -223,8 +266,10 @@ export class StorageVolumesComponent implements OnInit {
       pool_id: volume.pool_id,
       size_gb: volume.size_gb,
       volume_type: volume.volume_type || 'qcow2',
-      dataSource: 'blank' as 'blank' | 'url', // 编辑时默认为空白
+      dataSource: 'blank' as 'blank' | 'url' | 'backing', // 编辑时默认为空白
       source: null as string | null, // 编辑时不支持外部URL
+      backing_volume_id: null as number | null,
+      passphrase: '',
     };
     this.isModalVisible = true;
   }
@@ -243,10 +288,29 @@ export class StorageVolumesComponent implements OnInit {
   }
 
   // 数据源变化处理
-  onDataSourceChange(dataSource: 'blank' | 'url'): void {
-    if (dataSource === 'blank') {
+  onDataSourceChange(dataSource: 'blank' | 'url' | 'backing'): void {
+    if (dataSource !== 'url') {
       this.volumeFormData.source = null;
     }
+    if (dataSource === 'backing') {
+      this.volumeFormData.volume_type = 'qcow2';
+    } else {
+      this.volumeFormData.backing_volume_id = null;
+    }
+    if (dataSource !== 'blank') {
+      this.volumeFormData.passphrase = '';
+    }
+  }
+
+  // 可作为基础镜像的存储卷：同一存储池、qcow2、空闲且本身不是链接克隆
+  get backingCandidates(): StorageVolume[] {
+    return this.storageVolumes.filter(
+      (v) =>
+        v.pool_id === this.volumeFormData.pool_id &&
+        v.volume_type === 'qcow2' &&
+        v.status === 'available' &&
+        !v.backing_volume_id,
+    );
   }
 
   createVolume(): void {
@@ -256,16 +320,27 @@ export class StorageVolumesComponent implements OnInit {
       size_gb: this.volumeFormData.size_gb,
       volume_type: this.volumeFormData.volume_type,
       source: this.volumeFormData.dataSource === 'url' ? this.volumeFormData.source : null,
+      backing_volume_id:
+        this.volumeFormData.dataSource === 'backing' ? this.volumeFormData.backing_volume_id?.toString() : null,
+      encryption: this.volumeFormData.passphrase
+        ? { type: 'passphrase', passphrase: this.volumeFormData.passphrase }
+        : null,
     };
 
+    this.isCreating = true;
+    this.createProgress = null;
     this.storageService.createStorageVolume(createData).subscribe({
       next: (response) => {
+        this.isCreating = false;
+        this.createProgress = null;
         this.message.success('存储卷创建成功');
         this.isModalVisible = false;
         this.resetVolumeForm();
         this.loadStorageVolumes(this.pagination.current_page);
       },
       error: (error) => {
+        this.isCreating = false;
+        this.createProgress = null;
         console.error('创建存储卷失败:', error);
         this.message.error('创建存储卷失败');
       },
@@ -318,6 +393,8 @@ export class StorageVolumesComponent implements OnInit {
       volume_type: 'qcow2',
       dataSource: 'blank',
       source: null,
+      backing_volume_id: null,
+      passphrase: '',
     };
   }
 
@@ -328,6 +405,33 @@ export class StorageVolumesComponent implements OnInit {
     return `${sizeGb} GB`;
   }
 
+  // 创建进度文本：源站未返回文件大小时下载阶段只显示已下载的字节数
+  formatCreateProgress(): string {
+    const progress = this.createProgress;
+    if (!progress) {
+      return '';
+    }
+    if (progress.stage === 'download') {
+      const downloaded = this.formatBytes(progress.downloadedBytes ?? 0);
+      if (!progress.totalBytes) {
+        return `下载中：已下载 ${downloaded}`;
+      }
+      return `下载中：${progress.percent.toFixed(0)}%（${downloaded} / ${this.formatBytes(progress.totalBytes)}）`;
+    }
+    return `格式转换：${progress.percent.toFixed(0)}%`;
+  }
+
+  private formatBytes(bytes: number): string {
+    const units = ['B', 'KB', 'MB', 'GB', 'TB'];
+    let value = bytes;
+    let unit = 0;
+    while (value >= 1024 && unit < units.length - 1) {
+      value /= 1024;
+      unit++;
+    }
+    return unit === 0 ? `${value} ${units[unit]}` : `${value.toFixed(1)} ${units[unit]}`;
+  }
+
   // 从metadata中获取source信息
   getSourceFromMetadata(metadata: any): string | null {
     if (!metadata || typeof metadata !== 'object') {
@@ -348,15 +452,67 @@ export class StorageVolumesComponent implements OnInit {
     this.selectedVolume = null;
   }
 
+  // 显示导入镜像模态框
+  showImportVolumeModal(): void {
+    this.importFormData = { name: '', pool_id: null, path: '', mode: 'reference' };
+    if (!this.poolsLoaded) {
+      this.loadStoragePools();
+    }
+    this.isImportModalVisible = true;
+  }
+
+  // 处理导入确认
+  handleImportOk(): void {
+    const { name, pool_id, path, mode } = this.importFormData;
+    if (!name.trim() || !pool_id || !path.trim()) {
+      this.message.error('请填写名称、存储池和镜像路径');
+      return;
+    }
+
+    this.loading = true;
+    this.storageService
+      .importStorageVolume({ name: name.trim(), pool_id, path: path.trim(), mode })
+      .subscribe({
+        next: () => {
+          this.message.success('镜像导入成功');
+          this.isImportModalVisible = false;
+          this.loadStorageVolumes(this.pagination.current_page);
+        },
+        error: (error) => {
+          this.loading = false;
+          console.error('导入镜像失败:', error);
+          this.message.error('导入镜像失败: ' + (error.error?.message || error.message || '未知错误'));
+        },
+      });
+  }
+
+  // 处理导入取消
+  handleImportCancel(): void {
+    this.isImportModalVisible = false;
+  }
+
   // 显示克隆存储卷模态框
   showCloneVolumeModal(volume: StorageVolume): void {
     this.cloneSourceVolume = volume;
     this.cloneFormData = {
       targetName: `${volume.name}-clone`,
+      targetPoolId: volume.pool_id,
     };
+    if (!this.poolsLoaded) {
+      this.loadStoragePools();
+    }
     this.isCloneModalVisible = true;
   }
 
+  // 可作为克隆目标的存储池：与源存储池位于同一节点
+  get cloneTargetPools(): StoragePool[] {
+    const sourcePool = this.storagePools.find((p) => p.id === this.cloneSourceVolume?.pool_id);
+    if (!sourcePool) {
+      return [];
+    }
+    return this.storagePools.filter((p) => p.node_id && p.node_id === sourcePool.node_id);
+  }
+
   // 处理克隆确认
   handleCloneOk(): void {
     if (!this.cloneSourceVolume || !this.cloneFormData.targetName.trim()) {
@@ -366,13 +522,19 @@ export class StorageVolumesComponent implements OnInit {
 
     this.loading = true;
     this.storageService
-      .cloneVolume(this.cloneSourceVolume.id, this.cloneFormData.targetName.trim())
+      .cloneVolume(
+        this.cloneSourceVolume.id,
+        this.cloneFormData.targetName.trim(),
+        this.cloneFormData.targetPoolId !== this.cloneSourceVolume.pool_id
+          ? this.cloneFormData.targetPoolId
+          : null,
+      )
       .subscribe({
         next: (clonedVolume: StorageVolume) => {
           this.message.success('存储卷克隆成功');
           this.isCloneModalVisible = false;
           this.cloneSourceVolume = null;
-          this.cloneFormData = { targetName: '' };
+          this.cloneFormData = { targetName: '', targetPoolId: null };
           this.loadStorageVolumes(this.pagination.current_page);
         },
         error: (error) => {
@@ -389,7 +551,7 @@ export class StorageVolumesComponent implements OnInit {
   handleCloneCancel(): void {
     this.isCloneModalVisible = false;
     this.cloneSourceVolume = null;
-    this.cloneFormData = { targetName: '' };
+    this.cloneFormData = { targetName: '', targetPoolId: null };
   }
 
   // 显示扩容存储卷模态框
@@ -29,6 +29,9 @@ import {
   PaginatedResponse,
   DiskBusType,
   DiskDeviceType,
+  MigrateOptions,
+  MigrationFallbackPolicy,
+  MigrationStorageMode,
 } from '../../services/vm.service';
 import { StorageService } from '../../services/storage.service';
 import { NetworkService } from '../../services/network.service';
@@ -101,11 +104,29 @@ export class VmsComponent implements OnInit {
   // 虚拟机迁移相关
   isMigrateModalVisible = false;
   migrateLoading = false;
-  migrateForm = {
+  migrateForm: {
+    vm_id: string;
+    target_node_id: string;
+    live: boolean;
+    max_bandwidth_mbps: number | null;
+    max_downtime_ms: number | null;
+    fallback_policy: MigrationFallbackPolicy;
+    live_timeout_secs: number | null;
+    storage_mode: MigrationStorageMode;
+  } = {
     vm_id: '',
     target_node_id: '',
     live: false,
+    max_bandwidth_mbps: null,
+    max_downtime_ms: null,
+    fallback_policy: 'none',
+    live_timeout_secs: null,
+    storage_mode: 'shared_storage',
   };
+  // 迁移中虚拟机的进度百分比，迁移结束后移除
+  migrationProgress: Record<string, number> = {};
+  // 迁移中虚拟机生效的带宽上限（MiB/s），首次收到进度时查询
+  migrationSpeed: Record<string, number> = {};
 
   // WebSocket 相关
   private destroy$ = new Subject<void>();
@@ -164,6 +185,22 @@ export class VmsComponent implements OnInit {
       this.handleVmStatusUpdate(update);
     });
 
+    // 监听迁移进度，迁移结束后刷新列表以显示新的所在节点
+    this.websocketService.migrationProgress$
+      .pipe(takeUntil(this.destroy$))
+      .subscribe(progress => {
+        if (progress.completed) {
+          delete this.migrationProgress[progress.vm_id];
+          delete this.migrationSpeed[progress.vm_id];
+          this.loadVms(this.pagination.current_page);
+        } else {
+          if (this.migrationProgress[progress.vm_id] === undefined) {
+            this.loadMigrationSpeed(progress.vm_id);
+          }
+          this.migrationProgress[progress.vm_id] = progress.progress_percent;
+        }
+      });
+
     // 监听系统通知
     this.websocketService.systemNotifications$
       .pipe(takeUntil(this.destroy$))
@@ -173,6 +210,20 @@ export class VmsComponent implements OnInit {
       });
   }
 
+  /**
+   * 查询迁移带宽上限，查询失败时不显示
+   */
+  loadMigrationSpeed(vmId: string): void {
+    this.vmService.getMigrationSpeed(vmId).subscribe({
+      next: (speed) => {
+        if (this.migrationProgress[vmId] !== undefined) {
+          this.migrationSpeed[vmId] = speed.max_mbps;
+        }
+      },
+      error: (error) => console.warn('查询迁移带宽上限失败:', error),
+    });
+  }
+
   /**
    * 处理 VM 状态更新
    */
@@ -736,6 +787,8 @@ export class VmsComponent implements OnInit {
         return 'green';
       case 'scsi':
         return 'blue';
+      case 'sata':
+        return 'purple';
       case 'ide':
         return 'orange';
       default:
@@ -752,6 +805,8 @@ export class VmsComponent implements OnInit {
         return 'VirtIO';
       case 'scsi':
         return 'SCSI';
+      case 'sata':
+        return 'SATA';
       case 'ide':
         return 'IDE';
       default:
@@ -776,6 +831,11 @@ export class VmsComponent implements OnInit {
       vm_id: vm.id,
       target_node_id: '',
       live: vm.status === 'running', // 运行中默认热迁移，关机默认冷迁移
+      max_bandwidth_mbps: null,
+      max_downtime_ms: null,
+      fallback_policy: 'none',
+      live_timeout_secs: null,
+      storage_mode: 'shared_storage',
     };
     this.isMigrateModalVisible = true;
   }
@@ -811,10 +871,29 @@ export class VmsComponent implements OnInit {
       return;
     }
 
+    if (
+      this.migrateForm.live &&
+      this.migrateForm.fallback_policy === 'suspend' &&
+      !this.migrateForm.live_timeout_secs
+    ) {
+      this.message.error('超时挂起需要设置热迁移超时时间');
+      return;
+    }
+
     const migrationType = this.migrateForm.live ? '热迁移' : '冷迁移';
+    const options: MigrateOptions = this.migrateForm.live
+      ? {
+          max_bandwidth_mbps: this.migrateForm.max_bandwidth_mbps,
+          max_downtime_ms: this.migrateForm.max_downtime_ms,
+          fallback_policy: this.migrateForm.fallback_policy,
+          live_timeout_secs:
+            this.migrateForm.fallback_policy === 'suspend' ? this.migrateForm.live_timeout_secs : null,
+          storage_mode: this.migrateForm.storage_mode,
+        }
+      : {};
     this.migrateLoading = true;
     this.vmService
-      .migrateVM(this.selectedVm.id, this.migrateForm.target_node_id, this.migrateForm.live)
+      .migrateVM(this.selectedVm.id, this.migrateForm.target_node_id, this.migrateForm.live, options)
       .subscribe({
         next: () => {
           this.message.success(`虚拟机${migrationType}已开始，请稍候...`);
@@ -840,6 +919,11 @@ export class VmsComponent implements OnInit {
       vm_id: '',
       target_node_id: '',
       live: false,
+      max_bandwidth_mbps: null,
+      max_downtime_ms: null,
+      fallback_policy: 'none',
+      live_timeout_secs: null,
+      storage_mode: 'shared_storage',
     };
   }
 
@@ -10,11 +10,47 @@ export interface Department {
   parent_id: number | null;
   manager_id: number | null;
   sort_order: number;
+  max_vcpu?: number | null;
+  max_memory_mb?: number | null;
+  max_volumes?: number | null;
+  max_volume_gb?: number | null;
   created_at: string;
   updated_at: string;
   children?: Department[];
 }
 
+/** 部门资源配额，字段为空表示不限制 */
+export interface DepartmentQuota {
+  max_vcpu: number | null;
+  max_memory_mb: number | null;
+  max_volumes: number | null;
+  max_volume_gb: number | null;
+}
+
+export interface QuotaUsage {
+  used: number;
+  limit: number | null;
+}
+
+export interface DepartmentUsage {
+  department_id: number;
+  department_name: string;
+  vcpu: QuotaUsage;
+  memory_mb: QuotaUsage;
+  volumes: QuotaUsage;
+  volume_gb: QuotaUsage;
+}
+
+/** 创建虚拟机或存储卷超出部门配额时错误响应中的 details */
+export interface QuotaExceededDetails {
+  department_id: number;
+  department_name: string;
+  resource: 'vcpu' | 'memory_mb' | 'volumes' | 'volume_gb';
+  current: number;
+  requested: number;
+  limit: number;
+}
+
 export interface CreateDepartmentRequest {
   name: string;
   description?: string;
@@ -94,6 +130,22 @@ export class DepartmentService {
     return this.http.put<DepartmentResponse>(url, department);
   }
 
+  /**
+   * 设置部门资源配额
+   */
+  updateDepartmentQuota(id: number, quota: DepartmentQuota): Observable<DepartmentResponse> {
+    const url = this.apiUrl + '/' + id + '/quota';
+    return this.http.put<DepartmentResponse>(url, quota);
+  }
+
+  /**
+   * 获取部门资源用量
+   */
+  getDepartmentUsage(id: number): Observable<{ data: DepartmentUsage }> {
+    const url = this.apiUrl + '/' + id + '/usage';
+    return this.http.get<{ data: DepartmentUsage }>(url);
+  }
+
   /**
    * 删除部门
    */
@@ -14,6 +14,9 @@ export interface Node {
   cpu_threads?: number | null;
   memory_total?: number | null;
   disk_total?: number | null;
+  has_swtpm?: boolean;
+  has_ovs?: boolean;
+  architectures?: string[] | null;
   metadata?: any;
   last_heartbeat?: string | null;
   created_at: string;
@@ -100,10 +103,10 @@ export class NodeService {
   }
 
   /**
-   * 刷新节点状态
+   * 重新检测节点能力（虚拟化版本、swtpm、Open vSwitch、支持的架构）
    */
-  refreshNode(id: string): Observable<any> {
-    return this.http.post(this.apiConfig.buildUrl(`/nodes/${id}/refresh`), {});
+  refreshNode(id: string): Observable<Node> {
+    return this.http.post<Node>(this.apiConfig.buildUrl(`/nodes/${id}/refresh`), {});
   }
 
   /**
@@ -15,6 +15,7 @@ export interface SnapshotResponse {
   size_gb?: number;
   snapshot_tag?: string;
   description?: string;
+  protected: boolean;
   metadata?: any;
   created_at: string;
   updated_at: string;
@@ -41,11 +42,12 @@ export interface CreateSnapshotDto {
 }
 
 /**
- * 更新快照DTO（仅允许更新名称和描述）
+ * 更新快照DTO（仅允许更新名称、描述和保护标记）
  */
 export interface UpdateSnapshotDto {
   name?: string;
   description?: string;
+  protected?: boolean;
 }
 
 /**
@@ -65,6 +67,47 @@ export interface RestoreSnapshotResponse {
   message: string;
 }
 
+/**
+ * 快照策略响应接口
+ */
+export interface SnapshotPolicyResponse {
+  id: string;
+  name: string;
+  target_type: 'volume' | 'vm';
+  target_id: string;
+  schedule: string;
+  keep_last: number;
+  enabled: boolean;
+  next_run_at?: string;
+  last_run_at?: string;
+  last_status?: string;
+  last_error?: string;
+  created_at: string;
+  updated_at: string;
+}
+
+/**
+ * 创建快照策略DTO
+ */
+export interface CreateSnapshotPolicyDto {
+  name: string;
+  target_type: 'volume' | 'vm';
+  target_id: string;
+  schedule: string;
+  keep_last: number;
+  enabled?: boolean;
+}
+
+/**
+ * 更新快照策略DTO（作用对象不可修改）
+ */
+export interface UpdateSnapshotPolicyDto {
+  name?: string;
+  schedule?: string;
+  keep_last?: number;
+  enabled?: boolean;
+}
+
 /**
  * 快照服务
  * 提供存储卷快照相关的API操作
@@ -74,12 +117,14 @@ export interface RestoreSnapshotResponse {
 })
 export class SnapshotService {
   private apiUrl: string;
+  private policyUrl: string;
 
   constructor(
     private http: HttpClient,
     private apiConfig: ApiConfig,
   ) {
     this.apiUrl = this.apiConfig.buildUrl('/storage/snapshots');
+    this.policyUrl = this.apiConfig.buildUrl('/snapshot-policies');
   }
 
   /**
@@ -127,7 +172,7 @@ export class SnapshotService {
   }
 
   /**
-   * 更新快照（仅允许更新名称和描述）
+   * 更新快照（仅允许更新名称、描述和保护标记）
    * @param snapshotId 快照ID
    * @param dto 更新快照DTO
    * @returns Observable<SnapshotResponse>
@@ -172,4 +217,40 @@ export class SnapshotService {
       page_size: pageSize,
     });
   }
+
+  /**
+   * 获取快照策略列表
+   * @returns Observable<SnapshotPolicyResponse[]>
+   */
+  listPolicies(): Observable<SnapshotPolicyResponse[]> {
+    return this.http.get<SnapshotPolicyResponse[]>(this.policyUrl);
+  }
+
+  /**
+   * 创建快照策略
+   * @param dto 创建快照策略DTO
+   * @returns Observable<SnapshotPolicyResponse>
+   */
+  createPolicy(dto: CreateSnapshotPolicyDto): Observable<SnapshotPolicyResponse> {
+    return this.http.post<SnapshotPolicyResponse>(this.policyUrl, dto);
+  }
+
+  /**
+   * 更新快照策略
+   * @param policyId 策略ID
+   * @param dto 更新快照策略DTO
+   * @returns Observable<SnapshotPolicyResponse>
+   */
+  updatePolicy(policyId: string, dto: UpdateSnapshotPolicyDto): Observable<SnapshotPolicyResponse> {
+    return this.http.put<SnapshotPolicyResponse>(`${this.policyUrl}/${policyId}`, dto);
+  }
+
+  /**
+   * 删除快照策略（已创建的快照保留）
+   * @param policyId 策略ID
+   * @returns Observable<void>
+   */
+  deletePolicy(policyId: string): Observable<void> {
+    return this.http.delete<void>(`${this.policyUrl}/${policyId}`);
+  }
 }
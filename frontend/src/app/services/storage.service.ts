@@ -11,8 +11,9 @@ export interface StoragePool {
   type: 'lvm' | 'nfs' | 'ceph' | 'iscsi';
   status: 'active' | 'inactive' | 'error';
   total_size_gb: number;
-  used_size_gb: number;
+  used_size_gb: number;  // 已分配给存储卷的容量
   available_size_gb: number;
+  actual_used_size_gb?: number | null;  // 底层存储的实际占用，由 Server 定期刷新
   node_id?: string;
   node_name?: string;
   config?: any;  // 存储池配置
@@ -34,7 +35,10 @@ export interface StorageVolume {
   node_name?: string;  // 从存储池获取
   vm_id?: number;
   vm_name?: string;
+  backing_volume_id?: string | null;  // 链接克隆的基础镜像卷
+  encrypted?: boolean;  // LUKS 加密卷
   metadata?: any;  // 包含source等元数据信息
+  owner_id?: number | null;  // 创建者，按其所属部门计算配额
   created_at: string;
   updated_at: string;
 }
@@ -76,6 +80,16 @@ export interface CreateStorageVolumeRequest {
   size_gb: number;
   volume_type: 'qcow2' | 'raw';
   source?: string | null;  // 外部URL，用于下载初始数据
+  backing_volume_id?: string | null;  // 基础镜像卷，创建 qcow2 链接克隆
+  encryption?: { type: 'passphrase'; passphrase: string } | { type: 'key_file'; key_id: string } | null;  // LUKS 加密，仅空白 qcow2 卷
+}
+
+// 导入已有镜像请求
+export interface ImportStorageVolumeRequest {
+  name: string;
+  pool_id: number;
+  path: string;  // 存储池目录内的镜像文件绝对路径
+  mode: 'reference' | 'copy';  // reference 只链接原文件，copy 复制出独立文件
 }
 
 // 更新存储卷请求
@@ -124,6 +138,7 @@ export class StorageService {
       total_size_gb: pool.capacity_gb,
       used_size_gb: pool.allocated_gb,
       available_size_gb: pool.available_gb,
+      actual_used_size_gb: pool.used_gb,
       node_id: pool.node_id,
       node_name: pool.node_name,
       config: pool.config,
@@ -229,6 +244,11 @@ export class StorageService {
     return this.http.post<StorageVolume>(this.apiConfig.buildUrl('/storage/volumes'), volumeData);
   }
 
+  // 导入存储池中已存在的镜像
+  importStorageVolume(volumeData: ImportStorageVolumeRequest): Observable<StorageVolume> {
+    return this.http.post<StorageVolume>(this.apiConfig.buildUrl('/storage/volumes/import'), volumeData);
+  }
+
   // 更新存储卷
   updateStorageVolume(id: number, volumeData: UpdateStorageVolumeRequest): Observable<StorageVolume> {
     return this.http.put<StorageVolume>(this.apiConfig.buildUrl(`/storage/volumes/${id}`), volumeData);
@@ -240,10 +260,11 @@ export class StorageService {
   }
 
   // 克隆存储卷
-  cloneVolume(id: number, newName: string): Observable<StorageVolume> {
+  cloneVolume(id: number, newName: string, targetPoolId?: number | null): Observable<StorageVolume> {
     return this.http.post<StorageVolume>(this.apiConfig.buildUrl(`/storage/volumes/${id}/clone`), {
       source_volume_id: id.toString(),
-      target_name: newName
+      target_name: newName,
+      target_pool_id: targetPoolId != null ? targetPoolId.toString() : undefined
     });
   }
 
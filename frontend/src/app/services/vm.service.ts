@@ -7,7 +7,6 @@ import { ApiConfig } from '../config/api.config';
 // 虚拟机数据模型
 export interface VM {
   id: string;
-  uuid: string;
   name: string;
   node_id: string;
   node_name: string;
@@ -16,12 +15,13 @@ export interface VM {
   memory_mb: number;
   os_type: string; // 操作系统类型
   disk_size_gb: number;
+  owner_id?: number | null; // 创建者，按其所属部门计算配额
   created_at: string;
   updated_at: string;
 }
 
 // 磁盘总线类型
-export type DiskBusType = 'virtio' | 'scsi' | 'ide';
+export type DiskBusType = 'virtio' | 'scsi' | 'sata' | 'ide';
 
 // 磁盘设备类型
 export type DiskDeviceType = 'disk' | 'cdrom';
@@ -31,6 +31,7 @@ export interface DiskSpec {
   volume_id: string;
   bus_type?: DiskBusType;      // 总线类型，默认为 virtio
   device_type?: DiskDeviceType; // 设备类型，默认为 disk
+  ephemeral?: boolean;          // 临时盘，删除虚拟机时一并删除
 }
 
 // 网络接口规格
@@ -61,6 +62,84 @@ export interface UpdateVMRequest {
   disk_size_gb?: number;
 }
 
+// 热迁移超时降级策略
+export type MigrationFallbackPolicy = 'none' | 'suspend';
+
+// 热迁移磁盘处理方式：共享存储 / 复制磁盘到目标节点
+export type MigrationStorageMode = 'shared_storage' | 'copy_storage';
+
+// 热迁移选项
+export interface MigrateOptions {
+  max_bandwidth_mbps?: number | null;
+  max_downtime_ms?: number | null;
+  fallback_policy?: MigrationFallbackPolicy;
+  live_timeout_secs?: number | null;
+  storage_mode?: MigrationStorageMode;
+}
+
+// 迁移带宽上限
+export interface MigrationSpeed {
+  vm_id: string;
+  max_mbps: number;
+}
+
+// 批量操作
+export type BatchVMAction = 'start' | 'stop' | 'restart' | 'delete';
+
+export interface BatchVMResult {
+  vm_id: string;
+  success: boolean;
+  task_id?: string;
+  error?: string;
+}
+
+export interface BatchVMResponse {
+  action: BatchVMAction;
+  succeeded: number;
+  failed: number;
+  results: BatchVMResult[];
+}
+
+// 虚拟机模板规格，字段含义与创建虚拟机请求相同
+export interface VMTemplateSpec {
+  vcpu: number;
+  memory_mb: number;
+  os_type?: string;
+  root_disk_size_gb?: number | null; // 系统盘容量，默认与基础镜像相同
+  root_disk_bus?: DiskBusType | null;
+  networks?: NetworkInterfaceSpec[];
+  cloud_init?: any;
+  affinity_group_ids?: string[];
+  metadata?: any;
+}
+
+// 虚拟机模板
+export interface VMTemplate {
+  id: string;
+  name: string;
+  description?: string | null;
+  base_volume_id: string; // 系统盘的基础镜像存储卷
+  spec: VMTemplateSpec;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface CreateVMTemplateRequest {
+  name: string;
+  description?: string;
+  base_volume_id: string;
+  spec: VMTemplateSpec;
+}
+
+// 从模板创建虚拟机，未指定的字段使用模板中的值
+export interface CreateVMFromTemplateRequest {
+  name: string;
+  node_id?: string;
+  vcpu?: number;
+  memory_mb?: number;
+  cloud_init?: any;
+}
+
 // 分页响应
 export interface PaginatedResponse<T> {
   data: T[];
@@ -112,7 +191,6 @@ export class VmService {
   private transformVMsResponse(response: any): PaginatedResponse<VM> {
     const transformedVMs = (response.vms || []).map((vm: any) => ({
       id: vm.id,
-      uuid: vm.uuid || '',
       name: vm.name,
       node_id: vm.node_id || '',
       node_name: vm.node_name || 'Unknown', // 使用后端返回的节点名称
@@ -173,16 +251,65 @@ export class VmService {
     return this.http.post<void>(this.apiConfig.buildUrl(`/vms/${id}/restart`), {});
   }
 
+  // 批量操作虚拟机，逐台返回结果
+  batchOperation(vmIds: string[], action: BatchVMAction, force: boolean = false): Observable<BatchVMResponse> {
+    return this.http.post<BatchVMResponse>(this.apiConfig.buildUrl('/vms/batch'), {
+      vm_ids: vmIds,
+      action,
+      force
+    });
+  }
+
+  // 获取虚拟机模板列表
+  getTemplates(): Observable<VMTemplate[]> {
+    return this.http.get<VMTemplate[]>(this.apiConfig.buildUrl('/vm-templates'));
+  }
+
+  // 创建虚拟机模板
+  createTemplate(template: CreateVMTemplateRequest): Observable<VMTemplate> {
+    return this.http.post<VMTemplate>(this.apiConfig.buildUrl('/vm-templates'), template);
+  }
+
+  // 更新虚拟机模板
+  updateTemplate(id: string, template: Partial<CreateVMTemplateRequest>): Observable<VMTemplate> {
+    return this.http.put<VMTemplate>(this.apiConfig.buildUrl(`/vm-templates/${id}`), template);
+  }
+
+  // 删除虚拟机模板
+  deleteTemplate(id: string): Observable<void> {
+    return this.http.delete<void>(this.apiConfig.buildUrl(`/vm-templates/${id}`));
+  }
+
+  // 从模板创建虚拟机
+  createVMFromTemplate(templateId: string, request: CreateVMFromTemplateRequest): Observable<VM> {
+    return this.http.post<VM>(this.apiConfig.buildUrl(`/vms/from-template/${templateId}`), request);
+  }
+
 
 
   // 迁移虚拟机
-  migrateVM(id: string, targetNodeId: string, live: boolean = false): Observable<void> {
+  migrateVM(
+    id: string,
+    targetNodeId: string,
+    live: boolean = false,
+    options: MigrateOptions = {}
+  ): Observable<void> {
     return this.http.post<void>(this.apiConfig.buildUrl(`/vms/${id}/migrate`), {
       target_node_id: targetNodeId,
       live: live,
+      max_bandwidth_mbps: options.max_bandwidth_mbps ?? undefined,
+      max_downtime_ms: options.max_downtime_ms ?? undefined,
+      fallback_policy: options.fallback_policy ?? undefined,
+      live_timeout_secs: options.live_timeout_secs ?? undefined,
+      storage_mode: options.storage_mode ?? undefined,
     });
   }
 
+  // 查询虚拟机当前的迁移带宽上限（MiB/s）
+  getMigrationSpeed(id: string): Observable<MigrationSpeed> {
+    return this.http.get<MigrationSpeed>(this.apiConfig.buildUrl(`/vms/${id}/migrate/speed`));
+  }
+
   // 获取节点列表
   getNodes(): Observable<Node[]> {
     return this.http.get<any>(this.apiConfig.buildUrl('/nodes')).pipe(
@@ -7,14 +7,30 @@ export interface FrontendMessage {
     | 'NodeStatusUpdate'
     | 'SnapshotStatusUpdate'
     | 'TaskStatusUpdate'
+    | 'MigrationProgress'
+    | 'VolumeProgress'
+    | 'VolumeStatusUpdate'
+    | 'NetworkIpUpdate'
     | 'SystemNotification'
     | 'Pong';
   vm_id?: string;
   node_id?: string;
+  volume_id?: string;
   snapshot_id?: string;
   task_id?: string;
   status?: string;
   progress?: number;
+  stage?: string;
+  progress_percent?: number;
+  downloaded_bytes?: number | null;
+  total_bytes?: number | null;
+  size_gb?: number | null;
+  network_id?: string;
+  ip_address?: string;
+  action?: 'allocated' | 'reserved' | 'released';
+  mac_address?: string | null;
+  completed?: boolean;
+  remaining_secs?: number | null;
   message?: string;
   title?: string;
   level?: string;
@@ -74,6 +90,46 @@ export class WebSocketService {
   }>();
   public taskStatusUpdates$ = this.taskStatusUpdateSubject.asObservable();
 
+  // 虚拟机迁移进度流
+  private migrationProgressSubject = new Subject<{
+    vm_id: string;
+    stage: string;
+    progress_percent: number;
+    message: string;
+    completed: boolean;
+    remaining_secs?: number | null;
+  }>();
+  public migrationProgress$ = this.migrationProgressSubject.asObservable();
+
+  // 从 URL 创建存储卷的进度流
+  private volumeProgressSubject = new Subject<{
+    volume_id: string;
+    stage: string;
+    progress_percent: number;
+    downloaded_bytes?: number | null;
+    total_bytes?: number | null;
+  }>();
+  public volumeProgress$ = this.volumeProgressSubject.asObservable();
+
+  // 存储卷状态更新流
+  private volumeStatusUpdateSubject = new Subject<{
+    volume_id: string;
+    status: string;
+    size_gb?: number | null;
+    message?: string;
+  }>();
+  public volumeStatusUpdates$ = this.volumeStatusUpdateSubject.asObservable();
+
+  // 网络 IP 分配变化流
+  private networkIpUpdateSubject = new Subject<{
+    network_id: string;
+    ip_address: string;
+    action: 'allocated' | 'reserved' | 'released';
+    vm_id?: string | null;
+    mac_address?: string | null;
+  }>();
+  public networkIpUpdates$ = this.networkIpUpdateSubject.asObservable();
+
   // 系统通知流
   private systemNotificationSubject = new Subject<{
     title: string;
@@ -201,6 +257,54 @@ export class WebSocketService {
           }
           break;
 
+        case 'MigrationProgress':
+          if (message.vm_id && message.stage) {
+            this.migrationProgressSubject.next({
+              vm_id: message.vm_id,
+              stage: message.stage,
+              progress_percent: message.progress_percent ?? 0,
+              message: message.message ?? '',
+              completed: message.completed ?? false,
+              remaining_secs: message.remaining_secs,
+            });
+          }
+          break;
+
+        case 'VolumeProgress':
+          if (message.volume_id && message.stage) {
+            this.volumeProgressSubject.next({
+              volume_id: message.volume_id,
+              stage: message.stage,
+              progress_percent: message.progress_percent ?? 0,
+              downloaded_bytes: message.downloaded_bytes,
+              total_bytes: message.total_bytes,
+            });
+          }
+          break;
+
+        case 'VolumeStatusUpdate':
+          if (message.volume_id && message.status) {
+            this.volumeStatusUpdateSubject.next({
+              volume_id: message.volume_id,
+              status: message.status,
+              size_gb: message.size_gb,
+              message: message.message,
+            });
+          }
+          break;
+
+        case 'NetworkIpUpdate':
+          if (message.network_id && message.ip_address && message.action) {
+            this.networkIpUpdateSubject.next({
+              network_id: message.network_id,
+              ip_address: message.ip_address,
+              action: message.action,
+              vm_id: message.vm_id,
+              mac_address: message.mac_address,
+            });
+          }
+          break;
+
         case 'SystemNotification':
           if (message.title && message.message && message.level) {
             this.systemNotificationSubject.next({